@@ -1,4 +1,6 @@
-use flo_controller::{serve_grpc, serve_socket, ControllerState};
+use flo_controller::{
+  serve_grpc, serve_jobs, serve_metrics, serve_rest, serve_socket, ControllerState,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -32,7 +34,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
   }
 
-  tokio::try_join!(serve_grpc(state.clone()), serve_socket(state.clone()))?;
+  tokio::try_join!(
+    serve_grpc(state.clone()),
+    serve_socket(state.clone()),
+    serve_rest(state.clone()),
+    serve_jobs(state.clone()),
+    serve_metrics()
+  )?;
 
   Ok(())
 }
@@ -0,0 +1,26 @@
+//! Endpoints the other observer edges use to check this edge and look up the games it owns.
+
+use axum::extract::{Extension, Path};
+use axum::http::StatusCode;
+use axum::Json;
+use flo_observer_edge::game::snapshot::GameSnapshot;
+use flo_observer_edge::{Error, FloObserverEdgeHandle};
+
+pub async fn health_handler() -> &'static str {
+  "ok"
+}
+
+pub async fn game_handler(
+  Path(game_id): Path<i32>,
+  Extension(handle): Extension<FloObserverEdgeHandle>,
+) -> Result<Json<GameSnapshot>, (StatusCode, String)> {
+  match handle.get_game(game_id).await {
+    Ok(game) => Ok(Json(game)),
+    Err(err @ Error::GameNotFound(_)) => Err((StatusCode::NOT_FOUND, err.to_string())),
+    Err(err @ Error::GameNotReady(_)) => Err((StatusCode::SERVICE_UNAVAILABLE, err.to_string())),
+    Err(err) => {
+      tracing::error!("cluster: {}", err);
+      Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+    }
+  }
+}
@@ -0,0 +1,178 @@
+use super::table::{Kind, Table};
+use flo_observer_edge::game::finished::FinishedGame;
+use flo_observer_edge::game::{PlayerLeaveReason, Race};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const MINUTE_MS: u32 = 60 * 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dataset {
+  Games,
+  PlayerResults,
+  Apm,
+}
+
+impl Dataset {
+  pub fn name(&self) -> &'static str {
+    match *self {
+      Dataset::Games => "games",
+      Dataset::PlayerResults => "player_results",
+      Dataset::Apm => "apm",
+    }
+  }
+
+  pub fn build(&self, games: &[Arc<FinishedGame>]) -> Table {
+    match *self {
+      Dataset::Games => build_games(games),
+      Dataset::PlayerResults => build_player_results(games),
+      Dataset::Apm => build_apm(games),
+    }
+  }
+}
+
+impl std::str::FromStr for Dataset {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "games" => Ok(Dataset::Games),
+      "player_results" => Ok(Dataset::PlayerResults),
+      "apm" => Ok(Dataset::Apm),
+      _ => Err(()),
+    }
+  }
+}
+
+static GAMES_COLUMNS: &[(&str, Kind)] = &[
+  ("game_id", Kind::Int),
+  ("name", Kind::Str),
+  ("map_name", Kind::Str),
+  ("map_path", Kind::Str),
+  ("node", Kind::Str),
+  ("game_version", Kind::Str),
+  ("started_at", Kind::Timestamp),
+  ("ended_at", Kind::Timestamp),
+  ("duration_ms", Kind::Int),
+  ("game_time_ms", Kind::Int),
+  ("players", Kind::Int),
+];
+
+fn build_games(games: &[Arc<FinishedGame>]) -> Table {
+  let mut table = Table::new(GAMES_COLUMNS);
+  for game in games {
+    table.push(vec![
+      game.id.into(),
+      game.name.as_str().into(),
+      game.map_name.as_str().into(),
+      game.map_path.as_str().into(),
+      game.node_name.as_str().into(),
+      game.game_version.clone().into(),
+      game.started_at.into(),
+      game.ended_at.into(),
+      game.duration_ms.into(),
+      game.game_time_ms.into(),
+      game.players.len().into(),
+    ]);
+  }
+  table
+}
+
+static PLAYER_RESULTS_COLUMNS: &[(&str, Kind)] = &[
+  ("game_id", Kind::Int),
+  ("player_id", Kind::Int),
+  ("player_name", Kind::Str),
+  ("slot", Kind::Int),
+  ("team", Kind::Int),
+  ("race", Kind::Str),
+  ("result", Kind::Str),
+  ("left_at_ms", Kind::Int),
+];
+
+fn build_player_results(games: &[Arc<FinishedGame>]) -> Table {
+  let mut table = Table::new(PLAYER_RESULTS_COLUMNS);
+  for game in games {
+    for player in &game.players {
+      table.push(vec![
+        game.id.into(),
+        public_player_id(game, player.player_id).into(),
+        player.name.as_str().into(),
+        player.slot.into(),
+        player.team.into(),
+        race_name(player.race).into(),
+        player.left.map(|(_, reason)| result_name(reason)).into(),
+        player.left.map(|(time, _)| time).into(),
+      ]);
+    }
+  }
+  table
+}
+
+/// `None` for players with hidden profiles
+fn public_player_id(game: &FinishedGame, player_id: i32) -> Option<i32> {
+  let hidden = game
+    .players
+    .iter()
+    .any(|p| p.player_id == player_id && p.hidden);
+  if hidden {
+    None
+  } else {
+    Some(player_id)
+  }
+}
+
+static APM_COLUMNS: &[(&str, Kind)] = &[
+  ("game_id", Kind::Int),
+  ("minute", Kind::Int),
+  ("player_id", Kind::Int),
+  ("actions", Kind::Int),
+  ("apm", Kind::Float),
+];
+
+/// Folds the APM samples of each game into per-minute buckets,
+/// the last partial minute is scaled by its length.
+fn build_apm(games: &[Arc<FinishedGame>]) -> Table {
+  let mut table = Table::new(APM_COLUMNS);
+  for game in games {
+    let mut buckets = BTreeMap::<(u32, i32), u32>::new();
+    for sample in &game.apm {
+      let minute = sample.time.saturating_sub(1) / MINUTE_MS;
+      for item in &sample.data {
+        *buckets.entry((minute, item.player_id)).or_default() += item.total;
+      }
+    }
+    for ((minute, player_id), actions) in buckets {
+      let start = minute * MINUTE_MS;
+      let len = std::cmp::min(MINUTE_MS, game.game_time_ms.saturating_sub(start)).max(1);
+      table.push(vec![
+        game.id.into(),
+        minute.into(),
+        public_player_id(game, player_id).into(),
+        actions.into(),
+        (actions as f64 * MINUTE_MS as f64 / len as f64).into(),
+      ]);
+    }
+  }
+  table
+}
+
+fn race_name(race: Race) -> &'static str {
+  match race {
+    Race::Human => "human",
+    Race::Orc => "orc",
+    Race::NightElf => "night_elf",
+    Race::Undead => "undead",
+    Race::Random => "random",
+  }
+}
+
+fn result_name(reason: PlayerLeaveReason) -> &'static str {
+  match reason {
+    PlayerLeaveReason::LeaveWon => "win",
+    PlayerLeaveReason::LeaveLost | PlayerLeaveReason::LeaveLostBuildings => "loss",
+    PlayerLeaveReason::LeaveDraw => "draw",
+    PlayerLeaveReason::LeaveDisconnect => "disconnect",
+    PlayerLeaveReason::LeaveObserver => "observer",
+    PlayerLeaveReason::LeaveUnknown => "unknown",
+  }
+}
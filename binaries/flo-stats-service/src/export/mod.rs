@@ -0,0 +1,131 @@
+//! Bulk export of finished games for offline analysis.
+//!
+//! `GET /export/{games,player_results,apm}.{csv,parquet}?cursor=<game_id>&limit=<n>`
+//!
+//! Each response holds one chunk of games ordered by id. If there are more games,
+//! the id to pass as `cursor` for the next chunk is returned in the `x-flo-next-cursor` header.
+
+mod dataset;
+mod table;
+
+use axum::extract::{Extension, Path, Query};
+use axum::http::header::{HeaderName, CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use dataset::Dataset;
+use flo_observer_edge::FloObserverEdgeHandle;
+use serde::Deserialize;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+const DEFAULT_LIMIT: usize = 500;
+const MAX_LIMIT: usize = 5000;
+
+/// Export settings loaded at startup
+pub struct ExportConfig {
+  /// Exports are disabled if not set, callers must send a matching `x-flo-secret` header
+  pub api_secret: Option<String>,
+}
+
+impl ExportConfig {
+  pub fn from_env() -> Self {
+    Self {
+      api_secret: std::env::var("FLO_STATS_EXPORT_SECRET").ok(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+  Csv,
+  Parquet,
+}
+
+impl Format {
+  fn content_type(&self) -> &'static str {
+    match *self {
+      Format::Csv => "text/csv; charset=utf-8",
+      Format::Parquet => "application/vnd.apache.parquet",
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+  cursor: Option<i32>,
+  limit: Option<usize>,
+}
+
+type ExportError = (StatusCode, String);
+
+pub async fn export_handler(
+  Path(file): Path<String>,
+  Query(query): Query<ExportQuery>,
+  headers: HeaderMap,
+  Extension(config): Extension<Arc<ExportConfig>>,
+  Extension(handle): Extension<FloObserverEdgeHandle>,
+) -> Result<(HeaderMap, Vec<u8>), ExportError> {
+  let secret = config
+    .api_secret
+    .as_ref()
+    .ok_or_else(|| (StatusCode::FORBIDDEN, "export disabled".to_string()))?;
+  let caller = headers
+    .get("x-flo-secret")
+    .map(|v| v.as_bytes())
+    .unwrap_or_default();
+  if !bool::from(secret.as_bytes().ct_eq(caller)) {
+    return Err((StatusCode::UNAUTHORIZED, "unauthorized".to_string()));
+  }
+
+  let (dataset, format) = parse_file_name(&file)
+    .ok_or_else(|| (StatusCode::NOT_FOUND, format!("unknown dataset: {}", file)))?;
+  let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+  let page = handle
+    .list_finished_games(query.cursor, limit)
+    .await
+    .map_err(internal_error)?;
+  let table = dataset.build(&page.games);
+  let body = match format {
+    Format::Csv => table.to_csv(),
+    Format::Parquet => table.to_parquet(),
+  }
+  .map_err(internal_error)?;
+
+  tracing::debug!(
+    dataset = dataset.name(),
+    games = page.games.len(),
+    rows = table.num_rows(),
+    "export"
+  );
+
+  let mut res_headers = HeaderMap::new();
+  res_headers.insert(
+    CONTENT_TYPE,
+    HeaderValue::from_static(format.content_type()),
+  );
+  if let Ok(value) = HeaderValue::from_str(&format!("attachment; filename=\"{}\"", file)) {
+    res_headers.insert(CONTENT_DISPOSITION, value);
+  }
+  if let Some(cursor) = page.next_cursor {
+    res_headers.insert(
+      HeaderName::from_static("x-flo-next-cursor"),
+      HeaderValue::from(cursor),
+    );
+  }
+  Ok((res_headers, body))
+}
+
+fn parse_file_name(file: &str) -> Option<(Dataset, Format)> {
+  let (name, ext) = file.rsplit_once('.')?;
+  let format = match ext {
+    "csv" => Format::Csv,
+    "parquet" => Format::Parquet,
+    _ => return None,
+  };
+  Some((name.parse().ok()?, format))
+}
+
+fn internal_error<E: std::fmt::Display>(err: E) -> ExportError {
+  tracing::error!("export: {}", err);
+  (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
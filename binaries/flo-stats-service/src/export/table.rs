@@ -0,0 +1,205 @@
+use arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray, TimestampMillisecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, SecondsFormat, Utc};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum EncodeError {
+  #[error("csv: {0}")]
+  Csv(#[from] csv::Error),
+  #[error("arrow: {0}")]
+  Arrow(#[from] arrow::error::ArrowError),
+  #[error("parquet: {0}")]
+  Parquet(#[from] parquet::errors::ParquetError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+  Int,
+  Float,
+  Str,
+  Timestamp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Null,
+  Int(i64),
+  Float(f64),
+  Str(String),
+  Timestamp(DateTime<Utc>),
+}
+
+macro_rules! impl_from_int {
+  ($($ty:ty),*) => {
+    $(
+      impl From<$ty> for Value {
+        fn from(v: $ty) -> Self {
+          Value::Int(v as i64)
+        }
+      }
+    )*
+  };
+}
+
+impl_from_int!(i32, u32, u64, usize);
+
+impl From<f32> for Value {
+  fn from(v: f32) -> Self {
+    Value::Float(v as f64)
+  }
+}
+
+impl From<f64> for Value {
+  fn from(v: f64) -> Self {
+    Value::Float(v)
+  }
+}
+
+impl From<String> for Value {
+  fn from(v: String) -> Self {
+    Value::Str(v)
+  }
+}
+
+impl From<&str> for Value {
+  fn from(v: &str) -> Self {
+    Value::Str(v.to_string())
+  }
+}
+
+impl From<DateTime<Utc>> for Value {
+  fn from(v: DateTime<Utc>) -> Self {
+    Value::Timestamp(v)
+  }
+}
+
+impl<T> From<Option<T>> for Value
+where
+  T: Into<Value>,
+{
+  fn from(v: Option<T>) -> Self {
+    v.map(Into::into).unwrap_or(Value::Null)
+  }
+}
+
+/// Rows of a dataset, values must match the declared column kinds
+#[derive(Debug)]
+pub struct Table {
+  columns: &'static [(&'static str, Kind)],
+  rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+  pub fn new(columns: &'static [(&'static str, Kind)]) -> Self {
+    Self {
+      columns,
+      rows: vec![],
+    }
+  }
+
+  pub fn push(&mut self, row: Vec<Value>) {
+    debug_assert_eq!(row.len(), self.columns.len());
+    self.rows.push(row);
+  }
+
+  pub fn num_rows(&self) -> usize {
+    self.rows.len()
+  }
+
+  /// Timestamps are written as RFC 3339, nulls as empty fields
+  pub fn to_csv(&self) -> Result<Vec<u8>, EncodeError> {
+    let mut w = csv::Writer::from_writer(vec![]);
+    w.write_record(self.columns.iter().map(|(name, _)| *name))?;
+    for row in &self.rows {
+      w.write_record(row.iter().map(|v| match *v {
+        Value::Null => String::new(),
+        Value::Int(v) => v.to_string(),
+        Value::Float(v) => v.to_string(),
+        Value::Str(ref v) => v.clone(),
+        Value::Timestamp(v) => v.to_rfc3339_opts(SecondsFormat::Millis, true),
+      }))?;
+    }
+    w.into_inner()
+      .map_err(|err| EncodeError::Csv(err.into_error().into()))
+  }
+
+  pub fn to_parquet(&self) -> Result<Vec<u8>, EncodeError> {
+    let schema = Arc::new(Schema::new(
+      self
+        .columns
+        .iter()
+        .map(|(name, kind)| Field::new(*name, kind.data_type(), true))
+        .collect::<Vec<_>>(),
+    ));
+    let arrays = self
+      .columns
+      .iter()
+      .enumerate()
+      .map(|(idx, (_, kind))| self.column_array(idx, *kind))
+      .collect::<Vec<_>>();
+    let batch = RecordBatch::try_new(schema.clone(), arrays)?;
+
+    let props = WriterProperties::builder()
+      .set_compression(Compression::SNAPPY)
+      .build();
+    let mut w = ArrowWriter::try_new(vec![], schema, Some(props))?;
+    w.write(&batch)?;
+    w.into_inner().map_err(Into::into)
+  }
+
+  fn column_array(&self, idx: usize, kind: Kind) -> ArrayRef {
+    let values = self.rows.iter().map(|row| &row[idx]);
+    match kind {
+      Kind::Int => Arc::new(
+        values
+          .map(|v| match *v {
+            Value::Int(v) => Some(v),
+            _ => None,
+          })
+          .collect::<Int64Array>(),
+      ),
+      Kind::Float => Arc::new(
+        values
+          .map(|v| match *v {
+            Value::Float(v) => Some(v),
+            _ => None,
+          })
+          .collect::<Float64Array>(),
+      ),
+      Kind::Str => Arc::new(
+        values
+          .map(|v| match *v {
+            Value::Str(ref v) => Some(v.as_str()),
+            _ => None,
+          })
+          .collect::<StringArray>(),
+      ),
+      Kind::Timestamp => Arc::new(
+        values
+          .map(|v| match *v {
+            Value::Timestamp(v) => Some(v.timestamp_millis()),
+            _ => None,
+          })
+          .collect::<TimestampMillisecondArray>()
+          .with_timezone("UTC"),
+      ),
+    }
+  }
+}
+
+impl Kind {
+  fn data_type(&self) -> DataType {
+    match *self {
+      Kind::Int => DataType::Int64,
+      Kind::Float => DataType::Float64,
+      Kind::Str => DataType::Utf8,
+      Kind::Timestamp => DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+    }
+  }
+}
@@ -0,0 +1,203 @@
+//! In-process response cache for expensive aggregate queries.
+//!
+//! A query response is cached if every root field it resolves has a TTL in `FIELD_TTLS`,
+//! keyed by the query text, variables and operation name, and expires with the shortest
+//! TTL among its fields. Queries that touch any other field (live games, spectate info,
+//! mutations) always run.
+
+use async_graphql::extensions::{
+  Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextResolve,
+  ResolveInfo,
+};
+use async_graphql::parser::types::ExecutableDocument;
+use async_graphql::{Response, ServerResult, Value, Variables};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Root query fields that can be cached, and for how long
+const FIELD_TTLS: &[(&str, Duration)] = &[
+  // recomputed from the archived games of each day
+  ("gameVersions", Duration::from_secs(5 * 60)),
+];
+
+#[derive(Clone)]
+pub struct FieldCache {
+  entries: Arc<Mutex<Lru>>,
+}
+
+impl FieldCache {
+  pub fn new(cap: usize) -> Self {
+    Self {
+      entries: Arc::new(Mutex::new(Lru::new(cap))),
+    }
+  }
+
+  pub fn from_env() -> Self {
+    Self::new(
+      std::env::var("FLO_STATS_GRAPHQL_CACHE_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(256),
+    )
+  }
+}
+
+impl ExtensionFactory for FieldCache {
+  fn create(&self) -> Arc<dyn Extension> {
+    Arc::new(FieldCacheExtension {
+      entries: self.entries.clone(),
+      request: Mutex::new(RequestState::default()),
+    })
+  }
+}
+
+#[derive(Default)]
+struct RequestState {
+  query_key: Option<String>,
+  /// Shortest TTL of the resolved root fields
+  ttl: Option<Duration>,
+  uncacheable: bool,
+}
+
+struct FieldCacheExtension {
+  entries: Arc<Mutex<Lru>>,
+  request: Mutex<RequestState>,
+}
+
+#[async_trait::async_trait]
+impl Extension for FieldCacheExtension {
+  async fn parse_query(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    query: &str,
+    variables: &Variables,
+    next: NextParseQuery<'_>,
+  ) -> ServerResult<ExecutableDocument> {
+    let mut key = query.to_string();
+    for (name, value) in variables.iter() {
+      key.push_str(&format!("\n${}: {}", name, value));
+    }
+    self.request.lock().unwrap().query_key = Some(key);
+    next.run(ctx, query, variables).await
+  }
+
+  async fn execute(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    operation_name: Option<&str>,
+    next: NextExecute<'_>,
+  ) -> Response {
+    let key = match self.request.lock().unwrap().query_key.take() {
+      Some(query_key) => format!("{}\n#{}", query_key, operation_name.unwrap_or_default()),
+      None => return next.run(ctx, operation_name).await,
+    };
+
+    if let Some(data) = self.entries.lock().unwrap().get(&key) {
+      return Response::new(data);
+    }
+
+    let res = next.run(ctx, operation_name).await;
+    let ttl = {
+      let state = self.request.lock().unwrap();
+      if state.uncacheable {
+        None
+      } else {
+        state.ttl
+      }
+    };
+    if let Some(ttl) = ttl {
+      if res.is_ok() {
+        self
+          .entries
+          .lock()
+          .unwrap()
+          .insert(key, res.data.clone(), ttl);
+      }
+    }
+    res
+  }
+
+  async fn resolve(
+    &self,
+    ctx: &ExtensionContext<'_>,
+    info: ResolveInfo<'_>,
+    next: NextResolve<'_>,
+  ) -> ServerResult<Option<Value>> {
+    if info.path_node.parent.is_none() {
+      let mut state = self.request.lock().unwrap();
+      match field_ttl(info.name) {
+        Some(ttl) => state.ttl = Some(state.ttl.map_or(ttl, |v| v.min(ttl))),
+        None => state.uncacheable = true,
+      }
+    }
+    next.run(ctx, info).await
+  }
+}
+
+fn field_ttl(name: &str) -> Option<Duration> {
+  FIELD_TTLS
+    .iter()
+    .find(|(field, _)| *field == name)
+    .map(|(_, ttl)| *ttl)
+}
+
+struct Entry {
+  data: Value,
+  expires_at: Instant,
+  last_used: u64,
+}
+
+/// Least recently used entries are evicted once `cap` is exceeded
+struct Lru {
+  map: HashMap<String, Entry>,
+  cap: usize,
+  clock: u64,
+}
+
+impl Lru {
+  fn new(cap: usize) -> Self {
+    Self {
+      map: HashMap::new(),
+      cap: std::cmp::max(cap, 1),
+      clock: 0,
+    }
+  }
+
+  fn get(&mut self, key: &str) -> Option<Value> {
+    self.clock += 1;
+    let now = Instant::now();
+    match self.map.get_mut(key) {
+      Some(entry) if entry.expires_at > now => {
+        entry.last_used = self.clock;
+        Some(entry.data.clone())
+      }
+      Some(_) => {
+        self.map.remove(key);
+        None
+      }
+      None => None,
+    }
+  }
+
+  fn insert(&mut self, key: String, data: Value, ttl: Duration) {
+    self.clock += 1;
+    self.map.insert(
+      key,
+      Entry {
+        data,
+        expires_at: Instant::now() + ttl,
+        last_used: self.clock,
+      },
+    );
+    while self.map.len() > self.cap {
+      let key = self
+        .map
+        .iter()
+        .min_by_key(|(_, entry)| entry.last_used)
+        .map(|(key, _)| key.clone())
+        .expect("map is not empty");
+      self.map.remove(&key);
+    }
+  }
+}
@@ -1,14 +1,21 @@
 use async_graphql::{Context, Object, Result, Schema, SimpleObject, Subscription, Union};
 use flo_observer_edge::{
+  cluster::GameOwner,
   game::snapshot::GameSnapshot,
   game::{
-    event::{GameListUpdateEvent, GameUpdateEvent},
+    event::{GameListUpdateEvent, GameUpdateEvent, GameUpdateEventKind, GameUpdateEventMask},
     snapshot::GameSnapshotWithStats,
+    timeline::TimelineEvent,
+    versions::VersionDistribution,
   },
   FloObserverEdgeHandle,
 };
+use subtle::ConstantTimeEq;
 use tokio_stream::{once, Stream, StreamExt};
 
+mod cache;
+pub use cache::FieldCache;
+
 pub type FloLiveSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
 pub struct QueryRoot;
 
@@ -18,6 +25,95 @@ impl QueryRoot {
     let handle: &FloObserverEdgeHandle = ctx.data()?;
     handle.list_games().await.map_err(Into::into)
   }
+
+  /// Significant events of a game stamped with in-game time, for VOD syncing
+  async fn timeline(&self, ctx: &Context<'_>, game_id: i32) -> Result<Vec<TimelineEvent>> {
+    let handle: &FloObserverEdgeHandle = ctx.data()?;
+    handle.get_game_timeline(game_id).await.map_err(Into::into)
+  }
+
+  /// Game versions of the games finished on each of the last `days` days, most recent first
+  async fn game_versions(
+    &self,
+    ctx: &Context<'_>,
+    #[graphql(default = 30)] days: usize,
+  ) -> Result<Vec<VersionDistribution>> {
+    let handle: &FloObserverEdgeHandle = ctx.data()?;
+    handle
+      .get_version_distribution(days)
+      .await
+      .map_err(Into::into)
+  }
+
+  /// Everything the flo client needs to start spectating a game
+  async fn spectate_info(&self, ctx: &Context<'_>, game_id: i32) -> Result<SpectateInfo> {
+    let config: &SpectateConfig = ctx.data()?;
+    let caller = ctx.data_opt::<CallerSecret>().and_then(|v| v.0.as_ref());
+    if !config.is_authorized(caller) {
+      return Err("unauthorized".into());
+    }
+
+    let handle: &FloObserverEdgeHandle = ctx.data()?;
+    let (game, owner) = handle.get_routed_game(game_id).await?;
+    let delay_secs = Some(observer_delay_secs(&game));
+    // the game is streamed by the edge that owns it
+    let stream_host = match owner {
+      GameOwner::Remote(peer) => peer.stream_host,
+      GameOwner::Local => None,
+    }
+    .unwrap_or_else(|| config.stream_host.clone());
+    Ok(SpectateInfo {
+      game,
+      token: flo_observer::token::create_observer_token(game_id, delay_secs)?,
+      delay_secs,
+      stream_host,
+      stream_port: flo_constants::OBSERVER_SOCKET_PORT as i32,
+    })
+  }
+}
+
+/// Spectate settings loaded at startup
+pub struct SpectateConfig {
+  /// Spectate info is disabled if not set, callers must send a matching `x-flo-secret` header
+  pub api_secret: Option<String>,
+  pub stream_host: String,
+}
+
+impl SpectateConfig {
+  pub fn from_env() -> Self {
+    Self {
+      api_secret: std::env::var("FLO_STATS_API_SECRET").ok(),
+      stream_host: std::env::var("FLO_STATS_STREAM_HOST")
+        .unwrap_or_else(|_| flo_constants::STATS_HOST.to_string()),
+    }
+  }
+
+  fn is_authorized(&self, caller: Option<&String>) -> bool {
+    match (self.api_secret.as_ref(), caller) {
+      (Some(secret), Some(caller)) => secret.as_bytes().ct_eq(caller.as_bytes()).into(),
+      _ => false,
+    }
+  }
+}
+
+/// Value of the `x-flo-secret` header of the current request
+pub struct CallerSecret(pub Option<String>);
+
+#[derive(SimpleObject)]
+pub struct SpectateInfo {
+  pub game: GameSnapshot,
+  pub token: String,
+  pub delay_secs: Option<i64>,
+  pub stream_host: String,
+  pub stream_port: i32,
+}
+
+fn observer_delay_secs(game: &GameSnapshot) -> i64 {
+  if game.mask_player_names {
+    15 * 60
+  } else {
+    3 * 60
+  }
 }
 
 pub struct MutationRoot;
@@ -30,11 +126,11 @@ impl MutationRoot {
     game_id: i32,
   ) -> Result<ObserverTokenPayload> {
     let handle: &FloObserverEdgeHandle = ctx.data()?;
-    let game = handle.get_game(game_id).await?;
-    let delay_secs = Some(if game.mask_player_names { 15 * 60 } else { 3 * 60 });
+    let (game, _) = handle.get_routed_game(game_id).await?;
+    let delay_secs = Some(observer_delay_secs(&game));
     Ok(ObserverTokenPayload {
       game,
-      delay_secs: delay_secs.clone(),
+      delay_secs,
       token: flo_observer::token::create_observer_token(game_id, delay_secs)?,
     })
   }
@@ -68,13 +164,27 @@ impl SubscriptionRoot {
     )
   }
 
+  /// `kinds` limits the pushed events, e.g. `[PING_STATS, ACTION_STATS]` for stats only.
+  /// All events are pushed if omitted.
+  /// With `delta`, stats events between periodic full keyframes are pushed as
+  /// `PingStatsDelta`/`ActionStatsDelta` with only the changed players and fields.
+  /// `schema_version` pins the shape of the snapshot and events, events that don't exist
+  /// in the requested version are skipped. Defaults to the current version, `delta` requires 2.
   async fn game_update_events(
     &self,
     ctx: &Context<'_>,
     id: i32,
+    kinds: Option<Vec<GameUpdateEventKind>>,
+    delta: Option<bool>,
+    schema_version: Option<u32>,
   ) -> Result<impl Stream<Item = GameUpdateEventItem>> {
     let handle: &FloObserverEdgeHandle = ctx.data()?;
-    let (snapshot, rx) = handle.subscribe_game_updates(id).await?;
+    let mask = kinds
+      .map(GameUpdateEventMask::from_kinds)
+      .unwrap_or_default();
+    let (snapshot, rx) = handle
+      .subscribe_game_updates(id, mask, delta.unwrap_or_default(), schema_version)
+      .await?;
     let events = rx.into_stream().map(GameUpdateEventItem::Event);
     Ok(once(GameUpdateEventItem::Initial(snapshot)).chain(events))
   }
@@ -1,22 +1,39 @@
+mod cluster;
+mod export;
 mod graphql;
+mod widgets;
 
-use crate::graphql::{FloLiveSchema, MutationRoot, QueryRoot, SubscriptionRoot};
+use crate::export::ExportConfig;
+use crate::graphql::{
+  CallerSecret, FieldCache, FloLiveSchema, MutationRoot, QueryRoot, SpectateConfig,
+  SubscriptionRoot,
+};
+use crate::widgets::{TimelineCache, WidgetConfig};
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
 use async_graphql::Schema;
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 use axum::headers::HeaderValue;
-use axum::http::Method;
+use axum::http::{HeaderMap, Method};
 use axum::response::{self, IntoResponse};
 use axum::routing::get;
 use axum::{extract, AddExtensionLayer, Router, Server};
 use flo_observer_edge::FloObserverEdge;
+use std::sync::Arc;
 use tower_http::cors::{CorsLayer, Origin};
 
 async fn graphql_handler(
   schema: extract::Extension<FloLiveSchema>,
+  headers: HeaderMap,
   req: GraphQLRequest,
 ) -> GraphQLResponse {
-  schema.execute(req.into_inner()).await.into()
+  let secret = headers
+    .get("x-flo-secret")
+    .and_then(|v| v.to_str().ok())
+    .map(ToString::to_string);
+  schema
+    .execute(req.into_inner().data(CallerSecret(secret)))
+    .await
+    .into()
 }
 
 async fn graphql_playground() -> impl IntoResponse {
@@ -39,9 +56,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
   }
 
   let edge = FloObserverEdge::from_env().await?;
+  let handle = edge.handle();
 
   let schema = Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
-    .data(edge.handle())
+    .data(handle.clone())
+    .data(SpectateConfig::from_env())
+    .extension(FieldCache::from_env())
     .finish();
 
   tokio::spawn(async move {
@@ -53,7 +73,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
   let app = Router::new()
     .route("/", get(graphql_playground).post(graphql_handler))
     .route("/ws", GraphQLSubscription::new(schema.clone()))
+    .route("/export/:file", get(export::export_handler))
+    .route("/widgets/game/:id", get(widgets::game_widget_handler))
+    .route(
+      "/widgets/game/:id/timeline.png",
+      get(widgets::game_timeline_handler),
+    )
+    .route("/widgets/player/:id", get(widgets::player_widget_handler))
+    .route("/cluster/health", get(cluster::health_handler))
+    .route("/cluster/games/:id", get(cluster::game_handler))
     .layer(AddExtensionLayer::new(schema))
+    .layer(AddExtensionLayer::new(handle))
+    .layer(AddExtensionLayer::new(Arc::new(ExportConfig::from_env())))
+    .layer(AddExtensionLayer::new(Arc::new(WidgetConfig::from_env())))
+    .layer(AddExtensionLayer::new(Arc::new(TimelineCache::from_env())))
     .layer({
       let allowed_list: [HeaderValue; 4] = [
         "http://localhost:3000".parse().unwrap(),
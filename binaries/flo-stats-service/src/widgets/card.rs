@@ -0,0 +1,339 @@
+use flo_observer_edge::game::finished::{FinishedGame, PlayerGames};
+use flo_observer_edge::game::snapshot::GameSnapshotWithStats;
+use flo_observer_edge::game::{PlayerLeaveReason, Race};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+pub const WIDTH: u32 = 400;
+const HEADER_HEIGHT: u32 = 64;
+const SECTION_HEIGHT: u32 = 28;
+const ROW_HEIGHT: u32 = 22;
+
+const STYLE: &str = "body{margin:0;font:13px/1.4 sans-serif;background:#1b1d23;color:#e6e6e6}\
+.card{padding:10px 14px}.title{font-size:16px;font-weight:bold}.summary{color:#9aa0aa}\
+.live{color:#ff5c5c;font-weight:bold;margin-right:6px}.heading{margin-top:8px;color:#9aa0aa}\
+table{width:100%;border-collapse:collapse}td{padding:1px 0}td.detail{text-align:right;color:#c5c8ce}";
+
+/// Content of a widget, rendered as an HTML page or as oEmbed metadata
+pub struct Card {
+  pub title: String,
+  /// One line description, also used by link unfurlers
+  pub summary: String,
+  /// The card changes while the game is running
+  pub live: bool,
+  /// Path of the `og:image`
+  pub image_path: Option<String>,
+  pub sections: Vec<Section>,
+}
+
+pub struct Section {
+  pub heading: String,
+  pub rows: Vec<Row>,
+}
+
+pub struct Row {
+  pub label: String,
+  pub detail: String,
+}
+
+impl Card {
+  pub fn live_game(snapshot: &GameSnapshotWithStats) -> Self {
+    let game = &snapshot.game;
+    let apm: HashMap<i32, f32> = snapshot
+      .stats
+      .action
+      .last()
+      .map(|stats| stats.data.iter().map(|v| (v.player_id, v.apm)).collect())
+      .unwrap_or_default();
+
+    let mut teams = BTreeMap::new();
+    for player in &game.players {
+      let mut detail = vec![race_name(player.race).to_string()];
+      match player.leave_reason {
+        Some(reason) => detail.push(result_name(reason).to_string()),
+        None => {
+          if let Some(apm) = apm.get(&player.id) {
+            detail.push(format!("{:.0} APM", apm))
+          }
+        }
+      }
+      teams.entry(player.team).or_insert_with(Vec::new).push(Row {
+        label: player.name.clone(),
+        detail: detail.join(" · "),
+      });
+    }
+
+    let live = game.ended_at.is_none();
+    Self {
+      title: game.game_name.clone(),
+      summary: format!(
+        "{} · {} {}",
+        game.map_name,
+        if live { "playing" } else { "ended" },
+        format_game_time(game.game_time_ms)
+      ),
+      live,
+      image_path: None,
+      sections: team_sections(teams),
+    }
+  }
+
+  pub fn finished_game(game: &FinishedGame) -> Self {
+    let mut teams = BTreeMap::new();
+    for player in &game.players {
+      let mut detail = vec![race_name(player.race)];
+      if let Some((_, reason)) = player.left {
+        detail.push(result_name(reason));
+      }
+      teams.entry(player.team).or_insert_with(Vec::new).push(Row {
+        label: player.name.clone(),
+        detail: detail.join(" · "),
+      });
+    }
+
+    Self {
+      title: game.name.clone(),
+      summary: format!(
+        "{} · ended {}",
+        game.map_name,
+        format_game_time(game.game_time_ms)
+      ),
+      live: false,
+      image_path: Some(format!("/widgets/game/{}/timeline.png", game.id)),
+      sections: team_sections(teams),
+    }
+  }
+
+  /// Returns `None` if the player has no games in memory
+  pub fn player(player_id: i32, games: &PlayerGames) -> Option<Self> {
+    let name = games
+      .finished
+      .iter()
+      .flat_map(|g| g.players.iter())
+      .find(|p| p.player_id == player_id)
+      .map(|p| p.name.clone())
+      .or_else(|| {
+        games
+          .live
+          .iter()
+          .flat_map(|g| g.players.iter())
+          .find(|p| p.id == player_id)
+          .map(|p| p.name.clone())
+      })?;
+
+    let mut sections = vec![];
+    if !games.live.is_empty() {
+      sections.push(Section {
+        heading: "Live".to_string(),
+        rows: games
+          .live
+          .iter()
+          .map(|g| Row {
+            label: g.game_name.clone(),
+            detail: format!("{} · {}", g.map_name, format_game_time(g.game_time_ms)),
+          })
+          .collect(),
+      });
+    }
+
+    let (mut won, mut lost) = (0, 0);
+    let mut rows = vec![];
+    for game in &games.finished {
+      let reason = game
+        .players
+        .iter()
+        .find(|p| p.player_id == player_id)
+        .and_then(|p| p.left.map(|(_, reason)| reason));
+      match reason {
+        Some(PlayerLeaveReason::LeaveWon) => won += 1,
+        Some(PlayerLeaveReason::LeaveLost) | Some(PlayerLeaveReason::LeaveLostBuildings) => {
+          lost += 1
+        }
+        _ => {}
+      }
+      rows.push(Row {
+        label: game.name.clone(),
+        detail: match reason {
+          Some(reason) => format!(
+            "{} · {}",
+            result_name(reason),
+            format_game_time(game.game_time_ms)
+          ),
+          None => format_game_time(game.game_time_ms),
+        },
+      });
+    }
+    if !rows.is_empty() {
+      sections.push(Section {
+        heading: "Recent games".to_string(),
+        rows,
+      });
+    }
+
+    let summary = match games.live.first() {
+      Some(game) => format!("Playing {}", game.game_name),
+      None => format!(
+        "{} won, {} lost in the last {} games",
+        won,
+        lost,
+        games.finished.len()
+      ),
+    };
+
+    Some(Self {
+      title: name,
+      summary,
+      live: !games.live.is_empty(),
+      image_path: None,
+      sections,
+    })
+  }
+
+  /// Height of the rendered card in pixels, used to size oEmbed iframes
+  pub fn height(&self) -> u32 {
+    self.sections.iter().fold(HEADER_HEIGHT, |h, section| {
+      h + SECTION_HEIGHT + ROW_HEIGHT * section.rows.len() as u32
+    })
+  }
+
+  /// Renders a standalone page, `url` is the public url of the page itself
+  pub fn render_page(
+    &self,
+    url: &str,
+    image_url: Option<&str>,
+    refresh_secs: Option<u32>,
+  ) -> String {
+    let title = escape(&self.title);
+    let summary = escape(&self.summary);
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html><html><head><meta charset=\"utf-8\">");
+    write!(html, "<title>{}</title>", title).ok();
+    html.push_str("<meta property=\"og:site_name\" content=\"flo\">");
+    write!(html, "<meta property=\"og:title\" content=\"{}\">", title).ok();
+    write!(
+      html,
+      "<meta property=\"og:description\" content=\"{}\">",
+      summary
+    )
+    .ok();
+    if let Some(image_url) = image_url {
+      write!(
+        html,
+        "<meta property=\"og:image\" content=\"{}\">",
+        escape(image_url)
+      )
+      .ok();
+    }
+    write!(
+      html,
+      "<link rel=\"alternate\" type=\"application/json+oembed\" href=\"{}?format=json\" title=\"{}\">",
+      escape(url),
+      title
+    )
+    .ok();
+    if let Some(secs) = refresh_secs {
+      write!(html, "<meta http-equiv=\"refresh\" content=\"{}\">", secs).ok();
+    }
+    write!(
+      html,
+      "<style>{}</style></head><body><div class=\"card\">",
+      STYLE
+    )
+    .ok();
+    write!(
+      html,
+      "<div class=\"title\">{}</div><div class=\"summary\">",
+      title
+    )
+    .ok();
+    if self.live {
+      html.push_str("<span class=\"live\">LIVE</span>");
+    }
+    write!(html, "{}</div>", summary).ok();
+    for section in &self.sections {
+      write!(
+        html,
+        "<div class=\"heading\">{}</div><table>",
+        escape(&section.heading)
+      )
+      .ok();
+      for row in &section.rows {
+        write!(
+          html,
+          "<tr><td>{}</td><td class=\"detail\">{}</td></tr>",
+          escape(&row.label),
+          escape(&row.detail)
+        )
+        .ok();
+      }
+      html.push_str("</table>");
+    }
+    html.push_str("</div></body></html>");
+    html
+  }
+
+  /// Markup that embeds the page at `url`
+  pub fn render_iframe(&self, url: &str) -> String {
+    format!(
+      "<iframe src=\"{}\" width=\"{}\" height=\"{}\" frameborder=\"0\" scrolling=\"no\"></iframe>",
+      escape(url),
+      WIDTH,
+      self.height()
+    )
+  }
+}
+
+fn team_sections(teams: BTreeMap<i32, Vec<Row>>) -> Vec<Section> {
+  teams
+    .into_iter()
+    .map(|(team, rows)| Section {
+      heading: format!("Team {}", team + 1),
+      rows,
+    })
+    .collect()
+}
+
+fn race_name(race: Race) -> &'static str {
+  match race {
+    Race::Human => "Human",
+    Race::Orc => "Orc",
+    Race::NightElf => "Night Elf",
+    Race::Undead => "Undead",
+    Race::Random => "Random",
+  }
+}
+
+fn result_name(reason: PlayerLeaveReason) -> &'static str {
+  match reason {
+    PlayerLeaveReason::LeaveWon => "won",
+    PlayerLeaveReason::LeaveLost | PlayerLeaveReason::LeaveLostBuildings => "lost",
+    PlayerLeaveReason::LeaveDraw => "draw",
+    PlayerLeaveReason::LeaveDisconnect => "disconnected",
+    PlayerLeaveReason::LeaveObserver => "observer",
+    PlayerLeaveReason::LeaveUnknown => "left",
+  }
+}
+
+fn format_game_time(ms: u32) -> String {
+  let secs = ms / 1000;
+  if secs >= 3600 {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+  } else {
+    format!("{}:{:02}", secs / 60, secs % 60)
+  }
+}
+
+fn escape(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for c in value.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&#39;"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
@@ -0,0 +1,225 @@
+//! Embeddable game and player cards for community sites and link unfurlers.
+//!
+//! `GET /widgets/game/:id` and `GET /widgets/player/:id` return a small standalone HTML page
+//! with Open Graph tags, cards of running games reload themselves.
+//! With `?format=json` an oEmbed `rich` response is returned instead, its `html` embeds the page.
+//! `GET /widgets/game/:id/timeline.png` renders a finished game's timeline for social sharing,
+//! finished game pages use it as `og:image`.
+
+mod card;
+mod timeline;
+
+use axum::extract::{Extension, Path, Query};
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::Json;
+use card::Card;
+use flo_observer_edge::{Error, FloObserverEdgeHandle};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub use timeline::TimelineCache;
+
+const PLAYER_RECENT_GAMES: usize = 5;
+const LIVE_REFRESH_SECS: u32 = 30;
+const LIVE_CACHE_AGE_SECS: u32 = 10;
+const CACHE_AGE_SECS: u32 = 3600;
+
+/// Widget settings loaded at startup
+pub struct WidgetConfig {
+  /// Public url of this service, used in oEmbed responses and discovery links
+  pub base_url: String,
+  /// Local copy of the maps, timeline images include the map preview if the map is found here
+  pub map_dir: Option<PathBuf>,
+}
+
+impl WidgetConfig {
+  pub fn from_env() -> Self {
+    Self {
+      base_url: std::env::var("FLO_STATS_WIDGET_BASE_URL")
+        .map(|v| v.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| format!("https://{}", flo_constants::STATS_HOST)),
+      map_dir: std::env::var("FLO_STATS_MAP_DIR").ok().map(PathBuf::from),
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WidgetQuery {
+  format: Option<String>,
+}
+
+type WidgetError = (StatusCode, String);
+
+pub async fn game_widget_handler(
+  Path(game_id): Path<i32>,
+  Query(query): Query<WidgetQuery>,
+  Extension(config): Extension<Arc<WidgetConfig>>,
+  Extension(handle): Extension<FloObserverEdgeHandle>,
+) -> Result<Response, WidgetError> {
+  let finished = handle
+    .get_finished_game(game_id)
+    .await
+    .map_err(internal_error)?;
+  let card = match finished {
+    Some(game) => Card::finished_game(&game),
+    None => match handle.get_game_with_stats(game_id).await {
+      Ok(snapshot) => Card::live_game(&snapshot),
+      Err(Error::GameNotFound(_)) => {
+        return Err((
+          StatusCode::NOT_FOUND,
+          format!("game not found: {}", game_id),
+        ))
+      }
+      Err(err @ Error::GameNotReady(_)) => {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, err.to_string()))
+      }
+      Err(err) => return Err(internal_error(err)),
+    },
+  };
+  respond(&config, &format!("/widgets/game/{}", game_id), &card, query)
+}
+
+pub async fn game_timeline_handler(
+  Path(game_id): Path<i32>,
+  Extension(config): Extension<Arc<WidgetConfig>>,
+  Extension(cache): Extension<Arc<TimelineCache>>,
+  Extension(handle): Extension<FloObserverEdgeHandle>,
+) -> Result<Response, WidgetError> {
+  let png = match cache.get(game_id) {
+    Some(png) => png,
+    None => {
+      let game = handle
+        .get_finished_game(game_id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+          (
+            StatusCode::NOT_FOUND,
+            format!("finished game not found: {}", game_id),
+          )
+        })?;
+      let preview = match config.map_dir {
+        Some(ref dir) => timeline::load_preview(dir, &game.map_path).await,
+        None => None,
+      };
+      let png = tokio::task::spawn_blocking(move || timeline::render(&game, preview.as_ref()))
+        .await
+        .map_err(internal_error)?;
+      cache.insert(game_id, png)
+    }
+  };
+
+  let mut headers = HeaderMap::new();
+  headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+  if let Ok(value) = HeaderValue::from_str(&format!("public, max-age={}", CACHE_AGE_SECS)) {
+    headers.insert(CACHE_CONTROL, value);
+  }
+  Ok((headers, png.as_ref().clone()).into_response())
+}
+
+pub async fn player_widget_handler(
+  Path(player_id): Path<i32>,
+  Query(query): Query<WidgetQuery>,
+  Extension(config): Extension<Arc<WidgetConfig>>,
+  Extension(handle): Extension<FloObserverEdgeHandle>,
+) -> Result<Response, WidgetError> {
+  let games = handle
+    .list_player_games(player_id, PLAYER_RECENT_GAMES)
+    .await
+    .map_err(internal_error)?;
+  let card = Card::player(player_id, &games).ok_or_else(|| {
+    (
+      StatusCode::NOT_FOUND,
+      format!("no recent games: {}", player_id),
+    )
+  })?;
+  respond(
+    &config,
+    &format!("/widgets/player/{}", player_id),
+    &card,
+    query,
+  )
+}
+
+/// https://oembed.com/#section2.3
+#[derive(Debug, Serialize)]
+struct OEmbed {
+  version: &'static str,
+  #[serde(rename = "type")]
+  kind: &'static str,
+  provider_name: &'static str,
+  provider_url: String,
+  title: String,
+  html: String,
+  width: u32,
+  height: u32,
+  cache_age: u32,
+}
+
+fn respond(
+  config: &WidgetConfig,
+  path: &str,
+  card: &Card,
+  query: WidgetQuery,
+) -> Result<Response, WidgetError> {
+  let url = format!("{}{}", config.base_url, path);
+  let cache_age = if card.live {
+    LIVE_CACHE_AGE_SECS
+  } else {
+    CACHE_AGE_SECS
+  };
+  let mut headers = HeaderMap::new();
+  if let Ok(value) = HeaderValue::from_str(&format!("public, max-age={}", cache_age)) {
+    headers.insert(CACHE_CONTROL, value);
+  }
+
+  match query.format.as_deref() {
+    None | Some("html") => {
+      let refresh_secs = if card.live {
+        Some(LIVE_REFRESH_SECS)
+      } else {
+        None
+      };
+      let image_url = card
+        .image_path
+        .as_ref()
+        .map(|path| format!("{}{}", config.base_url, path));
+      Ok(
+        (
+          headers,
+          Html(card.render_page(&url, image_url.as_deref(), refresh_secs)),
+        )
+          .into_response(),
+      )
+    }
+    Some("json") => Ok(
+      (
+        headers,
+        Json(OEmbed {
+          version: "1.0",
+          kind: "rich",
+          provider_name: "flo",
+          provider_url: config.base_url.clone(),
+          title: card.title.clone(),
+          html: card.render_iframe(&url),
+          width: card::WIDTH,
+          height: card.height(),
+          cache_age,
+        }),
+      )
+        .into_response(),
+    ),
+    Some(other) => Err((
+      StatusCode::BAD_REQUEST,
+      format!("unknown format: {}", other),
+    )),
+  }
+}
+
+fn internal_error<E: std::fmt::Display>(err: E) -> WidgetError {
+  tracing::error!("widget: {}", err);
+  (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
@@ -0,0 +1,266 @@
+//! Timeline image of a finished game: the map preview, team APM over time and when each
+//! player left, colored by the result.
+//!
+//! The observer stream carries no unit or resource data, kills and gold can't be graphed.
+
+use flo_observer_edge::game::finished::FinishedGame;
+use flo_observer_edge::game::PlayerLeaveReason;
+use image::imageops::{overlay, resize, FilterType};
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const WIDTH: u32 = 800;
+const MIN_HEIGHT: u32 = 320;
+const PADDING: u32 = 16;
+const PREVIEW_SIZE: u32 = 256;
+const CHART_LEFT: u32 = PADDING * 2 + PREVIEW_SIZE;
+const CHART_RIGHT: u32 = WIDTH - PADDING;
+const CHART_TOP: u32 = PADDING;
+const CHART_BOTTOM: u32 = 200;
+const LANE_TOP: u32 = CHART_BOTTOM + PADDING;
+const LANE_HEIGHT: u32 = 12;
+const GRID_INTERVAL_MS: u32 = 5 * 60 * 1000;
+
+const BACKGROUND: Rgba<u8> = Rgba([0x1b, 0x1d, 0x23, 0xff]);
+const PANEL: Rgba<u8> = Rgba([0x2a, 0x2d, 0x35, 0xff]);
+const GRID: Rgba<u8> = Rgba([0x3a, 0x3d, 0x46, 0xff]);
+const WON: Rgba<u8> = Rgba([0x4c, 0xd1, 0x6b, 0xff]);
+const LOST: Rgba<u8> = Rgba([0xff, 0x5c, 0x5c, 0xff]);
+const DRAW: Rgba<u8> = Rgba([0xff, 0xd0, 0x4c, 0xff]);
+const LEFT: Rgba<u8> = Rgba([0x9a, 0xa0, 0xaa, 0xff]);
+
+// in-game team colors
+const TEAM_COLORS: [Rgba<u8>; 6] = [
+  Rgba([0xff, 0x03, 0x03, 0xff]),
+  Rgba([0x00, 0x42, 0xff, 0xff]),
+  Rgba([0x1c, 0xe6, 0xb9, 0xff]),
+  Rgba([0x54, 0x00, 0x81, 0xff]),
+  Rgba([0xff, 0xfc, 0x00, 0xff]),
+  Rgba([0xfe, 0x8a, 0x0e, 0xff]),
+];
+
+/// Rendered images of finished games, the lowest game ids are dropped first
+pub struct TimelineCache {
+  map: Mutex<BTreeMap<i32, Arc<Vec<u8>>>>,
+  cap: usize,
+}
+
+impl TimelineCache {
+  pub fn new(cap: usize) -> Self {
+    Self {
+      map: Mutex::new(BTreeMap::new()),
+      cap: std::cmp::max(cap, 1),
+    }
+  }
+
+  pub fn from_env() -> Self {
+    Self::new(
+      std::env::var("FLO_STATS_TIMELINE_CACHE_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(256),
+    )
+  }
+
+  pub fn get(&self, game_id: i32) -> Option<Arc<Vec<u8>>> {
+    self.map.lock().unwrap().get(&game_id).cloned()
+  }
+
+  pub fn insert(&self, game_id: i32, png: Vec<u8>) -> Arc<Vec<u8>> {
+    let png = Arc::new(png);
+    let mut map = self.map.lock().unwrap();
+    map.insert(game_id, png.clone());
+    while map.len() > self.cap {
+      let id = *map.keys().next().expect("map is not empty");
+      map.remove(&id);
+    }
+    png
+  }
+}
+
+/// Loads the minimap preview of `map_path` from a local copy of the maps,
+/// returns `None` if the map isn't there
+pub async fn load_preview(map_dir: &Path, map_path: &str) -> Option<RgbaImage> {
+  let relative = map_path.replace('\\', "/");
+  if relative.split('/').any(|part| part == "..") {
+    return None;
+  }
+  let path = map_dir.join(relative);
+  let map = match flo_w3map::W3Map::open_async(&path).await {
+    Ok(map) => map,
+    Err(err) => {
+      tracing::debug!("timeline: open map `{}`: {}", path.display(), err);
+      return None;
+    }
+  };
+  image::load_from_memory_with_format(&map.render_preview_png(), ImageFormat::Png)
+    .ok()
+    .map(|image| image.to_rgba8())
+}
+
+/// Renders the timeline as PNG
+pub fn render(game: &FinishedGame, preview: Option<&RgbaImage>) -> Vec<u8> {
+  let players: Vec<_> = game.players.iter().filter(|p| p.team != 24).collect();
+  let height = std::cmp::max(
+    MIN_HEIGHT,
+    LANE_TOP + LANE_HEIGHT * players.len() as u32 + PADDING,
+  );
+  let mut image = RgbaImage::from_pixel(WIDTH, height, BACKGROUND);
+
+  match preview {
+    Some(preview) => {
+      let preview = resize(preview, PREVIEW_SIZE, PREVIEW_SIZE, FilterType::Triangle);
+      overlay(&mut image, &preview, PADDING, PADDING);
+    }
+    None => fill_rect(
+      &mut image,
+      PADDING,
+      PADDING,
+      PREVIEW_SIZE,
+      PREVIEW_SIZE,
+      PANEL,
+    ),
+  }
+
+  fill_rect(
+    &mut image,
+    CHART_LEFT,
+    CHART_TOP,
+    CHART_RIGHT - CHART_LEFT,
+    CHART_BOTTOM - CHART_TOP,
+    PANEL,
+  );
+  let duration = std::cmp::max(game.game_time_ms, 1);
+  let chart_width = (CHART_RIGHT - CHART_LEFT - 1) as u64;
+  let time_x =
+    |time: u32| CHART_LEFT + (time.min(duration) as u64 * chart_width / duration as u64) as u32;
+  let mut grid = GRID_INTERVAL_MS;
+  while grid < duration {
+    let x = time_x(grid);
+    fill_rect(&mut image, x, CHART_TOP, 1, CHART_BOTTOM - CHART_TOP, GRID);
+    fill_rect(
+      &mut image,
+      x,
+      LANE_TOP,
+      1,
+      height - PADDING - LANE_TOP,
+      GRID,
+    );
+    grid += GRID_INTERVAL_MS;
+  }
+
+  let teams: Vec<i32> = {
+    let mut teams: Vec<i32> = players.iter().map(|p| p.team).collect();
+    teams.sort_unstable();
+    teams.dedup();
+    teams
+  };
+  let team_color = |team: i32| {
+    let idx = teams.iter().position(|t| *t == team).unwrap_or(0);
+    TEAM_COLORS[idx % TEAM_COLORS.len()]
+  };
+
+  let player_teams: HashMap<i32, i32> = players.iter().map(|p| (p.player_id, p.team)).collect();
+  let team_apm: Vec<(u32, HashMap<i32, f32>)> = game
+    .apm
+    .iter()
+    .map(|stats| {
+      let mut apm = HashMap::new();
+      for item in &stats.data {
+        if let Some(team) = player_teams.get(&item.player_id) {
+          *apm.entry(*team).or_insert(0.) += item.apm;
+        }
+      }
+      (stats.time, apm)
+    })
+    .collect();
+  let max_apm = team_apm
+    .iter()
+    .flat_map(|(_, apm)| apm.values().cloned())
+    .fold(1., f32::max);
+  let apm_y = |apm: f32| {
+    let range = (CHART_BOTTOM - CHART_TOP - 2) as f32;
+    CHART_BOTTOM - 1 - (apm / max_apm * range) as u32
+  };
+  for team in &teams {
+    let points: Vec<(u32, u32)> = team_apm
+      .iter()
+      .map(|(time, apm)| (time_x(*time), apm_y(apm.get(team).cloned().unwrap_or(0.))))
+      .collect();
+    for pair in points.windows(2) {
+      draw_line(&mut image, pair[0], pair[1], team_color(*team));
+    }
+  }
+
+  for (idx, player) in players.iter().enumerate() {
+    let y = LANE_TOP + LANE_HEIGHT * idx as u32;
+    let (end, marker) = match player.left {
+      Some((time, reason)) => (time, result_color(reason)),
+      None => (duration, LEFT),
+    };
+    let end_x = time_x(end);
+    fill_rect(
+      &mut image,
+      CHART_LEFT,
+      y + 3,
+      end_x - CHART_LEFT,
+      LANE_HEIGHT - 6,
+      team_color(player.team),
+    );
+    let marker_x = std::cmp::min(end_x.saturating_sub(3), CHART_RIGHT - 6);
+    fill_rect(&mut image, marker_x, y + 3, 6, 6, marker);
+  }
+
+  let mut bytes = vec![];
+  DynamicImage::ImageRgba8(image)
+    .write_to(&mut bytes, ImageFormat::Png)
+    .ok();
+  bytes
+}
+
+fn result_color(reason: PlayerLeaveReason) -> Rgba<u8> {
+  match reason {
+    PlayerLeaveReason::LeaveWon => WON,
+    PlayerLeaveReason::LeaveLost | PlayerLeaveReason::LeaveLostBuildings => LOST,
+    PlayerLeaveReason::LeaveDraw => DRAW,
+    PlayerLeaveReason::LeaveDisconnect
+    | PlayerLeaveReason::LeaveObserver
+    | PlayerLeaveReason::LeaveUnknown => LEFT,
+  }
+}
+
+fn fill_rect(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+  for py in y..std::cmp::min(y + height, image.height()) {
+    for px in x..std::cmp::min(x + width, image.width()) {
+      image.put_pixel(px, py, color);
+    }
+  }
+}
+
+// Bresenham, 2px thick
+fn draw_line(image: &mut RgbaImage, from: (u32, u32), to: (u32, u32), color: Rgba<u8>) {
+  let (mut x, mut y) = (from.0 as i64, from.1 as i64);
+  let (x1, y1) = (to.0 as i64, to.1 as i64);
+  let dx = (x1 - x).abs();
+  let dy = -(y1 - y).abs();
+  let sx = if x < x1 { 1 } else { -1 };
+  let sy = if y < y1 { 1 } else { -1 };
+  let mut err = dx + dy;
+  loop {
+    fill_rect(image, x as u32, y as u32, 2, 2, color);
+    if x == x1 && y == y1 {
+      break;
+    }
+    let e2 = 2 * err;
+    if e2 >= dy {
+      err += dy;
+      x += sx;
+    }
+    if e2 <= dx {
+      err += dx;
+      y += sy;
+    }
+  }
+}
@@ -0,0 +1,138 @@
+use flo_w3gs::action::{IncomingAction, IncomingAction2, OutgoingAction, TimeSlot};
+use flo_w3gs::constants::PacketTypeId;
+use flo_w3gs::error::Result;
+use flo_w3gs::packet::{Packet, ProtoBufPayload};
+use flo_w3gs::protocol::*;
+
+/// Decoded payload fields of the packet, empty for packet types without a decoder
+pub fn describe(packet: &Packet) -> Result<String> {
+  let text = match packet.type_id() {
+    PacketTypeId::PingFromHost => format!("{:?}", packet.decode_simple::<ping::PingFromHost>()?),
+    PacketTypeId::PongToHost => format!("{:?}", packet.decode_simple::<ping::PongToHost>()?),
+    PacketTypeId::SlotInfoJoin => format!("{:?}", packet.decode_simple::<join::SlotInfoJoin>()?),
+    PacketTypeId::RejectJoin => format!("{:?}", packet.decode_simple::<join::RejectJoin>()?),
+    PacketTypeId::ReqJoin => format!("{:?}", packet.decode_simple::<join::ReqJoin>()?),
+    PacketTypeId::PlayerInfo => format!("{:?}", packet.decode_simple::<player::PlayerInfo>()?),
+    PacketTypeId::PlayerLoaded => {
+      format!("{:?}", packet.decode_simple::<player::PlayerLoaded>()?)
+    }
+    PacketTypeId::PlayerLeft => format!("{:?}", packet.decode_simple::<leave::PlayerLeft>()?),
+    PacketTypeId::PlayerKicked => {
+      format!("{:?}", packet.decode_simple::<leave::PlayerKicked>()?)
+    }
+    PacketTypeId::LeaveReq => format!("{:?}", packet.decode_simple::<leave::LeaveReq>()?),
+    PacketTypeId::SlotInfo => format!("{:?}", packet.decode_simple::<slot::SlotInfo>()?),
+    PacketTypeId::IncomingAction => {
+      describe_time_slot(&packet.decode_payload::<IncomingAction>()?.0)
+    }
+    PacketTypeId::IncomingAction2 => {
+      describe_time_slot(&packet.decode_payload::<IncomingAction2>()?.0)
+    }
+    PacketTypeId::OutgoingAction => {
+      let payload = packet.decode_payload::<OutgoingAction>()?;
+      format!("crc32=0x{:08x} len={}", payload.crc32, payload.data.len())
+    }
+    PacketTypeId::OutgoingKeepAlive => {
+      format!("{:?}", packet.decode_simple::<action::OutgoingKeepAlive>()?)
+    }
+    PacketTypeId::Desync => format!("{:?}", packet.decode_simple::<desync::Desync>()?),
+    PacketTypeId::ChatToHost => format!("{:?}", packet.decode_simple::<chat::ChatToHost>()?),
+    PacketTypeId::ChatFromHost => {
+      format!("{:?}", packet.decode_simple::<chat::ChatFromHost>()?)
+    }
+    PacketTypeId::ChatFromOthers => {
+      format!("{:?}", packet.decode_simple::<chat::ChatFromOthers>()?)
+    }
+    PacketTypeId::StartLag => format!("{:?}", packet.decode_simple::<lag::StartLag>()?),
+    PacketTypeId::StopLag => format!("{:?}", packet.decode_simple::<lag::StopLag>()?),
+    PacketTypeId::MapCheck => format!("{:?}", packet.decode_simple::<map::MapCheck>()?),
+    PacketTypeId::MapSize => format!("{:?}", packet.decode_simple::<map::MapSize>()?),
+    PacketTypeId::ProtoBuf => {
+      let payload: ProtoBufPayload = packet.decode_simple()?;
+      format!("{:?} len={}", payload.type_id, payload.len)
+    }
+    _ => String::new(),
+  };
+  Ok(text)
+}
+
+// `time_increment_ms=100 [p1: SelectSubgroup114b, ChangeSelection] [p2: 12 bytes]`
+fn describe_time_slot(slot: &TimeSlot) -> String {
+  let mut text = format!("time_increment_ms={}", slot.time_increment_ms);
+  for player_action in &slot.actions {
+    let mut type_ids = vec![];
+    let mut ok = true;
+    for action in player_action.actions() {
+      match action {
+        Ok(action) => type_ids.push(format!("{:?}", action.type_id())),
+        Err(_) => {
+          ok = false;
+          break;
+        }
+      }
+    }
+    if ok {
+      text.push_str(&format!(
+        " [p{}: {}]",
+        player_action.player_id,
+        type_ids.join(", ")
+      ));
+    } else {
+      text.push_str(&format!(
+        " [p{}: {} bytes]",
+        player_action.player_id,
+        player_action.data.len()
+      ));
+    }
+  }
+  text
+}
+
+/// Matches packet types by name, e.g. `ChatToHost`, or id, e.g. `0x0C`
+pub struct TypeFilter {
+  types: Vec<PacketTypeId>,
+}
+
+impl TypeFilter {
+  pub fn parse(values: &[String]) -> Result<Self, String> {
+    let types = values
+      .iter()
+      .map(|value| {
+        if let Some(hex) = value
+          .strip_prefix("0x")
+          .or_else(|| value.strip_prefix("0X"))
+        {
+          return u8::from_str_radix(hex, 16)
+            .map(PacketTypeId::from)
+            .map_err(|_| format!("invalid packet type id: {}", value));
+        }
+        (0..=u8::MAX)
+          .map(PacketTypeId::from)
+          .find(|type_id| format!("{:?}", type_id).eq_ignore_ascii_case(value))
+          .ok_or_else(|| format!("unknown packet type: {}", value))
+      })
+      .collect::<Result<_, _>>()?;
+    Ok(Self { types })
+  }
+
+  pub fn matches(&self, type_id: PacketTypeId) -> bool {
+    self.types.is_empty() || self.types.contains(&type_id)
+  }
+
+  pub fn matches_all(&self) -> bool {
+    self.types.is_empty()
+  }
+}
+
+#[test]
+fn test_type_filter() {
+  let filter = TypeFilter::parse(&["chattohost".to_string(), "0x0C".to_string()]).unwrap();
+  assert!(filter.matches(PacketTypeId::ChatToHost));
+  assert!(filter.matches(PacketTypeId::IncomingAction));
+  assert!(!filter.matches(PacketTypeId::IncomingAction2));
+  assert!(TypeFilter::parse(&[])
+    .unwrap()
+    .matches(PacketTypeId::SlotInfo));
+  assert!(TypeFilter::parse(&["Nope".to_string()]).is_err());
+  assert!(TypeFilter::parse(&["0xZZ".to_string()]).is_err());
+}
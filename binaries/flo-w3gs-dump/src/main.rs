@@ -0,0 +1,331 @@
+//! Prints the decoded W3GS packets of
+//!
+//! - `pcap`: a libpcap capture, e.g. `tcpdump -i lo -w game.pcap port 6112`
+//! - `raw`: the bytes of one direction of a W3GS connection
+//! - `archive`: an archived observer game
+
+mod describe;
+mod pcap;
+
+use anyhow::{anyhow, Result};
+use bytes::{Buf, BytesMut};
+use clap::Parser;
+use describe::{describe, TypeFilter};
+use flo_observer::record::GameRecordData;
+use flo_observer_fs::GameDataArchiveReader;
+use flo_w3gs::action::TimeSlot;
+use flo_w3gs::constants::PacketTypeId;
+use flo_w3gs::packet::Packet;
+use pcap::{Flow, PcapReader, Protocol, Reassembled, StreamAssembler};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[clap(name = "w3gs-dump", version = "1.0")]
+struct Opts {
+  path: PathBuf,
+  /// `pcap`, `raw` or `archive`, detected from the file content if omitted
+  #[clap(short, long)]
+  format: Option<InputFormat>,
+  /// Only print packets of these types, by name or id, e.g. `-t ChatToHost -t 0x0C`
+  #[clap(short = 't', long = "type")]
+  types: Vec<String>,
+  /// Only read TCP/UDP flows from or to this port (pcap)
+  #[clap(short, long)]
+  port: Option<u16>,
+  /// Print payload hex dumps
+  #[clap(long)]
+  hex: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InputFormat {
+  Pcap,
+  Raw,
+  Archive,
+}
+
+impl InputFormat {
+  fn detect(data: &[u8]) -> Option<Self> {
+    if pcap::is_pcap(data) {
+      return Some(InputFormat::Pcap);
+    }
+    match data {
+      [0x1F, 0x8B, ..] => Some(InputFormat::Archive),
+      [0xF7, ..] => Some(InputFormat::Raw),
+      _ => None,
+    }
+  }
+}
+
+impl FromStr for InputFormat {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "pcap" => Ok(InputFormat::Pcap),
+      "raw" => Ok(InputFormat::Raw),
+      "archive" => Ok(InputFormat::Archive),
+      other => Err(format!("unknown format: {}", other)),
+    }
+  }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+  let opts = Opts::parse();
+  let filter = TypeFilter::parse(&opts.types).map_err(|err| anyhow!(err))?;
+  let mut printer = Printer {
+    filter,
+    hex: opts.hex,
+    count: 0,
+  };
+
+  let data = std::fs::read(&opts.path)?;
+  let format = match opts.format {
+    Some(format) => format,
+    None => InputFormat::detect(&data)
+      .ok_or_else(|| anyhow!("unknown input format, specify it with `--format`"))?,
+  };
+
+  match format {
+    InputFormat::Pcap => dump_pcap(&data, opts.port, &mut printer)?,
+    InputFormat::Raw => dump_raw(&data, &mut printer),
+    InputFormat::Archive => dump_archive(&opts.path, &mut printer).await?,
+  }
+
+  eprintln!("{} packets", printer.count);
+  Ok(())
+}
+
+fn dump_pcap(data: &[u8], port: Option<u16>, printer: &mut Printer) -> Result<()> {
+  let mut reader = PcapReader::new(data)?;
+  let mut streams: HashMap<Flow, (StreamAssembler, PacketStream)> = HashMap::new();
+  let mut start_time = None;
+
+  while let Some(segment) = reader.next_segment()? {
+    if let Some(port) = port {
+      if !segment.flow.has_port(port) {
+        continue;
+      }
+    }
+
+    let time = segment
+      .time
+      .checked_sub(*start_time.get_or_insert(segment.time))
+      .unwrap_or_default();
+    let source = format!(
+      "{:?} {} -> {} ",
+      segment.flow.protocol, segment.flow.src, segment.flow.dst
+    );
+
+    match segment.flow.protocol {
+      // LAN game discovery
+      Protocol::Udp => {
+        if segment.payload.first() != Some(&0xF7) {
+          continue;
+        }
+        let mut stream = PacketStream::default();
+        stream.extend(segment.payload);
+        while let Some(packet) = stream.next_packet() {
+          printer.print(Some(time), &source, &packet);
+        }
+      }
+      Protocol::Tcp => {
+        let (assembler, stream) = streams.entry(segment.flow).or_default();
+        for item in assembler.push(segment.seq, segment.syn, segment.payload) {
+          match item {
+            Reassembled::Data(data) => stream.extend(&data),
+            Reassembled::Gap(len) => {
+              printer.note(Some(time), &source, &format!("{} bytes not captured", len));
+              stream.clear();
+            }
+          }
+        }
+        while let Some(packet) = stream.next_packet() {
+          printer.print(Some(time), &source, &packet);
+        }
+        let skipped = stream.take_skipped();
+        if skipped > 0 {
+          printer.note(Some(time), &source, &format!("skipped {} bytes", skipped));
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
+fn dump_raw(data: &[u8], printer: &mut Printer) {
+  let mut stream = PacketStream::default();
+  stream.extend(data);
+  while let Some(packet) = stream.next_packet() {
+    let skipped = stream.take_skipped();
+    if skipped > 0 {
+      printer.note(None, "", &format!("skipped {} bytes", skipped));
+    }
+    printer.print(None, "", &packet);
+  }
+  let remaining = stream.take_skipped() + stream.buf.len();
+  if remaining > 0 {
+    printer.note(None, "", &format!("{} trailing bytes", remaining));
+  }
+}
+
+/// Times are game times, summed from the action packet time increments
+async fn dump_archive(path: &Path, printer: &mut Printer) -> Result<()> {
+  let reader = GameDataArchiveReader::open(path).await?;
+  printer.note(None, "", &format!("game {}", reader.game_id()));
+
+  let mut records = reader.records();
+  let mut game_time_ms: u64 = 0;
+  while let Some(record) = records.next().await? {
+    let time = Some(Duration::from_millis(game_time_ms));
+    match record {
+      GameRecordData::W3GS(packet) => {
+        printer.print(time, "", &packet);
+        if let PacketTypeId::IncomingAction | PacketTypeId::IncomingAction2 = packet.type_id() {
+          if let Some(time_increment_ms) = TimeSlot::peek_time_increment_ms(&packet.payload) {
+            game_time_ms += time_increment_ms as u64;
+          }
+        }
+      }
+      GameRecordData::Keyframe(_) => printer.note(time, "", "Keyframe"),
+      other => printer.note(time, "", &format!("{:?}", other)),
+    }
+  }
+
+  Ok(())
+}
+
+struct Printer {
+  filter: TypeFilter,
+  hex: bool,
+  count: usize,
+}
+
+impl Printer {
+  fn print(&mut self, time: Option<Duration>, source: &str, packet: &Packet) {
+    if !self.filter.matches(packet.type_id()) {
+      return;
+    }
+    self.count += 1;
+    let details = match describe(packet) {
+      Ok(text) => text,
+      Err(err) => format!("decode error: {}", err),
+    };
+    println!(
+      "{}{}{:?} (0x{:02X}) len={} {}",
+      format_time(time),
+      source,
+      packet.type_id(),
+      u8::from(packet.type_id()),
+      packet.payload_len(),
+      details
+    );
+    if self.hex && !packet.payload.is_empty() {
+      flo_util::dump_hex(&packet.payload);
+    }
+  }
+
+  /// Non-packet lines, hidden if only some packet types are printed
+  fn note(&self, time: Option<Duration>, source: &str, text: &str) {
+    if self.filter.matches_all() {
+      println!("{}{}-- {}", format_time(time), source, text);
+    }
+  }
+}
+
+fn format_time(time: Option<Duration>) -> String {
+  time
+    .map(|time| format!("[{:>10.3}s] ", time.as_secs_f64()))
+    .unwrap_or_default()
+}
+
+/// Splits the bytes of a W3GS stream into packets,
+/// bytes before the next packet signature are skipped
+#[derive(Debug, Default)]
+struct PacketStream {
+  buf: BytesMut,
+  skipped: usize,
+}
+
+impl PacketStream {
+  fn extend(&mut self, data: &[u8]) {
+    self.buf.extend_from_slice(data);
+  }
+
+  fn clear(&mut self) {
+    self.buf.clear();
+  }
+
+  fn take_skipped(&mut self) -> usize {
+    std::mem::take(&mut self.skipped)
+  }
+
+  fn skip(&mut self, len: usize) {
+    self.skipped += len;
+    self.buf.advance(len);
+  }
+
+  fn next_packet(&mut self) -> Option<Packet> {
+    loop {
+      match self.buf.iter().position(|b| *b == 0xF7) {
+        Some(0) => {}
+        Some(pos) => self.skip(pos),
+        None => {
+          self.skip(self.buf.len());
+          return None;
+        }
+      }
+
+      if self.buf.len() < 4 {
+        return None;
+      }
+      let len = u16::from_le_bytes([self.buf[2], self.buf[3]]) as usize;
+      if len < 4 {
+        self.skip(1);
+        continue;
+      }
+      if self.buf.len() < len {
+        return None;
+      }
+
+      let mut bytes = self.buf.split_to(len);
+      let packet =
+        Packet::decode_header(&mut bytes).and_then(|header| Packet::decode(header, &mut bytes));
+      match packet {
+        Ok(packet) => return Some(packet),
+        Err(_) => self.skipped += len,
+      }
+    }
+  }
+}
+
+#[test]
+fn test_packet_stream() {
+  let mut stream = PacketStream::default();
+  stream.extend(&[0x00, 0x01, 0xF7, 0x01, 0x08, 0x00, 0x01, 0x02]);
+  assert!(stream.next_packet().is_none());
+  assert_eq!(stream.take_skipped(), 2);
+
+  stream.extend(&[0x03, 0x04, 0xF7, 0x08, 0x05]);
+  let packet = stream.next_packet().unwrap();
+  assert_eq!(packet.type_id(), PacketTypeId::PingFromHost);
+  assert_eq!(packet.payload.as_ref(), &[0x01, 0x02, 0x03, 0x04]);
+  assert!(stream.next_packet().is_none());
+
+  stream.extend(&[0x00, 0x01]);
+  let packet = stream.next_packet().unwrap();
+  assert_eq!(packet.type_id(), PacketTypeId::PlayerLoaded);
+  assert_eq!(stream.take_skipped(), 0);
+
+  assert_eq!(InputFormat::detect(&[0xF7, 0x01]), Some(InputFormat::Raw));
+  assert_eq!(
+    InputFormat::detect(&[0x1F, 0x8B]),
+    Some(InputFormat::Archive)
+  );
+  assert_eq!(InputFormat::detect(&[0x00]), None);
+}
@@ -0,0 +1,413 @@
+//! Minimal libpcap file reader with TCP stream reassembly,
+//! enough to pull W3GS streams out of a `tcpdump` capture.
+//! IP fragments and pcapng files are not supported.
+
+use anyhow::{anyhow, bail, Result};
+use std::convert::TryInto;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+const MAGIC_MICROS: u32 = 0xa1b2c3d4;
+const MAGIC_NANOS: u32 = 0xa1b23c4d;
+const HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+const IP_PROTOCOL_TCP: u8 = 6;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+/// Out-of-order segments buffered per stream before the missing data is given up on
+const MAX_PENDING_SEGMENTS: usize = 256;
+
+pub fn is_pcap(data: &[u8]) -> bool {
+  read_magic(data).is_some()
+}
+
+// (big endian, nanosecond timestamps)
+fn read_magic(data: &[u8]) -> Option<(bool, bool)> {
+  let bytes: [u8; 4] = data.get(..4)?.try_into().ok()?;
+  for (big_endian, value) in [
+    (false, u32::from_le_bytes(bytes)),
+    (true, u32::from_be_bytes(bytes)),
+  ] {
+    match value {
+      MAGIC_MICROS => return Some((big_endian, false)),
+      MAGIC_NANOS => return Some((big_endian, true)),
+      _ => {}
+    }
+  }
+  None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+  Tcp,
+  Udp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Flow {
+  pub protocol: Protocol,
+  pub src: SocketAddr,
+  pub dst: SocketAddr,
+}
+
+impl Flow {
+  pub fn has_port(&self, port: u16) -> bool {
+    self.src.port() == port || self.dst.port() == port
+  }
+}
+
+/// Payload of a TCP segment or an UDP datagram
+#[derive(Debug)]
+pub struct Segment<'a> {
+  /// Capture timestamp
+  pub time: Duration,
+  pub flow: Flow,
+  /// TCP only
+  pub seq: u32,
+  /// TCP only
+  pub syn: bool,
+  pub payload: &'a [u8],
+}
+
+pub struct PcapReader<'a> {
+  data: &'a [u8],
+  pos: usize,
+  big_endian: bool,
+  nanos: bool,
+  link_type: u32,
+}
+
+impl<'a> PcapReader<'a> {
+  pub fn new(data: &'a [u8]) -> Result<Self> {
+    let (big_endian, nanos) = read_magic(data).ok_or_else(|| anyhow!("not a pcap file"))?;
+    if data.len() < HEADER_LEN {
+      bail!("truncated pcap header");
+    }
+    let mut reader = Self {
+      data,
+      pos: HEADER_LEN,
+      big_endian,
+      nanos,
+      link_type: 0,
+    };
+    // upper bits are FCS flags
+    reader.link_type = reader.u32_at(20) & 0x0FFF_FFFF;
+    match reader.link_type {
+      LINKTYPE_NULL | LINKTYPE_ETHERNET | LINKTYPE_RAW | LINKTYPE_LINUX_SLL | LINKTYPE_IPV4
+      | LINKTYPE_IPV6 | LINKTYPE_LINUX_SLL2 => {}
+      other => bail!("unsupported link type: {}", other),
+    }
+    Ok(reader)
+  }
+
+  fn u32_at(&self, pos: usize) -> u32 {
+    let bytes: [u8; 4] = self.data[pos..(pos + 4)].try_into().unwrap();
+    if self.big_endian {
+      u32::from_be_bytes(bytes)
+    } else {
+      u32::from_le_bytes(bytes)
+    }
+  }
+
+  /// Next TCP/UDP segment, other frames are skipped
+  pub fn next_segment(&mut self) -> Result<Option<Segment<'a>>> {
+    let data = self.data;
+    loop {
+      if self.pos + RECORD_HEADER_LEN > data.len() {
+        return Ok(None);
+      }
+      let ts_sec = self.u32_at(self.pos);
+      let ts_frac = self.u32_at(self.pos + 4);
+      let captured_len = self.u32_at(self.pos + 8) as usize;
+      let start = self.pos + RECORD_HEADER_LEN;
+      let end = start + captured_len;
+      if end > data.len() {
+        bail!("truncated packet record at offset {}", self.pos);
+      }
+      self.pos = end;
+
+      let time = Duration::from_secs(ts_sec as u64)
+        + if self.nanos {
+          Duration::from_nanos(ts_frac as u64)
+        } else {
+          Duration::from_micros(ts_frac as u64)
+        };
+      if let Some(segment) = parse_frame(self.link_type, &data[start..end], time) {
+        return Ok(Some(segment));
+      }
+    }
+  }
+}
+
+fn parse_frame(link_type: u32, frame: &[u8], time: Duration) -> Option<Segment<'_>> {
+  let packet = match link_type {
+    // 4 bytes address family in host byte order
+    LINKTYPE_NULL => frame.get(4..)?,
+    LINKTYPE_ETHERNET => {
+      let mut offset = 12;
+      let mut ether_type = read_u16(frame, offset)?;
+      while ether_type == ETHERTYPE_VLAN {
+        offset += 4;
+        ether_type = read_u16(frame, offset)?;
+      }
+      if ether_type != ETHERTYPE_IPV4 && ether_type != ETHERTYPE_IPV6 {
+        return None;
+      }
+      frame.get((offset + 2)..)?
+    }
+    LINKTYPE_LINUX_SLL => frame.get(16..)?,
+    LINKTYPE_LINUX_SLL2 => frame.get(20..)?,
+    _ => frame,
+  };
+  parse_ip(packet, time)
+}
+
+fn parse_ip(packet: &[u8], time: Duration) -> Option<Segment<'_>> {
+  let (src_ip, dst_ip, protocol, payload): (IpAddr, IpAddr, u8, &[u8]) = match packet.first()? >> 4
+  {
+    4 => {
+      let header_len = (packet[0] & 0x0F) as usize * 4;
+      let fragment = read_u16(packet, 6)?;
+      // more fragments or fragment offset
+      if fragment & 0x3FFF != 0 {
+        return None;
+      }
+      // 0 with TCP segmentation offload
+      let total_len = match read_u16(packet, 2)? as usize {
+        0 => packet.len(),
+        len => len.min(packet.len()),
+      };
+      let src: [u8; 4] = packet.get(12..16)?.try_into().ok()?;
+      let dst: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+      (
+        Ipv4Addr::from(src).into(),
+        Ipv4Addr::from(dst).into(),
+        *packet.get(9)?,
+        packet.get(header_len..total_len)?,
+      )
+    }
+    // extension headers are not supported
+    6 => {
+      let end = (40 + read_u16(packet, 4)? as usize).min(packet.len());
+      let src: [u8; 16] = packet.get(8..24)?.try_into().ok()?;
+      let dst: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+      (
+        Ipv6Addr::from(src).into(),
+        Ipv6Addr::from(dst).into(),
+        *packet.get(6)?,
+        packet.get(40..end)?,
+      )
+    }
+    _ => return None,
+  };
+
+  let src_port = read_u16(payload, 0)?;
+  let dst_port = read_u16(payload, 2)?;
+  let (protocol, seq, syn, payload) = match protocol {
+    IP_PROTOCOL_TCP => {
+      let data_offset = (*payload.get(12)? >> 4) as usize * 4;
+      let flags = *payload.get(13)?;
+      (
+        Protocol::Tcp,
+        read_u32(payload, 4)?,
+        flags & 0x02 != 0,
+        payload.get(data_offset..)?,
+      )
+    }
+    IP_PROTOCOL_UDP => (Protocol::Udp, 0, false, payload.get(8..)?),
+    _ => return None,
+  };
+
+  Some(Segment {
+    time,
+    flow: Flow {
+      protocol,
+      src: SocketAddr::new(src_ip, src_port),
+      dst: SocketAddr::new(dst_ip, dst_port),
+    },
+    seq,
+    syn,
+    payload,
+  })
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+  Some(u16::from_be_bytes(
+    data.get(pos..(pos + 2))?.try_into().ok()?,
+  ))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+  Some(u32::from_be_bytes(
+    data.get(pos..(pos + 4))?.try_into().ok()?,
+  ))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Reassembled {
+  Data(Vec<u8>),
+  /// Number of bytes that were never captured
+  Gap(u32),
+}
+
+/// Reassembles one direction of a TCP connection,
+/// retransmitted bytes are dropped and out-of-order segments are buffered
+#[derive(Debug, Default)]
+pub struct StreamAssembler {
+  next_seq: Option<u32>,
+  pending: Vec<(u32, Vec<u8>)>,
+}
+
+impl StreamAssembler {
+  pub fn push(&mut self, seq: u32, syn: bool, payload: &[u8]) -> Vec<Reassembled> {
+    // SYN takes one sequence number
+    let seq = if syn { seq.wrapping_add(1) } else { seq };
+    let mut next = *self.next_seq.get_or_insert(seq);
+    if !payload.is_empty() {
+      self.pending.push((seq, payload.to_vec()));
+    }
+
+    let mut items = vec![];
+    let mut data = vec![];
+    loop {
+      self
+        .pending
+        .retain(|(seq, bytes)| seq_offset(next, *seq) < bytes.len() as i64);
+
+      if let Some(idx) = self
+        .pending
+        .iter()
+        .position(|(seq, _)| seq_offset(next, *seq) >= 0)
+      {
+        let (seq, bytes) = self.pending.swap_remove(idx);
+        let skip = seq_offset(next, seq) as usize;
+        data.extend_from_slice(&bytes[skip..]);
+        next = next.wrapping_add((bytes.len() - skip) as u32);
+        continue;
+      }
+
+      if self.pending.len() > MAX_PENDING_SEGMENTS {
+        let resume = self
+          .pending
+          .iter()
+          .map(|(seq, _)| *seq)
+          .min_by_key(|seq| seq.wrapping_sub(next))
+          .expect("pending segments");
+        if !data.is_empty() {
+          items.push(Reassembled::Data(std::mem::take(&mut data)));
+        }
+        items.push(Reassembled::Gap(resume.wrapping_sub(next)));
+        next = resume;
+        continue;
+      }
+
+      break;
+    }
+
+    if !data.is_empty() {
+      items.push(Reassembled::Data(data));
+    }
+    self.next_seq = Some(next);
+    items
+  }
+}
+
+// positive if `seq` is before `next`
+fn seq_offset(next: u32, seq: u32) -> i64 {
+  next.wrapping_sub(seq) as i32 as i64
+}
+
+#[test]
+fn test_stream_assembler() {
+  use Reassembled::*;
+
+  let mut s = StreamAssembler::default();
+  assert_eq!(s.push(u32::MAX, true, &[]), vec![]);
+  assert_eq!(s.push(0, false, &[1, 2, 3]), vec![Data(vec![1, 2, 3])]);
+  // out of order
+  assert_eq!(s.push(5, false, &[6, 7]), vec![]);
+  // retransmission overlapping new data
+  assert_eq!(
+    s.push(1, false, &[2, 3, 4, 5]),
+    vec![Data(vec![4, 5, 6, 7])]
+  );
+  // retransmission
+  assert_eq!(s.push(3, false, &[4, 5]), vec![]);
+
+  for i in 0..=(MAX_PENDING_SEGMENTS as u32) {
+    s.push(100 + i, false, &[i as u8]);
+  }
+  assert_eq!(s.pending.len(), 0);
+  assert_eq!(s.next_seq, Some(100 + MAX_PENDING_SEGMENTS as u32 + 1));
+
+  let mut s = StreamAssembler::default();
+  s.push(10, false, &[1]);
+  for i in 0..(MAX_PENDING_SEGMENTS as u32) {
+    s.push(20 + i, false, &[2]);
+  }
+  assert_eq!(
+    s.push(1000, false, &[3]),
+    vec![Gap(9), Data(vec![2; MAX_PENDING_SEGMENTS])]
+  );
+}
+
+#[test]
+fn test_pcap_reader() {
+  let payload = [0xF7, 0x01, 0x08, 0x00, 0x01, 0x02, 0x03, 0x04];
+
+  let mut tcp = vec![0; 20];
+  tcp[0..2].copy_from_slice(&6112u16.to_be_bytes());
+  tcp[2..4].copy_from_slice(&50000u16.to_be_bytes());
+  tcp[4..8].copy_from_slice(&42u32.to_be_bytes());
+  tcp[12] = 5 << 4;
+  tcp.extend_from_slice(&payload);
+
+  let mut ip = vec![0; 20];
+  ip[0] = 0x45;
+  ip[2..4].copy_from_slice(&((20 + tcp.len()) as u16).to_be_bytes());
+  ip[9] = IP_PROTOCOL_TCP;
+  ip[12..16].copy_from_slice(&[127, 0, 0, 1]);
+  ip[16..20].copy_from_slice(&[127, 0, 0, 2]);
+  ip.extend_from_slice(&tcp);
+
+  let mut frame = vec![0; 12];
+  frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+  frame.extend_from_slice(&ip);
+
+  // magic, version 2.4, zone, sigfigs, snaplen, link type
+  let mut file = vec![];
+  for v in [MAGIC_MICROS, 0x0004_0002, 0, 0, 65535, LINKTYPE_ETHERNET] {
+    file.extend_from_slice(&v.to_le_bytes());
+  }
+  // timestamp, captured and original length
+  for v in [3u32, 500, frame.len() as u32, frame.len() as u32] {
+    file.extend_from_slice(&v.to_le_bytes());
+  }
+  file.extend_from_slice(&frame);
+
+  assert!(is_pcap(&file));
+  let mut reader = PcapReader::new(&file).unwrap();
+  let segment = reader.next_segment().unwrap().unwrap();
+  assert_eq!(segment.time, Duration::from_micros(3_000_500));
+  assert_eq!(segment.flow.protocol, Protocol::Tcp);
+  assert_eq!(segment.flow.src, "127.0.0.1:6112".parse().unwrap());
+  assert_eq!(segment.flow.dst, "127.0.0.2:50000".parse().unwrap());
+  assert!(segment.flow.has_port(6112));
+  assert_eq!(segment.seq, 42);
+  assert!(!segment.syn);
+  assert_eq!(segment.payload, &payload);
+  assert!(reader.next_segment().unwrap().is_none());
+}
@@ -0,0 +1,54 @@
+//! Opaque per-installation identifier, sent as `PacketClientConnect::client_hint`.
+//! The controller uses it to link accounts that log in from the same device.
+
+use rand::RngCore;
+use std::path::Path;
+
+const FILE_NAME: &str = ".flo_client_hint";
+
+/// Reads the hint stored in `dir`, or generates and stores a new one.
+/// Returns `None` if it can't be stored, a hint that changes every connection is useless.
+pub fn load_or_create(dir: &Path) -> Option<String> {
+  let path = dir.join(FILE_NAME);
+  if let Ok(value) = std::fs::read_to_string(&path) {
+    let value = value.trim();
+    if is_valid(value) {
+      return Some(value.to_string());
+    }
+  }
+  let value = generate();
+  match std::fs::write(&path, &value) {
+    Ok(_) => Some(value),
+    Err(err) => {
+      tracing::warn!("store client hint: {}", err);
+      None
+    }
+  }
+}
+
+fn generate() -> String {
+  let mut bytes = [0_u8; 16];
+  rand::thread_rng().fill_bytes(&mut bytes);
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn is_valid(value: &str) -> bool {
+  value.len() == 32 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[test]
+fn test_load_or_create() {
+  let dir = std::env::temp_dir().join(format!("flo_client_hint_{}", generate()));
+  std::fs::create_dir_all(&dir).unwrap();
+
+  let hint = load_or_create(&dir).unwrap();
+  assert!(is_valid(&hint));
+  assert_eq!(load_or_create(&dir), Some(hint.clone()));
+
+  std::fs::write(dir.join(FILE_NAME), "garbage").unwrap();
+  let regenerated = load_or_create(&dir).unwrap();
+  assert_ne!(regenerated, hint);
+  assert!(is_valid(&regenerated));
+
+  std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,3 +1,5 @@
+mod client_hint;
+mod revision;
 mod stream;
 #[cfg(test)]
 mod stream_test;
@@ -237,6 +239,21 @@ impl Handler<ControllerEvent> for ControllerClient {
                 )
               }
             }
+            PlayerSessionUpdateEvent::Penalty(penalty) => {
+              if let Some(current) = self.current_session.as_mut() {
+                tracing::info!(
+                  player_id = current.player.id,
+                  "player penalty updated: {:?}",
+                  penalty
+                );
+                current.penalty = penalty;
+              }
+            }
+            PlayerSessionUpdateEvent::Privacy(privacy) => {
+              if let Some(current) = self.current_session.as_mut() {
+                current.privacy = privacy;
+              }
+            }
           },
           ControllerEventData::GameInfoUpdate(event) => match event.game_info {
             Some(game_info) => {
@@ -537,6 +554,32 @@ impl Handler<UnmutePlayer> for ControllerClient {
   }
 }
 
+pub struct SendGameCommand {
+  pub game_id: i32,
+  pub command: flo_net::proto::flo_common::GameCommand,
+}
+
+impl Message for SendGameCommand {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<SendGameCommand> for ControllerClient {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    SendGameCommand { game_id, command }: SendGameCommand,
+  ) -> Result<()> {
+    let mut pkt = flo_net::proto::flo_connect::PacketGameCommandRequest {
+      game_id,
+      ..Default::default()
+    };
+    pkt.set_command(command);
+    self.send_frame(pkt.encode_as_frame()?).await?;
+    Ok(())
+  }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SetNodeAddrOverrides {
   pub overrides: Vec<SetNodeAddrOverride>,
@@ -0,0 +1,111 @@
+use crate::error::*;
+use flo_net::proto::flo_connect as proto;
+use flo_net::stream::FloStream;
+
+/// Tracks the lobby update revision of the current game.
+///
+/// The controller numbers lobby updates per game. Updates that are older than
+/// the applied game state are dropped. When an update is skipped, the full game info is requested
+/// once and updates are dropped until it arrives.
+#[derive(Debug, Default)]
+pub struct LobbyRevision {
+  game_id: Option<i32>,
+  revision: u64,
+  resync_requested: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RevisionCheck {
+  Apply,
+  Drop,
+  Resync { revision: u64 },
+}
+
+impl LobbyRevision {
+  /// Called when the full game info was received, or the player left the game
+  pub fn reset(&mut self, game_id: Option<i32>, revision: u64) {
+    self.game_id = game_id;
+    self.revision = revision;
+    self.resync_requested = false;
+  }
+
+  pub fn check(&mut self, game_id: i32, revision: u64) -> RevisionCheck {
+    // older controllers don't number updates
+    if revision == 0 || self.game_id != Some(game_id) {
+      return RevisionCheck::Apply;
+    }
+
+    if self.resync_requested {
+      return RevisionCheck::Drop;
+    }
+
+    // the game info didn't carry a revision, start from this update
+    if self.revision == 0 || revision == self.revision + 1 {
+      self.revision = revision;
+      return RevisionCheck::Apply;
+    }
+
+    if revision <= self.revision {
+      return RevisionCheck::Drop;
+    }
+
+    self.resync_requested = true;
+    RevisionCheck::Resync {
+      revision: self.revision,
+    }
+  }
+
+  /// Returns `true` if the update should be applied
+  pub async fn accept(
+    &mut self,
+    stream: &mut FloStream,
+    game_id: i32,
+    revision: u64,
+  ) -> Result<bool> {
+    match self.check(game_id, revision) {
+      RevisionCheck::Apply => Ok(true),
+      RevisionCheck::Drop => {
+        tracing::debug!(game_id, revision, "lobby update dropped");
+        Ok(false)
+      }
+      RevisionCheck::Resync {
+        revision: last_revision,
+      } => {
+        tracing::warn!(
+          game_id,
+          revision,
+          last_revision,
+          "lobby update skipped, requesting resync"
+        );
+        stream
+          .send(proto::PacketGameResyncRequest {
+            game_id,
+            revision: last_revision,
+          })
+          .await?;
+        Ok(false)
+      }
+    }
+  }
+}
+
+#[test]
+fn test_lobby_revision() {
+  let mut r = LobbyRevision::default();
+  assert_eq!(r.check(1, 5), RevisionCheck::Apply);
+
+  r.reset(Some(1), 3);
+  assert_eq!(r.check(1, 0), RevisionCheck::Apply);
+  assert_eq!(r.check(2, 9), RevisionCheck::Apply);
+  assert_eq!(r.check(1, 3), RevisionCheck::Drop);
+  assert_eq!(r.check(1, 4), RevisionCheck::Apply);
+  assert_eq!(r.check(1, 6), RevisionCheck::Resync { revision: 4 });
+  assert_eq!(r.check(1, 7), RevisionCheck::Drop);
+
+  r.reset(Some(1), 7);
+  assert_eq!(r.check(1, 8), RevisionCheck::Apply);
+
+  r.reset(Some(1), 0);
+  assert_eq!(r.check(1, 12), RevisionCheck::Apply);
+  assert_eq!(r.check(1, 13), RevisionCheck::Apply);
+}
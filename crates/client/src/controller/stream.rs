@@ -1,3 +1,4 @@
+use crate::controller::revision::LobbyRevision;
 use crate::controller::{ControllerClient, SendWs, UpdateMuteList};
 use crate::error::*;
 use crate::game::LocalGameInfo;
@@ -5,7 +6,7 @@ use crate::message::message;
 use crate::message::message::OutgoingMessage;
 use crate::node::{AddNode, GetNodePingMap, NodeRegistry, RemoveNode, UpdateNodes};
 use crate::ping::PingUpdate;
-use crate::platform::{CalcMapChecksum, GetClientPlatformInfo, Platform};
+use crate::platform::{CalcMapChecksum, GetClientPlatformInfo, Platform, WarmMapChecksum};
 use flo_net::packet::*;
 use flo_net::proto::flo_connect as proto;
 use flo_net::stream::FloStream;
@@ -86,10 +87,13 @@ impl ControllerStream {
     Ok(())
   }
 
+  #[allow(clippy::too_many_arguments)]
   async fn connect_and_serve(
     id: u64,
     domain: &str,
     token: String,
+    war3_version: String,
+    client_hint: String,
     mut frame_receiver: Receiver<Frame>,
     owner: Addr<Self>,
     parent: Addr<ControllerClient>,
@@ -106,21 +110,31 @@ impl ControllerStream {
       .send(proto::PacketClientConnect {
         connect_version: Some(crate::version::FLO_VERSION.into()),
         token,
+        client_hint,
+        war3_version,
       })
       .await?;
 
-    let reply = stream.recv_frame().await?;
-
-    let (session, nodes): (PlayerSession, _) = flo_net::try_flo_packet! {
-      reply => {
-        p: proto::PacketClientConnectAccept => {
-          (
-            PlayerSession::unpack(p.session)?,
-            p.nodes
-          )
-        }
-        p: proto::PacketClientConnectReject => {
-          return Err(Error::ConnectionRequestRejected(S2ProtoEnum::unpack_enum(p.reason())))
+    let (session, nodes): (PlayerSession, _) = loop {
+      let reply = stream.recv_frame().await?;
+      flo_net::try_flo_packet! {
+        reply => {
+          p: proto::PacketClientConnectAccept => {
+            break (
+              PlayerSession::unpack(p.session)?,
+              p.nodes
+            )
+          }
+          p: proto::PacketClientConnectReject => {
+            return Err(Error::ConnectionRequestRejected(S2ProtoEnum::unpack_enum(p.reason())))
+          }
+          // the controller is under attack and requires proof-of-work before checking the token
+          p: proto::PacketClientConnectChallenge => {
+            let solution = solve_connect_challenge(p).await?;
+            stream
+              .send(proto::PacketClientConnectChallengeResponse { solution })
+              .await?;
+          }
         }
       }
     };
@@ -151,6 +165,8 @@ impl ControllerStream {
       ))
       .await?;
 
+    let mut revision = LobbyRevision::default();
+
     loop {
       tokio::select! {
         next_send = frame_receiver.recv() => {
@@ -183,7 +199,7 @@ impl ControllerStream {
                 }
               }
 
-              match Self::handle_frame(id, player_id, frame, &mut stream, &owner, &parent, &nodes_reg).await {
+              match Self::handle_frame(id, player_id, frame, &mut stream, &mut revision, &owner, &parent, &nodes_reg).await {
                 Ok(_) => {},
                 Err(e) => {
                   tracing::error!("handle frame: {}", e);
@@ -219,11 +235,13 @@ impl ControllerStream {
   }
 
   // handle controller packets
+  #[allow(clippy::too_many_arguments)]
   async fn handle_frame(
     id: u64,
     player_id: i32,
     frame: Frame,
     stream: &mut FloStream,
+    revision: &mut LobbyRevision,
     owner: &Addr<Self>,
     parent: &Addr<ControllerClient>,
     nodes: &Addr<NodeRegistry>,
@@ -237,6 +255,9 @@ impl ControllerStream {
             })).notify(parent).await?;
         }
         p: proto::PacketGameInfo => {
+          if let Some(game) = p.game.as_ref() {
+            revision.reset(Some(game.id), game.revision);
+          }
           parent.notify(ControllerEventData::SelectNode(p.game.as_ref().and_then(|g| {
             g.node.as_ref().map(|node| node.id)
           })).wrap(id)).await?;
@@ -249,6 +270,9 @@ impl ControllerStream {
           SendWs::new(id, OutgoingMessage::CurrentGameInfo(game)).notify(parent).await?;
         }
         p: proto::PacketGamePlayerEnter => {
+          if !revision.accept(stream, p.game_id, p.revision).await? {
+            return Ok(());
+          }
           let slot_index = p.slot_index;
           let slot = p.slot.clone();
           owner.send(UpdateLocalGameInfo::new(
@@ -268,6 +292,9 @@ impl ControllerStream {
           ).notify(parent).await?;
         }
         p: proto::PacketGamePlayerLeave => {
+          if !revision.accept(stream, p.game_id, p.revision).await? {
+            return Ok(());
+          }
           owner.send(UpdateLocalGameInfo::new({
             let p = p.clone();
             move |info| -> Result<_> {
@@ -286,6 +313,9 @@ impl ControllerStream {
           ).notify(parent).await?;
         }
         p: proto::PacketGameSlotUpdate => {
+          if !revision.accept(stream, p.game_id, p.revision).await? {
+            return Ok(());
+          }
           owner.send(UpdateLocalGameInfo::new({
             let p = p.clone();
             move |info| -> Result<_> {
@@ -308,6 +338,7 @@ impl ControllerStream {
           let session = PlayerSessionUpdate::unpack(p)?;
           parent.notify(ControllerEventData::PlayerSessionUpdate(PlayerSessionUpdateEvent::Partial(session.clone())).wrap(id)).await?;
           if session.game_id.is_none() {
+            revision.reset(None, 0);
             owner.send(SetLocalGameInfo(None)).await??;
           }
           SendWs::new(
@@ -315,12 +346,31 @@ impl ControllerStream {
             OutgoingMessage::PlayerSessionUpdate(session)
           ).notify(parent).await?;
         }
+        p: proto::PacketPlayerPenaltyUpdate => {
+          let penalty = p.penalty.map(PlayerPenalty::unpack).transpose()?;
+          parent.notify(ControllerEventData::PlayerSessionUpdate(PlayerSessionUpdateEvent::Penalty(penalty.clone())).wrap(id)).await?;
+          SendWs::new(
+            id,
+            OutgoingMessage::PlayerPenaltyUpdate(penalty)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketPlayerPrivacyUpdate => {
+          let privacy = p.privacy.map(PlayerPrivacy::unpack).transpose()?;
+          parent.notify(ControllerEventData::PlayerSessionUpdate(PlayerSessionUpdateEvent::Privacy(privacy.clone())).wrap(id)).await?;
+          SendWs::new(
+            id,
+            OutgoingMessage::PlayerPrivacyUpdate(privacy)
+          ).notify(parent).await?;
+        }
         p: proto::PacketListNodes => {
           parent
             .send(UpdateNodes{ nodes: p.nodes.clone() })
             .await??;
         }
         p: proto::PacketGameSelectNode => {
+          if !revision.accept(stream, p.game_id, p.revision).await? {
+            return Ok(());
+          }
           parent.notify(ControllerEventData::SelectNode(p.node_id).wrap(id)).await?;
           owner.send(UpdateLocalGameInfo::new({
             let node_id = p.node_id;
@@ -352,6 +402,24 @@ impl ControllerStream {
             OutgoingMessage::GameStartReject(p)
           ).notify(parent).await?;
         }
+        p: proto::PacketGameStartQueued => {
+          SendWs::new(
+            id,
+            OutgoingMessage::GameStartQueued(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketGameLobbySummary => {
+          SendWs::new(
+            id,
+            OutgoingMessage::GameLobbySummary(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketWar3VersionMismatch => {
+          SendWs::new(
+            id,
+            OutgoingMessage::War3VersionMismatch(p)
+          ).notify(parent).await?;
+        }
         p: proto::PacketGameStarting => {
           let info = owner.send(GetGameStartClientInfo {
             game_id: p.game_id
@@ -390,8 +458,105 @@ impl ControllerStream {
             mute_list: p.mute_list
           }).await?;
         }
+        p: proto::PacketGameCommand => {
+          SendWs::new(
+            id,
+            OutgoingMessage::GameCommand(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketGameMetadataUpdate => {
+          if !revision.accept(stream, p.game_id, p.revision).await? {
+            return Ok(());
+          }
+          let mode = p.metadata.as_ref().map(|v| v.mode.clone()).filter(|v| !v.is_empty());
+          owner.send(UpdateLocalGameInfo::new(
+            move |info| -> Result<_> {
+              info.mode = mode;
+              Ok(())
+            }
+          )).await??;
+          SendWs::new(
+            id,
+            OutgoingMessage::GameMetadataUpdate(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketGameMapVote => {
+          SendWs::new(
+            id,
+            OutgoingMessage::GameMapVote(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketLobbyChatKey => {
+          SendWs::new(
+            id,
+            OutgoingMessage::LobbyChatKey(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketLobbyChatMessage => {
+          SendWs::new(
+            id,
+            OutgoingMessage::LobbyChatMessage(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketMaintenanceUpdate => {
+          SendWs::new(
+            id,
+            OutgoingMessage::MaintenanceUpdate(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketQuickJoinReject => {
+          SendWs::new(
+            id,
+            OutgoingMessage::QuickJoinReject(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketChatChannelJoined => {
+          SendWs::new(
+            id,
+            OutgoingMessage::ChatChannelJoined(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketChatChannelMemberUpdate => {
+          SendWs::new(
+            id,
+            OutgoingMessage::ChatChannelMemberUpdate(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketChatMessage => {
+          SendWs::new(
+            id,
+            OutgoingMessage::ChatMessage(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketChatReject => {
+          SendWs::new(
+            id,
+            OutgoingMessage::ChatReject(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketClanUpdate => {
+          SendWs::new(
+            id,
+            OutgoingMessage::ClanUpdate(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketClanInvite => {
+          SendWs::new(
+            id,
+            OutgoingMessage::ClanInvite(p)
+          ).notify(parent).await?;
+        }
+        p: proto::PacketClanReject => {
+          SendWs::new(
+            id,
+            OutgoingMessage::ClanReject(p)
+          ).notify(parent).await?;
+        }
         // client status update from node
         p: proto::PacketGameSlotClientStatusUpdate => {
+          if !revision.accept(stream, p.game_id, p.revision).await? {
+            return Ok(());
+          }
           SendWs::new(
             id,
             OutgoingMessage::GameSlotClientStatusUpdate(S2ProtoUnpack::unpack(p)?)
@@ -419,6 +584,14 @@ impl ControllerStream {
   }
 }
 
+async fn solve_connect_challenge(req: proto::PacketClientConnectChallenge) -> Result<u64> {
+  use flo_net::connect::challenge;
+  tracing::debug!(difficulty = req.difficulty, "solving connect challenge");
+  tokio::task::spawn_blocking(move || challenge::solve(&req.nonce, req.difficulty))
+    .await?
+    .ok_or_else(|| Error::ConnectionRequestRejected(RejectReason::ChallengeFailed))
+}
+
 #[async_trait]
 impl Actor for ControllerStream {
   async fn started(&mut self, ctx: &mut Context<Self>) {
@@ -454,10 +627,36 @@ impl Actor for ControllerStream {
         let owner = ctx.addr();
         let parent = self.parent.clone();
         let nodes = self.nodes.clone();
+        let platform = self.platform.clone();
         async move {
-          if let Err(err) =
-            Self::connect_and_serve(id, &domain, token, frame_rx, owner, parent.clone(), nodes)
-              .await
+          let info = platform
+            .send(GetClientPlatformInfo {
+              force_reload: false,
+            })
+            .await
+            .ok()
+            .and_then(|info| info.ok());
+          // reported to the controller so it can warn about unsupported versions early
+          let war3_version = info
+            .as_ref()
+            .map(|info| info.version.clone())
+            .unwrap_or_default();
+          let client_hint = info
+            .and_then(|info| super::client_hint::load_or_create(&info.user_data_path))
+            .unwrap_or_default();
+
+          if let Err(err) = Self::connect_and_serve(
+            id,
+            &domain,
+            token,
+            war3_version,
+            client_hint,
+            frame_rx,
+            owner,
+            parent.clone(),
+            nodes,
+          )
+          .await
           {
             tracing::error!("controller stream error: {}", err);
 
@@ -560,6 +759,20 @@ impl Handler<SetLocalGameInfo> for ControllerStream {
     SetLocalGameInfo(info): SetLocalGameInfo,
   ) -> <SetLocalGameInfo as Message>::Result {
     if let Some(info) = info {
+      let map_changed = self
+        .current_game_info
+        .as_ref()
+        .map(|current| current.map_path != info.map_path)
+        .unwrap_or(true);
+      if map_changed {
+        self
+          .platform
+          .notify(WarmMapChecksum {
+            path: info.map_path.clone(),
+          })
+          .await
+          .ok();
+      }
       self
         .parent
         .notify(ControllerEventData::SelectNode(info.node_id.clone()).wrap(self.id))
@@ -708,6 +921,8 @@ impl ControllerEventData {
 pub enum PlayerSessionUpdateEvent {
   Full(PlayerSession),
   Partial(PlayerSessionUpdate),
+  Penalty(Option<PlayerPenalty>),
+  Privacy(Option<PlayerPrivacy>),
 }
 
 #[derive(Debug)]
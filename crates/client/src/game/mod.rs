@@ -1,5 +1,6 @@
 use crate::error::{Error, Result};
 use flo_types::game::{GameInfo, PlayerInfo, Slot};
+use flo_w3gs::protocol::save::LoadGame;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
@@ -15,6 +16,10 @@ pub struct LocalGameInfo {
   pub players: HashMap<i32, PlayerInfo>,
   pub slots: Vec<Slot>,
   pub host_player: Option<PlayerInfo>,
+  /// Map mode string, see `GameMetadata.mode`
+  pub mode: Option<String>,
+  /// Save to load instead of the map
+  pub saved_game: Option<LoadGame>,
 }
 
 impl LocalGameInfo {
@@ -42,6 +47,15 @@ impl LocalGameInfo {
         .collect(),
       slots: game.slots.clone(),
       host_player: game.created_by.clone(),
+      mode: game
+        .metadata
+        .as_ref()
+        .map(|v| v.mode.clone())
+        .filter(|v| !v.is_empty()),
+      saved_game: game
+        .saved_game
+        .as_ref()
+        .map(|v| LoadGame::new(&v.name, v.magic_number)),
     })
   }
 }
@@ -52,11 +52,18 @@ pub async fn run_test_lobby(
     is_live: false,
     random_seed: 0,
     created_by: None,
+    metadata: None,
+    saved_game: None,
   };
 
   let info = LanGameInfo {
     game: Arc::new(LocalGameInfo::from_game_info(1, &game)?),
-    slot_info: crate::lan::game::slot::build_player_slot_info(1, game.random_seed, &game.slots)?,
+    slot_info: crate::lan::game::slot::build_player_slot_info(
+      1,
+      game.random_seed,
+      &game.slots,
+      None,
+    )?,
     map_checksum,
     game_settings: GameSettings {
       game_setting_flags: GameSettingFlags::SPEED_FAST
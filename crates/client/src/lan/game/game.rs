@@ -1,8 +1,9 @@
-use crate::controller::{ControllerClient, GetMuteList, MutePlayer, UnmutePlayer};
+use crate::controller::{ControllerClient, GetMuteList, MutePlayer, SendGameCommand, UnmutePlayer};
 use crate::error::*;
 use crate::lan::game::{GameEndReason, LanGameInfo};
 use crate::node::stream::NodeStreamSender;
 use crate::node::NodeInfo;
+use flo_net::proto::flo_common::GameCommand;
 use flo_net::w3gs::W3GSPacket;
 use flo_state::Addr;
 use flo_types::node::NodeGameStatus;
@@ -265,6 +266,9 @@ impl<'a> GameHandler<'a> {
           "-rtt: Print round-trip time information.".to_string(),
           "-stats: Print opponent/opponents statistics.".to_string(),
           "-stats <ID>: Print player statistics, or display a player list.".to_string(),
+          "-ff: Vote to surrender.".to_string(),
+          "-pause: Ask other players to pause the game.".to_string(),
+          "-desync: Report a desync to the server.".to_string(),
         ];
         self.send_chats_to_self(self.info.slot_info.my_slot_player_id, messages)
       }
@@ -718,6 +722,9 @@ impl<'a> GameHandler<'a> {
           }
         }
       }
+      "ff" => self.send_game_command(GameCommand::SurrenderVote),
+      "pause" => self.send_game_command(GameCommand::PauseRequest),
+      "desync" => self.send_game_command(GameCommand::DesyncReport),
       _ => {
         // unknown command treats like regular chat message
         return false;
@@ -744,6 +751,28 @@ impl<'a> GameHandler<'a> {
     tokio::spawn(async move { send_chats_to_self(&mut tx, player_id, messages).await });
   }
 
+  fn send_game_command(&self, command: GameCommand) {
+    let mut tx = self.w3gs_tx.clone();
+    let client = self.client.clone();
+    let game_id = self.info.game.game_id;
+    let my_slot_player_id = self.info.slot_info.my_slot_player_id;
+    tokio::spawn(async move {
+      let send = client
+        .send(SendGameCommand { game_id, command })
+        .await
+        .map_err(Error::from);
+      if let Err(err) = send.and_then(std::convert::identity) {
+        tracing::error!("send game command failed: {}", err);
+        send_chats_to_self(
+          &mut tx,
+          my_slot_player_id,
+          vec![format!("Command failed: {}", err)],
+        )
+        .await;
+      }
+    });
+  }
+
   fn save_mute(&self, player_id: i32, name: String, muted: bool) {
     let mut tx = self.w3gs_tx.clone();
     let client = self.client.clone();
@@ -66,6 +66,7 @@ impl LanGame {
       game.map_checksum,
     )?;
     let token = NodeConnectToken::from_vec(player_token).ok_or_else(|| Error::InvalidNodeToken)?;
+    let saved_game = game.saved_game.clone();
 
     let proxy = LanProxy::start(
       LanGameInfo {
@@ -73,6 +74,7 @@ impl LanGame {
           my_player_id,
           game.random_seed,
           &game.slots,
+          game.mode.as_deref(),
         )?,
         game,
         map_checksum,
@@ -83,6 +85,11 @@ impl LanGame {
       client.clone(),
     )
     .await?;
+    // the lobby map check still uses the map, only the advertised game points to the save
+    if let Some(load) = saved_game {
+      game_info.data.settings = load.game_settings(&game_info.data.settings);
+      game_info.data.flags = load.game_flags(game_info.data.flags);
+    }
     game_info.set_port(proxy.port());
     let scope = SpawnScope::new();
     let state = Arc::new(State {
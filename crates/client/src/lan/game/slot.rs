@@ -31,10 +31,12 @@ impl From<i32> for SelfPlayer {
   }
 }
 
+/// `mode` is encoded into the slot handicaps, see `SlotInfo::encode_hcl`
 pub fn build_player_slot_info<'a, P, S>(
   self_player: P,
   random_seed: i32,
   slots: &'a [S],
+  mode: Option<&str>,
 ) -> Result<LanSlotInfo>
 where
   P: Into<SelfPlayer>,
@@ -124,6 +126,12 @@ where
     slot.team = 24;
   };
 
+  if let Some(mode) = mode {
+    if !slot_info.encode_hcl(mode) {
+      tracing::warn!("mode string not encoded: {:?}", mode);
+    }
+  }
+
   let player_infos = occupied_slots
     .into_iter()
     .filter_map(|(i, slot)| {
@@ -5,18 +5,29 @@ use std::borrow::Cow;
 use std::str::FromStr;
 
 use flo_net::proto::flo_connect::{
-  PacketGamePlayerLeave, PacketGamePlayerPingMapSnapshot, PacketGamePlayerPingMapSnapshotRequest,
-  PacketGameSelectNode, PacketGameSelectNodeRequest, PacketGameStartReject, PacketGameStartRequest,
-  PacketGameStarting, PacketPlayerPingMapUpdate,
+  PacketChatChannelJoinRequest, PacketChatChannelJoined, PacketChatChannelLeaveRequest,
+  PacketChatChannelMemberUpdate, PacketChatMessage, PacketChatMessageSendRequest, PacketChatReject,
+  PacketClanCreateRequest, PacketClanInvite, PacketClanInviteReplyRequest, PacketClanInviteRequest,
+  PacketClanLeaveRequest, PacketClanMemberUpdateRequest, PacketClanReject, PacketClanUpdate,
+  PacketGameCommand, PacketGameCommandRequest, PacketGameLobbySummary, PacketGameMapVote,
+  PacketGameMapVoteRequest, PacketGameMapVoteStartRequest, PacketGameMetadataUpdate,
+  PacketGameMetadataUpdateRequest, PacketGamePlayerLeave, PacketGamePlayerPingMapSnapshot,
+  PacketGamePlayerPingMapSnapshotRequest, PacketGameSelectNode, PacketGameSelectNodeRequest,
+  PacketGameStartQueued, PacketGameStartReject, PacketGameStartRequest, PacketGameStarting,
+  PacketLobbyChatKey, PacketLobbyChatKeyUpdateRequest, PacketLobbyChatMessage,
+  PacketLobbyChatMessageSendRequest, PacketMaintenanceUpdate, PacketPlayerPingMapUpdate,
+  PacketPlayerPrivacyUpdateRequest, PacketQuickJoinReject, PacketQuickJoinRequest,
+  PacketWar3VersionMismatch,
 };
 
 use crate::error::{Error, Result};
+use crate::node::latency::NodeLatencyReport;
 use crate::observer::WatchGame;
 use crate::ping::PingUpdate;
 use crate::platform::{PlatformStateError, StartTestGame};
 pub use flo_types::game::{
-  DisconnectReason, MapDetail, MapForceOwned, MapPlayerOwned, PlayerSession, PlayerSessionUpdate,
-  RejectReason,
+  DisconnectReason, MapDetail, MapForceOwned, MapPlayerOwned, PlayerPenalty, PlayerPrivacy,
+  PlayerSession, PlayerSessionUpdate, RejectReason,
 };
 use flo_types::game::{GameInfo, GameStatusUpdate, PlayerInfo, Slot, SlotSettings};
 
@@ -37,6 +48,22 @@ pub enum IncomingMessage {
   SetNodeAddrOverrides(SetNodeAddrOverrides),
   ClearNodeAddrOverrides,
   WatchGame(WatchGame),
+  GameCommandRequest(PacketGameCommandRequest),
+  GameMetadataUpdateRequest(PacketGameMetadataUpdateRequest),
+  QuickJoinRequest(PacketQuickJoinRequest),
+  ChatChannelJoinRequest(PacketChatChannelJoinRequest),
+  ChatChannelLeaveRequest(PacketChatChannelLeaveRequest),
+  ChatMessageSendRequest(PacketChatMessageSendRequest),
+  ClanCreateRequest(PacketClanCreateRequest),
+  ClanInviteRequest(PacketClanInviteRequest),
+  ClanInviteReplyRequest(PacketClanInviteReplyRequest),
+  ClanLeaveRequest(PacketClanLeaveRequest),
+  ClanMemberUpdateRequest(PacketClanMemberUpdateRequest),
+  PlayerPrivacyUpdateRequest(PacketPlayerPrivacyUpdateRequest),
+  GameMapVoteStartRequest(PacketGameMapVoteStartRequest),
+  GameMapVoteRequest(PacketGameMapVoteRequest),
+  LobbyChatKeyUpdateRequest(PacketLobbyChatKeyUpdateRequest),
+  LobbyChatMessageSendRequest(PacketLobbyChatMessageSendRequest),
 }
 
 #[derive(Debug, Serialize)]
@@ -56,12 +83,17 @@ pub enum OutgoingMessage {
   GamePlayerLeave(PacketGamePlayerLeave),
   GameSlotUpdate(GameSlotUpdate),
   PlayerSessionUpdate(PlayerSessionUpdate),
+  PlayerPenaltyUpdate(Option<PlayerPenalty>),
+  PlayerPrivacyUpdate(Option<PlayerPrivacy>),
   ListNodes(NodeList),
   PingUpdate(PingUpdate),
   GameSelectNode(PacketGameSelectNode),
   PlayerPingMapUpdate(PacketPlayerPingMapUpdate),
   GamePlayerPingMapSnapshot(PacketGamePlayerPingMapSnapshot),
   GameStartReject(PacketGameStartReject),
+  GameStartQueued(PacketGameStartQueued),
+  GameLobbySummary(PacketGameLobbySummary),
+  War3VersionMismatch(PacketWar3VersionMismatch),
   GameStarting(PacketGameStarting),
   GameStarted(GameStarted),
   GameStartError(ErrorMessage),
@@ -69,6 +101,22 @@ pub enum OutgoingMessage {
   GameStatusUpdate(GameStatusUpdate),
   GameDisconnect,
   SetNodeAddrOverridesError(ErrorMessage),
+  GameCommand(PacketGameCommand),
+  GameMetadataUpdate(PacketGameMetadataUpdate),
+  MaintenanceUpdate(PacketMaintenanceUpdate),
+  QuickJoinReject(PacketQuickJoinReject),
+  /// The quick join request was not sent, no node meets its max ping
+  NodeLatencyTestFailed(NodeLatencyReport),
+  ChatChannelJoined(PacketChatChannelJoined),
+  ChatChannelMemberUpdate(PacketChatChannelMemberUpdate),
+  ChatMessage(PacketChatMessage),
+  ChatReject(PacketChatReject),
+  ClanUpdate(PacketClanUpdate),
+  ClanInvite(PacketClanInvite),
+  ClanReject(PacketClanReject),
+  GameMapVote(PacketGameMapVote),
+  LobbyChatKey(PacketLobbyChatKey),
+  LobbyChatMessage(PacketLobbyChatMessage),
 }
 
 impl FromStr for IncomingMessage {
@@ -9,6 +9,7 @@ pub(crate) use ws::{WsMessageListener as MessageListener, WsMessageStream as Mes
 
 use crate::controller::{ControllerClient, ReplaceSession};
 use crate::error::*;
+use crate::node::NodeRegistry;
 use crate::observer::ObserverClient;
 use crate::platform::Platform;
 use crate::StartConfig;
@@ -20,6 +21,7 @@ pub struct Listener {
   platform: Addr<Platform>,
   controller_client: Addr<ControllerClient>,
   observer_client: Addr<ObserverClient>,
+  nodes: Addr<NodeRegistry>,
   listener: Option<MessageListener>,
   port: u16,
 }
@@ -37,6 +39,7 @@ impl Actor for Listener {
       platform: self.platform.clone(),
       controller_client: self.controller_client.clone(),
       observer_client: self.observer_client.clone(),
+      nodes: self.nodes.clone(),
     };
     ctx.spawn(
       {
@@ -64,6 +67,7 @@ impl Service<StartConfig> for Listener {
     let platform = registry.resolve().await?;
     let controller_client = registry.resolve().await?;
     let observer_client = registry.resolve().await?;
+    let nodes = registry.resolve().await?;
 
     let listener = MessageListener::bind(registry).await?;
     let port = listener.port();
@@ -72,6 +76,7 @@ impl Service<StartConfig> for Listener {
       platform,
       controller_client,
       observer_client,
+      nodes,
       listener: listener.into(),
       port,
     })
@@ -82,6 +87,7 @@ struct Worker {
   platform: Addr<Platform>,
   controller_client: Addr<ControllerClient>,
   observer_client: Addr<ObserverClient>,
+  nodes: Addr<NodeRegistry>,
 }
 
 impl Worker {
@@ -98,6 +104,7 @@ impl Worker {
         self.platform.clone(),
         self.controller_client.clone(),
         self.observer_client.clone(),
+        self.nodes.clone(),
         stream,
       );
 
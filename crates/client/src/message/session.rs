@@ -7,6 +7,7 @@ use crate::controller::{
 };
 use crate::error::{Error, Result};
 use crate::message::MessageStream;
+use crate::node::{latency, NodeRegistry};
 use crate::observer::ObserverClient;
 use crate::platform::{
   GetClientPlatformInfo, GetMapDetail, GetMapList, KillTestGame, Platform, PlatformStateError,
@@ -14,8 +15,13 @@ use crate::platform::{
 };
 use flo_net::packet::FloPacket;
 use flo_net::proto::flo_connect::{
+  PacketChatChannelJoinRequest, PacketChatChannelLeaveRequest, PacketChatMessageSendRequest,
+  PacketClanCreateRequest, PacketClanInviteReplyRequest, PacketClanInviteRequest,
+  PacketClanLeaveRequest, PacketClanMemberUpdateRequest, PacketGameCommandRequest, PacketGameMapVoteRequest,
+  PacketGameMapVoteStartRequest, PacketGameMetadataUpdateRequest,
   PacketGamePlayerPingMapSnapshotRequest, PacketGameSlotUpdateRequest, PacketGameStartRequest,
-  PacketListNodesRequest,
+  PacketListNodesRequest, PacketLobbyChatKeyUpdateRequest, PacketLobbyChatMessageSendRequest,
+  PacketPlayerPrivacyUpdateRequest, PacketQuickJoinRequest,
 };
 use flo_platform::ClientPlatformInfo;
 use flo_state::Addr;
@@ -36,11 +42,17 @@ impl Session {
     platform: Addr<Platform>,
     controller_client: Addr<ControllerClient>,
     observer_client: Addr<ObserverClient>,
+    nodes: Addr<NodeRegistry>,
     stream: MessageStream,
   ) -> Self {
     let (tx, rx) = channel(3);
     let scope = SpawnScope::new();
-    let serve_state = Arc::new(Worker { platform, controller_client, observer_client });
+    let serve_state = Arc::new(Worker {
+      platform,
+      controller_client,
+      observer_client,
+      nodes,
+    });
     tokio::spawn(
       {
         let scope = scope.handle();
@@ -125,6 +137,7 @@ struct Worker {
   platform: Addr<Platform>,
   controller_client: Addr<ControllerClient>,
   observer_client: Addr<ObserverClient>,
+  nodes: Addr<NodeRegistry>,
 }
 
 impl Worker {
@@ -198,6 +211,70 @@ impl Worker {
       IncomingMessage::WatchGame(msg) => {
         self.observer_client.send(msg).await??;
       },
+      IncomingMessage::GameCommandRequest(req) => {
+        self.send_frame::<PacketGameCommandRequest>(req).await?;
+      }
+      IncomingMessage::GameMetadataUpdateRequest(req) => {
+        self.send_frame::<PacketGameMetadataUpdateRequest>(req).await?;
+      }
+      IncomingMessage::QuickJoinRequest(req) => {
+        if let Some(max_ping) = req.filters.as_ref().and_then(|f| f.max_ping) {
+          self.spawn_quick_join_pre_test(reply_sender.clone(), req, max_ping);
+        } else {
+          self.send_frame::<PacketQuickJoinRequest>(req).await?;
+        }
+      }
+      IncomingMessage::ChatChannelJoinRequest(req) => {
+        self.send_frame::<PacketChatChannelJoinRequest>(req).await?;
+      }
+      IncomingMessage::ChatChannelLeaveRequest(req) => {
+        self
+          .send_frame::<PacketChatChannelLeaveRequest>(req)
+          .await?;
+      }
+      IncomingMessage::ChatMessageSendRequest(req) => {
+        self.send_frame::<PacketChatMessageSendRequest>(req).await?;
+      }
+      IncomingMessage::ClanCreateRequest(req) => {
+        self.send_frame::<PacketClanCreateRequest>(req).await?;
+      }
+      IncomingMessage::ClanInviteRequest(req) => {
+        self.send_frame::<PacketClanInviteRequest>(req).await?;
+      }
+      IncomingMessage::ClanInviteReplyRequest(req) => {
+        self.send_frame::<PacketClanInviteReplyRequest>(req).await?;
+      }
+      IncomingMessage::ClanLeaveRequest(req) => {
+        self.send_frame::<PacketClanLeaveRequest>(req).await?;
+      }
+      IncomingMessage::ClanMemberUpdateRequest(req) => {
+        self
+          .send_frame::<PacketClanMemberUpdateRequest>(req)
+          .await?;
+      }
+      IncomingMessage::PlayerPrivacyUpdateRequest(req) => {
+        self
+          .send_frame::<PacketPlayerPrivacyUpdateRequest>(req)
+          .await?;
+      }
+      IncomingMessage::GameMapVoteStartRequest(req) => {
+        self
+          .send_frame::<PacketGameMapVoteStartRequest>(req)
+          .await?;
+      }
+      IncomingMessage::GameMapVoteRequest(req) => {
+        self.send_frame::<PacketGameMapVoteRequest>(req).await?;
+      }
+      IncomingMessage::LobbyChatKeyUpdateRequest(req) => {
+        self
+          .send_frame::<PacketLobbyChatKeyUpdateRequest>(req)
+          .await?;
+      }
+      IncomingMessage::LobbyChatMessageSendRequest(req) => {
+        self
+          .send_frame::<PacketLobbyChatMessageSendRequest>(req)
+          .await?;
+      }
     }
     Ok(())
   }
@@ -268,6 +345,40 @@ impl Worker {
     Ok(())
   }
 
+  // Pinging the nodes takes up to a few seconds, the session keeps serving meanwhile
+  fn spawn_quick_join_pre_test(
+    &self,
+    sender: Sender<OutgoingMessage>,
+    req: PacketQuickJoinRequest,
+    max_ping: u32,
+  ) {
+    let nodes = self.nodes.clone();
+    let controller_client = self.controller_client.clone();
+    tokio::spawn(
+      async move {
+        let res = async {
+          let report = latency::pre_test(&nodes, max_ping).await?;
+          if report.passed() {
+            controller_client
+              .send(SendFrame(req.encode_as_frame()?))
+              .await??;
+          } else {
+            tracing::info!(max_ping, "quick join refused: no node within max ping");
+            sender
+              .send(OutgoingMessage::NodeLatencyTestFailed(report))
+              .await?;
+          }
+          Ok::<_, Error>(())
+        }
+        .await;
+        if let Err(err) = res {
+          tracing::error!("quick join pre-test: {}", err);
+        }
+      }
+      .instrument(tracing::debug_span!("quick_join_pre_test")),
+    );
+  }
+
   async fn send_frame<T: FloPacket>(&self, pkt: T) -> Result<()> {
     self
       .controller_client
@@ -0,0 +1,122 @@
+//! Latency pre-test of the nodes a game could be hosted on.
+//!
+//! Before queueing with a max ping, every node is pinged again and the request is refused
+//! locally if none of them is within the limit, the controller could only find lobbies
+//! the player can't play on.
+
+use super::registry::{MeasureNodes, NodeRegistry};
+use crate::error::*;
+use flo_state::Addr;
+use flo_types::ping::PingStats;
+use futures::future::join_all;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct NodeLatencyReport {
+  pub max_ping: u32,
+  pub nodes: Vec<NodeLatency>,
+}
+
+impl NodeLatencyReport {
+  /// At least one node meets the max ping
+  pub fn passed(&self) -> bool {
+    self.nodes.iter().any(|node| node.failure.is_none())
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeLatency {
+  pub node_id: i32,
+  pub name: String,
+  /// Average RTT of the batch
+  pub ping: Option<u32>,
+  /// Spread between the fastest and slowest reply of the batch
+  pub jitter: Option<u32>,
+  pub loss_rate: f32,
+  pub failure: Option<NodeLatencyFailure>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum NodeLatencyFailure {
+  /// No reply to the batch
+  Unreachable,
+  /// Average RTT above the max ping
+  MaxPingExceeded,
+}
+
+/// Pings all known nodes and checks them against `max_ping`
+pub async fn pre_test(nodes: &Addr<NodeRegistry>, max_ping: u32) -> Result<NodeLatencyReport> {
+  let measures = nodes.send(MeasureNodes).await??;
+  let nodes = join_all(measures.into_iter().map(|(node, rx)| async move {
+    let stats = match rx {
+      Some(rx) => rx.await.ok(),
+      None => None,
+    };
+    check_node(node.id, node.name, max_ping, stats)
+  }))
+  .await;
+  Ok(NodeLatencyReport { max_ping, nodes })
+}
+
+fn check_node(node_id: i32, name: String, max_ping: u32, stats: Option<PingStats>) -> NodeLatency {
+  let stats = stats.unwrap_or(PingStats {
+    loss_rate: 1.0,
+    ..Default::default()
+  });
+  let (ping, jitter) = if stats.loss_rate < 1.0 {
+    let jitter = match (stats.min, stats.max) {
+      (Some(min), Some(max)) => Some(max.saturating_sub(min)),
+      _ => None,
+    };
+    (stats.avg, jitter)
+  } else {
+    (None, None)
+  };
+  let failure = match ping {
+    None => Some(NodeLatencyFailure::Unreachable),
+    Some(ping) if ping > max_ping => Some(NodeLatencyFailure::MaxPingExceeded),
+    Some(_) => None,
+  };
+  NodeLatency {
+    node_id,
+    name,
+    ping,
+    jitter,
+    loss_rate: stats.loss_rate,
+    failure,
+  }
+}
+
+#[test]
+fn test_check_node() {
+  let stats = |min, avg, max, loss_rate| PingStats {
+    min: Some(min),
+    max: Some(max),
+    avg: Some(avg),
+    current: Some(avg),
+    loss_rate,
+  };
+
+  let node = check_node(1, "a".to_string(), 100, Some(stats(40, 50, 70, 0.0)));
+  assert_eq!(node.failure, None);
+  assert_eq!(node.ping, Some(50));
+  assert_eq!(node.jitter, Some(30));
+
+  let node = check_node(2, "b".to_string(), 100, Some(stats(150, 160, 170, 0.0)));
+  assert_eq!(node.failure, Some(NodeLatencyFailure::MaxPingExceeded));
+
+  // timed out batches keep the previous min and max
+  let node = check_node(3, "c".to_string(), 100, Some(stats(40, 50, 70, 1.0)));
+  assert_eq!(node.failure, Some(NodeLatencyFailure::Unreachable));
+  assert_eq!(node.ping, None);
+
+  let node = check_node(4, "d".to_string(), 100, None);
+  assert_eq!(node.failure, Some(NodeLatencyFailure::Unreachable));
+  assert_eq!(node.loss_rate, 1.0);
+
+  let report = NodeLatencyReport {
+    max_ping: 100,
+    nodes: vec![node],
+  };
+  assert!(!report.passed());
+}
@@ -1,6 +1,7 @@
+pub mod latency;
 mod registry;
 pub mod stream;
 pub use registry::{
-  AddNode, ClearNodeAddrOverrides, GetNode, GetNodePingMap, NodeInfo, NodeRegistry, RemoveNode,
-  SetActiveNode, SetNodeAddrOverrides, UpdateAddressesAndGetNodePingMap, UpdateNodes,
+  AddNode, ClearNodeAddrOverrides, GetNode, GetNodePingMap, MeasureNodes, NodeInfo, NodeRegistry,
+  RemoveNode, SetActiveNode, SetNodeAddrOverrides, UpdateAddressesAndGetNodePingMap, UpdateNodes,
 };
@@ -1,6 +1,7 @@
 use crate::error::*;
 use crate::ping::{
-  AddAddress, GetPingMap, PingActor, RemoveAddress, SetActiveAddress, UpdateAddresses,
+  AddAddress, GetPingMap, MeasureAddresses, PingActor, RemoveAddress, SetActiveAddress,
+  UpdateAddresses,
 };
 use crate::StartConfig;
 use flo_net::proto::flo_connect::Node;
@@ -9,6 +10,7 @@ use flo_types::ping::PingStats;
 use serde::Serialize;
 use std::collections::BTreeMap;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use tokio::sync::oneshot;
 
 pub struct NodeRegistry {
   map: BTreeMap<i32, NodeInfo>,
@@ -221,6 +223,48 @@ impl Handler<GetNodePingMap> for NodeRegistry {
   }
 }
 
+/// Pings all nodes now, a node has no receiver if it has no ping collector
+pub struct MeasureNodes;
+impl Message for MeasureNodes {
+  type Result = Result<Vec<(NodeInfo, Option<oneshot::Receiver<PingStats>>)>>;
+}
+
+#[async_trait]
+impl Handler<MeasureNodes> for NodeRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    _: MeasureNodes,
+  ) -> <MeasureNodes as Message>::Result {
+    let nodes: Vec<_> = self
+      .map
+      .values()
+      .map(|node| {
+        let addr = self
+          .addr_overrides
+          .get(&node.id)
+          .cloned()
+          .unwrap_or_else(|| node.socket_addr.clone());
+        (node.clone(), addr)
+      })
+      .collect();
+    let mut receivers: BTreeMap<_, _> = self
+      .ping
+      .send(MeasureAddresses {
+        addresses: nodes.iter().map(|(_, addr)| *addr).collect(),
+      })
+      .await??
+      .into_iter()
+      .collect();
+    Ok(
+      nodes
+        .into_iter()
+        .map(|(node, addr)| (node, receivers.remove(&addr)))
+        .collect(),
+    )
+  }
+}
+
 pub struct UpdateAddressesAndGetNodePingMap(pub UpdateNodes);
 impl Message for UpdateAddressesAndGetNodePingMap {
   type Result = Result<BTreeMap<i32, PingStats>>;
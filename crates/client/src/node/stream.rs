@@ -302,6 +302,7 @@ impl Session {
       .send(proto::PacketClientConnect {
         version: Some(crate::version::FLO_VERSION.into()),
         token: self.token.to_vec(),
+        game_id: self.game_id,
         ..Default::default()
       })
       .await?;
@@ -380,6 +381,7 @@ impl Session {
         token: self.token.to_vec(),
         retry_shutdown: true,
         leave_reason,
+        game_id: self.game_id,
       })
       .await?;
 
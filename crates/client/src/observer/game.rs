@@ -124,6 +124,8 @@ where
       SelfPlayer::StreamObserver,
       self.info.random_seed,
       &self.info.slots,
+      // observer game info has no mode string yet
+      None,
     )?;
 
     let mut stream: W3GSStream = loop {
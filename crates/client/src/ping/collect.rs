@@ -8,6 +8,7 @@ use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::sync::mpsc::error::SendTimeoutError;
 use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
 use tokio::time::sleep;
 
 const PACKETS: usize = 3;
@@ -28,6 +29,8 @@ pub struct PingCollectActor {
   abort_timeout: Option<AbortHandle>,
   stats: PingStats,
   active: bool,
+  in_flight: bool,
+  measure_waiters: Vec<oneshot::Sender<PingStats>>,
 }
 
 impl PingCollectActor {
@@ -44,6 +47,8 @@ impl PingCollectActor {
       abort_timeout: None,
       stats: PingStats::default(),
       active: false,
+      in_flight: false,
+      measure_waiters: vec![],
     }
   }
 
@@ -128,6 +133,7 @@ impl PingCollectActor {
     self.results = [None; PACKETS];
     self.batch_id = self.batch_id.wrapping_add(1);
     self.current = None;
+    self.in_flight = true;
 
     let (timeout, abort) = abortable({
       let addr = ctx.addr();
@@ -159,6 +165,13 @@ impl PingCollectActor {
     });
   }
 
+  fn finish_batch(&mut self) {
+    self.in_flight = false;
+    for tx in self.measure_waiters.drain(..) {
+      tx.send(self.stats.clone()).ok();
+    }
+  }
+
   fn address_str(&self) -> &str {
     self.sock_addr_string.as_str()
   }
@@ -183,7 +196,10 @@ impl Handler<PingStart> for PingCollectActor {
     ctx: &mut Context<Self>,
     _: PingStart,
   ) -> <PingStart as Message>::Result {
-    self.start_ping(ctx);
+    // a measurement started a batch early
+    if !self.in_flight {
+      self.start_ping(ctx);
+    }
   }
 }
 
@@ -224,6 +240,7 @@ impl Handler<PingCollectTimeout> for PingCollectActor {
       loss_rate: 1.0,
       ..self.stats
     };
+    self.finish_batch();
     self.schedule_next(ctx, ERROR_DELAY)
   }
 }
@@ -285,6 +302,7 @@ impl Handler<PingReply> for PingCollectActor {
           current: finished.current,
           loss_rate: finished.loss_rate,
         };
+        self.finish_batch();
         self.schedule_next(ctx, self.interval());
       }
     } else {
@@ -316,6 +334,7 @@ impl Handler<PingError> for PingCollectActor {
     tracing::debug!(address = self.address_str(), "ping error: {}", message);
     self.stats.loss_rate = 1.0;
     self.stats.current = None;
+    self.finish_batch();
     self.schedule_next(ctx, ERROR_DELAY);
   }
 }
@@ -337,6 +356,26 @@ impl Handler<GetPingStats> for PingCollectActor {
   }
 }
 
+/// Requests stats of a fresh batch, sent now unless one is in flight.
+/// Resolves when the batch completes or times out
+pub struct Measure;
+
+impl Message for Measure {
+  type Result = oneshot::Receiver<PingStats>;
+}
+
+#[async_trait]
+impl Handler<Measure> for PingCollectActor {
+  async fn handle(&mut self, ctx: &mut Context<Self>, _: Measure) -> <Measure as Message>::Result {
+    let (tx, rx) = oneshot::channel();
+    self.measure_waiters.push(tx);
+    if !self.in_flight {
+      self.start_ping(ctx);
+    }
+    rx
+  }
+}
+
 pub struct SetActive {
   pub active: bool,
 }
@@ -1,5 +1,5 @@
 use crate::error::Result;
-use crate::ping::collect::{GetPingStats, PingCollectActor, PingReply, SetActive};
+use crate::ping::collect::{GetPingStats, Measure, PingCollectActor, PingReply, SetActive};
 use flo_state::{async_trait, Actor, Addr, Context, Handler, Message, Owner};
 use flo_types::ping::PingStats;
 use flo_util::binary::Ipv4Addr;
@@ -10,7 +10,7 @@ use std::net::SocketAddr;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::net::UdpSocket;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::sleep;
 
 mod collect;
@@ -233,6 +233,33 @@ impl Handler<GetAddressPing> for PingActor {
   }
 }
 
+/// Starts a ping batch to each known address, the receivers resolve with the results.
+/// Unknown addresses are skipped
+pub struct MeasureAddresses {
+  pub addresses: Vec<SocketAddr>,
+}
+
+impl Message for MeasureAddresses {
+  type Result = Result<Vec<(SocketAddr, oneshot::Receiver<PingStats>)>>;
+}
+
+#[async_trait]
+impl Handler<MeasureAddresses> for PingActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    MeasureAddresses { addresses }: MeasureAddresses,
+  ) -> <MeasureAddresses as Message>::Result {
+    let mut receivers = Vec::with_capacity(addresses.len());
+    for address in addresses {
+      if let Some(v) = self.map.get(&address) {
+        receivers.push((address, v.send(Measure).await?));
+      }
+    }
+    Ok(receivers)
+  }
+}
+
 pub struct SetActiveAddress {
   pub address: Option<SocketAddr>,
 }
@@ -4,13 +4,15 @@ use flo_config::ClientConfig;
 use flo_platform::error::Error as PlatformError;
 use flo_platform::ClientPlatformInfo;
 use flo_state::{async_trait, Actor, Context, Handler, Message, RegistryRef, Service};
-use flo_types::game::{MapDetail, MapForceOwned, MapPlayerOwned};
+use flo_types::game::{MapDetail, MapForceOwned, MapPlayerOwned, MapTextSegment};
 use flo_w3map::{MapChecksum, W3Map};
 use flo_w3storage::W3Storage;
 use futures::future::{abortable, AbortHandle};
 use futures::FutureExt;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime};
 
 #[derive(Debug)]
 pub struct Platform {
@@ -19,6 +21,7 @@ pub struct Platform {
   info: Result<ClientPlatformInfo, PlatformStateError>,
   storage: Option<W3Storage>,
   maps: Option<Value>,
+  map_checksums: MapChecksumCache,
   test_game_abort_handle: Option<AbortHandle>,
 }
 
@@ -31,6 +34,7 @@ impl Platform {
       info,
       storage: None,
       maps: None,
+      map_checksums: MapChecksumCache::default(),
       test_game_abort_handle: None,
     })
   }
@@ -67,6 +71,7 @@ impl Handler<Reload> for Platform {
     self.config = config;
     self.info = info;
     self.maps.take();
+    self.map_checksums.clear();
     Ok(())
   }
 }
@@ -146,9 +151,36 @@ impl Handler<CalcMapChecksum> for Platform {
     _: &mut Context<Self>,
     CalcMapChecksum { path }: CalcMapChecksum,
   ) -> <CalcMapChecksum as Message>::Result {
-    self
-      .with_storage(|storage| flo_w3map::W3Map::calc_checksum(storage, &path).map_err(Into::into))
-      .await
+    self.calc_map_checksum(&path).await
+  }
+}
+
+/// Computes and caches the checksum of a lobby's map ahead of the game start
+pub struct WarmMapChecksum {
+  pub path: String,
+}
+
+impl Message for WarmMapChecksum {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<WarmMapChecksum> for Platform {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    WarmMapChecksum { path }: WarmMapChecksum,
+  ) -> <WarmMapChecksum as Message>::Result {
+    let started = Instant::now();
+    match self.calc_map_checksum(&path).await {
+      Ok(checksum) => tracing::debug!(
+        "map checksum warmed: {}: {} bytes in {:?}",
+        path,
+        checksum.file_size,
+        started.elapsed()
+      ),
+      Err(err) => tracing::warn!("warm map checksum: {}: {}", path, err),
+    }
   }
 }
 
@@ -218,11 +250,21 @@ impl Handler<GetMapDetail> for Platform {
       .with_storage(move |storage| {
         let (map, checksum) = W3Map::open_storage_with_checksum(storage, &path)?;
         let (width, height) = map.dimension();
+        let name_text = map.name_text();
         Ok(MapDetail {
           path,
           sha1: checksum.get_sha1_hex_string(),
           crc32: checksum.crc32,
           name: map.name().to_string(),
+          plain_name: name_text.plain_text(),
+          name_segments: name_text
+            .segments()
+            .iter()
+            .map(|s| MapTextSegment {
+              text: s.text.clone(),
+              color: s.color.map(|c| c.to_hex_rgb()),
+            })
+            .collect(),
           author: map.author().to_string(),
           description: map.description().to_string(),
           width,
@@ -300,6 +342,15 @@ impl Handler<KillTestGame> for Platform {
 }
 
 impl Platform {
+  async fn calc_map_checksum(&mut self, path: &str) -> Result<MapChecksum> {
+    let mut cache = std::mem::take(&mut self.map_checksums);
+    let res = self
+      .with_storage(|storage| cache.get_or_calc(storage, path))
+      .await;
+    self.map_checksums = cache;
+    res
+  }
+
   pub async fn with_storage<F, R>(&mut self, f: F) -> Result<R>
   where
     F: FnOnce(&W3Storage) -> Result<R> + Send,
@@ -356,6 +407,66 @@ impl Platform {
   }
 }
 
+/// Checksums of recently used maps, keyed by path.
+/// Entries are recomputed if the file size or modification time changed.
+#[derive(Debug, Default)]
+struct MapChecksumCache {
+  entries: HashMap<String, CachedMapChecksum>,
+}
+
+#[derive(Debug)]
+struct CachedMapChecksum {
+  size: u64,
+  modified: Option<SystemTime>,
+  checksum: MapChecksum,
+  used_at: Instant,
+}
+
+impl MapChecksumCache {
+  const MAX_ENTRIES: usize = 16;
+
+  fn get_or_calc(&mut self, storage: &W3Storage, path: &str) -> Result<MapChecksum> {
+    let file = storage
+      .resolve_file(path)?
+      .ok_or_else(|| flo_w3map::error::Error::StorageFileNotFound(path.to_string()))?;
+    let size = file.size();
+    let modified = file.modified();
+
+    if let Some(entry) = self.entries.get_mut(path) {
+      if entry.size == size && entry.modified == modified {
+        entry.used_at = Instant::now();
+        return Ok(entry.checksum.clone());
+      }
+    }
+
+    let checksum = W3Map::calc_file_checksum(&file)?;
+    if self.entries.len() >= Self::MAX_ENTRIES && !self.entries.contains_key(path) {
+      let lru = self
+        .entries
+        .iter()
+        .min_by_key(|(_, entry)| entry.used_at)
+        .map(|(path, _)| path.clone());
+      if let Some(lru) = lru {
+        self.entries.remove(&lru);
+      }
+    }
+    self.entries.insert(
+      path.to_string(),
+      CachedMapChecksum {
+        size,
+        modified,
+        checksum: checksum.clone(),
+        used_at: Instant::now(),
+      },
+    );
+    Ok(checksum)
+  }
+
+  fn clear(&mut self) {
+    self.entries.clear();
+  }
+}
+
 async fn load(
   start_config: &StartConfig,
 ) -> (ClientConfig, Result<ClientPlatformInfo, PlatformStateError>) {
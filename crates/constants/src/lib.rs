@@ -5,6 +5,8 @@ pub const CONTROLLER_HOST: &str = "service.w3flo.com";
 pub const STATS_HOST: &str = "stats.w3flo.com";
 pub const CONTROLLER_GRPC_PORT: u16 = 3549;
 pub const CONTROLLER_SOCKET_PORT: u16 = 3550;
+pub const CONTROLLER_HTTP_PORT: u16 = 3559;
+pub const CONTROLLER_REST_PORT: u16 = 3560;
 pub const CLIENT_WS_PORT: u16 = 3551;
 pub const CLIENT_ORIGINS: &[&str] = &[
   "http://localhost:3000",
@@ -30,3 +32,6 @@ pub const OBSERVER_GRPC_PORT: u16 = 3556;
 pub const OBSERVER_SOCKET_PORT: u16 = 3557;
 pub const OBSERVER_GRAPHQL_PORT: u16 = 3558;
 pub const OBSERVER_FAST_FORWARDING_SPEED: f64 = 3.;
+/// `GetGame` reply metadata, comma separated ids of the players with these privacy settings
+pub const GRPC_METADATA_ANONYMIZED_PLAYERS: &str = "x-flo-anonymized-players";
+pub const GRPC_METADATA_HIDDEN_PLAYERS: &str = "x-flo-hidden-players";
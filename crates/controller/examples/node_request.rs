@@ -19,6 +19,7 @@ async fn main() -> Result<()> {
       secret: "".to_string(),
     },
     game_registry_mock.addr(),
+    Default::default(),
   )
   .start();
 
@@ -0,0 +1,171 @@
+use crate::chat::{ChatChannel, ChatChannelKind, ChatMessage};
+use crate::db::DbConn;
+use crate::error::*;
+use crate::player::{PlayerBanType, PlayerRef};
+use crate::schema::{
+  chat_channel, chat_channel_member, chat_message, player, player_ban, player_mute,
+};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+
+pub fn find_channel(conn: &DbConn, kind: ChatChannelKind, key: &str) -> Result<ChatChannel> {
+  chat_channel::table
+    .select(ChatChannel::COLUMNS)
+    .filter(chat_channel::kind.eq(kind).and(chat_channel::key.eq(key)))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::ChatChannelNotFound)
+}
+
+/// Channels the player is a member of
+pub fn get_player_channels(conn: &DbConn, player_id: i32) -> Result<Vec<ChatChannel>> {
+  chat_channel_member::table
+    .inner_join(chat_channel::table)
+    .select(ChatChannel::COLUMNS)
+    .filter(chat_channel_member::player_id.eq(player_id))
+    .order(chat_channel::id)
+    .load(conn)
+    .map_err(Into::into)
+}
+
+pub fn is_member(conn: &DbConn, channel_id: i32, player_id: i32) -> Result<bool> {
+  use diesel::dsl::{exists, select};
+  select(exists(
+    chat_channel_member::table.filter(
+      chat_channel_member::channel_id
+        .eq(channel_id)
+        .and(chat_channel_member::player_id.eq(player_id)),
+    ),
+  ))
+  .get_result(conn)
+  .map_err(Into::into)
+}
+
+pub fn add_member(conn: &DbConn, channel_id: i32, player_id: i32) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "chat_channel_member"]
+  struct Insert {
+    channel_id: i32,
+    player_id: i32,
+  }
+
+  diesel::insert_into(chat_channel_member::table)
+    .values(&Insert {
+      channel_id,
+      player_id,
+    })
+    .on_conflict((
+      chat_channel_member::channel_id,
+      chat_channel_member::player_id,
+    ))
+    .do_nothing()
+    .execute(conn)?;
+
+  Ok(())
+}
+
+pub fn remove_member(conn: &DbConn, channel_id: i32, player_id: i32) -> Result<()> {
+  diesel::delete(
+    chat_channel_member::table.filter(
+      chat_channel_member::channel_id
+        .eq(channel_id)
+        .and(chat_channel_member::player_id.eq(player_id)),
+    ),
+  )
+  .execute(conn)?;
+
+  Ok(())
+}
+
+/// Latest messages of a channel, oldest first
+pub fn get_history(conn: &DbConn, channel_id: i32, take: i64) -> Result<Vec<ChatMessage>> {
+  type Row = (i64, i32, PlayerRef, String, DateTime<Utc>);
+  let mut rows: Vec<Row> = chat_message::table
+    .inner_join(player::table)
+    .select((
+      chat_message::id,
+      chat_message::channel_id,
+      PlayerRef::COLUMNS,
+      chat_message::content,
+      chat_message::created_at,
+    ))
+    .filter(chat_message::channel_id.eq(channel_id))
+    .order(chat_message::id.desc())
+    .limit(take)
+    .load(conn)?;
+  rows.reverse();
+  Ok(
+    rows
+      .into_iter()
+      .map(
+        |(id, channel_id, player, content, created_at)| ChatMessage {
+          id,
+          channel_id,
+          player,
+          content,
+          created_at,
+        },
+      )
+      .collect(),
+  )
+}
+
+pub fn add_message(
+  conn: &DbConn,
+  channel_id: i32,
+  player: PlayerRef,
+  content: String,
+) -> Result<ChatMessage> {
+  #[derive(Insertable)]
+  #[table_name = "chat_message"]
+  struct Insert<'a> {
+    channel_id: i32,
+    player_id: i32,
+    content: &'a str,
+  }
+
+  let (id, created_at) = diesel::insert_into(chat_message::table)
+    .values(&Insert {
+      channel_id,
+      player_id: player.id,
+      content: &content,
+    })
+    .returning((chat_message::id, chat_message::created_at))
+    .get_result::<(i64, DateTime<Utc>)>(conn)?;
+
+  Ok(ChatMessage {
+    id,
+    channel_id,
+    player,
+    content,
+    created_at,
+  })
+}
+
+/// Returns whether the player has an active chat ban
+pub fn is_chat_banned(conn: &DbConn, player_id: i32) -> Result<bool> {
+  use diesel::dsl::{exists, select};
+  select(exists(
+    player_ban::table.filter(
+      player_ban::player_id
+        .eq(player_id)
+        .and(player_ban::ban_type.eq(PlayerBanType::Chat))
+        .and(
+          player_ban::ban_expires_at
+            .is_null()
+            .or(player_ban::ban_expires_at.gt(Utc::now())),
+        ),
+    ),
+  ))
+  .get_result(conn)
+  .map_err(Into::into)
+}
+
+/// Returns players who muted `player_id`
+pub fn get_muted_by(conn: &DbConn, player_id: i32) -> Result<Vec<i32>> {
+  player_mute::table
+    .select(player_mute::player_id)
+    .filter(player_mute::mute_player_id.eq(player_id))
+    .load(conn)
+    .map_err(Into::into)
+}
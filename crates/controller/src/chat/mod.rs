@@ -0,0 +1,632 @@
+pub mod db;
+mod moderation;
+
+pub use moderation::{
+  BlockedWordsModerator, ChatModerator, HttpModerator, ModerationRequest, ModerationVerdict,
+};
+
+use crate::error::*;
+use crate::player::state::sender::PlayerRegistryHandle;
+use crate::player::PlayerRef;
+use crate::schema::chat_channel;
+use crate::state::Data;
+use bs_diesel_utils::{BSDieselEnum, ExecutorRef};
+use chrono::{DateTime, Utc};
+use flo_net::packet::FloPacket;
+use flo_net::proto::flo_connect as proto;
+use flo_state::{async_trait, Actor, Context, Handler, Message, RegistryRef, Service};
+use once_cell::sync::Lazy;
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Messages delivered to a player joining a channel
+static CHAT_HISTORY_SIZE: Lazy<i64> = Lazy::new(|| {
+  std::env::var("FLO_CHAT_HISTORY_SIZE")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(50)
+});
+/// Messages a player can send in `CHAT_RATE_LIMIT_WINDOW`, across all channels
+static CHAT_RATE_LIMIT: Lazy<usize> = Lazy::new(|| {
+  std::env::var("FLO_CHAT_RATE_LIMIT")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(5)
+});
+const CHAT_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+const CHAT_MESSAGE_MAX_CHARS: usize = 500;
+
+#[derive(
+  Debug,
+  Serialize,
+  Deserialize,
+  Copy,
+  Clone,
+  PartialEq,
+  Eq,
+  PartialOrd,
+  Ord,
+  BSDieselEnum,
+  S2ProtoEnum,
+)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(flo_net::proto::flo_connect::ChatChannelKind))]
+pub enum ChatChannelKind {
+  Global = 0,
+  Ladder = 1,
+  Clan = 2,
+}
+
+#[derive(Debug, Clone, Queryable)]
+pub struct ChatChannel {
+  pub id: i32,
+  pub kind: ChatChannelKind,
+  /// Ladder or clan identifier, empty for the global channel
+  pub key: String,
+  pub name: String,
+  /// Only players added as members, e.g. by the clan website, can join
+  pub restricted: bool,
+}
+
+pub(crate) type ChatChannelColumns = (
+  chat_channel::dsl::id,
+  chat_channel::dsl::kind,
+  chat_channel::dsl::key,
+  chat_channel::dsl::name,
+  chat_channel::dsl::restricted,
+);
+
+impl ChatChannel {
+  pub(crate) const COLUMNS: ChatChannelColumns = (
+    chat_channel::dsl::id,
+    chat_channel::dsl::kind,
+    chat_channel::dsl::key,
+    chat_channel::dsl::name,
+    chat_channel::dsl::restricted,
+  );
+
+  fn pack(&self) -> proto::ChatChannel {
+    proto::ChatChannel {
+      id: self.id,
+      kind: self.kind.into_proto_enum().into(),
+      key: self.key.clone(),
+      name: self.name.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+  pub id: i64,
+  pub channel_id: i32,
+  pub player: PlayerRef,
+  pub content: String,
+  pub created_at: DateTime<Utc>,
+}
+
+impl ChatMessage {
+  fn pack(self) -> Result<proto::ChatMessage> {
+    Ok(proto::ChatMessage {
+      id: self.id,
+      channel_id: self.channel_id,
+      player: Some(self.player.pack()?),
+      content: self.content,
+      created_at_millis: self.created_at.timestamp_millis(),
+    })
+  }
+}
+
+/// Relays persistent chat channels outside of lobbies
+pub struct ChatService {
+  db: ExecutorRef,
+  players: PlayerRegistryHandle,
+  moderators: Arc<Vec<Box<dyn ChatModerator>>>,
+  channels: BTreeMap<i32, ChannelState>,
+  online: BTreeMap<i32, OnlinePlayer>,
+}
+
+struct ChannelState {
+  channel: ChatChannel,
+  members: BTreeSet<i32>,
+}
+
+struct OnlinePlayer {
+  player: PlayerRef,
+  channels: BTreeSet<i32>,
+  rate_limit: RateLimit,
+}
+
+impl ChatService {
+  fn moderators_from_env() -> Vec<Box<dyn ChatModerator>> {
+    let mut moderators: Vec<Box<dyn ChatModerator>> = vec![];
+    if let Ok(words) = std::env::var("FLO_CHAT_BLOCKED_WORDS") {
+      moderators.push(Box::new(BlockedWordsModerator::new(words.split(','))));
+    }
+    if let Ok(url) = std::env::var("FLO_CHAT_MODERATION_URL") {
+      moderators.push(Box::new(HttpModerator::new(url)));
+    }
+    moderators
+  }
+
+  fn online_members(&self, channel_id: i32) -> Vec<i32> {
+    self
+      .channels
+      .get(&channel_id)
+      .map(|state| state.members.iter().cloned().collect())
+      .unwrap_or_default()
+  }
+
+  fn join_online(&mut self, player_id: i32, channel: ChatChannel) {
+    let channel_id = channel.id;
+    self
+      .channels
+      .entry(channel_id)
+      .or_insert_with(|| ChannelState {
+        channel,
+        members: BTreeSet::new(),
+      })
+      .members
+      .insert(player_id);
+    if let Some(online) = self.online.get_mut(&player_id) {
+      online.channels.insert(channel_id);
+    }
+  }
+
+  fn leave_online(&mut self, player_id: i32, channel_id: i32) -> bool {
+    if let Some(online) = self.online.get_mut(&player_id) {
+      online.channels.remove(&channel_id);
+    }
+    match self.channels.get_mut(&channel_id) {
+      Some(state) => {
+        let removed = state.members.remove(&player_id);
+        if state.members.is_empty() {
+          self.channels.remove(&channel_id);
+        }
+        removed
+      }
+      None => false,
+    }
+  }
+
+  async fn send_joined(&self, player_id: i32, channel: &ChatChannel) -> Result<()> {
+    let history = self
+      .db
+      .exec({
+        let channel_id = channel.id;
+        move |conn| self::db::get_history(conn, channel_id, *CHAT_HISTORY_SIZE)
+      })
+      .await?;
+    let members = self
+      .online_members(channel.id)
+      .into_iter()
+      .filter_map(|id| self.online.get(&id).map(|p| p.player.clone()))
+      .map(|player| player.pack())
+      .collect::<Result<Vec<_>, _>>()?;
+    let packet = proto::PacketChatChannelJoined {
+      channel: Some(channel.pack()),
+      members,
+      history: history
+        .into_iter()
+        .map(ChatMessage::pack)
+        .collect::<Result<Vec<_>>>()?,
+    };
+    self
+      .players
+      .send(player_id, packet.encode_as_frame()?)
+      .await?;
+    Ok(())
+  }
+
+  async fn broadcast_member_update(
+    &self,
+    channel_id: i32,
+    player: &PlayerRef,
+    online: bool,
+  ) -> Result<()> {
+    let targets: Vec<i32> = self
+      .online_members(channel_id)
+      .into_iter()
+      .filter(|id| *id != player.id)
+      .collect();
+    if targets.is_empty() {
+      return Ok(());
+    }
+    let packet = proto::PacketChatChannelMemberUpdate {
+      channel_id,
+      player: Some(player.clone().pack()?),
+      online,
+    };
+    self
+      .players
+      .broadcast(targets, packet.encode_as_frame()?)
+      .await?;
+    Ok(())
+  }
+
+  async fn moderate(
+    &self,
+    channel: &ChatChannel,
+    player: &PlayerRef,
+    mut content: String,
+  ) -> Result<String> {
+    for moderator in self.moderators.iter() {
+      let verdict = moderator
+        .check(&ModerationRequest {
+          channel,
+          player,
+          content: &content,
+        })
+        .await;
+      match verdict {
+        Ok(ModerationVerdict::Allow) => {}
+        Ok(ModerationVerdict::Replace { content: replaced }) => content = replaced,
+        Ok(ModerationVerdict::Reject { reason }) => {
+          return Err(Error::ChatMessageRejected(reason));
+        }
+        // moderation outages should not take chat down
+        Err(err) => {
+          tracing::warn!(
+            player_id = player.id,
+            "chat moderator {}: {}",
+            moderator.name(),
+            err
+          );
+        }
+      }
+    }
+    Ok(content)
+  }
+}
+
+impl Actor for ChatService {}
+
+#[async_trait]
+impl Service<Data> for ChatService {
+  type Error = Error;
+
+  async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
+    let players = registry
+      .resolve::<crate::player::state::PlayerRegistry>()
+      .await?;
+    Ok(ChatService {
+      db: registry.data().db.clone(),
+      players: PlayerRegistryHandle::from(players),
+      moderators: Arc::new(Self::moderators_from_env()),
+      channels: BTreeMap::new(),
+      online: BTreeMap::new(),
+    })
+  }
+}
+
+/// Sends the channels the player is a member of, and marks the player online in them
+pub struct ChatPlayerConnect {
+  pub player_id: i32,
+}
+
+impl Message for ChatPlayerConnect {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<ChatPlayerConnect> for ChatService {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    ChatPlayerConnect { player_id }: ChatPlayerConnect,
+  ) {
+    if let Err(err) = self.connect(player_id).await {
+      tracing::error!(player_id, "chat: connect: {}", err);
+    }
+  }
+}
+
+impl ChatService {
+  async fn connect(&mut self, player_id: i32) -> Result<()> {
+    let (player, channels) = self
+      .db
+      .exec(move |conn| -> Result<_> {
+        Ok((
+          crate::player::db::get_ref(conn, player_id)?,
+          self::db::get_player_channels(conn, player_id)?,
+        ))
+      })
+      .await?;
+
+    // reconnected without a disconnect, e.g. a second client
+    if let Some(prev) = self.online.remove(&player_id) {
+      for channel_id in prev.channels {
+        self.leave_online(player_id, channel_id);
+      }
+    }
+
+    self.online.insert(
+      player_id,
+      OnlinePlayer {
+        player: player.clone(),
+        channels: BTreeSet::new(),
+        rate_limit: RateLimit::default(),
+      },
+    );
+
+    for channel in channels {
+      let channel_id = channel.id;
+      self.join_online(player_id, channel.clone());
+      self.send_joined(player_id, &channel).await?;
+      self
+        .broadcast_member_update(channel_id, &player, true)
+        .await?;
+    }
+
+    Ok(())
+  }
+}
+
+pub struct ChatPlayerDisconnect {
+  pub player_id: i32,
+}
+
+impl Message for ChatPlayerDisconnect {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<ChatPlayerDisconnect> for ChatService {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    ChatPlayerDisconnect { player_id }: ChatPlayerDisconnect,
+  ) {
+    let online = if let Some(online) = self.online.remove(&player_id) {
+      online
+    } else {
+      return;
+    };
+    for channel_id in online.channels {
+      if self.leave_online(player_id, channel_id) {
+        if let Err(err) = self
+          .broadcast_member_update(channel_id, &online.player, false)
+          .await
+        {
+          tracing::error!(player_id, channel_id, "chat: disconnect: {}", err);
+        }
+      }
+    }
+  }
+}
+
+pub struct JoinChatChannel {
+  pub player_id: i32,
+  pub kind: ChatChannelKind,
+  pub key: String,
+}
+
+impl Message for JoinChatChannel {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<JoinChatChannel> for ChatService {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    JoinChatChannel {
+      player_id,
+      kind,
+      key,
+    }: JoinChatChannel,
+  ) -> Result<()> {
+    let player = self
+      .online
+      .get(&player_id)
+      .map(|p| p.player.clone())
+      .ok_or_else(|| Error::PlayerNotFound)?;
+
+    let channel = self
+      .db
+      .exec(move |conn| -> Result<_> {
+        let channel = self::db::find_channel(conn, kind, &key)?;
+        if channel.restricted && !self::db::is_member(conn, channel.id, player_id)? {
+          return Err(Error::ChatNotMember);
+        }
+        self::db::add_member(conn, channel.id, player_id)?;
+        Ok(channel)
+      })
+      .await?;
+
+    let channel_id = channel.id;
+    self.join_online(player_id, channel.clone());
+    self.send_joined(player_id, &channel).await?;
+    self
+      .broadcast_member_update(channel_id, &player, true)
+      .await?;
+    Ok(())
+  }
+}
+
+pub struct LeaveChatChannel {
+  pub player_id: i32,
+  pub channel_id: i32,
+}
+
+impl Message for LeaveChatChannel {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<LeaveChatChannel> for ChatService {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    LeaveChatChannel {
+      player_id,
+      channel_id,
+    }: LeaveChatChannel,
+  ) -> Result<()> {
+    self
+      .db
+      .exec(move |conn| self::db::remove_member(conn, channel_id, player_id))
+      .await?;
+
+    let player = self.online.get(&player_id).map(|p| p.player.clone());
+    if self.leave_online(player_id, channel_id) {
+      if let Some(player) = player {
+        self
+          .broadcast_member_update(channel_id, &player, false)
+          .await?;
+      }
+    }
+    Ok(())
+  }
+}
+
+pub struct SendChatMessage {
+  pub player_id: i32,
+  pub channel_id: i32,
+  pub content: String,
+}
+
+impl Message for SendChatMessage {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<SendChatMessage> for ChatService {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    SendChatMessage {
+      player_id,
+      channel_id,
+      content,
+    }: SendChatMessage,
+  ) -> Result<()> {
+    let content = content.trim().to_string();
+    if content.is_empty() {
+      return Err(Error::ChatMessageRejected("Message is empty".to_string()));
+    }
+    if content.chars().count() > CHAT_MESSAGE_MAX_CHARS {
+      return Err(Error::ChatMessageRejected(format!(
+        "Message is longer than {} characters",
+        CHAT_MESSAGE_MAX_CHARS
+      )));
+    }
+
+    let channel = self
+      .channels
+      .get(&channel_id)
+      .filter(|state| state.members.contains(&player_id))
+      .map(|state| state.channel.clone())
+      .ok_or_else(|| Error::ChatNotMember)?;
+
+    let player = {
+      let online = self
+        .online
+        .get_mut(&player_id)
+        .ok_or_else(|| Error::ChatNotMember)?;
+      if !online.rate_limit.check(Instant::now()) {
+        return Err(Error::ChatRateLimited);
+      }
+      online.player.clone()
+    };
+
+    let banned = self
+      .db
+      .exec(move |conn| self::db::is_chat_banned(conn, player_id))
+      .await?;
+    if banned {
+      return Err(Error::ChatBanned);
+    }
+
+    let content = self.moderate(&channel, &player, content).await?;
+
+    let (message, muted_by) = self
+      .db
+      .exec(move |conn| -> Result<_> {
+        Ok((
+          self::db::add_message(conn, channel_id, player, content)?,
+          self::db::get_muted_by(conn, player_id)?,
+        ))
+      })
+      .await?;
+
+    let targets: Vec<i32> = self
+      .online_members(channel_id)
+      .into_iter()
+      .filter(|id| !muted_by.contains(id))
+      .collect();
+    let packet = proto::PacketChatMessage {
+      message: Some(message.pack()?),
+    };
+    self
+      .players
+      .broadcast(targets, packet.encode_as_frame()?)
+      .await?;
+    Ok(())
+  }
+}
+
+/// Maps chat errors to the reason sent back in `PacketChatReject`
+pub fn reject_reason(err: &Error) -> proto::ChatRejectReason {
+  use proto::ChatRejectReason;
+  match err {
+    Error::ChatChannelNotFound => ChatRejectReason::ChannelNotFound,
+    Error::ChatNotMember => ChatRejectReason::NotMember,
+    Error::ChatRateLimited => ChatRejectReason::RateLimited,
+    Error::ChatBanned => ChatRejectReason::Banned,
+    _ => ChatRejectReason::InvalidMessage,
+  }
+}
+
+/// Sliding window message counter
+pub(crate) struct RateLimit {
+  max: usize,
+  window: Duration,
+  sent: VecDeque<Instant>,
+}
+
+/// The chat rate limit of a player
+impl Default for RateLimit {
+  fn default() -> Self {
+    Self::new(*CHAT_RATE_LIMIT, CHAT_RATE_LIMIT_WINDOW)
+  }
+}
+
+impl RateLimit {
+  fn new(max: usize, window: Duration) -> Self {
+    Self {
+      max,
+      window,
+      sent: VecDeque::with_capacity(max),
+    }
+  }
+
+  pub(crate) fn check(&mut self, now: Instant) -> bool {
+    while let Some(t) = self.sent.front() {
+      if now.duration_since(*t) >= self.window {
+        self.sent.pop_front();
+      } else {
+        break;
+      }
+    }
+    if self.sent.len() >= self.max {
+      return false;
+    }
+    self.sent.push_back(now);
+    true
+  }
+}
+
+#[test]
+fn test_rate_limit() {
+  let mut limit = RateLimit::new(2, Duration::from_secs(10));
+  let t = Instant::now();
+  assert!(limit.check(t));
+  assert!(limit.check(t + Duration::from_secs(1)));
+  assert!(!limit.check(t + Duration::from_secs(2)));
+  assert!(limit.check(t + Duration::from_secs(10)));
+  assert!(!limit.check(t + Duration::from_secs(10)));
+  assert!(limit.check(t + Duration::from_secs(11)));
+}
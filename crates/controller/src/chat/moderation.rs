@@ -0,0 +1,159 @@
+use crate::chat::ChatChannel;
+use crate::error::*;
+use crate::player::PlayerRef;
+use flo_state::async_trait;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request};
+use serde::{Deserialize, Serialize};
+
+pub struct ModerationRequest<'a> {
+  pub channel: &'a ChatChannel,
+  pub player: &'a PlayerRef,
+  pub content: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ModerationVerdict {
+  Allow,
+  /// Deliver the message with the content replaced, e.g. masked words
+  Replace {
+    content: String,
+  },
+  Reject {
+    reason: String,
+  },
+}
+
+/// Checks a chat message before it is stored and relayed
+#[async_trait]
+pub trait ChatModerator: Send + Sync + 'static {
+  fn name(&self) -> &'static str;
+  async fn check(&self, req: &ModerationRequest<'_>) -> Result<ModerationVerdict>;
+}
+
+/// Masks words listed in `FLO_CHAT_BLOCKED_WORDS`, comma separated, case insensitive
+pub struct BlockedWordsModerator {
+  words: Vec<String>,
+}
+
+impl BlockedWordsModerator {
+  pub fn new<I, T>(words: I) -> Self
+  where
+    I: IntoIterator<Item = T>,
+    T: AsRef<str>,
+  {
+    Self {
+      words: words
+        .into_iter()
+        .map(|v| v.as_ref().trim().to_lowercase())
+        .filter(|v| !v.is_empty())
+        .collect(),
+    }
+  }
+
+  fn mask(&self, content: &str) -> Option<String> {
+    let mut chars: Vec<char> = content.chars().collect();
+    let lower: Vec<char> = content
+      .chars()
+      .map(|c| c.to_lowercase().next().unwrap_or(c))
+      .collect();
+    let mut masked = false;
+    for word in &self.words {
+      let word: Vec<char> = word.chars().collect();
+      if word.len() > lower.len() {
+        continue;
+      }
+      for i in 0..=(lower.len() - word.len()) {
+        if lower[i..(i + word.len())] == word[..] {
+          for c in &mut chars[i..(i + word.len())] {
+            *c = '*';
+          }
+          masked = true;
+        }
+      }
+    }
+    if masked {
+      Some(chars.into_iter().collect())
+    } else {
+      None
+    }
+  }
+}
+
+#[async_trait]
+impl ChatModerator for BlockedWordsModerator {
+  fn name(&self) -> &'static str {
+    "blocked_words"
+  }
+
+  async fn check(&self, req: &ModerationRequest<'_>) -> Result<ModerationVerdict> {
+    Ok(match self.mask(req.content) {
+      Some(content) => ModerationVerdict::Replace { content },
+      None => ModerationVerdict::Allow,
+    })
+  }
+}
+
+/// Posts messages as JSON to an external moderation service,
+/// which responds with a `ModerationVerdict`, e.g. `{"action": "allow"}`
+pub struct HttpModerator {
+  url: String,
+  client: Client<HttpConnector>,
+}
+
+impl HttpModerator {
+  pub fn new(url: String) -> Self {
+    Self {
+      url,
+      client: Client::new(),
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct ModerationPayload<'a> {
+  channel_id: i32,
+  channel_name: &'a str,
+  player_id: i32,
+  player_name: &'a str,
+  content: &'a str,
+}
+
+#[async_trait]
+impl ChatModerator for HttpModerator {
+  fn name(&self) -> &'static str {
+    "http"
+  }
+
+  async fn check(&self, req: &ModerationRequest<'_>) -> Result<ModerationVerdict> {
+    let payload = serde_json::to_vec(&ModerationPayload {
+      channel_id: req.channel.id,
+      channel_name: &req.channel.name,
+      player_id: req.player.id,
+      player_name: &req.player.name,
+      content: req.content,
+    })?;
+    let req = Request::builder()
+      .method(Method::POST)
+      .uri(&self.url)
+      .header("content-type", "application/json")
+      .body(Body::from(payload))
+      .map_err(|err| Error::ChatModeration(err.to_string()))?;
+    let res = self.client.request(req).await?;
+    if !res.status().is_success() {
+      return Err(Error::ChatModeration(res.status().to_string()));
+    }
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+    Ok(serde_json::from_slice(&body)?)
+  }
+}
+
+#[test]
+fn test_blocked_words_mask() {
+  let m = BlockedWordsModerator::new(vec!["noob", " ", "GG EZ"]);
+  assert_eq!(m.mask("good game"), None);
+  assert_eq!(m.mask("Noob, gg ez"), Some("****, *****".to_string()));
+  assert_eq!(m.mask("noobnoob"), Some("********".to_string()));
+  assert_eq!(m.mask("no"), None);
+}
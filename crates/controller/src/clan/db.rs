@@ -0,0 +1,363 @@
+use crate::clan::{Clan, ClanInvite, ClanMember, ClanRef, ClanRole, ClanStats};
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::SlotStatus;
+use crate::player::PlayerRef;
+use crate::schema::{clan, clan_invite, clan_match, clan_member, game_used_slot, player};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+
+/// Returns the id of the clan the player is a member of, and the player's role
+pub fn get_membership(conn: &DbConn, player_id: i32) -> Result<Option<(i32, ClanRole)>> {
+  clan_member::table
+    .select((clan_member::clan_id, clan_member::role))
+    .filter(clan_member::player_id.eq(player_id))
+    .first(conn)
+    .optional()
+    .map_err(Into::into)
+}
+
+pub fn get_clan(conn: &DbConn, clan_id: i32) -> Result<Clan> {
+  let clan = clan::table
+    .find(clan_id)
+    .select(ClanRef::COLUMNS)
+    .first::<ClanRef>(conn)
+    .optional()?
+    .ok_or_else(|| Error::ClanNotFound)?;
+
+  let rows: Vec<(PlayerRef, ClanRole, DateTime<Utc>)> = clan_member::table
+    .inner_join(player::table)
+    .select((
+      PlayerRef::COLUMNS,
+      clan_member::role,
+      clan_member::created_at,
+    ))
+    .filter(clan_member::clan_id.eq(clan_id))
+    .order((clan_member::role, clan_member::id))
+    .load(conn)?;
+
+  Ok(Clan {
+    clan,
+    members: rows
+      .into_iter()
+      .map(|(player, role, joined_at)| ClanMember {
+        player,
+        role,
+        joined_at,
+      })
+      .collect(),
+    stats: get_stats(conn, clan_id)?,
+  })
+}
+
+/// Returns `None` if the player is not in a clan
+pub fn get_player_clan(conn: &DbConn, player_id: i32) -> Result<Option<Clan>> {
+  match get_membership(conn, player_id)? {
+    Some((clan_id, _)) => get_clan(conn, clan_id).map(Some),
+    None => Ok(None),
+  }
+}
+
+pub fn get_player_invites(conn: &DbConn, player_id: i32) -> Result<Vec<ClanInvite>> {
+  let rows: Vec<(ClanRef, i32)> = clan_invite::table
+    .inner_join(clan::table)
+    .select((ClanRef::COLUMNS, clan_invite::invited_by))
+    .filter(clan_invite::player_id.eq(player_id))
+    .order(clan_invite::id)
+    .load(conn)?;
+  let ids: Vec<i32> = rows.iter().map(|(_, id)| *id).collect();
+  let players = crate::player::db::get_refs_by_ids(conn, &ids)?;
+  Ok(
+    rows
+      .into_iter()
+      .filter_map(|(clan, invited_by)| {
+        players
+          .iter()
+          .find(|p| p.id == invited_by)
+          .cloned()
+          .map(|invited_by| ClanInvite { clan, invited_by })
+      })
+      .collect(),
+  )
+}
+
+/// Creates a clan led by the player, pending invites of the player are removed
+pub fn create(conn: &DbConn, player_id: i32, tag: &str, name: &str) -> Result<i32> {
+  #[derive(Insertable)]
+  #[table_name = "clan"]
+  struct Insert<'a> {
+    tag: &'a str,
+    name: &'a str,
+    created_by: i32,
+  }
+
+  conn.transaction(|| {
+    if get_membership(conn, player_id)?.is_some() {
+      return Err(Error::ClanAlreadyMember);
+    }
+
+    // tags are alphanumeric, no pattern characters to escape
+    let taken: bool = diesel::select(diesel::dsl::exists(
+      clan::table.filter(clan::tag.ilike(tag)),
+    ))
+    .get_result(conn)?;
+    if taken {
+      return Err(Error::ClanTagTaken);
+    }
+
+    let clan_id = diesel::insert_into(clan::table)
+      .values(&Insert {
+        tag,
+        name,
+        created_by: player_id,
+      })
+      .returning(clan::id)
+      .get_result(conn)?;
+    add_member(conn, clan_id, player_id, ClanRole::Leader)?;
+    Ok(clan_id)
+  })
+}
+
+/// Returns the clan and the inviting player
+pub fn invite(
+  conn: &DbConn,
+  player_id: i32,
+  target_player_id: i32,
+) -> Result<(ClanRef, PlayerRef)> {
+  #[derive(Insertable)]
+  #[table_name = "clan_invite"]
+  struct Insert {
+    clan_id: i32,
+    player_id: i32,
+    invited_by: i32,
+  }
+
+  let (clan_id, role) = get_membership(conn, player_id)?.ok_or_else(|| Error::ClanNotFound)?;
+  if !role.can_manage(ClanRole::Member) {
+    return Err(Error::ClanPermissionDenied);
+  }
+  // fails if the player doesn't exist
+  crate::player::db::get_ref(conn, target_player_id)?;
+  if get_membership(conn, target_player_id)?.is_some() {
+    return Err(Error::ClanAlreadyMember);
+  }
+
+  diesel::insert_into(clan_invite::table)
+    .values(&Insert {
+      clan_id,
+      player_id: target_player_id,
+      invited_by: player_id,
+    })
+    .on_conflict((clan_invite::clan_id, clan_invite::player_id))
+    .do_nothing()
+    .execute(conn)?;
+
+  let clan = clan::table
+    .find(clan_id)
+    .select(ClanRef::COLUMNS)
+    .first(conn)?;
+  Ok((clan, crate::player::db::get_ref(conn, player_id)?))
+}
+
+/// Accepting joins the clan and removes all other invites of the player
+pub fn reply_invite(conn: &DbConn, player_id: i32, clan_id: i32, accept: bool) -> Result<()> {
+  conn.transaction(|| {
+    let removed = diesel::delete(
+      clan_invite::table.filter(
+        clan_invite::clan_id
+          .eq(clan_id)
+          .and(clan_invite::player_id.eq(player_id)),
+      ),
+    )
+    .execute(conn)?;
+    if removed == 0 {
+      return Err(Error::ClanInviteNotFound);
+    }
+
+    if accept {
+      if get_membership(conn, player_id)?.is_some() {
+        return Err(Error::ClanAlreadyMember);
+      }
+      add_member(conn, clan_id, player_id, ClanRole::Member)?;
+    }
+    Ok(())
+  })
+}
+
+/// Returns the id of the clan the player left, the clan is removed if the leader was the last member
+pub fn leave(conn: &DbConn, player_id: i32) -> Result<i32> {
+  conn.transaction(|| {
+    let (clan_id, role) = get_membership(conn, player_id)?.ok_or_else(|| Error::ClanNotFound)?;
+    if role == ClanRole::Leader {
+      let members: i64 = clan_member::table
+        .filter(clan_member::clan_id.eq(clan_id))
+        .count()
+        .get_result(conn)?;
+      if members > 1 {
+        return Err(Error::ClanLeaderCannotLeave);
+      }
+      diesel::delete(clan::table.find(clan_id)).execute(conn)?;
+    } else {
+      diesel::delete(clan_member::table.filter(clan_member::player_id.eq(player_id)))
+        .execute(conn)?;
+    }
+    Ok(clan_id)
+  })
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MemberUpdate {
+  Remove,
+  SetRole(ClanRole),
+}
+
+/// Returns the id of the clan
+pub fn update_member(
+  conn: &DbConn,
+  player_id: i32,
+  target_player_id: i32,
+  update: MemberUpdate,
+) -> Result<i32> {
+  conn.transaction(|| {
+    let (clan_id, role) = get_membership(conn, player_id)?.ok_or_else(|| Error::ClanNotFound)?;
+    let target_role = match get_membership(conn, target_player_id)? {
+      Some((target_clan_id, target_role)) if target_clan_id == clan_id => target_role,
+      _ => return Err(Error::ClanNotMember),
+    };
+    if !role.can_manage(target_role) {
+      return Err(Error::ClanPermissionDenied);
+    }
+
+    match update {
+      MemberUpdate::Remove => {
+        diesel::delete(clan_member::table.filter(clan_member::player_id.eq(target_player_id)))
+          .execute(conn)?;
+      }
+      MemberUpdate::SetRole(new_role) => {
+        if role != ClanRole::Leader {
+          return Err(Error::ClanPermissionDenied);
+        }
+        // leadership is transferred
+        if new_role == ClanRole::Leader {
+          set_role(conn, player_id, ClanRole::Officer)?;
+        }
+        set_role(conn, target_player_id, new_role)?;
+      }
+    }
+    Ok(clan_id)
+  })
+}
+
+/// Records the game as a clan match if the rosters line up,
+/// returns the ids of the two clans
+pub fn link_game(conn: &DbConn, game_id: i32) -> Result<Option<(i32, i32)>> {
+  #[derive(Insertable)]
+  #[table_name = "clan_match"]
+  struct Insert {
+    game_id: i32,
+    clan_id: i32,
+    opponent_clan_id: i32,
+  }
+
+  let slots: Vec<(i32, Option<i32>)> = game_used_slot::table
+    .select((game_used_slot::team, game_used_slot::player_id))
+    .filter(
+      game_used_slot::game_id
+        .eq(game_id)
+        .and(game_used_slot::status.eq(SlotStatus::Occupied)),
+    )
+    .load(conn)?;
+  let player_ids: Vec<i32> = slots.iter().filter_map(|(_, id)| *id).collect();
+  let clans: Vec<(i32, i32)> = clan_member::table
+    .select((clan_member::player_id, clan_member::clan_id))
+    .filter(clan_member::player_id.eq_any(&player_ids))
+    .load(conn)?;
+
+  let slots: Vec<(i32, Option<i32>)> = slots
+    .into_iter()
+    .map(|(team, player_id)| {
+      let clan_id = player_id.and_then(|player_id| {
+        clans
+          .iter()
+          .find(|(id, _)| *id == player_id)
+          .map(|(_, clan_id)| *clan_id)
+      });
+      (team, clan_id)
+    })
+    .collect();
+
+  let (clan_id, opponent_clan_id) = match crate::clan::match_clans(&slots) {
+    Some(v) => v,
+    None => return Ok(None),
+  };
+
+  diesel::insert_into(clan_match::table)
+    .values(&Insert {
+      game_id,
+      clan_id,
+      opponent_clan_id,
+    })
+    .on_conflict(clan_match::game_id)
+    .do_nothing()
+    .execute(conn)?;
+
+  Ok(Some((clan_id, opponent_clan_id)))
+}
+
+/// `None` records a draw or clears a reported result
+pub fn set_match_winner(conn: &DbConn, game_id: i32, winner_clan_id: Option<i32>) -> Result<()> {
+  let (clan_id, opponent_clan_id): (i32, i32) = clan_match::table
+    .select((clan_match::clan_id, clan_match::opponent_clan_id))
+    .filter(clan_match::game_id.eq(game_id))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+  if let Some(id) = winner_clan_id {
+    if id != clan_id && id != opponent_clan_id {
+      return Err(Error::ClanNotFound);
+    }
+  }
+  diesel::update(clan_match::table.filter(clan_match::game_id.eq(game_id)))
+    .set(clan_match::winner_clan_id.eq(winner_clan_id))
+    .execute(conn)?;
+  Ok(())
+}
+
+pub fn get_stats(conn: &DbConn, clan_id: i32) -> Result<ClanStats> {
+  let winners: Vec<Option<i32>> = clan_match::table
+    .select(clan_match::winner_clan_id)
+    .filter(
+      clan_match::clan_id
+        .eq(clan_id)
+        .or(clan_match::opponent_clan_id.eq(clan_id)),
+    )
+    .load(conn)?;
+  Ok(ClanStats::from_winners(clan_id, &winners))
+}
+
+fn add_member(conn: &DbConn, clan_id: i32, player_id: i32, role: ClanRole) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "clan_member"]
+  struct Insert {
+    clan_id: i32,
+    player_id: i32,
+    role: ClanRole,
+  }
+
+  diesel::insert_into(clan_member::table)
+    .values(&Insert {
+      clan_id,
+      player_id,
+      role,
+    })
+    .execute(conn)?;
+  diesel::delete(clan_invite::table.filter(clan_invite::player_id.eq(player_id))).execute(conn)?;
+  Ok(())
+}
+
+fn set_role(conn: &DbConn, player_id: i32, role: ClanRole) -> Result<()> {
+  diesel::update(clan_member::table.filter(clan_member::player_id.eq(player_id)))
+    .set(clan_member::role.eq(role))
+    .execute(conn)?;
+  Ok(())
+}
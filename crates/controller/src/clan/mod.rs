@@ -0,0 +1,383 @@
+pub mod db;
+
+use crate::error::*;
+use crate::player::PlayerRef;
+use crate::schema::clan;
+use crate::state::ControllerState;
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Utc};
+use db::MemberUpdate;
+use flo_net::packet::FloPacket;
+use flo_net::proto::flo_connect as proto;
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack};
+use serde::{Deserialize, Serialize};
+
+const CLAN_TAG_CHARS: std::ops::RangeInclusive<usize> = 2..=5;
+const CLAN_NAME_CHARS: std::ops::RangeInclusive<usize> = 3..=32;
+/// Slot team of observers and referees
+const OBSERVER_TEAM: i32 = 24;
+
+#[derive(
+  Debug,
+  Serialize,
+  Deserialize,
+  Copy,
+  Clone,
+  PartialEq,
+  Eq,
+  PartialOrd,
+  Ord,
+  BSDieselEnum,
+  S2ProtoEnum,
+)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(flo_net::proto::flo_connect::ClanRole))]
+pub enum ClanRole {
+  Leader = 0,
+  Officer = 1,
+  Member = 2,
+}
+
+impl ClanRole {
+  /// Officers manage members, the leader also manages officers
+  pub fn can_manage(self, target: ClanRole) -> bool {
+    self != ClanRole::Member && self < target
+  }
+}
+
+#[derive(Debug, Clone, Queryable)]
+pub struct ClanRef {
+  pub id: i32,
+  pub tag: String,
+  pub name: String,
+}
+
+pub(crate) type ClanRefColumns = (clan::dsl::id, clan::dsl::tag, clan::dsl::name);
+
+impl ClanRef {
+  pub(crate) const COLUMNS: ClanRefColumns = (clan::dsl::id, clan::dsl::tag, clan::dsl::name);
+
+  fn pack(&self) -> proto::ClanRef {
+    proto::ClanRef {
+      id: self.id,
+      tag: self.tag.clone(),
+      name: self.name.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClanMember {
+  pub player: PlayerRef,
+  pub role: ClanRole,
+  pub joined_at: DateTime<Utc>,
+}
+
+/// Results of the clan matches of a clan, matches without a reported winner count as played only
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClanStats {
+  pub matches: i32,
+  pub wins: i32,
+  pub losses: i32,
+}
+
+impl ClanStats {
+  pub fn from_winners(clan_id: i32, winners: &[Option<i32>]) -> Self {
+    let mut stats = ClanStats::default();
+    for winner in winners {
+      stats.matches += 1;
+      match winner {
+        Some(id) if *id == clan_id => stats.wins += 1,
+        Some(_) => stats.losses += 1,
+        None => {}
+      }
+    }
+    stats
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Clan {
+  pub clan: ClanRef,
+  /// Ordered by role
+  pub members: Vec<ClanMember>,
+  pub stats: ClanStats,
+}
+
+impl Clan {
+  pub fn member_ids(&self) -> Vec<i32> {
+    self.members.iter().map(|m| m.player.id).collect()
+  }
+
+  fn pack(self) -> Result<proto::Clan> {
+    Ok(proto::Clan {
+      clan: Some(self.clan.pack()),
+      members: self
+        .members
+        .into_iter()
+        .map(|member| -> Result<_> {
+          Ok(proto::ClanMember {
+            player: Some(member.player.pack()?),
+            role: member.role.into_proto_enum().into(),
+            joined_at_millis: member.joined_at.timestamp_millis(),
+          })
+        })
+        .collect::<Result<Vec<_>>>()?,
+      stats: Some(proto::ClanStats {
+        matches: self.stats.matches,
+        wins: self.stats.wins,
+        losses: self.stats.losses,
+      }),
+    })
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClanInvite {
+  pub clan: ClanRef,
+  pub invited_by: PlayerRef,
+}
+
+impl ClanInvite {
+  fn pack(self) -> Result<proto::PacketClanInvite> {
+    Ok(proto::PacketClanInvite {
+      clan: Some(self.clan.pack()),
+      invited_by: Some(self.invited_by.pack()?),
+    })
+  }
+}
+
+/// Clan state sent to a connecting player
+pub fn initial_frames(
+  clan: Option<Clan>,
+  invites: Vec<ClanInvite>,
+) -> Result<Vec<flo_net::packet::Frame>> {
+  let mut frames = Vec::with_capacity(1 + invites.len());
+  frames.push(
+    proto::PacketClanUpdate {
+      clan: clan.map(Clan::pack).transpose()?,
+    }
+    .encode_as_frame()?,
+  );
+  for invite in invites {
+    frames.push(invite.pack()?.encode_as_frame()?);
+  }
+  Ok(frames)
+}
+
+/// Returns the trimmed tag and name
+fn validate(tag: &str, name: &str) -> Result<(String, String)> {
+  let tag = tag.trim();
+  let name = name.trim();
+  if !CLAN_TAG_CHARS.contains(&tag.chars().count())
+    || !tag.chars().all(|c| c.is_ascii_alphanumeric())
+  {
+    return Err(Error::ClanInvalid(format!(
+      "tag must be {} to {} letters or digits",
+      CLAN_TAG_CHARS.start(),
+      CLAN_TAG_CHARS.end()
+    )));
+  }
+  if !CLAN_NAME_CHARS.contains(&name.chars().count()) {
+    return Err(Error::ClanInvalid(format!(
+      "name must be {} to {} characters",
+      CLAN_NAME_CHARS.start(),
+      CLAN_NAME_CHARS.end()
+    )));
+  }
+  Ok((tag.to_string(), name.to_string()))
+}
+
+/// `slots` are the team and the clan of the player of each occupied slot, `None` for computers
+/// and players without a clan.
+/// Returns the clans of a clan match: exactly two teams besides observers,
+/// each made up of members of one clan.
+pub fn match_clans(slots: &[(i32, Option<i32>)]) -> Option<(i32, i32)> {
+  let mut teams: Vec<(i32, i32)> = vec![];
+  for (team, clan_id) in slots {
+    if *team == OBSERVER_TEAM {
+      continue;
+    }
+    let clan_id = (*clan_id)?;
+    match teams.iter().find(|(t, _)| t == team) {
+      Some((_, id)) if *id != clan_id => return None,
+      Some(_) => {}
+      None => teams.push((*team, clan_id)),
+    }
+  }
+  match teams[..] {
+    [(_, a), (_, b)] if a != b => Some((a, b)),
+    _ => None,
+  }
+}
+
+pub async fn create(state: &ControllerState, player_id: i32, tag: &str, name: &str) -> Result<()> {
+  let (tag, name) = validate(tag, name)?;
+  let clan_id = state
+    .db
+    .exec(move |conn| self::db::create(conn, player_id, &tag, &name))
+    .await?;
+  send_clan_update(state, clan_id, vec![]).await
+}
+
+pub async fn invite(state: &ControllerState, player_id: i32, target_player_id: i32) -> Result<()> {
+  let (clan, invited_by) = state
+    .db
+    .exec(move |conn| self::db::invite(conn, player_id, target_player_id))
+    .await?;
+  let packet = ClanInvite { clan, invited_by }.pack()?;
+  state
+    .player_packet_sender
+    .send(target_player_id, packet.encode_as_frame()?)
+    .await?;
+  Ok(())
+}
+
+pub async fn reply_invite(
+  state: &ControllerState,
+  player_id: i32,
+  clan_id: i32,
+  accept: bool,
+) -> Result<()> {
+  state
+    .db
+    .exec(move |conn| self::db::reply_invite(conn, player_id, clan_id, accept))
+    .await?;
+  if accept {
+    send_clan_update(state, clan_id, vec![]).await?;
+  }
+  Ok(())
+}
+
+pub async fn leave(state: &ControllerState, player_id: i32) -> Result<()> {
+  let clan_id = state
+    .db
+    .exec(move |conn| self::db::leave(conn, player_id))
+    .await?;
+  send_clan_update(state, clan_id, vec![player_id]).await
+}
+
+pub async fn update_member(
+  state: &ControllerState,
+  player_id: i32,
+  target_player_id: i32,
+  update: MemberUpdate,
+) -> Result<()> {
+  let clan_id = state
+    .db
+    .exec(move |conn| self::db::update_member(conn, player_id, target_player_id, update))
+    .await?;
+  let removed = match update {
+    MemberUpdate::Remove => vec![target_player_id],
+    MemberUpdate::SetRole(_) => vec![],
+  };
+  send_clan_update(state, clan_id, removed).await
+}
+
+/// Reports the winner of a clan match, `None` for a draw.
+/// Results are not known to the controller, leagues report them after checking the game.
+pub async fn report_match_result(
+  state: &ControllerState,
+  game_id: i32,
+  winner_clan_id: Option<i32>,
+) -> Result<()> {
+  state
+    .db
+    .exec(move |conn| self::db::set_match_winner(conn, game_id, winner_clan_id))
+    .await?;
+  Ok(())
+}
+
+/// Sends the roster to online members, and an empty update to `removed` players
+async fn send_clan_update(state: &ControllerState, clan_id: i32, removed: Vec<i32>) -> Result<()> {
+  let clan = state
+    .db
+    .exec(move |conn| match self::db::get_clan(conn, clan_id) {
+      Ok(clan) => Ok(Some(clan)),
+      // the last member left
+      Err(Error::ClanNotFound) => Ok(None),
+      Err(err) => Err(err),
+    })
+    .await?;
+
+  if let Some(clan) = clan {
+    let member_ids = clan.member_ids();
+    let frame = proto::PacketClanUpdate {
+      clan: Some(clan.pack()?),
+    }
+    .encode_as_frame()?;
+    state
+      .player_packet_sender
+      .broadcast(member_ids, frame)
+      .await?;
+  }
+
+  if !removed.is_empty() {
+    let frame = proto::PacketClanUpdate { clan: None }.encode_as_frame()?;
+    state.player_packet_sender.broadcast(removed, frame).await?;
+  }
+  Ok(())
+}
+
+#[test]
+fn test_match_clans() {
+  // 2v2, observer without a clan
+  assert_eq!(
+    match_clans(&[
+      (0, Some(1)),
+      (0, Some(1)),
+      (1, Some(2)),
+      (1, Some(2)),
+      (24, None)
+    ]),
+    Some((1, 2))
+  );
+  // mixed team
+  assert_eq!(
+    match_clans(&[(0, Some(1)), (0, Some(3)), (1, Some(2)), (1, Some(2))]),
+    None
+  );
+  // player without a clan, or a computer
+  assert_eq!(match_clans(&[(0, Some(1)), (0, None), (1, Some(2))]), None);
+  // same clan on both teams
+  assert_eq!(match_clans(&[(0, Some(1)), (1, Some(1))]), None);
+  // free for all
+  assert_eq!(
+    match_clans(&[(0, Some(1)), (1, Some(2)), (2, Some(3))]),
+    None
+  );
+  assert_eq!(match_clans(&[(0, Some(1))]), None);
+}
+
+#[test]
+fn test_clan_role() {
+  assert!(ClanRole::Leader.can_manage(ClanRole::Officer));
+  assert!(ClanRole::Officer.can_manage(ClanRole::Member));
+  assert!(!ClanRole::Officer.can_manage(ClanRole::Officer));
+  assert!(!ClanRole::Officer.can_manage(ClanRole::Leader));
+  assert!(!ClanRole::Member.can_manage(ClanRole::Member));
+}
+
+#[test]
+fn test_validate() {
+  assert_eq!(
+    validate(" FLO ", " Flo Team ").unwrap(),
+    ("FLO".to_string(), "Flo Team".to_string())
+  );
+  assert!(validate("F", "Flo Team").is_err());
+  assert!(validate("FLOFLO", "Flo Team").is_err());
+  assert!(validate("F-O", "Flo Team").is_err());
+  assert!(validate("FLO", "Fl").is_err());
+}
+
+#[test]
+fn test_stats_from_winners() {
+  assert_eq!(
+    ClanStats::from_winners(1, &[Some(1), Some(2), None, Some(1)]),
+    ClanStats {
+      matches: 4,
+      wins: 2,
+      losses: 1,
+    }
+  );
+}
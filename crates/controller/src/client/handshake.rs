@@ -1,24 +1,54 @@
 use flo_net::connect::*;
 use flo_net::packet::*;
 use flo_net::stream::FloStream;
+use once_cell::sync::Lazy;
+use std::time::Duration;
 
 use crate::error::*;
 use crate::game::Game;
 use crate::player::token::validate_player_token;
 use flo_constants::version::Version;
 
+/// Leading zero bits of the proof-of-work required before the token is checked,
+/// set `FLO_CONNECT_CHALLENGE_DIFFICULTY` to blunt connection floods and credential stuffing.
+/// Disabled if unset or 0, clients without challenge support can't connect while enabled
+pub static CONNECT_CHALLENGE_DIFFICULTY: Lazy<Option<u32>> = Lazy::new(|| {
+  std::env::var("FLO_CONNECT_CHALLENGE_DIFFICULTY")
+    .ok()
+    .and_then(|v| v.parse::<u32>().ok())
+    .filter(|v| *v > 0)
+    .map(|v| v.min(challenge::MAX_DIFFICULTY))
+});
+
+const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(15);
+
 pub async fn handle_handshake(stream: &mut FloStream) -> Result<ConnectState> {
   let req: PacketClientConnect = stream.recv().await?;
   let client_version = req.connect_version.extract()?;
 
   tracing::debug!("client version = {}", client_version);
 
+  if let Some(difficulty) = *CONNECT_CHALLENGE_DIFFICULTY {
+    if let Err(err) = handle_challenge(stream, difficulty).await {
+      stream
+        .send(PacketClientConnectReject {
+          lobby_version: Some(From::from(crate::version::FLO_LOBBY_VERSION)),
+          reason: ClientConnectRejectReason::ChallengeFailed.into(),
+        })
+        .await
+        .ok();
+      return Err(err);
+    }
+  }
+
   let token = validate_player_token(&req.token)?;
 
   tracing::debug!(token.player_id);
 
   Ok(ConnectState {
     player_id: token.player_id,
+    client_hint: Some(req.client_hint).filter(|v| !v.is_empty()),
+    war3_version: Some(req.war3_version).filter(|v| !v.is_empty()),
     joined_game: None,
     client_version: Version {
       major: client_version.major,
@@ -28,9 +58,30 @@ pub async fn handle_handshake(stream: &mut FloStream) -> Result<ConnectState> {
   })
 }
 
+async fn handle_challenge(stream: &mut FloStream, difficulty: u32) -> Result<()> {
+  let nonce: [u8; 16] = rand::random();
+  stream
+    .send(PacketClientConnectChallenge {
+      nonce: nonce.to_vec(),
+      difficulty,
+    })
+    .await?;
+  let res: PacketClientConnectChallengeResponse =
+    tokio::time::timeout(CHALLENGE_TIMEOUT, stream.recv())
+      .await
+      .map_err(|_| Error::ConnectChallengeFailed)??;
+  if !challenge::verify(&nonce, difficulty, res.solution) {
+    return Err(Error::ConnectChallengeFailed);
+  }
+  Ok(())
+}
+
 #[derive(Debug)]
 pub struct ConnectState {
   pub player_id: i32,
+  pub client_hint: Option<String>,
+  /// Detected by the client, `None` for clients that don't report it
+  pub war3_version: Option<String>,
   pub joined_game: Option<Game>,
   pub client_version: Version,
 }
@@ -4,36 +4,52 @@ use flo_net::packet::FloPacket;
 use flo_net::packet::OptionalFieldExt;
 use flo_net::proto;
 use flo_net::stream::FloStream;
-use s2_grpc_utils::{S2ProtoPack, S2ProtoUnpack};
+use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack, S2ProtoUnpack};
 use std::collections::HashMap;
 use std::time::Duration;
 
+use crate::chat::{ChatChannelKind, ChatPlayerConnect, ChatPlayerDisconnect};
 use crate::error::*;
 use crate::state::{ActorMapExt, ControllerStateRef};
 
 mod handshake;
 mod sender;
 use crate::game::messages::{ResolveGamePlayerPingBroadcastTargets, UpdateSlot};
+use crate::game::quick_join::quick_join;
+use crate::game::state::command::GameCommandRequest;
+use crate::game::state::lobby_chat::{SendLobbyChatMessage, UpdateLobbyChatKey};
+use crate::game::state::map_vote::{CastMapVote, StartMapVote};
+use crate::game::state::metadata::UpdateGameMetadata;
 use crate::game::state::node::SelectNode;
 use crate::game::state::player::GetGamePlayers;
+use crate::game::state::quota::CheckNodeQuota;
 use crate::game::state::registry::UpdateGameNodeCache;
+use crate::game::state::restore::GetRestoreFrames;
+use crate::game::state::resync::{GetGameRevision, ResyncGame};
 use crate::game::state::start::{StartGameCheck, StartGamePlayerAck};
 use crate::game::SlotSettings;
-use crate::node::messages::ListNode;
+use crate::node::messages::ListCompatibleNodes;
+use crate::node::version::{version_mismatch, GAME_TARGET_VERSION};
+use crate::notification::NotificationChannel;
 use crate::player::state::conn::{Connect, Disconnect};
 use crate::player::state::ping::{GetPlayersPingSnapshot, UpdatePing};
 use flo_net::ping::{PingMsg, PingStream};
 use flo_types::ping::PingStats;
+use bs_diesel_utils::executor::ExecutorError;
 use futures::{StreamExt, TryStreamExt};
-pub use sender::{PlayerReceiver, PlayerSender, PlayerSenderMessage};
+pub use sender::{PlayerReceiver, PlayerSender, PlayerSenderMessage, SharedFrames};
 
 const PING_INTERVAL: Duration = Duration::from_secs(30);
 const PING_TIMEOUT: Duration = Duration::from_secs(5);
+/// Subscribe requests accepted from a single connection, later requests are ignored
+const MAX_NOTIFICATION_SUBSCRIBE_REQUESTS: usize = 20;
 
 pub async fn serve(state: ControllerStateRef) -> Result<()> {
   state
     .db
-    .exec(|conn| crate::game::db::reset_instance_state(conn))
+    .exec(crate::profiling::db(|conn| {
+      crate::game::db::reset_instance_state(conn)
+    }))
     .await?;
 
   let mut listener = FloListener::bind_v4(flo_constants::CONTROLLER_SOCKET_PORT).await?;
@@ -42,7 +58,8 @@ pub async fn serve(state: ControllerStateRef) -> Result<()> {
   while let Some(mut stream) = listener.incoming().try_next().await? {
     let state = state.clone();
     tokio::spawn(async move {
-      tracing::debug!("connected: {}", stream.peer_addr()?);
+      let peer_addr = stream.peer_addr()?;
+      tracing::debug!("connected: {}", peer_addr);
 
       let accepted = match handshake::handle_handshake(&mut stream).await {
         Ok(accepted) => accepted,
@@ -66,11 +83,27 @@ pub async fn serve(state: ControllerStateRef) -> Result<()> {
         return Ok(());
       }
 
-      if let Err(err) = handle_stream(state.clone(), player_id, stream).await {
+      let client_hint = accepted.client_hint;
+      if let Err(err) = state
+        .db
+        .exec(crate::profiling::db(move |conn| {
+          crate::player::smurf::record_login(conn, player_id, peer_addr.ip(), client_hint)
+        }))
+        .await
+      {
+        tracing::warn!(player_id, "record login: {}", err);
+      }
+
+      let war3_version = accepted.war3_version;
+      if let Err(err) = handle_stream(state.clone(), player_id, war3_version, stream).await {
         tracing::debug!("stream error: {}", err);
       }
 
       state.players.send(Disconnect { player_id }).await?;
+      state
+        .chat
+        .notify(ChatPlayerDisconnect { player_id })
+        .await?;
       tracing::debug!("exiting: player_id = {}", player_id);
       Ok::<_, crate::error::Error>(())
     });
@@ -85,15 +118,18 @@ pub async fn serve(state: ControllerStateRef) -> Result<()> {
 async fn handle_stream(
   state: ControllerStateRef,
   player_id: i32,
+  war3_version: Option<String>,
   mut stream: FloStream,
 ) -> Result<()> {
   let (sender, mut receiver) = PlayerSender::new(player_id);
 
-  send_initial_state(state.clone(), &mut stream, sender).await?;
+  send_initial_state(state.clone(), &mut stream, sender, war3_version).await?;
 
   let mut ping = PingStream::interval(PING_INTERVAL, PING_TIMEOUT);
   ping.start();
 
+  let mut notification_subscribe_requests = 0;
+
   loop {
     tokio::select! {
       Some(msg) = ping.next() => {
@@ -116,6 +152,12 @@ async fn handle_stream(
                 break;
               }
             }
+            PlayerSenderMessage::Encoded(frames) => {
+              if let Err(e) = stream.send_encoded_frames(&frames).await {
+                tracing::debug!("send error: {}", e);
+                break;
+              }
+            }
             PlayerSenderMessage::Disconnect(reason) => {
               use flo_net::proto::flo_connect::PacketClientDisconnect;
               if let Err(e) = stream.send(PacketClientDisconnect {
@@ -167,6 +209,68 @@ async fn handle_stream(
             packet: proto::flo_connect::PacketPlayerMuteRemoveRequest => {
               handle_player_mute_list_update_request(state.clone(), player_id, packet.into()).await?;
             }
+            packet: proto::flo_connect::PacketPlayerNotificationSubscribeRequest => {
+              notification_subscribe_requests += 1;
+              if notification_subscribe_requests > MAX_NOTIFICATION_SUBSCRIBE_REQUESTS {
+                tracing::debug!(player_id, "notification subscribe request limit reached");
+              } else {
+                handle_player_notification_subscribe_request(state.clone(), player_id, packet).await?;
+              }
+            }
+            packet: proto::flo_connect::PacketPlayerNotificationUnsubscribeRequest => {
+              handle_player_notification_unsubscribe_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketGameCommandRequest => {
+              handle_game_command_request(state.clone(), player_id, packet).await;
+            }
+            packet: proto::flo_connect::PacketGameMetadataUpdateRequest => {
+              handle_game_metadata_update_request(state.clone(), player_id, packet).await;
+            }
+            packet: proto::flo_connect::PacketQuickJoinRequest => {
+              handle_quick_join_request(state.clone(), player_id, packet).await?;
+            }
+            packet: proto::flo_connect::PacketGameResyncRequest => {
+              handle_game_resync_request(state.clone(), player_id, packet).await;
+            }
+            packet: proto::flo_connect::PacketGameMapVoteStartRequest => {
+              handle_game_map_vote_start_request(state.clone(), player_id, packet).await;
+            }
+            packet: proto::flo_connect::PacketGameMapVoteRequest => {
+              handle_game_map_vote_request(state.clone(), player_id, packet).await;
+            }
+            packet: proto::flo_connect::PacketLobbyChatKeyUpdateRequest => {
+              handle_lobby_chat_key_update_request(state.clone(), player_id, packet).await;
+            }
+            packet: proto::flo_connect::PacketLobbyChatMessageSendRequest => {
+              handle_lobby_chat_message_send_request(state.clone(), player_id, packet).await;
+            }
+            packet: proto::flo_connect::PacketChatChannelJoinRequest => {
+              handle_chat_request(state.clone(), player_id, 0, packet.into()).await?;
+            }
+            packet: proto::flo_connect::PacketChatChannelLeaveRequest => {
+              handle_chat_request(state.clone(), player_id, packet.channel_id, packet.into()).await?;
+            }
+            packet: proto::flo_connect::PacketChatMessageSendRequest => {
+              handle_chat_request(state.clone(), player_id, packet.channel_id, packet.into()).await?;
+            }
+            packet: proto::flo_connect::PacketClanCreateRequest => {
+              handle_clan_request(state.clone(), player_id, packet.into()).await?;
+            }
+            packet: proto::flo_connect::PacketClanInviteRequest => {
+              handle_clan_request(state.clone(), player_id, packet.into()).await?;
+            }
+            packet: proto::flo_connect::PacketClanInviteReplyRequest => {
+              handle_clan_request(state.clone(), player_id, packet.into()).await?;
+            }
+            packet: proto::flo_connect::PacketClanLeaveRequest => {
+              handle_clan_request(state.clone(), player_id, packet.into()).await?;
+            }
+            packet: proto::flo_connect::PacketClanMemberUpdateRequest => {
+              handle_clan_request(state.clone(), player_id, packet.into()).await?;
+            }
+            packet: proto::flo_connect::PacketPlayerPrivacyUpdateRequest => {
+              handle_player_privacy_update_request(state.clone(), player_id, packet).await?;
+            }
           }
         }
       }
@@ -180,17 +284,23 @@ async fn send_initial_state(
   state: ControllerStateRef,
   stream: &mut FloStream,
   sender: PlayerSender,
+  war3_version: Option<String>,
 ) -> Result<()> {
   let player_id = sender.player_id();
 
-  let (player, active_slots) = state
+  let (player, active_slots, penalty, privacy, features, clan, clan_invites) = state
     .db
-    .exec(move |conn| -> Result<_> {
+    .exec(crate::profiling::db(move |conn| -> Result<_> {
       Ok((
         crate::player::db::get_ref(conn, player_id)?,
         crate::game::db::get_player_active_slots(conn, player_id)?,
+        crate::player::penalty::get_penalty(conn, player_id)?,
+        crate::player::privacy::get_privacy(conn, player_id)?,
+        crate::feature::get_player_features(conn, player_id)?,
+        crate::clan::db::get_player_clan(conn, player_id)?,
+        crate::clan::db::get_player_invites(conn, player_id)?,
       ))
-    })
+    }))
     .await?;
 
   let game_id = active_slots.last().map(|s| s.game_id);
@@ -199,6 +309,7 @@ async fn send_initial_state(
     .players
     .notify(Connect {
       game_id: game_id.clone(),
+      war3_version: war3_version.clone(),
       sender,
     })
     .await?;
@@ -215,21 +326,56 @@ async fn send_initial_state(
           PlayerStatus::Idle.into()
         },
         game_id: game_id.clone(),
+        penalty: penalty.map(Into::into),
+        privacy: Some(privacy.into()),
+        features,
       }
     }),
-    nodes: state.nodes.send(ListNode).await?.pack()?,
+    nodes: state
+      .nodes
+      .send(ListCompatibleNodes {
+        war3_version: GAME_TARGET_VERSION.clone(),
+      })
+      .await?
+      .pack()?,
   }
   .encode_as_frame()?;
 
   let mut frames = vec![frame_accept];
 
+  if let Some(pkt) = state.maintenance.packet() {
+    frames.push(pkt.encode_as_frame()?);
+  }
+
+  frames.extend(crate::clan::initial_frames(clan, clan_invites)?);
+
+  if let Some(pkt) = version_mismatch(
+    war3_version.as_deref(),
+    GAME_TARGET_VERSION.as_deref(),
+    None,
+  ) {
+    tracing::debug!(player_id, "war3 version mismatch: {:?}", war3_version);
+    frames.push(pkt.encode_as_frame()?);
+  }
+
   if let Some(game_id) = game_id {
+    // read before the snapshot, updates in between are applied again by the client
+    let revision = match state.games.send_to(game_id, GetGameRevision).await {
+      Ok(revision) => revision,
+      Err(err) => {
+        tracing::warn!(game_id, player_id, "get game revision: {}", err);
+        0
+      }
+    };
     let (mut game, node_player_token) = state
       .db
-      .exec(move |conn| crate::game::db::get_full_and_node_token(conn, game_id, player_id))
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::db::get_full_and_node_token(conn, game_id, player_id)
+      }))
       .await?;
 
     let node_id = game.node.as_ref().map(|node| node.id);
+    game.revision = revision;
 
     if game.mask_player_names {
       for (idx, slot) in game.slots.iter_mut().enumerate() {
@@ -254,9 +400,25 @@ async fn send_initial_state(
       .encode_as_frame()?;
       frames.push(frame);
     }
+
+    match state
+      .games
+      .send_to(game_id, GetRestoreFrames { player_id })
+      .await
+    {
+      Ok(Some(pkt)) => frames.push(pkt.encode_as_frame()?),
+      Ok(None) => {}
+      Err(err) => {
+        tracing::warn!(game_id, player_id, "get restore frames: {}", err);
+      }
+    }
   }
 
   stream.send_frames(frames).await?;
+
+  // sent after the initial state so the client has its session first
+  state.chat.notify(ChatPlayerConnect { player_id }).await?;
+
   Ok(())
 }
 
@@ -280,7 +442,12 @@ async fn handle_game_slot_update_request(
 }
 
 async fn handle_list_nodes_request(state: ControllerStateRef, player_id: i32) -> Result<()> {
-  let nodes = state.nodes.send(ListNode).await?;
+  let nodes = state
+    .nodes
+    .send(ListCompatibleNodes {
+      war3_version: GAME_TARGET_VERSION.clone(),
+    })
+    .await?;
   let packet = proto::flo_connect::PacketListNodes {
     nodes: nodes.pack()?,
   };
@@ -384,6 +551,13 @@ async fn handle_game_select_node_request(
   player_id: i32,
   packet: proto::flo_connect::PacketGameSelectNodeRequest,
 ) -> Result<()> {
+  state
+    .games
+    .send(CheckNodeQuota {
+      game_id: packet.game_id,
+      node_id: packet.node_id,
+    })
+    .await??;
   state
     .games
     .send_to(
@@ -428,6 +602,329 @@ async fn handle_game_start_player_client_info_request(
   Ok(())
 }
 
+// invalid commands are not fatal to the lobby connection
+async fn handle_game_command_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameCommandRequest,
+) {
+  let game_id = packet.game_id;
+  let command = packet.command();
+  if let Err(err) = state
+    .games
+    .send_to(game_id, GameCommandRequest { player_id, command })
+    .await
+  {
+    tracing::warn!(game_id, player_id, "game command {:?}: {}", command, err);
+  }
+}
+
+// invalid metadata is not fatal to the lobby connection
+async fn handle_game_metadata_update_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameMetadataUpdateRequest,
+) {
+  let game_id = packet.game_id;
+  let metadata = packet.metadata.map(Into::into).unwrap_or_default();
+  if let Err(err) = state
+    .games
+    .send_to(
+      game_id,
+      UpdateGameMetadata {
+        player_id,
+        metadata,
+      },
+    )
+    .await
+  {
+    tracing::warn!(game_id, player_id, "update game metadata: {}", err);
+  }
+}
+
+// the game may have ended or the player left before the request arrived
+async fn handle_game_resync_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameResyncRequest,
+) {
+  let game_id = packet.game_id;
+  tracing::debug!(
+    game_id,
+    player_id,
+    revision = packet.revision,
+    "game resync requested"
+  );
+  if let Err(err) = state.games.send_to(game_id, ResyncGame { player_id }).await {
+    tracing::warn!(game_id, player_id, "resync game: {}", err);
+  }
+}
+
+// rejected votes are not fatal to the lobby connection
+async fn handle_game_map_vote_start_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameMapVoteStartRequest,
+) {
+  let game_id = packet.game_id;
+  if let Err(err) = state
+    .games
+    .send_to(
+      game_id,
+      StartMapVote {
+        player_id,
+        map_sha1s: packet.map_sha1s,
+      },
+    )
+    .await
+  {
+    tracing::warn!(game_id, player_id, "start map vote: {}", err);
+  }
+}
+
+async fn handle_game_map_vote_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketGameMapVoteRequest,
+) {
+  let game_id = packet.game_id;
+  if packet.map_index < 0 {
+    return;
+  }
+  if let Err(err) = state
+    .games
+    .send_to(
+      game_id,
+      CastMapVote {
+        player_id,
+        map_index: packet.map_index as usize,
+      },
+    )
+    .await
+  {
+    tracing::debug!(game_id, player_id, "map vote: {}", err);
+  }
+}
+
+// the controller only relays keys and ciphertexts of the end-to-end encrypted lobby chat
+async fn handle_lobby_chat_key_update_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketLobbyChatKeyUpdateRequest,
+) {
+  let game_id = packet.game_id;
+  if let Err(err) = state
+    .games
+    .send_to(
+      game_id,
+      UpdateLobbyChatKey {
+        player_id,
+        public_key: packet.public_key,
+      },
+    )
+    .await
+  {
+    tracing::warn!(game_id, player_id, "update lobby chat key: {}", err);
+  }
+}
+
+async fn handle_lobby_chat_message_send_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketLobbyChatMessageSendRequest,
+) {
+  let game_id = packet.game_id;
+  if let Err(err) = state
+    .games
+    .send_to(
+      game_id,
+      SendLobbyChatMessage {
+        player_id,
+        envelopes: packet.envelopes,
+      },
+    )
+    .await
+  {
+    tracing::debug!(game_id, player_id, "lobby chat message: {}", err);
+  }
+}
+
+// the joined player receives the game info, rejections are sent back
+async fn handle_quick_join_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketQuickJoinRequest,
+) -> Result<()> {
+  let filters = packet.filters.map(Into::into).unwrap_or_default();
+  if let Err(err) = quick_join(&state, player_id, filters).await {
+    tracing::debug!(player_id, "quick join: {}", err);
+    let packet = proto::flo_connect::PacketQuickJoinReject {
+      message: err.to_string(),
+    };
+    state
+      .player_packet_sender
+      .send(player_id, packet.encode_as_frame()?)
+      .await?;
+  }
+  Ok(())
+}
+
+enum ChatRequest {
+  Join(proto::flo_connect::PacketChatChannelJoinRequest),
+  Leave(proto::flo_connect::PacketChatChannelLeaveRequest),
+  Send(proto::flo_connect::PacketChatMessageSendRequest),
+}
+
+impl From<proto::flo_connect::PacketChatChannelJoinRequest> for ChatRequest {
+  fn from(v: proto::flo_connect::PacketChatChannelJoinRequest) -> Self {
+    ChatRequest::Join(v)
+  }
+}
+
+impl From<proto::flo_connect::PacketChatChannelLeaveRequest> for ChatRequest {
+  fn from(v: proto::flo_connect::PacketChatChannelLeaveRequest) -> Self {
+    ChatRequest::Leave(v)
+  }
+}
+
+impl From<proto::flo_connect::PacketChatMessageSendRequest> for ChatRequest {
+  fn from(v: proto::flo_connect::PacketChatMessageSendRequest) -> Self {
+    ChatRequest::Send(v)
+  }
+}
+
+// chat errors are sent back, they are not fatal to the lobby connection
+async fn handle_chat_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  channel_id: i32,
+  req: ChatRequest,
+) -> Result<()> {
+  use crate::chat::{JoinChatChannel, LeaveChatChannel, SendChatMessage};
+  let res = match req {
+    ChatRequest::Join(packet) => {
+      state
+        .chat
+        .send(JoinChatChannel {
+          player_id,
+          kind: ChatChannelKind::unpack_enum(packet.kind()),
+          key: packet.key,
+        })
+        .await?
+    }
+    ChatRequest::Leave(packet) => {
+      state
+        .chat
+        .send(LeaveChatChannel {
+          player_id,
+          channel_id: packet.channel_id,
+        })
+        .await?
+    }
+    ChatRequest::Send(packet) => {
+      state
+        .chat
+        .send(SendChatMessage {
+          player_id,
+          channel_id: packet.channel_id,
+          content: packet.content,
+        })
+        .await?
+    }
+  };
+  if let Err(err) = res {
+    tracing::debug!(player_id, channel_id, "chat: {}", err);
+    let mut packet = proto::flo_connect::PacketChatReject {
+      channel_id,
+      message: err.to_string(),
+      ..Default::default()
+    };
+    packet.set_reason(crate::chat::reject_reason(&err));
+    state
+      .player_packet_sender
+      .send(player_id, packet.encode_as_frame()?)
+      .await?;
+  }
+  Ok(())
+}
+
+enum ClanRequest {
+  Create(proto::flo_connect::PacketClanCreateRequest),
+  Invite(proto::flo_connect::PacketClanInviteRequest),
+  InviteReply(proto::flo_connect::PacketClanInviteReplyRequest),
+  Leave(proto::flo_connect::PacketClanLeaveRequest),
+  MemberUpdate(proto::flo_connect::PacketClanMemberUpdateRequest),
+}
+
+impl From<proto::flo_connect::PacketClanCreateRequest> for ClanRequest {
+  fn from(v: proto::flo_connect::PacketClanCreateRequest) -> Self {
+    ClanRequest::Create(v)
+  }
+}
+
+impl From<proto::flo_connect::PacketClanInviteRequest> for ClanRequest {
+  fn from(v: proto::flo_connect::PacketClanInviteRequest) -> Self {
+    ClanRequest::Invite(v)
+  }
+}
+
+impl From<proto::flo_connect::PacketClanInviteReplyRequest> for ClanRequest {
+  fn from(v: proto::flo_connect::PacketClanInviteReplyRequest) -> Self {
+    ClanRequest::InviteReply(v)
+  }
+}
+
+impl From<proto::flo_connect::PacketClanLeaveRequest> for ClanRequest {
+  fn from(v: proto::flo_connect::PacketClanLeaveRequest) -> Self {
+    ClanRequest::Leave(v)
+  }
+}
+
+impl From<proto::flo_connect::PacketClanMemberUpdateRequest> for ClanRequest {
+  fn from(v: proto::flo_connect::PacketClanMemberUpdateRequest) -> Self {
+    ClanRequest::MemberUpdate(v)
+  }
+}
+
+// clan errors are sent back, they are not fatal to the lobby connection
+async fn handle_clan_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  req: ClanRequest,
+) -> Result<()> {
+  use crate::clan::db::MemberUpdate;
+  use crate::clan::ClanRole;
+  let res = match req {
+    ClanRequest::Create(packet) => {
+      crate::clan::create(&state, player_id, &packet.tag, &packet.name).await
+    }
+    ClanRequest::Invite(packet) => crate::clan::invite(&state, player_id, packet.player_id).await,
+    ClanRequest::InviteReply(packet) => {
+      crate::clan::reply_invite(&state, player_id, packet.clan_id, packet.accept).await
+    }
+    ClanRequest::Leave(_) => crate::clan::leave(&state, player_id).await,
+    ClanRequest::MemberUpdate(packet) => {
+      let update = if packet.remove {
+        MemberUpdate::Remove
+      } else {
+        MemberUpdate::SetRole(ClanRole::unpack_enum(packet.role()))
+      };
+      crate::clan::update_member(&state, player_id, packet.player_id, update).await
+    }
+  };
+  if let Err(err) = res {
+    tracing::debug!(player_id, "clan: {}", err);
+    let packet = proto::flo_connect::PacketClanReject {
+      message: err.to_string(),
+    };
+    state
+      .player_packet_sender
+      .send(player_id, packet.encode_as_frame()?)
+      .await?;
+  }
+  Ok(())
+}
+
 enum PlayerMuteListUpdate {
   Add(proto::flo_connect::PacketPlayerMuteAddRequest),
   Remove(proto::flo_connect::PacketPlayerMuteRemoveRequest),
@@ -452,12 +949,75 @@ async fn handle_player_mute_list_update_request(
 ) -> Result<()> {
   state
     .db
-    .exec(move |conn| match update {
+    .exec(crate::profiling::db(move |conn| match update {
       PlayerMuteListUpdate::Add(req) => crate::player::db::add_mute(conn, player_id, req.player_id),
       PlayerMuteListUpdate::Remove(req) => {
         crate::player::db::remove_mute(conn, player_id, req.player_id)
       }
-    })
+    }))
+    .await?;
+  Ok(())
+}
+
+async fn handle_player_privacy_update_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketPlayerPrivacyUpdateRequest,
+) -> Result<()> {
+  use crate::player::privacy::PlayerPrivacy;
+  let privacy = packet.privacy.map(PlayerPrivacy::from).unwrap_or_default();
+  state
+    .db
+    .exec(crate::profiling::db(move |conn| {
+      crate::player::privacy::set_privacy(conn, player_id, privacy)
+    }))
+    .await?;
+  let packet = proto::flo_connect::PacketPlayerPrivacyUpdate {
+    privacy: Some(privacy.into()),
+  };
+  state
+    .player_packet_sender
+    .send(player_id, packet.encode_as_frame()?)
+    .await?;
+  Ok(())
+}
+
+async fn handle_player_notification_subscribe_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketPlayerNotificationSubscribeRequest,
+) -> Result<()> {
+  let channel = NotificationChannel::unpack_enum(packet.channel());
+  if let Err(err) = crate::notification::validate_target(channel, &packet.target) {
+    tracing::debug!(player_id, "notification subscribe: {}", err);
+    return Ok(());
+  }
+  let res = state
+    .db
+    .exec(crate::profiling::db(move |conn| {
+      crate::notification::db::subscribe(conn, player_id, channel, &packet.target)
+    }))
+    .await;
+  match res {
+    Err(ExecutorError::Task(err @ Error::NotificationSubscriptionLimit(_))) => {
+      tracing::debug!(player_id, "notification subscribe: {}", err);
+      Ok(())
+    }
+    res => res.map_err(Into::into),
+  }
+}
+
+async fn handle_player_notification_unsubscribe_request(
+  state: ControllerStateRef,
+  player_id: i32,
+  packet: proto::flo_connect::PacketPlayerNotificationUnsubscribeRequest,
+) -> Result<()> {
+  let channel = NotificationChannel::unpack_enum(packet.channel());
+  state
+    .db
+    .exec(crate::profiling::db(move |conn| {
+      crate::notification::db::unsubscribe(conn, player_id, channel, &packet.target)
+    }))
     .await?;
   Ok(())
 }
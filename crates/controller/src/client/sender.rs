@@ -1,13 +1,17 @@
 use flo_net::packet::*;
 use flo_net::proto::flo_connect::*;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 use crate::error::*;
 
 pub type PlayerReceiver = Receiver<PlayerSenderMessage>;
+/// Frames encoded once and shared by all recipients of a broadcast
+pub type SharedFrames = Arc<[EncodedFrame]>;
 pub enum PlayerSenderMessage {
   Frame(Frame),
+  Encoded(SharedFrames),
   Disconnect(ClientDisconnectReason),
 }
 
@@ -50,6 +54,13 @@ impl PlayerSender {
       .is_ok()
   }
 
+  pub fn try_send_encoded(&mut self, frames: SharedFrames) -> bool {
+    self
+      .sender
+      .try_send(PlayerSenderMessage::Encoded(frames))
+      .is_ok()
+  }
+
   pub async fn send_frame(&mut self, frame: Frame) -> Result<()> {
     self
       .sender
@@ -87,6 +87,17 @@ pub struct FloGrpcInterceptor {
   api_client_map: Arc<ArcSwap<BTreeMap<Vec<u8>, ApiClient>>>,
 }
 
+impl FloGrpcInterceptor {
+  /// Also used to authenticate REST requests
+  pub fn get_api_client_id(&self, secret: &[u8]) -> Option<i32> {
+    self
+      .api_client_map
+      .load()
+      .get(secret)
+      .map(|client| client.id)
+  }
+}
+
 impl Interceptor for FloGrpcInterceptor {
   fn call(&mut self, mut req: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
     let secret = req.metadata().get(REQUEST_META_SECRET);
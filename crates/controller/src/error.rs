@@ -24,14 +24,35 @@ pub enum Error {
   NodeRequestTimeout,
   #[error("Node request cancelled")]
   NodeRequestCancelled,
+  #[error("Node start queue full")]
+  NodeStartQueueFull,
+  #[error("Node busy, estimated wait: {}s", estimated_wait.as_secs())]
+  NodeBusy {
+    node_id: i32,
+    estimated_wait: std::time::Duration,
+  },
+  #[error("Node config rejected: {0}")]
+  NodeConfigRejected(String),
   #[error("Invalid node address: {0}")]
   InvalidNodeAddress(String),
+  #[error("Node {node_id} does not support game version {version}")]
+  NodeVersionNotSupported { node_id: i32, version: String },
+  #[error("No node supports game version {0}")]
+  NoNodeSupportsVersion(String),
   #[error("Player stream closed")]
   PlayerStreamClosed,
   #[error("Player token expired")]
   PlayerTokenExpired,
+  #[error("Token scope is required")]
+  TokenScopeRequired,
+  #[error("Token scope not allowed")]
+  TokenScopeNotAllowed,
+  #[error("Token signing key unknown or retired")]
+  TokenKeyUnknown,
   #[error("Join link expired")]
   JoinTokenExpired,
+  #[error("Connect challenge failed")]
+  ConnectChallengeFailed,
   #[error("You are not the host player")]
   PlayerNotHost,
   #[error("Player not found")]
@@ -52,20 +73,100 @@ pub enum Error {
   GameLeaveRejected(flo_net::proto::flo_node::UpdateSlotClientStatusRejectReason),
   #[error("Game node not selected")]
   GameNodeNotSelected,
+  #[error("Game has no save")]
+  GameSaveNotFound,
+  #[error("Saved slot layout doesn't match the game")]
+  GameSaveInvalid,
   #[error("Slot update denied")]
   GameSlotUpdateDenied,
+  #[error("Slot layout is locked")]
+  GameSlotLayoutLocked,
   #[error("Game already started")]
   GameStarted,
   #[error("Game not in starting state")]
   GameNotStarting,
+  #[error("Illegal game stage transition: {from:?} -> {to:?}")]
+  GameStageTransition {
+    from: crate::game::lifecycle::GameStage,
+    to: crate::game::lifecycle::GameStage,
+  },
+  #[error("Game is not running")]
+  GameNotRunning,
+  #[error("Game has not ended")]
+  GameNotEnded,
+  #[error("Game result already reported")]
+  GameResultReported,
+  #[error("Invalid game metadata: {0}")]
+  GameMetadataInvalid(&'static str),
+  #[error(
+    "A batch must have 1 to {} games, got {0}",
+    crate::game::db::MAX_BATCH_GAMES
+  )]
+  GameBatchSize(usize),
+  #[error("No open lobby matches the quick join filters")]
+  QuickJoinNoLobby,
+  #[error("Chat channel not found")]
+  ChatChannelNotFound,
+  #[error("You are not a member of this chat channel")]
+  ChatNotMember,
+  #[error("You are sending messages too fast")]
+  ChatRateLimited,
+  #[error("You are banned from chat")]
+  ChatBanned,
+  #[error("Message rejected: {0}")]
+  ChatMessageRejected(String),
+  #[error("Chat moderation: {0}")]
+  ChatModeration(String),
+  #[error("Clan not found")]
+  ClanNotFound,
+  #[error("Invalid clan: {0}")]
+  ClanInvalid(String),
+  #[error("Clan tag is already taken")]
+  ClanTagTaken,
+  #[error("Player is already in a clan")]
+  ClanAlreadyMember,
+  #[error("Player is not a member of your clan")]
+  ClanNotMember,
+  #[error("Your clan role does not allow this")]
+  ClanPermissionDenied,
+  #[error("Clan invite not found")]
+  ClanInviteNotFound,
+  #[error("Promote another member to leader before leaving the clan")]
+  ClanLeaderCannotLeave,
+  #[error("Season not found")]
+  SeasonNotFound,
+  #[error("Invalid season: {0}")]
+  SeasonInvalid(&'static str),
+  #[error("Close the current season before opening a new one")]
+  SeasonOpen,
+  #[error("Season is already closed")]
+  SeasonClosed,
+  #[error("{0}")]
+  Maintenance(String),
   #[error("This map has no player slot")]
   MapHasNoPlayer,
+  #[error("Map is not registered")]
+  MapNotRegistered,
+  #[error("A map vote is already in progress")]
+  MapVoteInProgress,
+  #[error("No map vote in progress")]
+  MapVoteNotFound,
+  #[error("Invalid map vote: {0}")]
+  MapVoteInvalid(&'static str),
+  #[error("End-to-end lobby chat is not enabled")]
+  LobbyChatDisabled,
+  #[error("Invalid lobby chat request: {0}")]
+  LobbyChatInvalid(&'static str),
   #[error("Player not in game")]
   PlayerNotInGame,
   #[error("Player already in game")]
   PlayerAlreadyInGame,
+  #[error("You can't join ladder lobbies until {0}")]
+  PlayerPenalized(chrono::DateTime<chrono::Utc>),
   #[error("Player slot not found")]
   PlayerSlotNotFound,
+  #[error("Surrender votes are only available in team games")]
+  SurrenderNotTeamGame,
   #[error("Send to player channel timeout")]
   PlayerChannelSendTimeout,
   #[error("Player channel closed")]
@@ -76,6 +177,11 @@ pub enum Error {
   InvalidPlayerSourceState,
   #[error("Actor not found")]
   ActorNotFound,
+  #[error("Actor did not respond within {timeout:?} ({attempts} attempts)")]
+  ActorTimeout {
+    timeout: std::time::Duration,
+    attempts: usize,
+  },
   #[error("Too many players")]
   TooManyPlayers,
   #[error("Game has no player")]
@@ -86,6 +192,16 @@ pub enum Error {
   PlayerTeamInvalid,
   #[error("Player not belongs to the current API client")]
   PlayerOwnerCheckFailed,
+  #[error("Quota exceeded: {0}")]
+  QuotaExceeded(#[from] crate::game::state::quota::QuotaExceeded),
+  #[error("Notification relay: {0}")]
+  NotificationRelay(String),
+  #[error("Invalid notification target: {0}")]
+  NotificationTargetInvalid(&'static str),
+  #[error("Notification subscription limit reached: {0}")]
+  NotificationSubscriptionLimit(usize),
+  #[error("Job timeout")]
+  JobTimeout,
   #[error("Operation timeout: {0}")]
   Timeout(anyhow::Error),
   #[error("net: {0}")]
@@ -100,6 +216,8 @@ pub enum Error {
   JsonWebToken(#[from] jsonwebtoken::errors::Error),
   #[error("proto: {0}")]
   Proto(#[from] s2_grpc_utils::result::Error),
+  #[error("http: {0}")]
+  Http(#[from] hyper::Error),
   #[error("gRPC transport: {0}")]
   GrpcTransport(#[from] tonic::transport::Error),
 }
@@ -126,8 +244,41 @@ impl From<Error> for Status {
       | e @ Error::MapHasNoPlayer
       | e @ Error::GameFull
       | e @ Error::GameNotCancellable
-      | e @ Error::JoinTokenExpired => Status::invalid_argument(e.to_string()),
+      | e @ Error::JoinTokenExpired
+      | e @ Error::GameMetadataInvalid(_)
+      | e @ Error::GameBatchSize(_) => Status::invalid_argument(e.to_string()),
       e @ Error::PlayerTokenExpired => Status::unauthenticated(e.to_string()),
+      e @ Error::TokenKeyUnknown => Status::unauthenticated(e.to_string()),
+      e @ Error::TokenScopeNotAllowed => Status::permission_denied(e.to_string()),
+      e @ Error::TokenScopeRequired => Status::invalid_argument(e.to_string()),
+      e @ Error::QuotaExceeded(_) => Status::resource_exhausted(e.to_string()),
+      e @ Error::Maintenance(_) => Status::unavailable(e.to_string()),
+      e @ Error::QuickJoinNoLobby => Status::not_found(e.to_string()),
+      e @ Error::ChatChannelNotFound => Status::not_found(e.to_string()),
+      e @ Error::ChatNotMember | e @ Error::ChatBanned => Status::permission_denied(e.to_string()),
+      e @ Error::ChatRateLimited => Status::resource_exhausted(e.to_string()),
+      e @ Error::ChatMessageRejected(_) => Status::invalid_argument(e.to_string()),
+      e @ Error::ClanNotFound | e @ Error::ClanInviteNotFound => Status::not_found(e.to_string()),
+      e @ Error::ClanInvalid(_) => Status::invalid_argument(e.to_string()),
+      e @ Error::ClanTagTaken | e @ Error::ClanAlreadyMember => {
+        Status::already_exists(e.to_string())
+      }
+      e @ Error::ClanNotMember
+      | e @ Error::ClanPermissionDenied
+      | e @ Error::ClanLeaderCannotLeave => Status::permission_denied(e.to_string()),
+      e @ Error::SeasonNotFound => Status::not_found(e.to_string()),
+      e @ Error::SeasonInvalid(_) => Status::invalid_argument(e.to_string()),
+      e @ Error::SeasonOpen | e @ Error::SeasonClosed => Status::failed_precondition(e.to_string()),
+      e @ Error::NodeVersionNotSupported { .. } | e @ Error::NoNodeSupportsVersion(_) => {
+        Status::failed_precondition(e.to_string())
+      }
+      e @ Error::PlayerPenalized(_) => Status::failed_precondition(e.to_string()),
+      e @ Error::GameNotEnded => Status::failed_precondition(e.to_string()),
+      e @ Error::GameResultReported => Status::already_exists(e.to_string()),
+      e @ Error::ActorTimeout { .. } => Status::deadline_exceeded(e.to_string()),
+      e @ Error::NodeStartQueueFull | e @ Error::NodeBusy { .. } => {
+        Status::resource_exhausted(e.to_string())
+      }
       Error::JsonWebToken(e) => Status::unauthenticated(e.to_string()),
       e => Status::internal(e.to_string()),
     }
@@ -161,6 +312,17 @@ impl From<flo_state::error::Error> for Error {
   }
 }
 
+impl From<flo_task::SendError> for Error {
+  fn from(err: flo_task::SendError) -> Self {
+    match err {
+      flo_task::SendError::Timeout { timeout, attempts } => {
+        Self::ActorTimeout { timeout, attempts }
+      }
+      flo_task::SendError::Actor(err) => err.into(),
+    }
+  }
+}
+
 impl From<flo_state::RegistryError> for Error {
   fn from(err: flo_state::RegistryError) -> Self {
     match err {
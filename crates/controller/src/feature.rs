@@ -0,0 +1,145 @@
+//! Feature flags of client-visible features, evaluated per player when the player connects.
+//!
+//! Defaults are set by `FLO_FEATURE_FLAGS`, e.g. `ready_check=100,chat_relay=10`,
+//! rows of the `feature_flag` table override them. A disabled flag is off for every player,
+//! otherwise it's on for the listed players and a stable `rollout_percent` share of the others.
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::feature_flag;
+use diesel::prelude::*;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+
+pub static FLO_FEATURE_FLAGS: Lazy<Vec<FeatureFlag>> = Lazy::new(|| {
+  std::env::var("FLO_FEATURE_FLAGS")
+    .map(|v| parse_env_flags(&v))
+    .unwrap_or_default()
+});
+
+#[derive(Debug, Clone, PartialEq, Queryable)]
+pub struct FeatureFlag {
+  pub name: String,
+  pub enabled: bool,
+  pub rollout_percent: i32,
+  pub player_ids: Vec<i32>,
+}
+
+impl FeatureFlag {
+  pub fn is_enabled_for(&self, player_id: i32) -> bool {
+    if !self.enabled {
+      return false;
+    }
+    if self.player_ids.contains(&player_id) {
+      return true;
+    }
+    (bucket(&self.name, player_id) as i32) < self.rollout_percent.clamp(0, 100)
+  }
+}
+
+/// FNV-1a of the flag name and the player id, stable across restarts and releases
+/// so raising the percent only adds players
+fn bucket(name: &str, player_id: i32) -> u32 {
+  let mut hash: u32 = 0x811c9dc5;
+  for b in name.bytes().chain(player_id.to_le_bytes().iter().cloned()) {
+    hash ^= b as u32;
+    hash = hash.wrapping_mul(0x01000193);
+  }
+  hash % 100
+}
+
+fn parse_env_flags(value: &str) -> Vec<FeatureFlag> {
+  value
+    .split(',')
+    .filter_map(|item| {
+      let (name, percent) = item.split_once('=')?;
+      let name = name.trim();
+      let percent: i32 = percent.trim().parse().ok()?;
+      if name.is_empty() {
+        return None;
+      }
+      Some(FeatureFlag {
+        name: name.to_string(),
+        enabled: true,
+        rollout_percent: percent,
+        player_ids: vec![],
+      })
+    })
+    .collect()
+}
+
+/// Env defaults merged with the stored flags
+pub fn list_flags(conn: &DbConn) -> Result<Vec<FeatureFlag>> {
+  let stored: Vec<FeatureFlag> = feature_flag::table
+    .select((
+      feature_flag::name,
+      feature_flag::enabled,
+      feature_flag::rollout_percent,
+      feature_flag::player_ids,
+    ))
+    .load(conn)?;
+  Ok(merge_flags(&FLO_FEATURE_FLAGS, stored))
+}
+
+fn merge_flags(defaults: &[FeatureFlag], stored: Vec<FeatureFlag>) -> Vec<FeatureFlag> {
+  let mut map: BTreeMap<String, FeatureFlag> = defaults
+    .iter()
+    .map(|flag| (flag.name.clone(), flag.clone()))
+    .collect();
+  for flag in stored {
+    map.insert(flag.name.clone(), flag);
+  }
+  map.into_values().collect()
+}
+
+/// Names of the flags enabled for the player
+pub fn get_player_features(conn: &DbConn, player_id: i32) -> Result<Vec<String>> {
+  Ok(
+    list_flags(conn)?
+      .into_iter()
+      .filter(|flag| flag.is_enabled_for(player_id))
+      .map(|flag| flag.name)
+      .collect(),
+  )
+}
+
+#[test]
+fn test_feature_flag() {
+  let flags = parse_env_flags("ready_check=100, chat_relay = 10,invalid,=5,x=y");
+  assert_eq!(
+    flags
+      .iter()
+      .map(|f| (f.name.as_str(), f.rollout_percent))
+      .collect::<Vec<_>>(),
+    vec![("ready_check", 100), ("chat_relay", 10)]
+  );
+
+  assert!((1..1000).all(|id| flags[0].is_enabled_for(id)));
+  let enabled = (1..1000).filter(|id| flags[1].is_enabled_for(*id)).count();
+  assert!(enabled > 50 && enabled < 150, "{}", enabled);
+  assert_eq!(bucket("chat_relay", 42), bucket("chat_relay", 42));
+
+  let merged = merge_flags(
+    &flags,
+    vec![
+      FeatureFlag {
+        name: "ready_check".to_string(),
+        enabled: false,
+        rollout_percent: 100,
+        player_ids: vec![],
+      },
+      FeatureFlag {
+        name: "beta".to_string(),
+        enabled: true,
+        rollout_percent: 0,
+        player_ids: vec![7],
+      },
+    ],
+  );
+  assert_eq!(merged.len(), 3);
+  let get = |name: &str| merged.iter().find(|f| f.name == name).unwrap();
+  // kill switch
+  assert!(!get("ready_check").is_enabled_for(1));
+  assert!(get("beta").is_enabled_for(7));
+  assert!(!get("beta").is_enabled_for(8));
+}
@@ -11,12 +11,12 @@ use crate::error::*;
 use crate::game::slots::{UsedSlot, UsedSlotInfo};
 use crate::game::state::GameStatusUpdate;
 use crate::game::{
-  Computer, CreateGameSlot, Game, GameEntry, GameStatus, Race, Slot, SlotClientStatus,
-  SlotSettings, SlotStatus, Slots,
+  Computer, CreateGameSlot, Game, GameEntry, GameMetadata, GameSave, GameStatus, Race, SavedGame,
+  Slot, SlotClientStatus, SlotSettings, SlotStatus, Slots,
 };
 use crate::map::Map;
 use crate::node::{NodeRef, NodeRefColumns, PlayerToken};
-use crate::player::{PlayerRef, PlayerRefColumns};
+use crate::player::{PlayerRef, PlayerRefColumns, PlayerSource};
 use crate::schema::{game, game_used_slot, node, player};
 use diesel::pg::expression::dsl::{all, any};
 
@@ -43,7 +43,7 @@ pub struct QueryGameParams {
   pub since_id: Option<i32>,
 }
 
-#[derive(Debug, S2ProtoPack)]
+#[derive(Debug, Serialize, S2ProtoPack)]
 #[s2_grpc(message_type = "flo_grpc::controller::ListGamesReply")]
 pub struct QueryGame {
   pub games: Vec<GameEntry>,
@@ -168,7 +168,11 @@ pub struct CreateGameParams {
 }
 
 /// Creates a game, make the creator as the first player
-pub fn create(conn: &DbConn, params: CreateGameParams) -> Result<Game> {
+pub fn create(
+  conn: &DbConn,
+  params: CreateGameParams,
+  target_version: Option<String>,
+) -> Result<Game> {
   let max_players = params.map.players.len();
 
   if max_players == 0 {
@@ -182,11 +186,17 @@ pub fn create(conn: &DbConn, params: CreateGameParams) -> Result<Game> {
   let meta = Meta {
     map: params.map,
     created_by: player.into(),
+    metadata: GameMetadata::default(),
+    target_version,
+    locked_layout: false,
+    saves: vec![],
+    saved_game: None,
   };
 
   let meta_value = serde_json::to_value(&meta)?;
 
   let insert = GameInsert {
+    id: None,
     name: &params.name,
     map_name: &meta.map.name,
     is_private: params.is_private,
@@ -198,6 +208,7 @@ pub fn create(conn: &DbConn, params: CreateGameParams) -> Result<Game> {
     locked: false,
     node_id: None,
     mask_player_names: false,
+    season_id: None,
   };
 
   let row = conn.transaction(|| -> Result<_> {
@@ -206,7 +217,8 @@ pub fn create(conn: &DbConn, params: CreateGameParams) -> Result<Game> {
       .returning(game::dsl::id)
       .get_result(conn)?;
     let row = get(conn, id)?;
-    upsert_used_slots(conn, row.id, slots.as_used())?;
+    insert_used_slots(conn, slots.as_used().into_iter().map(|slot| (id, slot)))?;
+    crate::map::db::register(conn, &meta.map)?;
     Ok(row)
   })?;
   Ok(row.into_game(meta, slots.into_inner())?)
@@ -230,33 +242,283 @@ pub fn create_as_bot(
   api_client_id: i32,
   api_player_id: i32,
   params: CreateGameAsBotParams,
+  target_version: Option<String>,
+  saved_game: Option<SavedGame>,
 ) -> Result<Game> {
-  use std::collections::{BTreeMap, BTreeSet};
-  let max_players = params.map.players.len();
+  let players = get_bot_game_players(conn, api_client_id, api_player_id, &[&params])?;
+  let mut game = BotGame::new(api_player_id, params, &players, target_version)?;
+  game.meta.saved_game = saved_game;
 
-  if max_players == 0 {
-    return Err(Error::MapHasNoPlayer);
+  let row = conn.transaction(|| -> Result<_> {
+    let season_id = crate::season::db::get_current_id(conn, api_client_id)?;
+    let insert = game.insert(None, season_id)?;
+    let id: i32 = diesel::insert_into(game::table)
+      .values(&insert)
+      .returning(game::dsl::id)
+      .get_result(conn)?;
+    let row = get(conn, id)?;
+    insert_used_slots(
+      conn,
+      game.slots.as_used().into_iter().map(|slot| (id, slot)),
+    )?;
+    crate::map::db::register(conn, &game.meta.map)?;
+    Ok(row)
+  })?;
+
+  Ok(row.into_game(game.meta, game.slots.into_inner())?)
+}
+
+/// Parameters recreating a game of the API client with the same map, node, name
+/// and players in the same slots with the same team, color, race and handicap.
+/// Returns the API player that created the game
+pub fn get_rematch_params(
+  conn: &DbConn,
+  api_client_id: i32,
+  game_id: i32,
+) -> Result<(i32, CreateGameAsBotParams)> {
+  let game = get_full(conn, game_id)?;
+  let created_by_client_id: i32 = player::table
+    .find(game.created_by.id)
+    .select(player::api_client_id)
+    .first(conn)?;
+  if game.created_by.source != PlayerSource::Api || created_by_client_id != api_client_id {
+    return Err(Error::GameNotFound);
   }
+  let node_id = game
+    .node
+    .as_ref()
+    .map(|node| node.id)
+    .ok_or_else(|| Error::GameNodeNotSelected)?;
+  Ok((game.created_by.id, rematch_params(game, node_id)))
+}
 
-  if params.slots.len() > 24 {
-    return Err(Error::TooManyPlayers);
+fn rematch_params(game: Game, node_id: i32) -> CreateGameAsBotParams {
+  CreateGameAsBotParams {
+    name: game.name,
+    map: game.map,
+    is_private: game.is_private,
+    is_live: game.is_live,
+    node_id,
+    slots: game
+      .slots
+      .into_iter()
+      .map(|slot| CreateGameSlot {
+        player_id: slot.player.map(|player| player.id),
+        settings: slot.settings,
+      })
+      .collect(),
+    mask_player_names: Some(game.mask_player_names),
   }
+}
+
+/// Parameters resuming the latest save of a game of the API client,
+/// players who saved keep their slots and the slots of players who left before the save are closed
+pub fn get_resume_params(
+  conn: &DbConn,
+  api_client_id: i32,
+  game_id: i32,
+) -> Result<(i32, CreateGameAsBotParams, GameSave)> {
+  let (api_player_id, mut params) = get_rematch_params(conn, api_client_id, game_id)?;
+  let meta: Value = game::table
+    .find(game_id)
+    .select(game::dsl::meta)
+    .first(conn)?;
+  let meta: Meta = serde_json::from_value(meta)?;
+  let save = meta
+    .saves
+    .into_iter()
+    .last()
+    .ok_or_else(|| Error::GameSaveNotFound)?;
+  params.slots = resume_slots(params.slots, &save)?;
+  Ok((api_player_id, params, save))
+}
 
-  let (player_slots, referee_slots): (Vec<_>, Vec<_>) = params
-    .slots
+// slot indexes are reported by the node, every saved player has to be in the slot it had in the game
+fn resume_slots(slots: Vec<CreateGameSlot>, save: &GameSave) -> Result<Vec<CreateGameSlot>> {
+  let is_saved = |slot_index: usize, slot: &CreateGameSlot| {
+    slot
+      .player_id
+      .map(|player_id| save.slots.get(&player_id) == Some(&slot_index))
+      .unwrap_or(false)
+  };
+  let resumed = slots
     .iter()
     .enumerate()
-    .filter(|(_idx, s)| s.settings.status == SlotStatus::Occupied)
-    .partition(|s| s.1.settings.team != 24);
+    .filter(|(i, slot)| is_saved(*i, slot))
+    .count();
+  if resumed != save.slots.len() {
+    return Err(Error::GameSaveInvalid);
+  }
+  Ok(
+    slots
+      .into_iter()
+      .enumerate()
+      .map(|(i, mut slot)| {
+        if slot.player_id.is_some() && !is_saved(i, &slot) {
+          slot.player_id = None;
+          slot.settings.status = SlotStatus::Closed;
+        }
+        slot
+      })
+      .collect(),
+  )
+}
+
+/// Saves kept per game, only the latest one can be resumed
+const MAX_GAME_SAVES: usize = 8;
+
+pub fn add_save(conn: &DbConn, game_id: i32, save: GameSave) -> Result<()> {
+  use game::dsl;
+
+  conn.transaction(|| -> Result<_> {
+    let meta: Value = game::table
+      .find(game_id)
+      .select(dsl::meta)
+      .for_update()
+      .first(conn)
+      .optional()?
+      .ok_or_else(|| Error::GameNotFound)?;
+    let mut meta: Meta = serde_json::from_value(meta)?;
+    meta.saves.push(save);
+    if meta.saves.len() > MAX_GAME_SAVES {
+      meta.saves.remove(0);
+    }
+    diesel::update(game::table.find(game_id))
+      .set(dsl::meta.eq(serde_json::to_value(&meta)?))
+      .execute(conn)?;
+    Ok(())
+  })
+}
+
+pub const MAX_BATCH_GAMES: usize = 64;
+
+/// Lobbies created by an API client from a shared template, e.g. a tournament round
+#[derive(Debug, Deserialize)]
+pub struct CreateGamesAsBotParams {
+  pub template: CreateGameTemplate,
+  pub games: Vec<CreateGameFromTemplate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGameTemplate {
+  pub name: String,
+  pub map: Map,
+  pub is_private: bool,
+  pub is_live: bool,
+  pub node_id: i32,
+  pub mask_player_names: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGameFromTemplate {
+  /// Defaults to the template name followed by the game number
+  pub name: Option<String>,
+  pub slots: Vec<CreateGameSlot>,
+}
 
-  if player_slots.len() > max_players {
-    return Err(Error::TooManyPlayers);
+impl CreateGamesAsBotParams {
+  fn into_games(self) -> Vec<CreateGameAsBotParams> {
+    let template = self.template;
+    self
+      .games
+      .into_iter()
+      .enumerate()
+      .map(|(i, game)| CreateGameAsBotParams {
+        name: game
+          .name
+          .unwrap_or_else(|| format!("{} #{}", template.name, i + 1)),
+        map: template.map.clone(),
+        is_private: template.is_private,
+        is_live: template.is_live,
+        node_id: template.node_id,
+        slots: game.slots,
+        mask_player_names: template.mask_player_names,
+      })
+      .collect()
+  }
+}
+
+/// Creates full games and lock them in one transaction,
+/// ids, games and slots are written with one statement each
+pub fn create_games_as_bot(
+  conn: &DbConn,
+  api_client_id: i32,
+  api_player_id: i32,
+  params: CreateGamesAsBotParams,
+  target_version: Option<String>,
+) -> Result<Vec<Game>> {
+  let params = params.into_games();
+  if params.is_empty() || params.len() > MAX_BATCH_GAMES {
+    return Err(Error::GameBatchSize(params.len()));
   }
 
+  let players = get_bot_game_players(
+    conn,
+    api_client_id,
+    api_player_id,
+    &params.iter().collect::<Vec<_>>(),
+  )?;
+  let games = params
+    .into_iter()
+    .map(|params| BotGame::new(api_player_id, params, &players, target_version.clone()))
+    .collect::<Result<Vec<_>>>()?;
+
+  let rows = conn.transaction(|| -> Result<_> {
+    let ids = reserve_game_ids(conn, games.len())?;
+    let season_id = crate::season::db::get_current_id(conn, api_client_id)?;
+    let inserts = ids
+      .iter()
+      .zip(&games)
+      .map(|(id, game)| game.insert(Some(*id), season_id))
+      .collect::<Result<Vec<_>>>()?;
+    diesel::insert_into(game::table)
+      .values(&inserts)
+      .execute(conn)?;
+    // games of a batch share the template map
+    if let Some(game) = games.first() {
+      crate::map::db::register(conn, &game.meta.map)?;
+    }
+    insert_used_slots(
+      conn,
+      ids.iter().zip(&games).flat_map(|(id, game)| {
+        game
+          .slots
+          .as_used()
+          .into_iter()
+          .map(move |slot| (*id, slot))
+      }),
+    )?;
+    let mut rows: HashMap<i32, GameRowWithRelated> = game::table
+      .filter(game::id.eq_any(&ids))
+      .left_outer_join(node::table)
+      .left_outer_join(player::table)
+      .select(GameRowWithRelated::columns())
+      .load::<GameRowWithRelated>(conn)?
+      .into_iter()
+      .map(|row| (row.id, row))
+      .collect();
+    ids
+      .iter()
+      .map(|id| rows.remove(id).ok_or_else(|| Error::GameNotFound))
+      .collect::<Result<Vec<_>>>()
+  })?;
+
+  rows
+    .into_iter()
+    .zip(games)
+    .map(|(row, game)| row.into_game(game.meta, game.slots.into_inner()))
+    .collect()
+}
+
+fn get_bot_game_players(
+  conn: &DbConn,
+  api_client_id: i32,
+  api_player_id: i32,
+  params: &[&CreateGameAsBotParams],
+) -> Result<HashMap<i32, PlayerRef>> {
   let mut player_ids: Vec<i32> = params
-    .slots
     .iter()
-    .filter_map(|s| s.player_id.clone())
+    .flat_map(|params| params.slots.iter().filter_map(|s| s.player_id.clone()))
     .collect();
 
   if player_ids.is_empty() {
@@ -267,90 +529,168 @@ pub fn create_as_bot(
   player_ids.sort();
   player_ids.dedup();
 
-  let mut players: BTreeMap<_, _> =
+  Ok(
     crate::player::db::get_client_refs_by_ids(conn, api_client_id, &player_ids)?
       .into_iter()
       .map(|p| (p.id, p))
-      .collect();
-  let mut slots = vec![];
-  let mut color_set = BTreeSet::new();
+      .collect(),
+  )
+}
 
-  for (i, slot) in player_slots.iter() {
-    if color_set.contains(&slot.settings.color) {
-      return Err(Error::PlayerColorConflict);
-    }
+#[derive(QueryableByName)]
+struct ReservedId {
+  #[sql_type = "diesel::sql_types::Integer"]
+  id: i32,
+}
 
-    color_set.insert(slot.settings.color);
+fn reserve_game_ids(conn: &DbConn, n: usize) -> Result<Vec<i32>> {
+  use diesel::sql_types::Integer;
+  let ids: Vec<ReservedId> =
+    diesel::sql_query("SELECT nextval('game_id_seq')::integer AS id FROM generate_series(1, $1)")
+      .bind::<Integer, _>(n as i32)
+      .load(conn)?;
+  Ok(ids.into_iter().map(|v| v.id).collect())
+}
+
+/// A validated game created by an API client, not yet inserted
+struct BotGame {
+  name: String,
+  is_private: bool,
+  is_live: bool,
+  node_id: i32,
+  mask_player_names: bool,
+  max_players: usize,
+  api_player_id: i32,
+  meta: Meta,
+  slots: Slots,
+}
 
-    if slot.settings.team < 0 || slot.settings.team > 24 {
-      return Err(Error::PlayerTeamInvalid);
+impl BotGame {
+  fn new(
+    api_player_id: i32,
+    params: CreateGameAsBotParams,
+    players: &HashMap<i32, PlayerRef>,
+    target_version: Option<String>,
+  ) -> Result<Self> {
+    use std::collections::BTreeSet;
+    let max_players = params.map.players.len();
+
+    if max_players == 0 {
+      return Err(Error::MapHasNoPlayer);
     }
 
-    let player = slot.player_id.clone().and_then(|id| players.remove(&id));
-    if slot.player_id.is_some() && player.is_none() {
-      return Err(Error::PlayerNotFound);
+    if params.slots.len() > 24 {
+      return Err(Error::TooManyPlayers);
     }
-    slots.push(UsedSlot {
-      slot_index: *i as i32,
-      settings: slot.settings.clone(),
-      client_status: SlotClientStatus::Pending,
-      player,
-    });
-  }
 
-  for (i, slot) in referee_slots.iter() {
-    let player = slot.player_id.clone().and_then(|id| players.remove(&id));
-    if slot.player_id.is_some() && player.is_none() {
-      return Err(Error::PlayerNotFound);
+    let (player_slots, referee_slots): (Vec<_>, Vec<_>) = params
+      .slots
+      .iter()
+      .enumerate()
+      .filter(|(_idx, s)| s.settings.status == SlotStatus::Occupied)
+      .partition(|s| s.1.settings.team != 24);
+
+    if player_slots.len() > max_players {
+      return Err(Error::TooManyPlayers);
     }
-    slots.push(UsedSlot {
-      slot_index: *i as i32,
-      settings: SlotSettings {
-        color: 0,
-        ..slot.settings.clone()
-      },
-      client_status: SlotClientStatus::Pending,
-      player,
-    });
-  }
 
-  let slots = Slots::from_used(max_players, slots);
+    if params.slots.iter().all(|s| s.player_id.is_none()) {
+      return Err(Error::GameHasNoPlayer);
+    }
 
-  let meta = Meta {
-    map: params.map,
-    created_by: players
-      .remove(&api_player_id)
-      .ok_or_else(|| Error::PlayerNotFound)?
-      .into(),
-  };
+    // each player can take one slot, the API player none
+    let mut used_player_ids = BTreeSet::new();
+    used_player_ids.insert(api_player_id);
+    let mut take_player = |id: Option<i32>| -> Result<Option<PlayerRef>> {
+      match id {
+        Some(id) => match players.get(&id) {
+          Some(player) if used_player_ids.insert(id) => Ok(Some(player.clone())),
+          _ => Err(Error::PlayerNotFound),
+        },
+        None => Ok(None),
+      }
+    };
 
-  let meta_value = serde_json::to_value(&meta)?;
+    let mut slots = vec![];
+    let mut color_set = BTreeSet::new();
 
-  let insert = GameInsert {
-    name: &params.name,
-    map_name: &meta.map.name,
-    is_private: params.is_private,
-    is_live: params.is_live,
-    max_players: max_players as i32,
-    created_by: Some(api_player_id),
-    meta: meta_value,
-    random_seed: rand::random(),
-    locked: true,
-    node_id: Some(params.node_id),
-    mask_player_names: params.mask_player_names.unwrap_or_default(),
-  };
+    for (i, slot) in player_slots.iter() {
+      if color_set.contains(&slot.settings.color) {
+        return Err(Error::PlayerColorConflict);
+      }
 
-  let row = conn.transaction(|| -> Result<_> {
-    let id: i32 = diesel::insert_into(game::table)
-      .values(&insert)
-      .returning(game::dsl::id)
-      .get_result(conn)?;
-    let row = get(conn, id)?;
-    upsert_used_slots(conn, row.id, slots.as_used())?;
-    Ok(row)
-  })?;
+      color_set.insert(slot.settings.color);
 
-  Ok(row.into_game(meta, slots.into_inner())?)
+      if slot.settings.team < 0 || slot.settings.team > 24 {
+        return Err(Error::PlayerTeamInvalid);
+      }
+
+      slots.push(UsedSlot {
+        slot_index: *i as i32,
+        settings: slot.settings.clone(),
+        client_status: SlotClientStatus::Pending,
+        player: take_player(slot.player_id.clone())?,
+      });
+    }
+
+    for (i, slot) in referee_slots.iter() {
+      slots.push(UsedSlot {
+        slot_index: *i as i32,
+        settings: SlotSettings {
+          color: 0,
+          ..slot.settings.clone()
+        },
+        client_status: SlotClientStatus::Pending,
+        player: take_player(slot.player_id.clone())?,
+      });
+    }
+
+    let slots = Slots::from_used(max_players, slots);
+
+    let meta = Meta {
+      map: params.map,
+      created_by: players
+        .get(&api_player_id)
+        .cloned()
+        .ok_or_else(|| Error::PlayerNotFound)?
+        .into(),
+      metadata: GameMetadata::default(),
+      target_version,
+      locked_layout: true,
+      saves: vec![],
+      saved_game: None,
+    };
+
+    Ok(Self {
+      name: params.name,
+      is_private: params.is_private,
+      is_live: params.is_live,
+      node_id: params.node_id,
+      mask_player_names: params.mask_player_names.unwrap_or_default(),
+      max_players,
+      api_player_id,
+      meta,
+      slots,
+    })
+  }
+
+  fn insert(&self, id: Option<i32>, season_id: Option<i32>) -> Result<GameInsert> {
+    Ok(GameInsert {
+      id,
+      name: &self.name,
+      map_name: &self.meta.map.name,
+      is_private: self.is_private,
+      is_live: self.is_live,
+      max_players: self.max_players as i32,
+      created_by: Some(self.api_player_id),
+      meta: serde_json::to_value(&self.meta)?,
+      random_seed: rand::random(),
+      locked: true,
+      node_id: Some(self.node_id),
+      mask_player_names: self.mask_player_names,
+      season_id,
+    })
+  }
 }
 
 /// Adds a player into a game
@@ -384,6 +724,52 @@ pub fn add_player(conn: &DbConn, game_id: i32, player_id: i32) -> Result<Vec<Slo
   Ok(slots.into_inner())
 }
 
+/// Number of games hosted by the player that are still in the lobby
+pub fn count_hosted_lobbies(conn: &DbConn, player_id: i32) -> Result<usize> {
+  use game::dsl;
+  let n: i64 = game::table
+    .filter(
+      dsl::created_by
+        .eq(player_id)
+        .and(dsl::status.eq(GameStatus::Preparing)),
+    )
+    .count()
+    .get_result(conn)?;
+  Ok(n as usize)
+}
+
+/// Number of games the player is in that haven't ended, except `except_game_id`.
+/// Locks the player row, concurrent joins of the same player are counted one at a time.
+pub fn count_player_active_games_for_update(
+  conn: &DbConn,
+  player_id: i32,
+  except_game_id: i32,
+) -> Result<usize> {
+  player::table
+    .find(player_id)
+    .select(player::id)
+    .for_update()
+    .first::<i32>(conn)
+    .optional()?
+    .ok_or(Error::PlayerNotFound)?;
+  let n: i64 = game_used_slot::table
+    .inner_join(game::table)
+    .filter(
+      game_used_slot::player_id
+        .eq(player_id)
+        .and(game_used_slot::game_id.ne(except_game_id)),
+    )
+    .filter(game::status.eq_any(&[
+      GameStatus::Preparing,
+      GameStatus::Created,
+      GameStatus::Running,
+      GameStatus::Paused,
+    ]))
+    .count()
+    .get_result(conn)?;
+  Ok(n as usize)
+}
+
 #[derive(Debug)]
 pub struct LeaveGame {
   pub game_ended: bool,
@@ -533,6 +919,55 @@ pub fn update_slot_settings(
     return Err(Error::GameStarted);
   }
 
+  apply_slot_settings(conn, game_id, slot_index, settings)
+}
+
+/// Updates the slot settings of a game created by an API client,
+/// ignores the game lock and layout lock that only apply to players
+pub fn update_slot_settings_as_bot(
+  conn: &DbConn,
+  api_client_id: i32,
+  game_id: i32,
+  slot_index: i32,
+  settings: SlotSettings,
+) -> Result<UpdateSlotSettings> {
+  let (status, source, created_by_client_id): (GameStatus, PlayerSource, i32) = game::table
+    .inner_join(player::table)
+    .filter(game::id.eq(game_id))
+    .select((game::status, player::source, player::api_client_id))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+
+  if source != PlayerSource::Api || created_by_client_id != api_client_id {
+    return Err(Error::GameNotFound);
+  }
+
+  if status != GameStatus::Preparing {
+    return Err(Error::GameStarted);
+  }
+
+  apply_slot_settings(conn, game_id, slot_index, settings)
+}
+
+/// Returns `true` if players are not allowed to change the slot settings of the game
+pub fn is_layout_locked(conn: &DbConn, game_id: i32) -> Result<bool> {
+  let meta: Value = game::table
+    .find(game_id)
+    .select(game::dsl::meta)
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+  let meta: Meta = serde_json::from_value(meta)?;
+  Ok(meta.locked_layout)
+}
+
+fn apply_slot_settings(
+  conn: &DbConn,
+  game_id: i32,
+  slot_index: i32,
+  settings: SlotSettings,
+) -> Result<UpdateSlotSettings> {
   let mut slots = get_slots(conn, game_id)?.slots;
   let mut updated_indexes = vec![];
   if let Some(slots) = slots.update_slot_at(slot_index, &settings) {
@@ -667,6 +1102,17 @@ fn upsert_used_slots(conn: &DbConn, game_id: i32, used_slots: Vec<UsedSlot>) ->
   })
 }
 
+/// Inserts the slots of new games, as `(game_id, slot)` pairs
+fn insert_used_slots(conn: &DbConn, slots: impl Iterator<Item = (i32, UsedSlot)>) -> Result<()> {
+  let inserts: Vec<_> = slots
+    .map(|(game_id, slot)| UsedSlotInsert::from_used_slot(game_id, slot))
+    .collect();
+  diesel::insert_into(game_used_slot::table)
+    .values(&inserts)
+    .execute(conn)?;
+  Ok(())
+}
+
 #[derive(Debug)]
 struct GetSlots {
   host_player_id: i32,
@@ -787,6 +1233,14 @@ pub struct GameStateFromDb {
   pub players: Vec<(i32, Option<Vec<u8>>)>,
   pub node_id: Option<i32>,
   pub created_by: i32,
+  /// Set if the game was created by an API client
+  pub api_client_id: Option<i32>,
+  pub client_status_map: HashMap<i32, SlotClientStatus>,
+  /// The lobby was waiting for players to ack game start when the controller stopped
+  pub start_interrupted: bool,
+  pub started_at: Option<DateTime<Utc>>,
+  pub target_version: Option<String>,
+  pub player_teams: HashMap<i32, i32>,
 }
 
 /// Loads game players info from database
@@ -794,52 +1248,118 @@ pub struct GameStateFromDb {
 pub fn get_all_active_game_state(conn: &DbConn) -> Result<Vec<GameStateFromDb>> {
   use game::dsl;
 
-  let rows: Vec<(i32, GameStatus, Option<i32>, i32)> = game::table
+  let rows: Vec<(
+    i32,
+    GameStatus,
+    Option<i32>,
+    i32,
+    PlayerSource,
+    i32,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+    Value,
+  )> = game::table
     .left_outer_join(node::table)
+    .inner_join(player::table)
     .filter(dsl::status.eq_any(&[
       GameStatus::Preparing,
       GameStatus::Created,
       GameStatus::Running,
     ]))
     .order(dsl::created_at)
-    .select((dsl::id, dsl::status, dsl::node_id, dsl::created_by))
+    .select((
+      dsl::id,
+      dsl::status,
+      dsl::node_id,
+      dsl::created_by,
+      player::source,
+      player::api_client_id,
+      dsl::start_requested_at,
+      dsl::started_at,
+      dsl::meta,
+    ))
     .load(conn)?;
 
-  let game_ids: Vec<_> = rows.iter().map(|(id, _, _, _)| *id).collect();
-  let mut game_players_map: HashMap<i32, Vec<(i32, Option<Vec<u8>>)>> = {
+  let game_ids: Vec<_> = rows.iter().map(|row| row.0).collect();
+  let mut game_players_map: HashMap<i32, Vec<(i32, Option<Vec<u8>>, SlotClientStatus, i32)>> = {
     use game_used_slot::dsl;
-    let rows: Vec<(i32, Option<i32>, Option<Vec<u8>>)> = game_used_slot::table
-      .select((dsl::game_id, dsl::player_id, dsl::node_token))
-      .filter(
-        dsl::game_id
-          .eq(any(game_ids))
-          .and(dsl::player_id.is_not_null())
-          .and(dsl::client_status.ne(all(
-            &[SlotClientStatus::Disconnected, SlotClientStatus::Left] as &[SlotClientStatus],
-          ))),
-      )
-      .load(conn)?;
+    let rows: Vec<(i32, Option<i32>, Option<Vec<u8>>, SlotClientStatus, i32)> =
+      game_used_slot::table
+        .select((
+          dsl::game_id,
+          dsl::player_id,
+          dsl::node_token,
+          dsl::client_status,
+          dsl::team,
+        ))
+        .filter(
+          dsl::game_id
+            .eq(any(game_ids))
+            .and(dsl::player_id.is_not_null())
+            .and(dsl::client_status.ne(all(
+              &[SlotClientStatus::Disconnected, SlotClientStatus::Left] as &[SlotClientStatus],
+            ))),
+        )
+        .load(conn)?;
     let mut map = HashMap::new();
-    for (game_id, player_id, node_token) in rows {
+    for (game_id, player_id, node_token, client_status, team) in rows {
       if let Some(player_id) = player_id {
-        map
-          .entry(game_id)
-          .or_insert_with(|| vec![])
-          .push((player_id, node_token))
+        map.entry(game_id).or_insert_with(|| vec![]).push((
+          player_id,
+          node_token,
+          client_status,
+          team,
+        ))
       }
     }
     map
   };
 
   let mut games = Vec::with_capacity(rows.len());
-  for (id, status, node_id, created_by) in rows {
-    let players = game_players_map.remove(&id).unwrap_or_default();
+  for (
+    id,
+    status,
+    node_id,
+    created_by,
+    created_by_source,
+    api_client_id,
+    start_requested_at,
+    started_at,
+    meta,
+  ) in rows
+  {
+    let target_version = serde_json::from_value::<Meta>(meta)
+      .ok()
+      .and_then(|meta| meta.target_version);
+    let slots = game_players_map.remove(&id).unwrap_or_default();
+    let client_status_map = slots
+      .iter()
+      .map(|(player_id, _, client_status, _)| (*player_id, *client_status))
+      .collect();
+    let player_teams = slots
+      .iter()
+      .map(|(player_id, _, _, team)| (*player_id, *team))
+      .collect();
+    let players = slots
+      .into_iter()
+      .map(|(player_id, node_token, _, _)| (player_id, node_token))
+      .collect();
     games.push(GameStateFromDb {
       id,
       status,
       players,
       node_id,
       created_by,
+      api_client_id: if created_by_source == PlayerSource::Api {
+        Some(api_client_id)
+      } else {
+        None
+      },
+      client_status_map,
+      start_interrupted: status == GameStatus::Preparing && start_requested_at.is_some(),
+      started_at,
+      target_version,
+      player_teams,
     });
   }
   Ok(games)
@@ -884,6 +1404,224 @@ pub fn select_node(conn: &DbConn, id: i32, player_id: i32, node_id: Option<i32>)
   Ok(())
 }
 
+/// Replaces the lobby metadata, only the host can update it before the game starts
+pub fn update_metadata(
+  conn: &DbConn,
+  id: i32,
+  player_id: i32,
+  metadata: GameMetadata,
+) -> Result<GameMetadata> {
+  use game::dsl;
+
+  metadata.validate()?;
+
+  conn.transaction(|| -> Result<_> {
+    let (status, created_by, is_private, meta): (GameStatus, Option<i32>, bool, Value) =
+      game::table
+        .find(id)
+        .select((dsl::status, dsl::created_by, dsl::is_private, dsl::meta))
+        .for_update()
+        .first(conn)
+        .optional()?
+        .ok_or_else(|| Error::GameNotFound)?;
+
+    if created_by != Some(player_id) {
+      return Err(Error::PlayerNotHost);
+    }
+
+    if status != GameStatus::Preparing {
+      return Err(Error::GameStarted);
+    }
+
+    if metadata.e2e_chat && !is_private {
+      return Err(Error::GameMetadataInvalid(
+        "end-to-end chat requires a private game",
+      ));
+    }
+
+    let mut meta: Meta = serde_json::from_value(meta)?;
+    meta.metadata = metadata.clone();
+    diesel::update(game::table.find(id))
+      .set(dsl::meta.eq(serde_json::to_value(&meta)?))
+      .execute(conn)?;
+    Ok(())
+  })?;
+
+  Ok(metadata)
+}
+
+pub fn get_metadata(conn: &DbConn, id: i32) -> Result<GameMetadata> {
+  let meta: Value = game::table
+    .find(id)
+    .select(game::dsl::meta)
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+  let meta: Meta = serde_json::from_value(meta)?;
+  Ok(meta.metadata)
+}
+
+/// Lobby metadata of each game in `ids`
+pub fn get_metadata_map(conn: &DbConn, ids: &[i32]) -> Result<HashMap<i32, GameMetadata>> {
+  use game::dsl;
+  let rows: Vec<(i32, Value)> = game::table
+    .select((dsl::id, dsl::meta))
+    .filter(dsl::id.eq(any(ids)))
+    .load(conn)?;
+  rows
+    .into_iter()
+    .map(|(id, meta)| {
+      let meta: Meta = serde_json::from_value(meta)?;
+      Ok((id, meta.metadata))
+    })
+    .collect()
+}
+
+/// Replaces the map of a lobby that hasn't started.
+/// Slots are reset to the new map, players keep their join order.
+pub fn update_map(conn: &DbConn, id: i32, map: Map) -> Result<Game> {
+  use game::dsl;
+
+  let max_players = map.players.len();
+  if max_players == 0 {
+    return Err(Error::MapHasNoPlayer);
+  }
+
+  conn.transaction(|| -> Result<_> {
+    let (status, meta): (GameStatus, Value) = game::table
+      .find(id)
+      .select((dsl::status, dsl::meta))
+      .for_update()
+      .first(conn)
+      .optional()?
+      .ok_or_else(|| Error::GameNotFound)?;
+
+    if status != GameStatus::Preparing {
+      return Err(Error::GameStarted);
+    }
+
+    let mut used_slots = get_used_slots(conn, id)?;
+    used_slots.sort_by_key(|slot| slot.slot_index);
+    let players: Vec<PlayerRef> = used_slots
+      .into_iter()
+      .filter_map(|slot| slot.player)
+      .collect();
+    let mut slots = Slots::new(max_players);
+    for player in &players {
+      if slots.join(player).is_none() {
+        return Err(Error::TooManyPlayers);
+      }
+    }
+
+    diesel::delete(game_used_slot::table.filter(game_used_slot::dsl::game_id.eq(id)))
+      .execute(conn)?;
+    insert_used_slots(conn, slots.as_used().into_iter().map(|slot| (id, slot)))?;
+
+    let mut meta: Meta = serde_json::from_value(meta)?;
+    meta.map = map;
+    diesel::update(game::table.find(id))
+      .set((
+        dsl::map_name.eq(&meta.map.name),
+        dsl::max_players.eq(max_players as i32),
+        dsl::meta.eq(serde_json::to_value(&meta)?),
+      ))
+      .execute(conn)?;
+    Ok(())
+  })?;
+
+  get_full(conn, id)
+}
+
+#[derive(Debug, Clone)]
+pub struct QuickJoinLobby {
+  pub game_id: i32,
+  pub node_id: i32,
+  pub created_at: DateTime<Utc>,
+  pub players: usize,
+  pub open_slots: usize,
+  pub language: Option<String>,
+}
+
+/// Public, unlocked lobbies with a node selected and at least one open slot,
+/// excluding lobbies the player is already in, newest first
+pub fn get_quick_join_lobbies(
+  conn: &DbConn,
+  player_id: i32,
+  keyword: Option<&str>,
+  take: i64,
+) -> Result<Vec<QuickJoinLobby>> {
+  use game::dsl;
+
+  let mut q = game::table
+    .select((
+      dsl::id,
+      dsl::node_id,
+      dsl::max_players,
+      dsl::created_at,
+      dsl::meta,
+    ))
+    .filter(
+      dsl::status
+        .eq(GameStatus::Preparing)
+        .and(dsl::is_private.eq(false))
+        .and(dsl::locked.eq(false))
+        .and(dsl::node_id.is_not_null()),
+    )
+    .order(dsl::id.desc())
+    .limit(take)
+    .into_boxed();
+
+  if let Some(keyword) = keyword {
+    let like = format!("%{}%", keyword.trim());
+    q = q.filter(dsl::name.ilike(like.clone()).or(dsl::map_name.ilike(like)));
+  }
+
+  let rows: Vec<(i32, Option<i32>, i32, DateTime<Utc>, Value)> = q.load(conn)?;
+  let game_ids: Vec<i32> = rows.iter().map(|row| row.0).collect();
+
+  let used_slots: Vec<(i32, Option<i32>)> = {
+    use game_used_slot::dsl;
+    game_used_slot::table
+      .select((dsl::game_id, dsl::player_id))
+      .filter(dsl::game_id.eq(any(game_ids)))
+      .load(conn)?
+  };
+
+  let lobbies = rows
+    .into_iter()
+    .filter_map(|(game_id, node_id, max_players, created_at, meta)| {
+      let node_id = node_id?;
+      let mut used = 0;
+      let mut players = 0;
+      for (_, slot_player_id) in used_slots.iter().filter(|(id, _)| *id == game_id) {
+        used += 1;
+        match *slot_player_id {
+          Some(id) if id == player_id => return None,
+          Some(_) => players += 1,
+          None => {}
+        }
+      }
+      let open_slots = (max_players as usize).saturating_sub(used);
+      if open_slots == 0 {
+        return None;
+      }
+      Some(QuickJoinLobby {
+        game_id,
+        node_id,
+        created_at,
+        players,
+        open_slots,
+        language: meta
+          .pointer("/metadata/language")
+          .and_then(Value::as_str)
+          .map(ToString::to_string),
+      })
+    })
+    .collect();
+
+  Ok(lobbies)
+}
+
 fn end_game(conn: &DbConn, id: i32, status: GameStatus) -> Result<()> {
   use game::dsl;
   conn.transaction(|| -> Result<_> {
@@ -957,10 +1695,22 @@ pub fn reset_instance_state(conn: &DbConn) -> Result<()> {
     diesel::update(game_used_slot::table.filter(gus::game_id.eq(any(active_game_id))))
       .set(gus::client_status_synced_node_conn_id.eq(Option::<i64>::None))
       .execute(conn)?;
+    // start negotiations don't survive restarts
+    diesel::update(game::table.filter(g::start_requested_at.is_not_null()))
+      .set(g::start_requested_at.eq(Option::<DateTime<Utc>>::None))
+      .execute(conn)?;
     Ok(())
   })
 }
 
+pub fn update_start_requested(conn: &DbConn, game_id: i32, requested: bool) -> Result<()> {
+  use game::dsl;
+  diesel::update(game::table.find(game_id))
+    .set(dsl::start_requested_at.eq(if requested { Some(Utc::now()) } else { None }))
+    .execute(conn)?;
+  Ok(())
+}
+
 pub fn get_node_active_game_ids(conn: &DbConn, node_id: i32) -> Result<Vec<i32>> {
   use game::dsl as g;
 
@@ -980,6 +1730,19 @@ pub fn get_node_active_game_ids(conn: &DbConn, node_id: i32) -> Result<Vec<i32>>
 pub struct Meta {
   pub map: Map,
   pub created_by: Option<PlayerRef>,
+  #[serde(default)]
+  pub metadata: GameMetadata,
+  /// Warcraft III version the game was created for
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub target_version: Option<String>,
+  /// Slot settings can only be changed by the API client that created the game
+  #[serde(default)]
+  pub locked_layout: bool,
+  /// Saves reported by the node, oldest first
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub saves: Vec<GameSave>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub saved_game: Option<SavedGame>,
 }
 
 #[derive(Debug, Queryable)]
@@ -1049,7 +1812,8 @@ impl GameRowWithRelated {
     )
   }
 
-  pub(crate) fn into_game(self, meta: Meta, slots: Vec<Slot>) -> Result<Game> {
+  pub(crate) fn into_game(self, meta: Meta, mut slots: Vec<Slot>) -> Result<Game> {
+    crate::game::names::dedupe_player_names(&mut slots);
     let num_players = slots.iter().filter(|s| s.player.is_some()).count() as i32;
     Ok(Game {
       id: self.id,
@@ -1071,6 +1835,10 @@ impl GameRowWithRelated {
       random_seed: self.random_seed,
       mask_player_names: self.mask_player_names,
       game_version: self.game_version,
+      metadata: meta.metadata,
+      locked_layout: meta.locked_layout,
+      revision: 0,
+      saved_game: meta.saved_game,
     })
   }
 }
@@ -1078,6 +1846,8 @@ impl GameRowWithRelated {
 #[derive(Debug, Insertable)]
 #[table_name = "game"]
 pub struct GameInsert<'a> {
+  /// Generated if not set
+  pub id: Option<i32>,
   pub name: &'a str,
   pub map_name: &'a str,
   pub is_private: bool,
@@ -1089,6 +1859,8 @@ pub struct GameInsert<'a> {
   pub locked: bool,
   pub node_id: Option<i32>,
   pub mask_player_names: bool,
+  /// The season of the ladder that created the game
+  pub season_id: Option<i32>,
 }
 
 #[derive(Debug, Insertable)]
@@ -1151,3 +1923,49 @@ impl UsedSlotUpdate {
     }
   }
 }
+
+#[test]
+fn test_resume_slots() {
+  let slot = |player_id: Option<i32>, status: SlotStatus| CreateGameSlot {
+    player_id,
+    settings: SlotSettings {
+      status,
+      ..Default::default()
+    },
+  };
+  let slots = vec![
+    slot(Some(1), SlotStatus::Occupied),
+    slot(None, SlotStatus::Occupied),
+    slot(Some(2), SlotStatus::Occupied),
+    slot(Some(3), SlotStatus::Occupied),
+  ];
+  let save = |slots: Vec<(i32, usize)>| GameSave {
+    name: "flo.w3z".to_string(),
+    slots: slots.into_iter().collect(),
+    saved_at: Utc::now(),
+  };
+
+  let resumed = resume_slots(slots.clone(), &save(vec![(1, 0), (3, 3)])).unwrap();
+  let players: Vec<_> = resumed
+    .iter()
+    .map(|slot| (slot.player_id, slot.settings.status))
+    .collect();
+  assert_eq!(
+    players,
+    vec![
+      (Some(1), SlotStatus::Occupied),
+      (None, SlotStatus::Occupied),
+      (None, SlotStatus::Closed),
+      (Some(3), SlotStatus::Occupied),
+    ]
+  );
+
+  assert!(matches!(
+    resume_slots(slots.clone(), &save(vec![(1, 2)])),
+    Err(Error::GameSaveInvalid)
+  ));
+  assert!(matches!(
+    resume_slots(slots, &save(vec![(4, 1)])),
+    Err(Error::GameSaveInvalid)
+  ));
+}
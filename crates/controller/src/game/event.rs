@@ -0,0 +1,158 @@
+use crate::game::result::{GameResultStatus, ResultSource};
+use crate::game::{GameStatus, SlotSettings};
+use chrono::{DateTime, Utc};
+use futures::stream::{Stream, StreamExt};
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+
+const CHANNEL_SIZE: usize = 256;
+
+/// Lobby lifecycle and slot change event, published for external services
+/// such as moderation dashboards
+#[derive(Debug, Clone, Serialize)]
+pub struct LobbyEvent {
+  pub game_id: i32,
+  /// The API client (ladder) that created the game
+  pub api_client_id: Option<i32>,
+  pub time: DateTime<Utc>,
+  pub kind: LobbyEventKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LobbyEventKind {
+  Created {
+    host_player_id: i32,
+  },
+  PlayerJoined {
+    player_id: i32,
+  },
+  PlayerLeft {
+    player_id: i32,
+  },
+  SlotUpdated {
+    slot_index: i32,
+    player_id: Option<i32>,
+    settings: SlotSettings,
+  },
+  StatusChanged {
+    status: GameStatus,
+  },
+  MetadataUpdated,
+  /// The lobby switched to the winning map of a map vote
+  MapChanged {
+    map_name: String,
+  },
+  /// The player's name only differs from another player's in lookalike characters
+  NameImpersonation {
+    player_id: i32,
+    imitated_player_id: i32,
+  },
+  /// Results were reported for the ended game
+  ResultReported {
+    status: GameResultStatus,
+    source: ResultSource,
+    conflict: bool,
+  },
+  Cancelled,
+  Removed,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LobbyEventFilter {
+  /// Only games created by this API client
+  pub api_client_id: Option<i32>,
+}
+
+impl LobbyEventFilter {
+  pub fn matches(&self, event: &LobbyEvent) -> bool {
+    match self.api_client_id {
+      Some(id) => event.api_client_id == Some(id),
+      None => true,
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct LobbyEventSender {
+  tx: broadcast::Sender<LobbyEvent>,
+  api_client_map: Arc<RwLock<BTreeMap<i32, i32>>>,
+}
+
+impl Default for LobbyEventSender {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl LobbyEventSender {
+  pub fn new() -> Self {
+    let (tx, _) = broadcast::channel(CHANNEL_SIZE);
+    Self {
+      tx,
+      api_client_map: Default::default(),
+    }
+  }
+
+  pub fn register_game(&self, game_id: i32, api_client_id: Option<i32>) {
+    if let Some(api_client_id) = api_client_id {
+      self.api_client_map.write().insert(game_id, api_client_id);
+    }
+  }
+
+  pub fn send(&self, game_id: i32, kind: LobbyEventKind) {
+    // no receivers
+    if self.tx.receiver_count() == 0 {
+      return;
+    }
+    let api_client_id = self.api_client_map.read().get(&game_id).cloned();
+    self.send_for_api_client(game_id, api_client_id, kind)
+  }
+
+  /// For events of games that were already removed
+  pub fn send_for_api_client(
+    &self,
+    game_id: i32,
+    api_client_id: Option<i32>,
+    kind: LobbyEventKind,
+  ) {
+    if self.tx.receiver_count() == 0 {
+      return;
+    }
+    self
+      .tx
+      .send(LobbyEvent {
+        game_id,
+        api_client_id,
+        time: Utc::now(),
+        kind,
+      })
+      .ok();
+  }
+
+  pub fn remove_game(&self, game_id: i32) {
+    self.send(game_id, LobbyEventKind::Removed);
+    self.api_client_map.write().remove(&game_id);
+  }
+
+  /// Events of all games matching `filter`.
+  /// Events are dropped if the subscriber falls behind.
+  pub fn subscribe(&self, filter: LobbyEventFilter) -> impl Stream<Item = LobbyEvent> {
+    BroadcastStream::new(self.tx.subscribe()).filter_map(move |res| {
+      let item = match res {
+        Ok(event) if filter.matches(&event) => Some(event),
+        Ok(_) => None,
+        Err(BroadcastStreamRecvError::Lagged(n)) => {
+          tracing::warn!("lobby event subscriber lagged: {} events dropped", n);
+          None
+        }
+      };
+      futures::future::ready(item)
+    })
+  }
+}
@@ -0,0 +1,135 @@
+//! Game lifecycle state machine, all status changes of a game actor are validated here.
+//!
+//! ```text
+//! Lobby -> Starting -> Created -> Running <-> Paused
+//! ```
+//!
+//! `Lobby` and `Starting` are both stored as `GameStatus::Preparing`, a game is `Starting`
+//! while the start check is running. `Created` means the node created the game and
+//! is waiting for the players, it falls back to `Lobby` if the game is reset.
+//! Every stage can be ended or terminated, `Ended` and `Terminated` are final.
+
+use crate::error::*;
+use crate::game::GameStatus;
+use crate::metrics;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameStage {
+  Lobby,
+  Starting,
+  Created,
+  Running,
+  Paused,
+  Ended,
+  Terminated,
+}
+
+impl GameStage {
+  pub fn new(status: GameStatus, starting: bool) -> Self {
+    match status {
+      GameStatus::Preparing if starting => Self::Starting,
+      GameStatus::Preparing => Self::Lobby,
+      GameStatus::Created => Self::Created,
+      GameStatus::Running => Self::Running,
+      GameStatus::Paused => Self::Paused,
+      GameStatus::Ended => Self::Ended,
+      GameStatus::Terminated => Self::Terminated,
+    }
+  }
+
+  pub fn status(self) -> GameStatus {
+    match self {
+      Self::Lobby | Self::Starting => GameStatus::Preparing,
+      Self::Created => GameStatus::Created,
+      Self::Running => GameStatus::Running,
+      Self::Paused => GameStatus::Paused,
+      Self::Ended => GameStatus::Ended,
+      Self::Terminated => GameStatus::Terminated,
+    }
+  }
+
+  pub fn is_final(self) -> bool {
+    matches!(self, Self::Ended | Self::Terminated)
+  }
+
+  pub fn can_transition_to(self, next: GameStage) -> bool {
+    use GameStage::*;
+    match (self, next) {
+      // nodes report the same status repeatedly, along with slot client status updates
+      (Created, Created) | (Running, Running) | (Paused, Paused) => true,
+      (Ended, Ended) | (Terminated, Terminated) => true,
+      (from, Ended) | (from, Terminated) => !from.is_final(),
+      (Lobby, Starting) => true,
+      (Starting, Lobby) | (Starting, Created) => true,
+      (Created, Lobby) | (Created, Running) | (Created, Paused) => true,
+      (Running, Paused) | (Paused, Running) => true,
+      _ => false,
+    }
+  }
+
+  fn as_str(self) -> &'static str {
+    match self {
+      Self::Lobby => "lobby",
+      Self::Starting => "starting",
+      Self::Created => "created",
+      Self::Running => "running",
+      Self::Paused => "paused",
+      Self::Ended => "ended",
+      Self::Terminated => "terminated",
+    }
+  }
+}
+
+/// Rejected transitions are logged and counted in `GAME_STAGE_TRANSITIONS_REJECTED`
+pub fn transition(game_id: i32, from: GameStage, to: GameStage) -> Result<GameStage> {
+  if from.can_transition_to(to) {
+    return Ok(to);
+  }
+  tracing::warn!(game_id, ?from, ?to, "illegal game stage transition");
+  metrics::GAME_STAGE_TRANSITIONS_REJECTED
+    .with_label_values(&[from.as_str(), to.as_str()])
+    .inc();
+  Err(Error::GameStageTransition { from, to })
+}
+
+#[test]
+fn test_game_stage_transition() {
+  use GameStage::*;
+
+  let mut stage = Lobby;
+  for next in [Starting, Created, Running, Paused, Running, Running, Ended] {
+    stage = transition(1, stage, next).unwrap();
+  }
+  assert_eq!(stage, Ended);
+
+  assert!(Starting.can_transition_to(Lobby));
+  assert!(Created.can_transition_to(Lobby));
+  assert!(Lobby.can_transition_to(Terminated));
+
+  for (from, to) in [
+    (Starting, Starting),
+    (Lobby, Running),
+    (Lobby, Created),
+    (Running, Lobby),
+    (Running, Created),
+    (Ended, Running),
+    (Terminated, Ended),
+  ] {
+    assert!(matches!(
+      transition(1, from, to),
+      Err(Error::GameStageTransition { .. })
+    ));
+  }
+
+  for status in [
+    GameStatus::Preparing,
+    GameStatus::Created,
+    GameStatus::Running,
+    GameStatus::Ended,
+    GameStatus::Paused,
+    GameStatus::Terminated,
+  ] {
+    assert_eq!(GameStage::new(status, false).status(), status);
+  }
+  assert_eq!(GameStage::new(GameStatus::Preparing, true), Starting);
+}
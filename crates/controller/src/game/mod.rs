@@ -1,6 +1,12 @@
 pub mod db;
+pub mod event;
+pub mod lifecycle;
+pub mod names;
+pub mod quick_join;
+pub mod result;
 mod slots;
 pub(crate) mod state;
+pub mod summary;
 pub mod token;
 mod types;
 
@@ -14,9 +20,10 @@ pub mod messages {
   pub use super::state::registry::{
     AddGamePlayer, Register, Remove, RemoveGamePlayer, ResolveGamePlayerPingBroadcastTargets,
   };
-  pub use super::state::slot::UpdateSlot;
+  pub use super::state::slot::{UpdateSlot, UpdateSlotAsBot};
   pub use super::state::start::{StartGameCheck, StartGamePlayerAck};
 }
 
 pub use slots::Slots;
+pub use state::quota::{GameQuota, QuotaExceeded};
 pub use types::*;
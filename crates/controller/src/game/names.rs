@@ -0,0 +1,233 @@
+use crate::game::Slot;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A player whose display name collides with an earlier player's in the same lobby
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameConflict {
+  pub player_id: i32,
+  /// The player keeping the name, the one with the lowest id
+  pub conflicts_with: i32,
+  /// Display name with a discriminator appended, e.g. `Grubby#2`
+  pub display_name: String,
+  /// The names only match after folding lookalike characters,
+  /// e.g. Cyrillic `о` for Latin `o`
+  pub impersonation: bool,
+}
+
+/// Finds colliding display names, compared by their lookalike-folded form.
+/// The player with the lowest id, usually the older account, keeps the name,
+/// later players get a discriminator appended.
+pub fn name_conflicts(slots: &[Slot]) -> Vec<NameConflict> {
+  let mut players: Vec<(i32, &str)> = slots
+    .iter()
+    .filter_map(|slot| slot.player.as_ref().map(|p| (p.id, p.name.as_str())))
+    .collect();
+  players.sort_by_key(|(id, _)| *id);
+
+  let mut taken: BTreeSet<String> = players.iter().map(|(_, name)| skeleton(name)).collect();
+  // skeleton => (player id, name, next discriminator)
+  let mut owners: BTreeMap<String, (i32, &str, usize)> = BTreeMap::new();
+  let mut conflicts = vec![];
+
+  for (player_id, name) in players {
+    let key = skeleton(name);
+    match owners.get_mut(&key) {
+      None => {
+        owners.insert(key, (player_id, name, 2));
+      }
+      Some((owner_id, owner_name, next)) => {
+        let display_name = loop {
+          let candidate = format!("{}#{}", name, next);
+          *next += 1;
+          let candidate_key = skeleton(&candidate);
+          if !taken.contains(&candidate_key) {
+            taken.insert(candidate_key);
+            break candidate;
+          }
+        };
+        conflicts.push(NameConflict {
+          player_id,
+          conflicts_with: *owner_id,
+          display_name,
+          impersonation: name != *owner_name,
+        });
+      }
+    }
+  }
+
+  conflicts
+}
+
+/// Renames colliding players so every display name in the lobby is distinct
+pub fn dedupe_player_names(slots: &mut [Slot]) -> Vec<NameConflict> {
+  let conflicts = name_conflicts(slots);
+  for conflict in &conflicts {
+    let player = slots
+      .iter_mut()
+      .filter_map(|slot| slot.player.as_mut())
+      .find(|p| p.id == conflict.player_id);
+    if let Some(player) = player {
+      player.name = conflict.display_name.clone();
+    }
+  }
+  conflicts
+}
+
+/// Folds a name into a form where lookalike names are equal:
+/// case, fullwidth forms, accents, Cyrillic and Greek lookalikes, `I`/`l`/`1`, `0`/`o`,
+/// `rn`/`m` and invisible or separator characters
+pub fn skeleton(name: &str) -> String {
+  let mut folded = String::with_capacity(name.len());
+  for c in name.chars() {
+    let c = match c {
+      // fullwidth ASCII
+      '\u{FF01}'..='\u{FF5E}' => std::char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+      c => c,
+    };
+    if is_ignored(c) {
+      continue;
+    }
+    let c = match c {
+      'I' | '1' | '|' => 'l',
+      '0' => 'o',
+      c => fold_lookalike(c),
+    };
+    folded.extend(c.to_lowercase());
+  }
+  folded.replace("rn", "m").replace("vv", "w")
+}
+
+fn is_ignored(c: char) -> bool {
+  c.is_whitespace()
+    || matches!(
+      c,
+      '_' | '-' | '.' | '\u{00AD}' | '\u{034F}' | '\u{FEFF}'
+        | '\u{0300}'..='\u{036F}'
+        | '\u{200B}'..='\u{200F}'
+        | '\u{2060}'..='\u{2064}'
+    )
+}
+
+fn fold_lookalike(c: char) -> char {
+  match c {
+    // Latin-1 accented letters
+    'À'..='Å' | 'à'..='å' => 'a',
+    'Ç' | 'ç' => 'c',
+    'È'..='Ë' | 'è'..='ë' => 'e',
+    'Ì'..='Ï' | 'ì'..='ï' => 'i',
+    'Ñ' | 'ñ' => 'n',
+    'Ò'..='Ö' | 'Ø' | 'ò'..='ö' | 'ø' => 'o',
+    'Ù'..='Ü' | 'ù'..='ü' => 'u',
+    'Ý' | 'ý' | 'ÿ' => 'y',
+    // Cyrillic
+    'А' | 'а' => 'a',
+    'В' | 'в' | 'Ь' | 'ь' => 'b',
+    'С' | 'с' => 'c',
+    'ԁ' => 'd',
+    'Е' | 'е' | 'Ё' | 'ё' => 'e',
+    'Н' | 'н' | 'һ' => 'h',
+    'І' | 'і' | 'Ї' | 'ї' => 'i',
+    'Ј' | 'ј' => 'j',
+    'К' | 'к' => 'k',
+    'Ӏ' | 'ӏ' => 'l',
+    'М' | 'м' => 'm',
+    'О' | 'о' => 'o',
+    'Р' | 'р' => 'p',
+    'Ԛ' | 'ԛ' => 'q',
+    'Ѕ' | 'ѕ' => 's',
+    'Т' | 'т' => 't',
+    'У' | 'у' => 'y',
+    'Ԝ' | 'ԝ' => 'w',
+    'Х' | 'х' => 'x',
+    // Greek
+    'Α' | 'α' => 'a',
+    'Β' | 'β' => 'b',
+    'Ε' | 'ε' => 'e',
+    'Ζ' => 'z',
+    'Η' => 'h',
+    'Ι' => 'l',
+    'ι' => 'i',
+    'Κ' | 'κ' => 'k',
+    'Μ' => 'm',
+    'Ν' | 'η' => 'n',
+    'ν' => 'v',
+    'Ο' | 'ο' => 'o',
+    'Ρ' | 'ρ' => 'p',
+    'Τ' | 'τ' => 't',
+    'Υ' | 'υ' => 'y',
+    'Χ' | 'χ' => 'x',
+    c => c,
+  }
+}
+
+#[test]
+fn test_skeleton() {
+  assert_eq!(skeleton("Grubby"), "grubby");
+  assert_eq!(skeleton("Grubby"), skeleton("GRUBBY"));
+  assert_eq!(skeleton("Grubby"), skeleton("Grubbу")); // Cyrillic у
+  assert_eq!(skeleton("Moon"), skeleton("M0on"));
+  assert_eq!(skeleton("Moon"), skeleton("Mοοn")); // Greek ο
+  assert_eq!(skeleton("Moon"), skeleton("Ｍｏｏｎ"));
+  assert_eq!(skeleton("Moon"), skeleton("Mo\u{200B}on"));
+  assert_eq!(skeleton("Moon"), skeleton("rnoon"));
+  assert_eq!(skeleton("Infi"), skeleton("lnfi"));
+  assert_eq!(skeleton("Lyn"), skeleton("Lyn_"));
+  assert_eq!(skeleton("Café"), skeleton("Cafe"));
+  assert_ne!(skeleton("Moon"), skeleton("Mood"));
+}
+
+#[test]
+fn test_dedupe_player_names() {
+  use crate::game::{SlotSettings, SlotStatus};
+  use crate::player::{PlayerRef, PlayerSource};
+
+  let slot = |id: i32, name: &str| Slot {
+    player: Some(PlayerRef {
+      id,
+      name: name.to_string(),
+      source: PlayerSource::Test,
+      realm: None,
+    }),
+    settings: SlotSettings {
+      status: SlotStatus::Occupied,
+      ..Default::default()
+    },
+    ..Default::default()
+  };
+
+  let mut slots = vec![
+    slot(9, "Grubbу"),
+    slot(3, "Grubby"),
+    slot(5, "Moon"),
+    slot(7, "Moon"),
+    slot(8, "Moon#2"),
+    Slot::default(),
+  ];
+  let conflicts = dedupe_player_names(&mut slots);
+
+  assert_eq!(
+    conflicts,
+    vec![
+      NameConflict {
+        player_id: 7,
+        conflicts_with: 5,
+        display_name: "Moon#3".to_string(),
+        impersonation: false,
+      },
+      NameConflict {
+        player_id: 9,
+        conflicts_with: 3,
+        display_name: "Grubbу#2".to_string(),
+        impersonation: true,
+      },
+    ]
+  );
+  let names: Vec<_> = slots
+    .iter()
+    .filter_map(|s| s.player.as_ref().map(|p| p.name.as_str()))
+    .collect();
+  assert_eq!(
+    names,
+    vec!["Grubbу#2", "Grubby", "Moon", "Moon#3", "Moon#2"]
+  );
+}
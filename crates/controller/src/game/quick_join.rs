@@ -0,0 +1,229 @@
+use crate::error::*;
+use crate::game::db::QuickJoinLobby;
+use crate::game::messages::{AddGamePlayer, PlayerJoin};
+use crate::game::Game;
+use crate::node::messages::ListNodeRouting;
+use crate::player::state::ping::GetPlayersPingSnapshot;
+use crate::state::{ActorMapExt, ControllerState};
+use flo_types::ping::PingStats;
+use std::collections::BTreeMap;
+
+/// Lobbies loaded from the database per request, before filtering by ping and language
+const QUICK_JOIN_MAX_LOBBIES: i64 = 100;
+/// Lobbies tried before giving up, the next one is tried if the previous filled up or started
+const QUICK_JOIN_MAX_ATTEMPTS: usize = 3;
+
+#[derive(Debug, Default, Clone)]
+pub struct QuickJoinFilters {
+  pub keyword: Option<String>,
+  pub language: Option<String>,
+  pub max_ping: Option<u32>,
+}
+
+impl From<flo_net::proto::flo_connect::QuickJoinFilters> for QuickJoinFilters {
+  fn from(v: flo_net::proto::flo_connect::QuickJoinFilters) -> Self {
+    fn non_empty(v: String) -> Option<String> {
+      let v = v.trim();
+      if v.is_empty() {
+        None
+      } else {
+        Some(v.to_string())
+      }
+    }
+    Self {
+      keyword: non_empty(v.keyword),
+      language: non_empty(v.language),
+      max_ping: v.max_ping,
+    }
+  }
+}
+
+/// Seats the player in the best open public lobby matching the filters.
+///
+/// Lobbies are ranked by the player's ping to the lobby node, then by player count,
+/// so almost full lobbies start sooner, then by age.
+/// Lobbies on nodes at capacity are skipped, they couldn't start.
+pub async fn quick_join(
+  state: &ControllerState,
+  player_id: i32,
+  filters: QuickJoinFilters,
+) -> Result<Game> {
+  let lobbies = state
+    .db
+    .exec(crate::profiling::db({
+      let keyword = filters.keyword.clone();
+      move |conn| {
+        crate::game::db::get_quick_join_lobbies(
+          conn,
+          player_id,
+          keyword.as_deref(),
+          QUICK_JOIN_MAX_LOBBIES,
+        )
+      }
+    }))
+    .await?;
+
+  let ping_map = state
+    .players
+    .send(GetPlayersPingSnapshot {
+      players: vec![player_id],
+    })
+    .await?
+    .map
+    .remove(&player_id)
+    .unwrap_or_default();
+
+  let now = chrono::Utc::now();
+  let saturated: Vec<i32> = state
+    .nodes
+    .send(ListNodeRouting)
+    .await?
+    .into_iter()
+    .filter(|(_, routing)| routing.busy_for(now).is_some())
+    .map(|(node_id, _)| node_id)
+    .collect();
+  let lobbies = lobbies
+    .into_iter()
+    .filter(|lobby| !saturated.contains(&lobby.node_id))
+    .collect();
+
+  let game_ids = rank_lobbies(lobbies, &ping_map, &filters);
+
+  for game_id in game_ids.into_iter().take(QUICK_JOIN_MAX_ATTEMPTS) {
+    let game = match state.games.send_to(game_id, PlayerJoin { player_id }).await {
+      Ok(game) => game,
+      // another player took the last slot, or the lobby started or closed
+      Err(err) if is_join_race(&err) => {
+        tracing::debug!(game_id, player_id, "quick join: {}", err);
+        continue;
+      }
+      Err(err) => return Err(err),
+    };
+
+    state
+      .games
+      .send(AddGamePlayer { game_id, player_id })
+      .await?;
+
+    return Ok(game);
+  }
+
+  Err(Error::QuickJoinNoLobby)
+}
+
+fn is_join_race(err: &Error) -> bool {
+  matches!(
+    err,
+    Error::GameFull
+      | Error::GameStarted
+      | Error::GameSlotUpdateDenied
+      | Error::GameNotFound
+      | Error::ActorNotFound
+  )
+}
+
+fn rank_lobbies(
+  lobbies: Vec<QuickJoinLobby>,
+  ping_map: &BTreeMap<i32, PingStats>,
+  filters: &QuickJoinFilters,
+) -> Vec<i32> {
+  let mut ranked: Vec<_> = lobbies
+    .into_iter()
+    .filter(|lobby| match filters.language {
+      Some(ref language) => lobby
+        .language
+        .as_ref()
+        .map(|v| language_matches(language, v))
+        .unwrap_or(false),
+      None => true,
+    })
+    .filter_map(|lobby| {
+      let ping = ping_map
+        .get(&lobby.node_id)
+        .and_then(|stats| stats.avg.or(stats.current));
+      match (filters.max_ping, ping) {
+        (Some(max), Some(ping)) if ping > max => None,
+        (Some(_), None) => None,
+        _ => Some((ping, lobby)),
+      }
+    })
+    .collect();
+
+  ranked.sort_by(|(a_ping, a), (b_ping, b)| {
+    // lobbies without ping data go last
+    let a_ping = a_ping.unwrap_or(u32::MAX);
+    let b_ping = b_ping.unwrap_or(u32::MAX);
+    a_ping
+      .cmp(&b_ping)
+      .then_with(|| b.players.cmp(&a.players))
+      .then_with(|| a.created_at.cmp(&b.created_at))
+  });
+
+  ranked.into_iter().map(|(_, lobby)| lobby.game_id).collect()
+}
+
+/// `en` matches `en` and `en-US`
+fn language_matches(filter: &str, language: &str) -> bool {
+  language.eq_ignore_ascii_case(filter)
+    || language
+      .split('-')
+      .next()
+      .map(|primary| primary.eq_ignore_ascii_case(filter))
+      .unwrap_or(false)
+}
+
+#[test]
+fn test_rank_lobbies() {
+  use chrono::{Duration, Utc};
+
+  let now = Utc::now();
+  let lobby = |game_id, node_id, players, age, language: Option<&str>| QuickJoinLobby {
+    game_id,
+    node_id,
+    created_at: now - Duration::seconds(age),
+    players,
+    open_slots: 1,
+    language: language.map(ToString::to_string),
+  };
+  let lobbies = vec![
+    lobby(1, 1, 1, 10, Some("en")),
+    lobby(2, 2, 3, 20, Some("en-US")),
+    lobby(3, 1, 3, 30, None),
+    lobby(4, 1, 3, 40, Some("zh-CN")),
+    lobby(5, 3, 5, 50, Some("en")),
+  ];
+  let stats = |avg| PingStats {
+    avg: Some(avg),
+    ..Default::default()
+  };
+  let mut ping_map = BTreeMap::new();
+  ping_map.insert(1, stats(30));
+  ping_map.insert(2, stats(120));
+
+  assert_eq!(
+    rank_lobbies(lobbies.clone(), &ping_map, &Default::default()),
+    vec![4, 3, 1, 2, 5]
+  );
+  assert_eq!(
+    rank_lobbies(
+      lobbies.clone(),
+      &ping_map,
+      &QuickJoinFilters {
+        language: Some("EN".to_string()),
+        ..Default::default()
+      }
+    ),
+    vec![1, 2, 5]
+  );
+  assert_eq!(
+    rank_lobbies(
+      lobbies,
+      &ping_map,
+      &QuickJoinFilters {
+        max_ping: Some(100),
+        ..Default::default()
+      }
+    ),
+    vec![4, 3, 1]
+  );
+}
@@ -0,0 +1,574 @@
+//! Results of ladder games.
+//!
+//! Results are inferred from how players left the game (won, lost or draw), as reported by
+//! the service watching the game stream. Ladders of custom maps can configure results to come
+//! from W3MMD flags reported by the map instead. Map results are validated against the game's
+//! slots and compared with the leave-based results, conflicts are handled by the ladder's policy.
+//!
+//! Accepted results of games attached to a season update the season standings.
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::{GameStatus, SlotStatus};
+use crate::player::PlayerSource;
+use crate::schema::{api_client_result_policy, game, game_result, game_used_slot, player};
+use crate::state::ControllerState;
+use bs_diesel_utils::BSDieselEnum;
+use diesel::prelude::*;
+use flo_w3gs::mmd::{Flag, MmdState};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Slot team of observers and referees
+const OBSERVER_TEAM: i32 = 24;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum ResultSource {
+  Leaves = 0,
+  Map = 1,
+}
+
+/// How map results that disagree with leave-based results are handled
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum ResultConflictPolicy {
+  PreferMap = 0,
+  PreferLeaves = 1,
+  /// The result is recorded as disputed, for ladder admins to review
+  Dispute = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResultPolicy {
+  pub source: ResultSource,
+  pub on_conflict: ResultConflictPolicy,
+  /// Map results have to include every player,
+  /// otherwise players without one get their leave-based result
+  pub require_all_players: bool,
+}
+
+impl Default for ResultPolicy {
+  fn default() -> Self {
+    Self {
+      source: ResultSource::Leaves,
+      on_conflict: ResultConflictPolicy::Dispute,
+      require_all_players: false,
+    }
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayerResult {
+  Win,
+  Loss,
+  Draw,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum GameResultStatus {
+  Accepted = 0,
+  Disputed = 1,
+}
+
+/// Results reported for an ended game
+#[derive(Debug, Default)]
+pub struct ResultReport {
+  /// Leave-based results by player id
+  pub leaves: BTreeMap<i32, PlayerResult>,
+  /// W3MMD state of the game, if the map reported anything
+  pub map: Option<MmdState>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameResult {
+  pub status: GameResultStatus,
+  /// Where `players` came from
+  pub source: ResultSource,
+  /// Map results disagreed with leave-based results
+  pub conflict: bool,
+  /// Why map results were rejected
+  pub map_error: Option<String>,
+  pub players: BTreeMap<i32, PlayerResult>,
+}
+
+/// An occupied, non-observer player slot
+#[derive(Debug, Clone, Copy)]
+pub struct ResultSlot {
+  pub slot_index: i32,
+  pub team: i32,
+  pub player_id: i32,
+}
+
+impl ResultPolicy {
+  pub fn resolve(&self, slots: &[ResultSlot], report: &ResultReport) -> GameResult {
+    let leaves = GameResult {
+      status: GameResultStatus::Accepted,
+      source: ResultSource::Leaves,
+      conflict: false,
+      map_error: None,
+      players: report.leaves.clone(),
+    };
+
+    if self.source == ResultSource::Leaves {
+      return leaves;
+    }
+
+    let map = match map_results(slots, report.map.as_ref(), self.require_all_players) {
+      Ok(map) => map,
+      Err(err) => {
+        return GameResult {
+          status: if self.on_conflict == ResultConflictPolicy::PreferLeaves {
+            GameResultStatus::Accepted
+          } else {
+            GameResultStatus::Disputed
+          },
+          map_error: Some(err),
+          ..leaves
+        }
+      }
+    };
+
+    let conflict = map
+      .iter()
+      .any(|(id, result)| report.leaves.get(id).map(|v| v != result).unwrap_or(false));
+    if conflict && self.on_conflict == ResultConflictPolicy::PreferLeaves {
+      return GameResult { conflict, ..leaves };
+    }
+
+    let mut players = report.leaves.clone();
+    players.extend(map);
+    GameResult {
+      status: if conflict && self.on_conflict == ResultConflictPolicy::Dispute {
+        GameResultStatus::Disputed
+      } else {
+        GameResultStatus::Accepted
+      },
+      source: ResultSource::Map,
+      conflict,
+      map_error: None,
+      players,
+    }
+  }
+}
+
+/// Validates the flags reported by the map and maps them to player ids.
+/// W3MMD player numbers are slot indices, flags of computer slots are ignored.
+fn map_results(
+  slots: &[ResultSlot],
+  map: Option<&MmdState>,
+  require_all_players: bool,
+) -> Result<BTreeMap<i32, PlayerResult>, String> {
+  let map = map.ok_or_else(|| "no results reported by the map".to_string())?;
+
+  let mut results = BTreeMap::new();
+  let mut team_results: BTreeMap<i32, PlayerResult> = BTreeMap::new();
+  for (pid, player) in &map.players {
+    let slot = match slots.iter().find(|s| s.slot_index == *pid as i32) {
+      Some(slot) => slot,
+      None => continue,
+    };
+    if player.flags.contains(&Flag::Practicing) {
+      return Err(format!("player {} is practicing", pid));
+    }
+    let mut flagged = player.flags.iter().filter_map(|flag| match flag {
+      Flag::Winner => Some(PlayerResult::Win),
+      Flag::Loser => Some(PlayerResult::Loss),
+      Flag::Drawer => Some(PlayerResult::Draw),
+      Flag::Leaver | Flag::Practicing => None,
+    });
+    let result = match (flagged.next(), flagged.next()) {
+      (Some(result), None) => result,
+      // leaver only
+      (None, _) => continue,
+      (Some(_), Some(_)) => return Err(format!("conflicting flags for player {}", pid)),
+    };
+    if *team_results.entry(slot.team).or_insert(result) != result {
+      return Err(format!("different results in team {}", slot.team + 1));
+    }
+    results.insert(slot.player_id, result);
+  }
+
+  if results.is_empty() {
+    return Err("no results reported by the map".to_string());
+  }
+  if require_all_players {
+    if let Some(slot) = slots.iter().find(|s| !results.contains_key(&s.player_id)) {
+      return Err(format!("no result for player {}", slot.slot_index));
+    }
+  }
+  let all_draw = results.values().all(|v| *v == PlayerResult::Draw);
+  if !all_draw && !results.values().any(|v| *v == PlayerResult::Win) {
+    return Err("no winner".to_string());
+  }
+  Ok(results)
+}
+
+/// Resolves and records the result of an ended game with the policy of the ladder that created it,
+/// games not created by a ladder use the default policy
+pub async fn report(
+  state: &ControllerState,
+  game_id: i32,
+  report: ResultReport,
+) -> Result<GameResult> {
+  let (api_client_id, result) = state
+    .db
+    .exec(move |conn| {
+      let (api_client_id, slots) = get_result_slots(conn, game_id)?;
+      let policy = match api_client_id {
+        Some(id) => get_policy(conn, id)?,
+        None => ResultPolicy::default(),
+      };
+      let result = policy.resolve(&slots, &report);
+      let season_id = crate::season::db::get_game_season_id(conn, game_id)?;
+      conn.transaction(|| -> Result<_> {
+        insert_result(conn, game_id, season_id, &result)?;
+        if let Some(season_id) = season_id {
+          crate::season::db::apply_result(conn, season_id, &slots, &result)?;
+        }
+        Ok(())
+      })?;
+      Ok::<_, Error>((api_client_id, result))
+    })
+    .await?;
+
+  if result.conflict || result.map_error.is_some() {
+    tracing::warn!(
+      game_id,
+      "result {:?}: conflict = {}, map error = {:?}",
+      result.status,
+      result.conflict,
+      result.map_error
+    );
+  }
+  state.lobby_events.send_for_api_client(
+    game_id,
+    api_client_id,
+    crate::game::event::LobbyEventKind::ResultReported {
+      status: result.status,
+      source: result.source,
+      conflict: result.conflict,
+    },
+  );
+  Ok(result)
+}
+
+pub fn get_policy(conn: &DbConn, api_client_id: i32) -> Result<ResultPolicy> {
+  use api_client_result_policy::dsl;
+  let row = api_client_result_policy::table
+    .find(api_client_id)
+    .select((dsl::source, dsl::on_conflict, dsl::require_all_players))
+    .first::<(ResultSource, ResultConflictPolicy, bool)>(conn)
+    .optional()?;
+  Ok(
+    row
+      .map(|(source, on_conflict, require_all_players)| ResultPolicy {
+        source,
+        on_conflict,
+        require_all_players,
+      })
+      .unwrap_or_default(),
+  )
+}
+
+pub fn set_policy(conn: &DbConn, api_client_id: i32, policy: ResultPolicy) -> Result<()> {
+  use api_client_result_policy::dsl;
+  #[derive(Insertable, AsChangeset)]
+  #[table_name = "api_client_result_policy"]
+  struct Upsert {
+    api_client_id: i32,
+    source: ResultSource,
+    on_conflict: ResultConflictPolicy,
+    require_all_players: bool,
+  }
+
+  let upsert = Upsert {
+    api_client_id,
+    source: policy.source,
+    on_conflict: policy.on_conflict,
+    require_all_players: policy.require_all_players,
+  };
+  diesel::insert_into(api_client_result_policy::table)
+    .values(&upsert)
+    .on_conflict(dsl::api_client_id)
+    .do_update()
+    .set((&upsert, dsl::updated_at.eq(diesel::dsl::now)))
+    .execute(conn)?;
+  Ok(())
+}
+
+pub fn get_result(conn: &DbConn, game_id: i32) -> Result<Option<GameResult>> {
+  use game_result::dsl;
+  let row = game_result::table
+    .filter(dsl::game_id.eq(game_id))
+    .select((
+      dsl::status,
+      dsl::source,
+      dsl::conflict,
+      dsl::map_error,
+      dsl::players,
+    ))
+    .first::<(
+      GameResultStatus,
+      ResultSource,
+      bool,
+      Option<String>,
+      serde_json::Value,
+    )>(conn)
+    .optional()?;
+  row
+    .map(|(status, source, conflict, map_error, players)| {
+      Ok(GameResult {
+        status,
+        source,
+        conflict,
+        map_error,
+        players: serde_json::from_value(players)?,
+      })
+    })
+    .transpose()
+}
+
+/// Returns the API client that created the game, and the player slots
+fn get_result_slots(conn: &DbConn, game_id: i32) -> Result<(Option<i32>, Vec<ResultSlot>)> {
+  let (status, created_by_source, api_client_id): (GameStatus, PlayerSource, i32) = game::table
+    .find(game_id)
+    .inner_join(player::table)
+    .select((game::status, player::source, player::api_client_id))
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::GameNotFound)?;
+  if status != GameStatus::Ended {
+    return Err(Error::GameNotEnded);
+  }
+
+  let slots: Vec<(i32, i32, Option<i32>)> = game_used_slot::table
+    .select((
+      game_used_slot::slot_index,
+      game_used_slot::team,
+      game_used_slot::player_id,
+    ))
+    .filter(
+      game_used_slot::game_id
+        .eq(game_id)
+        .and(game_used_slot::status.eq(SlotStatus::Occupied))
+        .and(game_used_slot::team.ne(OBSERVER_TEAM)),
+    )
+    .load(conn)?;
+
+  Ok((
+    if created_by_source == PlayerSource::Api {
+      Some(api_client_id)
+    } else {
+      None
+    },
+    slots
+      .into_iter()
+      .filter_map(|(slot_index, team, player_id)| {
+        player_id.map(|player_id| ResultSlot {
+          slot_index,
+          team,
+          player_id,
+        })
+      })
+      .collect(),
+  ))
+}
+
+fn insert_result(
+  conn: &DbConn,
+  game_id: i32,
+  season_id: Option<i32>,
+  result: &GameResult,
+) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "game_result"]
+  struct Insert<'a> {
+    game_id: i32,
+    season_id: Option<i32>,
+    status: GameResultStatus,
+    source: ResultSource,
+    conflict: bool,
+    map_error: Option<&'a str>,
+    players: serde_json::Value,
+  }
+
+  let inserted = diesel::insert_into(game_result::table)
+    .values(&Insert {
+      game_id,
+      season_id,
+      status: result.status,
+      source: result.source,
+      conflict: result.conflict,
+      map_error: result.map_error.as_deref(),
+      players: serde_json::to_value(&result.players)?,
+    })
+    .on_conflict(game_result::game_id)
+    .do_nothing()
+    .execute(conn)?;
+  if inserted == 0 {
+    return Err(Error::GameResultReported);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+fn test_mmd(messages: &[&str]) -> MmdState {
+  use flo_w3gs::mmd::{MmdEvent, MmdMessage};
+  let mut state = MmdState::default();
+  for (id, message) in messages.iter().enumerate() {
+    state
+      .apply(MmdMessage {
+        id: id as u32,
+        event: MmdEvent::parse(message).unwrap(),
+      })
+      .unwrap();
+  }
+  state
+}
+
+#[test]
+fn test_map_results() {
+  // 2v2, slot 4 is a computer
+  let slots = [
+    ResultSlot {
+      slot_index: 0,
+      team: 0,
+      player_id: 10,
+    },
+    ResultSlot {
+      slot_index: 1,
+      team: 0,
+      player_id: 11,
+    },
+    ResultSlot {
+      slot_index: 2,
+      team: 1,
+      player_id: 12,
+    },
+    ResultSlot {
+      slot_index: 3,
+      team: 1,
+      player_id: 13,
+    },
+  ];
+
+  let check = |messages: &[&str], require_all_players| {
+    map_results(&slots, Some(&test_mmd(messages)), require_all_players)
+  };
+
+  let messages = [
+    "FlagP 0 winner",
+    "FlagP 1 winner",
+    "FlagP 2 loser",
+    "FlagP 2 leaver",
+    "FlagP 4 loser",
+  ];
+  assert_eq!(
+    check(&messages, false)
+      .unwrap()
+      .into_iter()
+      .collect::<Vec<_>>(),
+    vec![
+      (10, PlayerResult::Win),
+      (11, PlayerResult::Win),
+      (12, PlayerResult::Loss)
+    ]
+  );
+  assert!(check(&messages, true).is_err());
+
+  assert!(map_results(&slots, None, false).is_err());
+  // teammates disagree
+  assert!(check(&["FlagP 0 winner", "FlagP 1 loser"], false).is_err());
+  assert!(check(&["FlagP 0 winner", "FlagP 0 loser"], false).is_err());
+  assert!(check(&["FlagP 0 loser"], false).is_err());
+  assert!(check(&["FlagP 0 practicing"], false).is_err());
+  assert!(check(&["FlagP 0 drawer", "FlagP 2 drawer"], false).is_ok());
+}
+
+#[test]
+fn test_resolve() {
+  let slots = [
+    ResultSlot {
+      slot_index: 0,
+      team: 0,
+      player_id: 10,
+    },
+    ResultSlot {
+      slot_index: 1,
+      team: 1,
+      player_id: 11,
+    },
+  ];
+  let map = || Some(test_mmd(&["FlagP 0 winner", "FlagP 1 loser"]));
+  let leaves = |a, b| {
+    vec![(10, a), (11, b)]
+      .into_iter()
+      .collect::<BTreeMap<_, _>>()
+  };
+
+  let report = ResultReport {
+    leaves: leaves(PlayerResult::Loss, PlayerResult::Win),
+    map: map(),
+  };
+  let policy = ResultPolicy {
+    source: ResultSource::Map,
+    ..Default::default()
+  };
+
+  // the default policy ignores the map
+  let result = ResultPolicy::default().resolve(&slots, &report);
+  assert_eq!(result.source, ResultSource::Leaves);
+  assert_eq!(result.players, report.leaves);
+
+  let result = policy.resolve(&slots, &report);
+  assert_eq!(result.status, GameResultStatus::Disputed);
+  assert_eq!(result.source, ResultSource::Map);
+  assert!(result.conflict);
+  assert_eq!(result.players[&10], PlayerResult::Win);
+
+  let result = ResultPolicy {
+    on_conflict: ResultConflictPolicy::PreferLeaves,
+    ..policy
+  }
+  .resolve(&slots, &report);
+  assert_eq!(result.status, GameResultStatus::Accepted);
+  assert_eq!(result.source, ResultSource::Leaves);
+  assert!(result.conflict);
+
+  let result = ResultPolicy {
+    on_conflict: ResultConflictPolicy::PreferMap,
+    ..policy
+  }
+  .resolve(&slots, &report);
+  assert_eq!(result.status, GameResultStatus::Accepted);
+  assert_eq!(result.source, ResultSource::Map);
+
+  // agreeing results, a player without a leave-based result
+  let report = ResultReport {
+    leaves: vec![(10, PlayerResult::Win)].into_iter().collect(),
+    map: map(),
+  };
+  let result = policy.resolve(&slots, &report);
+  assert_eq!(result.status, GameResultStatus::Accepted);
+  assert!(!result.conflict);
+  assert_eq!(
+    result.players,
+    leaves(PlayerResult::Win, PlayerResult::Loss)
+  );
+
+  // invalid map results fall back to leaves
+  let report = ResultReport {
+    leaves: leaves(PlayerResult::Win, PlayerResult::Loss),
+    map: None,
+  };
+  let result = policy.resolve(&slots, &report);
+  assert_eq!(result.status, GameResultStatus::Disputed);
+  assert_eq!(result.source, ResultSource::Leaves);
+  assert!(result.map_error.is_some());
+}
@@ -1,5 +1,7 @@
 use crate::error::*;
+use crate::game::event::LobbyEventKind;
 use crate::game::state::GameActor;
+use crate::game::summary::{LobbyEndReason, LobbySummary};
 
 use crate::player::state::sender::PlayerFrames;
 
@@ -26,7 +28,9 @@ impl Handler<CancelGame> for GameActor {
 
     self
       .db
-      .exec(move |conn| crate::game::db::cancel(conn, game_id, player_id))
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::db::cancel(conn, game_id, player_id)
+      }))
       .await
       .map_err(Error::from)?;
 
@@ -35,6 +39,7 @@ impl Handler<CancelGame> for GameActor {
       .players_leave_game(self.players.clone(), game_id)
       .await?;
 
+    let revision = self.next_revision();
     let packet_iter = self
       .players
       .iter()
@@ -45,6 +50,7 @@ impl Handler<CancelGame> for GameActor {
           game_id,
           player_id,
           reason: PlayerLeaveReason::GameCancelled.into(),
+          revision,
         }
         .encode_as_frame()?;
 
@@ -55,6 +61,19 @@ impl Handler<CancelGame> for GameActor {
 
     self.player_reg.broadcast_map(packet_iter).await?;
 
+    let message = match player_id {
+      Some(player_id) if player_id == self.host_player => "The host cancelled the game.",
+      _ => "The game was cancelled.",
+    };
+    self
+      .send_lobby_summary(
+        self.players.clone(),
+        LobbySummary::new(game_id, LobbyEndReason::Cancelled, message),
+      )
+      .await;
+
+    self.events.send(game_id, LobbyEventKind::Cancelled);
+
     Ok(())
   }
 }
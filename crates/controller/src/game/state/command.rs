@@ -0,0 +1,116 @@
+use crate::error::*;
+use crate::game::state::surrender::SurrenderVoteResult;
+use crate::game::state::GameActor;
+use crate::game::GameStatus;
+use crate::node::messages::{NodeGameCommand, NodeGameSurrender};
+use crate::state::ActorMapExt;
+use flo_net::packet::FloPacket;
+use flo_net::proto;
+use flo_net::proto::flo_common::GameCommand;
+use flo_state::{async_trait, Context, Handler, Message};
+use std::time::{Duration, Instant};
+
+const COMMAND_COOLDOWN: Duration = Duration::from_secs(3);
+
+/// Out-of-band command sent by a player during a live game,
+/// relayed to the other players and the game node
+pub struct GameCommandRequest {
+  pub player_id: i32,
+  pub command: GameCommand,
+}
+
+impl Message for GameCommandRequest {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<GameCommandRequest> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GameCommandRequest { player_id, command }: GameCommandRequest,
+  ) -> Result<()> {
+    let game_id = self.game_id;
+
+    if !self.players.contains(&player_id) {
+      return Err(Error::PlayerNotInGame);
+    }
+
+    match self.status {
+      GameStatus::Running | GameStatus::Paused => {}
+      _ => return Err(Error::GameNotRunning),
+    }
+
+    let node_id = self
+      .selected_node_id
+      .ok_or_else(|| Error::GameNodeNotSelected)?;
+
+    let now = Instant::now();
+    if let Some(last) = self.player_command_time_map.get(&player_id) {
+      if now.saturating_duration_since(*last) < COMMAND_COOLDOWN {
+        tracing::debug!(game_id, player_id, "game command throttled: {:?}", command);
+        return Ok(());
+      }
+    }
+    self.player_command_time_map.insert(player_id, now);
+
+    if command == GameCommand::SurrenderVote {
+      self.surrender_team(player_id)?;
+    }
+
+    tracing::info!(game_id, player_id, "game command: {:?}", command);
+
+    let mut pkt = proto::flo_connect::PacketGameCommand {
+      game_id,
+      player_id,
+      ..Default::default()
+    };
+    pkt.set_command(command);
+
+    let recipients: Vec<i32> = self
+      .players
+      .iter()
+      .cloned()
+      .filter(|id| *id != player_id)
+      .collect();
+    self
+      .player_reg
+      .broadcast(recipients, pkt.encode_as_frame()?)
+      .await?;
+
+    self
+      .nodes
+      .send_to(
+        node_id,
+        NodeGameCommand {
+          game_id,
+          player_id,
+          command,
+        },
+      )
+      .await?;
+
+    if command == GameCommand::SurrenderVote {
+      match self.surrender_vote(player_id)? {
+        SurrenderVoteResult::Pending { votes, required } => {
+          tracing::debug!(game_id, player_id, "surrender vote: {}/{}", votes, required);
+        }
+        SurrenderVoteResult::Passed { player_ids } => {
+          tracing::info!(game_id, "surrender vote passed: {:?}", player_ids);
+          self
+            .nodes
+            .send_to(
+              node_id,
+              NodeGameSurrender {
+                game_id,
+                player_ids,
+              },
+            )
+            .await?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
@@ -1,9 +1,13 @@
 use crate::error::{Error, Result};
-use crate::game::db::{CreateGameAsBotParams, CreateGameParams};
+use crate::game::db::{CreateGameAsBotParams, CreateGameParams, CreateGamesAsBotParams};
 use crate::game::state::registry::Register;
 use crate::game::state::GameRegistry;
-use crate::game::{Game, GameStatus};
+use crate::game::{Game, GameStatus, SavedGame};
+use crate::metrics::{GAMES_CREATED, GAME_CREATE_SECONDS};
+use crate::node::version::GAME_TARGET_VERSION;
 use flo_state::{async_trait, Context, Handler, Message};
+use std::collections::BTreeMap;
+use std::time::Instant;
 
 pub struct CreateGame {
   pub params: CreateGameParams,
@@ -21,10 +25,26 @@ impl Handler<CreateGame> for GameRegistry {
     CreateGame { params }: CreateGame,
   ) -> <CreateGame as Message>::Result {
     let player_id = params.player_id;
+    self.maintenance.check()?;
+    self.check_create_quota(player_id)?;
+    let t = Instant::now();
+
+    let target_version = GAME_TARGET_VERSION.clone();
+    if let Some(ref version) = target_version {
+      self.node_versions.check_any(version)?;
+    }
+
     let game = self
       .db
-      .exec(move |conn| crate::game::db::create(conn, params))
+      .exec(crate::profiling::db({
+        let target_version = target_version.clone();
+        move |conn| {
+          crate::game::state::quota::check_hosted_lobbies(conn, player_id)?;
+          crate::game::db::create(conn, params, target_version)
+        }
+      }))
       .await?;
+    observe_created("player", t, 1);
 
     self.register(Register {
       id: game.id,
@@ -32,6 +52,8 @@ impl Handler<CreateGame> for GameRegistry {
       host_player: game.created_by.id,
       players: game.get_player_ids(),
       node_id: None,
+      api_client_id: None,
+      target_version,
     });
 
     self
@@ -47,6 +69,8 @@ pub struct CreateGameAsBot {
   pub api_client_id: i32,
   pub api_player_id: i32,
   pub params: CreateGameAsBotParams,
+  /// Loads the save instead of the map
+  pub saved_game: Option<SavedGame>,
 }
 
 impl Message for CreateGameAsBot {
@@ -62,18 +86,135 @@ impl Handler<CreateGameAsBot> for GameRegistry {
       api_client_id,
       api_player_id,
       params,
+      saved_game,
     }: CreateGameAsBot,
   ) -> <CreateGameAsBot as Message>::Result {
+    self.maintenance.check()?;
+    self.check_create_as_bot_quota(api_client_id, params.node_id, 1)?;
+    let t = Instant::now();
+
+    let target_version = GAME_TARGET_VERSION.clone();
+    if let Some(ref version) = target_version {
+      self.node_versions.check_node(params.node_id, version)?;
+    }
+
     let (mut game, player_ids, mute_list_map) = self
       .db
-      .exec(move |conn| {
-        let game = crate::game::db::create_as_bot(conn, api_client_id, api_player_id, params)?;
-        let player_ids = game.get_player_ids();
-        let mute_list_map = crate::player::db::get_mute_list_map(conn, &player_ids)?;
-        Ok::<_, Error>((game, player_ids, mute_list_map))
-      })
+      .exec(crate::profiling::db({
+        let target_version = target_version.clone();
+        move |conn| {
+          let game = crate::game::db::create_as_bot(
+            conn,
+            api_client_id,
+            api_player_id,
+            params,
+            target_version,
+            saved_game,
+          )?;
+          let player_ids = game.get_player_ids();
+          let mute_list_map = crate::player::db::get_mute_list_map(conn, &player_ids)?;
+          Ok::<_, Error>((game, player_ids, mute_list_map))
+        }
+      }))
       .await?;
+    observe_created("bot", t, 1);
 
+    self
+      .register_bot_game(
+        api_client_id,
+        &mut game,
+        player_ids,
+        mute_list_map,
+        target_version,
+      )
+      .await?;
+
+    Ok(game)
+  }
+}
+
+/// Creates lobbies from a template in one transaction, e.g. for tournaments
+pub struct CreateGamesAsBot {
+  pub api_client_id: i32,
+  pub api_player_id: i32,
+  pub params: CreateGamesAsBotParams,
+}
+
+impl Message for CreateGamesAsBot {
+  type Result = Result<Vec<Game>>;
+}
+
+#[async_trait]
+impl Handler<CreateGamesAsBot> for GameRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    CreateGamesAsBot {
+      api_client_id,
+      api_player_id,
+      params,
+    }: CreateGamesAsBot,
+  ) -> <CreateGamesAsBot as Message>::Result {
+    let node_id = params.template.node_id;
+    self.maintenance.check()?;
+    self.check_create_as_bot_quota(api_client_id, node_id, params.games.len())?;
+    let t = Instant::now();
+
+    let target_version = GAME_TARGET_VERSION.clone();
+    if let Some(ref version) = target_version {
+      self.node_versions.check_node(node_id, version)?;
+    }
+
+    let (mut games, mut mute_list_map) = self
+      .db
+      .exec(crate::profiling::db({
+        let target_version = target_version.clone();
+        move |conn| {
+          let games = crate::game::db::create_games_as_bot(
+            conn,
+            api_client_id,
+            api_player_id,
+            params,
+            target_version,
+          )?;
+          let player_ids: Vec<i32> = games.iter().flat_map(|g| g.get_player_ids()).collect();
+          let mute_list_map = crate::player::db::get_mute_list_map(conn, &player_ids)?;
+          Ok::<_, Error>((games, mute_list_map))
+        }
+      }))
+      .await?;
+    observe_created("batch", t, games.len());
+
+    for game in &mut games {
+      let player_ids = game.get_player_ids();
+      let mute_list_map = player_ids
+        .iter()
+        .filter_map(|id| mute_list_map.remove(id).map(|v| (*id, v)))
+        .collect();
+      self
+        .register_bot_game(
+          api_client_id,
+          game,
+          player_ids,
+          mute_list_map,
+          target_version.clone(),
+        )
+        .await?;
+    }
+
+    Ok(games)
+  }
+}
+
+impl GameRegistry {
+  async fn register_bot_game(
+    &mut self,
+    api_client_id: i32,
+    game: &mut Game,
+    player_ids: Vec<i32>,
+    mute_list_map: BTreeMap<i32, Vec<i32>>,
+    target_version: Option<String>,
+  ) -> Result<()> {
     if game.mask_player_names {
       for (idx, slot) in game.slots.iter_mut().enumerate() {
         slot
@@ -89,6 +230,8 @@ impl Handler<CreateGameAsBot> for GameRegistry {
       host_player: game.created_by.id,
       players: player_ids.clone(),
       node_id: game.node.as_ref().map(|v| v.id),
+      api_client_id: Some(api_client_id),
+      target_version,
     });
 
     self
@@ -96,6 +239,15 @@ impl Handler<CreateGameAsBot> for GameRegistry {
       .players_replace_game(player_ids, game.clone(), mute_list_map)
       .await?;
 
-    Ok(game)
+    Ok(())
   }
 }
+
+fn observe_created(kind: &str, t: Instant, games: usize) {
+  GAME_CREATE_SECONDS
+    .with_label_values(&[kind])
+    .observe(t.elapsed().as_secs_f64());
+  GAMES_CREATED
+    .with_label_values(&[kind])
+    .inc_by(games as u64);
+}
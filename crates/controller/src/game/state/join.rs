@@ -1,6 +1,10 @@
 use crate::error::*;
+use crate::game::event::LobbyEventKind;
+use crate::game::names::name_conflicts;
 use crate::game::state::GameActor;
 use crate::game::Game;
+use crate::node::version::version_mismatch;
+use crate::notification::{GameNotification, GameNotificationKind, NotifyGamePlayers};
 use diesel::prelude::*;
 use flo_net::packet::FloPacket;
 use flo_net::proto;
@@ -23,37 +27,84 @@ impl Handler<PlayerJoin> for GameActor {
     _: &mut Context<Self>,
     PlayerJoin { player_id }: PlayerJoin,
   ) -> Result<Game> {
+    self.require_lobby()?;
     let game_id = self.game_id;
-    let (game, mute_list) = self
+    let ranked = self.api_client_id.is_some();
+    let (mut game, mut mute_list_map, name_conflicts) = self
       .db
-      .exec(move |conn| {
+      .exec(crate::profiling::db(move |conn| {
         conn.transaction(|| {
-          crate::game::db::add_player(conn, game_id, player_id)?;
+          if ranked {
+            crate::player::penalty::check_ranked_join(conn, player_id)?;
+          }
+          crate::game::state::quota::check_player_join(conn, game_id, player_id)?;
+          let slots = crate::game::db::add_player(conn, game_id, player_id)?;
           let game = crate::game::db::get_full(conn, game_id)?;
-          let mut mute_list_map =
-            crate::player::db::get_mute_list_map(conn, &game.get_player_ids())?;
-          Ok::<_, Error>((game, mute_list_map.remove(&player_id).unwrap_or_default()))
+          let mute_list_map = crate::player::db::get_mute_list_map(conn, &game.get_player_ids())?;
+          Ok::<_, Error>((game, mute_list_map, name_conflicts(&slots)))
         })
-      })
+      }))
       .await?;
 
     self.players.push(player_id);
+    game.revision = self.next_revision();
+    self
+      .events
+      .send(game_id, LobbyEventKind::PlayerJoined { player_id });
+
+    // the joined player either got a discriminator, or has the lower id and took
+    // the name over from a player already in the lobby
+    let mut renamed_others = false;
+    for conflict in name_conflicts {
+      if conflict.player_id != player_id && conflict.conflicts_with != player_id {
+        continue;
+      }
+      renamed_others = renamed_others || conflict.player_id != player_id;
+      if conflict.impersonation {
+        tracing::warn!(
+          game_id,
+          player_id = conflict.player_id,
+          imitated_player_id = conflict.conflicts_with,
+          "lookalike player name"
+        );
+        self.events.send(
+          game_id,
+          LobbyEventKind::NameImpersonation {
+            player_id: conflict.player_id,
+            imitated_player_id: conflict.conflicts_with,
+          },
+        );
+      }
+    }
 
     // send game info to joined player
     self
       .player_reg
-      .player_replace_game(player_id, game.clone(), mute_list)
+      .player_replace_game(
+        player_id,
+        game.clone(),
+        mute_list_map.remove(&player_id).unwrap_or_default(),
+      )
       .await?;
 
-    {
+    self.warn_version_mismatch(player_id).await;
+
+    let mut players = game.get_player_ids();
+    players.retain(|id| *id != player_id);
+
+    if renamed_others {
+      // names of players already in the lobby changed, resend the whole game
+      self
+        .player_reg
+        .players_replace_game(players, game.clone(), mute_list_map)
+        .await?;
+    } else {
       let slot_info = game
         .get_player_slot_info(player_id)
         .ok_or_else(|| Error::PlayerSlotNotFound)?;
       let player: proto::flo_connect::PlayerInfo = slot_info.player.clone().pack()?;
 
       // send notification to other players in this game
-      let mut players = game.get_player_ids();
-      players.retain(|id| *id != player_id);
       let frame = {
         use proto::flo_connect::*;
         PacketGamePlayerEnter {
@@ -65,12 +116,70 @@ impl Handler<PlayerJoin> for GameActor {
             ..Default::default()
           }
           .into(),
+          revision: game.revision,
         }
       }
       .encode_as_frame()?;
       self.player_reg.broadcast(players, frame).await?;
     }
 
+    // a join always takes an open slot, so the lobby just filled up
+    if game.is_full() {
+      self.notify_lobby_full(&game).await;
+    }
+
     Ok(game)
   }
 }
+
+impl GameActor {
+  async fn notify_lobby_full(&self, game: &Game) {
+    let message = NotifyGamePlayers {
+      notification: GameNotification {
+        game_id: game.id,
+        game_name: game.name.clone(),
+        kind: GameNotificationKind::LobbyFull,
+      },
+      player_ids: game.get_player_ids(),
+    };
+    if let Err(err) = self.notifications.notify(message).await {
+      tracing::error!(game_id = game.id, "notify game players: {}", err);
+    }
+  }
+
+  /// Lets the player know early that the start version check is going to fail,
+  /// the game start isn't blocked so players can still update their game in the lobby
+  async fn warn_version_mismatch(&self, player_id: i32) {
+    let game_id = self.game_id;
+    let war3_version = match self.player_reg.war3_version(player_id).await {
+      Ok(version) => version,
+      Err(err) => {
+        tracing::error!(game_id, player_id, "get player war3 version: {}", err);
+        return;
+      }
+    };
+    let pkt = match version_mismatch(
+      war3_version.as_deref(),
+      self.target_version.as_deref(),
+      Some(game_id),
+    ) {
+      Some(pkt) => pkt,
+      None => return,
+    };
+    tracing::debug!(
+      game_id,
+      player_id,
+      "war3 version mismatch: {}, expected {}",
+      pkt.war3_version,
+      pkt.expected_version
+    );
+    match pkt.encode_as_frame() {
+      Ok(frame) => {
+        if let Err(err) = self.player_reg.send(player_id, frame).await {
+          tracing::error!(game_id, player_id, "send war3 version mismatch: {}", err);
+        }
+      }
+      Err(err) => tracing::error!(game_id, "encode war3 version mismatch: {}", err),
+    }
+  }
+}
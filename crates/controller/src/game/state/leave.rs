@@ -1,7 +1,10 @@
 use crate::error::*;
+use crate::game::event::LobbyEventKind;
 use crate::game::state::GameActor;
+use crate::game::summary::{LobbyEndReason, LobbySummary};
 use crate::game::{GameStatus, SlotClientStatus};
 use crate::node::{messages as node_messages, PlayerLeaveResponse};
+use crate::player::penalty::OffenseKind;
 use crate::player::state::sender::PlayerFrames;
 use crate::state::ActorMapExt;
 use diesel::prelude::*;
@@ -61,6 +64,10 @@ impl Handler<PlayerLeave> for GameActor {
       .player_leave_game(player_id, self.game_id)
       .await?;
 
+    self
+      .events
+      .send(game_id, LobbyEventKind::PlayerLeft { player_id });
+
     Ok(result)
   }
 }
@@ -73,7 +80,9 @@ async fn leave_game_lobby(
 ) -> Result<PlayerLeaveResult> {
   let leave = state
     .db
-    .exec(move |conn| crate::game::db::remove_player(conn, game_id, player_id))
+    .exec(crate::profiling::db(move |conn| {
+      crate::game::db::remove_player(conn, game_id, player_id)
+    }))
     .await?;
 
   let recipient_player_ids: Vec<i32> = leave
@@ -92,6 +101,26 @@ async fn leave_game_lobby(
   )
   .await?;
 
+  if leave.game_ended {
+    let message = if player_id == state.host_player {
+      "The host left the game."
+    } else {
+      "All players left the game."
+    };
+    let recipients = leave
+      .removed_players
+      .iter()
+      .cloned()
+      .filter(|id| *id != player_id)
+      .collect();
+    state
+      .send_lobby_summary(
+        recipients,
+        LobbySummary::new(game_id, LobbyEndReason::Closed, message),
+      )
+      .await;
+  }
+
   Ok(PlayerLeaveResult {
     game_ended: leave.game_ended,
   })
@@ -105,14 +134,15 @@ async fn leave_game_abort(
   player_id: i32,
   node_id: i32,
 ) -> Result<PlayerLeaveResult> {
+  let left_early = state.is_early_leave();
   let active_player_ids = state
     .db
-    .exec(move |conn| {
+    .exec(crate::profiling::db(move |conn| {
       conn.transaction(|| {
         crate::game::db::leave_node(conn, game_id, player_id)?;
         crate::game::db::get_node_active_player_ids(conn, game_id)
       })
-    })
+    }))
     .await?;
 
   let res = state
@@ -163,6 +193,7 @@ async fn leave_game_abort(
     let mut pkt = flo_net::proto::flo_connect::PacketGameSlotClientStatusUpdate {
       game_id,
       player_id,
+      revision: state.next_revision(),
       ..Default::default()
     };
     pkt.set_status(SlotClientStatus::Left.into_proto_enum());
@@ -184,6 +215,12 @@ async fn leave_game_abort(
   )
   .await?;
 
+  if left_early {
+    state
+      .penalize(vec![player_id], OffenseKind::EarlyLeave)
+      .await;
+  }
+
   Ok(PlayerLeaveResult { game_ended: false })
 }
 
@@ -212,6 +249,7 @@ async fn broadcast(
       game_id,
       player_id,
       reason: proto::flo_connect::PlayerLeaveReason::Left.into(),
+      revision: state.next_revision(),
     }
     .encode_as_frame()?;
 
@@ -0,0 +1,187 @@
+//! End-to-end encrypted chat of private lobbies, enabled by the host with `GameMetadata.e2e_chat`.
+//!
+//! Players publish a public key, the controller relays it to the other players of the lobby.
+//! Messages are encrypted by the sender for each recipient and relayed as is,
+//! they skip the chat moderators and are not stored.
+
+use crate::chat::RateLimit;
+use crate::error::*;
+use crate::game::state::GameActor;
+use crate::game::GameStatus;
+use flo_net::packet::FloPacket;
+use flo_net::proto::flo_connect::{LobbyChatEnvelope, PacketLobbyChatKey, PacketLobbyChatMessage};
+use flo_state::{async_trait, Context, Handler, Message};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+const LOBBY_CHAT_MAX_KEY_LEN: usize = 256;
+const LOBBY_CHAT_MAX_CIPHERTEXT_LEN: usize = 4096;
+
+/// Created when the first player publishes a key
+#[derive(Default)]
+pub struct LobbyChat {
+  keys: BTreeMap<i32, Vec<u8>>,
+  rate_limits: BTreeMap<i32, RateLimit>,
+}
+
+impl GameActor {
+  async fn lobby_chat_mut(&mut self) -> Result<&mut LobbyChat> {
+    if self.lobby_chat.is_none() {
+      let game_id = self.game_id;
+      let metadata = self
+        .db
+        .exec(crate::profiling::db(move |conn| {
+          crate::game::db::get_metadata(conn, game_id)
+        }))
+        .await?;
+      if !metadata.e2e_chat {
+        return Err(Error::LobbyChatDisabled);
+      }
+    }
+    Ok(self.lobby_chat.get_or_insert_with(Default::default))
+  }
+}
+
+pub struct UpdateLobbyChatKey {
+  pub player_id: i32,
+  pub public_key: Vec<u8>,
+}
+
+impl Message for UpdateLobbyChatKey {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<UpdateLobbyChatKey> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    UpdateLobbyChatKey {
+      player_id,
+      public_key,
+    }: UpdateLobbyChatKey,
+  ) -> Result<()> {
+    let game_id = self.game_id;
+
+    if !self.players.contains(&player_id) {
+      return Err(Error::PlayerNotInGame);
+    }
+
+    if self.status != GameStatus::Preparing {
+      return Err(Error::GameStarted);
+    }
+
+    if public_key.is_empty() || public_key.len() > LOBBY_CHAT_MAX_KEY_LEN {
+      return Err(Error::LobbyChatInvalid("public key"));
+    }
+
+    let players = self.players.clone();
+    let chat = self.lobby_chat_mut().await?;
+    chat.keys.insert(player_id, public_key.clone());
+    let keys: Vec<_> = chat
+      .keys
+      .iter()
+      .filter(|(id, _)| **id != player_id && players.contains(*id))
+      .map(|(id, key)| (*id, key.clone()))
+      .collect();
+
+    let frame = PacketLobbyChatKey {
+      game_id,
+      player_id,
+      public_key,
+    }
+    .encode_as_frame()?;
+    let others = players.into_iter().filter(|id| *id != player_id).collect();
+    self.player_reg.broadcast(others, frame).await?;
+
+    for (id, public_key) in keys {
+      let frame = PacketLobbyChatKey {
+        game_id,
+        player_id: id,
+        public_key,
+      }
+      .encode_as_frame()?;
+      self.player_reg.send(player_id, frame).await?;
+    }
+
+    Ok(())
+  }
+}
+
+pub struct SendLobbyChatMessage {
+  pub player_id: i32,
+  pub envelopes: Vec<LobbyChatEnvelope>,
+}
+
+impl Message for SendLobbyChatMessage {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<SendLobbyChatMessage> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    SendLobbyChatMessage {
+      player_id,
+      envelopes,
+    }: SendLobbyChatMessage,
+  ) -> Result<()> {
+    let game_id = self.game_id;
+
+    if !self.players.contains(&player_id) {
+      return Err(Error::PlayerNotInGame);
+    }
+
+    let chat = self.lobby_chat.as_mut().ok_or(Error::LobbyChatDisabled)?;
+    if !chat.keys.contains_key(&player_id) {
+      return Err(Error::LobbyChatInvalid("sender has no key"));
+    }
+
+    if envelopes.is_empty() || envelopes.len() >= self.players.len() {
+      return Err(Error::LobbyChatInvalid("number of recipients"));
+    }
+    for envelope in &envelopes {
+      if envelope.player_id == player_id
+        || !self.players.contains(&envelope.player_id)
+        || !chat.keys.contains_key(&envelope.player_id)
+      {
+        return Err(Error::LobbyChatInvalid("recipient"));
+      }
+      if envelope.ciphertext.len() > LOBBY_CHAT_MAX_CIPHERTEXT_LEN {
+        return Err(Error::LobbyChatInvalid("message too long"));
+      }
+    }
+
+    if !chat
+      .rate_limits
+      .entry(player_id)
+      .or_default()
+      .check(Instant::now())
+    {
+      return Err(Error::ChatRateLimited);
+    }
+
+    let muted_by = self
+      .db
+      .exec(crate::profiling::db(move |conn| {
+        crate::chat::db::get_muted_by(conn, player_id)
+      }))
+      .await?;
+
+    for envelope in envelopes {
+      if muted_by.contains(&envelope.player_id) {
+        continue;
+      }
+      let frame = PacketLobbyChatMessage {
+        game_id,
+        player_id,
+        ciphertext: envelope.ciphertext,
+      }
+      .encode_as_frame()?;
+      self.player_reg.send(envelope.player_id, frame).await?;
+    }
+
+    Ok(())
+  }
+}
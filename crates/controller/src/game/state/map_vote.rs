@@ -0,0 +1,267 @@
+use crate::error::*;
+use crate::game::event::LobbyEventKind;
+use crate::game::state::resync::game_info_frame;
+use crate::game::state::GameActor;
+use crate::game::GameStatus;
+use crate::map::Map;
+use flo_net::packet::FloPacket;
+use flo_net::proto::flo_connect::{self as proto, PacketGameMapVote};
+use flo_state::{async_trait, Context, Handler, Message};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+pub const MAP_VOTE_MIN_MAPS: usize = 2;
+pub const MAP_VOTE_MAX_MAPS: usize = 5;
+
+/// Players have to vote within this window after the host started the vote
+pub static FLO_MAP_VOTE_WINDOW_SECS: Lazy<Duration> = Lazy::new(|| {
+  Duration::from_secs(
+    std::env::var("FLO_MAP_VOTE_WINDOW_SECS")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .unwrap_or(30),
+  )
+});
+
+#[derive(Debug)]
+pub struct MapVote {
+  started_at: Instant,
+  maps: Vec<Map>,
+  /// Map index voted by each player, players can change their vote
+  votes: BTreeMap<i32, usize>,
+}
+
+impl MapVote {
+  fn new(maps: Vec<Map>) -> Self {
+    Self {
+      started_at: Instant::now(),
+      maps,
+      votes: BTreeMap::new(),
+    }
+  }
+
+  fn counts(&self) -> Vec<i32> {
+    let mut counts = vec![0; self.maps.len()];
+    for index in self.votes.values() {
+      counts[*index] += 1;
+    }
+    counts
+  }
+
+  fn packet(&self, game_id: i32, ended: bool, winner_index: Option<usize>) -> PacketGameMapVote {
+    let remaining = FLO_MAP_VOTE_WINDOW_SECS.saturating_sub(self.started_at.elapsed());
+    PacketGameMapVote {
+      game_id,
+      maps: self
+        .maps
+        .iter()
+        .map(|map| proto::Map {
+          sha1: map.sha1.to_vec(),
+          checksum: map.checksum,
+          path: map.path.clone(),
+        })
+        .collect(),
+      votes: self.counts(),
+      remaining_secs: if ended { 0 } else { remaining.as_secs() as i32 },
+      ended,
+      winner_index: winner_index.map(|v| v as i32),
+    }
+  }
+}
+
+/// Most voted map, ties go to the map proposed first
+fn winner_index(counts: &[i32]) -> Option<usize> {
+  let max = counts.iter().cloned().max().filter(|v| *v > 0)?;
+  counts.iter().position(|v| *v == max)
+}
+
+/// Starts a vote among the joined players between registered maps proposed by the host
+pub struct StartMapVote {
+  pub player_id: i32,
+  pub map_sha1s: Vec<Vec<u8>>,
+}
+
+impl Message for StartMapVote {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<StartMapVote> for GameActor {
+  async fn handle(
+    &mut self,
+    ctx: &mut Context<Self>,
+    StartMapVote {
+      player_id,
+      map_sha1s,
+    }: StartMapVote,
+  ) -> Result<()> {
+    let game_id = self.game_id;
+
+    if player_id != self.host_player {
+      return Err(Error::PlayerNotHost);
+    }
+
+    if self.status != GameStatus::Preparing || self.started() {
+      return Err(Error::GameStarted);
+    }
+
+    if self.map_vote.is_some() {
+      return Err(Error::MapVoteInProgress);
+    }
+
+    if map_sha1s.len() < MAP_VOTE_MIN_MAPS || map_sha1s.len() > MAP_VOTE_MAX_MAPS {
+      return Err(Error::MapVoteInvalid("number of maps"));
+    }
+
+    if (1..map_sha1s.len()).any(|i| map_sha1s[..i].contains(&map_sha1s[i])) {
+      return Err(Error::MapVoteInvalid("duplicate maps"));
+    }
+
+    let maps = self
+      .db
+      .exec(crate::profiling::db(move |conn| {
+        crate::map::db::get_registered(conn, &map_sha1s)
+      }))
+      .await?;
+
+    let vote = MapVote::new(maps);
+    let started_at = vote.started_at;
+    let frame = vote.packet(game_id, false, None).encode_as_frame()?;
+    self.map_vote = Some(vote);
+    self
+      .player_reg
+      .broadcast(self.players.clone(), frame)
+      .await?;
+
+    let addr = ctx.addr();
+    ctx.spawn(async move {
+      tokio::time::sleep(*FLO_MAP_VOTE_WINDOW_SECS).await;
+      addr.notify(EndMapVote { started_at }).await.ok();
+    });
+
+    Ok(())
+  }
+}
+
+pub struct CastMapVote {
+  pub player_id: i32,
+  pub map_index: usize,
+}
+
+impl Message for CastMapVote {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<CastMapVote> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    CastMapVote {
+      player_id,
+      map_index,
+    }: CastMapVote,
+  ) -> Result<()> {
+    let game_id = self.game_id;
+
+    if !self.players.contains(&player_id) {
+      return Err(Error::PlayerNotInGame);
+    }
+
+    let vote = self
+      .map_vote
+      .as_mut()
+      .ok_or_else(|| Error::MapVoteNotFound)?;
+    if map_index >= vote.maps.len() {
+      return Err(Error::MapVoteInvalid("map index"));
+    }
+    vote.votes.insert(player_id, map_index);
+
+    // ends early once every player has voted
+    if self.players.iter().all(|id| vote.votes.contains_key(id)) {
+      return self.end_map_vote().await;
+    }
+
+    let frame = vote.packet(game_id, false, None).encode_as_frame()?;
+    self
+      .player_reg
+      .broadcast(self.players.clone(), frame)
+      .await?;
+    Ok(())
+  }
+}
+
+/// Sent when the voting window of the vote started at `started_at` closes
+struct EndMapVote {
+  started_at: Instant,
+}
+
+impl Message for EndMapVote {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<EndMapVote> for GameActor {
+  async fn handle(&mut self, _: &mut Context<Self>, EndMapVote { started_at }: EndMapVote) {
+    // the vote ended early
+    if self.map_vote.as_ref().map(|v| v.started_at) != Some(started_at) {
+      return;
+    }
+    if let Err(err) = self.end_map_vote().await {
+      tracing::warn!(game_id = self.game_id, "end map vote: {}", err);
+    }
+  }
+}
+
+impl GameActor {
+  /// Broadcasts the result and switches the lobby to the winning map
+  async fn end_map_vote(&mut self) -> Result<()> {
+    let game_id = self.game_id;
+    let vote = if let Some(vote) = self.map_vote.take() {
+      vote
+    } else {
+      return Ok(());
+    };
+
+    let winner_index = winner_index(&vote.counts());
+    let frame = vote.packet(game_id, true, winner_index).encode_as_frame()?;
+    self
+      .player_reg
+      .broadcast(self.players.clone(), frame)
+      .await?;
+
+    let map = if let Some(map) = winner_index.and_then(|idx| vote.maps.into_iter().nth(idx)) {
+      map
+    } else {
+      return Ok(());
+    };
+
+    let mut game = self
+      .db
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::db::update_map(conn, game_id, map)
+      }))
+      .await?;
+    game.revision = self.next_revision();
+    let map_name = game.map.name.clone();
+    self
+      .player_reg
+      .broadcast(self.players.clone(), game_info_frame(game)?)
+      .await?;
+
+    self
+      .events
+      .send(game_id, LobbyEventKind::MapChanged { map_name });
+
+    Ok(())
+  }
+}
+
+#[test]
+fn test_map_vote_winner_index() {
+  assert_eq!(winner_index(&[]), None);
+  assert_eq!(winner_index(&[0, 0, 0]), None);
+  assert_eq!(winner_index(&[1, 3, 2]), Some(1));
+  assert_eq!(winner_index(&[2, 1, 2]), Some(0));
+}
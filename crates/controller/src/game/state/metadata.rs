@@ -0,0 +1,60 @@
+use crate::error::*;
+use crate::game::event::LobbyEventKind;
+use crate::game::state::GameActor;
+use crate::game::GameMetadata;
+use flo_net::packet::FloPacket;
+use flo_net::proto::flo_connect::PacketGameMetadataUpdate;
+use flo_state::{async_trait, Context, Handler, Message};
+
+/// Replaces the lobby description, tags, language and localized names,
+/// only the host can update them before the game starts
+pub struct UpdateGameMetadata {
+  pub player_id: i32,
+  pub metadata: GameMetadata,
+}
+
+impl Message for UpdateGameMetadata {
+  type Result = Result<GameMetadata>;
+}
+
+#[async_trait]
+impl Handler<UpdateGameMetadata> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    UpdateGameMetadata {
+      player_id,
+      metadata,
+    }: UpdateGameMetadata,
+  ) -> Result<GameMetadata> {
+    let game_id = self.game_id;
+
+    let metadata = metadata.normalize();
+
+    let metadata = self
+      .db
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::db::update_metadata(conn, game_id, player_id, metadata)
+      }))
+      .await?;
+
+    if !metadata.e2e_chat {
+      self.lobby_chat = None;
+    }
+
+    let frame = PacketGameMetadataUpdate {
+      game_id,
+      metadata: Some(metadata.clone().into()),
+      revision: self.next_revision(),
+    }
+    .encode_as_frame()?;
+    self
+      .player_reg
+      .broadcast(self.players.clone(), frame)
+      .await?;
+
+    self.events.send(game_id, LobbyEventKind::MetadataUpdated);
+
+    Ok(metadata)
+  }
+}
@@ -1,20 +1,36 @@
 pub mod cancel;
+pub mod command;
 pub mod create;
 pub mod join;
 pub mod leave;
+pub mod lobby_chat;
+pub mod map_vote;
+pub mod metadata;
 pub mod node;
+pub mod penalty;
 pub mod player;
+pub mod quota;
 pub mod registry;
+pub mod restore;
+pub mod resync;
+pub mod save;
 pub mod slot;
 pub mod start;
 pub mod status;
+pub mod summary;
+pub mod surrender;
 
 pub use status::{GameSlotClientStatusUpdate, GameStatusUpdate};
 
 use crate::error::*;
 use crate::game::db::{get_all_active_game_state, get_expired_games};
+use crate::game::event::LobbyEventSender;
+use crate::game::lifecycle::{self, GameStage};
 use crate::game::{GameStatus, SlotClientStatus};
+use crate::maintenance::MaintenanceState;
+use crate::node::version::NodeVersionMatrix;
 use crate::node::{NodeRegistry, PlayerToken};
+use crate::notification::NotificationDispatcher;
 use crate::player::state::sender::PlayerRegistryHandle;
 
 use crate::game::state::cancel::CancelGame;
@@ -22,11 +38,15 @@ use crate::game::state::registry::Remove;
 use crate::player::state::PlayerRegistry;
 use crate::state::{Data, GetActorEntry};
 use bs_diesel_utils::ExecutorRef;
+use chrono::{DateTime, Utc};
 use flo_state::*;
+use lobby_chat::LobbyChat;
+use map_vote::MapVote;
 use start::StartGameState;
+use surrender::SurrenderVote;
 use std::collections::BTreeMap;
-use std::collections::HashMap;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
 const GAME_INACTIVE_CHECK_INTERVAL: Duration = Duration::from_secs(3600 * 30);
@@ -35,10 +55,15 @@ pub struct GameRegistry {
   db: ExecutorRef,
   players: PlayerRegistryHandle,
   nodes: Addr<NodeRegistry>,
+  notifications: Addr<NotificationDispatcher>,
   map: BTreeMap<i32, Owner<GameActor>>,
   player_games_map: BTreeMap<i32, Vec<i32>>,
   game_players_map: BTreeMap<i32, Vec<i32>>,
   game_node_map: BTreeMap<i32, i32>,
+  game_api_client_map: BTreeMap<i32, i32>,
+  events: LobbyEventSender,
+  maintenance: MaintenanceState,
+  node_versions: NodeVersionMatrix,
 }
 
 impl GameRegistry {
@@ -46,18 +71,31 @@ impl GameRegistry {
     db: ExecutorRef,
     player_packet_sender: PlayerRegistryHandle,
     nodes: Addr<NodeRegistry>,
+    notifications: Addr<NotificationDispatcher>,
+    events: LobbyEventSender,
+    maintenance: MaintenanceState,
+    node_versions: NodeVersionMatrix,
   ) -> Result<GameRegistry> {
-    let games = db.exec(|conn| get_all_active_game_state(conn)).await?;
+    let games = db
+      .exec(crate::profiling::db(|conn| get_all_active_game_state(conn)))
+      .await?;
     let mut map = BTreeMap::new();
     let mut player_games_map = BTreeMap::new();
     let mut game_players_map = BTreeMap::new();
     let mut game_node_map = BTreeMap::new();
+    let mut game_api_client_map = BTreeMap::new();
 
     for game in games {
       let mut players = Vec::with_capacity(game.players.len());
       let mut player_tokens = HashMap::new();
 
       game_players_map.insert(game.id, game.players.iter().map(|t| t.0).collect());
+      let start_interrupted_players = if game.start_interrupted {
+        tracing::info!(game_id = game.id, "lobby start interrupted by restart");
+        game.players.iter().map(|t| t.0).collect()
+      } else {
+        HashSet::new()
+      };
       for (id, token) in game.players {
         players.push(id);
         if let Some(token) = token.and_then(|v| PlayerToken::from_vec(id, v)) {
@@ -73,6 +111,11 @@ impl GameRegistry {
         game_node_map.insert(game.id, node_id);
       }
 
+      if let Some(api_client_id) = game.api_client_id {
+        game_api_client_map.insert(game.id, api_client_id);
+      }
+      events.register_game(game.id, game.api_client_id);
+
       map.insert(
         game.id,
         Owner::new(GameActor {
@@ -80,13 +123,27 @@ impl GameRegistry {
           db: db.clone(),
           player_reg: player_packet_sender.clone(),
           nodes: nodes.clone(),
+          notifications: notifications.clone(),
           status: game.status,
           host_player: game.created_by,
           players,
           selected_node_id: game.node_id,
           start_state: None,
           player_tokens,
-          player_client_status_map: Default::default(),
+          player_client_status_map: game.client_status_map,
+          start_interrupted_players,
+          player_command_time_map: Default::default(),
+          surrender_votes: Default::default(),
+          player_teams: game.player_teams,
+          map_vote: None,
+          lobby_chat: None,
+          events: events.clone(),
+          maintenance: maintenance.clone(),
+          node_versions: node_versions.clone(),
+          target_version: game.target_version,
+          revision: 0,
+          api_client_id: game.api_client_id,
+          started_at: game.started_at,
         }),
       );
     }
@@ -95,17 +152,25 @@ impl GameRegistry {
       db: db.clone(),
       players: player_packet_sender.clone(),
       nodes: nodes.clone(),
+      notifications,
       map,
       player_games_map,
       game_players_map,
       game_node_map,
+      game_api_client_map,
+      events,
+      maintenance,
+      node_versions,
     };
 
     Ok(state)
   }
 
   async fn remove_expired_games(&mut self, ctx: &mut Context<Self>) -> Result<()> {
-    let ids = self.db.exec(|conn| get_expired_games(conn)).await?;
+    let ids = self
+      .db
+      .exec(crate::profiling::db(|conn| get_expired_games(conn)))
+      .await?;
 
     let mut cancelled = vec![];
     for id in ids {
@@ -147,7 +212,17 @@ impl Service<Data> for GameRegistry {
   async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
     let players = registry.resolve::<PlayerRegistry>().await?;
     let nodes = registry.resolve::<NodeRegistry>().await?;
-    Self::init(registry.data().db.clone(), players.into(), nodes).await
+    let notifications = registry.resolve::<NotificationDispatcher>().await?;
+    Self::init(
+      registry.data().db.clone(),
+      players.into(),
+      nodes,
+      notifications,
+      registry.data().lobby_events.clone(),
+      registry.data().maintenance.clone(),
+      registry.data().node_versions.clone(),
+    )
+    .await
   }
 }
 
@@ -191,6 +266,7 @@ pub struct GameActor {
   pub db: ExecutorRef,
   pub player_reg: PlayerRegistryHandle,
   pub nodes: Addr<NodeRegistry>,
+  pub notifications: Addr<NotificationDispatcher>,
   pub status: GameStatus,
   pub host_player: i32,
   pub players: Vec<i32>,
@@ -198,6 +274,26 @@ pub struct GameActor {
   pub start_state: Option<Owner<StartGameState>>,
   pub player_tokens: HashMap<i32, [u8; 16]>,
   pub player_client_status_map: HashMap<i32, SlotClientStatus>,
+  /// Players that haven't been told their game start was interrupted by a restart
+  pub start_interrupted_players: HashSet<i32>,
+  pub player_command_time_map: HashMap<i32, Instant>,
+  pub surrender_votes: HashMap<i32, SurrenderVote>,
+  /// Team of each player, set when the game starts
+  pub player_teams: HashMap<i32, i32>,
+  pub map_vote: Option<MapVote>,
+  pub lobby_chat: Option<LobbyChat>,
+  pub events: LobbyEventSender,
+  pub maintenance: MaintenanceState,
+  pub node_versions: NodeVersionMatrix,
+  /// Warcraft III version the game was created for, selected nodes must support it
+  pub target_version: Option<String>,
+  /// Lobby update revision, sent with every lobby update so clients can detect missed ones.
+  /// Starts over from 0 when the controller restarts, clients resync on the next game info
+  pub revision: u64,
+  /// Set if the game was created by an API client, joining requires no active penalty
+  pub api_client_id: Option<i32>,
+  /// Time the game started running, used to tell early leaves
+  pub started_at: Option<DateTime<Utc>>,
 }
 
 impl Actor for GameActor {}
@@ -206,4 +302,27 @@ impl GameActor {
   fn started(&self) -> bool {
     self.start_state.is_some() || !self.player_tokens.is_empty()
   }
+
+  fn stage(&self) -> GameStage {
+    GameStage::new(self.status, self.start_state.is_some())
+  }
+
+  fn check_transition(&self, to: GameStage) -> Result<()> {
+    lifecycle::transition(self.game_id, self.stage(), to).map(|_| ())
+  }
+
+  /// Players and slots can't change once the start check began,
+  /// or the game could be created with open slots
+  fn require_lobby(&self) -> Result<()> {
+    if self.stage() != GameStage::Lobby {
+      return Err(Error::GameStarted);
+    }
+    Ok(())
+  }
+
+  /// Called once per lobby update packet, in broadcast order
+  fn next_revision(&mut self) -> u64 {
+    self.revision += 1;
+    self.revision
+  }
 }
@@ -27,14 +27,25 @@ impl Handler<SelectNode> for GameActor {
       return Err(Error::GameStarted);
     }
 
+    if let (Some(node_id), Some(version)) = (node_id, self.target_version.as_ref()) {
+      self.node_versions.check_node(node_id, version)?;
+    }
+
     self
       .db
-      .exec(move |conn| crate::game::db::select_node(conn, game_id, player_id, node_id))
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::db::select_node(conn, game_id, player_id, node_id)
+      }))
       .await?;
 
     self.selected_node_id = node_id;
 
-    let frame = proto::flo_connect::PacketGameSelectNode { game_id, node_id }.encode_as_frame()?;
+    let frame = proto::flo_connect::PacketGameSelectNode {
+      game_id,
+      node_id,
+      revision: self.next_revision(),
+    }
+    .encode_as_frame()?;
     self
       .player_reg
       .broadcast(self.players.clone(), frame)
@@ -0,0 +1,65 @@
+use crate::game::state::GameActor;
+use crate::game::GameStatus;
+use crate::player::penalty::{OffenseKind, PENALTY_POLICY};
+use chrono::Utc;
+use flo_net::packet::FloPacket;
+use flo_net::proto::flo_connect::PacketPlayerPenaltyUpdate;
+
+impl GameActor {
+  /// Leaving now counts as an offense
+  pub(crate) fn is_early_leave(&self) -> bool {
+    match self.status {
+      GameStatus::Created | GameStatus::Running | GameStatus::Paused => {}
+      GameStatus::Preparing | GameStatus::Ended | GameStatus::Terminated => return false,
+    }
+    self
+      .started_at
+      .map(|t| Utc::now() - t < PENALTY_POLICY.early_leave)
+      .unwrap_or(true)
+  }
+
+  /// Records an offense for each player and notifies the penalized ones.
+  /// Errors are logged, penalties never fail the caller
+  pub(crate) async fn penalize(&self, player_ids: Vec<i32>, kind: OffenseKind) {
+    let game_id = self.game_id;
+    for player_id in player_ids {
+      let res = self
+        .db
+        .exec(crate::profiling::db(move |conn| {
+          crate::player::penalty::record_offense(conn, player_id, game_id, kind)
+        }))
+        .await;
+      let penalty = match res {
+        Ok(Some(penalty)) => penalty,
+        Ok(None) => continue,
+        Err(err) => {
+          tracing::error!(game_id, player_id, "record offense: {}", err);
+          continue;
+        }
+      };
+
+      tracing::info!(
+        game_id,
+        player_id,
+        offenses = penalty.offenses,
+        "player penalized: {:?}",
+        kind
+      );
+
+      let frame = match (PacketPlayerPenaltyUpdate {
+        penalty: Some(penalty.into()),
+      })
+      .encode_as_frame()
+      {
+        Ok(frame) => frame,
+        Err(err) => {
+          tracing::error!(game_id, player_id, "encode penalty update: {}", err);
+          continue;
+        }
+      };
+      if let Err(err) = self.player_reg.send(player_id, frame).await {
+        tracing::error!(game_id, player_id, "send penalty update: {}", err);
+      }
+    }
+  }
+}
@@ -0,0 +1,177 @@
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::state::GameRegistry;
+use flo_state::{async_trait, Context, Handler, Message};
+use once_cell::sync::Lazy;
+use thiserror::Error;
+
+/// Limits applied when games are created or joined.
+/// `None` means unlimited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameQuota {
+  /// Max games hosted by a single player that are still in the lobby
+  pub max_open_lobbies_per_player: Option<usize>,
+  /// Max games a single player can be a member of
+  pub max_games_per_player: Option<usize>,
+  /// Max concurrent games created by a single API client (ladder)
+  pub max_games_per_api_client: Option<usize>,
+  /// Max concurrent games hosted on a single node
+  pub max_games_per_node: Option<usize>,
+}
+
+pub static GAME_QUOTA: Lazy<GameQuota> = Lazy::new(GameQuota::from_env);
+
+impl GameQuota {
+  pub fn from_env() -> Self {
+    fn get(name: &str) -> Option<usize> {
+      std::env::var(name).ok().and_then(|v| v.parse().ok())
+    }
+    GameQuota {
+      max_open_lobbies_per_player: get("FLO_QUOTA_MAX_OPEN_LOBBIES_PER_PLAYER"),
+      max_games_per_player: get("FLO_QUOTA_MAX_GAMES_PER_PLAYER"),
+      max_games_per_api_client: get("FLO_QUOTA_MAX_GAMES_PER_API_CLIENT"),
+      max_games_per_node: get("FLO_QUOTA_MAX_GAMES_PER_NODE"),
+    }
+  }
+
+  pub fn check_open_lobbies(&self, count: usize) -> Result<(), QuotaExceeded> {
+    check(self.max_open_lobbies_per_player, count).map_err(QuotaExceeded::OpenLobbiesPerPlayer)
+  }
+
+  pub fn check_player_games(&self, count: usize) -> Result<(), QuotaExceeded> {
+    check(self.max_games_per_player, count).map_err(QuotaExceeded::GamesPerPlayer)
+  }
+
+  pub fn check_api_client_games(&self, count: usize) -> Result<(), QuotaExceeded> {
+    check(self.max_games_per_api_client, count).map_err(QuotaExceeded::GamesPerApiClient)
+  }
+
+  pub fn check_node_games(&self, count: usize) -> Result<(), QuotaExceeded> {
+    check(self.max_games_per_node, count).map_err(QuotaExceeded::GamesPerNode)
+  }
+}
+
+// `count` is the current usage, adding one more must not exceed the limit
+fn check(limit: Option<usize>, count: usize) -> Result<(), usize> {
+  match limit {
+    Some(limit) if count >= limit => Err(limit),
+    _ => Ok(()),
+  }
+}
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum QuotaExceeded {
+  #[error("You can host at most {0} open lobbies")]
+  OpenLobbiesPerPlayer(usize),
+  #[error("You can be in at most {0} games")]
+  GamesPerPlayer(usize),
+  #[error("API client concurrent game limit reached: {0}")]
+  GamesPerApiClient(usize),
+  #[error("Node concurrent game limit reached: {0}")]
+  GamesPerNode(usize),
+}
+
+/// Checked in the create transaction, games leave the lobby without the registry knowing
+pub(crate) fn check_hosted_lobbies(conn: &DbConn, player_id: i32) -> Result<()> {
+  if GAME_QUOTA.max_open_lobbies_per_player.is_some() {
+    GAME_QUOTA.check_open_lobbies(crate::game::db::count_hosted_lobbies(conn, player_id)?)?;
+  }
+  Ok(())
+}
+
+/// Checked in the join transaction, so concurrent joins of a player can't exceed the limit
+pub(crate) fn check_player_join(conn: &DbConn, game_id: i32, player_id: i32) -> Result<()> {
+  if GAME_QUOTA.max_games_per_player.is_some() {
+    GAME_QUOTA.check_player_games(crate::game::db::count_player_active_games_for_update(
+      conn, player_id, game_id,
+    )?)?;
+  }
+  Ok(())
+}
+
+impl GameRegistry {
+  pub(crate) fn count_player_games(&self, player_id: i32) -> usize {
+    self
+      .player_games_map
+      .get(&player_id)
+      .map(|v| v.len())
+      .unwrap_or_default()
+  }
+
+  pub(crate) fn count_api_client_games(&self, api_client_id: i32) -> usize {
+    self
+      .game_api_client_map
+      .values()
+      .filter(|id| **id == api_client_id)
+      .count()
+  }
+
+  pub(crate) fn count_node_games(&self, node_id: i32, except_game_id: Option<i32>) -> usize {
+    self
+      .game_node_map
+      .iter()
+      .filter(|(game_id, id)| **id == node_id && Some(**game_id) != except_game_id)
+      .count()
+  }
+
+  pub(crate) fn check_create_quota(&self, player_id: i32) -> Result<()> {
+    GAME_QUOTA.check_player_games(self.count_player_games(player_id))?;
+    Ok(())
+  }
+
+  /// `games` is the number of games to be created, at least one
+  pub(crate) fn check_create_as_bot_quota(
+    &self,
+    api_client_id: i32,
+    node_id: i32,
+    games: usize,
+  ) -> Result<()> {
+    let added = games.saturating_sub(1);
+    GAME_QUOTA.check_api_client_games(self.count_api_client_games(api_client_id) + added)?;
+    GAME_QUOTA.check_node_games(self.count_node_games(node_id, None) + added)?;
+    Ok(())
+  }
+}
+
+pub struct CheckNodeQuota {
+  pub game_id: i32,
+  pub node_id: Option<i32>,
+}
+
+impl Message for CheckNodeQuota {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<CheckNodeQuota> for GameRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    CheckNodeQuota { game_id, node_id }: CheckNodeQuota,
+  ) -> Result<()> {
+    if let Some(node_id) = node_id {
+      GAME_QUOTA.check_node_games(self.count_node_games(node_id, Some(game_id)))?;
+    }
+    Ok(())
+  }
+}
+
+#[test]
+fn test_game_quota() {
+  let quota = GameQuota {
+    max_open_lobbies_per_player: Some(1),
+    max_games_per_node: Some(0),
+    ..Default::default()
+  };
+  assert_eq!(quota.check_open_lobbies(0), Ok(()));
+  assert_eq!(
+    quota.check_open_lobbies(1),
+    Err(QuotaExceeded::OpenLobbiesPerPlayer(1))
+  );
+  assert_eq!(quota.check_player_games(1000), Ok(()));
+  assert_eq!(quota.check_api_client_games(1000), Ok(()));
+  assert_eq!(
+    quota.check_node_games(0),
+    Err(QuotaExceeded::GamesPerNode(0))
+  );
+}
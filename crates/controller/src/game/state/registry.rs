@@ -1,4 +1,5 @@
 use crate::error::*;
+use crate::game::event::LobbyEventKind;
 use crate::game::state::{GameActor, GameRegistry};
 use crate::game::GameStatus;
 use flo_state::{async_trait, Context, Handler, Message, Owner};
@@ -11,6 +12,8 @@ pub struct Register {
   pub host_player: i32,
   pub players: Vec<i32>,
   pub node_id: Option<i32>,
+  pub api_client_id: Option<i32>,
+  pub target_version: Option<String>,
 }
 
 impl Message for Register {
@@ -33,11 +36,26 @@ impl GameRegistry {
       host_player,
       players,
       node_id,
+      api_client_id,
+      target_version,
     }: Register,
   ) {
     for player in &players {
       self.add_game_player(id, *player);
     }
+    if let Some(node_id) = node_id {
+      self.game_node_map.insert(id, node_id);
+    }
+    if let Some(api_client_id) = api_client_id {
+      self.game_api_client_map.insert(id, api_client_id);
+    }
+    self.events.register_game(id, api_client_id);
+    self.events.send(
+      id,
+      LobbyEventKind::Created {
+        host_player_id: host_player,
+      },
+    );
     self.map.insert(
       id,
       Owner::new(GameActor {
@@ -45,6 +63,7 @@ impl GameRegistry {
         db: self.db.clone(),
         player_reg: self.players.clone(),
         nodes: self.nodes.clone(),
+        notifications: self.notifications.clone(),
         status,
         host_player,
         players,
@@ -52,6 +71,19 @@ impl GameRegistry {
         start_state: None,
         player_tokens: Default::default(),
         player_client_status_map: Default::default(),
+        start_interrupted_players: Default::default(),
+        player_command_time_map: Default::default(),
+        surrender_votes: Default::default(),
+        player_teams: Default::default(),
+        map_vote: None,
+        lobby_chat: None,
+        events: self.events.clone(),
+        maintenance: self.maintenance.clone(),
+        node_versions: self.node_versions.clone(),
+        target_version,
+        revision: 0,
+        api_client_id,
+        started_at: None,
       }),
     );
   }
@@ -72,6 +104,8 @@ impl Handler<Remove> for GameRegistry {
     if let Some(owner) = self.map.remove(&id) {
       self.game_players_map.remove(&id);
       self.game_node_map.remove(&id);
+      self.game_api_client_map.remove(&id);
+      self.events.remove_game(id);
 
       let addr = ctx.addr();
       ctx.spawn(async move {
@@ -0,0 +1,33 @@
+use crate::error::*;
+use crate::game::state::GameActor;
+use flo_net::proto;
+use flo_state::{async_trait, Context, Handler, Message};
+
+/// Returns the packets a reconnecting player needs to resync a lobby
+/// restored after controller restart
+pub struct GetRestoreFrames {
+  pub player_id: i32,
+}
+
+impl Message for GetRestoreFrames {
+  type Result = Result<Option<proto::flo_connect::PacketGameStartReject>>;
+}
+
+#[async_trait]
+impl Handler<GetRestoreFrames> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GetRestoreFrames { player_id }: GetRestoreFrames,
+  ) -> <GetRestoreFrames as Message>::Result {
+    if !self.start_interrupted_players.remove(&player_id) {
+      return Ok(None);
+    }
+    // the client is still waiting for the start negotiation
+    Ok(Some(proto::flo_connect::PacketGameStartReject {
+      game_id: self.game_id,
+      message: "Game start was interrupted by a server restart, please start again.".to_string(),
+      ..Default::default()
+    }))
+  }
+}
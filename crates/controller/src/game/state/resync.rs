@@ -0,0 +1,80 @@
+use crate::error::*;
+use crate::game::state::GameActor;
+use crate::game::Game;
+use flo_net::packet::{FloPacket, Frame};
+use flo_net::proto;
+use flo_state::{async_trait, Context, Handler, Message};
+use s2_grpc_utils::S2ProtoPack;
+
+/// Sends the full game info to a player that missed a lobby update revision.
+///
+/// Handled by the game actor so the snapshot can't interleave with another lobby update,
+/// updates with later revisions are queued to the player after it.
+pub struct ResyncGame {
+  pub player_id: i32,
+}
+
+impl Message for ResyncGame {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<ResyncGame> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    ResyncGame { player_id }: ResyncGame,
+  ) -> Result<()> {
+    let game_id = self.game_id;
+
+    if !self.players.contains(&player_id) {
+      return Err(Error::PlayerNotInGame);
+    }
+
+    let mut game = self
+      .db
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::db::get_full(conn, game_id)
+      }))
+      .await?;
+    game.revision = self.revision;
+
+    self
+      .player_reg
+      .send(player_id, game_info_frame(game)?)
+      .await?;
+
+    Ok(())
+  }
+}
+
+/// Full game info sent to lobby players, player names are masked if the game requires it
+pub(crate) fn game_info_frame(mut game: Game) -> Result<Frame> {
+  if game.mask_player_names {
+    for (idx, slot) in game.slots.iter_mut().enumerate() {
+      slot.player.as_mut().map(|v| {
+        v.name = format!("Player {}", idx + 1);
+      });
+    }
+  }
+
+  proto::flo_connect::PacketGameInfo {
+    game: Some(game.pack()?),
+  }
+  .encode_as_frame()
+  .map_err(Into::into)
+}
+
+/// Current lobby update revision, read before loading a game snapshot outside of the actor
+pub struct GetGameRevision;
+
+impl Message for GetGameRevision {
+  type Result = Result<u64>;
+}
+
+#[async_trait]
+impl Handler<GetGameRevision> for GameActor {
+  async fn handle(&mut self, _: &mut Context<Self>, _: GetGameRevision) -> Result<u64> {
+    Ok(self.revision)
+  }
+}
@@ -0,0 +1,55 @@
+use crate::error::*;
+use crate::game::state::GameActor;
+use crate::game::{db, GameSave, GameStatus};
+use chrono::Utc;
+use flo_net::proto::flo_node::PacketNodeGameSaved;
+use flo_state::{async_trait, Context, Handler, Message};
+
+/// A save reported by the node, recorded so the API client can resume the game later
+#[derive(Debug)]
+pub struct GameSaved(pub PacketNodeGameSaved);
+
+impl Message for GameSaved {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<GameSaved> for GameActor {
+  async fn handle(&mut self, _: &mut Context<Self>, GameSaved(packet): GameSaved) -> Result<()> {
+    let game_id = self.game_id;
+
+    if self.status != GameStatus::Running {
+      tracing::warn!(game_id, "game saved: game is not running");
+      return Ok(());
+    }
+
+    let players = &self.players;
+    if packet
+      .slots
+      .iter()
+      .any(|slot| !players.contains(&slot.player_id))
+    {
+      return Err(Error::GameSaveInvalid);
+    }
+
+    tracing::info!(game_id, "game saved: {}", packet.name);
+
+    let save = GameSave {
+      name: packet.name,
+      slots: packet
+        .slots
+        .into_iter()
+        .map(|slot| (slot.player_id, slot.slot_index as usize))
+        .collect(),
+      saved_at: Utc::now(),
+    };
+    self
+      .db
+      .exec(crate::profiling::db(move |conn| {
+        db::add_save(conn, game_id, save)
+      }))
+      .await?;
+
+    Ok(())
+  }
+}
@@ -1,5 +1,6 @@
 use crate::error::*;
 use crate::game::db::UpdateSlotSettings;
+use crate::game::event::LobbyEventKind;
 use crate::game::state::GameActor;
 use crate::game::{Slot, SlotSettings};
 use diesel::prelude::*;
@@ -29,6 +30,7 @@ impl Handler<UpdateSlot> for GameActor {
       settings,
     }: UpdateSlot,
   ) -> Result<Vec<Slot>> {
+    self.require_lobby()?;
     let game_id = self.game_id;
 
     let UpdateSlotSettings {
@@ -36,17 +38,80 @@ impl Handler<UpdateSlot> for GameActor {
       updated_indexes,
     } = self
       .db
-      .exec(move |conn| {
+      .exec(crate::profiling::db(move |conn| {
         conn.transaction(|| {
+          if crate::game::db::is_layout_locked(conn, game_id)? {
+            return Err(Error::GameSlotLayoutLocked);
+          }
           let info = crate::game::db::get_slot_owner_info(conn, game_id, slot_index)?;
           if !info.is_slot_owner(player_id) {
             return Err(Error::GameSlotUpdateDenied);
           }
           crate::game::db::update_slot_settings(conn, game_id, slot_index, settings)
         })
-      })
+      }))
+      .await?;
+
+    self.broadcast_slot_update(&slots, updated_indexes).await?;
+
+    Ok(slots)
+  }
+}
+
+/// Slot update requested by the API client that created the game
+pub struct UpdateSlotAsBot {
+  pub api_client_id: i32,
+  pub slot_index: i32,
+  pub settings: SlotSettings,
+}
+
+impl Message for UpdateSlotAsBot {
+  type Result = Result<Vec<Slot>>;
+}
+
+#[async_trait]
+impl Handler<UpdateSlotAsBot> for GameActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    UpdateSlotAsBot {
+      api_client_id,
+      slot_index,
+      settings,
+    }: UpdateSlotAsBot,
+  ) -> Result<Vec<Slot>> {
+    self.require_lobby()?;
+    let game_id = self.game_id;
+
+    let UpdateSlotSettings {
+      slots,
+      updated_indexes,
+    } = self
+      .db
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::db::update_slot_settings_as_bot(
+          conn,
+          api_client_id,
+          game_id,
+          slot_index,
+          settings,
+        )
+      }))
       .await?;
 
+    self.broadcast_slot_update(&slots, updated_indexes).await?;
+
+    Ok(slots)
+  }
+}
+
+impl GameActor {
+  async fn broadcast_slot_update(
+    &mut self,
+    slots: &[Slot],
+    updated_indexes: Vec<i32>,
+  ) -> Result<()> {
+    let game_id = self.game_id;
     let mut frames_slot_update = Vec::with_capacity(updated_indexes.len());
 
     for index in updated_indexes {
@@ -57,9 +122,18 @@ impl Handler<UpdateSlot> for GameActor {
         slot_index: index,
         slot_settings: settings.into(),
         player: slot.player.clone().map(|p| p.pack()).transpose()?,
+        revision: self.next_revision(),
       }
       .encode_as_frame()?;
       frames_slot_update.push(frame);
+      self.events.send(
+        game_id,
+        LobbyEventKind::SlotUpdated {
+          slot_index: index,
+          player_id: slot.player.as_ref().map(|p| p.id),
+          settings: slot.settings.clone(),
+        },
+      );
     }
 
     let players = slots
@@ -71,6 +145,6 @@ impl Handler<UpdateSlot> for GameActor {
       .broadcast(players, frames_slot_update)
       .await?;
 
-    Ok(slots)
+    Ok(())
   }
 }
@@ -1,7 +1,11 @@
 use crate::error::*;
+use crate::game::event::LobbyEventKind;
+use crate::game::lifecycle::{self, GameStage};
 use crate::game::state::GameActor;
+use crate::game::summary::{LobbyEndReason, LobbySummary};
 use crate::game::{GameStatus, SlotClientStatus};
-use crate::node::messages::NodeCreateGame;
+use crate::node::messages::{NodeCommitGame, NodeCreateGame, StartOwner};
+use crate::player::penalty::OffenseKind;
 use crate::player::state::sender::PlayerFrames;
 use crate::state::ActorMapExt;
 use flo_net::packet::FloPacket;
@@ -9,7 +13,7 @@ use flo_net::proto;
 use flo_state::{async_trait, Actor, Addr, Context, Handler, Message};
 use std::collections::HashMap;
 use std::time::Duration;
-use tokio::sync::oneshot;
+use tokio::sync::{mpsc, oneshot};
 
 use tokio::time::sleep;
 
@@ -44,10 +48,25 @@ impl Handler<StartGameCheck> for GameActor {
     if self.start_state.is_some() {
       return Err(Error::GameStarted);
     }
+    self.check_transition(GameStage::Starting)?;
+
+    if let Err(err) = self.maintenance.check() {
+      let pkt = proto::flo_connect::PacketGameStartReject {
+        game_id,
+        message: err.to_string(),
+        ..Default::default()
+      };
+      self
+        .player_reg
+        .send(player_id, pkt.encode_as_frame()?)
+        .await?;
+      return Ok(());
+    }
 
     self.start_state = StartGameState::new(game_id, ctx.addr(), players, None)
       .start()
       .into();
+    self.persist_start_requested(true).await;
 
     let frame = proto::flo_connect::PacketGameStarting { game_id }.encode_as_frame()?;
     self
@@ -72,6 +91,13 @@ impl GameActor {
 
     tracing::debug!(game_id, "start game check proceed.");
 
+    // the start state was taken by the caller
+    lifecycle::transition(
+      game_id,
+      GameStage::new(self.status, true),
+      GameStage::Created,
+    )?;
+
     let mut pass = true;
     let agreed_version: Option<String>;
     {
@@ -96,6 +122,7 @@ impl GameActor {
         message: "Unable to start the game because the game and map version check failed."
           .to_string(),
         player_client_info_map: map.clone(),
+        ..Default::default()
       };
       let frame = pkt.encode_as_frame()?;
       self
@@ -108,31 +135,99 @@ impl GameActor {
         "start game failed: version check failed"
       );
 
+      let summary = LobbySummary::new(game_id, LobbyEndReason::VersionCheckFailed, &pkt.message)
+        .with_start_check(&self.players, self.host_player, &map);
+      self.send_lobby_summary(self.players.clone(), summary).await;
+
       return Ok(Err(pkt));
     }
 
     let (game, ban_list_map) = self
       .db
-      .exec(move |conn| {
+      .exec(crate::profiling::db(move |conn| {
         let game = crate::game::db::get_full(conn, game_id)?;
         let players = game.get_player_ids();
         Ok::<_, Error>((game, crate::player::db::get_ban_list_map(conn, &players)?))
-      })
+      }))
       .await?;
 
+    let mut game_players = game.get_player_ids();
+    game_players.sort_unstable();
+    let mut checked_players: Vec<i32> = map.keys().cloned().collect();
+    checked_players.sort_unstable();
+    if game_players != checked_players {
+      tracing::error!(
+        game_id,
+        "start game failed: players changed during start check"
+      );
+      return Ok(Err(proto::flo_connect::PacketGameStartReject {
+        game_id,
+        message: "Unable to start the game because the players changed.".to_string(),
+        player_client_info_map: map,
+        ..Default::default()
+      }));
+    }
+
     let node_id = if let Some(id) = game.node.as_ref().map(|node| node.id) {
       id
     } else {
       return Err(Error::GameNodeNotSelected);
     };
 
+    self.player_teams = game
+      .slots
+      .iter()
+      .filter_map(|slot| Some((slot.player.as_ref()?.id, slot.settings.team)))
+      .collect();
+
+    let owner = match self.api_client_id {
+      Some(api_client_id) => StartOwner::ApiClient(api_client_id),
+      None => StartOwner::Player(self.host_player),
+    };
+    let (queue_tx, mut queue_rx) = mpsc::unbounded_channel();
+    tokio::spawn({
+      let player_reg = self.player_reg.clone();
+      let host_player = self.host_player;
+      async move {
+        while let Some(position) = queue_rx.recv().await {
+          let pkt = proto::flo_connect::PacketGameStartQueued {
+            game_id,
+            position: position as u32,
+          };
+          if let Ok(frame) = pkt.encode_as_frame() {
+            player_reg.send(host_player, frame).await.ok();
+          }
+        }
+      }
+    });
+
     let created = self
       .nodes
-      .send_to(node_id, NodeCreateGame { game, ban_list_map })
+      .send_to(
+        node_id,
+        NodeCreateGame {
+          game,
+          ban_list_map,
+          owner,
+          queue_tx: Some(queue_tx),
+        },
+      )
       .await?
       .await
       .or_cancelled();
 
+    // the node rolls back the reserved game if the commit doesn't arrive in time
+    let created = match created {
+      Ok(created) if created.reserved => self
+        .nodes
+        .send_to(node_id, NodeCommitGame { game_id })
+        .await?
+        .await
+        .or_cancelled()
+        .map(|_| created),
+      res => res,
+    };
+
     let created = match created {
       Ok(created) => created,
       // failed, reply host player
@@ -143,6 +238,27 @@ impl GameActor {
             message: format!("Create game timeout."),
             ..Default::default()
           },
+          Error::NodeStartQueueFull => proto::flo_connect::PacketGameStartReject {
+            game_id,
+            message: format!("Server busy, please try again later."),
+            node_busy: Some(proto::flo_connect::GameStartRejectNodeBusy {
+              node_id,
+              retry_after_secs: 0,
+            }),
+            ..Default::default()
+          },
+          Error::NodeBusy { estimated_wait, .. } => proto::flo_connect::PacketGameStartReject {
+            game_id,
+            message: format!(
+              "Server busy, please try again in {} seconds or select another server.",
+              estimated_wait.as_secs()
+            ),
+            node_busy: Some(proto::flo_connect::GameStartRejectNodeBusy {
+              node_id,
+              retry_after_secs: estimated_wait.as_secs() as u32,
+            }),
+            ..Default::default()
+          },
           Error::GameCreateReject(reason) => {
             use proto::flo_node::ControllerCreateGameRejectReason;
             proto::flo_connect::PacketGameStartReject {
@@ -158,6 +274,12 @@ impl GameActor {
                 ControllerCreateGameRejectReason::Maintenance => {
                   format!("Create game request rejected: Server Maintenance.")
                 }
+                ControllerCreateGameRejectReason::ReservationExpired => {
+                  format!("Create game timeout.")
+                }
+                ControllerCreateGameRejectReason::NodeBusy => {
+                  format!("Server busy, please try again later.")
+                }
               },
               ..Default::default()
             }
@@ -174,6 +296,10 @@ impl GameActor {
 
         tracing::error!(game_id = self.game_id, "start game failed: {}", pkt.message);
 
+        let summary = LobbySummary::new(game_id, LobbyEndReason::StartRejected, &pkt.message)
+          .with_start_check(&self.players, self.host_player, &map);
+        self.send_lobby_summary(self.players.clone(), summary).await;
+
         return Ok(Err(pkt));
       }
     };
@@ -218,9 +344,17 @@ impl GameActor {
 
     self
       .db
-      .exec(move |conn| crate::game::db::update_created(conn, game_id, agreed_version, token_map))
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::db::update_created(conn, game_id, agreed_version, token_map)
+      }))
       .await?;
     self.status = GameStatus::Created;
+    self.events.send(
+      game_id,
+      LobbyEventKind::StatusChanged {
+        status: self.status,
+      },
+    );
 
     Ok(Ok(()))
   }
@@ -248,6 +382,20 @@ impl Handler<StartGameCheckTimeout> for GameActor {
 }
 
 impl GameActor {
+  // Lets a restarted controller tell the players the start negotiation was interrupted
+  async fn persist_start_requested(&self, requested: bool) {
+    let game_id = self.game_id;
+    if let Err(err) = self
+      .db
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::db::update_start_requested(conn, game_id, requested)
+      }))
+      .await
+    {
+      tracing::error!(game_id, "update start requested: {}", err);
+    }
+  }
+
   async fn send_game_start_reject_timeout(
     &mut self,
     StartGameCheckTimeout { map }: StartGameCheckTimeout,
@@ -260,11 +408,27 @@ impl GameActor {
       return Ok(());
     };
     let start_state = start_state.shutdown().await?;
+    self.persist_start_requested(false).await;
+
+    let timed_out_players: Vec<i32> = self
+      .players
+      .iter()
+      .filter(|player_id| !map.contains_key(*player_id))
+      .cloned()
+      .collect();
+
+    let summary = LobbySummary::new(
+      game_id,
+      LobbyEndReason::StartAckTimeout,
+      "Some of the players didn't response in time.",
+    )
+    .with_start_check(&self.players, self.host_player, &map);
 
     let pkt = proto::flo_connect::PacketGameStartReject {
       game_id,
-      message: "Some of the players didn't response in time.".to_string(),
+      message: summary.message.clone(),
       player_client_info_map: map,
+      ..Default::default()
     };
     let frame = pkt.encode_as_frame()?;
 
@@ -277,6 +441,11 @@ impl GameActor {
       .broadcast(self.players.clone(), frame)
       .await?;
 
+    self.send_lobby_summary(self.players.clone(), summary).await;
+    self
+      .penalize(timed_out_players, OffenseKind::StartAckTimeout)
+      .await;
+
     Ok(())
   }
 }
@@ -479,6 +648,7 @@ impl Handler<StartGamePlayerAck> for GameActor {
         .take()
         .ok_or_else(|| Error::GameNotStarting)?;
       let start_state = start_state.shutdown().await?;
+      self.persist_start_requested(false).await;
 
       match self.start_game_proceed(proceed).await {
         Ok(Ok(_)) => {
@@ -503,6 +673,9 @@ impl Handler<StartGamePlayerAck> for GameActor {
             message: format!("Internal error: {}", err),
             ..Default::default()
           };
+          let summary =
+            LobbySummary::new(self.game_id, LobbyEndReason::StartRejected, &pkt.message);
+          self.send_lobby_summary(self.players.clone(), summary).await;
           self
             .player_reg
             .send(self.host_player, pkt.encode_as_frame()?)
@@ -564,6 +737,8 @@ impl Handler<StartGameCheckAsBot> for GameActor {
   ) -> <StartGameCheckAsBot as Message>::Result {
     let game_id = self.game_id;
 
+    self.maintenance.check()?;
+
     if self.selected_node_id.is_none() {
       return Err(Error::GameNodeNotSelected);
     }
@@ -572,10 +747,12 @@ impl Handler<StartGameCheckAsBot> for GameActor {
     if self.start_state.is_some() {
       return Err(Error::GameStarted);
     }
+    self.check_transition(GameStage::Starting)?;
 
     self.start_state = StartGameState::new(game_id, ctx.addr(), players, Some(tx))
       .start()
       .into();
+    self.persist_start_requested(true).await;
 
     let frame = proto::flo_connect::PacketGameStarting { game_id }.encode_as_frame()?;
     self
@@ -1,7 +1,11 @@
 use crate::error::*;
+use crate::game::event::LobbyEventKind;
+use crate::game::lifecycle::GameStage;
 use crate::game::state::GameActor;
 use crate::game::{db, GameStatus, NodeGameStatus, SlotClientStatus};
+use crate::player::penalty::OffenseKind;
 use crate::player::state::sender::PlayerFrames;
+use chrono::Utc;
 use flo_net::packet::FloPacket;
 use flo_net::proto;
 use flo_state::{async_trait, Context, Handler, Message};
@@ -38,15 +42,21 @@ impl Handler<GameSlotClientStatusUpdate> for GameActor {
     );
 
     let status = message.status;
+    let left_early = status == SlotClientStatus::Left
+      && self.player_client_status_map.get(&player_id) != Some(&SlotClientStatus::Left)
+      && self.is_early_leave();
 
     self
       .db
-      .exec(move |conn| db::update_slot_client_status(conn, game_id, player_id, status))
+      .exec(crate::profiling::db(move |conn| {
+        db::update_slot_client_status(conn, game_id, player_id, status)
+      }))
       .await?;
 
     let mut pkt = proto::flo_connect::PacketGameSlotClientStatusUpdate {
       player_id,
       game_id,
+      revision: self.next_revision(),
       ..Default::default()
     };
     pkt.set_status(status.into_proto_enum());
@@ -58,6 +68,12 @@ impl Handler<GameSlotClientStatusUpdate> for GameActor {
 
     self.player_client_status_map.insert(player_id, status);
 
+    if left_early {
+      self
+        .penalize(vec![player_id], OffenseKind::EarlyLeave)
+        .await;
+    }
+
     Ok(())
   }
 }
@@ -80,25 +96,51 @@ impl Handler<GameStatusUpdate> for GameActor {
     _ctx: &mut Context<Self>,
     message: GameStatusUpdate,
   ) -> Result<GameStatus> {
+    let status = GameStatus::from(message.status);
+    self.check_transition(GameStage::new(status, false))?;
+
     self
       .db
-      .exec({
+      .exec(crate::profiling::db({
         let message = message.clone();
         move |conn| -> Result<_> {
           db::update_status(conn, &message)?;
           Ok(())
         }
-      })
+      }))
       .await?;
 
     let frame_game_status = message.to_packet().encode_as_frame()?;
-    self.status = GameStatus::from(message.status);
+    if status != self.status {
+      self
+        .events
+        .send(self.game_id, LobbyEventKind::StatusChanged { status });
+    }
+    self.status = status;
+    let started = status == GameStatus::Running && self.started_at.is_none();
+    if started {
+      self.started_at.replace(Utc::now());
+    }
 
     let ended = match self.status {
       GameStatus::Ended | GameStatus::Terminated => true,
       _ => false,
     };
 
+    let early_leavers: Vec<i32> = if !ended && self.is_early_leave() {
+      message
+        .updated_player_game_client_status_map
+        .iter()
+        .filter(|(player_id, status)| {
+          **status == SlotClientStatus::Left
+            && self.player_client_status_map.get(*player_id) != Some(&SlotClientStatus::Left)
+        })
+        .map(|(player_id, _)| *player_id)
+        .collect()
+    } else {
+      vec![]
+    };
+
     let frame_iter = self
       .players
       .iter()
@@ -118,6 +160,27 @@ impl Handler<GameStatusUpdate> for GameActor {
         .await?;
     }
 
+    if !early_leavers.is_empty() {
+      self.penalize(early_leavers, OffenseKind::EarlyLeave).await;
+    }
+
+    if started {
+      let game_id = self.game_id;
+      match self
+        .db
+        .exec(crate::profiling::db(move |conn| {
+          crate::clan::db::link_game(conn, game_id)
+        }))
+        .await
+      {
+        Ok(Some((clan_id, opponent_clan_id))) => {
+          tracing::info!(game_id, clan_id, opponent_clan_id, "clan match");
+        }
+        Ok(None) => {}
+        Err(err) => tracing::error!(game_id, "link clan match: {}", err),
+      }
+    }
+
     Ok(self.status)
   }
 }
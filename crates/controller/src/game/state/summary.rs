@@ -0,0 +1,37 @@
+use crate::game::state::GameActor;
+use crate::game::summary::LobbySummary;
+use flo_net::packet::FloPacket;
+
+impl GameActor {
+  /// Sends the summary to `player_ids` and persists it.
+  /// Errors are logged, summaries never fail the caller
+  pub(crate) async fn send_lobby_summary(&self, player_ids: Vec<i32>, summary: LobbySummary) {
+    let game_id = self.game_id;
+    tracing::info!(
+      game_id,
+      failed_players = ?summary.failed_player_ids(),
+      "lobby summary: {:?}: {}",
+      summary.reason,
+      summary.message
+    );
+
+    match summary.to_packet().encode_as_frame() {
+      Ok(frame) => {
+        if let Err(err) = self.player_reg.broadcast(player_ids, frame).await {
+          tracing::error!(game_id, "send lobby summary: {}", err);
+        }
+      }
+      Err(err) => tracing::error!(game_id, "encode lobby summary: {}", err),
+    }
+
+    if let Err(err) = self
+      .db
+      .exec(crate::profiling::db(move |conn| {
+        crate::game::summary::insert(conn, &summary)
+      }))
+      .await
+    {
+      tracing::error!(game_id, "insert lobby summary: {}", err);
+    }
+  }
+}
@@ -0,0 +1,118 @@
+use crate::error::*;
+use crate::game::state::GameActor;
+use crate::game::SlotClientStatus;
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+const OBSERVER_TEAM: i32 = 24;
+
+/// Teammates have to vote within this window after the vote was initiated
+pub const SURRENDER_VOTE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub struct SurrenderVote {
+  started_at: Instant,
+  votes: BTreeSet<i32>,
+}
+
+impl SurrenderVote {
+  fn new() -> Self {
+    Self {
+      started_at: Instant::now(),
+      votes: BTreeSet::new(),
+    }
+  }
+
+  fn expired(&self) -> bool {
+    self.started_at.elapsed() > SURRENDER_VOTE_WINDOW
+  }
+}
+
+#[derive(Debug)]
+pub enum SurrenderVoteResult {
+  Pending { votes: usize, required: usize },
+  Passed { player_ids: Vec<i32> },
+}
+
+impl GameActor {
+  /// Team of `player_id`, surrender votes are only allowed in team games
+  pub(crate) fn surrender_team(&self, player_id: i32) -> Result<i32> {
+    let team = self
+      .player_teams
+      .get(&player_id)
+      .cloned()
+      .filter(|team| *team != OBSERVER_TEAM)
+      .ok_or(Error::PlayerSlotNotFound)?;
+    if !is_team_game(&self.player_teams) {
+      return Err(Error::SurrenderNotTeamGame);
+    }
+    Ok(team)
+  }
+
+  /// Records a surrender vote of `player_id`.
+  /// The vote passes once every teammate still in the game has voted.
+  pub(crate) fn surrender_vote(&mut self, player_id: i32) -> Result<SurrenderVoteResult> {
+    let team = self.surrender_team(player_id)?;
+
+    let mut teammates: Vec<i32> = self
+      .player_teams
+      .iter()
+      .filter(|(_, t)| **t == team)
+      .map(|(id, _)| *id)
+      .filter(|id| {
+        self
+          .player_client_status_map
+          .get(id)
+          .map(|status| *status != SlotClientStatus::Left)
+          .unwrap_or(true)
+      })
+      .collect();
+    teammates.sort_unstable();
+
+    let vote = self
+      .surrender_votes
+      .entry(team)
+      .or_insert_with(SurrenderVote::new);
+    if vote.expired() {
+      *vote = SurrenderVote::new();
+    }
+    vote.votes.insert(player_id);
+
+    let votes = teammates
+      .iter()
+      .filter(|id| vote.votes.contains(*id))
+      .count();
+    if votes < teammates.len() {
+      return Ok(SurrenderVoteResult::Pending {
+        votes,
+        required: teammates.len(),
+      });
+    }
+
+    self.surrender_votes.remove(&team);
+    Ok(SurrenderVoteResult::Passed {
+      player_ids: teammates,
+    })
+  }
+}
+
+/// At least two teams, one of them with more than one player
+fn is_team_game(player_teams: &HashMap<i32, i32>) -> bool {
+  let mut team_sizes = HashMap::<i32, usize>::new();
+  for team in player_teams.values().filter(|team| **team != OBSERVER_TEAM) {
+    *team_sizes.entry(*team).or_default() += 1;
+  }
+  team_sizes.len() > 1 && team_sizes.values().any(|size| *size > 1)
+}
+
+#[test]
+fn test_is_team_game() {
+  let teams = |pairs: &[(i32, i32)]| pairs.iter().cloned().collect::<HashMap<_, _>>();
+  assert!(is_team_game(&teams(&[(1, 0), (2, 0), (3, 1), (4, 1)])));
+  assert!(is_team_game(&teams(&[(1, 0), (2, 0), (3, 1)])));
+  // 1v1 with an observer
+  assert!(!is_team_game(&teams(&[(1, 0), (2, 1), (3, OBSERVER_TEAM)])));
+  // FFA
+  assert!(!is_team_game(&teams(&[(1, 0), (2, 1), (3, 2), (4, 3)])));
+  assert!(!is_team_game(&teams(&[(1, 0), (2, 0)])));
+}
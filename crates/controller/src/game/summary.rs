@@ -0,0 +1,235 @@
+//! Summaries of lobbies that closed without starting the game and of failed start attempts.
+//!
+//! A summary is sent to the lobby players and persisted. It names the players that didn't ack
+//! the start check or reported a different game or map version than the host, so players can
+//! tell who broke the start and support has data to look into flaky starts.
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::lobby_summary;
+use bs_diesel_utils::BSDieselEnum;
+use diesel::prelude::*;
+use flo_net::proto::flo_connect::{
+  PacketGameLobbySummary, PacketGameStartPlayerClientInfoRequest, PlayerStartCheck as ProtoCheck,
+};
+use s2_grpc_utils::S2ProtoEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum, S2ProtoEnum)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(flo_net::proto::flo_connect::LobbyEndReason))]
+pub enum LobbyEndReason {
+  Cancelled = 0,
+  StartAckTimeout = 1,
+  VersionCheckFailed = 2,
+  /// The node rejected or failed to create the game
+  StartRejected = 3,
+  /// The host left the lobby
+  Closed = 4,
+}
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, S2ProtoEnum)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(flo_net::proto::flo_connect::PlayerStartCheckStatus))]
+pub enum PlayerStartCheckStatus {
+  Acked = 0,
+  NoAck = 1,
+  VersionMismatch = 2,
+  MapMismatch = 3,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PlayerStartCheck {
+  pub player_id: i32,
+  pub status: PlayerStartCheckStatus,
+  /// `None` if the player didn't ack
+  pub war3_version: Option<String>,
+  pub map_sha1: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LobbySummary {
+  pub game_id: i32,
+  pub reason: LobbyEndReason,
+  pub message: String,
+  /// Start check results, empty if no start check was in progress
+  pub players: Vec<PlayerStartCheck>,
+}
+
+impl LobbySummary {
+  pub fn new(game_id: i32, reason: LobbyEndReason, message: impl Into<String>) -> Self {
+    Self {
+      game_id,
+      reason,
+      message: message.into(),
+      players: vec![],
+    }
+  }
+
+  /// Checks the acks of `players` against the host's,
+  /// or against the most common values if the host didn't ack
+  pub fn with_start_check(
+    mut self,
+    players: &[i32],
+    host_player: i32,
+    acks: &HashMap<i32, PacketGameStartPlayerClientInfoRequest>,
+  ) -> Self {
+    let expected_version = acks
+      .get(&host_player)
+      .map(|ack| ack.war3_version.as_str())
+      .or_else(|| most_common(players, acks, |ack| ack.war3_version.as_str()));
+    let expected_sha1 = acks
+      .get(&host_player)
+      .map(|ack| ack.map_sha1.as_slice())
+      .or_else(|| most_common(players, acks, |ack| ack.map_sha1.as_slice()));
+
+    self.players = players
+      .iter()
+      .map(|player_id| {
+        let ack = match acks.get(player_id) {
+          Some(ack) => ack,
+          None => {
+            return PlayerStartCheck {
+              player_id: *player_id,
+              status: PlayerStartCheckStatus::NoAck,
+              war3_version: None,
+              map_sha1: None,
+            }
+          }
+        };
+        let status = if Some(ack.war3_version.as_str()) != expected_version {
+          PlayerStartCheckStatus::VersionMismatch
+        } else if Some(ack.map_sha1.as_slice()) != expected_sha1 {
+          PlayerStartCheckStatus::MapMismatch
+        } else {
+          PlayerStartCheckStatus::Acked
+        };
+        PlayerStartCheck {
+          player_id: *player_id,
+          status,
+          war3_version: Some(ack.war3_version.clone()),
+          map_sha1: Some(ack.map_sha1.clone()),
+        }
+      })
+      .collect();
+    self
+  }
+
+  /// Players that didn't ack or failed the version check
+  pub fn failed_player_ids(&self) -> Vec<i32> {
+    self
+      .players
+      .iter()
+      .filter(|check| check.status != PlayerStartCheckStatus::Acked)
+      .map(|check| check.player_id)
+      .collect()
+  }
+
+  pub fn to_packet(&self) -> PacketGameLobbySummary {
+    PacketGameLobbySummary {
+      game_id: self.game_id,
+      reason: self.reason.into_proto_enum().into(),
+      message: self.message.clone(),
+      players: self
+        .players
+        .iter()
+        .map(|check| ProtoCheck {
+          player_id: check.player_id,
+          status: check.status.into_proto_enum().into(),
+          war3_version: check.war3_version.clone().unwrap_or_default(),
+          map_sha1: check.map_sha1.clone().unwrap_or_default(),
+        })
+        .collect(),
+    }
+  }
+}
+
+fn most_common<'a, F, T>(
+  players: &[i32],
+  acks: &'a HashMap<i32, PacketGameStartPlayerClientInfoRequest>,
+  f: F,
+) -> Option<T>
+where
+  F: Fn(&'a PacketGameStartPlayerClientInfoRequest) -> T,
+  T: PartialEq + Copy,
+{
+  let mut counts: Vec<(T, usize)> = vec![];
+  for ack in players.iter().filter_map(|player_id| acks.get(player_id)) {
+    let value = f(ack);
+    match counts.iter_mut().find(|(v, _)| *v == value) {
+      Some((_, count)) => *count += 1,
+      None => counts.push((value, 1)),
+    }
+  }
+  // the first one wins ties
+  counts
+    .into_iter()
+    .fold(None, |max: Option<(T, usize)>, item| match max {
+      Some(max) if max.1 >= item.1 => Some(max),
+      _ => Some(item),
+    })
+    .map(|(value, _)| value)
+}
+
+pub fn insert(conn: &DbConn, summary: &LobbySummary) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "lobby_summary"]
+  struct Insert<'a> {
+    game_id: i32,
+    reason: LobbyEndReason,
+    message: &'a str,
+    players: serde_json::Value,
+  }
+
+  diesel::insert_into(lobby_summary::table)
+    .values(&Insert {
+      game_id: summary.game_id,
+      reason: summary.reason,
+      message: &summary.message,
+      players: serde_json::to_value(&summary.players)?,
+    })
+    .execute(conn)?;
+  Ok(())
+}
+
+#[test]
+fn test_lobby_summary_start_check() {
+  fn ack(version: &str, sha1: &[u8]) -> PacketGameStartPlayerClientInfoRequest {
+    PacketGameStartPlayerClientInfoRequest {
+      war3_version: version.to_string(),
+      map_sha1: sha1.to_vec(),
+      ..Default::default()
+    }
+  }
+
+  let mut acks = HashMap::new();
+  acks.insert(1, ack("1.36", &[1]));
+  acks.insert(2, ack("1.36", &[2]));
+  acks.insert(3, ack("1.35", &[1]));
+  let summary = LobbySummary::new(7, LobbyEndReason::VersionCheckFailed, "failed")
+    .with_start_check(&[1, 2, 3, 4], 1, &acks);
+  let statuses: Vec<_> = summary
+    .players
+    .iter()
+    .map(|check| (check.player_id, check.status))
+    .collect();
+  assert_eq!(
+    statuses,
+    vec![
+      (1, PlayerStartCheckStatus::Acked),
+      (2, PlayerStartCheckStatus::MapMismatch),
+      (3, PlayerStartCheckStatus::VersionMismatch),
+      (4, PlayerStartCheckStatus::NoAck),
+    ]
+  );
+  assert_eq!(summary.failed_player_ids(), vec![2, 3, 4]);
+
+  // the host didn't ack, the majority is expected
+  let summary = LobbySummary::new(7, LobbyEndReason::StartAckTimeout, "timeout");
+  let summary = summary.with_start_check(&[4, 3, 2, 1], 4, &acks);
+  assert_eq!(summary.failed_player_ids(), vec![4, 3, 2]);
+  assert_eq!(summary.to_packet().players[1].war3_version, "1.35");
+}
@@ -1,3 +1,4 @@
+use crate::error::Error;
 use crate::map::Map;
 use crate::node::{NodeRef, NodeRefColumns};
 use crate::player::{PlayerRef, PlayerRefColumns};
@@ -7,8 +8,9 @@ use chrono::{DateTime, Utc};
 use diesel::prelude::*;
 use s2_grpc_utils::{S2ProtoEnum, S2ProtoPack, S2ProtoUnpack};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize, Deserialize, S2ProtoPack, S2ProtoUnpack, Clone)]
+#[derive(Debug, Serialize, Deserialize, S2ProtoPack, Clone)]
 #[s2_grpc(message_type(flo_grpc::game::Game))]
 pub struct Game {
   pub id: i32,
@@ -31,6 +33,20 @@ pub struct Game {
   pub updated_at: DateTime<Utc>,
   pub mask_player_names: bool,
   pub game_version: Option<String>,
+  #[s2_grpc(skip_pack)]
+  pub metadata: GameMetadata,
+  /// Slot settings can only be changed by the API client that created the game
+  #[s2_grpc(skip_pack)]
+  #[serde(default)]
+  pub locked_layout: bool,
+  /// Lobby update revision the game was loaded at, set by the game actor
+  #[s2_grpc(skip_pack)]
+  #[serde(default)]
+  pub revision: u64,
+  /// Save loaded instead of the map, set for resume lobbies
+  #[s2_grpc(skip_pack)]
+  #[serde(default)]
+  pub saved_game: Option<SavedGame>,
 }
 
 impl S2ProtoPack<flo_net::proto::flo_connect::GameInfo> for Game {
@@ -52,6 +68,10 @@ impl S2ProtoPack<flo_net::proto::flo_connect::GameInfo> for Game {
       is_live: self.is_live,
       random_seed: self.random_seed,
       created_by: self.created_by.pack()?,
+      metadata: Some(self.metadata.into()),
+      revision: self.revision,
+      locked_layout: self.locked_layout,
+      saved_game: self.saved_game.map(Into::into),
     })
   }
 }
@@ -65,6 +85,14 @@ impl Game {
       .collect()
   }
 
+  /// Returns true if there is no open slot left
+  pub fn is_full(&self) -> bool {
+    !self
+      .slots
+      .iter()
+      .any(|slot| slot.settings.status == SlotStatus::Open)
+  }
+
   pub fn find_player_slot_index(&self, player_id: i32) -> Option<usize> {
     self.slots.iter().position(|slot| {
       slot
@@ -88,6 +116,180 @@ impl Game {
   }
 }
 
+const METADATA_MAX_DESCRIPTION_LEN: usize = 500;
+const METADATA_MAX_TAGS: usize = 8;
+const METADATA_MAX_TAG_LEN: usize = 24;
+const METADATA_MAX_LANGUAGE_LEN: usize = 16;
+const METADATA_MAX_LOCALIZED_NAMES: usize = 8;
+const METADATA_MAX_NAME_LEN: usize = 64;
+const METADATA_MAX_MODE_LEN: usize = 24;
+
+/// Optional lobby details for the game browser, stored in the `meta` column
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct GameMetadata {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub tags: Vec<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub language: Option<String>,
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub localized_names: BTreeMap<String, String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub map_preview_checksum: Option<u32>,
+  /// Encoded into slot handicaps by clients, one character per occupied slot
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub mode: Option<String>,
+  /// Lobby chat is end-to-end encrypted, the controller only relays ciphertext
+  #[serde(default)]
+  pub e2e_chat: bool,
+}
+
+impl GameMetadata {
+  /// Trims values, drops empty ones and removes duplicated tags
+  pub fn normalize(self) -> Self {
+    fn non_empty(v: String) -> Option<String> {
+      let v = v.trim();
+      if v.is_empty() {
+        None
+      } else {
+        Some(v.to_string())
+      }
+    }
+    let mut tags: Vec<String> = vec![];
+    for tag in self.tags.into_iter().filter_map(non_empty) {
+      if !tags.iter().any(|v| v.eq_ignore_ascii_case(&tag)) {
+        tags.push(tag);
+      }
+    }
+    Self {
+      description: self.description.and_then(non_empty),
+      tags,
+      language: self.language.and_then(non_empty),
+      localized_names: self
+        .localized_names
+        .into_iter()
+        .filter_map(|(lang, name)| Some((non_empty(lang)?, non_empty(name)?)))
+        .collect(),
+      map_preview_checksum: self.map_preview_checksum.filter(|v| *v != 0),
+      mode: self
+        .mode
+        .and_then(non_empty)
+        .map(|v| v.to_ascii_lowercase()),
+      e2e_chat: self.e2e_chat,
+    }
+  }
+
+  pub fn validate(&self) -> Result<(), Error> {
+    if let Some(ref v) = self.description {
+      if v.chars().count() > METADATA_MAX_DESCRIPTION_LEN {
+        return Err(Error::GameMetadataInvalid("description too long"));
+      }
+    }
+    if self.tags.len() > METADATA_MAX_TAGS {
+      return Err(Error::GameMetadataInvalid("too many tags"));
+    }
+    if self
+      .tags
+      .iter()
+      .any(|v| v.chars().count() > METADATA_MAX_TAG_LEN)
+    {
+      return Err(Error::GameMetadataInvalid("tag too long"));
+    }
+    if self.localized_names.len() > METADATA_MAX_LOCALIZED_NAMES {
+      return Err(Error::GameMetadataInvalid("too many localized names"));
+    }
+    if !self
+      .language
+      .iter()
+      .chain(self.localized_names.keys())
+      .all(|v| is_language_tag(v))
+    {
+      return Err(Error::GameMetadataInvalid("invalid language"));
+    }
+    if self
+      .localized_names
+      .values()
+      .any(|v| v.chars().count() > METADATA_MAX_NAME_LEN)
+    {
+      return Err(Error::GameMetadataInvalid("localized name too long"));
+    }
+    if let Some(ref v) = self.mode {
+      if v.chars().count() > METADATA_MAX_MODE_LEN {
+        return Err(Error::GameMetadataInvalid("mode too long"));
+      }
+      if !v.chars().all(|c| flo_w3gs::slot::HCL_CHARS.contains(c)) {
+        return Err(Error::GameMetadataInvalid("invalid mode"));
+      }
+    }
+    Ok(())
+  }
+}
+
+// BCP 47 like: `en`, `zh-CN`
+fn is_language_tag(v: &str) -> bool {
+  v.len() <= METADATA_MAX_LANGUAGE_LEN
+    && v
+      .split('-')
+      .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+impl From<flo_net::proto::flo_connect::GameMetadata> for GameMetadata {
+  fn from(v: flo_net::proto::flo_connect::GameMetadata) -> Self {
+    Self {
+      description: Some(v.description),
+      tags: v.tags,
+      language: Some(v.language),
+      localized_names: v.localized_names.into_iter().collect(),
+      map_preview_checksum: Some(v.map_preview_checksum),
+      mode: Some(v.mode),
+      e2e_chat: v.e2e_chat,
+    }
+    .normalize()
+  }
+}
+
+impl From<GameMetadata> for flo_net::proto::flo_connect::GameMetadata {
+  fn from(v: GameMetadata) -> Self {
+    Self {
+      description: v.description.unwrap_or_default(),
+      tags: v.tags,
+      language: v.language.unwrap_or_default(),
+      localized_names: v.localized_names.into_iter().collect(),
+      map_preview_checksum: v.map_preview_checksum.unwrap_or_default(),
+      mode: v.mode.unwrap_or_default(),
+      e2e_chat: v.e2e_chat,
+    }
+  }
+}
+
+/// A save written by all remaining players of a game, reported by the node
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct GameSave {
+  /// File name sent with the `SaveGame` action
+  pub name: String,
+  /// Slot index of each player who saved, by player id
+  pub slots: BTreeMap<i32, usize>,
+  pub saved_at: DateTime<Utc>,
+}
+
+/// Save loaded by a resume lobby,
+/// the magic number is stored in the save file and has to be provided by the API client
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SavedGame {
+  pub name: String,
+  pub magic_number: u32,
+}
+
+impl From<SavedGame> for flo_net::proto::flo_connect::SavedGame {
+  fn from(v: SavedGame) -> Self {
+    Self {
+      name: v.name,
+      magic_number: v.magic_number,
+    }
+  }
+}
+
 #[derive(Debug)]
 pub struct PlayerSlotInfo<'a> {
   pub slot_index: usize,
@@ -5,11 +5,16 @@ use crate::game::messages::{CreateGame, PlayerJoin, PlayerLeave};
 use crate::game::state::cancel::CancelGame;
 use crate::game::state::create::CreateGameAsBot;
 use crate::game::state::node::SelectNode;
+use crate::game::state::quota::CheckNodeQuota;
 use crate::game::state::registry::{AddGamePlayer, Remove, RemoveGamePlayer, UpdateGameNodeCache};
 use crate::game::state::start::{StartGameCheckAsBot, StartGameCheckAsBotResult};
-use crate::node::messages::ListNode;
+use crate::game::Game;
+use crate::node::messages::ListCompatibleNodes;
+use crate::node::version::GAME_TARGET_VERSION;
+use crate::notification::{GameNotification, GameNotificationKind, NotifyGamePlayers};
 use crate::player::state::ping::GetPlayersPingSnapshot;
 use crate::player::{PlayerBanType, PlayerSource, SourceState};
+use crate::player::token::TokenScope;
 use crate::state::{ActorMapExt, ControllerStateRef};
 use bs_diesel_utils::executor::ExecutorError;
 use chrono::{DateTime, Utc};
@@ -39,6 +44,20 @@ impl FloControllerService {
   pub fn new(state: ControllerStateRef) -> Self {
     FloControllerService { state }
   }
+
+  async fn notify_game_players(&self, game: &Game, kind: GameNotificationKind) {
+    let message = NotifyGamePlayers {
+      notification: GameNotification {
+        game_id: game.id,
+        game_name: game.name.clone(),
+        kind,
+      },
+      player_ids: game.get_player_ids(),
+    };
+    if let Err(err) = self.state.notifications.notify(message).await {
+      tracing::error!(game_id = game.id, "notify game players: {}", err);
+    }
+  }
 }
 
 #[tonic::async_trait]
@@ -105,7 +124,12 @@ impl FloController for FloControllerService {
       .exec(move |conn| db::upsert(conn, &upsert))
       .await
       .map_err(Error::from)?;
-    let token = crate::player::token::create_player_token(player.id)?;
+    let token = crate::player::token::issue_scoped_token(
+      player.id,
+      &[TokenScope::Connect],
+      None,
+      Some(api_client_id),
+    )?;
     Ok(Response::new(UpdateAndGetPlayerReply {
       player: player.pack().map_err(Status::internal)?,
       token,
@@ -113,7 +137,14 @@ impl FloController for FloControllerService {
   }
 
   async fn list_nodes(&self, _request: Request<()>) -> Result<Response<ListNodesReply>, Status> {
-    let nodes = self.state.nodes.send(ListNode).await.map_err(Error::from)?;
+    let nodes = self
+      .state
+      .nodes
+      .send(ListCompatibleNodes {
+        war3_version: GAME_TARGET_VERSION.clone(),
+      })
+      .await
+      .map_err(Error::from)?;
     Ok(Response::new(ListNodesReply {
       nodes: nodes.pack().map_err(Error::from)?,
     }))
@@ -140,18 +171,24 @@ impl FloController for FloControllerService {
     request: Request<GetGameRequest>,
   ) -> Result<Response<GetGameReply>, Status> {
     let game_id = request.into_inner().game_id;
-    let game = self
+    let (game, privacy) = self
       .state
       .db
-      .exec(move |conn| crate::game::db::get_full(conn, game_id))
+      .exec(move |conn| -> Result<_> {
+        let game = crate::game::db::get_full(conn, game_id)?;
+        let privacy = crate::player::privacy::get_privacy_map(conn, &game.get_player_ids())?;
+        Ok((game, privacy))
+      })
       .await
       .map_err(|e| match e {
         ExecutorError::Task(Error::GameNotFound) => Status::invalid_argument(e.to_string()),
         other => Status::internal(other.to_string()),
       })?;
-    Ok(Response::new(GetGameReply {
+    let mut res = Response::new(GetGameReply {
       game: game.pack().map_err(Error::from)?,
-    }))
+    });
+    crate::player::privacy::insert_metadata(res.metadata_mut(), &privacy);
+    Ok(res)
   }
 
   async fn create_game(
@@ -313,6 +350,13 @@ impl FloController for FloControllerService {
       node_id,
     } = request.into_inner();
 
+    self
+      .state
+      .games
+      .send(CheckNodeQuota { game_id, node_id })
+      .await
+      .map_err(Error::from)??;
+
     self
       .state
       .games
@@ -456,10 +500,15 @@ impl FloController for FloControllerService {
         api_client_id: request.get_api_client_id(),
         api_player_id: request.get_api_player_id(),
         params: CreateGameAsBotParams::unpack(request.into_inner()).map_err(Error::from)?,
+        saved_game: None,
       })
       .await
       .map_err(Error::from)??;
 
+    self
+      .notify_game_players(&game, GameNotificationKind::GameScheduled)
+      .await;
+
     Ok(Response::new(CreateGameAsBotReply {
       game: game.pack().map_err(Status::internal)?,
     }))
@@ -0,0 +1,100 @@
+use crate::db::DbConn;
+use crate::error::*;
+use crate::job::JobPayload;
+use crate::schema::job;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde_json::Value;
+use std::time::Duration;
+
+#[derive(Debug, Queryable)]
+pub struct Job {
+  pub id: i32,
+  pub payload: Value,
+  /// Including the current attempt
+  pub attempts: i32,
+  pub max_attempts: i32,
+}
+
+pub fn enqueue(conn: &DbConn, payload: &JobPayload, run_at: DateTime<Utc>) -> Result<i32> {
+  diesel::insert_into(job::table)
+    .values((
+      job::kind.eq(payload.kind()),
+      job::payload.eq(serde_json::to_value(payload)?),
+      job::run_at.eq(run_at),
+    ))
+    .returning(job::id)
+    .get_result(conn)
+    .map_err(Into::into)
+}
+
+/// Locks up to `limit` due jobs for `lease` and counts the attempt,
+/// jobs locked by another worker are skipped
+pub fn lease(conn: &DbConn, limit: i64, lease: Duration) -> Result<Vec<Job>> {
+  use diesel::pg::expression::dsl::any;
+  use job::dsl;
+
+  conn.transaction(|| -> Result<_> {
+    let now = Utc::now();
+    let ids: Vec<i32> = job::table
+      .select(dsl::id)
+      .filter(
+        dsl::failed_at
+          .is_null()
+          .and(dsl::run_at.le(now))
+          .and(dsl::locked_until.le(now)),
+      )
+      .order(dsl::run_at)
+      .limit(limit)
+      .for_update()
+      .skip_locked()
+      .load(conn)?;
+
+    if ids.is_empty() {
+      return Ok(vec![]);
+    }
+
+    let locked_until =
+      now + chrono::Duration::from_std(lease).unwrap_or_else(|_| chrono::Duration::minutes(10));
+    diesel::update(job::table.filter(dsl::id.eq(any(&ids))))
+      .set((
+        dsl::locked_until.eq(locked_until),
+        dsl::attempts.eq(dsl::attempts + 1),
+        dsl::updated_at.eq(now),
+      ))
+      .returning((dsl::id, dsl::payload, dsl::attempts, dsl::max_attempts))
+      .get_results(conn)
+      .map_err(Into::into)
+  })
+}
+
+pub fn complete(conn: &DbConn, id: i32) -> Result<()> {
+  diesel::delete(job::table.find(id)).execute(conn)?;
+  Ok(())
+}
+
+/// Schedules the next attempt at `retry_at`, or marks the job as failed if `None`
+pub fn fail(conn: &DbConn, id: i32, error: &str, retry_at: Option<DateTime<Utc>>) -> Result<()> {
+  use job::dsl;
+  let now = Utc::now();
+  let update = diesel::update(job::table.find(id));
+  match retry_at {
+    Some(run_at) => update
+      .set((
+        dsl::run_at.eq(run_at),
+        dsl::locked_until.eq(now),
+        dsl::last_error.eq(error),
+        dsl::updated_at.eq(now),
+      ))
+      .execute(conn)?,
+    None => update
+      .set((
+        dsl::failed_at.eq(now),
+        dsl::locked_until.eq(now),
+        dsl::last_error.eq(error),
+        dsl::updated_at.eq(now),
+      ))
+      .execute(conn)?,
+  };
+  Ok(())
+}
@@ -0,0 +1,176 @@
+//! Persistent queue of deferred work that must survive controller restarts,
+//! e.g. retries of failed notification deliveries and scheduled season closes.
+//!
+//! The worker leases due jobs for `JOB_LEASE_DURATION`, jobs with an expired lease
+//! (the controller stopped while running them) are picked up again.
+//! Failed jobs are retried with exponential backoff until `max_attempts` is reached,
+//! then kept with `failed_at` set for inspection.
+//!
+//! The worker also prunes expired player login records every `LOGIN_PRUNE_INTERVAL`.
+
+pub mod db;
+
+use crate::error::*;
+use crate::notification::{
+  GameNotification, GetNotificationProviders, NotificationProviders, NotificationTarget,
+};
+use crate::state::{ControllerState, ControllerStateRef};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+const JOB_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const JOB_LEASE_BATCH: i64 = 16;
+const JOB_RUN_TIMEOUT: Duration = Duration::from_secs(30);
+/// Jobs of a batch run one by one
+const JOB_LEASE_DURATION: Duration = Duration::from_secs(10 * 60);
+const JOB_RETRY_BASE_DELAY_SECS: i64 = 10;
+const JOB_RETRY_MAX_DELAY_SECS: i64 = 60 * 60;
+const LOGIN_PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobPayload {
+  NotificationDelivery {
+    target: NotificationTarget,
+    notification: GameNotification,
+  },
+  SeasonClose {
+    season_id: i32,
+  },
+}
+
+impl JobPayload {
+  pub fn kind(&self) -> &'static str {
+    match *self {
+      JobPayload::NotificationDelivery { .. } => "notification_delivery",
+      JobPayload::SeasonClose { .. } => "season_close",
+    }
+  }
+}
+
+/// Delay before the next attempt of a job that failed `attempts` times
+pub fn retry_delay(attempts: i32) -> chrono::Duration {
+  let exp = attempts.saturating_sub(1).clamp(0, 16) as u32;
+  chrono::Duration::seconds(
+    JOB_RETRY_BASE_DELAY_SECS
+      .saturating_mul(2i64.pow(exp))
+      .min(JOB_RETRY_MAX_DELAY_SECS),
+  )
+}
+
+pub async fn serve(state: ControllerStateRef) -> Result<()> {
+  let providers = state.notifications.send(GetNotificationProviders).await?;
+  let mut interval = tokio::time::interval(JOB_POLL_INTERVAL);
+  let mut logins_pruned_at: Option<Instant> = None;
+
+  loop {
+    interval.tick().await;
+
+    if logins_pruned_at.map_or(true, |t| t.elapsed() >= LOGIN_PRUNE_INTERVAL) {
+      logins_pruned_at = Some(Instant::now());
+      let before = Utc::now() - crate::player::smurf::login_retention();
+      match state
+        .db
+        .exec(move |conn| crate::player::smurf::prune_logins(conn, before))
+        .await
+      {
+        Ok(0) => {}
+        Ok(n) => tracing::info!("job: pruned {} login records", n),
+        Err(err) => tracing::error!("job: prune logins: {}", err),
+      }
+    }
+
+    let jobs = match state
+      .db
+      .exec(|conn| self::db::lease(conn, JOB_LEASE_BATCH, JOB_LEASE_DURATION))
+      .await
+    {
+      Ok(v) => v,
+      Err(err) => {
+        tracing::error!("job: lease: {}", err);
+        continue;
+      }
+    };
+
+    for job in jobs {
+      let job_id = job.id;
+      let res = match run(&state, &providers, &job).await {
+        Ok(_) => {
+          state
+            .db
+            .exec(move |conn| self::db::complete(conn, job_id))
+            .await
+        }
+        Err(err) => {
+          let retry_at = if job.attempts < job.max_attempts {
+            tracing::warn!(job_id, attempts = job.attempts, "job: {}", err);
+            Some(Utc::now() + retry_delay(job.attempts))
+          } else {
+            tracing::error!(job_id, attempts = job.attempts, "job: giving up: {}", err);
+            None
+          };
+          let error = err.to_string();
+          state
+            .db
+            .exec(move |conn| self::db::fail(conn, job_id, &error, retry_at))
+            .await
+        }
+      };
+      if let Err(err) = res {
+        tracing::error!(job_id, "job: update: {}", err);
+      }
+    }
+  }
+}
+
+async fn run(
+  state: &ControllerState,
+  providers: &NotificationProviders,
+  job: &self::db::Job,
+) -> Result<()> {
+  let payload: JobPayload = serde_json::from_value(job.payload.clone())?;
+  let task = async {
+    match payload {
+      JobPayload::NotificationDelivery {
+        target,
+        notification,
+      } => {
+        let provider = providers.get(&target.channel).ok_or_else(|| {
+          Error::NotificationRelay(format!("provider not configured: {:?}", target.channel))
+        })?;
+        provider.send(&target, &notification).await
+      }
+      JobPayload::SeasonClose { season_id } => {
+        let closed = state
+          .db
+          .exec(
+            move |conn| match crate::season::db::close(conn, season_id, None) {
+              Ok(_) => Ok(true),
+              // closed by the ladder before its end
+              Err(Error::SeasonClosed) => Ok(false),
+              Err(err) => Err(err),
+            },
+          )
+          .await?;
+        if closed {
+          tracing::info!(season_id, "season closed at its end");
+        }
+        Ok(())
+      }
+    }
+  };
+  tokio::time::timeout(JOB_RUN_TIMEOUT, task)
+    .await
+    .map_err(|_| Error::JobTimeout)?
+}
+
+#[test]
+fn test_retry_delay() {
+  assert_eq!(retry_delay(0), chrono::Duration::seconds(10));
+  assert_eq!(retry_delay(1), chrono::Duration::seconds(10));
+  assert_eq!(retry_delay(2), chrono::Duration::seconds(20));
+  assert_eq!(retry_delay(4), chrono::Duration::seconds(80));
+  assert_eq!(retry_delay(10), chrono::Duration::seconds(60 * 60));
+  assert_eq!(retry_delay(i32::MAX), chrono::Duration::seconds(60 * 60));
+}
@@ -14,17 +14,30 @@ extern crate diesel;
 mod db;
 mod schema;
 
+pub mod chat;
+pub mod clan;
 mod client;
 mod config;
 pub mod error;
+pub mod feature;
 pub mod game;
 mod grpc;
 pub mod host;
+pub mod job;
+pub mod maintenance;
 pub mod map;
+mod metrics;
 pub mod node;
+pub mod notification;
 pub mod player;
+mod profiling;
+mod rest;
+pub mod season;
 mod state;
 
 pub use client::serve as serve_socket;
 pub use grpc::serve as serve_grpc;
+pub use job::serve as serve_jobs;
+pub use metrics::serve_metrics;
+pub use rest::serve as serve_rest;
 pub use state::{ControllerState, ControllerStateRef};
@@ -0,0 +1,130 @@
+//! Controller-wide maintenance mode.
+//!
+//! While active, new games can't be created or started.
+//! Running games finish normally and connected clients stay connected.
+
+use crate::error::*;
+use chrono::{DateTime, Utc};
+use flo_net::proto::flo_connect::PacketMaintenanceUpdate;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use s2_grpc_utils::S2ProtoEnum;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Starts the controller in maintenance mode: `scheduled`, `upgrade` or `incident`
+static MAINTENANCE_REASON: Lazy<Option<MaintenanceReason>> = Lazy::new(|| {
+  std::env::var("FLO_MAINTENANCE_REASON")
+    .ok()
+    .and_then(|v| match v.as_str() {
+      "scheduled" => Some(MaintenanceReason::Scheduled),
+      "upgrade" => Some(MaintenanceReason::Upgrade),
+      "incident" => Some(MaintenanceReason::Incident),
+      _ => None,
+    })
+});
+static MAINTENANCE_MESSAGE: Lazy<Option<String>> =
+  Lazy::new(|| std::env::var("FLO_MAINTENANCE_MESSAGE").ok());
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, S2ProtoEnum)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(flo_net::proto::flo_connect::MaintenanceReason))]
+pub enum MaintenanceReason {
+  Scheduled = 0,
+  Upgrade = 1,
+  Incident = 2,
+}
+
+impl MaintenanceReason {
+  fn default_message(&self) -> &'static str {
+    match *self {
+      MaintenanceReason::Scheduled => "Flo is under scheduled maintenance.",
+      MaintenanceReason::Upgrade => "Flo is being upgraded.",
+      MaintenanceReason::Incident => "Flo is recovering from an incident.",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Maintenance {
+  pub reason: MaintenanceReason,
+  pub message: Option<String>,
+  pub started_at: DateTime<Utc>,
+}
+
+impl Maintenance {
+  pub fn new(reason: MaintenanceReason, message: Option<String>) -> Self {
+    Self {
+      reason,
+      message: message.filter(|v| !v.trim().is_empty()),
+      started_at: Utc::now(),
+    }
+  }
+
+  pub fn message(&self) -> &str {
+    self
+      .message
+      .as_deref()
+      .unwrap_or_else(|| self.reason.default_message())
+  }
+
+  fn to_packet(&self) -> PacketMaintenanceUpdate {
+    let mut pkt = PacketMaintenanceUpdate {
+      active: true,
+      message: self.message().to_string(),
+      ..Default::default()
+    };
+    pkt.set_reason(self.reason.into_proto_enum());
+    pkt
+  }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MaintenanceState {
+  inner: Arc<RwLock<Option<Maintenance>>>,
+}
+
+impl MaintenanceState {
+  pub fn from_env() -> Self {
+    let state = Self::default();
+    if let Some(reason) = *MAINTENANCE_REASON {
+      tracing::warn!("starting in maintenance mode: {:?}", reason);
+      state.set(Some(Maintenance::new(reason, MAINTENANCE_MESSAGE.clone())));
+    }
+    state
+  }
+
+  pub fn get(&self) -> Option<Maintenance> {
+    self.inner.read().clone()
+  }
+
+  /// Returns `Error::Maintenance` if maintenance mode is active
+  pub fn check(&self) -> Result<()> {
+    if let Some(ref maintenance) = *self.inner.read() {
+      return Err(Error::Maintenance(maintenance.message().to_string()));
+    }
+    Ok(())
+  }
+
+  /// Returns the update packet to broadcast if the state changed
+  pub(crate) fn set(&self, value: Option<Maintenance>) -> Option<PacketMaintenanceUpdate> {
+    let mut guard = self.inner.write();
+    if guard.is_none() && value.is_none() {
+      return None;
+    }
+    let pkt = match value.as_ref() {
+      Some(maintenance) => maintenance.to_packet(),
+      None => PacketMaintenanceUpdate {
+        active: false,
+        ..Default::default()
+      },
+    };
+    *guard = value;
+    Some(pkt)
+  }
+
+  /// Sent to players on connect
+  pub fn packet(&self) -> Option<PacketMaintenanceUpdate> {
+    self.inner.read().as_ref().map(Maintenance::to_packet)
+  }
+}
@@ -1,10 +1,12 @@
 use diesel::prelude::*;
 use s2_grpc_utils::S2ProtoUnpack;
 use serde::Deserialize;
+use serde_json::Value;
 
 use crate::db::DbConn;
 use crate::error::*;
-use crate::schema::map_checksum;
+use crate::map::Map;
+use crate::schema::{map_checksum, registered_map};
 
 pub fn search_checksum(conn: &DbConn, sha1: String) -> Result<Option<u32>> {
   use map_checksum::dsl;
@@ -62,3 +64,57 @@ struct Insert<'a> {
   sha1: &'a str,
   checksum: Vec<u8>,
 }
+
+/// Adds the map to the registered maps lobbies can switch to, or refreshes its metadata
+pub fn register(conn: &DbConn, map: &Map) -> Result<()> {
+  use registered_map::dsl;
+
+  let insert = RegisteredMapInsert {
+    sha1: map.sha1.to_vec(),
+    name: &map.name,
+    map: serde_json::to_value(map)?,
+  };
+
+  diesel::insert_into(registered_map::table)
+    .values(&insert)
+    .on_conflict(dsl::sha1)
+    .do_update()
+    .set((
+      dsl::name.eq(&insert.name),
+      dsl::map.eq(&insert.map),
+      dsl::updated_at.eq(diesel::dsl::now),
+    ))
+    .execute(conn)?;
+  Ok(())
+}
+
+/// Registered maps with the given sha1s, in the same order.
+/// Returns `Error::MapNotRegistered` if any of them is missing.
+pub fn get_registered(conn: &DbConn, sha1s: &[Vec<u8>]) -> Result<Vec<Map>> {
+  use registered_map::dsl;
+
+  let rows: Vec<(Vec<u8>, Value)> = registered_map::table
+    .filter(dsl::sha1.eq_any(sha1s))
+    .select((dsl::sha1, dsl::map))
+    .load(conn)?;
+
+  sha1s
+    .iter()
+    .map(|sha1| {
+      let value = rows
+        .iter()
+        .find(|(v, _)| v == sha1)
+        .map(|(_, map)| map.clone())
+        .ok_or_else(|| Error::MapNotRegistered)?;
+      serde_json::from_value(value).map_err(Into::into)
+    })
+    .collect()
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "registered_map"]
+struct RegisteredMapInsert<'a> {
+  sha1: Vec<u8>,
+  name: &'a str,
+  map: Value,
+}
@@ -0,0 +1,89 @@
+use once_cell::sync::Lazy;
+use prometheus::{
+  register_histogram_vec, register_int_counter, register_int_counter_vec, Encoder, HistogramVec,
+  IntCounter, IntCounterVec, TextEncoder,
+};
+
+use crate::error::*;
+use hyper::header::CONTENT_TYPE;
+
+/// `kind`: `player`, `bot` or `batch`. A batch is observed once for all of its games
+pub static GAME_CREATE_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+  register_histogram_vec!(
+    "flocontroller_game_create_seconds",
+    "Game creation latency",
+    &["kind"]
+  )
+  .unwrap()
+});
+pub static GAMES_CREATED: Lazy<IntCounterVec> = Lazy::new(|| {
+  register_int_counter_vec!(
+    "flocontroller_games_created_total",
+    "Number of created games",
+    &["kind"]
+  )
+  .unwrap()
+});
+
+/// `kind`: `all`, `players` or `map`. Observed by the caller,
+/// includes the time the broadcast waited in the player registry mailbox
+pub static BROADCAST_FANOUT_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+  register_histogram_vec!(
+    "flocontroller_broadcast_fanout_seconds",
+    "Player broadcast fanout latency",
+    &["kind"],
+    vec![0.0001, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5]
+  )
+  .unwrap()
+});
+/// Game stage changes refused by `game::lifecycle`, labeled by `from` and `to` stage
+pub static GAME_STAGE_TRANSITIONS_REJECTED: Lazy<IntCounterVec> = Lazy::new(|| {
+  register_int_counter_vec!(
+    "flocontroller_game_stage_transitions_rejected_total",
+    "Number of rejected game stage transitions",
+    &["from", "to"]
+  )
+  .unwrap()
+});
+/// Player connections removed because their send queue was full or closed
+pub static PLAYER_SENDERS_DROPPED: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flocontroller_player_senders_dropped_total",
+    "Number of player connections dropped on send"
+  )
+  .unwrap()
+});
+
+pub async fn serve_metrics() -> Result<()> {
+  use hyper::service::{make_service_fn, service_fn};
+  use hyper::{Body, Request, Response, Server};
+  use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
+  async fn serve_req(_req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+    let encoder = TextEncoder::new();
+
+    let metric_families = prometheus::gather();
+    let mut buffer = vec![];
+    encoder.encode(&metric_families, &mut buffer).unwrap();
+
+    let response = Response::builder()
+      .status(200)
+      .header(CONTENT_TYPE, encoder.format_type())
+      .body(Body::from(buffer))
+      .unwrap();
+
+    Ok(response)
+  }
+
+  let addr = SocketAddr::from(SocketAddrV4::new(
+    Ipv4Addr::UNSPECIFIED,
+    flo_constants::CONTROLLER_HTTP_PORT,
+  ));
+
+  let server = Server::bind(&addr).serve(make_service_fn(|_| async {
+    Ok::<_, hyper::Error>(service_fn(serve_req))
+  }));
+  server.await?;
+
+  Ok(())
+}
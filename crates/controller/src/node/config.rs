@@ -0,0 +1,42 @@
+use flo_net::proto::flo_node::{NodeConfig as NodeConfigPacket, NodeMapRepositories};
+use serde::{Deserialize, Serialize};
+
+/// Node settings pushed to a connected node, applied live and persisted by the node.
+/// `None` fields keep the node's current value in updates,
+/// the config returned by the node has all fields set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeConfig {
+  pub map_repositories: Option<Vec<String>>,
+  /// 0 if unlimited
+  pub max_games: Option<u32>,
+  /// `RUST_LOG` format directives, empty to restore the node's `RUST_LOG`
+  pub log_level: Option<String>,
+  /// 0 disables keyframes
+  pub obs_keyframe_interval_secs: Option<u32>,
+  /// 0 disables RTT stats
+  pub obs_rtt_stats_interval_secs: Option<u32>,
+}
+
+impl From<NodeConfig> for NodeConfigPacket {
+  fn from(v: NodeConfig) -> Self {
+    Self {
+      map_repositories: v.map_repositories.map(|urls| NodeMapRepositories { urls }),
+      max_games: v.max_games,
+      log_level: v.log_level,
+      obs_keyframe_interval_secs: v.obs_keyframe_interval_secs,
+      obs_rtt_stats_interval_secs: v.obs_rtt_stats_interval_secs,
+    }
+  }
+}
+
+impl From<NodeConfigPacket> for NodeConfig {
+  fn from(v: NodeConfigPacket) -> Self {
+    Self {
+      map_repositories: v.map_repositories.map(|v| v.urls),
+      max_games: v.max_games,
+      log_level: v.log_level,
+      obs_keyframe_interval_secs: v.obs_keyframe_interval_secs,
+      obs_rtt_stats_interval_secs: v.obs_rtt_stats_interval_secs,
+    }
+  }
+}
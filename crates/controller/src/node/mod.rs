@@ -1,12 +1,19 @@
+pub mod config;
 pub mod db;
+pub mod routing;
 mod state;
 mod types;
+pub mod version;
 
 pub use state::conn::NodeConnActor;
 pub use state::request::PlayerLeaveResponse;
 pub use state::NodeRegistry;
 pub use types::*;
 pub mod messages {
-  pub use crate::node::state::conn::{NodeCreateGame, NodePlayerLeave};
-  pub use crate::node::state::ListNode;
+  pub use crate::node::state::conn::{
+    NodeCommitGame, NodeCreateGame, NodeGameCommand, NodeGameSurrender, NodePlayerLeave,
+    NodeUpdateConfig,
+  };
+  pub use crate::node::state::start_queue::StartOwner;
+  pub use crate::node::state::{ListCompatibleNodes, ListNode, ListNodeRouting};
 }
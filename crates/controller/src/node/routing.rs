@@ -0,0 +1,216 @@
+use chrono::{DateTime, Utc};
+use flo_net::proto::flo_node::{NodeCapabilities, PacketNodeRoutingStats};
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Client connection routing reported by a node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeRouting {
+  /// The node routes client connections by game id,
+  /// `false` for nodes that only resolve player tokens
+  pub game_routing: bool,
+  /// The node reserves games until the controller commits them
+  pub two_phase_create: bool,
+  /// Games the node hosts at most, 0 if unlimited or not reported
+  pub max_games: u32,
+  /// The node rejected a game for being at capacity, and expected room at this time
+  pub busy_until: Option<DateTime<Utc>>,
+  pub stats: Option<NodeRoutingStats>,
+}
+
+impl NodeRouting {
+  /// Returns the estimated wait if the node can't take more games
+  pub fn busy_for(&self, now: DateTime<Utc>) -> Option<Duration> {
+    if let Some(until) = self.busy_until {
+      if until > now {
+        return (until - now).to_std().ok();
+      }
+    }
+    match self.stats {
+      // the reported count may be stale, the wait is unknown
+      Some(ref stats) if self.max_games > 0 && stats.games >= self.max_games => {
+        Some(Duration::from_secs(0))
+      }
+      _ => None,
+    }
+  }
+}
+
+/// Counters since the node started, gauges at `updated_at`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeRoutingStats {
+  pub games: u32,
+  pub player_connections: u32,
+  pub pending_player_tokens: u32,
+  pub accepted: u64,
+  pub routed_by_game_id: u64,
+  pub rejected_invalid_token: u64,
+  pub rejected_game_not_found: u64,
+  pub rejected_other: u64,
+  pub updated_at: DateTime<Utc>,
+}
+
+impl From<PacketNodeRoutingStats> for NodeRoutingStats {
+  fn from(v: PacketNodeRoutingStats) -> Self {
+    Self {
+      games: v.games,
+      player_connections: v.player_connections,
+      pending_player_tokens: v.pending_player_tokens,
+      accepted: v.accepted,
+      routed_by_game_id: v.routed_by_game_id,
+      rejected_invalid_token: v.rejected_invalid_token,
+      rejected_game_not_found: v.rejected_game_not_found,
+      rejected_other: v.rejected_other,
+      updated_at: Utc::now(),
+    }
+  }
+}
+
+/// Shared by the node registry and its connection actors, which keep it up to date
+#[derive(Debug, Clone, Default)]
+pub struct NodeRoutingTable(Arc<RwLock<BTreeMap<i32, NodeRouting>>>);
+
+impl NodeRoutingTable {
+  pub fn snapshot(&self) -> BTreeMap<i32, NodeRouting> {
+    self.0.read().clone()
+  }
+
+  pub fn get(&self, node_id: i32) -> Option<NodeRouting> {
+    self.0.read().get(&node_id).cloned()
+  }
+
+  /// Drops removed nodes
+  pub(crate) fn retain_nodes(&self, node_ids: &[i32]) {
+    self.0.write().retain(|id, _| node_ids.contains(id));
+  }
+
+  /// Called when the controller connects to the node, old nodes report no capabilities
+  pub(crate) fn set_connected(&self, node_id: i32, capabilities: Option<NodeCapabilities>) {
+    self.0.write().insert(
+      node_id,
+      NodeRouting {
+        game_routing: capabilities
+          .as_ref()
+          .map(|v| v.game_routing)
+          .unwrap_or(false),
+        two_phase_create: capabilities
+          .as_ref()
+          .map(|v| v.two_phase_create)
+          .unwrap_or(false),
+        max_games: capabilities.as_ref().map(|v| v.max_games).unwrap_or(0),
+        busy_until: None,
+        stats: None,
+      },
+    );
+  }
+
+  pub(crate) fn set_stats(&self, node_id: i32, stats: PacketNodeRoutingStats) {
+    if let Some(entry) = self.0.write().get_mut(&node_id) {
+      let stats = NodeRoutingStats::from(stats);
+      // a game ended since the node rejected one
+      if entry.max_games > 0 && stats.games < entry.max_games {
+        entry.busy_until.take();
+      }
+      entry.stats = Some(stats);
+    }
+  }
+
+  /// Called when the node accepted a config update
+  pub(crate) fn set_max_games(&self, node_id: i32, max_games: u32) {
+    if let Some(entry) = self.0.write().get_mut(&node_id) {
+      entry.max_games = max_games;
+      // the node may have room now
+      entry.busy_until.take();
+    }
+  }
+
+  /// Called when the node rejected a game for being at capacity
+  pub(crate) fn set_busy(&self, node_id: i32, estimated_wait: Duration) {
+    if let Some(entry) = self.0.write().get_mut(&node_id) {
+      entry.busy_until = chrono::Duration::from_std(estimated_wait)
+        .ok()
+        .map(|wait| Utc::now() + wait);
+    }
+  }
+
+  /// Stats of a disconnected node are stale
+  pub(crate) fn clear_stats(&self, node_id: i32) {
+    if let Some(entry) = self.0.write().get_mut(&node_id) {
+      entry.stats.take();
+      entry.busy_until.take();
+    }
+  }
+}
+
+#[test]
+fn test_node_routing_table() {
+  let table = NodeRoutingTable::default();
+  table.set_stats(1, Default::default());
+  assert_eq!(table.get(1), None);
+
+  table.set_connected(
+    1,
+    Some(NodeCapabilities {
+      game_routing: true,
+      routing_stats_interval_secs: 30,
+      two_phase_create: true,
+      max_games: 4,
+    }),
+  );
+  table.set_connected(2, None);
+  table.set_stats(
+    1,
+    PacketNodeRoutingStats {
+      games: 3,
+      accepted: 10,
+      ..Default::default()
+    },
+  );
+  let routing = table.get(1).unwrap();
+  assert!(routing.game_routing);
+  assert!(routing.two_phase_create);
+  assert_eq!(
+    routing.stats.as_ref().map(|v| (v.games, v.accepted)),
+    Some((3, 10))
+  );
+  assert!(!table.get(2).unwrap().game_routing);
+  assert!(!table.get(2).unwrap().two_phase_create);
+
+  let busy = |node_id| table.get(node_id).unwrap().busy_for(Utc::now());
+  assert_eq!(busy(1), None);
+  table.set_busy(1, Duration::from_secs(60));
+  let wait = busy(1).unwrap();
+  assert!(wait > Duration::from_secs(50) && wait <= Duration::from_secs(60));
+  table.set_stats(
+    1,
+    PacketNodeRoutingStats {
+      games: 4,
+      ..Default::default()
+    },
+  );
+  table.set_busy(1, Duration::from_secs(0));
+  assert_eq!(busy(1), Some(Duration::from_secs(0)));
+  table.set_stats(
+    1,
+    PacketNodeRoutingStats {
+      games: 3,
+      ..Default::default()
+    },
+  );
+  assert_eq!(busy(1), None);
+  table.set_max_games(1, 3);
+  assert_eq!(busy(1), Some(Duration::from_secs(0)));
+  table.set_max_games(1, 0);
+  assert_eq!(busy(1), None);
+
+  table.clear_stats(1);
+  assert_eq!(table.get(1).unwrap().stats, None);
+
+  table.retain_nodes(&[2]);
+  assert_eq!(
+    table.snapshot().keys().cloned().collect::<Vec<_>>(),
+    vec![2]
+  );
+}
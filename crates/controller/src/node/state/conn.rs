@@ -2,7 +2,11 @@ use crate::error::*;
 use crate::game::state::GameRegistry;
 use crate::game::state::{GameSlotClientStatusUpdate, GameStatusUpdate};
 use crate::game::{Game, GameStatus};
+use crate::node::config::NodeConfig;
+use crate::node::routing::NodeRoutingTable;
 use crate::node::state::request::{CreatedGameInfo, NodeRequestActor, NodeRequestExt};
+use crate::node::state::start_queue::{self, StartOwner, StartQueue, FLO_NODE_START_QUEUE_SIZE};
+use crate::node::version::NodeVersionMatrix;
 use crate::node::{NodeConnConfig, PlayerLeaveResponse};
 use crate::state::ActorMapExt;
 use backoff::backoff::Backoff;
@@ -16,12 +20,13 @@ use s2_grpc_utils::{S2ProtoEnum, S2ProtoUnpack};
 use std::collections::BTreeMap;
 
 use crate::game::state::registry::Remove;
+use crate::game::state::save::GameSaved;
 use crate::player::PlayerBanType;
 use flo_net::ping::{PingMsg, PingStream};
 use futures::StreamExt;
 use std::net::{Ipv4Addr, SocketAddrV4};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::sleep;
 use tracing_futures::Instrument;
 
@@ -33,16 +38,29 @@ pub struct NodeConnActor {
   status: NodeConnStatus,
   request_actor: Option<Owner<NodeRequestActor>>,
   game_reg_addr: Addr<GameRegistry>,
+  versions: NodeVersionMatrix,
+  routing: NodeRoutingTable,
+  starts: StartQueue<QueuedCreateGame>,
+  starts_in_flight: usize,
 }
 
 impl NodeConnActor {
-  pub fn new(config: NodeConnConfig, game_reg_addr: Addr<GameRegistry>) -> Self {
+  pub fn new(
+    config: NodeConnConfig,
+    game_reg_addr: Addr<GameRegistry>,
+    versions: NodeVersionMatrix,
+    routing: NodeRoutingTable,
+  ) -> Self {
     Self {
       config,
       status: NodeConnStatus::Connecting,
       reconnect_backoff: None,
       request_actor: None,
       game_reg_addr,
+      versions,
+      routing,
+      starts: StartQueue::default(),
+      starts_in_flight: 0,
     }
   }
 
@@ -103,7 +121,7 @@ impl NodeConnActor {
     ip: Ipv4Addr,
     port: u16,
     secret: &str,
-  ) -> Result<FloStream, NodeConnectError> {
+  ) -> Result<(FloStream, PacketControllerConnectAccept), NodeConnectError> {
     let addr = SocketAddrV4::new(ip, port);
     let mut stream = FloStream::connect(addr).await?;
 
@@ -116,10 +134,18 @@ impl NodeConnActor {
 
     let res = stream.recv_frame().await?;
 
+    let accept;
     flo_net::try_flo_packet! {
       res => {
         packet: PacketControllerConnectAccept => {
-          tracing::info!(node_id, "node connected: version = {:?}", packet.version);
+          tracing::info!(
+            node_id,
+            "node connected: version = {:?}, war3 versions = {:?}, capabilities = {:?}",
+            packet.version,
+            packet.war3_versions,
+            packet.capabilities
+          );
+          accept = packet;
         }
         packet: PacketControllerConnectReject => {
           tracing::error!(node_id, "node connect rejected: reason = {:?}", packet.reason());
@@ -131,7 +157,7 @@ impl NodeConnActor {
       }
     };
 
-    Ok(stream)
+    Ok((stream, accept))
   }
 
   async fn stream_worker(addr: Addr<Self>, mut rx: mpsc::Receiver<Frame>, mut stream: FloStream) {
@@ -220,7 +246,11 @@ impl Handler<Connect> for NodeConnActor {
     let node_id = self.config.id;
     let secret = self.config.secret.clone();
     let stream = match Self::connect(node_id, ip, port, &secret).await {
-      Ok(stream) => stream,
+      Ok((stream, accept)) => {
+        self.versions.set_reported(node_id, accept.war3_versions);
+        self.routing.set_connected(node_id, accept.capabilities);
+        stream
+      }
       Err(NodeConnectError::Retry(err)) => {
         tracing::error!(node_id, "error: {}", err);
         self.schedule_reconnect(ctx);
@@ -251,6 +281,7 @@ impl Message for Disconnected {
 #[async_trait]
 impl Handler<Disconnected> for NodeConnActor {
   async fn handle(&mut self, ctx: &mut Context<Self>, _: Disconnected) {
+    self.routing.clear_stats(self.config.id);
     self.schedule_reconnect(ctx);
   }
 }
@@ -274,6 +305,8 @@ impl Handler<IncomingFrame> for NodeConnActor {
       Response(RequestDone),
       GameSlotClientStatusUpdate(GameSlotClientStatusUpdate),
       GameStatusUpdate(Vec<GameStatusUpdate>),
+      RoutingStats(PacketNodeRoutingStats),
+      GameSaved(PacketNodeGameSaved),
     }
 
     let parsed = flo_net::try_flo_packet! {
@@ -289,9 +322,43 @@ impl Handler<IncomingFrame> for NodeConnActor {
         }
         packet: PacketControllerCreateGameReject => {
           let game_id = packet.game_id;
+          let err = match packet.reason() {
+            ControllerCreateGameRejectReason::NodeBusy => {
+              let estimated_wait = Duration::from_secs(packet.estimated_wait_secs as u64);
+              tracing::warn!(
+                node_id = self.config.id,
+                game_id,
+                "node busy, estimated wait: {:?}",
+                estimated_wait
+              );
+              self.routing.set_busy(self.config.id, estimated_wait);
+              Error::NodeBusy {
+                node_id: self.config.id,
+                estimated_wait,
+              }
+            }
+            reason => Error::GameCreateReject(reason),
+          };
           Parsed::Response(
             RequestDone::new(
               RequestId::CreateGame(game_id),
+              Err(err)
+            )
+          )
+        }
+        packet: PacketControllerCommitGameAccept => {
+          Parsed::Response(
+            RequestDone::new(
+              RequestId::CommitGame(packet.game_id),
+              Ok(Response::GameCommitted),
+            )
+          )
+        }
+        packet: PacketControllerCommitGameReject => {
+          let game_id = packet.game_id;
+          Parsed::Response(
+            RequestDone::new(
+              RequestId::CommitGame(game_id),
               Err(Error::GameCreateReject(packet.reason()))
             )
           )
@@ -322,6 +389,22 @@ impl Handler<IncomingFrame> for NodeConnActor {
             )
           )
         }
+        packet: PacketControllerUpdateNodeConfigAccept => {
+          Parsed::Response(
+            RequestDone::new(
+              RequestId::UpdateConfig,
+              Ok(Response::ConfigUpdated(packet.config.unwrap_or_default().into())),
+            )
+          )
+        }
+        packet: PacketControllerUpdateNodeConfigReject => {
+          Parsed::Response(
+            RequestDone::new(
+              RequestId::UpdateConfig,
+              Err(Error::NodeConfigRejected(packet.reason)),
+            )
+          )
+        }
         packet: PacketClientUpdateSlotClientStatus => {
           Parsed::GameSlotClientStatusUpdate(S2ProtoUnpack::unpack(packet)?)
         }
@@ -331,6 +414,12 @@ impl Handler<IncomingFrame> for NodeConnActor {
         packet: PacketNodeGameStatusUpdateBulk => {
           Parsed::GameStatusUpdate(packet.games.into_iter().map(Into::into).collect())
         }
+        packet: PacketNodeRoutingStats => {
+          Parsed::RoutingStats(packet)
+        }
+        packet: PacketNodeGameSaved => {
+          Parsed::GameSaved(packet)
+        }
       }
     };
 
@@ -350,6 +439,18 @@ impl Handler<IncomingFrame> for NodeConnActor {
           }
         });
       }
+      Parsed::RoutingStats(stats) => {
+        self.routing.set_stats(self.config.id, stats);
+      }
+      Parsed::GameSaved(packet) => {
+        let addr = self.game_reg_addr.clone();
+        ctx.spawn(async move {
+          let game_id = packet.game_id;
+          if let Err(err) = addr.send_to(game_id, GameSaved(packet)).await {
+            tracing::warn!(game_id, "GameSaved: {}", err);
+          }
+        });
+      }
       Parsed::GameStatusUpdate(messages) => {
         let addr = self.game_reg_addr.clone();
         ctx.spawn(async move {
@@ -384,6 +485,9 @@ impl Handler<IncomingFrame> for NodeConnActor {
 pub struct NodeCreateGame {
   pub game: Game,
   pub ban_list_map: BTreeMap<i32, Vec<PlayerBanType>>,
+  pub owner: StartOwner,
+  /// Receives the queue position while the request waits for the node
+  pub queue_tx: Option<mpsc::UnboundedSender<usize>>,
 }
 
 impl Message for NodeCreateGame {
@@ -395,8 +499,141 @@ impl Handler<NodeCreateGame> for NodeConnActor {
   async fn handle(
     &mut self,
     ctx: &mut Context<Self>,
-    NodeCreateGame { game, ban_list_map }: NodeCreateGame,
+    NodeCreateGame {
+      game,
+      ban_list_map,
+      owner,
+      queue_tx,
+    }: NodeCreateGame,
   ) -> Result<FutureReply<Result<CreatedGameInfo>>> {
+    if self.request_actor.is_none() {
+      return Err(Error::NodeNotReady);
+    }
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let (tx, rx) = FutureReply::channel();
+    ctx.spawn(async move {
+      tx.send(reply_rx.await.unwrap_or(Err(Error::TaskCancelled)))
+        .ok();
+    });
+    // fails fast until the time the node expected room when it last rejected a game
+    let busy_for = self
+      .routing
+      .get(self.config.id)
+      .and_then(|routing| routing.busy_until)
+      .and_then(|until| (until - chrono::Utc::now()).to_std().ok());
+    if let Some(estimated_wait) = busy_for {
+      tracing::warn!(node_id = self.config.id, game_id = game.id, "node busy");
+      reply_tx
+        .send(Err(Error::NodeBusy {
+          node_id: self.config.id,
+          estimated_wait,
+        }))
+        .ok();
+      return Ok(rx);
+    }
+    if self.starts.len() >= *FLO_NODE_START_QUEUE_SIZE {
+      tracing::warn!(
+        node_id = self.config.id,
+        game_id = game.id,
+        "start queue full"
+      );
+      reply_tx.send(Err(Error::NodeStartQueueFull)).ok();
+      return Ok(rx);
+    }
+    self.starts.push(
+      owner,
+      QueuedCreateGame {
+        game,
+        ban_list_map,
+        reply_tx,
+        queue_tx,
+        position: 0,
+      },
+    );
+    self.dispatch_starts(ctx);
+    Ok(rx)
+  }
+}
+
+struct QueuedCreateGame {
+  game: Game,
+  ban_list_map: BTreeMap<i32, Vec<PlayerBanType>>,
+  reply_tx: oneshot::Sender<Result<CreatedGameInfo>>,
+  queue_tx: Option<mpsc::UnboundedSender<usize>>,
+  /// Last position sent to `queue_tx`
+  position: usize,
+}
+
+impl NodeConnActor {
+  fn dispatch_starts(&mut self, ctx: &mut Context<Self>) {
+    let routing = self.routing.get(self.config.id);
+    let concurrency = start_queue::concurrency(routing.as_ref());
+    let reserve = routing.map(|v| v.two_phase_create).unwrap_or(false);
+
+    while self.starts_in_flight < concurrency {
+      let item = if let Some(item) = self.starts.pop() {
+        item
+      } else {
+        break;
+      };
+      let addr = if let Some(addr) = self.request_actor.as_ref().map(|v| v.addr()) {
+        addr
+      } else {
+        item.reply_tx.send(Err(Error::NodeNotReady)).ok();
+        continue;
+      };
+      self.starts_in_flight += 1;
+      let conn_addr = ctx.addr();
+      ctx.spawn(async move {
+        let res = addr
+          .create_game(item.game, item.ban_list_map, reserve)
+          .await;
+        item.reply_tx.send(res).ok();
+        conn_addr.send(CreateGameDone).await.ok();
+      });
+    }
+
+    self.starts.for_each_position(|item, position| {
+      if item.position != position {
+        item.position = position;
+        if let Some(tx) = item.queue_tx.as_ref() {
+          tx.send(position).ok();
+        }
+      }
+    });
+  }
+}
+
+struct CreateGameDone;
+
+impl Message for CreateGameDone {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<CreateGameDone> for NodeConnActor {
+  async fn handle(&mut self, ctx: &mut Context<Self>, _: CreateGameDone) {
+    self.starts_in_flight = self.starts_in_flight.saturating_sub(1);
+    self.dispatch_starts(ctx);
+  }
+}
+
+/// Commits a game reserved by `NodeCreateGame`
+pub struct NodeCommitGame {
+  pub game_id: i32,
+}
+
+impl Message for NodeCommitGame {
+  type Result = Result<FutureReply<Result<()>>>;
+}
+
+#[async_trait]
+impl Handler<NodeCommitGame> for NodeConnActor {
+  async fn handle(
+    &mut self,
+    ctx: &mut Context<Self>,
+    NodeCommitGame { game_id }: NodeCommitGame,
+  ) -> Result<FutureReply<Result<()>>> {
     let addr = self
       .request_actor
       .as_ref()
@@ -404,7 +641,7 @@ impl Handler<NodeCreateGame> for NodeConnActor {
       .ok_or_else(|| Error::NodeNotReady)?;
     let (tx, rx) = FutureReply::channel();
     ctx.spawn(async move {
-      tx.send(addr.create_game(game, ban_list_map).await).ok();
+      tx.send(addr.commit_game(game_id).await).ok();
     });
     Ok(rx)
   }
@@ -440,6 +677,102 @@ impl Handler<NodePlayerLeave> for NodeConnActor {
   }
 }
 
+pub struct NodeGameCommand {
+  pub game_id: i32,
+  pub player_id: i32,
+  pub command: flo_net::proto::flo_common::GameCommand,
+}
+
+impl Message for NodeGameCommand {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<NodeGameCommand> for NodeConnActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    NodeGameCommand {
+      game_id,
+      player_id,
+      command,
+    }: NodeGameCommand,
+  ) -> Result<()> {
+    let addr = self
+      .request_actor
+      .as_ref()
+      .map(|v| v.addr())
+      .ok_or_else(|| Error::NodeNotReady)?;
+    addr.game_command(game_id, player_id, command).await
+  }
+}
+
+pub struct NodeGameSurrender {
+  pub game_id: i32,
+  pub player_ids: Vec<i32>,
+}
+
+impl Message for NodeGameSurrender {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<NodeGameSurrender> for NodeConnActor {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    NodeGameSurrender {
+      game_id,
+      player_ids,
+    }: NodeGameSurrender,
+  ) -> Result<()> {
+    let addr = self
+      .request_actor
+      .as_ref()
+      .map(|v| v.addr())
+      .ok_or_else(|| Error::NodeNotReady)?;
+    addr.game_surrender(game_id, player_ids).await
+  }
+}
+
+/// Pushes config changes to the node, the node persists and applies them without a restart
+pub struct NodeUpdateConfig {
+  pub config: NodeConfig,
+}
+
+impl Message for NodeUpdateConfig {
+  type Result = Result<FutureReply<Result<NodeConfig>>>;
+}
+
+#[async_trait]
+impl Handler<NodeUpdateConfig> for NodeConnActor {
+  async fn handle(
+    &mut self,
+    ctx: &mut Context<Self>,
+    NodeUpdateConfig { config }: NodeUpdateConfig,
+  ) -> Result<FutureReply<Result<NodeConfig>>> {
+    let addr = self
+      .request_actor
+      .as_ref()
+      .map(|v| v.addr())
+      .ok_or_else(|| Error::NodeNotReady)?;
+    let node_id = self.config.id;
+    let routing = self.routing.clone();
+    let (tx, rx) = FutureReply::channel();
+    ctx.spawn(async move {
+      let res = addr.update_config(config).await;
+      if let Ok(ref config) = res {
+        tracing::info!(node_id, "node config updated: {:?}", config);
+        if let Some(max_games) = config.max_games {
+          routing.set_max_games(node_id, max_games);
+        }
+      }
+      tx.send(res).ok();
+    });
+    Ok(rx)
+  }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum NodeConnStatus {
   Connecting,
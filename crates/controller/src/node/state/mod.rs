@@ -1,9 +1,12 @@
 pub mod conn;
 pub mod request;
+pub mod start_queue;
 
 use crate::db::ExecutorRef;
 use crate::error::*;
 use crate::game::state::GameRegistry;
+use crate::node::routing::{NodeRouting, NodeRoutingTable};
+use crate::node::version::NodeVersionMatrix;
 use crate::node::{Node, NodeConnConfig};
 use crate::player::state::sender::PlayerRegistryHandle;
 use crate::state::{Data, GetActorEntry, Reload};
@@ -21,6 +24,8 @@ pub struct NodeRegistry {
   player_reg_handle: PlayerRegistryHandle,
   map: BTreeMap<i32, Owner<NodeConnActor>>,
   nodes_snapshot: ArcSwap<Vec<Node>>,
+  versions: NodeVersionMatrix,
+  routing: NodeRoutingTable,
 }
 
 #[async_trait]
@@ -36,6 +41,8 @@ impl Service<Data> for NodeRegistry {
       player_reg_handle: PlayerRegistryHandle::from(player_reg_addr),
       map: BTreeMap::new(),
       nodes_snapshot: ArcSwap::new(Arc::new(vec![])),
+      versions: registry.data().node_versions.clone(),
+      routing: NodeRoutingTable::default(),
     })
   }
 }
@@ -53,12 +60,19 @@ impl NodeRegistry {
   async fn init(&mut self) -> Result<()> {
     let game_reg_addr = self.game_reg_addr.resolve().await?;
     let nodes = self.load_snapshot().await?;
+    self.versions.set_nodes(&nodes);
 
     for node in &nodes {
       tracing::debug!(node_id = node.id, "added");
       self.map.insert(
         node.id,
-        NodeConnActor::new(node.into(), game_reg_addr.clone()).start(),
+        NodeConnActor::new(
+          node.into(),
+          game_reg_addr.clone(),
+          self.versions.clone(),
+          self.routing.clone(),
+        )
+        .start(),
       );
     }
 
@@ -95,10 +109,12 @@ impl Handler<Reload> for NodeRegistry {
     use s2_grpc_utils::S2ProtoPack;
 
     let nodes = self.load_snapshot().await?;
+    self.versions.set_nodes(&nodes);
 
     let mut broadcast_frames = vec![];
 
     let new_ids: Vec<i32> = nodes.iter().map(|c| c.id).collect();
+    self.routing.retain_nodes(&new_ids);
     {
       for id in self.map.keys().cloned().collect::<Vec<i32>>() {
         if !new_ids.contains(&id) {
@@ -114,7 +130,13 @@ impl Handler<Reload> for NodeRegistry {
         tracing::info!(id = config.id, "node added: {}", config.addr);
         self.map.insert(
           config.id,
-          NodeConnActor::new(config, self.game_reg_addr.resolve().await?).start(),
+          NodeConnActor::new(
+            config,
+            self.game_reg_addr.resolve().await?,
+            self.versions.clone(),
+            self.routing.clone(),
+          )
+          .start(),
         );
         broadcast_frames.push(
           PacketAddNode {
@@ -150,3 +172,52 @@ impl Handler<ListNode> for NodeRegistry {
     Vec::<_>::clone(&self.nodes_snapshot.load())
   }
 }
+
+/// Nodes supporting a Warcraft III version, all nodes if `war3_version` is `None`
+pub struct ListCompatibleNodes {
+  pub war3_version: Option<String>,
+}
+
+impl Message for ListCompatibleNodes {
+  type Result = Vec<Node>;
+}
+
+#[async_trait]
+impl Handler<ListCompatibleNodes> for NodeRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    ListCompatibleNodes { war3_version }: ListCompatibleNodes,
+  ) -> Vec<Node> {
+    let war3_version = match war3_version {
+      Some(v) => v,
+      None => return Vec::<_>::clone(&self.nodes_snapshot.load()),
+    };
+    let ids = self.versions.compatible_node_ids(&war3_version);
+    self
+      .nodes_snapshot
+      .load()
+      .iter()
+      .filter(|node| ids.contains(&node.id))
+      .cloned()
+      .collect()
+  }
+}
+
+/// Client connection routing capability and stats of connected nodes
+pub struct ListNodeRouting;
+
+impl Message for ListNodeRouting {
+  type Result = BTreeMap<i32, NodeRouting>;
+}
+
+#[async_trait]
+impl Handler<ListNodeRouting> for NodeRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    _: ListNodeRouting,
+  ) -> BTreeMap<i32, NodeRouting> {
+    self.routing.snapshot()
+  }
+}
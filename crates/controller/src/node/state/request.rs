@@ -1,5 +1,6 @@
 use crate::error::*;
 use crate::game::{Game, SlotClientStatus, SlotStatus};
+use crate::node::config::NodeConfig;
 use crate::node::PlayerToken;
 use crate::player::PlayerBanType;
 use flo_net::packet::*;
@@ -53,13 +54,17 @@ impl Drop for PendingRequest {
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum RequestId {
   CreateGame(i32),
+  CommitGame(i32),
   PlayerLeave(PlayerLeaveRequestId),
+  UpdateConfig,
 }
 
 #[derive(Debug)]
 pub enum Response {
   GameCreated(CreatedGameInfo),
+  GameCommitted,
   PlayerLeave(PlayerLeaveResponse),
+  ConfigUpdated(NodeConfig),
 }
 
 #[derive(Debug, S2ProtoUnpack)]
@@ -67,6 +72,8 @@ pub enum Response {
 pub struct CreatedGameInfo {
   pub game_id: i32,
   pub player_tokens: Vec<PlayerToken>,
+  /// The game has to be committed with `commit_game`
+  pub reserved: bool,
 }
 
 impl S2ProtoUnpack<flo_net::proto::flo_node::PlayerToken> for PlayerToken {
@@ -175,6 +182,24 @@ impl Handler<Request> for NodeRequestActor {
   }
 }
 
+// frames that don't expect a response
+struct Forward(Frame);
+
+impl Message for Forward {
+  type Result = Result<()>;
+}
+
+#[async_trait]
+impl Handler<Forward> for NodeRequestActor {
+  async fn handle(&mut self, _: &mut Context<Self>, Forward(frame): Forward) -> Result<()> {
+    self
+      .frame_tx
+      .send(frame)
+      .await
+      .map_err(|_| Error::NodeRequestCancelled)
+  }
+}
+
 async fn request_callback(addr: &Addr<NodeRequestActor>, id: RequestId, result: Result<Response>) {
   if addr.notify(RequestDone { id, result }).await.is_err() {
     tracing::debug!("RequestDone: cancelled: request_id = {:?}", id);
@@ -231,8 +256,18 @@ pub trait NodeRequestExt {
     &self,
     game: Game,
     ban_list_map: BTreeMap<i32, Vec<PlayerBanType>>,
+    reserve: bool,
   ) -> Result<CreatedGameInfo>;
+  async fn commit_game(&self, game_id: i32) -> Result<()>;
   async fn player_force_leave(&self, game_id: i32, player_id: i32) -> Result<PlayerLeaveResponse>;
+  async fn game_command(
+    &self,
+    game_id: i32,
+    player_id: i32,
+    command: flo_net::proto::flo_common::GameCommand,
+  ) -> Result<()>;
+  async fn game_surrender(&self, game_id: i32, player_ids: Vec<i32>) -> Result<()>;
+  async fn update_config(&self, config: NodeConfig) -> Result<NodeConfig>;
 }
 
 #[async_trait]
@@ -241,6 +276,7 @@ impl NodeRequestExt for Addr<NodeRequestActor> {
     &self,
     game: Game,
     mut ban_list_map: BTreeMap<i32, Vec<PlayerBanType>>,
+    reserve: bool,
   ) -> Result<CreatedGameInfo> {
     let game_id = game.id;
 
@@ -280,6 +316,7 @@ impl NodeRequestExt for Addr<NodeRequestActor> {
         slots,
         status: Default::default(),
       }),
+      reserve,
     };
 
     let req = Request {
@@ -297,6 +334,22 @@ impl NodeRequestExt for Addr<NodeRequestActor> {
     }
   }
 
+  async fn commit_game(&self, game_id: i32) -> Result<()> {
+    let req = Request {
+      id: RequestId::CommitGame(game_id),
+      frame: PacketControllerCommitGame { game_id }.encode_as_frame()?,
+    };
+
+    let res = self.send(req).await??;
+    match res.await? {
+      Response::GameCommitted => Ok(()),
+      other => {
+        tracing::error!(game_id, "unexpected node response: {:?}", other);
+        Err(Error::NodeResponseUnexpected)
+      }
+    }
+  }
+
   async fn player_force_leave(&self, game_id: i32, player_id: i32) -> Result<PlayerLeaveResponse> {
     let req_id = RequestId::PlayerLeave(PlayerLeaveRequestId { game_id, player_id });
 
@@ -322,4 +375,46 @@ impl NodeRequestExt for Addr<NodeRequestActor> {
       }
     }
   }
+
+  async fn game_command(
+    &self,
+    game_id: i32,
+    player_id: i32,
+    command: flo_net::proto::flo_common::GameCommand,
+  ) -> Result<()> {
+    let mut pkt = PacketControllerGameCommand {
+      game_id,
+      player_id,
+      ..Default::default()
+    };
+    pkt.set_command(command);
+    self.send(Forward(pkt.encode_as_frame()?)).await?
+  }
+
+  async fn game_surrender(&self, game_id: i32, player_ids: Vec<i32>) -> Result<()> {
+    let pkt = PacketControllerGameSurrender {
+      game_id,
+      player_ids,
+    };
+    self.send(Forward(pkt.encode_as_frame()?)).await?
+  }
+
+  async fn update_config(&self, config: NodeConfig) -> Result<NodeConfig> {
+    let pkt = PacketControllerUpdateNodeConfig {
+      config: Some(config.into()),
+    };
+    let req = Request {
+      id: RequestId::UpdateConfig,
+      frame: pkt.encode_as_frame()?,
+    };
+
+    let res = self.send(req).await??;
+    match res.await? {
+      Response::ConfigUpdated(config) => Ok(config),
+      other => {
+        tracing::error!("unexpected node response: {:?}", other);
+        Err(Error::NodeResponseUnexpected)
+      }
+    }
+  }
 }
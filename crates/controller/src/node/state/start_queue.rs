@@ -0,0 +1,170 @@
+//! Throttling of create game requests sent to a node.
+//!
+//! Mass starts (e.g. a tournament round) would otherwise send hundreds of create requests
+//! at once and time them out. A node gets at most `FLO_NODE_START_CONCURRENCY` requests
+//! at a time, one at a time while it reports `FLO_NODE_START_MAX_PENDING_TOKENS` or more
+//! player tokens pending, i.e. many games are already waiting for their players to connect.
+//! Requests over the limit are queued, owners take turns so a single API client
+//! can't starve the others.
+
+use crate::node::routing::NodeRouting;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, VecDeque};
+
+static FLO_NODE_START_CONCURRENCY: Lazy<usize> = Lazy::new(|| {
+  std::env::var("FLO_NODE_START_CONCURRENCY")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .filter(|v| *v > 0)
+    .unwrap_or(8)
+});
+
+static FLO_NODE_START_MAX_PENDING_TOKENS: Lazy<u32> = Lazy::new(|| {
+  std::env::var("FLO_NODE_START_MAX_PENDING_TOKENS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(500)
+});
+
+/// Requests queued over this size are rejected
+pub static FLO_NODE_START_QUEUE_SIZE: Lazy<usize> = Lazy::new(|| {
+  std::env::var("FLO_NODE_START_QUEUE_SIZE")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(1000)
+});
+
+/// Number of create requests a node can process at the same time
+pub fn concurrency(routing: Option<&NodeRouting>) -> usize {
+  let pending_tokens = routing
+    .and_then(|v| v.stats.as_ref())
+    .map(|v| v.pending_player_tokens)
+    .unwrap_or(0);
+  if pending_tokens >= *FLO_NODE_START_MAX_PENDING_TOKENS {
+    1
+  } else {
+    *FLO_NODE_START_CONCURRENCY
+  }
+}
+
+/// Requests of the same owner are processed in order, owners take turns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum StartOwner {
+  ApiClient(i32),
+  Player(i32),
+}
+
+#[derive(Debug)]
+pub struct StartQueue<T> {
+  queues: BTreeMap<StartOwner, VecDeque<T>>,
+  /// Owners with queued requests, the front one is next
+  turns: VecDeque<StartOwner>,
+  len: usize,
+}
+
+impl<T> Default for StartQueue<T> {
+  fn default() -> Self {
+    Self {
+      queues: BTreeMap::new(),
+      turns: VecDeque::new(),
+      len: 0,
+    }
+  }
+}
+
+impl<T> StartQueue<T> {
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  pub fn push(&mut self, owner: StartOwner, item: T) {
+    let queue = self.queues.entry(owner).or_default();
+    if queue.is_empty() {
+      self.turns.push_back(owner);
+    }
+    queue.push_back(item);
+    self.len += 1;
+  }
+
+  pub fn pop(&mut self) -> Option<T> {
+    let owner = self.turns.pop_front()?;
+    let queue = self.queues.get_mut(&owner)?;
+    let item = queue.pop_front()?;
+    if queue.is_empty() {
+      self.queues.remove(&owner);
+    } else {
+      self.turns.push_back(owner);
+    }
+    self.len -= 1;
+    Some(item)
+  }
+
+  /// Calls `f` with every item and its position, 1 for the item `pop` returns next
+  pub fn for_each_position<F>(&mut self, mut f: F)
+  where
+    F: FnMut(&mut T, usize),
+  {
+    let lens: Vec<usize> = self
+      .turns
+      .iter()
+      .map(|owner| self.queues.get(owner).map(|v| v.len()).unwrap_or(0))
+      .collect();
+    for (turn, owner) in self.turns.iter().enumerate() {
+      let queue = match self.queues.get_mut(owner) {
+        Some(queue) => queue,
+        None => continue,
+      };
+      for (idx, item) in queue.iter_mut().enumerate() {
+        // every owner gets `idx` turns before this item,
+        // the owners before this one get one more
+        let ahead: usize = lens
+          .iter()
+          .enumerate()
+          .map(|(other, len)| std::cmp::min(*len, idx + if other < turn { 1 } else { 0 }))
+          .sum();
+        f(item, ahead + 1);
+      }
+    }
+  }
+}
+
+#[test]
+fn test_start_queue() {
+  let mut queue = StartQueue::default();
+  let bot = StartOwner::ApiClient(1);
+  for id in 0..3 {
+    queue.push(bot, id);
+  }
+  queue.push(StartOwner::Player(7), 10);
+  queue.push(StartOwner::ApiClient(2), 20);
+  queue.push(StartOwner::ApiClient(2), 21);
+  assert_eq!(queue.len(), 6);
+
+  let mut positions = vec![];
+  queue.for_each_position(|item, position| positions.push((*item, position)));
+  positions.sort_by_key(|(_, position)| *position);
+  assert_eq!(
+    positions,
+    vec![(0, 1), (10, 2), (20, 3), (1, 4), (21, 5), (2, 6)]
+  );
+
+  let mut order = vec![];
+  while let Some(item) = queue.pop() {
+    order.push(item);
+  }
+  assert_eq!(order, vec![0, 10, 20, 1, 21, 2]);
+  assert!(queue.is_empty());
+
+  // an owner joining later waits for one turn of each queued owner
+  queue.push(bot, 0);
+  queue.push(bot, 1);
+  assert_eq!(queue.pop(), Some(0));
+  queue.push(StartOwner::Player(7), 10);
+  assert_eq!(queue.pop(), Some(1));
+  assert_eq!(queue.pop(), Some(10));
+  assert_eq!(queue.pop(), None);
+}
@@ -20,6 +20,9 @@ pub struct Node {
   pub country_id: String,
   #[s2_grpc(skip_pack)]
   pub disabled: bool,
+  /// Supported Warcraft III versions set by admins, overrides the versions reported by the node
+  #[s2_grpc(skip_pack)]
+  pub war3_versions: Option<Vec<String>>,
 }
 
 pub type NodeRefColumns = (
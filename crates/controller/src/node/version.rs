@@ -0,0 +1,188 @@
+use crate::error::{Error, Result};
+use crate::node::Node;
+use flo_net::proto::flo_connect::PacketWar3VersionMismatch;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Warcraft III version new games are created for, e.g. `1.26` or `1.32.10`.
+/// Game creation and node selection only accept nodes supporting it.
+pub static GAME_TARGET_VERSION: Lazy<Option<String>> = Lazy::new(|| {
+  std::env::var("FLO_GAME_TARGET_VERSION")
+    .ok()
+    .map(|v| v.trim().to_string())
+    .filter(|v| !v.is_empty())
+});
+
+/// Warcraft III versions supported by a node
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeVersions {
+  /// Set by admins in the database, overrides `reported`
+  pub configured: Option<Vec<String>>,
+  /// Reported by the node when the controller connects
+  pub reported: Option<Vec<String>>,
+}
+
+impl NodeVersions {
+  /// The effective version list, `None` if unknown
+  pub fn effective(&self) -> Option<&[String]> {
+    self
+      .configured
+      .as_ref()
+      .or_else(|| self.reported.as_ref())
+      .map(|v| v.as_slice())
+  }
+
+  /// Nodes that neither report nor have configured versions are assumed to support every version
+  pub fn supports(&self, version: &str) -> bool {
+    self
+      .effective()
+      .map(|list| list.iter().any(|pattern| version_matches(pattern, version)))
+      .unwrap_or(true)
+  }
+}
+
+/// `1.32` matches `1.32` and `1.32.10.18820` but not `1.321`
+pub fn version_matches(pattern: &str, version: &str) -> bool {
+  match version.strip_prefix(pattern) {
+    Some(rest) => rest.is_empty() || rest.starts_with('.'),
+    None => false,
+  }
+}
+
+/// Warning for players whose client reported a version that can't play games targeting `expected`,
+/// `None` if they match or either version is unknown
+pub fn version_mismatch(
+  reported: Option<&str>,
+  expected: Option<&str>,
+  game_id: Option<i32>,
+) -> Option<PacketWar3VersionMismatch> {
+  match (reported, expected) {
+    (Some(reported), Some(expected)) if !version_matches(expected, reported) => {
+      Some(PacketWar3VersionMismatch {
+        war3_version: reported.to_string(),
+        expected_version: expected.to_string(),
+        game_id,
+      })
+    }
+    _ => None,
+  }
+}
+
+/// Shared by the node registry, which keeps it up to date,
+/// and the game registry, which checks it when games are created or nodes selected
+#[derive(Debug, Clone, Default)]
+pub struct NodeVersionMatrix(Arc<RwLock<BTreeMap<i32, NodeVersions>>>);
+
+impl NodeVersionMatrix {
+  pub fn snapshot(&self) -> BTreeMap<i32, NodeVersions> {
+    self.0.read().clone()
+  }
+
+  pub fn get(&self, node_id: i32) -> Option<NodeVersions> {
+    self.0.read().get(&node_id).cloned()
+  }
+
+  /// Replaces configured versions from the node list, and drops removed nodes
+  pub(crate) fn set_nodes(&self, nodes: &[Node]) {
+    let mut guard = self.0.write();
+    guard.retain(|id, _| nodes.iter().any(|node| node.id == *id));
+    for node in nodes {
+      guard.entry(node.id).or_default().configured = node.war3_versions.clone();
+    }
+  }
+
+  pub(crate) fn set_reported(&self, node_id: i32, versions: Vec<String>) {
+    let reported = if versions.is_empty() {
+      None
+    } else {
+      Some(versions)
+    };
+    if let Some(entry) = self.0.write().get_mut(&node_id) {
+      entry.reported = reported;
+    }
+  }
+
+  pub fn supports(&self, node_id: i32, version: &str) -> bool {
+    self
+      .0
+      .read()
+      .get(&node_id)
+      .map(|v| v.supports(version))
+      .unwrap_or(false)
+  }
+
+  pub fn compatible_node_ids(&self, version: &str) -> Vec<i32> {
+    self
+      .0
+      .read()
+      .iter()
+      .filter(|(_, v)| v.supports(version))
+      .map(|(id, _)| *id)
+      .collect()
+  }
+
+  pub fn check_node(&self, node_id: i32, version: &str) -> Result<()> {
+    if self.supports(node_id, version) {
+      Ok(())
+    } else {
+      Err(Error::NodeVersionNotSupported {
+        node_id,
+        version: version.to_string(),
+      })
+    }
+  }
+
+  pub fn check_any(&self, version: &str) -> Result<()> {
+    if self.0.read().values().any(|v| v.supports(version)) {
+      Ok(())
+    } else {
+      Err(Error::NoNodeSupportsVersion(version.to_string()))
+    }
+  }
+}
+
+#[test]
+fn test_node_versions() {
+  assert!(version_matches("1.32", "1.32.10.18820"));
+  assert!(version_matches("1.32.10.18820", "1.32.10.18820"));
+  assert!(!version_matches("1.32", "1.321"));
+  assert!(!version_matches("1.32.10", "1.32"));
+  assert!(version_mismatch(Some("1.32.10.18820"), Some("1.32"), None).is_none());
+  assert!(version_mismatch(None, Some("1.32"), None).is_none());
+  assert!(version_mismatch(Some("1.26.0.6401"), None, None).is_none());
+  assert_eq!(
+    version_mismatch(Some("1.26.0.6401"), Some("1.32"), Some(1)),
+    Some(PacketWar3VersionMismatch {
+      war3_version: "1.26.0.6401".to_string(),
+      expected_version: "1.32".to_string(),
+      game_id: Some(1),
+    })
+  );
+
+  let matrix = NodeVersionMatrix::default();
+  {
+    let mut guard = matrix.0.write();
+    guard.insert(1, NodeVersions::default());
+    guard.insert(
+      2,
+      NodeVersions {
+        configured: Some(vec!["1.26".to_string()]),
+        reported: Some(vec!["1.32".to_string()]),
+      },
+    );
+    guard.insert(
+      3,
+      NodeVersions {
+        configured: None,
+        reported: Some(vec!["1.32".to_string()]),
+      },
+    );
+  }
+  assert_eq!(matrix.compatible_node_ids("1.32.10"), vec![1, 3]);
+  assert_eq!(matrix.compatible_node_ids("1.26.0"), vec![1, 2]);
+  assert!(matrix.check_node(2, "1.32.10").is_err());
+  assert!(matrix.check_node(4, "1.32.10").is_err());
+  assert!(matrix.check_any("1.31").is_ok());
+}
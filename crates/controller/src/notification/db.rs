@@ -0,0 +1,81 @@
+use crate::db::DbConn;
+use crate::error::*;
+use crate::notification::{NotificationChannel, NotificationTarget, MAX_SUBSCRIPTIONS_PER_PLAYER};
+use crate::schema::player_notification_subscription;
+use diesel::prelude::*;
+
+/// Fails with `NotificationSubscriptionLimit` if the player already has
+/// `MAX_SUBSCRIPTIONS_PER_PLAYER` other subscriptions
+pub fn subscribe(
+  conn: &DbConn,
+  player_id: i32,
+  channel: NotificationChannel,
+  target: &str,
+) -> Result<()> {
+  use player_notification_subscription::dsl;
+
+  #[derive(Insertable)]
+  #[table_name = "player_notification_subscription"]
+  struct Insert<'a> {
+    player_id: i32,
+    channel: NotificationChannel,
+    target: &'a str,
+  }
+
+  conn.transaction(|| -> Result<_> {
+    let targets: Vec<(NotificationChannel, String)> = player_notification_subscription::table
+      .select((dsl::channel, dsl::target))
+      .filter(dsl::player_id.eq(player_id))
+      .for_update()
+      .load(conn)?;
+    if targets.iter().any(|(c, t)| *c == channel && t == target) {
+      return Ok(());
+    }
+    if targets.len() >= MAX_SUBSCRIPTIONS_PER_PLAYER {
+      return Err(Error::NotificationSubscriptionLimit(
+        MAX_SUBSCRIPTIONS_PER_PLAYER,
+      ));
+    }
+
+    diesel::insert_into(player_notification_subscription::table)
+      .values(&Insert {
+        player_id,
+        channel,
+        target,
+      })
+      .on_conflict((dsl::player_id, dsl::channel, dsl::target))
+      .do_nothing()
+      .execute(conn)?;
+    Ok(())
+  })
+}
+
+pub fn unsubscribe(
+  conn: &DbConn,
+  player_id: i32,
+  channel: NotificationChannel,
+  target: &str,
+) -> Result<()> {
+  use player_notification_subscription::dsl;
+  diesel::delete(
+    player_notification_subscription::table.filter(
+      dsl::player_id
+        .eq(player_id)
+        .and(dsl::channel.eq(channel))
+        .and(dsl::target.eq(target)),
+    ),
+  )
+  .execute(conn)?;
+
+  Ok(())
+}
+
+pub fn get_targets(conn: &DbConn, player_ids: &[i32]) -> Result<Vec<NotificationTarget>> {
+  use diesel::pg::expression::dsl::any;
+  use player_notification_subscription::dsl;
+  player_notification_subscription::table
+    .select((dsl::player_id, dsl::channel, dsl::target))
+    .filter(dsl::player_id.eq(any(player_ids)))
+    .load(conn)
+    .map_err(Into::into)
+}
@@ -0,0 +1,293 @@
+pub mod db;
+mod provider;
+
+pub use provider::{HttpRelayProvider, NotificationProvider};
+
+use crate::error::*;
+use crate::job::{retry_delay, JobPayload};
+use crate::player::state::conn::FilterOfflinePlayers;
+use crate::player::state::PlayerRegistry;
+use crate::state::Data;
+use bs_diesel_utils::{BSDieselEnum, ExecutorRef};
+use chrono::Utc;
+use flo_state::{async_trait, Actor, Addr, Context, Handler, Message, RegistryRef, Service};
+use s2_grpc_utils::S2ProtoEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Max subscriptions of a player, across all channels
+pub const MAX_SUBSCRIPTIONS_PER_PLAYER: usize = 10;
+const MAX_WEB_PUSH_ENDPOINT_LEN: usize = 2048;
+const MAX_EMAIL_LEN: usize = 254;
+
+#[derive(
+  Debug,
+  Serialize,
+  Deserialize,
+  Copy,
+  Clone,
+  PartialEq,
+  Eq,
+  PartialOrd,
+  Ord,
+  BSDieselEnum,
+  S2ProtoEnum,
+)]
+#[repr(i32)]
+#[s2_grpc(proto_enum_type(flo_net::proto::flo_connect::NotificationChannel))]
+pub enum NotificationChannel {
+  WebPush = 0,
+  Email = 1,
+}
+
+#[derive(Debug, Clone, Queryable, Serialize, Deserialize)]
+pub struct NotificationTarget {
+  pub player_id: i32,
+  pub channel: NotificationChannel,
+  /// Web push subscription endpoint or email address
+  pub target: String,
+}
+
+/// Checks that `target` is a web push endpoint or an email address, depending on `channel`
+pub fn validate_target(channel: NotificationChannel, target: &str) -> Result<()> {
+  if target.is_empty() {
+    return Err(Error::NotificationTargetInvalid("empty"));
+  }
+  if target.chars().any(|c| c.is_whitespace() || c.is_control()) {
+    return Err(Error::NotificationTargetInvalid("whitespace"));
+  }
+  match channel {
+    NotificationChannel::WebPush => {
+      if target.len() > MAX_WEB_PUSH_ENDPOINT_LEN {
+        return Err(Error::NotificationTargetInvalid("endpoint too long"));
+      }
+      let host = target
+        .strip_prefix("https://")
+        .and_then(|rest| rest.split('/').next())
+        .unwrap_or_default();
+      if host.is_empty() {
+        return Err(Error::NotificationTargetInvalid("not a https endpoint"));
+      }
+    }
+    NotificationChannel::Email => {
+      if target.len() > MAX_EMAIL_LEN {
+        return Err(Error::NotificationTargetInvalid("email too long"));
+      }
+      let valid = match target.split_once('@') {
+        Some((local, domain)) => {
+          !local.is_empty()
+            && !domain.contains('@')
+            && domain.contains('.')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+        }
+        None => false,
+      };
+      if !valid {
+        return Err(Error::NotificationTargetInvalid("not an email address"));
+      }
+    }
+  }
+  Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameNotificationKind {
+  /// All slots of the lobby are occupied
+  LobbyFull,
+  /// The game was created by a bot and will start shortly
+  GameScheduled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameNotification {
+  pub game_id: i32,
+  pub game_name: String,
+  pub kind: GameNotificationKind,
+}
+
+impl GameNotification {
+  pub fn title(&self) -> String {
+    match self.kind {
+      GameNotificationKind::LobbyFull => format!("Lobby full: {}", self.game_name),
+      GameNotificationKind::GameScheduled => format!("Game ready: {}", self.game_name),
+    }
+  }
+
+  pub fn body(&self) -> String {
+    match self.kind {
+      GameNotificationKind::LobbyFull => {
+        "All players have joined, start flo to get ready.".to_string()
+      }
+      GameNotificationKind::GameScheduled => {
+        "Your game is about to start, start flo to join.".to_string()
+      }
+    }
+  }
+}
+
+pub type NotificationProviders = Arc<BTreeMap<NotificationChannel, Box<dyn NotificationProvider>>>;
+
+/// Sends game notifications to opted-in players who are not connected,
+/// failed deliveries are retried by the job queue
+pub struct NotificationDispatcher {
+  db: ExecutorRef,
+  players: Addr<PlayerRegistry>,
+  providers: NotificationProviders,
+}
+
+impl NotificationDispatcher {
+  fn providers_from_env() -> BTreeMap<NotificationChannel, Box<dyn NotificationProvider>> {
+    let mut providers: BTreeMap<NotificationChannel, Box<dyn NotificationProvider>> =
+      BTreeMap::new();
+    if let Ok(url) = std::env::var("FLO_NOTIFICATION_WEBPUSH_RELAY_URL") {
+      providers.insert(
+        NotificationChannel::WebPush,
+        Box::new(HttpRelayProvider::new("webpush", url)),
+      );
+    }
+    if let Ok(url) = std::env::var("FLO_NOTIFICATION_EMAIL_RELAY_URL") {
+      providers.insert(
+        NotificationChannel::Email,
+        Box::new(HttpRelayProvider::new("email", url)),
+      );
+    }
+    providers
+  }
+}
+
+impl Actor for NotificationDispatcher {}
+
+#[async_trait]
+impl Service<Data> for NotificationDispatcher {
+  type Error = Error;
+
+  async fn create(registry: &mut RegistryRef<Data>) -> Result<Self, Self::Error> {
+    let players = registry.resolve::<PlayerRegistry>().await?;
+    Ok(NotificationDispatcher {
+      db: registry.data().db.clone(),
+      players,
+      providers: Arc::new(Self::providers_from_env()),
+    })
+  }
+}
+
+pub struct NotifyGamePlayers {
+  pub notification: GameNotification,
+  pub player_ids: Vec<i32>,
+}
+
+impl Message for NotifyGamePlayers {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<NotifyGamePlayers> for NotificationDispatcher {
+  async fn handle(
+    &mut self,
+    ctx: &mut Context<Self>,
+    NotifyGamePlayers {
+      notification,
+      player_ids,
+    }: NotifyGamePlayers,
+  ) {
+    if self.providers.is_empty() {
+      return;
+    }
+
+    let db = self.db.clone();
+    let players = self.players.clone();
+    let providers = self.providers.clone();
+    ctx.spawn(async move {
+      let game_id = notification.game_id;
+      let offline = match players.send(FilterOfflinePlayers { player_ids }).await {
+        Ok(v) => v,
+        Err(err) => {
+          tracing::error!(game_id, "notification: filter offline players: {}", err);
+          return;
+        }
+      };
+      if offline.is_empty() {
+        return;
+      }
+
+      let targets = match db.exec(move |conn| self::db::get_targets(conn, &offline)).await {
+        Ok(v) => v,
+        Err(err) => {
+          tracing::error!(game_id, "notification: load targets: {}", err);
+          return;
+        }
+      };
+
+      for target in targets {
+        if let Some(provider) = providers.get(&target.channel) {
+          if let Err(err) = provider.send(&target, &notification).await {
+            tracing::warn!(
+              game_id,
+              player_id = target.player_id,
+              "notification: {}: {}",
+              provider.name(),
+              err
+            );
+            let payload = JobPayload::NotificationDelivery {
+              target,
+              notification: notification.clone(),
+            };
+            let run_at = Utc::now() + retry_delay(1);
+            if let Err(err) = db
+              .exec(move |conn| crate::job::db::enqueue(conn, &payload, run_at))
+              .await
+            {
+              tracing::error!(game_id, "notification: enqueue retry: {}", err);
+            }
+          }
+        }
+      }
+    });
+  }
+}
+
+pub struct GetNotificationProviders;
+
+impl Message for GetNotificationProviders {
+  type Result = NotificationProviders;
+}
+
+#[async_trait]
+impl Handler<GetNotificationProviders> for NotificationDispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    _: GetNotificationProviders,
+  ) -> NotificationProviders {
+    self.providers.clone()
+  }
+}
+
+#[test]
+fn test_validate_target() {
+  use NotificationChannel::*;
+  assert!(validate_target(WebPush, "https://fcm.googleapis.com/fcm/send/abc").is_ok());
+  assert!(validate_target(Email, "player@example.com").is_ok());
+  for (channel, target) in [
+    (WebPush, ""),
+    (WebPush, "http://example.com/push"),
+    (WebPush, "https:///push"),
+    (WebPush, "https://example.com/a b"),
+    (Email, "player"),
+    (Email, "@example.com"),
+    (Email, "player@localhost"),
+    (Email, "a@b@example.com"),
+  ] {
+    assert!(
+      matches!(
+        validate_target(channel, target),
+        Err(Error::NotificationTargetInvalid(_))
+      ),
+      "{:?}: {}",
+      channel,
+      target
+    );
+  }
+}
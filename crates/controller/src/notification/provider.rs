@@ -0,0 +1,68 @@
+use crate::error::*;
+use crate::notification::{GameNotification, NotificationTarget};
+use flo_state::async_trait;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request};
+use serde::Serialize;
+
+/// Delivers a notification to a single subscription target
+#[async_trait]
+pub trait NotificationProvider: Send + Sync + 'static {
+  fn name(&self) -> &'static str;
+  async fn send(&self, target: &NotificationTarget, notification: &GameNotification) -> Result<()>;
+}
+
+/// Posts notifications as JSON to a relay service
+/// which talks to the actual web push endpoint or SMTP server.
+pub struct HttpRelayProvider {
+  name: &'static str,
+  url: String,
+  client: Client<HttpConnector>,
+}
+
+impl HttpRelayProvider {
+  pub fn new(name: &'static str, url: String) -> Self {
+    Self {
+      name,
+      url,
+      client: Client::new(),
+    }
+  }
+}
+
+#[derive(Serialize)]
+struct RelayPayload<'a> {
+  player_id: i32,
+  target: &'a str,
+  game_id: i32,
+  title: String,
+  body: String,
+}
+
+#[async_trait]
+impl NotificationProvider for HttpRelayProvider {
+  fn name(&self) -> &'static str {
+    self.name
+  }
+
+  async fn send(&self, target: &NotificationTarget, notification: &GameNotification) -> Result<()> {
+    let payload = serde_json::to_vec(&RelayPayload {
+      player_id: target.player_id,
+      target: &target.target,
+      game_id: notification.game_id,
+      title: notification.title(),
+      body: notification.body(),
+    })?;
+    let req = Request::builder()
+      .method(Method::POST)
+      .uri(&self.url)
+      .header("content-type", "application/json")
+      .body(Body::from(payload))
+      .map_err(|err| Error::NotificationRelay(err.to_string()))?;
+    let res = self.client.request(req).await?;
+    if !res.status().is_success() {
+      return Err(Error::NotificationRelay(res.status().to_string()));
+    }
+    Ok(())
+  }
+}
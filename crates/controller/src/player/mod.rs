@@ -1,5 +1,8 @@
 pub mod db;
+pub mod penalty;
+pub mod privacy;
 pub mod session;
+pub mod smurf;
 pub(crate) mod state;
 pub mod token;
 mod types;
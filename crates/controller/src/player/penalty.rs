@@ -0,0 +1,209 @@
+//! Automatic penalties for griefing.
+//!
+//! Leaving a game shortly after it started and not acking a game start are offenses,
+//! recorded at most once per player and game. Enough recent offenses block the player from
+//! joining ladder lobbies for a cooldown counted from the latest offense, longer cooldowns
+//! apply as offenses accumulate. Offenses expire after `FLO_PENALTY_OFFENSE_TTL_HOURS`.
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::player_offense;
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use flo_net::proto::flo_connect as proto;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum)]
+#[repr(i32)]
+pub enum OffenseKind {
+  EarlyLeave = 0,
+  StartAckTimeout = 1,
+}
+
+#[derive(Debug, Clone)]
+pub struct PenaltyPolicy {
+  /// Leaving within this time after the game started is an offense,
+  /// leaving while the game is loading always is
+  pub early_leave: Duration,
+  pub offense_ttl: Duration,
+  /// Cooldown by offense count, the last one applies to any higher count.
+  /// Zero cooldowns are warnings, an empty list disables penalties.
+  pub cooldowns: Vec<Duration>,
+}
+
+pub static PENALTY_POLICY: Lazy<PenaltyPolicy> = Lazy::new(PenaltyPolicy::from_env);
+
+impl Default for PenaltyPolicy {
+  fn default() -> Self {
+    PenaltyPolicy {
+      early_leave: Duration::minutes(5),
+      offense_ttl: Duration::hours(72),
+      cooldowns: [0, 15, 60, 360, 1440]
+        .iter()
+        .map(|v| Duration::minutes(*v))
+        .collect(),
+    }
+  }
+}
+
+impl PenaltyPolicy {
+  pub fn from_env() -> Self {
+    fn get(name: &str) -> Option<i64> {
+      std::env::var(name).ok().and_then(|v| v.parse().ok())
+    }
+    let default = Self::default();
+    PenaltyPolicy {
+      early_leave: get("FLO_PENALTY_EARLY_LEAVE_SECS")
+        .map(Duration::seconds)
+        .unwrap_or(default.early_leave),
+      offense_ttl: get("FLO_PENALTY_OFFENSE_TTL_HOURS")
+        .map(Duration::hours)
+        .unwrap_or(default.offense_ttl),
+      cooldowns: std::env::var("FLO_PENALTY_COOLDOWN_MINUTES")
+        .ok()
+        .map(|v| {
+          v.split(',')
+            .filter_map(|v| v.trim().parse().ok())
+            .map(Duration::minutes)
+            .collect()
+        })
+        .unwrap_or(default.cooldowns),
+    }
+  }
+
+  pub fn enabled(&self) -> bool {
+    !self.cooldowns.is_empty()
+  }
+
+  /// `offense_times` are the player's recorded offenses, in any order
+  pub fn evaluate(&self, offense_times: &[DateTime<Utc>], now: DateTime<Utc>) -> Option<Penalty> {
+    let since = now - self.offense_ttl;
+    let recent = offense_times.iter().filter(|t| **t > since);
+    let offenses = recent.clone().count();
+    let latest = *recent.max()?;
+    let cooldown = *self.cooldowns.get(offenses.min(self.cooldowns.len()) - 1)?;
+    let ranked_join_blocked_until = latest + cooldown;
+    if ranked_join_blocked_until <= now {
+      return None;
+    }
+    Some(Penalty {
+      offenses,
+      ranked_join_blocked_until,
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Penalty {
+  pub offenses: usize,
+  pub ranked_join_blocked_until: DateTime<Utc>,
+}
+
+impl From<Penalty> for proto::PlayerPenalty {
+  fn from(v: Penalty) -> Self {
+    proto::PlayerPenalty {
+      offenses: v.offenses as i32,
+      ranked_join_blocked_until_millis: v.ranked_join_blocked_until.timestamp_millis(),
+    }
+  }
+}
+
+/// Returns the resulting penalty, `None` if the offense was already recorded for this game
+/// or no cooldown applies
+pub fn record_offense(
+  conn: &DbConn,
+  player_id: i32,
+  game_id: i32,
+  kind: OffenseKind,
+) -> Result<Option<Penalty>> {
+  #[derive(Insertable)]
+  #[table_name = "player_offense"]
+  struct Insert {
+    player_id: i32,
+    game_id: i32,
+    kind: OffenseKind,
+  }
+
+  if !PENALTY_POLICY.enabled() {
+    return Ok(None);
+  }
+
+  let inserted = diesel::insert_into(player_offense::table)
+    .values(&Insert {
+      player_id,
+      game_id,
+      kind,
+    })
+    .on_conflict((player_offense::player_id, player_offense::game_id))
+    .do_nothing()
+    .execute(conn)?;
+
+  if inserted == 0 {
+    return Ok(None);
+  }
+
+  get_penalty(conn, player_id)
+}
+
+pub fn get_penalty(conn: &DbConn, player_id: i32) -> Result<Option<Penalty>> {
+  if !PENALTY_POLICY.enabled() {
+    return Ok(None);
+  }
+  let now = Utc::now();
+  let offense_times: Vec<DateTime<Utc>> = player_offense::table
+    .filter(
+      player_offense::player_id
+        .eq(player_id)
+        .and(player_offense::created_at.gt(now - PENALTY_POLICY.offense_ttl)),
+    )
+    .select(player_offense::created_at)
+    .load(conn)?;
+  Ok(PENALTY_POLICY.evaluate(&offense_times, now))
+}
+
+/// Called when a player joins a lobby created by an API client (ladder)
+pub fn check_ranked_join(conn: &DbConn, player_id: i32) -> Result<()> {
+  if let Some(penalty) = get_penalty(conn, player_id)? {
+    return Err(Error::PlayerPenalized(penalty.ranked_join_blocked_until));
+  }
+  Ok(())
+}
+
+#[test]
+fn test_evaluate() {
+  use chrono::TimeZone;
+
+  let policy = PenaltyPolicy::default();
+  let now = Utc.ymd(2021, 1, 1).and_hms(12, 0, 0);
+  let ago = |minutes| now - Duration::minutes(minutes);
+
+  assert_eq!(policy.evaluate(&[], now), None);
+  // first offense is a warning
+  assert_eq!(policy.evaluate(&[ago(1)], now), None);
+  assert_eq!(
+    policy.evaluate(&[ago(30), ago(5)], now),
+    Some(Penalty {
+      offenses: 2,
+      ranked_join_blocked_until: now + Duration::minutes(10),
+    })
+  );
+  // cooldown is over
+  assert_eq!(policy.evaluate(&[ago(60), ago(20)], now), None);
+  // the last cooldown applies to any higher count
+  assert_eq!(
+    policy
+      .evaluate(&[ago(5), ago(4), ago(3), ago(2), ago(1), ago(0)], now)
+      .map(|v| v.ranked_join_blocked_until),
+    Some(now + Duration::minutes(1440))
+  );
+  // expired offenses are not counted
+  assert_eq!(policy.evaluate(&[ago(73 * 60), ago(1)], now), None);
+
+  let disabled = PenaltyPolicy {
+    cooldowns: vec![],
+    ..Default::default()
+  };
+  assert_eq!(disabled.evaluate(&[ago(2), ago(1)], now), None);
+}
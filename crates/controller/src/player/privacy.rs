@@ -0,0 +1,158 @@
+//! Privacy settings of players, applied to the public stats.
+//!
+//! The observer edge gets the settings of the players of a game as `GetGame` reply metadata,
+//! the gRPC messages are shared with other API clients and are left unchanged.
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::player_privacy;
+use diesel::prelude::*;
+use flo_net::proto::flo_connect as proto;
+use std::collections::BTreeMap;
+use tonic::metadata::{AsciiMetadataValue, MetadataMap};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Queryable)]
+pub struct PlayerPrivacy {
+  /// Games of the player are left out of player game lists and widgets
+  pub hide_profile: bool,
+  /// The player's name is replaced with the slot number
+  pub anonymize: bool,
+}
+
+impl PlayerPrivacy {
+  fn is_default(&self) -> bool {
+    *self == Self::default()
+  }
+}
+
+impl From<PlayerPrivacy> for proto::PlayerPrivacy {
+  fn from(v: PlayerPrivacy) -> Self {
+    proto::PlayerPrivacy {
+      hide_profile: v.hide_profile,
+      anonymize: v.anonymize,
+    }
+  }
+}
+
+impl From<proto::PlayerPrivacy> for PlayerPrivacy {
+  fn from(v: proto::PlayerPrivacy) -> Self {
+    PlayerPrivacy {
+      hide_profile: v.hide_profile,
+      anonymize: v.anonymize,
+    }
+  }
+}
+
+pub fn get_privacy(conn: &DbConn, player_id: i32) -> Result<PlayerPrivacy> {
+  player_privacy::table
+    .find(player_id)
+    .select((player_privacy::hide_profile, player_privacy::anonymize))
+    .first(conn)
+    .optional()
+    .map(Option::unwrap_or_default)
+    .map_err(Into::into)
+}
+
+/// Players without privacy settings are left out
+pub fn get_privacy_map(conn: &DbConn, player_ids: &[i32]) -> Result<BTreeMap<i32, PlayerPrivacy>> {
+  let rows: Vec<(i32, PlayerPrivacy)> = player_privacy::table
+    .select((
+      player_privacy::player_id,
+      (player_privacy::hide_profile, player_privacy::anonymize),
+    ))
+    .filter(player_privacy::player_id.eq_any(player_ids))
+    .load(conn)?;
+  Ok(
+    rows
+      .into_iter()
+      .filter(|(_, privacy)| !privacy.is_default())
+      .collect(),
+  )
+}
+
+pub fn set_privacy(conn: &DbConn, player_id: i32, privacy: PlayerPrivacy) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "player_privacy"]
+  struct Insert {
+    player_id: i32,
+    hide_profile: bool,
+    anonymize: bool,
+  }
+
+  diesel::insert_into(player_privacy::table)
+    .values(&Insert {
+      player_id,
+      hide_profile: privacy.hide_profile,
+      anonymize: privacy.anonymize,
+    })
+    .on_conflict(player_privacy::player_id)
+    .do_update()
+    .set((
+      player_privacy::hide_profile.eq(privacy.hide_profile),
+      player_privacy::anonymize.eq(privacy.anonymize),
+      player_privacy::updated_at.eq(diesel::dsl::now),
+    ))
+    .execute(conn)?;
+  Ok(())
+}
+
+/// Adds the ids of anonymized players and players with hidden profiles, comma separated
+pub fn insert_metadata(metadata: &mut MetadataMap, map: &BTreeMap<i32, PlayerPrivacy>) {
+  let join = |filter: fn(&PlayerPrivacy) -> bool| -> Option<AsciiMetadataValue> {
+    let ids: Vec<String> = map
+      .iter()
+      .filter(|(_, privacy)| filter(privacy))
+      .map(|(id, _)| id.to_string())
+      .collect();
+    if ids.is_empty() {
+      return None;
+    }
+    ids.join(",").parse().ok()
+  };
+  if let Some(value) = join(|v| v.anonymize) {
+    metadata.insert(flo_constants::GRPC_METADATA_ANONYMIZED_PLAYERS, value);
+  }
+  if let Some(value) = join(|v| v.hide_profile) {
+    metadata.insert(flo_constants::GRPC_METADATA_HIDDEN_PLAYERS, value);
+  }
+}
+
+#[test]
+fn test_insert_metadata() {
+  let mut metadata = MetadataMap::new();
+  let map: BTreeMap<i32, PlayerPrivacy> = vec![
+    (
+      1,
+      PlayerPrivacy {
+        hide_profile: true,
+        anonymize: true,
+      },
+    ),
+    (
+      2,
+      PlayerPrivacy {
+        hide_profile: false,
+        anonymize: true,
+      },
+    ),
+  ]
+  .into_iter()
+  .collect();
+  insert_metadata(&mut metadata, &map);
+  assert_eq!(
+    metadata
+      .get(flo_constants::GRPC_METADATA_ANONYMIZED_PLAYERS)
+      .unwrap(),
+    "1,2"
+  );
+  assert_eq!(
+    metadata
+      .get(flo_constants::GRPC_METADATA_HIDDEN_PLAYERS)
+      .unwrap(),
+    "1"
+  );
+
+  let mut metadata = MetadataMap::new();
+  insert_metadata(&mut metadata, &BTreeMap::new());
+  assert!(metadata.is_empty());
+}
@@ -0,0 +1,367 @@
+//! Heuristics for accounts likely operated by the same person.
+//!
+//! Accounts are paired by shared client hints, IP addresses and IP ranges,
+//! then scored with their active hours and whether they ever played in the same game.
+//! The report is advisory, ladder admins decide what to do with it.
+//!
+//! Login records are kept for `FLO_PLAYER_LOGIN_RETENTION_DAYS` (90 by default),
+//! older records are pruned by the job worker.
+
+use crate::db::DbConn;
+use crate::error::*;
+use crate::schema::{game, game_used_slot, player, player_login};
+use chrono::{DateTime, Timelike, Utc};
+use diesel::pg::expression::dsl::any;
+use diesel::prelude::*;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+
+const SCORE_SHARED_CLIENT_HINT: f32 = 0.6;
+const SCORE_SHARED_IP: f32 = 0.3;
+const SCORE_SHARED_IP_RANGE: f32 = 0.1;
+const SCORE_SIMILAR_ACTIVE_HOURS: f32 = 0.1;
+const SCORE_PLAYED_TOGETHER: f32 = -0.5;
+
+/// Addresses shared by more accounts than this are likely NAT gateways or internet cafes
+const MAX_GROUP_SIZE: usize = 16;
+const MIN_ACTIVE_HOURS_LOGINS: usize = 5;
+const MIN_ACTIVE_HOURS_SIMILARITY: f32 = 0.9;
+
+static LOGIN_RETENTION_DAYS: Lazy<i64> = Lazy::new(|| {
+  std::env::var("FLO_PLAYER_LOGIN_RETENTION_DAYS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .filter(|v| *v > 0)
+    .unwrap_or(90)
+});
+
+/// Login records older than this are pruned
+pub fn login_retention() -> chrono::Duration {
+  chrono::Duration::days(*LOGIN_RETENTION_DAYS)
+}
+
+pub fn record_login(
+  conn: &DbConn,
+  player_id: i32,
+  ip_addr: IpAddr,
+  client_hint: Option<String>,
+) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "player_login"]
+  struct Insert {
+    player_id: i32,
+    ip_addr: String,
+    client_hint: Option<String>,
+  }
+
+  diesel::insert_into(player_login::table)
+    .values(&Insert {
+      player_id,
+      ip_addr: ip_addr.to_string(),
+      client_hint,
+    })
+    .execute(conn)?;
+
+  Ok(())
+}
+
+/// Deletes login records created before `before`, returns the number of deleted records
+pub fn prune_logins(conn: &DbConn, before: DateTime<Utc>) -> Result<usize> {
+  diesel::delete(player_login::table.filter(player_login::created_at.lt(before)))
+    .execute(conn)
+    .map_err(Into::into)
+}
+
+#[derive(Debug, Clone)]
+pub struct SmurfReportParams {
+  pub api_client_id: Option<i32>,
+  pub since: DateTime<Utc>,
+  pub min_score: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SmurfReport {
+  pub generated_at: DateTime<Utc>,
+  pub candidates: Vec<SmurfCandidate>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SmurfCandidate {
+  pub player_ids: [i32; 2],
+  pub score: f32,
+  pub signals: Vec<SmurfSignal>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SmurfSignal {
+  SharedClientHint,
+  SharedIp { count: usize },
+  SharedIpRange,
+  SimilarActiveHours { similarity: f32 },
+  PlayedTogether { games: usize },
+}
+
+impl SmurfSignal {
+  fn score(&self) -> f32 {
+    match *self {
+      SmurfSignal::SharedClientHint => SCORE_SHARED_CLIENT_HINT,
+      SmurfSignal::SharedIp { .. } => SCORE_SHARED_IP,
+      SmurfSignal::SharedIpRange => SCORE_SHARED_IP_RANGE,
+      SmurfSignal::SimilarActiveHours { .. } => SCORE_SIMILAR_ACTIVE_HOURS,
+      SmurfSignal::PlayedTogether { .. } => SCORE_PLAYED_TOGETHER,
+    }
+  }
+}
+
+#[derive(Debug, Queryable)]
+pub struct LoginRecord {
+  pub player_id: i32,
+  pub ip_addr: String,
+  pub client_hint: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
+
+pub fn generate_report(conn: &DbConn, params: &SmurfReportParams) -> Result<SmurfReport> {
+  let logins = load_logins(conn, params)?;
+  let player_ids: Vec<Option<i32>> = logins
+    .iter()
+    .map(|l| l.player_id)
+    .collect::<BTreeSet<_>>()
+    .into_iter()
+    .map(Some)
+    .collect();
+  let games = load_player_games(conn, &player_ids, params.since)?;
+  Ok(SmurfReport {
+    generated_at: Utc::now(),
+    candidates: analyze(&logins, &games, params.min_score),
+  })
+}
+
+fn load_logins(conn: &DbConn, params: &SmurfReportParams) -> Result<Vec<LoginRecord>> {
+  use player_login::dsl;
+  let mut q = player_login::table
+    .inner_join(player::table)
+    .select((
+      dsl::player_id,
+      dsl::ip_addr,
+      dsl::client_hint,
+      dsl::created_at,
+    ))
+    .filter(dsl::created_at.ge(params.since))
+    .into_boxed();
+  if let Some(api_client_id) = params.api_client_id {
+    q = q.filter(player::api_client_id.eq(api_client_id));
+  }
+  q.load(conn).map_err(Into::into)
+}
+
+fn load_player_games(
+  conn: &DbConn,
+  player_ids: &[Option<i32>],
+  since: DateTime<Utc>,
+) -> Result<BTreeMap<i32, BTreeSet<i32>>> {
+  let rows: Vec<(Option<i32>, i32)> = game_used_slot::table
+    .inner_join(game::table)
+    .select((game_used_slot::player_id, game_used_slot::game_id))
+    .filter(game_used_slot::player_id.eq(any(player_ids)))
+    .filter(game::started_at.ge(since))
+    .load(conn)?;
+  let mut map = BTreeMap::<i32, BTreeSet<i32>>::new();
+  for (player_id, game_id) in rows {
+    if let Some(player_id) = player_id {
+      map.entry(player_id).or_default().insert(game_id);
+    }
+  }
+  Ok(map)
+}
+
+#[derive(Debug, Default)]
+struct PairSignals {
+  client_hint: bool,
+  shared_ips: usize,
+  ip_range: bool,
+}
+
+/// Pairs accounts sharing client hints or addresses, then scores each pair.
+/// `games` maps player ids to the ids of the games they played.
+pub fn analyze(
+  logins: &[LoginRecord],
+  games: &BTreeMap<i32, BTreeSet<i32>>,
+  min_score: f32,
+) -> Vec<SmurfCandidate> {
+  let mut hint_groups = BTreeMap::<&str, BTreeSet<i32>>::new();
+  let mut ip_groups = BTreeMap::<&str, BTreeSet<i32>>::new();
+  let mut range_groups = BTreeMap::<String, BTreeSet<i32>>::new();
+  let mut hours = BTreeMap::<i32, ([u32; 24], usize)>::new();
+
+  for login in logins {
+    if let Some(hint) = login.client_hint.as_deref() {
+      hint_groups.entry(hint).or_default().insert(login.player_id);
+    }
+    ip_groups
+      .entry(login.ip_addr.as_str())
+      .or_default()
+      .insert(login.player_id);
+    if let Some(range) = ip_range(&login.ip_addr) {
+      range_groups
+        .entry(range)
+        .or_default()
+        .insert(login.player_id);
+    }
+    let (histogram, count) = hours.entry(login.player_id).or_default();
+    histogram[login.created_at.hour() as usize] += 1;
+    *count += 1;
+  }
+
+  let mut pairs = BTreeMap::<(i32, i32), PairSignals>::new();
+  for group in hint_groups.values() {
+    for_each_pair(group, |pair| {
+      pairs.entry(pair).or_default().client_hint = true
+    });
+  }
+  for group in ip_groups.values() {
+    for_each_pair(group, |pair| pairs.entry(pair).or_default().shared_ips += 1);
+  }
+  for group in range_groups.values() {
+    for_each_pair(group, |pair| pairs.entry(pair).or_default().ip_range = true);
+  }
+
+  let mut candidates: Vec<_> = pairs
+    .into_iter()
+    .filter_map(|((a, b), pair)| {
+      let mut signals = vec![];
+      if pair.client_hint {
+        signals.push(SmurfSignal::SharedClientHint);
+      }
+      if pair.shared_ips > 0 {
+        signals.push(SmurfSignal::SharedIp {
+          count: pair.shared_ips,
+        });
+      } else if pair.ip_range {
+        signals.push(SmurfSignal::SharedIpRange);
+      }
+      if let (Some(x), Some(y)) = (hours.get(&a), hours.get(&b)) {
+        if x.1 >= MIN_ACTIVE_HOURS_LOGINS && y.1 >= MIN_ACTIVE_HOURS_LOGINS {
+          let similarity = cosine_similarity(&x.0, &y.0);
+          if similarity >= MIN_ACTIVE_HOURS_SIMILARITY {
+            signals.push(SmurfSignal::SimilarActiveHours { similarity });
+          }
+        }
+      }
+      if let (Some(x), Some(y)) = (games.get(&a), games.get(&b)) {
+        let count = x.intersection(y).count();
+        if count > 0 {
+          signals.push(SmurfSignal::PlayedTogether { games: count });
+        }
+      }
+      let score = signals.iter().map(SmurfSignal::score).sum::<f32>().min(1.0);
+      if score < min_score {
+        return None;
+      }
+      Some(SmurfCandidate {
+        player_ids: [a, b],
+        score,
+        signals,
+      })
+    })
+    .collect();
+
+  candidates.sort_by(|a, b| {
+    b.score
+      .partial_cmp(&a.score)
+      .unwrap_or(std::cmp::Ordering::Equal)
+  });
+  candidates
+}
+
+fn for_each_pair<F>(group: &BTreeSet<i32>, mut f: F)
+where
+  F: FnMut((i32, i32)),
+{
+  if group.len() < 2 || group.len() > MAX_GROUP_SIZE {
+    return;
+  }
+  let ids: Vec<i32> = group.iter().cloned().collect();
+  for (i, a) in ids.iter().enumerate() {
+    for b in &ids[(i + 1)..] {
+      f((*a, *b));
+    }
+  }
+}
+
+/// `/24` for IPv4, `/48` for IPv6
+fn ip_range(addr: &str) -> Option<String> {
+  match addr.parse::<IpAddr>().ok()? {
+    IpAddr::V4(addr) => {
+      let [a, b, c, _] = addr.octets();
+      Some(format!("{}.{}.{}.0/24", a, b, c))
+    }
+    IpAddr::V6(addr) => {
+      let s = addr.segments();
+      Some(format!("{:x}:{:x}:{:x}::/48", s[0], s[1], s[2]))
+    }
+  }
+}
+
+fn cosine_similarity(a: &[u32; 24], b: &[u32; 24]) -> f32 {
+  let dot: f32 = a
+    .iter()
+    .zip(b)
+    .map(|(x, y)| (*x as f32) * (*y as f32))
+    .sum();
+  let norm = |v: &[u32; 24]| v.iter().map(|x| (*x as f32).powi(2)).sum::<f32>().sqrt();
+  let d = norm(a) * norm(b);
+  if d == 0. {
+    0.
+  } else {
+    dot / d
+  }
+}
+
+#[test]
+fn test_analyze() {
+  use chrono::TimeZone;
+
+  let login = |player_id, ip_addr: &str, client_hint: Option<&str>, hour| LoginRecord {
+    player_id,
+    ip_addr: ip_addr.to_string(),
+    client_hint: client_hint.map(ToString::to_string),
+    created_at: Utc.ymd(2021, 1, 1).and_hms(hour, 0, 0),
+  };
+
+  let logins = vec![
+    login(1, "10.0.0.1", Some("a"), 20),
+    login(2, "10.0.0.1", Some("a"), 21),
+    login(3, "10.0.0.2", None, 20),
+    login(4, "192.168.0.1", None, 8),
+  ];
+
+  let mut games = BTreeMap::new();
+  games.insert(1, vec![100].into_iter().collect::<BTreeSet<_>>());
+  games.insert(3, vec![100].into_iter().collect::<BTreeSet<_>>());
+
+  // 1 and 3 played together
+  let candidates = analyze(&logins, &games, 0.0);
+  assert_eq!(candidates.len(), 2);
+  assert_eq!(candidates[0].player_ids, [1, 2]);
+  assert_eq!(
+    candidates[0].signals,
+    vec![
+      SmurfSignal::SharedClientHint,
+      SmurfSignal::SharedIp { count: 1 }
+    ]
+  );
+  assert_eq!(candidates[1].player_ids, [2, 3]);
+  assert_eq!(candidates[1].signals, vec![SmurfSignal::SharedIpRange]);
+
+  assert_eq!(analyze(&logins, &games, 0.5).len(), 1);
+
+  assert_eq!(ip_range("1.2.3.4").as_deref(), Some("1.2.3.0/24"));
+  assert_eq!(
+    ip_range("2001:db8:1:2::1").as_deref(),
+    Some("2001:db8:1::/48")
+  );
+}
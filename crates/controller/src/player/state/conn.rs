@@ -5,6 +5,7 @@ use flo_state::{async_trait, Context, Handler, Message};
 
 pub struct Connect {
   pub game_id: Option<i32>,
+  pub war3_version: Option<String>,
   pub sender: PlayerSender,
 }
 
@@ -18,7 +19,12 @@ impl Handler<Connect> for PlayerRegistry {
     let player_id = message.sender.player_id();
     let removed = self.registry.insert(
       player_id,
-      PlayerState::new(player_id, message.game_id, message.sender),
+      PlayerState::new(
+        player_id,
+        message.game_id,
+        message.war3_version,
+        message.sender,
+      ),
     );
     if let Some(state) = removed {
       state.shutdown().await;
@@ -43,3 +49,47 @@ impl Handler<Disconnect> for PlayerRegistry {
     }
   }
 }
+
+/// Returns players without an active controller connection
+pub struct FilterOfflinePlayers {
+  pub player_ids: Vec<i32>,
+}
+
+impl Message for FilterOfflinePlayers {
+  type Result = Vec<i32>;
+}
+
+#[async_trait]
+impl Handler<FilterOfflinePlayers> for PlayerRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    FilterOfflinePlayers { mut player_ids }: FilterOfflinePlayers,
+  ) -> Vec<i32> {
+    player_ids.retain(|id| !self.registry.contains_key(id));
+    player_ids
+  }
+}
+
+/// The Warcraft III version reported by the player's client
+pub struct GetPlayerWar3Version {
+  pub player_id: i32,
+}
+
+impl Message for GetPlayerWar3Version {
+  type Result = Option<String>;
+}
+
+#[async_trait]
+impl Handler<GetPlayerWar3Version> for PlayerRegistry {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GetPlayerWar3Version { player_id }: GetPlayerWar3Version,
+  ) -> Option<String> {
+    self
+      .registry
+      .get(&player_id)
+      .and_then(|state| state.war3_version.clone())
+  }
+}
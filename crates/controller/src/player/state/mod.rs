@@ -2,7 +2,7 @@ pub mod conn;
 pub mod ping;
 pub mod sender;
 
-use crate::client::PlayerSender;
+use crate::client::{PlayerSender, SharedFrames};
 use crate::error::Error;
 use crate::state::Data;
 use flo_state::{async_trait, Actor, RegistryRef, Service};
@@ -40,29 +40,64 @@ pub struct PlayerState {
   pub player_id: i32,
   pub ping_map: BTreeMap<i32, PingStats>,
   pub game_id: Option<i32>,
+  /// Reported by the client at connect
+  pub war3_version: Option<String>,
   pub sender: PlayerSender,
 }
 
 impl PlayerState {
-  fn new(player_id: i32, game_id: Option<i32>, sender: PlayerSender) -> PlayerState {
+  fn new(
+    player_id: i32,
+    game_id: Option<i32>,
+    war3_version: Option<String>,
+    sender: PlayerSender,
+  ) -> PlayerState {
     Self {
       player_id,
       game_id,
+      war3_version,
       ping_map: Default::default(),
       sender,
     }
   }
 
+  fn try_send_encoded(&mut self, frames: SharedFrames) -> bool {
+    self.sender.try_send_encoded(frames)
+  }
+
+  /// Multiple frames are encoded into one message,
+  /// so an update takes a single slot of the bounded connection queue
   fn try_send_frames(&mut self, frames: PlayerFrames) -> bool {
-    for frame in frames {
-      if !self.sender.try_send(frame) {
-        return false;
-      }
+    match frames {
+      PlayerFrames::Single(frame) => self.sender.try_send(frame),
+      frames => self.sender.try_send_encoded(frames.encode_shared()),
     }
-    true
   }
 
   async fn shutdown(mut self) {
     self.sender.disconnect_multi().await;
   }
 }
+
+#[test]
+fn test_try_send_frames_batched() {
+  use crate::client::PlayerSenderMessage;
+  use crate::player::session::get_session_update_packet;
+  use flo_net::packet::FloPacket;
+
+  let (sender, mut receiver) = PlayerSender::new(1);
+  let mut state = PlayerState::new(1, None, None, sender);
+  let frame = get_session_update_packet(None).encode_as_frame().unwrap();
+  assert!(state.try_send_frames(vec![frame.clone(); 16].into()));
+  match receiver.try_recv().unwrap() {
+    PlayerSenderMessage::Encoded(frames) => assert_eq!(frames.len(), 16),
+    _ => panic!("expected encoded frames"),
+  }
+  assert!(receiver.try_recv().is_err());
+
+  assert!(state.try_send_frames(frame.into()));
+  assert!(matches!(
+    receiver.try_recv().unwrap(),
+    PlayerSenderMessage::Frame(_)
+  ));
+}
@@ -1,7 +1,11 @@
+use super::conn::GetPlayerWar3Version;
 use super::{PlayerRegistry, PlayerState};
+use crate::client::SharedFrames;
 use crate::error::*;
 use crate::game::Game;
+use crate::metrics::{BROADCAST_FANOUT_SECONDS, PLAYER_SENDERS_DROPPED};
 use crate::player::session::get_session_update_packet;
+use crate::profiling::{self, ProfileKind};
 use flo_net::packet::{FloPacket, Frame};
 use flo_state::{async_trait, Addr, Context, Handler, Message};
 use s2_grpc_utils::S2ProtoPack;
@@ -37,12 +41,14 @@ impl Message for BroadcastToAll {
 #[async_trait]
 impl Handler<BroadcastToAll> for PlayerRegistry {
   async fn handle(&mut self, _: &mut Context<Self>, BroadcastToAll { frames }: BroadcastToAll) {
+    let frames = frames.encode_shared();
     let mut remove_list = vec![];
     for (player_id, state) in self.registry.iter_mut() {
-      let remove = { !state.try_send_frames(frames.clone()) };
+      let remove = { !state.try_send_encoded(frames.clone()) };
       if remove {
         let player_id = *player_id;
         tracing::debug!(player_id, "remove broken player sender");
+        PLAYER_SENDERS_DROPPED.inc();
         remove_list.push(player_id);
       }
     }
@@ -65,8 +71,9 @@ impl Message for Broadcast {
 #[async_trait]
 impl Handler<Broadcast> for PlayerRegistry {
   async fn handle(&mut self, _: &mut Context<Self>, Broadcast { players, frames }: Broadcast) {
+    let frames = frames.encode_shared();
     for player_id in players {
-      send_to_player(&mut self.registry, player_id, frames.clone());
+      send_encoded_to_player(&mut self.registry, player_id, frames.clone());
     }
   }
 }
@@ -124,6 +131,7 @@ impl Handler<PlayerReplaceGame> for PlayerRegistry {
       ];
       entry.get_mut().game_id = Some(game_id);
       if !entry.get_mut().try_send_frames(frames.into()) {
+        PLAYER_SENDERS_DROPPED.inc();
         entry.remove();
       }
     }
@@ -174,6 +182,7 @@ impl Handler<PlayersReplaceGame> for PlayerRegistry {
         ];
         entry.get_mut().game_id = Some(game_id);
         if !entry.get_mut().try_send_frames(frames.into()) {
+          PLAYER_SENDERS_DROPPED.inc();
           entry.remove();
         }
       }
@@ -206,6 +215,7 @@ impl Handler<PlayerLeaveGame> for PlayerRegistry {
           .sender
           .try_send(get_session_update_packet(None).encode_as_frame()?)
         {
+          PLAYER_SENDERS_DROPPED.inc();
           entry.remove();
         } else {
           entry.get_mut().game_id = None;
@@ -254,6 +264,13 @@ pub enum PlayerFrames {
   Multi(Vec<Frame>),
 }
 
+impl PlayerFrames {
+  /// Encodes the frames once so a broadcast doesn't encode them for every connection
+  pub fn encode_shared(self) -> SharedFrames {
+    self.into_iter().map(|frame| frame.to_encoded()).collect()
+  }
+}
+
 impl IntoIterator for PlayerFrames {
   type Item = Frame;
   type IntoIter = PlayerFramesIntoIterator;
@@ -317,6 +334,23 @@ fn send_to_player(map: &mut BTreeMap<i32, PlayerState>, player_id: i32, frames:
   };
   if remove {
     tracing::debug!(player_id, "remove broken player sender");
+    PLAYER_SENDERS_DROPPED.inc();
+    map.remove(&player_id);
+  }
+}
+
+fn send_encoded_to_player(
+  map: &mut BTreeMap<i32, PlayerState>,
+  player_id: i32,
+  frames: SharedFrames,
+) {
+  let remove = map
+    .get_mut(&player_id)
+    .map(|entry| !entry.try_send_encoded(frames))
+    .unwrap_or(false);
+  if remove {
+    tracing::debug!(player_id, "remove broken player sender");
+    PLAYER_SENDERS_DROPPED.inc();
     map.remove(&player_id);
   }
 }
@@ -342,6 +376,10 @@ impl PlayerRegistryHandle {
   where
     T: Into<PlayerFrames>,
   {
+    let _timer = BROADCAST_FANOUT_SECONDS
+      .with_label_values(&["all"])
+      .start_timer();
+    let _profile = profiling::start(ProfileKind::Broadcast, "all");
     self
       .0
       .send(BroadcastToAll {
@@ -351,10 +389,17 @@ impl PlayerRegistryHandle {
     Ok(())
   }
 
+  /// Frames are encoded once and queued on every connection without waiting,
+  /// each connection writes its bounded queue from its own task.
+  /// A connection whose queue is full is dropped instead of delaying the others
   pub async fn broadcast<T>(&self, players: Vec<i32>, frames: T) -> Result<()>
   where
     T: Into<PlayerFrames>,
   {
+    let _timer = BROADCAST_FANOUT_SECONDS
+      .with_label_values(&["players"])
+      .start_timer();
+    let _profile = profiling::start(ProfileKind::Broadcast, "players");
     self
       .0
       .send(Broadcast {
@@ -369,6 +414,10 @@ impl PlayerRegistryHandle {
   where
     T: IntoIterator<Item = (i32, PlayerFrames)>,
   {
+    let _timer = BROADCAST_FANOUT_SECONDS
+      .with_label_values(&["map"])
+      .start_timer();
+    let _profile = profiling::start(ProfileKind::Broadcast, "map");
     self
       .0
       .send(BroadcastMap {
@@ -430,6 +479,11 @@ impl PlayerRegistryHandle {
       .await??;
     Ok(())
   }
+
+  /// `None` if the player is offline or the client didn't report it
+  pub async fn war3_version(&self, player_id: i32) -> Result<Option<String>> {
+    Ok(self.0.send(GetPlayerWar3Version { player_id }).await?)
+  }
 }
 
 impl From<Addr<PlayerRegistry>> for PlayerRegistryHandle {
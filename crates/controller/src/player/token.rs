@@ -1,8 +1,9 @@
 use chrono::Utc;
 use jsonwebtoken::errors::ErrorKind;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, decode_header, encode, DecodingKey, EncodingKey, Header, Validation};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 use crate::error::*;
 
@@ -10,30 +11,180 @@ use crate::error::*;
 const TOKEN_EXPIRATION_SECS: i64 = 3600 * 24 * 30;
 const TOKEN_SUB: &str = "flo";
 
+/// Key id of `JWT_SECRET_BASE64`, written to the `kid` header of issued tokens
+static JWT_KEY_ID: Lazy<Option<String>> =
+  Lazy::new(|| std::env::var("JWT_KEY_ID").ok().filter(|v| !v.is_empty()));
+
+/// Retired signing keys that are still accepted, format: `kid1:base64,kid2:base64`.
+/// Tokens without a `kid` were issued before key ids were configured,
+/// they are also checked against these keys during the grace window
+static JWT_PREVIOUS_SECRETS: Lazy<BTreeMap<String, String>> = Lazy::new(|| {
+  std::env::var("JWT_PREVIOUS_SECRETS")
+    .ok()
+    .map(|v| {
+      v.split(',')
+        .filter_map(|pair| {
+          let mut parts = pair.splitn(2, ':');
+          let kid = parts.next()?.trim();
+          let secret = parts.next()?.trim();
+          if kid.is_empty() || secret.is_empty() {
+            return None;
+          }
+          Some((kid.to_string(), secret.to_string()))
+        })
+        .collect()
+    })
+    .unwrap_or_default()
+});
+
+/// Unix time the signing key was replaced. Tokens without a `kid` are checked against
+/// `JWT_PREVIOUS_SECRETS` until they could have expired, `TOKEN_EXPIRATION_SECS` later.
+/// If unset, they are checked as long as the previous keys are configured
+static JWT_KEY_ROTATED_AT: Lazy<Option<i64>> = Lazy::new(|| {
+  std::env::var("JWT_KEY_ROTATED_AT")
+    .ok()
+    .and_then(|v| v.parse().ok())
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+  /// Connect to the lobby socket and call player APIs
+  Connect,
+  /// Spectate games
+  Observer,
+  /// Administrative APIs
+  Admin,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PlayerToken {
   pub sub: String,
   pub player_id: i32,
   pub exp: usize,
+  // tokens issued before scopes existed are connect tokens
+  #[serde(default = "default_scopes")]
+  pub scopes: Vec<TokenScope>,
+  /// Set if issued on behalf of an API client
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub api_client_id: Option<i32>,
+}
+
+fn default_scopes() -> Vec<TokenScope> {
+  vec![TokenScope::Connect]
+}
+
+impl PlayerToken {
+  pub fn has_scope(&self, scope: TokenScope) -> bool {
+    self.scopes.contains(&scope)
+  }
 }
 
 pub fn create_player_token(player_id: i32) -> Result<String> {
+  issue_scoped_token(player_id, &[TokenScope::Connect], None, None)
+}
+
+/// Issues a token limited to `scopes`.
+/// `expires_in_secs` is capped to the default player token lifetime.
+pub fn issue_scoped_token(
+  player_id: i32,
+  scopes: &[TokenScope],
+  expires_in_secs: Option<i64>,
+  api_client_id: Option<i32>,
+) -> Result<String> {
   static ENCODING_KEY: Lazy<EncodingKey> = Lazy::new(|| {
     EncodingKey::from_base64_secret(&crate::config::JWT_SECRET_BASE64)
       .expect("DecodingKey::from_base64_secret")
   });
 
-  let exp = Utc::now().timestamp() + TOKEN_EXPIRATION_SECS;
+  if scopes.is_empty() {
+    return Err(Error::TokenScopeRequired);
+  }
+
+  let expires_in_secs = expires_in_secs
+    .filter(|v| *v > 0)
+    .map(|v| std::cmp::min(v, TOKEN_EXPIRATION_SECS))
+    .unwrap_or(TOKEN_EXPIRATION_SECS);
+  let exp = Utc::now().timestamp() + expires_in_secs;
+  let mut scopes = scopes.to_vec();
+  scopes.sort();
+  scopes.dedup();
   let claims = PlayerToken {
     sub: TOKEN_SUB.to_string(),
     player_id,
     exp: exp as usize,
+    scopes,
+    api_client_id,
   };
-  encode(&Header::default(), &claims, &ENCODING_KEY).map_err(Into::into)
+  let header = Header {
+    kid: JWT_KEY_ID.clone(),
+    ..Default::default()
+  };
+  encode(&header, &claims, &ENCODING_KEY).map_err(Into::into)
 }
 
+/// Validates a token that can be used to connect to the lobby
 pub fn validate_player_token(token: &str) -> Result<PlayerToken> {
-  let decoding_key = DecodingKey::from_base64_secret(&crate::config::JWT_SECRET_BASE64)?;
+  validate_scoped_token(token, TokenScope::Connect)
+}
+
+pub fn validate_scoped_token(token: &str, scope: TokenScope) -> Result<PlayerToken> {
+  let keys = SigningKeys {
+    current: &crate::config::JWT_SECRET_BASE64,
+    current_kid: JWT_KEY_ID.as_deref(),
+    previous: &JWT_PREVIOUS_SECRETS,
+    rotated_at: *JWT_KEY_ROTATED_AT,
+  };
+  let token = keys.decode(token, Utc::now().timestamp())?;
+  if !token.has_scope(scope) {
+    return Err(Error::TokenScopeNotAllowed);
+  }
+  Ok(token)
+}
+
+struct SigningKeys<'a> {
+  current: &'a str,
+  current_kid: Option<&'a str>,
+  previous: &'a BTreeMap<String, String>,
+  rotated_at: Option<i64>,
+}
+
+impl<'a> SigningKeys<'a> {
+  fn decode(&self, token: &str, now: i64) -> Result<PlayerToken> {
+    let header = decode_header(token)?;
+    match header.kid {
+      Some(ref kid) if Some(kid.as_str()) != self.current_kid => {
+        let secret = self.previous.get(kid).ok_or(Error::TokenKeyUnknown)?;
+        decode_claims(token, secret)
+      }
+      Some(_) => decode_claims(token, self.current),
+      None => match decode_claims(token, self.current) {
+        Err(Error::JsonWebToken(err))
+          if matches!(err.kind(), ErrorKind::InvalidSignature) && self.in_grace_window(now) =>
+        {
+          for secret in self.previous.values() {
+            match decode_claims(token, secret) {
+              Err(Error::JsonWebToken(_)) => continue,
+              res => return res,
+            }
+          }
+          Err(err.into())
+        }
+        res => res,
+      },
+    }
+  }
+
+  fn in_grace_window(&self, now: i64) -> bool {
+    self
+      .rotated_at
+      .map(|rotated_at| now < rotated_at + TOKEN_EXPIRATION_SECS)
+      .unwrap_or(true)
+  }
+}
+
+fn decode_claims(token: &str, secret: &str) -> Result<PlayerToken> {
+  let decoding_key = DecodingKey::from_base64_secret(secret)?;
   decode(token, &decoding_key, &Validation::default())
     .map(|data| data.claims)
     .map_err(|e| match e.kind() {
@@ -49,3 +200,68 @@ fn test_player_token() {
   let token = validate_player_token(&token).unwrap();
   dbg!(token);
 }
+
+#[test]
+fn test_scoped_token() {
+  dotenv::dotenv().unwrap();
+  let token = issue_scoped_token(100, &[TokenScope::Observer], Some(60), Some(1)).unwrap();
+  let claims = validate_scoped_token(&token, TokenScope::Observer).unwrap();
+  assert_eq!(claims.api_client_id, Some(1));
+  assert!(matches!(
+    validate_player_token(&token),
+    Err(Error::TokenScopeNotAllowed)
+  ));
+  assert!(matches!(
+    issue_scoped_token(100, &[], None, None),
+    Err(Error::TokenScopeRequired)
+  ));
+}
+
+#[test]
+fn test_kid_less_token_rotation() {
+  // base64 of "old-secret" and "new-secret"
+  const OLD: &str = "b2xkLXNlY3JldA==";
+  const NEW: &str = "bmV3LXNlY3JldA==";
+
+  let claims = PlayerToken {
+    sub: TOKEN_SUB.to_string(),
+    player_id: 100,
+    exp: (Utc::now().timestamp() + 60) as usize,
+    scopes: default_scopes(),
+    api_client_id: None,
+  };
+  let token = encode(
+    &Header::default(),
+    &claims,
+    &EncodingKey::from_base64_secret(OLD).unwrap(),
+  )
+  .unwrap();
+
+  let previous: BTreeMap<_, _> = vec![("k1".to_string(), OLD.to_string())]
+    .into_iter()
+    .collect();
+  let now = Utc::now().timestamp();
+  let keys = SigningKeys {
+    current: NEW,
+    current_kid: Some("k2"),
+    previous: &previous,
+    rotated_at: Some(now),
+  };
+  assert_eq!(keys.decode(&token, now).unwrap().player_id, 100);
+
+  // grace window is over
+  assert!(matches!(
+    keys.decode(&token, now + TOKEN_EXPIRATION_SECS),
+    Err(Error::JsonWebToken(_))
+  ));
+
+  let empty = BTreeMap::new();
+  let keys = SigningKeys {
+    previous: &empty,
+    ..keys
+  };
+  assert!(matches!(
+    keys.decode(&token, now),
+    Err(Error::JsonWebToken(_))
+  ));
+}
@@ -0,0 +1,212 @@
+//! Timing aggregation of the lobby hot paths, compiled in with the `profiling` feature.
+//!
+//! Instrumented:
+//! - DB calls of the lobby path, closures wrapped with `db`, named after the calling function
+//! - messages sent to game actors with `ActorMapExt::send_to`, named after the message type
+//! - player broadcasts, named after the fanout kind
+//!
+//! Totals are kept in memory until reset and dumped by `GET /v1/admin/profiling` of the REST API.
+//! Without the feature the hooks are no-ops.
+
+use crate::db::DbConn;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileKind {
+  Db,
+  Actor,
+  Broadcast,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProfileEntry {
+  pub kind: ProfileKind,
+  pub name: String,
+  pub count: u64,
+  pub total_micros: u64,
+  pub mean_micros: u64,
+  pub max_micros: u64,
+}
+
+pub fn is_enabled() -> bool {
+  cfg!(feature = "profiling")
+}
+
+/// Records the time until dropped
+#[must_use]
+pub struct ProfileTimer {
+  #[cfg(feature = "profiling")]
+  key: (ProfileKind, &'static str),
+  #[cfg(feature = "profiling")]
+  started_at: std::time::Instant,
+}
+
+impl Drop for ProfileTimer {
+  fn drop(&mut self) {
+    #[cfg(feature = "profiling")]
+    imp::record(self.key, self.started_at.elapsed());
+  }
+}
+
+#[allow(unused_variables)]
+pub fn start(kind: ProfileKind, name: &'static str) -> ProfileTimer {
+  ProfileTimer {
+    #[cfg(feature = "profiling")]
+    key: (kind, name),
+    #[cfg(feature = "profiling")]
+    started_at: std::time::Instant::now(),
+  }
+}
+
+/// Times a message from sending until the actor's reply
+pub fn actor_message<M>() -> ProfileTimer {
+  start(ProfileKind::Actor, std::any::type_name::<M>())
+}
+
+/// Wraps a `db.exec` closure to time the query on the DB thread, pool wait excluded
+#[cfg(feature = "profiling")]
+pub fn db<F, R>(f: F) -> impl FnOnce(&DbConn) -> R + Send + 'static
+where
+  F: FnOnce(&DbConn) -> R + Send + 'static,
+{
+  let name = std::any::type_name::<F>();
+  move |conn| {
+    let _timer = start(ProfileKind::Db, name);
+    f(conn)
+  }
+}
+
+#[cfg(not(feature = "profiling"))]
+#[inline(always)]
+pub fn db<F, R>(f: F) -> F
+where
+  F: FnOnce(&DbConn) -> R + Send + 'static,
+{
+  f
+}
+
+/// Entries sorted by total time, longest first
+pub fn snapshot() -> Vec<ProfileEntry> {
+  #[cfg(feature = "profiling")]
+  {
+    imp::snapshot()
+  }
+  #[cfg(not(feature = "profiling"))]
+  {
+    vec![]
+  }
+}
+
+pub fn reset() {
+  #[cfg(feature = "profiling")]
+  imp::reset()
+}
+
+// closure type names end with the function that created the closure
+#[allow(dead_code)]
+fn display_name(name: &str) -> &str {
+  let mut name = name;
+  while let Some(v) = name.strip_suffix("::{{closure}}") {
+    name = v;
+  }
+  name.strip_prefix("flo_controller::").unwrap_or(name)
+}
+
+#[cfg(feature = "profiling")]
+mod imp {
+  use super::{display_name, ProfileEntry, ProfileKind};
+  use once_cell::sync::Lazy;
+  use parking_lot::Mutex;
+  use std::collections::HashMap;
+  use std::time::Duration;
+
+  #[derive(Debug, Default, Clone, Copy)]
+  struct Stat {
+    count: u64,
+    total: Duration,
+    max: Duration,
+  }
+
+  static STATS: Lazy<Mutex<HashMap<(ProfileKind, &'static str), Stat>>> =
+    Lazy::new(Default::default);
+
+  pub fn record(key: (ProfileKind, &'static str), elapsed: Duration) {
+    let mut stats = STATS.lock();
+    let stat = stats.entry(key).or_default();
+    stat.count += 1;
+    stat.total += elapsed;
+    stat.max = stat.max.max(elapsed);
+  }
+
+  pub fn snapshot() -> Vec<ProfileEntry> {
+    let stats = STATS.lock().clone();
+    let mut entries: Vec<_> = stats
+      .into_iter()
+      .map(|((kind, name), stat)| ProfileEntry {
+        kind,
+        name: display_name(name).to_string(),
+        count: stat.count,
+        total_micros: stat.total.as_micros() as u64,
+        mean_micros: (stat.total / stat.count as u32).as_micros() as u64,
+        max_micros: stat.max.as_micros() as u64,
+      })
+      .collect();
+    entries.sort_by(|a, b| {
+      b.total_micros
+        .cmp(&a.total_micros)
+        .then_with(|| (a.kind, &a.name).cmp(&(b.kind, &b.name)))
+    });
+    entries
+  }
+
+  pub fn reset() {
+    STATS.lock().clear();
+  }
+}
+
+#[test]
+fn test_display_name() {
+  assert_eq!(
+    display_name(
+      "flo_controller::game::state::join::<impl Handler<PlayerJoin> for GameActor>::handle::{{closure}}::{{closure}}"
+    ),
+    "game::state::join::<impl Handler<PlayerJoin> for GameActor>::handle"
+  );
+  assert_eq!(display_name("all"), "all");
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn test_profiling() {
+  use std::time::Duration;
+
+  reset();
+  imp::record(
+    (ProfileKind::Db, "a::{{closure}}"),
+    Duration::from_millis(3),
+  );
+  imp::record(
+    (ProfileKind::Db, "a::{{closure}}"),
+    Duration::from_millis(1),
+  );
+  imp::record((ProfileKind::Broadcast, "all"), Duration::from_millis(10));
+  drop(start(ProfileKind::Actor, "Ping"));
+  let entries = snapshot();
+  assert_eq!(entries.len(), 3);
+  assert_eq!(entries[0].name, "all");
+  assert_eq!(
+    entries[1],
+    ProfileEntry {
+      kind: ProfileKind::Db,
+      name: "a".to_string(),
+      count: 2,
+      total_micros: 4000,
+      mean_micros: 2000,
+      max_micros: 3000,
+    }
+  );
+  assert_eq!(entries[2].kind, ProfileKind::Actor);
+  reset();
+  assert!(snapshot().is_empty());
+}
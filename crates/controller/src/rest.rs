@@ -0,0 +1,702 @@
+//! REST facade of the gRPC queries and ladder season management,
+//! for integrators that can't speak gRPC.
+//!
+//! Requests are authenticated with the API client secret in the `x-flo-secret` header.
+//!
+//! - `GET /v1/nodes`: nodes compatible with the game target version
+//! - `GET /v1/games?keyword=<keyword>&take=<n>&since_id=<game_id>`: open public games,
+//!   with the lobby metadata set by the host
+//! - `POST /v1/games/:id/rematch`: recreates a game of the API client with the same players,
+//!   slots and settings, the players are notified like for a new game
+//! - `POST /v1/games/:id/resume`: creates a lobby loading the latest save of a game of the
+//!   API client, see `ResumeGameParams`
+//! - `PUT /v1/games/:id/slots/:index`: updates the slot settings of a game of the API client,
+//!   also allowed for games with a locked layout, returns the updated slots
+//! - `GET /v1/players/:id`
+//! - `GET /v1/players?source_ids=<id>,<id>`: players of the API client by source id
+//! - `POST /v1/players/:id/token`: issues a token for a player of the API client,
+//!   signed with the current key, see `IssueTokenParams`
+//! - `GET /v1/seasons`: seasons of the API client, most recent first
+//! - `POST /v1/seasons`: opens a season, see `OpenSeasonParams`
+//! - `POST /v1/seasons/:id/close`
+//! - `GET /v1/seasons/:id/standings?take=<n>&skip=<n>`
+//! - `GET /v1/events/lobby`: server-sent `lobby` events of the games of the API client,
+//!   see `LobbyEvent`
+//!
+//! Admin routes take a player token with the `admin` scope in the `authorization` header
+//! as `Bearer <token>`:
+//!
+//! - `GET /v1/admin/profiling`: timing totals, controllers built with the `profiling` feature only
+//! - `POST /v1/admin/profiling/reset`
+//! - `PUT /v1/admin/nodes/:id/config`: pushes config changes to a connected node, see `NodeConfig`,
+//!   returns the config in effect on the node
+//! - `GET /v1/admin/maintenance`: the active maintenance, or `null`
+//! - `PUT /v1/admin/maintenance`: enables maintenance mode, see `SetMaintenanceParams`,
+//!   new games can't be created or started until it's disabled
+//! - `DELETE /v1/admin/maintenance`
+//! - `GET /v1/admin/smurfs?api_client_id=<id>&days=<n>&min_score=<score>`: accounts likely
+//!   operated by the same person, from the logins of the last `days` days, see `SmurfReport`
+
+use crate::config::{FloGrpcInterceptor, GetInterceptor, REQUEST_META_SECRET};
+use crate::error::{Error, Result, TaskCancelledExt};
+use crate::game::db::{GameStatusFilter, QueryGameParams};
+use crate::game::event::{LobbyEvent, LobbyEventFilter, LobbyEventSender};
+use crate::game::messages::UpdateSlotAsBot;
+use crate::game::{Game, GameEntry, GameMetadata, Slot, SlotSettings};
+use crate::maintenance::{Maintenance, MaintenanceReason};
+use crate::node::config::NodeConfig;
+use crate::node::messages::{ListCompatibleNodes, NodeUpdateConfig};
+use crate::node::version::GAME_TARGET_VERSION;
+use crate::node::NodeRef;
+use crate::player::smurf::{SmurfReport, SmurfReportParams};
+use crate::player::token::{issue_scoped_token, validate_scoped_token, TokenScope};
+use crate::player::PlayerRef;
+use crate::profiling::{self, ProfileEntry};
+use crate::season::{OpenSeasonParams, Season, SeasonStanding, MAX_STANDINGS_TAKE};
+use crate::state::{ActorMapExt, ControllerStateRef};
+use axum::extract::{Extension, Path, Query};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::{delete, get, post, put};
+use axum::{AddExtensionLayer, Json, Router, Server};
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
+const MAX_SOURCE_IDS: usize = 100;
+
+type RestError = (StatusCode, String);
+
+pub async fn serve(state: ControllerStateRef) -> Result<()> {
+  let interceptor = state.config.send(GetInterceptor).await?;
+
+  let app = Router::new()
+    .route("/v1/nodes", get(list_nodes_handler))
+    .route("/v1/games", get(list_games_handler))
+    .route("/v1/games/:id/rematch", post(rematch_game_handler))
+    .route("/v1/games/:id/resume", post(resume_game_handler))
+    .route("/v1/games/:id/slots/:index", put(update_slot_handler))
+    .route("/v1/players", get(list_players_handler))
+    .route("/v1/players/:id", get(get_player_handler))
+    .route("/v1/players/:id/token", post(issue_player_token_handler))
+    .route(
+      "/v1/seasons",
+      get(list_seasons_handler).post(open_season_handler),
+    )
+    .route("/v1/seasons/:id/close", post(close_season_handler))
+    .route("/v1/seasons/:id/standings", get(get_standings_handler))
+    .route("/v1/events/lobby", get(lobby_events_handler))
+    .route("/v1/admin/profiling", get(get_profiling_handler))
+    .route("/v1/admin/profiling/reset", post(reset_profiling_handler))
+    .route(
+      "/v1/admin/nodes/:id/config",
+      put(update_node_config_handler),
+    )
+    .route("/v1/admin/smurfs", get(get_smurf_report_handler))
+    .route(
+      "/v1/admin/maintenance",
+      get(get_maintenance_handler)
+        .put(set_maintenance_handler)
+        .delete(clear_maintenance_handler),
+    )
+    .layer(AddExtensionLayer::new(state))
+    .layer(AddExtensionLayer::new(interceptor));
+
+  let addr = SocketAddr::from(SocketAddrV4::new(
+    Ipv4Addr::UNSPECIFIED,
+    flo_constants::CONTROLLER_REST_PORT,
+  ));
+
+  Server::bind(&addr).serve(app.into_make_service()).await?;
+  Ok(())
+}
+
+/// Returns the API client id
+fn authorize(headers: &HeaderMap, interceptor: &FloGrpcInterceptor) -> Result<i32, RestError> {
+  let secret = headers.get(REQUEST_META_SECRET).ok_or_else(|| {
+    (
+      StatusCode::UNAUTHORIZED,
+      "`x-flo-secret` header was not found".to_string(),
+    )
+  })?;
+  interceptor
+    .get_api_client_id(secret.as_bytes())
+    .ok_or_else(|| (StatusCode::UNAUTHORIZED, "invalid secret".to_string()))
+}
+
+fn authorize_admin(headers: &HeaderMap) -> Result<(), RestError> {
+  let token = headers
+    .get(AUTHORIZATION)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.strip_prefix("Bearer "))
+    .ok_or_else(|| {
+      (
+        StatusCode::UNAUTHORIZED,
+        "bearer token was not found".to_string(),
+      )
+    })?;
+  validate_scoped_token(token, TokenScope::Admin)
+    .map(|_| ())
+    .map_err(|err| match err {
+      Error::TokenScopeNotAllowed => (StatusCode::FORBIDDEN, err.to_string()),
+      err => (StatusCode::UNAUTHORIZED, err.to_string()),
+    })
+}
+
+fn error_response(err: Error) -> RestError {
+  match err {
+    Error::PlayerNotFound
+    | Error::SeasonNotFound
+    | Error::GameNotFound
+    | Error::NodeNotFound
+    | Error::GameSaveNotFound => (StatusCode::NOT_FOUND, err.to_string()),
+    Error::NodeConfigRejected(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+    Error::NodeNotReady | Error::NodeRequestTimeout => {
+      (StatusCode::SERVICE_UNAVAILABLE, err.to_string())
+    }
+    Error::SeasonInvalid(_) | Error::TokenScopeRequired => {
+      (StatusCode::BAD_REQUEST, err.to_string())
+    }
+    Error::PlayerOwnerCheckFailed | Error::TokenScopeNotAllowed => {
+      (StatusCode::FORBIDDEN, err.to_string())
+    }
+    Error::GameNodeNotSelected | Error::GameStarted | Error::GameSaveInvalid => {
+      (StatusCode::CONFLICT, err.to_string())
+    }
+    Error::SeasonOpen | Error::SeasonClosed => (StatusCode::CONFLICT, err.to_string()),
+    err => {
+      tracing::error!("rest: {}", err);
+      (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+    }
+  }
+}
+
+async fn list_nodes_handler(
+  headers: HeaderMap,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<Vec<NodeRef>>, RestError> {
+  authorize(&headers, &interceptor)?;
+  let nodes = state
+    .nodes
+    .send(ListCompatibleNodes {
+      war3_version: GAME_TARGET_VERSION.clone(),
+    })
+    .await
+    .map_err(|err| error_response(err.into()))?;
+  Ok(Json(nodes.into_iter().map(Into::into).collect()))
+}
+
+#[derive(Debug, Deserialize)]
+struct ListGamesQuery {
+  keyword: Option<String>,
+  take: Option<i64>,
+  since_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+struct ListGamesResponse {
+  games: Vec<ListGamesEntry>,
+  has_more: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ListGamesEntry {
+  #[serde(flatten)]
+  entry: GameEntry,
+  metadata: GameMetadata,
+}
+
+async fn list_games_handler(
+  headers: HeaderMap,
+  Query(query): Query<ListGamesQuery>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<ListGamesResponse>, RestError> {
+  authorize(&headers, &interceptor)?;
+  let params = QueryGameParams {
+    keyword: query.keyword,
+    status: GameStatusFilter::Open,
+    is_private: Some(false),
+    is_live: None,
+    take: query.take,
+    since_id: query.since_id,
+  };
+  let res = state
+    .db
+    .exec(move |conn| {
+      let res = crate::game::db::query(conn, &params)?;
+      let ids: Vec<i32> = res.games.iter().map(|game| game.id).collect();
+      let metadata = crate::game::db::get_metadata_map(conn, &ids)?;
+      Ok::<_, Error>((res, metadata))
+    })
+    .await
+    .map_err(|err| error_response(err.into()))?;
+  let (res, mut metadata) = res;
+  Ok(Json(ListGamesResponse {
+    games: res
+      .games
+      .into_iter()
+      .map(|entry| ListGamesEntry {
+        metadata: metadata.remove(&entry.id).unwrap_or_default(),
+        entry,
+      })
+      .collect(),
+    has_more: res.has_more,
+  }))
+}
+
+async fn rematch_game_handler(
+  headers: HeaderMap,
+  Path(game_id): Path<i32>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<Game>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let game = state
+    .rematch_as_bot(api_client_id, game_id)
+    .await
+    .map_err(error_response)?;
+  Ok(Json(game))
+}
+
+/// Warcraft III only lists a saved game if the save file of the player has the same magic number,
+/// it's stored in the save file and can't be derived from the save actions seen by the node
+#[derive(Debug, Deserialize)]
+struct ResumeGameParams {
+  magic_number: u32,
+}
+
+async fn resume_game_handler(
+  headers: HeaderMap,
+  Path(game_id): Path<i32>,
+  Json(params): Json<ResumeGameParams>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<Game>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let game = state
+    .resume_as_bot(api_client_id, game_id, params.magic_number)
+    .await
+    .map_err(error_response)?;
+  Ok(Json(game))
+}
+
+async fn update_slot_handler(
+  headers: HeaderMap,
+  Path((game_id, slot_index)): Path<(i32, i32)>,
+  Json(settings): Json<SlotSettings>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<Vec<Slot>>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let slots = state
+    .games
+    .send_to(
+      game_id,
+      UpdateSlotAsBot {
+        api_client_id,
+        slot_index,
+        settings,
+      },
+    )
+    .await
+    .map_err(|err| match err {
+      Error::ActorNotFound => error_response(Error::GameNotFound),
+      err => error_response(err),
+    })?;
+  Ok(Json(slots))
+}
+
+async fn get_player_handler(
+  headers: HeaderMap,
+  Path(player_id): Path<i32>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<PlayerRef>, RestError> {
+  authorize(&headers, &interceptor)?;
+  let player = state
+    .db
+    .exec(move |conn| crate::player::db::get_ref(conn, player_id))
+    .await
+    .map_err(|err| error_response(err.into()))?;
+  Ok(Json(player))
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueTokenParams {
+  /// Defaults to `connect`, the `admin` scope can't be requested
+  #[serde(default)]
+  scopes: Vec<TokenScope>,
+  /// Capped to the default token lifetime
+  expires_in_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct IssueTokenResponse {
+  token: String,
+}
+
+async fn issue_player_token_handler(
+  headers: HeaderMap,
+  Path(player_id): Path<i32>,
+  Json(params): Json<IssueTokenParams>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<IssueTokenResponse>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let scopes = token_scopes(params.scopes).map_err(error_response)?;
+  state
+    .db
+    .exec(move |conn| crate::player::db::check_player_api_client_id(conn, api_client_id, player_id))
+    .await
+    .map_err(|err| error_response(err.into()))?;
+  let token = issue_scoped_token(
+    player_id,
+    &scopes,
+    params.expires_in_secs,
+    Some(api_client_id),
+  )
+  .map_err(error_response)?;
+  Ok(Json(IssueTokenResponse { token }))
+}
+
+fn token_scopes(scopes: Vec<TokenScope>) -> Result<Vec<TokenScope>> {
+  if scopes.contains(&TokenScope::Admin) {
+    return Err(Error::TokenScopeNotAllowed);
+  }
+  if scopes.is_empty() {
+    return Ok(vec![TokenScope::Connect]);
+  }
+  Ok(scopes)
+}
+
+#[derive(Debug, Deserialize)]
+struct ListPlayersQuery {
+  /// Comma separated
+  source_ids: String,
+}
+
+async fn list_players_handler(
+  headers: HeaderMap,
+  Query(query): Query<ListPlayersQuery>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<HashMap<String, PlayerRef>>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let source_ids = parse_source_ids(&query.source_ids);
+  if source_ids.len() > MAX_SOURCE_IDS {
+    return Err((
+      StatusCode::BAD_REQUEST,
+      format!("at most {} source ids are allowed", MAX_SOURCE_IDS),
+    ));
+  }
+  let players = state
+    .db
+    .exec(move |conn| {
+      crate::player::db::get_player_map_by_api_source_ids(conn, api_client_id, source_ids)
+    })
+    .await
+    .map_err(|err| error_response(err.into()))?;
+  Ok(Json(players))
+}
+
+async fn list_seasons_handler(
+  headers: HeaderMap,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<Vec<Season>>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let seasons = state
+    .db
+    .exec(move |conn| crate::season::db::list(conn, api_client_id))
+    .await
+    .map_err(|err| error_response(err.into()))?;
+  Ok(Json(seasons))
+}
+
+async fn open_season_handler(
+  headers: HeaderMap,
+  Json(params): Json<OpenSeasonParams>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<Season>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let season = crate::season::open(&state, api_client_id, params)
+    .await
+    .map_err(error_response)?;
+  Ok(Json(season))
+}
+
+async fn close_season_handler(
+  headers: HeaderMap,
+  Path(season_id): Path<i32>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<Season>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let season = crate::season::close(&state, api_client_id, season_id)
+    .await
+    .map_err(error_response)?;
+  Ok(Json(season))
+}
+
+#[derive(Debug, Deserialize)]
+struct GetStandingsQuery {
+  take: Option<i64>,
+  skip: Option<i64>,
+}
+
+async fn get_standings_handler(
+  headers: HeaderMap,
+  Path(season_id): Path<i32>,
+  Query(query): Query<GetStandingsQuery>,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Json<Vec<SeasonStanding>>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let take = query.take.unwrap_or(100).clamp(1, MAX_STANDINGS_TAKE);
+  let skip = query.skip.unwrap_or(0).max(0);
+  let standings = state
+    .db
+    .exec(move |conn| {
+      crate::season::db::get(conn, api_client_id, season_id)?;
+      crate::season::db::get_standings(conn, season_id, take, skip)
+    })
+    .await
+    .map_err(|err| error_response(err.into()))?;
+  Ok(Json(standings))
+}
+
+async fn lobby_events_handler(
+  headers: HeaderMap,
+  Extension(state): Extension<ControllerStateRef>,
+  Extension(interceptor): Extension<FloGrpcInterceptor>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, RestError> {
+  let api_client_id = authorize(&headers, &interceptor)?;
+  let stream = lobby_event_stream(
+    &state.lobby_events,
+    LobbyEventFilter {
+      api_client_id: Some(api_client_id),
+    },
+  );
+  Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn lobby_event_stream(
+  sender: &LobbyEventSender,
+  filter: LobbyEventFilter,
+) -> impl Stream<Item = Result<Event, axum::Error>> {
+  sender
+    .subscribe(filter)
+    .map(|event: LobbyEvent| Event::default().event("lobby").json_data(event))
+}
+
+async fn get_profiling_handler(headers: HeaderMap) -> Result<Json<Vec<ProfileEntry>>, RestError> {
+  authorize_admin(&headers)?;
+  if !profiling::is_enabled() {
+    return Err((
+      StatusCode::NOT_IMPLEMENTED,
+      "the controller was built without the `profiling` feature".to_string(),
+    ));
+  }
+  Ok(Json(profiling::snapshot()))
+}
+
+async fn reset_profiling_handler(headers: HeaderMap) -> Result<StatusCode, RestError> {
+  authorize_admin(&headers)?;
+  profiling::reset();
+  Ok(StatusCode::NO_CONTENT)
+}
+
+async fn update_node_config_handler(
+  headers: HeaderMap,
+  Path(node_id): Path<i32>,
+  Json(config): Json<NodeConfig>,
+  Extension(state): Extension<ControllerStateRef>,
+) -> Result<Json<NodeConfig>, RestError> {
+  authorize_admin(&headers)?;
+  let config = state
+    .nodes
+    .send_to(node_id, NodeUpdateConfig { config })
+    .await
+    .map_err(|err| match err {
+      Error::ActorNotFound => error_response(Error::NodeNotFound),
+      err => error_response(err),
+    })?
+    .await
+    .or_cancelled()
+    .map_err(error_response)?;
+  Ok(Json(config))
+}
+
+async fn get_maintenance_handler(
+  headers: HeaderMap,
+  Extension(state): Extension<ControllerStateRef>,
+) -> Result<Json<Option<Maintenance>>, RestError> {
+  authorize_admin(&headers)?;
+  Ok(Json(state.maintenance.get()))
+}
+
+#[derive(Debug, Deserialize)]
+struct SetMaintenanceParams {
+  reason: MaintenanceReason,
+  /// Shown to players, defaults to a message for the reason
+  message: Option<String>,
+}
+
+impl SetMaintenanceParams {
+  fn into_maintenance(self) -> Maintenance {
+    Maintenance::new(self.reason, self.message)
+  }
+}
+
+async fn set_maintenance_handler(
+  headers: HeaderMap,
+  Json(params): Json<SetMaintenanceParams>,
+  Extension(state): Extension<ControllerStateRef>,
+) -> Result<Json<Option<Maintenance>>, RestError> {
+  authorize_admin(&headers)?;
+  state
+    .set_maintenance(Some(params.into_maintenance()))
+    .await
+    .map_err(error_response)?;
+  Ok(Json(state.maintenance.get()))
+}
+
+async fn clear_maintenance_handler(
+  headers: HeaderMap,
+  Extension(state): Extension<ControllerStateRef>,
+) -> Result<StatusCode, RestError> {
+  authorize_admin(&headers)?;
+  state.set_maintenance(None).await.map_err(error_response)?;
+  Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct SmurfReportQuery {
+  api_client_id: Option<i32>,
+  days: Option<i64>,
+  min_score: Option<f32>,
+}
+
+impl SmurfReportQuery {
+  fn into_params(self) -> SmurfReportParams {
+    // older logins are pruned
+    let max_days = crate::player::smurf::login_retention().num_days();
+    let days = self.days.unwrap_or(30).clamp(1, max_days);
+    SmurfReportParams {
+      api_client_id: self.api_client_id,
+      since: chrono::Utc::now() - chrono::Duration::days(days),
+      min_score: self.min_score.unwrap_or(0.5),
+    }
+  }
+}
+
+async fn get_smurf_report_handler(
+  headers: HeaderMap,
+  Query(query): Query<SmurfReportQuery>,
+  Extension(state): Extension<ControllerStateRef>,
+) -> Result<Json<SmurfReport>, RestError> {
+  authorize_admin(&headers)?;
+  let params = query.into_params();
+  let report = state
+    .db
+    .exec(move |conn| crate::player::smurf::generate_report(conn, &params))
+    .await
+    .map_err(|err| error_response(err.into()))?;
+  Ok(Json(report))
+}
+
+fn parse_source_ids(value: &str) -> Vec<String> {
+  value
+    .split(',')
+    .map(str::trim)
+    .filter(|v| !v.is_empty())
+    .map(ToString::to_string)
+    .collect()
+}
+
+#[test]
+fn test_parse_source_ids() {
+  assert!(parse_source_ids("").is_empty());
+  assert_eq!(parse_source_ids("a, b,,c "), vec!["a", "b", "c"]);
+}
+
+#[test]
+fn test_set_maintenance_params() {
+  use crate::maintenance::MaintenanceState;
+
+  let params: SetMaintenanceParams =
+    serde_json::from_str(r#"{"reason":"Upgrade","message":"back in 10 minutes"}"#).unwrap();
+  let state = MaintenanceState::default();
+  assert!(state.check().is_ok());
+
+  assert!(state.set(Some(params.into_maintenance())).is_some());
+  // new lobbies are rejected
+  assert!(matches!(
+    state.check(),
+    Err(Error::Maintenance(ref message)) if message == "back in 10 minutes"
+  ));
+
+  assert!(state.set(None).is_some());
+  assert!(state.check().is_ok());
+}
+
+#[test]
+fn test_smurf_report_query() {
+  let params = SmurfReportQuery {
+    api_client_id: Some(1),
+    days: Some(100_000),
+    min_score: None,
+  }
+  .into_params();
+  assert_eq!(params.api_client_id, Some(1));
+  assert_eq!(params.min_score, 0.5);
+  let since = chrono::Utc::now() - crate::player::smurf::login_retention();
+  assert!((params.since - since).num_seconds().abs() < 60);
+}
+
+#[test]
+fn test_token_scopes() {
+  assert_eq!(token_scopes(vec![]).unwrap(), vec![TokenScope::Connect]);
+  assert_eq!(
+    token_scopes(vec![TokenScope::Observer]).unwrap(),
+    vec![TokenScope::Observer]
+  );
+  assert!(matches!(
+    token_scopes(vec![TokenScope::Connect, TokenScope::Admin]),
+    Err(Error::TokenScopeNotAllowed)
+  ));
+}
+
+#[test]
+fn test_lobby_event_stream() {
+  use crate::game::event::LobbyEventKind;
+
+  let sender = LobbyEventSender::new();
+  sender.register_game(1, Some(7));
+  sender.register_game(2, Some(8));
+  let mut stream = Box::pin(lobby_event_stream(
+    &sender,
+    LobbyEventFilter {
+      api_client_id: Some(7),
+    },
+  ));
+
+  sender.send(2, LobbyEventKind::Cancelled);
+  sender.send(1, LobbyEventKind::PlayerJoined { player_id: 3 });
+  drop(sender);
+
+  let events: Vec<_> = futures::executor::block_on(async {
+    let mut events = vec![];
+    while let Some(event) = stream.next().await {
+      events.push(event);
+    }
+    events
+  });
+  assert_eq!(events.len(), 1);
+  assert!(events[0].is_ok());
+}
@@ -7,6 +7,97 @@ table! {
     }
 }
 
+table! {
+    api_client_result_policy (api_client_id) {
+        api_client_id -> Int4,
+        source -> Int4,
+        on_conflict -> Int4,
+        require_all_players -> Bool,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    chat_channel (id) {
+        id -> Int4,
+        kind -> Int4,
+        key -> Text,
+        name -> Text,
+        restricted -> Bool,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    chat_channel_member (id) {
+        id -> Int4,
+        channel_id -> Int4,
+        player_id -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    chat_message (id) {
+        id -> Int8,
+        channel_id -> Int4,
+        player_id -> Int4,
+        content -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    clan (id) {
+        id -> Int4,
+        tag -> Text,
+        name -> Text,
+        created_by -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    clan_invite (id) {
+        id -> Int4,
+        clan_id -> Int4,
+        player_id -> Int4,
+        invited_by -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    clan_match (id) {
+        id -> Int4,
+        game_id -> Int4,
+        clan_id -> Int4,
+        opponent_clan_id -> Int4,
+        winner_clan_id -> Nullable<Int4>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    clan_member (id) {
+        id -> Int4,
+        clan_id -> Int4,
+        player_id -> Int4,
+        role -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    feature_flag (name) {
+        name -> Text,
+        enabled -> Bool,
+        rollout_percent -> Int4,
+        player_ids -> Array<Int4>,
+        updated_at -> Timestamptz,
+    }
+}
+
 table! {
     game (id) {
         id -> Int4,
@@ -28,6 +119,22 @@ table! {
         locked -> Bool,
         mask_player_names -> Bool,
         game_version -> Nullable<Text>,
+        start_requested_at -> Nullable<Timestamptz>,
+        season_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    game_result (id) {
+        id -> Int4,
+        game_id -> Int4,
+        status -> Int4,
+        source -> Int4,
+        conflict -> Bool,
+        map_error -> Nullable<Text>,
+        players -> Jsonb,
+        created_at -> Timestamptz,
+        season_id -> Nullable<Int4>,
     }
 }
 
@@ -51,6 +158,33 @@ table! {
     }
 }
 
+table! {
+    job (id) {
+        id -> Int4,
+        kind -> Text,
+        payload -> Jsonb,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        run_at -> Timestamptz,
+        locked_until -> Timestamptz,
+        last_error -> Nullable<Text>,
+        failed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    lobby_summary (id) {
+        id -> Int4,
+        game_id -> Int4,
+        reason -> Int4,
+        message -> Text,
+        players -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     map_checksum (id) {
         id -> Int4,
@@ -70,6 +204,7 @@ table! {
         updated_at -> Timestamptz,
         country_id -> Text,
         disabled -> Bool,
+        war3_versions -> Nullable<Array<Text>>,
     }
 }
 
@@ -97,6 +232,16 @@ table! {
     }
 }
 
+table! {
+    player_login (id) {
+        id -> Int4,
+        player_id -> Int4,
+        ip_addr -> Text,
+        client_hint -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     player_mute (id) {
         id -> Int4,
@@ -106,20 +251,127 @@ table! {
     }
 }
 
+table! {
+    player_notification_subscription (id) {
+        id -> Int4,
+        player_id -> Int4,
+        channel -> Int4,
+        target -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    player_offense (id) {
+        id -> Int4,
+        player_id -> Int4,
+        game_id -> Nullable<Int4>,
+        kind -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    player_privacy (player_id) {
+        player_id -> Int4,
+        hide_profile -> Bool,
+        anonymize -> Bool,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    registered_map (id) {
+        id -> Int4,
+        sha1 -> Bytea,
+        name -> Text,
+        map -> Jsonb,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    season (id) {
+        id -> Int4,
+        api_client_id -> Int4,
+        name -> Text,
+        reset_policy -> Int4,
+        starts_at -> Timestamptz,
+        ends_at -> Nullable<Timestamptz>,
+        closed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    season_standing (id) {
+        id -> Int4,
+        season_id -> Int4,
+        player_id -> Int4,
+        rating -> Float8,
+        wins -> Int4,
+        losses -> Int4,
+        draws -> Int4,
+        rank -> Nullable<Int4>,
+        updated_at -> Timestamptz,
+    }
+}
+
+joinable!(api_client_result_policy -> api_client (api_client_id));
+joinable!(chat_channel_member -> chat_channel (channel_id));
+joinable!(chat_channel_member -> player (player_id));
+joinable!(chat_message -> chat_channel (channel_id));
+joinable!(chat_message -> player (player_id));
+joinable!(clan_invite -> clan (clan_id));
+joinable!(clan_match -> game (game_id));
+joinable!(clan_member -> clan (clan_id));
+joinable!(clan_member -> player (player_id));
 joinable!(game -> node (node_id));
 joinable!(game -> player (created_by));
+joinable!(game -> season (season_id));
+joinable!(game_result -> game (game_id));
+joinable!(game_result -> season (season_id));
 joinable!(game_used_slot -> game (game_id));
 joinable!(game_used_slot -> player (player_id));
+joinable!(lobby_summary -> game (game_id));
 joinable!(player -> api_client (api_client_id));
 joinable!(player_ban -> player (player_id));
+joinable!(player_login -> player (player_id));
+joinable!(player_notification_subscription -> player (player_id));
+joinable!(player_offense -> game (game_id));
+joinable!(player_offense -> player (player_id));
+joinable!(player_privacy -> player (player_id));
+joinable!(season -> api_client (api_client_id));
+joinable!(season_standing -> player (player_id));
+joinable!(season_standing -> season (season_id));
 
 allow_tables_to_appear_in_same_query!(
-    api_client,
-    game,
-    game_used_slot,
-    map_checksum,
-    node,
-    player,
-    player_ban,
-    player_mute,
+  api_client,
+  api_client_result_policy,
+  chat_channel,
+  chat_channel_member,
+  chat_message,
+  clan,
+  clan_invite,
+  clan_match,
+  clan_member,
+  feature_flag,
+  game,
+  game_result,
+  game_used_slot,
+  job,
+  lobby_summary,
+  map_checksum,
+  node,
+  player,
+  player_ban,
+  player_login,
+  player_mute,
+  player_notification_subscription,
+  player_offense,
+  player_privacy,
+  registered_map,
+  season,
+  season_standing,
 );
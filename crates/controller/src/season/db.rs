@@ -0,0 +1,351 @@
+use crate::db::DbConn;
+use crate::error::*;
+use crate::game::result::{GameResult, GameResultStatus, PlayerResult, ResultSlot};
+use crate::job::JobPayload;
+use crate::player::PlayerRef;
+use crate::schema::{game, player, season, season_standing};
+use crate::season::{
+  rating_changes, OpenSeasonParams, RatedPlayer, Season, SeasonStanding, INITIAL_RATING,
+};
+use chrono::Utc;
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+type SeasonColumns = (
+  season::id,
+  season::api_client_id,
+  season::name,
+  season::reset_policy,
+  season::starts_at,
+  season::ends_at,
+  season::closed_at,
+  season::created_at,
+);
+
+const SEASON_COLUMNS: SeasonColumns = (
+  season::id,
+  season::api_client_id,
+  season::name,
+  season::reset_policy,
+  season::starts_at,
+  season::ends_at,
+  season::closed_at,
+  season::created_at,
+);
+
+/// Returns a season of the API client
+pub fn get(conn: &DbConn, api_client_id: i32, season_id: i32) -> Result<Season> {
+  season::table
+    .find(season_id)
+    .filter(season::api_client_id.eq(api_client_id))
+    .select(SEASON_COLUMNS)
+    .first(conn)
+    .optional()?
+    .ok_or_else(|| Error::SeasonNotFound)
+}
+
+/// Most recent first
+pub fn list(conn: &DbConn, api_client_id: i32) -> Result<Vec<Season>> {
+  season::table
+    .filter(season::api_client_id.eq(api_client_id))
+    .select(SEASON_COLUMNS)
+    .order(season::id.desc())
+    .load(conn)
+    .map_err(Into::into)
+}
+
+/// Returns the id of the season the API client is running now
+pub fn get_current_id(conn: &DbConn, api_client_id: i32) -> Result<Option<i32>> {
+  let now = Utc::now();
+  season::table
+    .filter(
+      season::api_client_id
+        .eq(api_client_id)
+        .and(season::closed_at.is_null())
+        .and(season::starts_at.le(now))
+        .and(season::ends_at.is_null().or(season::ends_at.gt(now))),
+    )
+    .select(season::id)
+    .first(conn)
+    .optional()
+    .map_err(Into::into)
+}
+
+pub fn get_game_season_id(conn: &DbConn, game_id: i32) -> Result<Option<i32>> {
+  game::table
+    .find(game_id)
+    .select(game::season_id)
+    .first::<Option<i32>>(conn)
+    .optional()
+    .map(Option::flatten)
+    .map_err(Into::into)
+}
+
+/// Inserts the season, carries over ratings of the previous season by the reset policy,
+/// and schedules closing the season at its end
+pub fn open(conn: &DbConn, api_client_id: i32, params: &OpenSeasonParams) -> Result<Season> {
+  #[derive(Insertable)]
+  #[table_name = "season"]
+  struct Insert<'a> {
+    api_client_id: i32,
+    name: &'a str,
+    reset_policy: crate::season::SeasonResetPolicy,
+    starts_at: chrono::DateTime<Utc>,
+    ends_at: Option<chrono::DateTime<Utc>>,
+  }
+
+  #[derive(Insertable)]
+  #[table_name = "season_standing"]
+  struct StandingInsert {
+    season_id: i32,
+    player_id: i32,
+    rating: f64,
+  }
+
+  conn.transaction(|| {
+    let open_exists = diesel::select(diesel::dsl::exists(
+      season::table.filter(
+        season::api_client_id
+          .eq(api_client_id)
+          .and(season::closed_at.is_null()),
+      ),
+    ))
+    .get_result::<bool>(conn)?;
+    if open_exists {
+      return Err(Error::SeasonOpen);
+    }
+
+    let previous_id: Option<i32> = season::table
+      .filter(season::api_client_id.eq(api_client_id))
+      .select(season::id)
+      .order(season::closed_at.desc())
+      .first(conn)
+      .optional()?;
+
+    let season: Season = diesel::insert_into(season::table)
+      .values(&Insert {
+        api_client_id,
+        name: params.name.trim(),
+        reset_policy: params.reset_policy,
+        starts_at: params.starts_at.unwrap_or_else(Utc::now),
+        ends_at: params.ends_at,
+      })
+      .returning(SEASON_COLUMNS)
+      .get_result(conn)?;
+
+    if let Some(previous_id) = previous_id {
+      let ratings: Vec<(i32, f64)> = season_standing::table
+        .filter(season_standing::season_id.eq(previous_id))
+        .select((season_standing::player_id, season_standing::rating))
+        .load(conn)?;
+      let inserts: Vec<_> = ratings
+        .into_iter()
+        .filter_map(|(player_id, rating)| {
+          season
+            .reset_policy
+            .carry_over(rating)
+            .map(|rating| StandingInsert {
+              season_id: season.id,
+              player_id,
+              rating,
+            })
+        })
+        .collect();
+      if !inserts.is_empty() {
+        diesel::insert_into(season_standing::table)
+          .values(&inserts)
+          .execute(conn)?;
+      }
+    }
+
+    if let Some(ends_at) = season.ends_at {
+      crate::job::db::enqueue(
+        conn,
+        &JobPayload::SeasonClose {
+          season_id: season.id,
+        },
+        ends_at,
+      )?;
+    }
+
+    Ok(season)
+  })
+}
+
+/// Closes the season and records the final ranks.
+/// `api_client_id` is checked if set, scheduled closes run without it.
+pub fn close(conn: &DbConn, season_id: i32, api_client_id: Option<i32>) -> Result<Season> {
+  use diesel::sql_types::Integer;
+
+  conn.transaction(|| {
+    let (owner_id, closed_at): (i32, Option<chrono::DateTime<Utc>>) = season::table
+      .find(season_id)
+      .select((season::api_client_id, season::closed_at))
+      .for_update()
+      .first(conn)
+      .optional()?
+      .ok_or_else(|| Error::SeasonNotFound)?;
+    if api_client_id.map(|id| id != owner_id).unwrap_or(false) {
+      return Err(Error::SeasonNotFound);
+    }
+    if closed_at.is_some() {
+      return Err(Error::SeasonClosed);
+    }
+
+    diesel::sql_query(
+      "UPDATE season_standing s SET rank = r.rank FROM (\
+        SELECT id, (row_number() OVER (ORDER BY rating DESC, player_id))::integer AS rank \
+        FROM season_standing WHERE season_id = $1\
+      ) r WHERE s.id = r.id",
+    )
+    .bind::<Integer, _>(season_id)
+    .execute(conn)?;
+
+    diesel::update(season::table.find(season_id))
+      .set(season::closed_at.eq(Utc::now()))
+      .returning(SEASON_COLUMNS)
+      .get_result(conn)
+      .map_err(Into::into)
+  })
+}
+
+/// Ordered by rating, the rank of closed seasons follows the same order
+pub fn get_standings(
+  conn: &DbConn,
+  season_id: i32,
+  take: i64,
+  skip: i64,
+) -> Result<Vec<SeasonStanding>> {
+  let rows: Vec<(PlayerRef, f64, i32, i32, i32, Option<i32>)> = season_standing::table
+    .inner_join(player::table)
+    .select((
+      PlayerRef::COLUMNS,
+      season_standing::rating,
+      season_standing::wins,
+      season_standing::losses,
+      season_standing::draws,
+      season_standing::rank,
+    ))
+    .filter(season_standing::season_id.eq(season_id))
+    .order((season_standing::rating.desc(), season_standing::player_id))
+    .limit(take)
+    .offset(skip)
+    .load(conn)?;
+  Ok(
+    rows
+      .into_iter()
+      .map(
+        |(player, rating, wins, losses, draws, rank)| SeasonStanding {
+          player,
+          rating,
+          wins,
+          losses,
+          draws,
+          rank,
+        },
+      )
+      .collect(),
+  )
+}
+
+/// Updates the standings of the players of an accepted result,
+/// results of games that end after the season closed don't count
+pub fn apply_result(
+  conn: &DbConn,
+  season_id: i32,
+  slots: &[ResultSlot],
+  result: &GameResult,
+) -> Result<()> {
+  #[derive(Insertable)]
+  #[table_name = "season_standing"]
+  struct Upsert {
+    season_id: i32,
+    player_id: i32,
+    rating: f64,
+    wins: i32,
+    losses: i32,
+    draws: i32,
+  }
+
+  if result.status != GameResultStatus::Accepted {
+    return Ok(());
+  }
+  let closed_at: Option<chrono::DateTime<Utc>> = season::table
+    .find(season_id)
+    .select(season::closed_at)
+    .first(conn)?;
+  if closed_at.is_some() {
+    return Ok(());
+  }
+
+  let player_ids: Vec<i32> = slots
+    .iter()
+    .filter(|slot| result.players.contains_key(&slot.player_id))
+    .map(|slot| slot.player_id)
+    .collect();
+  let ratings: HashMap<i32, (f64, i32, i32, i32)> = season_standing::table
+    .filter(
+      season_standing::season_id
+        .eq(season_id)
+        .and(season_standing::player_id.eq_any(&player_ids)),
+    )
+    .select((
+      season_standing::player_id,
+      (
+        season_standing::rating,
+        season_standing::wins,
+        season_standing::losses,
+        season_standing::draws,
+      ),
+    ))
+    .for_update()
+    .load::<(i32, (f64, i32, i32, i32))>(conn)?
+    .into_iter()
+    .collect();
+
+  let players: Vec<RatedPlayer> = slots
+    .iter()
+    .filter_map(|slot| {
+      let result = *result.players.get(&slot.player_id)?;
+      Some(RatedPlayer {
+        player_id: slot.player_id,
+        team: slot.team,
+        rating: ratings
+          .get(&slot.player_id)
+          .map(|v| v.0)
+          .unwrap_or(INITIAL_RATING),
+        result,
+      })
+    })
+    .collect();
+  let changes = rating_changes(&players);
+
+  for player in players {
+    let (_, wins, losses, draws) =
+      ratings
+        .get(&player.player_id)
+        .cloned()
+        .unwrap_or((INITIAL_RATING, 0, 0, 0));
+    let upsert = Upsert {
+      season_id,
+      player_id: player.player_id,
+      rating: player.rating + changes.get(&player.player_id).cloned().unwrap_or(0.),
+      wins: wins + (player.result == PlayerResult::Win) as i32,
+      losses: losses + (player.result == PlayerResult::Loss) as i32,
+      draws: draws + (player.result == PlayerResult::Draw) as i32,
+    };
+    diesel::insert_into(season_standing::table)
+      .values(&upsert)
+      .on_conflict((season_standing::season_id, season_standing::player_id))
+      .do_update()
+      .set((
+        season_standing::rating.eq(upsert.rating),
+        season_standing::wins.eq(upsert.wins),
+        season_standing::losses.eq(upsert.losses),
+        season_standing::draws.eq(upsert.draws),
+        season_standing::updated_at.eq(diesel::dsl::now),
+      ))
+      .execute(conn)?;
+  }
+  Ok(())
+}
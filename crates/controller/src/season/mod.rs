@@ -0,0 +1,235 @@
+//! Ladder seasons.
+//!
+//! A season belongs to the API client (ladder) that opened it. Games the ladder creates while the
+//! season is running are attached to it, and their accepted results update the season standings:
+//! wins, losses, draws and an Elo rating. Opening a season applies its reset policy to the
+//! ratings of the ladder's previous season. Closing a season freezes the final ranks, standings
+//! of closed seasons stay queryable as archived leaderboards.
+
+pub mod db;
+
+use crate::error::*;
+use crate::game::result::PlayerResult;
+use crate::player::PlayerRef;
+use crate::state::ControllerState;
+use bs_diesel_utils::BSDieselEnum;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const INITIAL_RATING: f64 = 1500.;
+const RATING_K: f64 = 32.;
+const SEASON_NAME_CHARS: std::ops::RangeInclusive<usize> = 1..=64;
+pub const MAX_STANDINGS_TAKE: i64 = 500;
+
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, BSDieselEnum)]
+#[serde(rename_all = "snake_case")]
+#[repr(i32)]
+pub enum SeasonResetPolicy {
+  /// Ratings carry over unchanged
+  Keep = 0,
+  /// Ratings move halfway back to the initial rating
+  Soft = 1,
+  /// Everyone starts at the initial rating
+  Hard = 2,
+}
+
+impl SeasonResetPolicy {
+  /// Starting rating of a player that ended the previous season with `rating`,
+  /// `None` if the player starts fresh
+  pub fn carry_over(self, rating: f64) -> Option<f64> {
+    match self {
+      SeasonResetPolicy::Keep => Some(rating),
+      SeasonResetPolicy::Soft => Some(INITIAL_RATING + (rating - INITIAL_RATING) / 2.),
+      SeasonResetPolicy::Hard => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct Season {
+  pub id: i32,
+  pub api_client_id: i32,
+  pub name: String,
+  pub reset_policy: SeasonResetPolicy,
+  pub starts_at: DateTime<Utc>,
+  /// The season is closed automatically at this time
+  pub ends_at: Option<DateTime<Utc>>,
+  pub closed_at: Option<DateTime<Utc>>,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeasonStanding {
+  pub player: PlayerRef,
+  pub rating: f64,
+  pub wins: i32,
+  pub losses: i32,
+  pub draws: i32,
+  /// Final rank, set when the season closes
+  pub rank: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenSeasonParams {
+  pub name: String,
+  pub reset_policy: SeasonResetPolicy,
+  /// Defaults to now
+  pub starts_at: Option<DateTime<Utc>>,
+  pub ends_at: Option<DateTime<Utc>>,
+}
+
+impl OpenSeasonParams {
+  fn validate(&self) -> Result<()> {
+    if !SEASON_NAME_CHARS.contains(&self.name.trim().chars().count()) {
+      return Err(Error::SeasonInvalid("name must be 1 to 64 characters"));
+    }
+    if let Some(ends_at) = self.ends_at {
+      if ends_at <= self.starts_at.unwrap_or_else(Utc::now) {
+        return Err(Error::SeasonInvalid("the season must end after it starts"));
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Opens a season for the API client, the previous season has to be closed first
+pub async fn open(
+  state: &ControllerState,
+  api_client_id: i32,
+  params: OpenSeasonParams,
+) -> Result<Season> {
+  params.validate()?;
+  let season = state
+    .db
+    .exec(move |conn| db::open(conn, api_client_id, &params))
+    .await?;
+  tracing::info!(
+    season_id = season.id,
+    api_client_id,
+    "season opened: {:?}",
+    season.reset_policy
+  );
+  Ok(season)
+}
+
+pub async fn close(state: &ControllerState, api_client_id: i32, season_id: i32) -> Result<Season> {
+  let season = state
+    .db
+    .exec(move |conn| db::close(conn, season_id, Some(api_client_id)))
+    .await?;
+  tracing::info!(season_id, api_client_id, "season closed");
+  Ok(season)
+}
+
+/// A player of an ended game, with the player's rating before the game
+#[derive(Debug, Clone, Copy)]
+pub struct RatedPlayer {
+  pub player_id: i32,
+  pub team: i32,
+  pub rating: f64,
+  pub result: PlayerResult,
+}
+
+/// Elo rating changes of the players of an ended game by player id.
+/// Teams play with the average rating of their players, every other team counts as one opponent.
+pub fn rating_changes(players: &[RatedPlayer]) -> BTreeMap<i32, f64> {
+  let mut teams: BTreeMap<i32, (f64, usize, PlayerResult)> = BTreeMap::new();
+  for player in players {
+    let entry = teams.entry(player.team).or_insert((0., 0, player.result));
+    entry.0 += player.rating;
+    entry.1 += 1;
+  }
+  if teams.len() < 2 {
+    return BTreeMap::new();
+  }
+
+  let teams: Vec<(i32, f64, PlayerResult)> = teams
+    .into_iter()
+    .map(|(team, (sum, count, result))| (team, sum / count as f64, result))
+    .collect();
+  let team_deltas: BTreeMap<i32, f64> = teams
+    .iter()
+    .map(|(team, rating, result)| {
+      let sum: f64 = teams
+        .iter()
+        .filter(|(other, _, _)| other != team)
+        .map(|(_, other_rating, other_result)| {
+          let expected = 1. / (1. + 10f64.powf((other_rating - rating) / 400.));
+          score(*result, *other_result) - expected
+        })
+        .sum();
+      (*team, RATING_K * sum / (teams.len() - 1) as f64)
+    })
+    .collect();
+
+  players
+    .iter()
+    .map(|player| (player.player_id, team_deltas[&player.team]))
+    .collect()
+}
+
+fn score(result: PlayerResult, opponent: PlayerResult) -> f64 {
+  fn value(result: PlayerResult) -> i32 {
+    match result {
+      PlayerResult::Win => 2,
+      PlayerResult::Draw => 1,
+      PlayerResult::Loss => 0,
+    }
+  }
+  match value(result).cmp(&value(opponent)) {
+    std::cmp::Ordering::Greater => 1.,
+    std::cmp::Ordering::Equal => 0.5,
+    std::cmp::Ordering::Less => 0.,
+  }
+}
+
+#[test]
+fn test_rating_changes() {
+  let player = |player_id, team, rating, result| RatedPlayer {
+    player_id,
+    team,
+    rating,
+    result,
+  };
+
+  // equal ratings
+  let changes = rating_changes(&[
+    player(1, 0, 1500., PlayerResult::Win),
+    player(2, 1, 1500., PlayerResult::Loss),
+  ]);
+  assert_eq!(changes[&1], 16.);
+  assert_eq!(changes[&2], -16.);
+
+  // the favorite wins less, teammates share the team change
+  let changes = rating_changes(&[
+    player(1, 0, 1700., PlayerResult::Win),
+    player(2, 0, 1700., PlayerResult::Win),
+    player(3, 1, 1500., PlayerResult::Loss),
+    player(4, 1, 1500., PlayerResult::Loss),
+  ]);
+  assert!(changes[&1] > 0. && changes[&1] < 16.);
+  assert_eq!(changes[&1], changes[&2]);
+  assert!((changes[&1] + changes[&3]).abs() < 1e-9);
+
+  let changes = rating_changes(&[
+    player(1, 0, 1500., PlayerResult::Draw),
+    player(2, 1, 1500., PlayerResult::Draw),
+  ]);
+  assert_eq!(changes[&1], 0.);
+
+  // free for all, the winner beats both
+  let changes = rating_changes(&[
+    player(1, 0, 1500., PlayerResult::Win),
+    player(2, 1, 1500., PlayerResult::Loss),
+    player(3, 2, 1500., PlayerResult::Loss),
+  ]);
+  assert_eq!(changes[&1], 16.);
+  assert_eq!(changes[&2], -8.);
+
+  assert!(rating_changes(&[player(1, 0, 1500., PlayerResult::Win)]).is_empty());
+
+  assert_eq!(SeasonResetPolicy::Soft.carry_over(1700.), Some(1600.));
+  assert_eq!(SeasonResetPolicy::Keep.carry_over(1700.), Some(1700.));
+  assert_eq!(SeasonResetPolicy::Hard.carry_over(1700.), None);
+}
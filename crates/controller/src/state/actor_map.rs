@@ -1,7 +1,24 @@
 use crate::error::*;
 use flo_state::{async_trait, Actor, Addr, Handler, Message};
-
+use flo_task::{SendExt, SendPolicy};
+use once_cell::sync::Lazy;
 use std::marker::PhantomData;
+use std::time::Duration;
+
+/// Messages sent to map entries fail with `Error::ActorTimeout` if not handled within this time.
+/// Game actors wait for node responses, so this is well above the node request timeout
+pub static ACTOR_SEND_TIMEOUT: Lazy<Duration> = Lazy::new(|| {
+  Duration::from_secs(
+    std::env::var("FLO_ACTOR_SEND_TIMEOUT_SECS")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .unwrap_or(30),
+  )
+});
+
+// entry lookups don't change the parent, they are safe to retry
+static ACTOR_LOOKUP_POLICY: Lazy<SendPolicy> =
+  Lazy::new(|| SendPolicy::new(Duration::from_secs(5)).with_retries(2, Duration::from_millis(100)));
 
 pub struct GetActorEntry<S, K = i32>(K, PhantomData<S>);
 
@@ -11,6 +28,15 @@ impl<S, K> GetActorEntry<S, K> {
   }
 }
 
+impl<S, K> Clone for GetActorEntry<S, K>
+where
+  K: Clone,
+{
+  fn clone(&self) -> Self {
+    Self(self.0.clone(), PhantomData)
+  }
+}
+
 impl<S, K> Message for GetActorEntry<S, K>
 where
   S: Actor,
@@ -24,7 +50,7 @@ impl<Parent, Entry, K> ActorMapExt<Entry, K> for Addr<Parent>
 where
   Parent: Actor + Handler<GetActorEntry<Entry, K>>,
   Entry: Actor,
-  K: Send + 'static,
+  K: Clone + Send + 'static,
 {
   async fn send_to<M, R>(&self, key: K, message: M) -> Result<R>
   where
@@ -32,14 +58,18 @@ where
     R: Send + 'static,
     Entry: Handler<M>,
   {
-    let addr = match self.send(GetActorEntry(key, PhantomData)).await {
+    let _timer = crate::profiling::actor_message::<M>();
+    let addr = match self
+      .send_with_policy(*ACTOR_LOOKUP_POLICY, GetActorEntry(key, PhantomData))
+      .await
+    {
       Ok(Some(v)) => v,
       Ok(None) => return Err(Error::ActorNotFound),
       Err(err) => return Err(err.into()),
     };
 
     addr
-      .send(message)
+      .send_within(*ACTOR_SEND_TIMEOUT, message)
       .await
       .map_err(Error::from)
       .and_then(std::convert::identity)
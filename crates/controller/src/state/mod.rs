@@ -2,22 +2,36 @@ mod actor_map;
 
 use bs_diesel_utils::{Executor, ExecutorRef};
 use flo_state::{Addr, Message, Registry};
+use flo_task::SendExt;
 
 use std::sync::Arc;
 
+use crate::chat::ChatService;
 use crate::error::*;
+use crate::game::db::CreateGamesAsBotParams;
+use crate::game::event::LobbyEventSender;
+use crate::game::state::create::{CreateGameAsBot, CreateGamesAsBot};
 use crate::game::state::GameRegistry;
+use crate::game::{Game, SavedGame};
+use crate::maintenance::{Maintenance, MaintenanceState};
 
+use crate::node::version::NodeVersionMatrix;
 use crate::node::NodeRegistry;
+use crate::notification::{
+  GameNotification, GameNotificationKind, NotificationDispatcher, NotifyGamePlayers,
+};
 use crate::player::state::PlayerRegistry;
 
 use crate::config::ConfigStorage;
 use crate::player::state::sender::PlayerRegistryHandle;
-pub use actor_map::{ActorMapExt, GetActorEntry};
+pub use actor_map::{ActorMapExt, GetActorEntry, ACTOR_SEND_TIMEOUT};
 
 #[derive(Debug)]
 pub struct Data {
   pub db: ExecutorRef,
+  pub lobby_events: LobbyEventSender,
+  pub maintenance: MaintenanceState,
+  pub node_versions: NodeVersionMatrix,
 }
 
 pub struct ControllerState {
@@ -28,6 +42,11 @@ pub struct ControllerState {
   pub players: Addr<PlayerRegistry>,
   pub player_packet_sender: PlayerRegistryHandle,
   pub config: Addr<ConfigStorage>,
+  pub notifications: Addr<NotificationDispatcher>,
+  pub chat: Addr<ChatService>,
+  pub lobby_events: LobbyEventSender,
+  pub maintenance: MaintenanceState,
+  pub node_versions: NodeVersionMatrix,
 }
 
 pub type ControllerStateRef = Arc<ControllerState>;
@@ -41,12 +60,22 @@ impl ControllerState {
       db.exec(|conn| crate::migration::run(conn)).await?;
     }
 
-    let registry = Registry::with_data(Data { db: db.clone() });
+    let lobby_events = LobbyEventSender::new();
+    let maintenance = MaintenanceState::from_env();
+    let node_versions = NodeVersionMatrix::default();
+    let registry = Registry::with_data(Data {
+      db: db.clone(),
+      lobby_events: lobby_events.clone(),
+      maintenance: maintenance.clone(),
+      node_versions: node_versions.clone(),
+    });
 
     let nodes = registry.resolve().await?;
     let games = registry.resolve().await?;
     let players = registry.resolve().await?;
     let config = registry.resolve().await?;
+    let notifications = registry.resolve().await?;
+    let chat = registry.resolve().await?;
 
     Ok(ControllerState {
       db,
@@ -56,15 +85,157 @@ impl ControllerState {
       players: players.clone(),
       player_packet_sender: PlayerRegistryHandle::from(players),
       config,
+      notifications,
+      chat,
+      lobby_events,
+      maintenance,
+      node_versions,
     })
   }
 
   pub async fn reload(&self) -> Result<()> {
-    self.config.send(Reload).await??;
-    self.nodes.send(Reload).await??;
+    self
+      .config
+      .send_within(*ACTOR_SEND_TIMEOUT, Reload)
+      .await??;
+    self
+      .nodes
+      .send_within(*ACTOR_SEND_TIMEOUT, Reload)
+      .await??;
     Ok(())
   }
 
+  /// Enables or disables maintenance mode and notifies all connected players.
+  /// Running games are not affected.
+  pub async fn set_maintenance(&self, value: Option<Maintenance>) -> Result<()> {
+    use flo_net::packet::FloPacket;
+    if let Some(pkt) = self.maintenance.set(value) {
+      tracing::info!(active = pkt.active, "maintenance mode updated");
+      self
+        .player_packet_sender
+        .broadcast_to_all(pkt.encode_as_frame()?)
+        .await?;
+    }
+    Ok(())
+  }
+
+  /// Creates lobbies for an API client from a template in one transaction,
+  /// players are notified like for single games
+  pub async fn create_games_as_bot(
+    &self,
+    api_client_id: i32,
+    api_player_id: i32,
+    params: CreateGamesAsBotParams,
+  ) -> Result<Vec<Game>> {
+    let games = self
+      .games
+      .send_within(
+        *ACTOR_SEND_TIMEOUT,
+        CreateGamesAsBot {
+          api_client_id,
+          api_player_id,
+          params,
+        },
+      )
+      .await??;
+
+    for game in &games {
+      let message = NotifyGamePlayers {
+        notification: GameNotification {
+          game_id: game.id,
+          game_name: game.name.clone(),
+          kind: GameNotificationKind::GameScheduled,
+        },
+        player_ids: game.get_player_ids(),
+      };
+      if let Err(err) = self.notifications.notify(message).await {
+        tracing::error!(game_id = game.id, "notify game players: {}", err);
+      }
+    }
+
+    Ok(games)
+  }
+
+  /// Recreates a game of an API client with the same slot layout for a quick rematch,
+  /// players are notified like for a new game
+  pub async fn rematch_as_bot(&self, api_client_id: i32, game_id: i32) -> Result<Game> {
+    let (api_player_id, params) = self
+      .db
+      .exec(move |conn| crate::game::db::get_rematch_params(conn, api_client_id, game_id))
+      .await?;
+
+    let game = self
+      .games
+      .send_within(
+        *ACTOR_SEND_TIMEOUT,
+        CreateGameAsBot {
+          api_client_id,
+          api_player_id,
+          params,
+          saved_game: None,
+        },
+      )
+      .await??;
+
+    let message = NotifyGamePlayers {
+      notification: GameNotification {
+        game_id: game.id,
+        game_name: game.name.clone(),
+        kind: GameNotificationKind::GameScheduled,
+      },
+      player_ids: game.get_player_ids(),
+    };
+    if let Err(err) = self.notifications.notify(message).await {
+      tracing::error!(game_id = game.id, "notify game players: {}", err);
+    }
+
+    Ok(game)
+  }
+
+  /// Creates a lobby loading the latest save of a game of an API client,
+  /// players who saved are placed in the slots they had when the game was saved
+  pub async fn resume_as_bot(
+    &self,
+    api_client_id: i32,
+    game_id: i32,
+    magic_number: u32,
+  ) -> Result<Game> {
+    let (api_player_id, params, save) = self
+      .db
+      .exec(move |conn| crate::game::db::get_resume_params(conn, api_client_id, game_id))
+      .await?;
+
+    let game = self
+      .games
+      .send_within(
+        *ACTOR_SEND_TIMEOUT,
+        CreateGameAsBot {
+          api_client_id,
+          api_player_id,
+          params,
+          saved_game: Some(SavedGame {
+            name: save.name,
+            magic_number,
+          }),
+        },
+      )
+      .await??;
+
+    let message = NotifyGamePlayers {
+      notification: GameNotification {
+        game_id: game.id,
+        game_name: game.name.clone(),
+        kind: GameNotificationKind::GameScheduled,
+      },
+      player_ids: game.get_player_ids(),
+    };
+    if let Err(err) = self.notifications.notify(message).await {
+      tracing::error!(game_id = game.id, "notify game players: {}", err);
+    }
+
+    Ok(game)
+  }
+
   pub fn into_ref(self) -> Arc<ControllerState> {
     Arc::new(self)
   }
@@ -1,16 +1,25 @@
+use once_cell::sync::OnceCell;
 use std::sync::Once;
 pub use tracing::{debug, error, info, instrument, span, warn, Level};
 pub use tracing_futures::Instrument;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, EnvFilter, Registry};
 
 static INIT: Once = Once::new();
+static FILTER: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
 
 pub fn init() {
   INIT.call_once(|| {
+    let (filter, handle) = reload::Layer::new(EnvFilter::from_default_env());
+
     #[cfg(debug_assertions)]
-    tracing_subscriber::fmt::init();
+    let fmt = tracing_subscriber::fmt::layer();
 
     #[cfg(not(debug_assertions))]
-    tracing_subscriber::fmt::fmt().with_ansi(false).init();
+    let fmt = tracing_subscriber::fmt::layer().with_ansi(false);
+
+    tracing_subscriber::registry().with(filter).with(fmt).init();
+    FILTER.set(handle).ok();
   });
 }
 
@@ -18,3 +27,20 @@ pub fn init_env_override(env: &str) {
   std::env::set_var("RUST_LOG", env);
   init();
 }
+
+/// Replaces the filter of the subscriber installed by `init`,
+/// `directives` is in `RUST_LOG` format
+pub fn set_filter(directives: &str) -> Result<(), String> {
+  let filter = EnvFilter::try_new(directives).map_err(|err| err.to_string())?;
+  let handle = FILTER
+    .get()
+    .ok_or_else(|| "log subscriber not initialized".to_string())?;
+  handle.reload(filter).map_err(|err| err.to_string())
+}
+
+/// Checks `RUST_LOG` format directives without applying them
+pub fn validate_filter(directives: &str) -> Result<(), String> {
+  EnvFilter::try_new(directives)
+    .map(|_| ())
+    .map_err(|err| err.to_string())
+}
@@ -0,0 +1,101 @@
+//! Compares broadcasting a batch of frames by encoding it for every connection
+//! against encoding it once and writing the shared buffers with vectored writes.
+//!
+//! cargo run --release -p flo-net --example broadcast_bench -- [connections] [rounds]
+//!
+//! The default 5000 connections needs `ulimit -n` above 10000.
+
+use flo_net::listener::FloListener;
+use flo_net::packet::{EncodedFrame, Frame, PacketTypeId};
+use flo_net::stream::FloStream;
+use futures::StreamExt;
+use std::time::{Duration, Instant};
+
+const FRAMES_PER_ROUND: usize = 4;
+const PAYLOAD_LEN: usize = 512;
+
+#[tokio::main]
+async fn main() -> flo_net::error::Result<()> {
+  let mut args = std::env::args().skip(1);
+  let connections: usize = args.next().and_then(|v| v.parse().ok()).unwrap_or(5000);
+  let rounds: usize = args.next().and_then(|v| v.parse().ok()).unwrap_or(50);
+
+  let mut listener = FloListener::bind_v4(0).await?;
+  let port = listener.port();
+  tokio::spawn(async move {
+    let mut incoming = listener.incoming();
+    while let Some(Ok(mut stream)) = incoming.next().await {
+      tokio::spawn(async move { while stream.recv_frame().await.is_ok() {} });
+    }
+  });
+
+  let mut streams = Vec::with_capacity(connections);
+  for _ in 0..connections {
+    streams.push(FloStream::connect_no_delay(("127.0.0.1", port)).await?);
+  }
+
+  let frames: Vec<Frame> = (0..FRAMES_PER_ROUND)
+    .map(|i| Frame::new(PacketTypeId::ListNodes, vec![i as u8; PAYLOAD_LEN]))
+    .collect();
+
+  println!(
+    "{} connections, {} rounds of {} x {} byte frames",
+    connections, rounds, FRAMES_PER_ROUND, PAYLOAD_LEN
+  );
+
+  let (elapsed, cpu) = measure(async {
+    for _ in 0..rounds {
+      for stream in streams.iter_mut() {
+        stream.send_frames(frames.iter().cloned()).await?;
+      }
+    }
+    Ok(())
+  })
+  .await?;
+  report("per-connection encode", elapsed, cpu);
+
+  let (elapsed, cpu) = measure(async {
+    for _ in 0..rounds {
+      let shared: Vec<EncodedFrame> = frames.iter().map(Frame::to_encoded).collect();
+      for stream in streams.iter_mut() {
+        stream.send_encoded_frames(&shared).await?;
+      }
+    }
+    Ok(())
+  })
+  .await?;
+  report("shared + vectored", elapsed, cpu);
+
+  Ok(())
+}
+
+async fn measure<F>(f: F) -> flo_net::error::Result<(Duration, Option<Duration>)>
+where
+  F: std::future::Future<Output = flo_net::error::Result<()>>,
+{
+  let cpu_start = process_cpu_time();
+  let start = Instant::now();
+  f.await?;
+  let elapsed = start.elapsed();
+  let cpu = match (cpu_start, process_cpu_time()) {
+    (Some(a), Some(b)) => Some(b.saturating_sub(a)),
+    _ => None,
+  };
+  Ok((elapsed, cpu))
+}
+
+fn report(name: &str, elapsed: Duration, cpu: Option<Duration>) {
+  match cpu {
+    Some(cpu) => println!("{:>24}: wall {:?}, process cpu {:?}", name, elapsed, cpu),
+    None => println!("{:>24}: wall {:?}", name, elapsed),
+  }
+}
+
+// utime + stime from /proc/self/stat, in clock ticks of 10ms
+fn process_cpu_time() -> Option<Duration> {
+  let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+  let fields: Vec<&str> = stat.rsplit(')').next()?.split_whitespace().collect();
+  let utime: u64 = fields.get(11)?.parse().ok()?;
+  let stime: u64 = fields.get(12)?.parse().ok()?;
+  Some(Duration::from_millis((utime + stime) * 10))
+}
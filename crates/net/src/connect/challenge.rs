@@ -0,0 +1,56 @@
+//! Proof-of-work of the controller connect challenge.
+//!
+//! The client searches a `solution` so that `SHA-256(nonce || solution)` starts with
+//! `difficulty` zero bits, `solution` is encoded as little endian `u64`.
+//! Each extra bit doubles the expected work of the client, verifying costs one hash.
+
+use sha2::{Digest, Sha256};
+
+/// Challenges above this can't be solved in reasonable time
+pub const MAX_DIFFICULTY: u32 = 32;
+
+pub fn verify(nonce: &[u8], difficulty: u32, solution: u64) -> bool {
+  leading_zero_bits(&hash(nonce, solution)) >= difficulty.min(MAX_DIFFICULTY)
+}
+
+/// Returns `None` if `difficulty` is above `MAX_DIFFICULTY`
+pub fn solve(nonce: &[u8], difficulty: u32) -> Option<u64> {
+  if difficulty > MAX_DIFFICULTY {
+    return None;
+  }
+  (0..=u64::MAX).find(|solution| leading_zero_bits(&hash(nonce, *solution)) >= difficulty)
+}
+
+fn hash(nonce: &[u8], solution: u64) -> [u8; 32] {
+  let mut hasher = Sha256::new();
+  hasher.update(nonce);
+  hasher.update(solution.to_le_bytes());
+  hasher.finalize().into()
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+  let mut bits = 0;
+  for b in hash {
+    if *b == 0 {
+      bits += 8;
+    } else {
+      bits += b.leading_zeros();
+      break;
+    }
+  }
+  bits
+}
+
+#[test]
+fn test_challenge() {
+  assert_eq!(leading_zero_bits(&[0, 0x10, 0xFF]), 11);
+  assert_eq!(leading_zero_bits(&[0x80]), 0);
+
+  let nonce = b"flo";
+  let solution = solve(nonce, 12).unwrap();
+  assert!(verify(nonce, 12, solution));
+  assert!(verify(nonce, 0, 0));
+  assert_eq!(solve(nonce, MAX_DIFFICULTY + 1), None);
+  // solutions are bound to the nonce
+  assert!(!verify(b"other", 12, solution));
+}
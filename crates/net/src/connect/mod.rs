@@ -1,2 +1,3 @@
+pub mod challenge;
 mod packets;
 pub use packets::*;
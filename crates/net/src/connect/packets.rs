@@ -35,3 +35,55 @@ packet_type!(RemoveNode, PacketRemoveNode);
 packet_type!(PlayerMuteListUpdate, PacketPlayerMuteListUpdate);
 packet_type!(PlayerMuteAddRequest, PacketPlayerMuteAddRequest);
 packet_type!(PlayerMuteRemoveRequest, PacketPlayerMuteRemoveRequest);
+packet_type!(
+  PlayerNotificationSubscribeRequest,
+  PacketPlayerNotificationSubscribeRequest
+);
+packet_type!(
+  PlayerNotificationUnsubscribeRequest,
+  PacketPlayerNotificationUnsubscribeRequest
+);
+packet_type!(GameCommandRequest, PacketGameCommandRequest);
+packet_type!(GameCommand, PacketGameCommand);
+packet_type!(GameMetadataUpdateRequest, PacketGameMetadataUpdateRequest);
+packet_type!(GameMetadataUpdate, PacketGameMetadataUpdate);
+packet_type!(MaintenanceUpdate, PacketMaintenanceUpdate);
+packet_type!(QuickJoinRequest, PacketQuickJoinRequest);
+packet_type!(QuickJoinReject, PacketQuickJoinReject);
+packet_type!(ChatChannelJoinRequest, PacketChatChannelJoinRequest);
+packet_type!(ChatChannelLeaveRequest, PacketChatChannelLeaveRequest);
+packet_type!(ChatMessageSendRequest, PacketChatMessageSendRequest);
+packet_type!(ChatChannelJoined, PacketChatChannelJoined);
+packet_type!(ChatChannelMemberUpdate, PacketChatChannelMemberUpdate);
+packet_type!(ChatMessage, PacketChatMessage);
+packet_type!(ChatReject, PacketChatReject);
+packet_type!(GameResyncRequest, PacketGameResyncRequest);
+packet_type!(PlayerPenaltyUpdate, PacketPlayerPenaltyUpdate);
+packet_type!(ClanCreateRequest, PacketClanCreateRequest);
+packet_type!(ClanInviteRequest, PacketClanInviteRequest);
+packet_type!(ClanInviteReplyRequest, PacketClanInviteReplyRequest);
+packet_type!(ClanLeaveRequest, PacketClanLeaveRequest);
+packet_type!(ClanMemberUpdateRequest, PacketClanMemberUpdateRequest);
+packet_type!(ClanUpdate, PacketClanUpdate);
+packet_type!(ClanInvite, PacketClanInvite);
+packet_type!(ClanReject, PacketClanReject);
+packet_type!(PlayerPrivacyUpdateRequest, PacketPlayerPrivacyUpdateRequest);
+packet_type!(PlayerPrivacyUpdate, PacketPlayerPrivacyUpdate);
+packet_type!(GameMapVoteStartRequest, PacketGameMapVoteStartRequest);
+packet_type!(GameMapVoteRequest, PacketGameMapVoteRequest);
+packet_type!(GameMapVote, PacketGameMapVote);
+packet_type!(LobbyChatKeyUpdateRequest, PacketLobbyChatKeyUpdateRequest);
+packet_type!(LobbyChatKey, PacketLobbyChatKey);
+packet_type!(
+  LobbyChatMessageSendRequest,
+  PacketLobbyChatMessageSendRequest
+);
+packet_type!(LobbyChatMessage, PacketLobbyChatMessage);
+packet_type!(GameStartQueued, PacketGameStartQueued);
+packet_type!(GameLobbySummary, PacketGameLobbySummary);
+packet_type!(War3VersionMismatch, PacketWar3VersionMismatch);
+packet_type!(ConnectControllerChallenge, PacketClientConnectChallenge);
+packet_type!(
+  ConnectControllerChallengeResponse,
+  PacketClientConnectChallengeResponse
+);
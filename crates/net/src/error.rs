@@ -13,6 +13,10 @@ pub enum Error {
   StreamTimeout,
   #[error("stream closed")]
   StreamClosed,
+  #[error("binary stream is not supported over TLS")]
+  BinaryStreamOverTls,
+  #[error("tls config: {0}")]
+  TlsConfig(String),
   #[error("unexpected packet type: expected {expected:?}, got {got:?}")]
   UnexpectedPacketType {
     expected: PacketTypeId,
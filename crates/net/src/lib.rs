@@ -11,6 +11,9 @@ pub mod listener;
 pub mod ping;
 pub mod stream;
 pub mod time;
+#[cfg(feature = "tls")]
+pub mod tls;
+mod transport;
 pub mod w3gs;
 
 pub mod proto {
@@ -1,5 +1,6 @@
 use futures::ready;
 
+use flo_w3gs::net::SocketConfig;
 use futures::stream::Stream;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::pin::Pin;
@@ -9,25 +10,52 @@ use tokio::net::TcpListener;
 use crate::error::*;
 
 use crate::stream::FloStream;
+#[cfg(feature = "tls")]
+use crate::tls::{TlsConfig, TlsServerConfig};
+use crate::transport::Transport;
 
 #[derive(Debug)]
 pub struct FloListener {
   listener: TcpListener,
   local_addr: SocketAddr,
+  config: SocketConfig,
+  #[cfg(feature = "tls")]
+  tls: Option<TlsServerConfig>,
 }
 
 impl FloListener {
+  /// Socket options are loaded from env, see `SocketConfig`,
+  /// and so is TLS if the `tls` feature is enabled, see `crate::tls`
   pub async fn bind_v4(port: u16) -> Result<Self, Error> {
-    let listener = TcpListener::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)).await?;
+    Self::bind_v4_with_config(port, SocketConfig::global().clone()).await
+  }
+
+  pub async fn bind_v4_with_config(port: u16, config: SocketConfig) -> Result<Self, Error> {
+    let listener = config.bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into())?;
     let local_addr = listener.local_addr()?;
     Ok(FloListener {
       listener,
       local_addr,
+      config,
+      #[cfg(feature = "tls")]
+      tls: TlsConfig::global()?.server.clone(),
     })
   }
 
+  /// Replaces the TLS config loaded from env, `None` accepts plain TCP connections
+  #[cfg(feature = "tls")]
+  pub fn with_tls(mut self, config: Option<TlsServerConfig>) -> Self {
+    self.tls = config;
+    self
+  }
+
   pub fn incoming(&mut self) -> Incoming {
-    Incoming::new(&mut self.listener)
+    Incoming {
+      inner: &mut self.listener,
+      config: &self.config,
+      #[cfg(feature = "tls")]
+      tls: self.tls.as_ref(),
+    }
   }
 
   pub fn local_addr(&self) -> &SocketAddr {
@@ -41,22 +69,27 @@ impl FloListener {
 
 pub struct Incoming<'a> {
   inner: &'a mut TcpListener,
+  config: &'a SocketConfig,
+  #[cfg(feature = "tls")]
+  tls: Option<&'a TlsServerConfig>,
 }
 
-impl Incoming<'_> {
-  pub(crate) fn new(listener: &mut TcpListener) -> Incoming<'_> {
-    Incoming { inner: listener }
-  }
-
+impl<'a> Incoming<'a> {
+  /// TLS handshakes are completed by the first read or write of the stream,
+  /// so a slow client doesn't hold up the accept loop
   pub fn poll_accept(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<FloStream>> {
     let (socket, _addr) = ready!(self.inner.poll_accept(cx))?;
 
-    socket.set_nodelay(true).ok();
+    if let Err(err) = self.config.apply(&socket) {
+      tracing::warn!("set socket options: {}", err);
+    }
 
-    //TODO: not supported atm by tokio
-    //socket.set_keepalive(None).ok();
+    #[cfg(feature = "tls")]
+    let transport = Transport::accept(socket, self.tls);
+    #[cfg(not(feature = "tls"))]
+    let transport = Transport::Tcp(socket);
 
-    let stream = FloStream::new(socket);
+    let stream = FloStream::with_transport(transport);
 
     Poll::Ready(Ok(stream))
   }
@@ -15,7 +15,21 @@ packet_type!(
 packet_type!(ControllerCreateGame, PacketControllerCreateGame);
 packet_type!(ControllerCreateGameAccept, PacketControllerCreateGameAccept);
 packet_type!(ControllerCreateGameReject, PacketControllerCreateGameReject);
+packet_type!(ControllerCommitGame, PacketControllerCommitGame);
+packet_type!(ControllerCommitGameAccept, PacketControllerCommitGameAccept);
+packet_type!(ControllerCommitGameReject, PacketControllerCommitGameReject);
 packet_type!(ControllerQueryGameStatus, PacketControllerQueryGameStatus);
+packet_type!(ControllerGameCommand, PacketControllerGameCommand);
+packet_type!(ControllerGameSurrender, PacketControllerGameSurrender);
+packet_type!(ControllerUpdateNodeConfig, PacketControllerUpdateNodeConfig);
+packet_type!(
+  ControllerUpdateNodeConfigAccept,
+  PacketControllerUpdateNodeConfigAccept
+);
+packet_type!(
+  ControllerUpdateNodeConfigReject,
+  PacketControllerUpdateNodeConfigReject
+);
 packet_type!(ClientConnect, PacketClientConnect);
 packet_type!(ClientConnectAccept, PacketClientConnectAccept);
 packet_type!(ClientConnectReject, PacketClientConnectReject);
@@ -33,3 +47,5 @@ packet_type!(
 );
 packet_type!(NodeGameStatusUpdate, PacketNodeGameStatusUpdate);
 packet_type!(NodeGameStatusUpdateBulk, PacketNodeGameStatusUpdateBulk);
+packet_type!(NodeRoutingStats, PacketNodeRoutingStats);
+packet_type!(NodeGameSaved, PacketNodeGameSaved);
@@ -137,6 +137,50 @@ impl Frame {
   }
 }
 
+impl Frame {
+  /// Encodes the frame, header included, into a frozen buffer
+  /// that can be written to many connections without encoding it again.
+  pub fn to_encoded(&self) -> EncodedFrame {
+    let mut buf = BytesMut::with_capacity(Header::MIN_SIZE + self.payload.len());
+    self.encode(&mut buf);
+    EncodedFrame {
+      type_id: self.type_id,
+      bytes: buf.freeze(),
+    }
+  }
+}
+
+/// A frame encoded once and shared across connections, cloning only bumps the reference count
+#[derive(Debug, Clone)]
+pub struct EncodedFrame {
+  type_id: PacketTypeId,
+  bytes: Bytes,
+}
+
+impl EncodedFrame {
+  pub fn type_id(&self) -> PacketTypeId {
+    self.type_id
+  }
+
+  pub fn bytes(&self) -> &Bytes {
+    &self.bytes
+  }
+
+  pub fn len(&self) -> usize {
+    self.bytes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.bytes.is_empty()
+  }
+}
+
+impl<'a> From<&'a Frame> for EncodedFrame {
+  fn from(frame: &'a Frame) -> Self {
+    frame.to_encoded()
+  }
+}
+
 /// Decodes packet by type id
 /// If no branch matches, returns Err(...)
 ///
@@ -236,6 +280,38 @@ pub enum PacketTypeId {
   PlayerMuteAddRequest,
   #[bin(value = 0x1F)]
   PlayerMuteRemoveRequest,
+  #[bin(value = 0x20)]
+  PlayerNotificationSubscribeRequest,
+  #[bin(value = 0x21)]
+  PlayerNotificationUnsubscribeRequest,
+  #[bin(value = 0x22)]
+  GameCommandRequest,
+  #[bin(value = 0x23)]
+  GameCommand,
+  #[bin(value = 0x24)]
+  GameMetadataUpdateRequest,
+  #[bin(value = 0x25)]
+  GameMetadataUpdate,
+  #[bin(value = 0x26)]
+  MaintenanceUpdate,
+  #[bin(value = 0x27)]
+  QuickJoinRequest,
+  #[bin(value = 0x28)]
+  QuickJoinReject,
+  #[bin(value = 0x29)]
+  ChatChannelJoinRequest,
+  #[bin(value = 0x2A)]
+  ChatChannelLeaveRequest,
+  #[bin(value = 0x2B)]
+  ChatMessageSendRequest,
+  #[bin(value = 0x2C)]
+  ChatChannelJoined,
+  #[bin(value = 0x2D)]
+  ChatChannelMemberUpdate,
+  #[bin(value = 0x2E)]
+  ChatMessage,
+  #[bin(value = 0x2F)]
+  ChatReject,
 
   // Lobby <-> Node
   #[bin(value = 0x30)]
@@ -258,6 +334,18 @@ pub enum PacketTypeId {
   ControllerUpdateSlotStatusReject,
   #[bin(value = 0x39)]
   ControllerQueryGameStatus,
+  #[bin(value = 0x3A)]
+  ControllerGameCommand,
+  #[bin(value = 0x3B)]
+  ControllerGameSurrender,
+  #[bin(value = 0x3C)]
+  ControllerCommitGame,
+  #[bin(value = 0x3D)]
+  ControllerCommitGameAccept,
+  #[bin(value = 0x3E)]
+  ControllerCommitGameReject,
+  #[bin(value = 0x3F)]
+  ControllerUpdateNodeConfig,
 
   // Client <-> Node
   #[bin(value = 0x40)]
@@ -282,6 +370,14 @@ pub enum PacketTypeId {
   NodeGameStatusUpdate,
   #[bin(value = 0x51)]
   NodeGameStatusUpdateBulk,
+  #[bin(value = 0x52)]
+  NodeRoutingStats,
+  #[bin(value = 0x53)]
+  ControllerUpdateNodeConfigAccept,
+  #[bin(value = 0x54)]
+  ControllerUpdateNodeConfigReject,
+  #[bin(value = 0x55)]
+  NodeGameSaved,
 
   // Client <-> Observer
   #[bin(value = 0x60)]
@@ -295,6 +391,56 @@ pub enum PacketTypeId {
   #[bin(value = 0x64)]
   ObserverDataEnd,
 
+  // Client <-> Lobby (continued)
+  #[bin(value = 0x70)]
+  GameResyncRequest,
+  #[bin(value = 0x71)]
+  PlayerPenaltyUpdate,
+  #[bin(value = 0x72)]
+  ClanCreateRequest,
+  #[bin(value = 0x73)]
+  ClanInviteRequest,
+  #[bin(value = 0x74)]
+  ClanInviteReplyRequest,
+  #[bin(value = 0x75)]
+  ClanLeaveRequest,
+  #[bin(value = 0x76)]
+  ClanMemberUpdateRequest,
+  #[bin(value = 0x77)]
+  ClanUpdate,
+  #[bin(value = 0x78)]
+  ClanInvite,
+  #[bin(value = 0x79)]
+  ClanReject,
+  #[bin(value = 0x7A)]
+  PlayerPrivacyUpdateRequest,
+  #[bin(value = 0x7B)]
+  PlayerPrivacyUpdate,
+  #[bin(value = 0x7C)]
+  GameMapVoteStartRequest,
+  #[bin(value = 0x7D)]
+  GameMapVoteRequest,
+  #[bin(value = 0x7E)]
+  GameMapVote,
+  #[bin(value = 0x7F)]
+  LobbyChatKeyUpdateRequest,
+  #[bin(value = 0x80)]
+  LobbyChatKey,
+  #[bin(value = 0x81)]
+  LobbyChatMessageSendRequest,
+  #[bin(value = 0x82)]
+  LobbyChatMessage,
+  #[bin(value = 0x83)]
+  GameStartQueued,
+  #[bin(value = 0x84)]
+  GameLobbySummary,
+  #[bin(value = 0x85)]
+  War3VersionMismatch,
+  #[bin(value = 0x86)]
+  ConnectControllerChallenge,
+  #[bin(value = 0x87)]
+  ConnectControllerChallengeResponse,
+
   #[bin(value = 0xF7)]
   W3GS,
   UnknownValue(u8),
@@ -1,8 +1,10 @@
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 use futures::future::poll_fn;
 use futures::sink::SinkExt;
 use futures::stream::TryStreamExt;
 use futures::{Sink, Stream};
+use std::collections::VecDeque;
+use std::io::IoSlice;
 use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -13,7 +15,10 @@ use tokio_util::codec::Framed;
 
 use crate::codec::FloFrameCodec;
 use crate::error::*;
-use crate::packet::{FloPacket, Frame};
+use crate::packet::{EncodedFrame, FloPacket, Frame};
+#[cfg(feature = "tls")]
+use crate::tls::{TlsClientConfig, TlsConfig};
+use crate::transport::Transport;
 use tokio::io::AsyncWriteExt;
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
@@ -21,7 +26,7 @@ const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
 #[derive(Debug)]
 pub struct FloStream {
   pub timeout: Duration,
-  pub(crate) transport: Framed<TcpStream, FloFrameCodec>,
+  pub(crate) transport: Framed<Transport, FloFrameCodec>,
 }
 
 impl FloStream {
@@ -33,11 +38,7 @@ impl FloStream {
     //TODO: not supported by current tokio
     //socket.set_keepalive(None).ok();
 
-    let transport = Framed::new(socket, FloFrameCodec::new());
-    Ok(FloStream {
-      transport,
-      timeout: DEFAULT_TIMEOUT,
-    })
+    Self::from_socket(socket).await
   }
 
   pub async fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self> {
@@ -46,16 +47,33 @@ impl FloStream {
     // not supported by tokio atm
     //socket.set_keepalive(Some(Duration::from_secs(30)))?;
 
-    let transport = Framed::new(socket, FloFrameCodec::new());
-    Ok(FloStream {
-      transport,
-      timeout: DEFAULT_TIMEOUT,
-    })
+    Self::from_socket(socket).await
+  }
+
+  #[cfg(feature = "tls")]
+  pub async fn connect_tls<A: ToSocketAddrs>(addr: A, config: &TlsClientConfig) -> Result<Self> {
+    let socket = TcpStream::connect(addr).await?;
+    socket.set_nodelay(true).ok();
+    let transport = Transport::connect(socket, Some(config)).await?;
+    Ok(Self::with_transport(transport))
+  }
+
+  /// Connects with TLS if it's enabled by env, see `crate::tls`
+  async fn from_socket(socket: TcpStream) -> Result<Self> {
+    #[cfg(feature = "tls")]
+    let transport = Transport::connect(socket, TlsConfig::global()?.client.as_ref()).await?;
+    #[cfg(not(feature = "tls"))]
+    let transport = Transport::Tcp(socket);
+    Ok(Self::with_transport(transport))
   }
 
   pub fn new(socket: TcpStream) -> Self {
+    Self::with_transport(Transport::Tcp(socket))
+  }
+
+  pub(crate) fn with_transport(transport: Transport) -> Self {
     FloStream {
-      transport: Framed::new(socket, FloFrameCodec::new()),
+      transport: Framed::new(transport, FloFrameCodec::new()),
       timeout: DEFAULT_TIMEOUT,
     }
   }
@@ -100,6 +118,20 @@ impl FloStream {
     Ok(())
   }
 
+  /// Writes pre-encoded frames with vectored writes, skipping the codec write buffer.
+  /// Frames queued by `send_frame` are flushed first to keep the order.
+  pub async fn send_encoded_frames(&mut self, frames: &[EncodedFrame]) -> Result<()> {
+    let mut buf = BufList::new(frames.iter().map(|frame| frame.bytes().clone()));
+    timeout(self.timeout, async {
+      poll_fn(|ctx| Pin::new(&mut self.transport).poll_flush(ctx)).await?;
+      self.transport.get_mut().write_all_buf(&mut buf).await?;
+      Ok::<_, Error>(())
+    })
+    .await
+    .map_err(|_elapsed| Error::StreamTimeout)??;
+    Ok(())
+  }
+
   #[inline]
   pub async fn send<T>(&mut self, packet: T) -> Result<()>
   where
@@ -160,9 +192,10 @@ impl FloStream {
     Ok(())
   }
 
+  /// Not supported by TLS streams
   pub async fn downgrade_to_binary_stream(self) -> Result<(Bytes, TcpStream)> {
     let parts = self.transport.into_parts();
-    let mut stream = parts.io;
+    let mut stream = parts.io.into_tcp().ok_or(Error::BinaryStreamOverTls)?;
     if !parts.write_buf.is_empty() {
       stream.write_all(parts.write_buf.as_ref()).await?;
     }
@@ -178,6 +211,69 @@ impl Stream for FloStream {
   }
 }
 
+/// Chains shared buffers so `write_all_buf` can hand all of them to a single `writev`
+struct BufList {
+  bufs: VecDeque<Bytes>,
+  remaining: usize,
+}
+
+impl BufList {
+  fn new<I: IntoIterator<Item = Bytes>>(iter: I) -> Self {
+    let bufs: VecDeque<Bytes> = iter.into_iter().filter(|b| !b.is_empty()).collect();
+    let remaining = bufs.iter().map(|b| b.len()).sum();
+    Self { bufs, remaining }
+  }
+}
+
+impl Buf for BufList {
+  fn remaining(&self) -> usize {
+    self.remaining
+  }
+
+  fn chunk(&self) -> &[u8] {
+    self.bufs.front().map(|b| b.as_ref()).unwrap_or_default()
+  }
+
+  fn advance(&mut self, mut cnt: usize) {
+    assert!(cnt <= self.remaining, "advance past end");
+    self.remaining -= cnt;
+    while cnt > 0 {
+      let front = self.bufs.front_mut().expect("front");
+      if cnt < front.len() {
+        front.advance(cnt);
+        break;
+      }
+      cnt -= front.len();
+      self.bufs.pop_front();
+    }
+  }
+
+  fn chunks_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+    let mut n = 0;
+    for (slot, buf) in dst.iter_mut().zip(self.bufs.iter()) {
+      *slot = IoSlice::new(buf.as_ref());
+      n += 1;
+    }
+    n
+  }
+}
+
+#[test]
+fn test_buf_list() {
+  let mut buf = BufList::new(vec![
+    Bytes::from_static(b"ab"),
+    Bytes::new(),
+    Bytes::from_static(b"cde"),
+  ]);
+  assert_eq!(buf.remaining(), 5);
+  let mut slices = [IoSlice::new(&[]); 4];
+  assert_eq!(buf.chunks_vectored(&mut slices), 2);
+  buf.advance(3);
+  assert_eq!(buf.chunk(), b"de");
+  assert_eq!(buf.copy_to_bytes(2), Bytes::from_static(b"de"));
+  assert_eq!(buf.remaining(), 0);
+}
+
 #[test]
 fn test_lookup() {
   use std::net::ToSocketAddrs;
@@ -0,0 +1,192 @@
+//! TLS for controller and node connections, built with the `tls` feature.
+//!
+//! Listeners accept TLS connections only if `FLO_TLS_CERT_PATH` (PEM certificate chain)
+//! and `FLO_TLS_KEY_PATH` (PEM private key) are set.
+//! Streams connect with TLS only if `FLO_TLS_SERVER_NAME` is set, the server certificate
+//! must be valid for that name and issued by a root in `FLO_TLS_CA_PATH` (PEM),
+//! or by a public CA if it's not set.
+
+use once_cell::sync::OnceCell;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{
+  Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerConfig, ServerName,
+};
+use tokio_rustls::{client, Accept, TlsAcceptor, TlsConnector};
+
+use crate::error::*;
+
+static TLS_CONFIG: OnceCell<TlsConfig> = OnceCell::new();
+
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+  pub server: Option<TlsServerConfig>,
+  pub client: Option<TlsClientConfig>,
+}
+
+impl TlsConfig {
+  /// Loaded once, used by listeners and streams created without a TLS config
+  pub fn global() -> Result<&'static TlsConfig> {
+    TLS_CONFIG.get_or_try_init(Self::from_env)
+  }
+
+  pub fn from_env() -> Result<Self> {
+    Self::from_vars(|name| std::env::var(name).ok())
+  }
+
+  fn from_vars<F>(get: F) -> Result<Self>
+  where
+    F: Fn(&str) -> Option<String>,
+  {
+    let server = match (get("FLO_TLS_CERT_PATH"), get("FLO_TLS_KEY_PATH")) {
+      (Some(cert_path), Some(key_path)) => Some(TlsServerConfig::load(&cert_path, &key_path)?),
+      (None, None) => None,
+      _ => {
+        return Err(Error::TlsConfig(
+          "`FLO_TLS_CERT_PATH` and `FLO_TLS_KEY_PATH` must be set together".to_string(),
+        ))
+      }
+    };
+    let client = get("FLO_TLS_SERVER_NAME")
+      .map(|server_name| TlsClientConfig::load(&server_name, get("FLO_TLS_CA_PATH").as_deref()))
+      .transpose()?;
+    Ok(Self { server, client })
+  }
+}
+
+#[derive(Clone)]
+pub struct TlsServerConfig {
+  acceptor: TlsAcceptor,
+}
+
+impl TlsServerConfig {
+  pub fn load(cert_path: &str, key_path: &str) -> Result<Self> {
+    let certs = read_certs(cert_path)?;
+    let key = read_private_key(key_path)?;
+    let config = ServerConfig::builder()
+      .with_safe_defaults()
+      .with_no_client_auth()
+      .with_single_cert(certs, key)
+      .map_err(|err| Error::TlsConfig(format!("server certificate: {}", err)))?;
+    Ok(Self {
+      acceptor: TlsAcceptor::from(Arc::new(config)),
+    })
+  }
+
+  pub(crate) fn accept(&self, socket: TcpStream) -> Accept<TcpStream> {
+    self.acceptor.accept(socket)
+  }
+}
+
+impl fmt::Debug for TlsServerConfig {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("TlsServerConfig").finish()
+  }
+}
+
+#[derive(Clone)]
+pub struct TlsClientConfig {
+  connector: TlsConnector,
+  server_name: ServerName,
+}
+
+impl TlsClientConfig {
+  /// Trusts the roots in `ca_path`, or public CAs if `None`
+  pub fn load(server_name: &str, ca_path: Option<&str>) -> Result<Self> {
+    let server_name = ServerName::try_from(server_name)
+      .map_err(|_| Error::TlsConfig(format!("invalid server name: {}", server_name)))?;
+    let mut roots = RootCertStore::empty();
+    if let Some(ca_path) = ca_path {
+      for cert in read_certs(ca_path)? {
+        roots
+          .add(&cert)
+          .map_err(|err| Error::TlsConfig(format!("CA certificate: {}", err)))?;
+      }
+    } else {
+      roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+          ta.subject,
+          ta.spki,
+          ta.name_constraints,
+        )
+      }));
+    }
+    let config = ClientConfig::builder()
+      .with_safe_defaults()
+      .with_root_certificates(roots)
+      .with_no_client_auth();
+    Ok(Self {
+      connector: TlsConnector::from(Arc::new(config)),
+      server_name,
+    })
+  }
+
+  pub(crate) async fn connect(&self, socket: TcpStream) -> Result<client::TlsStream<TcpStream>> {
+    let stream = self
+      .connector
+      .connect(self.server_name.clone(), socket)
+      .await?;
+    Ok(stream)
+  }
+}
+
+impl fmt::Debug for TlsClientConfig {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("TlsClientConfig")
+      .field("server_name", &self.server_name)
+      .finish()
+  }
+}
+
+fn read_certs(path: &str) -> Result<Vec<Certificate>> {
+  let mut r = BufReader::new(File::open(path)?);
+  let certs = rustls_pemfile::certs(&mut r)?;
+  if certs.is_empty() {
+    return Err(Error::TlsConfig(format!(
+      "no certificate found in {}",
+      path
+    )));
+  }
+  Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_private_key(path: &str) -> Result<PrivateKey> {
+  use rustls_pemfile::Item;
+  let mut r = BufReader::new(File::open(path)?);
+  for item in rustls_pemfile::read_all(&mut r)? {
+    match item {
+      Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => return Ok(PrivateKey(key)),
+      _ => {}
+    }
+  }
+  Err(Error::TlsConfig(format!(
+    "no private key found in {}",
+    path
+  )))
+}
+
+#[test]
+fn test_tls_config_from_vars() {
+  let config = TlsConfig::from_vars(|_| None).unwrap();
+  assert!(config.server.is_none());
+  assert!(config.client.is_none());
+
+  let config = TlsConfig::from_vars(|name| match name {
+    "FLO_TLS_SERVER_NAME" => Some("flo.example.com".to_string()),
+    _ => None,
+  })
+  .unwrap();
+  assert!(config.server.is_none());
+  assert!(config.client.is_some());
+
+  let err = TlsConfig::from_vars(|name| match name {
+    "FLO_TLS_CERT_PATH" => Some("cert.pem".to_string()),
+    _ => None,
+  })
+  .unwrap_err();
+  assert!(matches!(err, Error::TlsConfig(_)));
+}
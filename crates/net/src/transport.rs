@@ -0,0 +1,151 @@
+use std::fmt;
+use std::io::{self, IoSlice};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+#[cfg(feature = "tls")]
+use crate::tls::{TlsClientConfig, TlsServerConfig};
+
+/// Byte stream under `FloStream`, TCP or TLS over TCP
+pub(crate) enum Transport {
+  Tcp(TcpStream),
+  /// Server side handshake, completed by the first read or write
+  #[cfg(feature = "tls")]
+  TlsAccepting(Box<tokio_rustls::Accept<TcpStream>>),
+  #[cfg(feature = "tls")]
+  Tls(Box<tokio_rustls::TlsStream<TcpStream>>),
+}
+
+impl Transport {
+  #[cfg(feature = "tls")]
+  pub fn accept(socket: TcpStream, config: Option<&TlsServerConfig>) -> Self {
+    match config {
+      Some(config) => Self::TlsAccepting(Box::new(config.accept(socket))),
+      None => Self::Tcp(socket),
+    }
+  }
+
+  #[cfg(feature = "tls")]
+  pub async fn connect(
+    socket: TcpStream,
+    config: Option<&TlsClientConfig>,
+  ) -> crate::error::Result<Self> {
+    Ok(match config {
+      Some(config) => Self::Tls(Box::new(config.connect(socket).await?.into())),
+      None => Self::Tcp(socket),
+    })
+  }
+
+  fn tcp(&self) -> io::Result<&TcpStream> {
+    match *self {
+      Self::Tcp(ref socket) => Ok(socket),
+      #[cfg(feature = "tls")]
+      Self::TlsAccepting(ref accept) => accept
+        .get_ref()
+        .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected)),
+      #[cfg(feature = "tls")]
+      Self::Tls(ref stream) => Ok(stream.get_ref().0),
+    }
+  }
+
+  pub fn local_addr(&self) -> io::Result<SocketAddr> {
+    self.tcp()?.local_addr()
+  }
+
+  pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+    self.tcp()?.peer_addr()
+  }
+
+  /// Returns the TCP socket, `None` if the bytes are encrypted
+  pub fn into_tcp(self) -> Option<TcpStream> {
+    match self {
+      Self::Tcp(socket) => Some(socket),
+      #[cfg(feature = "tls")]
+      _ => None,
+    }
+  }
+
+  #[cfg(feature = "tls")]
+  fn poll_handshake(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    use std::future::Future;
+    if let Self::TlsAccepting(ref mut accept) = *self {
+      let stream = futures::ready!(Pin::new(accept.as_mut()).poll(cx))?;
+      *self = Self::Tls(Box::new(stream.into()));
+    }
+    Poll::Ready(Ok(()))
+  }
+
+  #[cfg(not(feature = "tls"))]
+  fn poll_handshake(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    Poll::Ready(Ok(()))
+  }
+}
+
+impl fmt::Debug for Transport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match *self {
+      Self::Tcp(ref socket) => f.debug_tuple("Tcp").field(socket).finish(),
+      #[cfg(feature = "tls")]
+      Self::TlsAccepting(_) => f.write_str("TlsAccepting"),
+      #[cfg(feature = "tls")]
+      Self::Tls(ref stream) => f.debug_tuple("Tls").field(stream.get_ref().0).finish(),
+    }
+  }
+}
+
+macro_rules! poll_inner {
+  ($self:ident, $cx:ident, $method:ident($($arg:expr),*)) => {{
+    let this = $self.get_mut();
+    futures::ready!(this.poll_handshake($cx))?;
+    match *this {
+      Transport::Tcp(ref mut stream) => Pin::new(stream).$method($($arg),*),
+      #[cfg(feature = "tls")]
+      Transport::Tls(ref mut stream) => Pin::new(stream.as_mut()).$method($($arg),*),
+      #[cfg(feature = "tls")]
+      Transport::TlsAccepting(_) => unreachable!("handshake completed"),
+    }
+  }};
+}
+
+impl AsyncRead for Transport {
+  fn poll_read(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<io::Result<()>> {
+    poll_inner!(self, cx, poll_read(cx, buf))
+  }
+}
+
+impl AsyncWrite for Transport {
+  fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    poll_inner!(self, cx, poll_write(cx, buf))
+  }
+
+  fn poll_write_vectored(
+    self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    bufs: &[IoSlice<'_>],
+  ) -> Poll<io::Result<usize>> {
+    poll_inner!(self, cx, poll_write_vectored(cx, bufs))
+  }
+
+  fn is_write_vectored(&self) -> bool {
+    match *self {
+      Self::Tcp(ref socket) => socket.is_write_vectored(),
+      #[cfg(feature = "tls")]
+      _ => false,
+    }
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    poll_inner!(self, cx, poll_flush(cx))
+  }
+
+  fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    poll_inner!(self, cx, poll_shutdown(cx))
+  }
+}
@@ -1,3 +1,6 @@
+mod routing;
+pub use routing::RoutingStats;
+
 use futures::stream::StreamExt;
 
 use flo_constants::NODE_CLIENT_PORT;
@@ -21,8 +24,10 @@ pub async fn serve_client(state: GlobalStateRef) -> Result<()> {
           Err(err) => {
             let reason = match &err {
               Error::InvalidToken => ClientConnectRejectReason::InvalidToken,
+              Error::GameNotFound => ClientConnectRejectReason::GameNotFound,
               _ => ClientConnectRejectReason::Unknown,
             };
+            state.routing_stats().rejected(reason);
             stream
               .send(PacketClientConnectReject {
                 reason: reason.into(),
@@ -43,9 +48,11 @@ pub async fn serve_client(state: GlobalStateRef) -> Result<()> {
         let session = match state.get_game(claim.game_id) {
           Some(session) => session,
           None => {
+            let reason = ClientConnectRejectReason::GameNotFound;
+            state.routing_stats().rejected(reason);
             stream
               .send(PacketClientConnectReject {
-                reason: ClientConnectRejectReason::Unknown.into(),
+                reason: reason.into(),
                 message: format!("Game session was not found."),
               })
               .await
@@ -53,6 +60,7 @@ pub async fn serve_client(state: GlobalStateRef) -> Result<()> {
             return;
           }
         };
+        state.routing_stats().accepted(claim.routed_by_game_id);
 
         if claim.shutdown_retry {
           if let Err(err) = session
@@ -116,15 +124,16 @@ async fn handshake(state: &GlobalState, stream: &mut FloStream) -> Result<Claim>
     return Err(Error::InvalidToken);
   };
 
-  let pending = state
-    .get_pending_player(&token)
-    .ok_or_else(|| Error::InvalidToken)?;
+  let pending = routing::route(connect.game_id, state.get_pending_player(&token), |id| {
+    state.get_game(id).is_some()
+  })?;
 
   Ok(Claim {
     game_id: pending.game_id,
     player_id: pending.player_id,
     shutdown_retry: connect.retry_shutdown,
     leave_reason: connect.leave_reason.map(LeaveReason::from),
+    routed_by_game_id: connect.game_id != 0,
   })
 }
 
@@ -134,4 +143,5 @@ pub struct Claim {
   player_id: i32,
   shutdown_retry: bool,
   leave_reason: Option<LeaveReason>,
+  routed_by_game_id: bool,
 }
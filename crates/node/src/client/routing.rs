@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use flo_net::proto::flo_node::{ClientConnectRejectReason, PacketNodeRoutingStats};
+
+use crate::error::*;
+use crate::metrics;
+use crate::state::RegisteredPlayer;
+
+/// Resolves the game a client connection belongs to.
+///
+/// All games share the client port. Clients send the game id their token was issued for,
+/// so connections for games this node no longer hosts are rejected before the token lookup,
+/// and a token can't be used to join another game. Older clients send 0 and are routed by
+/// token only.
+pub fn route<F>(
+  game_id_hint: i32,
+  pending: Option<RegisteredPlayer>,
+  game_exists: F,
+) -> Result<RegisteredPlayer>
+where
+  F: FnOnce(i32) -> bool,
+{
+  if game_id_hint != 0 && !game_exists(game_id_hint) {
+    return Err(Error::GameNotFound);
+  }
+  let pending = pending.ok_or_else(|| Error::InvalidToken)?;
+  if game_id_hint != 0 && pending.game_id != game_id_hint {
+    return Err(Error::InvalidToken);
+  }
+  Ok(pending)
+}
+
+/// Client connection routing counters, reported to the controller
+#[derive(Debug, Default)]
+pub struct RoutingStats {
+  accepted: AtomicU64,
+  routed_by_game_id: AtomicU64,
+  rejected_invalid_token: AtomicU64,
+  rejected_game_not_found: AtomicU64,
+  rejected_other: AtomicU64,
+}
+
+impl RoutingStats {
+  pub fn accepted(&self, by_game_id: bool) {
+    self.accepted.fetch_add(1, Ordering::Relaxed);
+    if by_game_id {
+      self.routed_by_game_id.fetch_add(1, Ordering::Relaxed);
+    }
+  }
+
+  pub fn rejected(&self, reason: ClientConnectRejectReason) {
+    let counter = match reason {
+      ClientConnectRejectReason::InvalidToken => &self.rejected_invalid_token,
+      ClientConnectRejectReason::GameNotFound => &self.rejected_game_not_found,
+      _ => &self.rejected_other,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn snapshot(&self) -> PacketNodeRoutingStats {
+    PacketNodeRoutingStats {
+      games: metrics::GAME_SESSIONS.get() as u32,
+      player_connections: metrics::PLAYERS_CONNECTIONS.get() as u32,
+      pending_player_tokens: metrics::PLAYER_TOKENS.get() as u32,
+      accepted: self.accepted.load(Ordering::Relaxed),
+      routed_by_game_id: self.routed_by_game_id.load(Ordering::Relaxed),
+      rejected_invalid_token: self.rejected_invalid_token.load(Ordering::Relaxed),
+      rejected_game_not_found: self.rejected_game_not_found.load(Ordering::Relaxed),
+      rejected_other: self.rejected_other.load(Ordering::Relaxed),
+    }
+  }
+}
+
+#[test]
+fn test_route() {
+  let player = |game_id| {
+    Some(RegisteredPlayer {
+      player_id: 1,
+      game_id,
+    })
+  };
+
+  assert_eq!(route(0, player(2), |_| false).unwrap().game_id, 2);
+  assert_eq!(route(2, player(2), |id| id == 2).unwrap().game_id, 2);
+  assert!(matches!(
+    route(3, player(2), |id| id == 2),
+    Err(Error::GameNotFound)
+  ));
+  assert!(matches!(
+    route(3, player(2), |_| true),
+    Err(Error::InvalidToken)
+  ));
+  assert!(matches!(route(0, None, |_| true), Err(Error::InvalidToken)));
+}
@@ -0,0 +1,202 @@
+//! Node settings the controller can change without a restart.
+//!
+//! Defaults come from the `FLO_NODE_*` env vars. Updates pushed by the controller are applied
+//! live and saved to `FLO_NODE_CONFIG_PATH`, the saved config replaces the defaults on start.
+
+use flo_net::proto::flo_node::{NodeConfig as NodeConfigPacket, NodeMapRepositories};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::*;
+
+pub static CONFIG_PATH: Lazy<PathBuf> = Lazy::new(|| {
+  std::env::var_os("FLO_NODE_CONFIG_PATH")
+    .map(PathBuf::from)
+    .unwrap_or_else(|| PathBuf::from("flo-node-config.json"))
+});
+
+static CURRENT: Lazy<RwLock<NodeConfig>> = Lazy::new(|| RwLock::new(NodeConfig::from_env()));
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NodeConfig {
+  pub map_repositories: Vec<String>,
+  /// 0 if unlimited
+  pub max_games: usize,
+  /// `RUST_LOG` format directives, `None` uses `RUST_LOG`
+  pub log_level: Option<String>,
+  /// 0 disables keyframes
+  pub obs_keyframe_interval_secs: u64,
+  /// 0 disables RTT stats
+  pub obs_rtt_stats_interval_secs: u64,
+}
+
+impl Default for NodeConfig {
+  fn default() -> Self {
+    Self::from_env()
+  }
+}
+
+impl NodeConfig {
+  fn from_env() -> Self {
+    Self {
+      map_repositories: crate::constants::MAP_REPOSITORIES.clone(),
+      max_games: *crate::constants::MAX_GAMES,
+      log_level: None,
+      obs_keyframe_interval_secs: crate::constants::OBS_KEYFRAME_INTERVAL.as_secs(),
+      obs_rtt_stats_interval_secs: crate::constants::RTT_STATS_REPORT_INTERVAL.as_secs(),
+    }
+  }
+
+  pub fn obs_keyframe_interval(&self) -> Duration {
+    Duration::from_secs(self.obs_keyframe_interval_secs)
+  }
+
+  pub fn obs_rtt_stats_interval(&self) -> Duration {
+    Duration::from_secs(self.obs_rtt_stats_interval_secs)
+  }
+
+  /// Returns the config with the fields set in `update`,
+  /// fails without changes if any of them is invalid
+  pub fn merge(&self, update: NodeConfigPacket) -> Result<Self> {
+    let mut next = self.clone();
+    if let Some(repos) = update.map_repositories {
+      for url in &repos.urls {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+          return Err(Error::InvalidConfig(format!(
+            "map repository url must be http(s): {}",
+            url
+          )));
+        }
+      }
+      next.map_repositories = repos.urls;
+    }
+    if let Some(max_games) = update.max_games {
+      next.max_games = max_games as usize;
+    }
+    if let Some(log_level) = update.log_level {
+      next.log_level = if log_level.is_empty() {
+        None
+      } else {
+        flo_log_subscriber::validate_filter(&log_level)
+          .map_err(|err| Error::InvalidConfig(format!("log level: {}", err)))?;
+        Some(log_level)
+      };
+    }
+    if let Some(secs) = update.obs_keyframe_interval_secs {
+      next.obs_keyframe_interval_secs = secs as u64;
+    }
+    if let Some(secs) = update.obs_rtt_stats_interval_secs {
+      next.obs_rtt_stats_interval_secs = secs as u64;
+    }
+    Ok(next)
+  }
+
+  pub fn pack(&self) -> NodeConfigPacket {
+    NodeConfigPacket {
+      map_repositories: Some(NodeMapRepositories {
+        urls: self.map_repositories.clone(),
+      }),
+      max_games: Some(self.max_games as u32),
+      log_level: Some(self.log_level.clone().unwrap_or_default()),
+      obs_keyframe_interval_secs: Some(self.obs_keyframe_interval_secs as u32),
+      obs_rtt_stats_interval_secs: Some(self.obs_rtt_stats_interval_secs as u32),
+    }
+  }
+
+  fn apply_log_level(&self) {
+    let directives = match self.log_level.clone() {
+      Some(v) => v,
+      None => std::env::var("RUST_LOG").unwrap_or_default(),
+    };
+    if let Err(err) = flo_log_subscriber::set_filter(&directives) {
+      tracing::warn!("set log level: {}", err);
+    }
+  }
+}
+
+pub fn current() -> NodeConfig {
+  CURRENT.read().clone()
+}
+
+/// Replaces the env config with the saved config, if any
+pub fn load() -> Result<NodeConfig> {
+  let bytes = match std::fs::read(&*CONFIG_PATH) {
+    Ok(bytes) => bytes,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(current()),
+    Err(err) => return Err(err.into()),
+  };
+  let config: NodeConfig = serde_json::from_slice(&bytes)?;
+  if config.log_level.is_some() {
+    config.apply_log_level();
+  }
+  *CURRENT.write() = config.clone();
+  tracing::info!("config loaded from {}: {:?}", CONFIG_PATH.display(), config);
+  Ok(config)
+}
+
+/// Saves and applies an update from the controller.
+/// Components that read the config when they start, like game dispatchers,
+/// pick up the update the next time they start.
+pub fn update(update: NodeConfigPacket) -> Result<NodeConfig> {
+  let mut current = CURRENT.write();
+  let next = current.merge(update)?;
+  save(&next)?;
+  if next.log_level != current.log_level {
+    next.apply_log_level();
+  }
+  *current = next.clone();
+  Ok(next)
+}
+
+fn save(config: &NodeConfig) -> Result<()> {
+  let tmp_path = CONFIG_PATH.with_extension("tmp");
+  std::fs::write(&tmp_path, serde_json::to_vec_pretty(config)?)?;
+  std::fs::rename(&tmp_path, &*CONFIG_PATH)?;
+  Ok(())
+}
+
+#[test]
+fn test_node_config_merge() {
+  let config = NodeConfig {
+    map_repositories: vec![],
+    max_games: 0,
+    log_level: None,
+    obs_keyframe_interval_secs: 30,
+    obs_rtt_stats_interval_secs: 15,
+  };
+
+  let merged = config
+    .merge(NodeConfigPacket {
+      map_repositories: Some(NodeMapRepositories {
+        urls: vec!["https://maps.example.com".to_string()],
+      }),
+      max_games: Some(20),
+      obs_keyframe_interval_secs: Some(0),
+      ..Default::default()
+    })
+    .unwrap();
+  assert_eq!(merged.map_repositories, vec!["https://maps.example.com"]);
+  assert_eq!(merged.max_games, 20);
+  assert_eq!(merged.obs_keyframe_interval_secs, 0);
+  // unset fields are kept
+  assert_eq!(merged.obs_rtt_stats_interval_secs, 15);
+  assert_eq!(merged.log_level, None);
+
+  assert!(matches!(
+    config.merge(NodeConfigPacket {
+      max_games: Some(20),
+      map_repositories: Some(NodeMapRepositories {
+        urls: vec!["ftp://maps.example.com".to_string()],
+      }),
+      ..Default::default()
+    }),
+    Err(Error::InvalidConfig(_))
+  ));
+
+  let packed = merged.pack();
+  assert_eq!(config.merge(packed).unwrap(), merged);
+}
@@ -1,5 +1,6 @@
 use flo_observer::record::ObserverRecordSource;
 use once_cell::sync::Lazy;
+use std::path::PathBuf;
 use std::time::Duration;
 
 pub const PEER_CHANNEL_SIZE: usize = 250;
@@ -13,10 +14,80 @@ pub static GAME_DEFAULT_STEP_MS: Lazy<u16> = Lazy::new(|| {
     .and_then(|v| v.parse().ok())
     .unwrap_or(30)
 });
+// Comma separated Warcraft III versions this node supports, e.g. `1.26,1.32.10`, reported to the controller
+pub static WAR3_VERSIONS: Lazy<Vec<String>> = Lazy::new(|| {
+  std::env::var("FLO_NODE_WAR3_VERSIONS")
+    .map(|v| {
+      v.split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+    })
+    .unwrap_or_default()
+});
+// Comma separated base URLs of map repositories, can be changed by the controller
+pub static MAP_REPOSITORIES: Lazy<Vec<String>> = Lazy::new(|| {
+  std::env::var("FLO_NODE_MAP_REPOSITORIES")
+    .map(|v| {
+      v.split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+    })
+    .unwrap_or_default()
+});
+pub const ROUTING_STATS_INTERVAL: Duration = Duration::from_secs(30);
+// Reserved games not committed by the controller within this time are rolled back
+pub static GAME_RESERVATION_TIMEOUT: Lazy<Duration> = Lazy::new(|| {
+  Duration::from_secs(
+    std::env::var("FLO_NODE_GAME_RESERVATION_TIMEOUT_SECS")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .unwrap_or(15),
+  )
+});
+// Games the node hosts at most, 0 if unlimited, can be changed by the controller
+pub static MAX_GAMES: Lazy<usize> = Lazy::new(|| {
+  std::env::var("FLO_NODE_MAX_GAMES")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(0)
+});
+pub const GAME_RESERVATION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 pub const GAME_PING_INTERVAL: Duration = Duration::from_secs(1);
 pub const GAME_PING_TIMEOUT: Duration = Duration::from_secs(5);
 pub const GAME_CLOCK_MAX_PAUSE: Duration = Duration::from_secs(60 - 3);
 
+/// What happens when the lag screen times out for a player who stopped acking timeslots
+/// while their connection is still open, set by `FLO_NODE_STALLED_PLAYER_MODE`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StalledPlayerMode {
+  /// Drop the player, the same as a disconnected player
+  Drop,
+  /// Keep the game paused for another `STALLED_PLAYER_GRACE`, then drop
+  Pause,
+  /// Resume without waiting for the player and tell the other players,
+  /// the player is waited for again once their client catches up
+  Continue,
+}
+
+pub static STALLED_PLAYER_MODE: Lazy<StalledPlayerMode> = Lazy::new(|| {
+  let mode = std::env::var("FLO_NODE_STALLED_PLAYER_MODE").unwrap_or_default();
+  match mode.as_str() {
+    "pause" => StalledPlayerMode::Pause,
+    "continue" => StalledPlayerMode::Continue,
+    _ => StalledPlayerMode::Drop,
+  }
+});
+pub static STALLED_PLAYER_GRACE: Lazy<Duration> = Lazy::new(|| {
+  Duration::from_secs(
+    std::env::var("FLO_NODE_STALLED_PLAYER_GRACE_SECS")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .unwrap_or(60),
+  )
+});
+
 #[cfg(not(debug_assertions))]
 pub const GAME_DELAY_RANGE: [Duration; 2] = [Duration::from_millis(25), Duration::from_millis(100)];
 #[cfg(debug_assertions)]
@@ -26,6 +97,16 @@ pub const GAME_DELAY_RANGE: [Duration; 2] =
 pub const OBS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
 pub const OBS_CHANNEL_SIZE: usize = 10000;
 pub const OBS_MAX_CHUNK_SIZE: usize = 512 * 1024;
+// Interval of game state keyframes in the observer stream, 0 to disable,
+// can be changed by the controller
+pub static OBS_KEYFRAME_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+  Duration::from_secs(
+    std::env::var("FLO_NODE_OBS_KEYFRAME_INTERVAL_SECS")
+      .ok()
+      .and_then(|v| v.parse().ok())
+      .unwrap_or(30),
+  )
+});
 pub static OBS_SOURCE: Lazy<ObserverRecordSource> = Lazy::new(|| {
   std::env::var("OBSERVER_SOURCE")
     .ok()
@@ -33,5 +114,35 @@ pub static OBS_SOURCE: Lazy<ObserverRecordSource> = Lazy::new(|| {
     .unwrap_or(ObserverRecordSource::Test)
 });
 
+// Set to disable publishing game records to Kinesis
+pub static OBS_KINESIS_DISABLED: Lazy<bool> = Lazy::new(|| {
+  std::env::var("FLO_NODE_KINESIS_DISABLED")
+    .map(|v| v == "1" || v == "true")
+    .unwrap_or(false)
+});
+
+// Local game record files, disabled if `FLO_NODE_RECORD_DIR` is not set
+pub static RECORD_DIR: Lazy<Option<PathBuf>> =
+  Lazy::new(|| std::env::var_os("FLO_NODE_RECORD_DIR").map(PathBuf::from));
+pub static RECORD_MAX_FILE_SIZE: Lazy<u64> = Lazy::new(|| {
+  std::env::var("FLO_NODE_RECORD_MAX_FILE_SIZE")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(8 * 1024 * 1024)
+});
+pub static RECORD_RETENTION: Lazy<Duration> = Lazy::new(|| {
+  Duration::from_secs(
+    std::env::var("FLO_NODE_RECORD_RETENTION_HOURS")
+      .ok()
+      .and_then(|v| v.parse::<u64>().ok())
+      .unwrap_or(72)
+      * 3600,
+  )
+});
+pub const RECORD_CHANNEL_SIZE: usize = 10000;
+pub const RECORD_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+pub const RECORD_CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
 pub const RTT_STATS_REPORT_DELAY: Duration = std::time::Duration::from_secs(5);
+// Default interval of RTT stats in the observer stream, can be changed by the controller
 pub const RTT_STATS_REPORT_INTERVAL: Duration = std::time::Duration::from_secs(15);
@@ -7,7 +7,7 @@ use tracing_futures::Instrument;
 
 use flo_constants::NODE_CONTROLLER_PORT;
 use flo_net::listener::FloListener;
-use flo_net::packet::Frame;
+use flo_net::packet::{FloPacket, Frame};
 use flo_net::proto::flo_node::*;
 use flo_net::stream::FloStream;
 use flo_net::try_flo_packet;
@@ -77,9 +77,18 @@ impl ControllerServer {
       return Err(Error::InvalidSecret);
     }
 
+    let config = crate::config::current();
     stream
       .send(PacketControllerConnectAccept {
         version: Some(crate::version::FLO_NODE_VERSION.into()),
+        war3_versions: crate::constants::WAR3_VERSIONS.clone(),
+        capabilities: Some(NodeCapabilities {
+          game_routing: true,
+          routing_stats_interval_secs: crate::constants::ROUTING_STATS_INTERVAL.as_secs() as u32,
+          two_phase_create: true,
+          max_games: config.max_games as u32,
+        }),
+        config: Some(config.pack()),
       })
       .await?;
 
@@ -111,6 +120,32 @@ impl ControllerServerHandle {
   }
 }
 
+/// Reports client connection routing counters to the controller
+pub async fn serve_routing_stats(
+  g_state: GlobalStateRef,
+  ctrl: ControllerServerHandle,
+) -> Result<()> {
+  let mut interval = tokio::time::interval(crate::constants::ROUTING_STATS_INTERVAL);
+  loop {
+    interval.tick().await;
+    if ctrl.state.current.read().is_none() {
+      continue;
+    }
+    let frame = g_state.routing_stats().snapshot().encode_as_frame()?;
+    // stale reports are useless, drop instead of waiting for a full send buffer
+    ctrl.state.frame_tx.try_send(frame).ok();
+  }
+}
+
+/// Rolls back reserved games the controller didn't commit in time
+pub async fn serve_game_reservations(g_state: GlobalStateRef) -> Result<()> {
+  let mut interval = tokio::time::interval(crate::constants::GAME_RESERVATION_CHECK_INTERVAL);
+  loop {
+    interval.tick().await;
+    g_state.rollback_reservations(Some(*crate::constants::GAME_RESERVATION_TIMEOUT));
+  }
+}
+
 #[derive(Debug)]
 struct ControllerConn {
   _scope: SpawnScope,
@@ -141,30 +176,37 @@ async fn handle_stream(
   mut scope: SpawnScopeHandle,
 ) -> Result<()> {
   let mut rx = state.frame_rx.lock().await;
-  loop {
-    tokio::select! {
-      _ = scope.left() => {
-        break;
-      }
-      frame = stream.recv_frame() => {
-        let frame = frame?;
-        let state = state.clone();
-        tokio::spawn(async move {
-          if let Err(e) = handle_frame(&state, frame).await {
-            tracing::error!("handle_frame: {}", e);
-          }
-        }.instrument(tracing::debug_span!("handle_frame_worker")));
-      }
-      next = rx.recv() => {
-        if let Some(frame) = next {
-          stream.send_frame_timeout(frame).await?;
-        } else {
+  let res = async {
+    loop {
+      tokio::select! {
+        _ = scope.left() => {
           break;
         }
+        frame = stream.recv_frame() => {
+          let frame = frame?;
+          let state = state.clone();
+          tokio::spawn(async move {
+            if let Err(e) = handle_frame(&state, frame).await {
+              tracing::error!("handle_frame: {}", e);
+            }
+          }.instrument(tracing::debug_span!("handle_frame_worker")));
+        }
+        next = rx.recv() => {
+          if let Some(frame) = next {
+            stream.send_frame_timeout(frame).await?;
+          } else {
+            break;
+          }
+        }
       }
     }
+    Ok(())
   }
-  Ok(())
+  .await;
+  // the commits of reserved games can't arrive on a new connection before `rx` is released,
+  // the controller fails to start them
+  state.g_state.rollback_reservations(None);
+  res
 }
 
 async fn handle_frame(state: &Arc<State>, mut frame: Frame) -> Result<()> {
@@ -181,10 +223,24 @@ async fn handle_frame(state: &Arc<State>, mut frame: Frame) -> Result<()> {
         let frame = state.g_state.handle_controller_create_game(ControllerServerHandle::new(state.clone()), pkt)?;
         flo_log::result_ok!("create game", tx.send(frame).await);
       }
+      pkt: PacketControllerCommitGame => {
+        let frame = state.g_state.handle_controller_commit_game(pkt)?;
+        flo_log::result_ok!("commit game", tx.send(frame).await);
+      }
       pkt: PacketControllerUpdateSlotStatus => {
         let frame = state.g_state.handle_controller_update_slot_client_status(pkt).await?;
         flo_log::result_ok!("update slot status", tx.send(frame).await);
       }
+      pkt: PacketControllerGameCommand => {
+        state.g_state.handle_controller_game_command(pkt).await;
+      }
+      pkt: PacketControllerGameSurrender => {
+        state.g_state.handle_controller_game_surrender(pkt).await;
+      }
+      pkt: PacketControllerUpdateNodeConfig => {
+        let frame = state.g_state.handle_controller_update_node_config(pkt)?;
+        flo_log::result_ok!("update node config", tx.send(frame).await);
+      }
     }
   }
   Ok(())
@@ -7,6 +7,8 @@ pub enum Error {
   Cancelled,
   #[error("game exists")]
   GameExists,
+  #[error("game not found")]
+  GameNotFound,
   #[error("game desync: {0:?}")]
   GameDesync(#[from] AckError),
   #[error("game has no player")]
@@ -29,6 +31,8 @@ pub enum Error {
   InvalidSecret,
   #[error("invalid token")]
   InvalidToken,
+  #[error("invalid config: {0}")]
+  InvalidConfig(String),
   #[error("invalid client status transition: {0:?} => {1:?}")]
   InvalidClientStatusTransition(SlotClientStatus, SlotClientStatus),
   #[error("observer put record: {0}")]
@@ -43,6 +47,8 @@ pub enum Error {
   Net(#[from] flo_net::error::Error),
   #[error("proto: {0}")]
   Proto(#[from] s2_grpc_utils::result::Error),
+  #[error("json: {0}")]
+  Json(#[from] serde_json::Error),
   #[error("http: {0}")]
   Http(#[from] hyper::Error),
 }
@@ -14,8 +14,9 @@ use crate::game::{
 use crate::observer::ObserverPublisherHandle;
 use flo_net::packet::{Frame, PacketTypeId};
 use flo_net::ping::{PingMsg, PingStream};
+use flo_net::proto::flo_common::GameCommand;
 use flo_net::w3gs::{W3GSFrameExt, W3GSMetadata, W3GSPacket, W3GSPacketTypeId};
-use flo_observer::record::{RTTStats, RTTStatsItem};
+use flo_observer::record::{Keyframe, KeyframePlayer, RTTStats, RTTStatsItem};
 use flo_util::chat::{parse_chat_command, ChatCommand};
 use flo_w3gs::action::{IncomingAction, IncomingAction2, OutgoingKeepAlive};
 use flo_w3gs::protocol::action::{OutgoingAction, PlayerAction, TimeSlot};
@@ -25,6 +26,7 @@ use flo_w3gs::protocol::lag::{LagPlayer, StartLag, StopLag};
 use flo_w3gs::protocol::leave::LeaveReq;
 use flo_w3gs::protocol::leave::{LeaveAck, PlayerLeft};
 use flo_w3gs::protocol::packet::*;
+use flo_w3gs::protocol::save::{SaveAction, SaveGameProgress, SavedSlot, SavedSlotMap};
 use futures::stream::StreamExt;
 use parking_lot::Mutex;
 use s2_grpc_utils::S2ProtoEnum;
@@ -50,6 +52,13 @@ pub enum Cmd {
     player_id: i32,
     leave_reason: Option<LeaveReason>,
   },
+  GameCommand {
+    player_id: i32,
+    command: GameCommand,
+  },
+  Surrender {
+    player_ids: Vec<i32>,
+  },
 }
 
 enum PeerMsg {
@@ -115,6 +124,7 @@ impl Dispatcher {
       obs.clone(),
       status_rx,
       action_tx.clone(),
+      out_tx.clone(),
       ct.clone(),
     );
 
@@ -184,6 +194,24 @@ impl Dispatcher {
     Ok(())
   }
 
+  pub async fn game_command(&self, player_id: i32, command: GameCommand) -> Result<()> {
+    self
+      .cmd_tx
+      .send(Cmd::GameCommand { player_id, command })
+      .await
+      .map_err(|_| Error::Cancelled)?;
+    Ok(())
+  }
+
+  pub async fn surrender(&self, player_ids: Vec<i32>) -> Result<()> {
+    self
+      .cmd_tx
+      .send(Cmd::Surrender { player_ids })
+      .await
+      .map_err(|_| Error::Cancelled)?;
+    Ok(())
+  }
+
   async fn serve(
     mut state: State,
     mut rx: Receiver<Cmd>,
@@ -255,14 +283,18 @@ impl Dispatcher {
       let pause_timeout = sleep(Duration::from_secs(0));
       tokio::pin!(pause_timeout);
 
-      {
+      let config = crate::config::current();
+      let rtt_stats_interval = config.obs_rtt_stats_interval();
+      let keyframe_interval = config.obs_keyframe_interval();
+
+      if !rtt_stats_interval.is_zero() {
         let ct = ct.clone();
         let shared = shared.clone();
         tokio::spawn(async move {
           let base_time = tokio::time::Instant::from_std(Instant::now());
           let mut stream = interval_at(
             base_time + crate::constants::RTT_STATS_REPORT_DELAY,
-            crate::constants::RTT_STATS_REPORT_INTERVAL,
+            rtt_stats_interval,
           );
           stream.set_missed_tick_behavior(MissedTickBehavior::Skip);
           loop {
@@ -279,6 +311,26 @@ impl Dispatcher {
         });
       }
 
+      if !keyframe_interval.is_zero() {
+        let ct = ct.clone();
+        let shared = shared.clone();
+        tokio::spawn(async move {
+          let interval = keyframe_interval;
+          let mut stream = interval_at(tokio::time::Instant::now() + interval, interval);
+          stream.set_missed_tick_behavior(MissedTickBehavior::Skip);
+          loop {
+            tokio::select! {
+              _ = ct.cancelled() => {
+                break;
+              }
+              _ = stream.tick() => {
+                shared.lock().push_keyframe();
+              }
+            }
+          }
+        });
+      }
+
       loop {
         tokio::select! {
           _ = ct.cancelled() => {
@@ -342,14 +394,21 @@ impl Dispatcher {
             }
           }
           _ = &mut pause_timeout, if tick_stream.is_paused() => {
-            if let Err(err) = shared.lock().drop_all_lag_players() {
-              tracing::error!(
-                game_id,
-                "drop all lag players: {}", err
-              );
-              break;
+            match shared.lock().handle_lag_timeout() {
+              Ok(LagTimeoutAction::Resume) => {
+                tick_stream.resume();
+              }
+              Ok(LagTimeoutAction::ExtendPause(duration)) => {
+                pause_timeout.as_mut().reset((Instant::now() + duration).into());
+              }
+              Err(err) => {
+                tracing::error!(
+                  game_id,
+                  "handle lag timeout: {}", err
+                );
+                break;
+              }
             }
-            tick_stream.resume();
           }
         }
       }
@@ -384,12 +443,13 @@ impl State {
     obs: ObserverPublisherHandle,
     status_rx: watch::Receiver<DispatchStatus>,
     _action_tx: Sender<ActionMsg>,
+    out_tx: GameEventSender,
     ct: CancellationToken,
   ) -> Self {
     State {
       game_id,
       ct,
-      shared: Arc::new(Mutex::new(Shared::new(game_id, slots, obs))),
+      shared: Arc::new(Mutex::new(Shared::new(game_id, slots, obs, out_tx))),
       status_rx,
       game_player_id_lookup: slots
         .into_iter()
@@ -443,6 +503,49 @@ impl State {
           tracing::error!(game_id = self.game_id, player_id, "send shutdown: {}", err);
         }
       }
+      Cmd::GameCommand { player_id, command } => {
+        let name = self
+          ._player_name_lookup
+          .get(&player_id)
+          .cloned()
+          .unwrap_or_else(|| format!("Player {}", player_id));
+        let message = match command {
+          GameCommand::SurrenderVote => format!("{} voted to surrender.", name),
+          GameCommand::PauseRequest => format!("{} requested a pause.", name),
+          GameCommand::DesyncReport => format!("{} reported a desync.", name),
+        };
+        tracing::info!(
+          game_id = self.game_id,
+          player_id,
+          "game command: {:?}",
+          command
+        );
+        self.shared.lock().broadcast_message(message);
+      }
+      Cmd::Surrender { player_ids } => {
+        let player_ids: Vec<i32> = player_ids
+          .into_iter()
+          .filter(|id| !self.left_players.contains(id))
+          .collect();
+        if player_ids.is_empty() {
+          return Ok(());
+        }
+        let names: Vec<_> = player_ids
+          .iter()
+          .filter_map(|id| self._player_name_lookup.get(id).cloned())
+          .collect();
+        tracing::info!(game_id = self.game_id, "surrender: {:?}", player_ids);
+        self
+          .shared
+          .lock()
+          .broadcast_message(format!("{} surrendered.", names.join(", ")));
+        // reported as a loss instead of a disconnect
+        for player_id in player_ids {
+          self
+            .handle_player_leave(player_id, Some(LeaveReason::LeaveLost), action_tx, out_tx)
+            .await?;
+        }
+      }
     }
 
     Ok(())
@@ -1034,11 +1137,19 @@ struct Shared {
   sync: SyncMap,
   lagging_player_ids: BTreeSet<i32>,
   drop_votes: BTreeSet<i32>,
+  stalled_grace_used: bool,
   obs: ObserverPublisherHandle,
+  save: Option<SaveGameProgress>,
+  out_tx: GameEventSender,
 }
 
 impl Shared {
-  fn new(game_id: i32, slots: &[PlayerSlot], obs: ObserverPublisherHandle) -> Self {
+  fn new(
+    game_id: i32,
+    slots: &[PlayerSlot],
+    obs: ObserverPublisherHandle,
+    out_tx: GameEventSender,
+  ) -> Self {
     let sync = SyncMap::new(slots.iter().map(|s| s.player.player_id).collect());
     let mut slot_id_lookup = BTreeMap::new();
     Self {
@@ -1056,7 +1167,10 @@ impl Shared {
       sync,
       lagging_player_ids: BTreeSet::new(),
       drop_votes: BTreeSet::new(),
+      stalled_grace_used: false,
       obs,
+      save: None,
+      out_tx,
     }
   }
 
@@ -1078,6 +1192,10 @@ impl Shared {
       }
     }
 
+    for (slot_player_id, save) in SaveAction::parse_player_actions(&tick.actions) {
+      self.handle_save_action(slot_player_id, save);
+    }
+
     if tick.actions_bytes_len > DISPATCH_ACTIONS_MTU {
       tracing::debug!(
         "over-sized actions: tick = {}, size = {}, len = {}",
@@ -1154,6 +1272,20 @@ impl Shared {
       .push_rtt_stat(self.game_id, RTTStats::new(time, items))
   }
 
+  fn push_keyframe(&mut self) {
+    let players = self
+      .slot_id_lookup
+      .iter()
+      .map(|(player_id, slot_player_id)| KeyframePlayer {
+        player_id: *player_id,
+        slot_player_id: *slot_player_id,
+        left: !self.map.contains_key(player_id),
+        lagging: self.lagging_player_ids.contains(player_id),
+      });
+    let keyframe = Keyframe::new(self.sync.time(), self.sync.tick(), players);
+    self.obs.push_keyframe(self.game_id, keyframe)
+  }
+
   fn handle_lag(&mut self, add_player_ids: Vec<i32>) -> Result<bool> {
     self.lagging_player_ids.extend(add_player_ids);
     self.obs.push_start_lag(
@@ -1207,8 +1339,7 @@ impl Shared {
     if self.lagging_player_ids.is_empty() {
       return Ok(false);
     }
-    let mut stop_lag_players = vec![];
-    let mut packets = vec![];
+    let mut ended = vec![];
     for id in self.lagging_player_ids.clone() {
       let info = if let Some(info) = self.map.get_mut(&id) {
         let reconnected =
@@ -1223,18 +1354,35 @@ impl Shared {
         self.slot_id_lookup.get(&id).cloned().map(|slot| (slot, 0))
       };
       if let Some((slot, lag_duration_ms)) = info {
-        self.obs.push_end_lag(self.game_id, id);
-        self.lagging_player_ids.remove(&id);
-        stop_lag_players.push(id);
-        packets.push((
-          slot,
-          W3GSPacket::simple(StopLag(LagPlayer {
-            player_id: slot,
-            lag_duration_ms,
-          }))?,
-        ));
+        ended.push((id, slot, lag_duration_ms));
       }
     }
+    self.end_lag(ended)?;
+
+    // tracing::debug!("remaining lag players: {:?}", self.lagging_player_ids);
+    let done = self.lagging_player_ids.is_empty();
+    if done {
+      self.stalled_grace_used = false;
+    }
+    Ok(done)
+  }
+
+  // removes players from the lag screen: (player id, slot, lag duration)
+  fn end_lag(&mut self, items: Vec<(i32, u8, u32)>) -> Result<()> {
+    let mut stop_lag_players = vec![];
+    let mut packets = vec![];
+    for (id, slot, lag_duration_ms) in items {
+      self.obs.push_end_lag(self.game_id, id);
+      self.lagging_player_ids.remove(&id);
+      stop_lag_players.push(id);
+      packets.push((
+        slot,
+        W3GSPacket::simple(StopLag(LagPlayer {
+          player_id: slot,
+          lag_duration_ms,
+        }))?,
+      ));
+    }
     for (slot, pkt) in packets {
       let targets = self
         .map
@@ -1255,9 +1403,91 @@ impl Shared {
       );
       self.broadcast(pkt, broadcast::AllowList(&targets))?;
     }
+    Ok(())
+  }
 
-    // tracing::debug!("remaining lag players: {:?}", self.lagging_player_ids);
-    Ok(self.lagging_player_ids.is_empty())
+  /// The clock has been paused for `GAME_CLOCK_MAX_PAUSE`.
+  /// Disconnected players are dropped, players who stopped acking with their connection
+  /// still open are handled according to `STALLED_PLAYER_MODE`.
+  fn handle_lag_timeout(&mut self) -> Result<LagTimeoutAction> {
+    use crate::constants::{StalledPlayerMode, STALLED_PLAYER_GRACE, STALLED_PLAYER_MODE};
+
+    let stalled: Vec<i32> = self
+      .lagging_player_ids
+      .iter()
+      .filter(|id| {
+        self
+          .map
+          .get(*id)
+          .map(|p| p.stream_id().is_some())
+          .unwrap_or(false)
+      })
+      .cloned()
+      .collect();
+
+    if stalled.is_empty() {
+      self.drop_all_lag_players()?;
+      return Ok(LagTimeoutAction::Resume);
+    }
+
+    match *STALLED_PLAYER_MODE {
+      StalledPlayerMode::Drop => {
+        self.drop_all_lag_players()?;
+        Ok(LagTimeoutAction::Resume)
+      }
+      StalledPlayerMode::Pause => {
+        if self.stalled_grace_used {
+          self.drop_all_lag_players()?;
+          return Ok(LagTimeoutAction::Resume);
+        }
+        self.stalled_grace_used = true;
+        let grace = *STALLED_PLAYER_GRACE;
+        for id in stalled {
+          tracing::info!(
+            game_id = self.game_id,
+            player_id = id,
+            "stalled player, pause extended"
+          );
+          if let Some(name) = self.map.get(&id).map(|p| p.player_name().to_string()) {
+            self.broadcast_message(format!(
+              "{} is not responding but still connected, waiting {} more seconds.",
+              name,
+              grace.as_secs()
+            ));
+          }
+        }
+        Ok(LagTimeoutAction::ExtendPause(grace))
+      }
+      StalledPlayerMode::Continue => {
+        let mut ended = vec![];
+        for id in stalled {
+          tracing::info!(
+            game_id = self.game_id,
+            player_id = id,
+            "stalled player detached"
+          );
+          if let Some(desync) = self.sync.detach_player(id) {
+            tracing::warn!(
+              player_id = id,
+              "desync detected after detaching player: {:?}",
+              desync
+            );
+          }
+          if let Some(info) = self.map.get_mut(&id) {
+            let name = info.player_name().to_string();
+            ended.push((id, info.slot_player_id(), info.end_lag()));
+            self.broadcast_message(format!(
+              "{} is not responding, the game continues without waiting for them.",
+              name
+            ));
+          }
+        }
+        self.end_lag(ended)?;
+        // disconnected players
+        self.drop_all_lag_players()?;
+        Ok(LagTimeoutAction::Resume)
+      }
+    }
   }
 
   fn refresh_lag_packet(&mut self) -> Result<Option<Vec<(i32, u8, u32)>>> {
@@ -1343,9 +1573,78 @@ impl Shared {
       tracing::warn!("{}", self.sync.debug_pending())
     }
     player.close_stream();
+
+    if let Some(save) = self.save.as_mut() {
+      if save.remove_player(player.slot_player_id()) {
+        self.finish_save();
+      }
+    }
     Ok(())
   }
 
+  fn handle_save_action(&mut self, slot_player_id: u8, action: SaveAction) {
+    match action {
+      SaveAction::Started { name } => {
+        tracing::info!(
+          game_id = self.game_id,
+          slot_player_id,
+          "save game: {}",
+          name
+        );
+        let player_ids = self.map.values().map(|p| p.slot_player_id());
+        self.save = Some(SaveGameProgress::new(slot_player_id, name, player_ids));
+      }
+      SaveAction::Finished => {
+        let done = self
+          .save
+          .as_mut()
+          .map(|save| save.finish(slot_player_id))
+          .unwrap_or(false);
+        if done {
+          self.finish_save();
+        }
+      }
+    }
+  }
+
+  // reports the slot layout of the saved game, the controller needs it to create a resume lobby
+  fn finish_save(&mut self) {
+    let save = if let Some(v) = self.save.take() {
+      v
+    } else {
+      return;
+    };
+    let slots = SavedSlotMap::new(
+      self
+        .map
+        .values()
+        .map(|p| SavedSlot {
+          slot_index: p.slot_player_id() - 1,
+          player_id: p.slot_player_id(),
+          name: p.player_name().to_string(),
+        })
+        .collect(),
+    );
+    tracing::info!(
+      game_id = self.game_id,
+      "game saved: {}, slots: {:?}",
+      save.name(),
+      slots.slots()
+    );
+    let player_slots = self
+      .map
+      .iter()
+      .map(|(player_id, p)| (*player_id, p.slot_player_id() - 1))
+      .collect();
+    if let Err(err) = self
+      .out_tx
+      .try_send(GameEvent::GameSaved(save.name().to_string(), player_slots))
+    {
+      tracing::error!(game_id = self.game_id, "report game saved: {}", err);
+    }
+    self.broadcast_message(format!("Game saved: {}", save.name()));
+  }
+
   pub fn broadcast<T: broadcast::BroadcastTarget>(
     &mut self,
     packet: Packet,
@@ -1451,6 +1750,7 @@ impl Shared {
       self.remove_player_and_broadcast(*drop_player_id, None)?;
     }
     self.lagging_player_ids.clear();
+    self.stalled_grace_used = false;
     Ok(())
   }
 
@@ -1487,6 +1787,20 @@ impl Shared {
         };
       }
     };
+    if res.reattached {
+      tracing::info!(
+        game_id = self.game_id,
+        player_id,
+        "stalled player reattached"
+      );
+      if let Some(name) = self
+        .map
+        .get(&player_id)
+        .map(|p| p.player_name().to_string())
+      {
+        self.broadcast_message(format!("{} is responding again.", name));
+      }
+    }
     let has_desync = res.desync.is_some();
     if let Some(desync) = res.desync {
       self.handle_desync(desync)?;
@@ -1545,6 +1859,11 @@ enum RequestDropResult {
   Done,
 }
 
+enum LagTimeoutAction {
+  Resume,
+  ExtendPause(Duration),
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum DispatchStatus {
   Pending,
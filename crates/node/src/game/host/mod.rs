@@ -79,4 +79,16 @@ impl GameHost {
       .notify_player_shutdown(player_id, leave_reason)
       .await
   }
+
+  pub async fn game_command(
+    &mut self,
+    player_id: i32,
+    command: flo_net::proto::flo_common::GameCommand,
+  ) -> Result<()> {
+    self.dispatcher.game_command(player_id, command).await
+  }
+
+  pub async fn surrender(&mut self, player_ids: Vec<i32>) -> Result<()> {
+    self.dispatcher.surrender(player_ids).await
+  }
 }
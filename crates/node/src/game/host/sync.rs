@@ -55,21 +55,24 @@ impl SyncMap {
       if (self.time + time_increment as u32) - item.time
         > crate::constants::GAME_PLAYER_LAGGING_THRESHOLD_MS
       {
-        return Some(
-          self
-            .players
-            .keys()
-            .filter_map(|player_id| {
-              if !item.checksums.contains_key(player_id) {
-                Some(PlayerTimeout {
-                  player_id: *player_id,
-                })
-              } else {
-                None
-              }
-            })
-            .collect(),
-        );
+        let timeouts: Vec<_> = self
+          .players
+          .iter()
+          .filter_map(|(player_id, state)| {
+            if !state.detached && !item.checksums.contains_key(player_id) {
+              Some(PlayerTimeout {
+                player_id: *player_id,
+              })
+            } else {
+              None
+            }
+          })
+          .collect();
+        return if timeouts.is_empty() {
+          None
+        } else {
+          Some(timeouts)
+        };
       } else {
         break;
       }
@@ -80,12 +83,37 @@ impl SyncMap {
   #[must_use]
   pub fn remove_player(&mut self, player_id: i32) -> Option<Vec<PlayerDesync>> {
     self.players.remove(&player_id);
+    for id in self.pending_tick.values() {
+      self.pending_slab[*id].checksums.remove(&player_id);
+    }
+    self.flush_pending()
+  }
+
+  /// Stops waiting for the player's acks without removing them from the game.
+  /// The player is attached again once their acks catch up with the clock.
+  #[must_use]
+  pub fn detach_player(&mut self, player_id: i32) -> Option<Vec<PlayerDesync>> {
+    if let Some(state) = self.players.get_mut(&player_id) {
+      state.detached = true;
+    }
+    self.flush_pending()
+  }
+
+  pub fn is_player_detached(&self, player_id: i32) -> bool {
+    self
+      .players
+      .get(&player_id)
+      .map(|v| v.detached)
+      .unwrap_or(false)
+  }
+
+  // completes pending ticks acked by all attached players
+  fn flush_pending(&mut self) -> Option<Vec<PlayerDesync>> {
     self.desync_buf.clear();
     let mut finished = None;
     for (tick, id) in &self.pending_tick {
       let item = &mut self.pending_slab[*id];
-      item.checksums.remove(&player_id);
-      if let Some(token) = item.should_check_desync(self.players.len()) {
+      if let Some(token) = item.should_check_desync(&self.players) {
         finished.get_or_insert_with(|| vec![]).push((*tick, *id));
         item.check_desync(token, &mut self.desync_buf);
       }
@@ -114,19 +142,37 @@ impl SyncMap {
     let tick = state.tick + 1;
     let time = self.time;
     state.tick = tick;
-    let id = self.pending_tick.get(&tick).cloned().ok_or_else(|| {
-      AckError::TickNotFound(PlayerDesync {
-        player_id,
-        tick,
-        time,
-        checksum,
-      })
-    })?;
+    let reattached = state.detached && tick >= self.tick;
+    if reattached {
+      state.detached = false;
+    }
+    let id = match self.pending_tick.get(&tick).cloned() {
+      Some(id) => id,
+      // the tick completed without waiting for this player
+      None if state.detached || reattached => {
+        return Ok(AckResult {
+          player_tick: tick,
+          game_tick: self.tick,
+          rtt: Duration::from_secs(0),
+          agreed_checksum: None,
+          desync: None,
+          reattached,
+        });
+      }
+      None => {
+        return Err(AckError::TickNotFound(PlayerDesync {
+          player_id,
+          tick,
+          time,
+          checksum,
+        }))
+      }
+    };
     let pending = &mut self.pending_slab[id];
     state.time = pending.time;
     pending.checksums.insert(player_id, checksum);
     let rtt = Instant::now().saturating_duration_since(pending.t);
-    if let Some(token) = pending.should_check_desync(self.players.len()) {
+    if let Some(token) = pending.should_check_desync(&self.players) {
       self.desync_buf.clear();
       pending.check_desync(token, &mut self.desync_buf);
       self.pending_tick.remove(&tick);
@@ -138,6 +184,7 @@ impl SyncMap {
           rtt,
           agreed_checksum: Some(checksum),
           desync: None,
+          reattached,
         })
       } else {
         let desync = self.take_desync();
@@ -151,6 +198,7 @@ impl SyncMap {
             .find(|(k, _v)| self.desync_buf.iter().all(|v| v.player_id != **k))
             .map(|t| t.1.clone()),
           desync,
+          reattached,
         })
       }
     } else {
@@ -160,6 +208,7 @@ impl SyncMap {
         rtt,
         agreed_checksum: None,
         desync: None,
+        reattached,
       })
     }
   }
@@ -195,6 +244,8 @@ pub struct AckResult {
   pub rtt: Duration,
   pub agreed_checksum: Option<u32>,
   pub desync: Option<Vec<PlayerDesync>>,
+  /// A detached player caught up with the clock
+  pub reattached: bool,
 }
 
 #[derive(Error, Debug)]
@@ -223,12 +274,16 @@ impl Pending {
     }
   }
 
-  fn should_check_desync(&self, player_count: usize) -> Option<CheckDesyncToken> {
-    if player_count == 0 {
+  fn should_check_desync(&self, players: &BTreeMap<i32, PlayerState>) -> Option<CheckDesyncToken> {
+    let mut attached = players
+      .iter()
+      .filter(|(_, state)| !state.detached)
+      .peekable();
+    if attached.peek().is_none() {
       return None;
     }
 
-    if self.checksums.len() >= player_count {
+    if attached.all(|(player_id, _)| self.checksums.contains_key(player_id)) {
       Some(CheckDesyncToken)
     } else {
       None
@@ -330,11 +385,16 @@ struct CheckDesyncToken;
 pub struct PlayerState {
   tick: u32,
   time: u32,
+  detached: bool,
 }
 
 impl PlayerState {
   fn new() -> Self {
-    Self { tick: 0, time: 0 }
+    Self {
+      tick: 0,
+      time: 0,
+      detached: false,
+    }
   }
 }
 
@@ -457,3 +517,56 @@ fn test_sync_map() {
   assert!(map.pending_tick.is_empty());
   dbg!(&map.pending_slab.capacity());
 }
+
+#[test]
+fn test_sync_map_detach() {
+  let mut map = SyncMap::new(vec![0, 1, 2]);
+  let step = 100;
+  let lag_ticks = crate::constants::GAME_PLAYER_LAGGING_THRESHOLD_MS / step + 1;
+
+  let mut ticks = 0;
+  loop {
+    match map.clock(step as u16) {
+      ClockResult::Tick => {
+        ticks += 1;
+        for player_id in 0..2 {
+          assert!(map.ack(player_id, 1).unwrap().desync.is_none());
+        }
+      }
+      ClockResult::Lag(items) => {
+        assert_eq!(items, vec![PlayerTimeout { player_id: 2 }]);
+        break;
+      }
+    }
+    assert!(ticks <= lag_ticks);
+  }
+
+  assert!(map.detach_player(2).is_none());
+  assert!(map.is_player_detached(2));
+  assert!(map.pending_tick.is_empty());
+
+  // the clock no longer waits for the detached player
+  for _ in 0..(lag_ticks * 2) {
+    assert!(matches!(map.clock(step as u16), ClockResult::Tick));
+    ticks += 1;
+    for player_id in 0..2 {
+      assert!(map.ack(player_id, 1).unwrap().desync.is_none());
+    }
+  }
+
+  // catching up attaches the player again
+  for tick in 1..=ticks {
+    let res = map.ack(2, 1).unwrap();
+    assert!(res.desync.is_none());
+    assert_eq!(res.reattached, tick == ticks);
+  }
+  assert!(!map.is_player_detached(2));
+
+  assert!(matches!(map.clock(step as u16), ClockResult::Tick));
+  for player_id in 0..2 {
+    map.ack(player_id, 1).unwrap();
+  }
+  assert_eq!(map.pending_tick.len(), 1);
+  map.ack(2, 1).unwrap();
+  assert!(map.pending_tick.is_empty());
+}
@@ -30,6 +30,8 @@ mod host;
 pub enum GameEvent {
   GameStatusChange(NodeGameStatus),
   PlayerStatusChange(i32, SlotClientStatus, SlotClientStatusUpdateSource),
+  /// Save file name and the slot index of each player who saved
+  GameSaved(String, Vec<(i32, u8)>),
 }
 
 pub type GameEventSender = Sender<GameEvent>;
@@ -138,6 +140,27 @@ impl GameSession {
           _ => {}
         }
       }
+      GameEvent::GameSaved(name, slots) => {
+        let guard = handle.0.lock().await;
+        let frame = proto::PacketNodeGameSaved {
+          game_id: guard.game_id,
+          name,
+          slots: slots
+            .into_iter()
+            .map(|(player_id, slot_index)| proto::SavedPlayerSlot {
+              player_id,
+              slot_index: slot_index as u32,
+            })
+            .collect(),
+        }
+        .encode_as_frame()?;
+        if guard.ctrl.send(frame).await.is_err() {
+          tracing::error!(
+            game_id = guard.game_id,
+            "report game saved: controller disconnected"
+          );
+        }
+      }
     }
     Ok(())
   }
@@ -208,6 +231,23 @@ impl GameSessionHandle {
     Ok(())
   }
 
+  pub async fn game_command(
+    &self,
+    player_id: i32,
+    command: flo_net::proto::flo_common::GameCommand,
+  ) -> Result<()> {
+    let mut guard = self.0.lock().await;
+    if !guard.player_slots.contains_key(&player_id) {
+      return Err(Error::PlayerNotFoundInGame);
+    }
+    guard.host.game_command(player_id, command).await
+  }
+
+  pub async fn surrender(&self, player_ids: Vec<i32>) -> Result<()> {
+    let mut guard = self.0.lock().await;
+    guard.host.surrender(player_ids).await
+  }
+
   pub async fn update_player_client_status(
     &self,
     source: SlotClientStatusUpdateSource,
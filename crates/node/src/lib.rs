@@ -1,4 +1,5 @@
 mod client;
+mod config;
 mod controller;
 mod echo;
 mod env;
@@ -22,6 +23,10 @@ use crate::state::GlobalState;
 use state::event::{handle_global_events, FloNodeEventContext, GlobalEvent};
 
 pub async fn serve() -> Result<()> {
+  if let Err(err) = config::load() {
+    tracing::error!("load config: {}", err);
+  }
+
   let (event_sender, event_receiver) = GlobalEvent::channel(30);
   let state = GlobalState::new(event_sender).into_ref();
   let mut ctrl = controller::ControllerServer::new(state.clone());
@@ -30,6 +35,8 @@ pub async fn serve() -> Result<()> {
   tokio::try_join!(
     ctrl.serve(),
     serve_client(state.clone()),
+    controller::serve_routing_stats(state.clone(), ctrl_handle.clone()),
+    controller::serve_game_reservations(state.clone()),
     serve_metrics(),
     serve_echo(),
     handle_global_events(
@@ -1,5 +1,7 @@
 use once_cell::sync::Lazy;
-use prometheus::{register_int_gauge, Encoder, IntGauge, TextEncoder};
+use prometheus::{
+  register_int_counter, register_int_gauge, Encoder, IntCounter, IntGauge, TextEncoder,
+};
 
 use crate::error::*;
 use hyper::header::CONTENT_TYPE;
@@ -20,6 +22,29 @@ pub static PLAYER_TOKENS: Lazy<IntGauge> = Lazy::new(|| {
   )
   .unwrap()
 });
+pub static GAME_RESERVATIONS: Lazy<IntGauge> = Lazy::new(|| {
+  register_int_gauge!(
+    "flonode_game_reservations",
+    "Number of games reserved but not yet committed by the controller"
+  )
+  .unwrap()
+});
+
+pub static GAME_CREATE_REJECTED_BUSY: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_game_create_rejected_busy",
+    "Number of create game requests rejected because the node is at capacity"
+  )
+  .unwrap()
+});
+
+pub static RECORD_FRAMES_DROPPED: Lazy<IntCounter> = Lazy::new(|| {
+  register_int_counter!(
+    "flonode_record_frames_dropped",
+    "Number of game records dropped by the local recorder because its channel was full"
+  )
+  .unwrap()
+});
 
 pub async fn serve_metrics() -> Result<()> {
   use hyper::service::{make_service_fn, service_fn};
@@ -1,7 +1,9 @@
+mod recorder;
+
 use crate::error::Result;
 use backoff::backoff::Backoff;
 use bytes::{BufMut, Bytes, BytesMut};
-use flo_observer::{record::GameRecord, record::RTTStats, KINESIS_CLIENT};
+use flo_observer::{record::GameRecord, record::Keyframe, record::RTTStats, KINESIS_CLIENT};
 use flo_w3gs::packet::Packet;
 use parking_lot::Mutex;
 use std::cell::Cell;
@@ -13,12 +15,15 @@ use tokio::sync::Notify;
 use tokio::time::{sleep, Instant};
 use tokio_util::sync::CancellationToken;
 
+pub use recorder::{LocalRecorder, LocalRecorderHandle};
+
 const BUFFER_TIMEOUT: Duration = Duration::from_secs(15 * 60);
 
 #[derive(Debug)]
 pub struct ObserverPublisher {
   ct: CancellationToken,
-  tx: Sender<Cmd>,
+  tx: Option<Sender<Cmd>>,
+  recorder: Option<LocalRecorder>,
 }
 
 impl Drop for ObserverPublisher {
@@ -29,20 +34,37 @@ impl Drop for ObserverPublisher {
 
 impl ObserverPublisher {
   pub fn new() -> Self {
-    let (tx, rx) = channel(crate::constants::OBS_CHANNEL_SIZE);
     let ct = CancellationToken::new();
-    let bm = BufferMap::new();
 
-    tokio::spawn(Handler::new(ct.clone(), rx, bm.clone()).run());
-    tokio::spawn(Pusher::new(ct.clone(), bm.clone()).run());
+    let tx = if *crate::constants::OBS_KINESIS_DISABLED {
+      tracing::warn!("obs: kinesis publishing disabled.");
+      None
+    } else {
+      let (tx, rx) = channel(crate::constants::OBS_CHANNEL_SIZE);
+      let bm = BufferMap::new();
+      tokio::spawn(Handler::new(ct.clone(), rx, bm.clone()).run());
+      tokio::spawn(Pusher::new(ct.clone(), bm.clone()).run());
+      Some(tx)
+    };
+
+    let recorder = crate::constants::RECORD_DIR
+      .clone()
+      .and_then(|dir| match LocalRecorder::new(dir) {
+        Ok(v) => Some(v),
+        Err(err) => {
+          tracing::error!("obs: start local recorder: {}", err);
+          None
+        }
+      });
 
-    Self { ct, tx }
+    Self { ct, tx, recorder }
   }
 
   pub fn handle(&self) -> ObserverPublisherHandle {
     ObserverPublisherHandle {
       broken: Cell::new(false),
       tx: self.tx.clone(),
+      recorder: self.recorder.as_ref().map(|v| v.handle()),
     }
   }
 }
@@ -50,7 +72,8 @@ impl ObserverPublisher {
 #[derive(Debug, Clone)]
 pub struct ObserverPublisherHandle {
   broken: Cell<bool>,
-  tx: Sender<Cmd>,
+  tx: Option<Sender<Cmd>>,
+  recorder: Option<LocalRecorderHandle>,
 }
 
 impl ObserverPublisherHandle {
@@ -78,23 +101,40 @@ impl ObserverPublisherHandle {
     self.push_record(GameRecord::new_rtt_stats(game_id, stats))
   }
 
+  pub fn push_keyframe(&self, game_id: i32, keyframe: Keyframe) {
+    self.push_record(GameRecord::new_keyframe(game_id, keyframe))
+  }
+
   fn push_record(&self, record: GameRecord) {
+    if let Some(recorder) = self.recorder.as_ref() {
+      recorder.push_record(&record);
+    }
+    let tx = match self.tx.as_ref() {
+      Some(tx) => tx,
+      None => return,
+    };
     if self.broken.get() {
       return;
     }
-    self.tx.try_send(Cmd::AddRecord(record)).err().map(|_| {
+    tx.try_send(Cmd::AddRecord(record)).err().map(|_| {
       tracing::error!("observer pushing disabled.");
       self.broken.set(true)
     });
   }
 
   pub fn remove_game(&self, game_id: i32) {
+    if let Some(recorder) = self.recorder.as_ref() {
+      recorder.remove_game(game_id);
+    }
+    let tx = match self.tx.as_ref() {
+      Some(tx) => tx,
+      None => return,
+    };
     if self.broken.get() {
       return;
     }
 
-    self
-      .tx
+    tx
       .try_send(Cmd::RemoveGame { game_id })
       .err()
       .map(|_| self.broken.set(true));
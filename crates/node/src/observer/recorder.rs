@@ -0,0 +1,345 @@
+use crate::constants::{
+  RECORD_CHANNEL_SIZE, RECORD_CLEANUP_INTERVAL, RECORD_FLUSH_INTERVAL, RECORD_MAX_FILE_SIZE,
+  RECORD_RETENTION,
+};
+use crate::error::Result;
+use crate::metrics;
+use bytes::{BufMut, BytesMut};
+use flo_observer::record::GameRecord;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+use std::time::{Duration, Instant, SystemTime};
+
+const SIGNATURE: &[u8] = b"flo\x01";
+const PART_EXT: &str = "part";
+const SEALED_EXT: &str = "rec";
+const IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Writes game records to `<dir>/<game_id>/<segment>.rec`, independent of Kinesis.
+///
+/// Each segment starts with `[signature: 4][game_id: i32 LE]`, followed by encoded
+/// `GameRecordData`, the same layout as the observer archive content.
+/// Open segments have the `.part` extension and are synced every `RECORD_FLUSH_INTERVAL`,
+/// after a crash the leftover `.part` files are sealed on next startup.
+#[derive(Debug)]
+pub struct LocalRecorder {
+  tx: SyncSender<Cmd>,
+}
+
+impl LocalRecorder {
+  pub fn new(dir: PathBuf) -> Result<Self> {
+    fs::create_dir_all(&dir)?;
+    let (tx, rx) = sync_channel(RECORD_CHANNEL_SIZE);
+    std::thread::Builder::new()
+      .name("flo-node-recorder".to_string())
+      .spawn(move || Worker::new(dir).run(rx))?;
+    Ok(Self { tx })
+  }
+
+  pub fn handle(&self) -> LocalRecorderHandle {
+    LocalRecorderHandle {
+      dropped: RefCell::new(BTreeMap::new()),
+      tx: self.tx.clone(),
+    }
+  }
+}
+
+/// Records are dropped while the channel is full, recording resumes once the worker catches up.
+/// The worker is told how many records of a game were dropped before the next one is sent,
+/// and starts a new segment so the gap is at a segment boundary.
+#[derive(Debug, Clone)]
+pub struct LocalRecorderHandle {
+  dropped: RefCell<BTreeMap<i32, u64>>,
+  tx: SyncSender<Cmd>,
+}
+
+impl LocalRecorderHandle {
+  pub fn push_record(&self, record: &GameRecord) {
+    let game_id = record.game_id;
+    let mut dropped = self.dropped.borrow_mut();
+    if let Some(count) = dropped.get(&game_id).cloned() {
+      if self
+        .tx
+        .try_send(Cmd::RecordsDropped { game_id, count })
+        .is_err()
+      {
+        dropped.insert(game_id, count + 1);
+        metrics::RECORD_FRAMES_DROPPED.inc();
+        return;
+      }
+      dropped.remove(&game_id);
+    }
+    if self.tx.try_send(Cmd::AddRecord(record.clone())).is_err() {
+      tracing::warn!(game_id, "record: channel full, dropping records");
+      dropped.insert(game_id, 1);
+      metrics::RECORD_FRAMES_DROPPED.inc();
+    }
+  }
+
+  pub fn remove_game(&self, game_id: i32) {
+    self.dropped.borrow_mut().remove(&game_id);
+    if self.tx.try_send(Cmd::RemoveGame { game_id }).is_err() {
+      tracing::warn!(
+        game_id,
+        "record: channel full, game will be sealed once idle"
+      );
+    }
+  }
+}
+
+#[derive(Debug)]
+enum Cmd {
+  AddRecord(GameRecord),
+  RecordsDropped { game_id: i32, count: u64 },
+  RemoveGame { game_id: i32 },
+}
+
+struct Worker {
+  dir: PathBuf,
+  games: BTreeMap<i32, GameFile>,
+  buf: BytesMut,
+}
+
+impl Worker {
+  fn new(dir: PathBuf) -> Self {
+    Self {
+      dir,
+      games: BTreeMap::new(),
+      buf: BytesMut::new(),
+    }
+  }
+
+  fn run(mut self, rx: Receiver<Cmd>) {
+    if let Err(err) = seal_leftover_parts(&self.dir) {
+      tracing::error!("record: seal leftover files: {}", err);
+    }
+    self.cleanup();
+
+    let mut last_flush = Instant::now();
+    let mut last_cleanup = Instant::now();
+    loop {
+      match rx.recv_timeout(RECORD_FLUSH_INTERVAL) {
+        Ok(Cmd::AddRecord(record)) => {
+          let game_id = record.game_id;
+          if let Err(err) = self.write(record) {
+            tracing::error!(game_id, "record: write: {}", err);
+            self.games.remove(&game_id);
+          }
+        }
+        Ok(Cmd::RecordsDropped { game_id, count }) => {
+          tracing::warn!(
+            game_id,
+            count,
+            "record: records dropped, starting a new segment"
+          );
+          if let Some(file) = self.games.remove(&game_id) {
+            if let Err(err) = file.seal() {
+              tracing::error!(game_id, "record: seal: {}", err);
+            }
+          }
+        }
+        Ok(Cmd::RemoveGame { game_id }) => {
+          if let Some(file) = self.games.remove(&game_id) {
+            if let Err(err) = file.seal() {
+              tracing::error!(game_id, "record: seal: {}", err);
+            }
+          }
+        }
+        Err(RecvTimeoutError::Timeout) => {}
+        Err(RecvTimeoutError::Disconnected) => break,
+      }
+
+      if last_flush.elapsed() >= RECORD_FLUSH_INTERVAL {
+        self.flush();
+        last_flush = Instant::now();
+      }
+
+      if last_cleanup.elapsed() >= RECORD_CLEANUP_INTERVAL {
+        self.cleanup();
+        last_cleanup = Instant::now();
+      }
+    }
+
+    for (game_id, file) in std::mem::take(&mut self.games) {
+      if let Err(err) = file.seal() {
+        tracing::error!(game_id, "record: seal: {}", err);
+      }
+    }
+  }
+
+  fn write(&mut self, record: GameRecord) -> Result<()> {
+    let game_id = record.game_id;
+    let len = record.data.encode_len() as u64;
+
+    let file = match self.games.remove(&game_id) {
+      Some(file) if file.len > 0 && file.len + len > *RECORD_MAX_FILE_SIZE => {
+        let next = file.segment + 1;
+        file.seal()?;
+        GameFile::create(&self.dir, game_id, next)?
+      }
+      Some(file) => file,
+      None => {
+        let segment = next_segment(&self.dir.join(game_id.to_string()))?;
+        GameFile::create(&self.dir, game_id, segment)?
+      }
+    };
+    let file = self.games.entry(game_id).or_insert(file);
+
+    self.buf.clear();
+    record.data.encode(&mut self.buf);
+    file.w.write_all(&self.buf)?;
+    file.len += len;
+    file.dirty = true;
+    file.last_write = Instant::now();
+    Ok(())
+  }
+
+  fn flush(&mut self) {
+    let mut idle_ids = vec![];
+    for (game_id, file) in self.games.iter_mut() {
+      if file.last_write.elapsed() > IDLE_TIMEOUT {
+        idle_ids.push(*game_id);
+        continue;
+      }
+      if let Err(err) = file.sync() {
+        tracing::error!(game_id, "record: flush: {}", err);
+      }
+    }
+    for game_id in idle_ids {
+      if let Some(file) = self.games.remove(&game_id) {
+        tracing::warn!(game_id, "record: game idle, sealed");
+        if let Err(err) = file.seal() {
+          tracing::error!(game_id, "record: seal: {}", err);
+        }
+      }
+    }
+  }
+
+  // removes game folders older than `RECORD_RETENTION`
+  fn cleanup(&self) {
+    let entries = match fs::read_dir(&self.dir) {
+      Ok(v) => v,
+      Err(err) => {
+        tracing::error!("record: cleanup: {}", err);
+        return;
+      }
+    };
+    let now = SystemTime::now();
+    for entry in entries.filter_map(|v| v.ok()) {
+      let game_id = match entry
+        .file_name()
+        .to_str()
+        .and_then(|v| v.parse::<i32>().ok())
+      {
+        Some(v) => v,
+        None => continue,
+      };
+      if self.games.contains_key(&game_id) {
+        continue;
+      }
+      let expired = entry
+        .metadata()
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| now.duration_since(t).ok())
+        .map(|age| age > *RECORD_RETENTION)
+        .unwrap_or(false);
+      if expired {
+        if let Err(err) = fs::remove_dir_all(entry.path()) {
+          tracing::error!(game_id, "record: remove expired: {}", err);
+        } else {
+          tracing::debug!(game_id, "record: removed expired");
+        }
+      }
+    }
+  }
+}
+
+struct GameFile {
+  segment: u32,
+  path: PathBuf,
+  w: BufWriter<File>,
+  len: u64,
+  dirty: bool,
+  last_write: Instant,
+}
+
+impl GameFile {
+  fn create(dir: &Path, game_id: i32, segment: u32) -> Result<Self> {
+    let dir = dir.join(game_id.to_string());
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{:04}.{}", segment, PART_EXT));
+    let mut w = BufWriter::new(File::create(&path)?);
+    let mut header = BytesMut::with_capacity(8);
+    header.put_slice(SIGNATURE);
+    header.put_i32_le(game_id);
+    w.write_all(&header)?;
+    Ok(Self {
+      segment,
+      path,
+      w,
+      len: 0,
+      dirty: true,
+      last_write: Instant::now(),
+    })
+  }
+
+  fn sync(&mut self) -> Result<()> {
+    if !self.dirty {
+      return Ok(());
+    }
+    self.w.flush()?;
+    self.w.get_ref().sync_data()?;
+    self.dirty = false;
+    Ok(())
+  }
+
+  fn seal(mut self) -> Result<()> {
+    self.w.flush()?;
+    self.w.get_ref().sync_all()?;
+    fs::rename(&self.path, self.path.with_extension(SEALED_EXT))?;
+    Ok(())
+  }
+}
+
+fn next_segment(dir: &Path) -> Result<u32> {
+  let entries = match fs::read_dir(dir) {
+    Ok(v) => v,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+    Err(err) => return Err(err.into()),
+  };
+  let next = entries
+    .filter_map(|v| v.ok())
+    .filter_map(|entry| {
+      entry
+        .path()
+        .file_stem()
+        .and_then(|v| v.to_str())
+        .and_then(|v| v.parse::<u32>().ok())
+    })
+    .max()
+    .map(|v| v + 1)
+    .unwrap_or(0);
+  Ok(next)
+}
+
+// a `.part` file can only exist after a crash, its tail may contain a truncated record
+fn seal_leftover_parts(dir: &Path) -> Result<()> {
+  for game_dir in fs::read_dir(dir)?.filter_map(|v| v.ok()) {
+    if !game_dir.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+      continue;
+    }
+    for entry in fs::read_dir(game_dir.path())?.filter_map(|v| v.ok()) {
+      let path = entry.path();
+      if path.extension().and_then(|v| v.to_str()) == Some(PART_EXT) {
+        tracing::warn!("record: sealing leftover file: {}", path.display());
+        fs::rename(&path, path.with_extension(SEALED_EXT))?;
+      }
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,103 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Used until enough games ended to estimate how often a slot frees up
+const DEFAULT_WAIT: Duration = Duration::from_secs(60);
+const MIN_WAIT: Duration = Duration::from_secs(5);
+const MAX_WAIT: Duration = Duration::from_secs(10 * 60);
+
+/// Limits the number of games the node hosts, set by `FLO_NODE_MAX_GAMES` or the controller.
+/// Rejected create game requests carry how long until a game is expected to end,
+/// estimated from the moving average of the intervals between ended games.
+#[derive(Debug)]
+pub struct GameCapacity {
+  max_games: AtomicUsize,
+  ends: Mutex<GameEnds>,
+}
+
+#[derive(Debug, Default)]
+struct GameEnds {
+  last: Option<Instant>,
+  avg_interval: Option<Duration>,
+}
+
+impl GameCapacity {
+  /// `max_games` 0 is unlimited
+  pub fn new(max_games: usize) -> Self {
+    Self {
+      max_games: AtomicUsize::new(max_games),
+      ends: Mutex::new(GameEnds::default()),
+    }
+  }
+
+  pub fn max_games(&self) -> usize {
+    self.max_games.load(Ordering::Relaxed)
+  }
+
+  /// Games already hosted above a lowered limit keep running
+  pub fn set_max_games(&self, max_games: usize) {
+    self.max_games.store(max_games, Ordering::Relaxed)
+  }
+
+  /// Returns the estimated wait if `games` already fill the node
+  pub fn check(&self, games: usize, now: Instant) -> Result<(), Duration> {
+    let max_games = self.max_games();
+    if max_games == 0 || games < max_games {
+      return Ok(());
+    }
+    let ends = self.ends.lock();
+    let wait = match (ends.last, ends.avg_interval) {
+      (Some(last), Some(avg)) => avg.saturating_sub(now.saturating_duration_since(last)),
+      _ => DEFAULT_WAIT,
+    };
+    Err(wait.max(MIN_WAIT).min(MAX_WAIT))
+  }
+
+  pub fn record_end(&self, now: Instant) {
+    let mut ends = self.ends.lock();
+    if let Some(last) = ends.last {
+      let interval = now.saturating_duration_since(last);
+      ends.avg_interval = Some(match ends.avg_interval {
+        // weight of the latest interval: 1/4
+        Some(avg) => (avg * 3 + interval) / 4,
+        None => interval,
+      });
+    }
+    ends.last = Some(now);
+  }
+}
+
+#[test]
+fn test_game_capacity() {
+  let now = Instant::now();
+
+  let unlimited = GameCapacity::new(0);
+  assert_eq!(unlimited.check(1000, now), Ok(()));
+
+  let capacity = GameCapacity::new(2);
+  assert_eq!(capacity.check(1, now), Ok(()));
+  assert_eq!(capacity.check(2, now), Err(DEFAULT_WAIT));
+
+  capacity.record_end(now);
+  assert_eq!(capacity.check(2, now), Err(DEFAULT_WAIT));
+  capacity.record_end(now + Duration::from_secs(120));
+  assert_eq!(
+    capacity.check(2, now + Duration::from_secs(150)),
+    Err(Duration::from_secs(90))
+  );
+  capacity.record_end(now + Duration::from_secs(160));
+  // (120 * 3 + 40) / 4
+  assert_eq!(
+    capacity.check(2, now + Duration::from_secs(160)),
+    Err(Duration::from_secs(100))
+  );
+  // overdue
+  assert_eq!(
+    capacity.check(2, now + Duration::from_secs(1000)),
+    Err(MIN_WAIT)
+  );
+
+  capacity.set_max_games(3);
+  assert_eq!(capacity.check(2, now), Ok(()));
+}
@@ -1,35 +1,45 @@
 pub use event::{handle_global_events, GlobalEvent, GlobalEventSender};
+mod capacity;
 pub mod event;
 mod types;
 
 pub use types::*;
 
 use dashmap::DashMap;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use s2_grpc_utils::S2ProtoEnum;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use flo_net::packet::{FloPacket, Frame, OptionalFieldExt};
 use flo_net::proto::flo_node::{
-  ControllerCreateGameRejectReason, Game, PacketControllerCreateGame,
-  PacketControllerCreateGameAccept, PacketControllerCreateGameReject,
+  ControllerCreateGameRejectReason, Game, PacketControllerCommitGame,
+  PacketControllerCommitGameAccept, PacketControllerCommitGameReject, PacketControllerCreateGame,
+  PacketControllerCreateGameAccept, PacketControllerCreateGameReject, PacketControllerGameCommand,
+  PacketControllerGameSurrender, PacketControllerUpdateNodeConfig,
+  PacketControllerUpdateNodeConfigAccept, PacketControllerUpdateNodeConfigReject,
   PacketControllerUpdateSlotStatus, PacketControllerUpdateSlotStatusAccept,
   PacketControllerUpdateSlotStatusReject,
 };
 
+use crate::client::RoutingStats;
 use crate::controller::ControllerServerHandle;
 use crate::error::*;
 use crate::game::{GameSession, GameSessionHandle, SlotClientStatusUpdateSource};
 use crate::metrics;
 use crate::observer::{ObserverPublisher, ObserverPublisherHandle};
+use capacity::GameCapacity;
 
 #[derive(Debug)]
 pub struct GlobalState {
   event_sender: GlobalEventSender,
   players: PlayerRegistry,
   games: GameRegistry,
+  reservations: GameReservations,
+  capacity: GameCapacity,
   obs: ObserverPublisher,
+  routing: RoutingStats,
 }
 
 pub type GlobalStateRef = Arc<GlobalState>;
@@ -40,7 +50,10 @@ impl GlobalState {
       event_sender,
       players: PlayerRegistry::new(),
       games: GameRegistry::new(),
+      reservations: GameReservations::default(),
+      capacity: GameCapacity::new(crate::config::current().max_games),
       obs: ObserverPublisher::new(),
+      routing: RoutingStats::default(),
     }
   }
 
@@ -52,13 +65,19 @@ impl GlobalState {
     self.players.get_by_token(token)
   }
 
+  pub fn routing_stats(&self) -> &RoutingStats {
+    &self.routing
+  }
+
   pub fn get_game(&self, id: i32) -> Option<GameSessionHandle> {
     self.games.get(id)
   }
 
   pub fn end_game(&self, id: i32) {
     self.players.remove_game(id);
-    self.games.remove(id);
+    if self.games.remove(id) {
+      self.capacity.record_end(Instant::now());
+    }
   }
 
   pub fn handle_controller_create_game(
@@ -66,6 +85,7 @@ impl GlobalState {
     ctrl: ControllerServerHandle,
     packet: PacketControllerCreateGame,
   ) -> Result<Frame> {
+    let reserve = packet.reserve;
     let game = packet.game.extract()?;
 
     let game_id = game.id;
@@ -79,6 +99,46 @@ impl GlobalState {
       return Err(Error::NoPlayer);
     }
 
+    let reject = |reason: ControllerCreateGameRejectReason| -> Result<Frame> {
+      Ok(
+        PacketControllerCreateGameReject {
+          game_id,
+          reason: reason.into(),
+          ..Default::default()
+        }
+        .encode_as_frame()?,
+      )
+    };
+
+    // retries of a registered game don't need room
+    if self.games.get(game_id).is_none() {
+      if let Err(wait) = self.capacity.check(self.games.len(), Instant::now()) {
+        tracing::warn!(
+          game_id,
+          "create game: node busy, estimated wait: {:?}",
+          wait
+        );
+        metrics::GAME_CREATE_REJECTED_BUSY.inc();
+        return Ok(
+          PacketControllerCreateGameReject {
+            game_id,
+            reason: ControllerCreateGameRejectReason::NodeBusy.into(),
+            estimated_wait_secs: wait.as_secs() as u32,
+          }
+          .encode_as_frame()?,
+        );
+      }
+    }
+
+    if reserve {
+      // a reserved game can be reserved again if the controller retries,
+      // but a committed game must not be rolled back
+      if !self.reservations.contains(game_id) && self.games.get(game_id).is_some() {
+        return reject(ControllerCreateGameRejectReason::GameExists);
+      }
+      self.reservations.reserve(game_id, Instant::now());
+    }
+
     let pending: Vec<(PlayerToken, RegisteredPlayer)> = {
       let players: Vec<_> = game
         .slots
@@ -109,13 +169,7 @@ impl GlobalState {
         Error::GameExists => ControllerCreateGameRejectReason::GameExists,
         err => return Err(err),
       };
-      return Ok(
-        PacketControllerCreateGameReject {
-          game_id,
-          reason: reason.into(),
-        }
-        .encode_as_frame()?,
-      );
+      return reject(reason);
     }
 
     let player_tokens: Vec<_> = pending
@@ -144,11 +198,66 @@ impl GlobalState {
       PacketControllerCreateGameAccept {
         game_id,
         player_tokens,
+        reserved: reserve,
       }
       .encode_as_frame()?,
     )
   }
 
+  pub fn handle_controller_commit_game(&self, packet: PacketControllerCommitGame) -> Result<Frame> {
+    let game_id = packet.game_id;
+    // the controller retries the commit if the accept was lost
+    if self.reservations.commit(game_id) || self.games.get(game_id).is_some() {
+      Ok(PacketControllerCommitGameAccept { game_id }.encode_as_frame()?)
+    } else {
+      tracing::warn!(game_id, "commit game: reservation expired");
+      Ok(
+        PacketControllerCommitGameReject {
+          game_id,
+          reason: ControllerCreateGameRejectReason::ReservationExpired.into(),
+        }
+        .encode_as_frame()?,
+      )
+    }
+  }
+
+  pub fn handle_controller_update_node_config(
+    &self,
+    packet: PacketControllerUpdateNodeConfig,
+  ) -> Result<Frame> {
+    let update = packet.config.unwrap_or_default();
+    match crate::config::update(update) {
+      Ok(config) => {
+        tracing::info!("config updated: {:?}", config);
+        self.capacity.set_max_games(config.max_games);
+        Ok(
+          PacketControllerUpdateNodeConfigAccept {
+            config: Some(config.pack()),
+          }
+          .encode_as_frame()?,
+        )
+      }
+      Err(err) => {
+        tracing::error!("update config: {}", err);
+        Ok(
+          PacketControllerUpdateNodeConfigReject {
+            reason: err.to_string(),
+          }
+          .encode_as_frame()?,
+        )
+      }
+    }
+  }
+
+  /// Removes the reserved games not committed within `timeout`,
+  /// or all reserved games if `timeout` is `None`
+  pub fn rollback_reservations(&self, timeout: Option<Duration>) {
+    for game_id in self.reservations.take_expired(timeout, Instant::now()) {
+      tracing::warn!(game_id, "game reservation rolled back");
+      self.end_game(game_id);
+    }
+  }
+
   pub async fn handle_controller_update_slot_client_status(
     &self,
     packet: PacketControllerUpdateSlotStatus,
@@ -227,6 +336,36 @@ impl GlobalState {
       },
     }
   }
+
+  pub async fn handle_controller_game_command(&self, packet: PacketControllerGameCommand) {
+    let game_id = packet.game_id;
+    let player_id = packet.player_id;
+    let command = packet.command();
+    let game = match self.games.get(game_id) {
+      Some(game) => game,
+      None => {
+        tracing::warn!(game_id, player_id, "game command: game not found");
+        return;
+      }
+    };
+    if let Err(err) = game.game_command(player_id, command).await {
+      tracing::error!(game_id, player_id, "game command {:?}: {}", command, err);
+    }
+  }
+
+  pub async fn handle_controller_game_surrender(&self, packet: PacketControllerGameSurrender) {
+    let game_id = packet.game_id;
+    let game = match self.games.get(game_id) {
+      Some(game) => game,
+      None => {
+        tracing::warn!(game_id, "game surrender: game not found");
+        return;
+      }
+    };
+    if let Err(err) = game.surrender(packet.player_ids).await {
+      tracing::error!(game_id, "game surrender: {}", err);
+    }
+  }
 }
 
 #[derive(Debug)]
@@ -354,9 +493,86 @@ impl GameRegistry {
     self.map.get(&game_id).map(|r| r.value().handle())
   }
 
-  fn remove(&self, id: i32) {
+  fn len(&self) -> usize {
+    self.map.len()
+  }
+
+  /// Returns `false` if the game was not registered
+  fn remove(&self, id: i32) -> bool {
     if let Some(_) = self.map.remove(&id) {
       metrics::GAME_SESSIONS.dec();
+      true
+    } else {
+      false
+    }
+  }
+}
+
+/// Games created by `PacketControllerCreateGame` with `reserve`, not committed yet
+#[derive(Debug, Default)]
+struct GameReservations {
+  map: Mutex<HashMap<i32, Instant>>,
+}
+
+impl GameReservations {
+  fn contains(&self, game_id: i32) -> bool {
+    self.map.lock().contains_key(&game_id)
+  }
+
+  fn reserve(&self, game_id: i32, now: Instant) {
+    if self.map.lock().insert(game_id, now).is_none() {
+      metrics::GAME_RESERVATIONS.inc();
     }
   }
+
+  /// Returns `false` if the game is not reserved
+  fn commit(&self, game_id: i32) -> bool {
+    if self.map.lock().remove(&game_id).is_some() {
+      metrics::GAME_RESERVATIONS.dec();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn take_expired(&self, timeout: Option<Duration>, now: Instant) -> Vec<i32> {
+    let mut map = self.map.lock();
+    let expired: Vec<i32> = map
+      .iter()
+      .filter(|(_, reserved_at)| {
+        timeout
+          .map(|timeout| now.saturating_duration_since(**reserved_at) >= timeout)
+          .unwrap_or(true)
+      })
+      .map(|(game_id, _)| *game_id)
+      .collect();
+    for game_id in &expired {
+      map.remove(game_id);
+      metrics::GAME_RESERVATIONS.dec();
+    }
+    expired
+  }
+}
+
+#[test]
+fn test_game_reservations() {
+  let reservations = GameReservations::default();
+  let now = Instant::now();
+  let timeout = Duration::from_secs(15);
+  reservations.reserve(1, now);
+  reservations.reserve(2, now + Duration::from_secs(10));
+  reservations.reserve(3, now);
+  assert!(reservations.contains(1));
+
+  assert!(reservations.commit(1));
+  assert!(!reservations.commit(1));
+  assert!(!reservations.contains(1));
+
+  assert_eq!(
+    reservations.take_expired(Some(timeout), now + Duration::from_secs(20)),
+    vec![3]
+  );
+  assert!(reservations.contains(2));
+  assert_eq!(reservations.take_expired(None, now), vec![2]);
+  assert!(!reservations.contains(2));
 }
@@ -0,0 +1,287 @@
+mod sink;
+
+use crate::env::ENV;
+use crate::error::{Error, Result};
+use crate::game::{GameHandler, PlayerLeaveReason};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sink::AlertSink;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+pub use sink::AlertSinkConfig;
+
+/// Alerting configuration, read from `FLO_ALERT_RULES_FILE` or `FLO_ALERT_RULES` (JSON)
+///
+/// ```json
+/// {
+///   "interval_secs": 30,
+///   "sinks": [
+///     { "kind": "webhook", "url": "http://ops/hooks/flo" },
+///     { "kind": "pager_duty", "routing_key": "..." }
+///   ],
+///   "rules": [
+///     { "name": "stuck_loading", "condition": { "kind": "stuck_loading", "secs": 300 } },
+///     { "name": "disconnects", "condition": { "kind": "disconnects", "count": 3 } }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct AlertConfig {
+  #[serde(default = "default_interval_secs")]
+  pub interval_secs: u64,
+  #[serde(default)]
+  pub sinks: Vec<AlertSinkConfig>,
+  pub rules: Vec<AlertRule>,
+}
+
+fn default_interval_secs() -> u64 {
+  30
+}
+
+impl AlertConfig {
+  pub fn from_env() -> Result<Option<Self>> {
+    let json = if let Some(path) = ENV.alert_rules_file.as_ref() {
+      std::fs::read_to_string(path)?
+    } else if let Some(value) = ENV.alert_rules.clone() {
+      value
+    } else {
+      return Ok(None);
+    };
+    let config: Self =
+      serde_json::from_str(&json).map_err(|err| Error::AlertConfig(err.to_string()))?;
+    if config.rules.is_empty() {
+      return Ok(None);
+    }
+    Ok(Some(config))
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlertRule {
+  pub name: String,
+  #[serde(default)]
+  pub severity: AlertSeverity,
+  pub condition: AlertCondition,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertSeverity {
+  Info,
+  Warning,
+  Error,
+  Critical,
+}
+
+impl Default for AlertSeverity {
+  fn default() -> Self {
+    AlertSeverity::Warning
+  }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+  /// No time slot received `secs` after the game started
+  StuckLoading { secs: u64 },
+  /// A running game received no records for `secs`
+  Stalled { secs: u64 },
+  /// At least `count` players disconnected, desync kicks are recorded as disconnects
+  Disconnects { count: usize },
+}
+
+impl AlertCondition {
+  fn eval(&self, game: &AlertGameState) -> Option<String> {
+    match *self {
+      AlertCondition::StuckLoading { secs } => {
+        let elapsed = game.elapsed_since(game.started_at);
+        if !game.ended && game.game_time_ms == 0 && elapsed > Duration::from_secs(secs) {
+          return Some(format!("game still loading after {}s", elapsed.as_secs()));
+        }
+      }
+      AlertCondition::Stalled { secs } => {
+        let elapsed = game.elapsed_since(game.last_record_at);
+        if !game.ended && game.game_time_ms > 0 && elapsed > Duration::from_secs(secs) {
+          return Some(format!("no records for {}s", elapsed.as_secs()));
+        }
+      }
+      AlertCondition::Disconnects { count } => {
+        if game.disconnects >= count {
+          return Some(format!("{} players disconnected", game.disconnects));
+        }
+      }
+    }
+    None
+  }
+}
+
+/// The values rules are evaluated against
+#[derive(Debug)]
+pub struct AlertGameState {
+  pub game_id: i32,
+  pub now: DateTime<Utc>,
+  pub started_at: DateTime<Utc>,
+  pub last_record_at: DateTime<Utc>,
+  pub game_time_ms: u32,
+  pub ended: bool,
+  pub disconnects: usize,
+}
+
+impl AlertGameState {
+  pub fn new(handler: &GameHandler, now: DateTime<Utc>) -> Self {
+    let meta = handler.meta();
+    Self {
+      game_id: meta.id,
+      now,
+      started_at: meta.started_at,
+      last_record_at: handler
+        .last_arrival_timestamp()
+        .map(|v| Utc.timestamp_millis((v * 1000.) as i64))
+        .unwrap_or(meta.started_at),
+      game_time_ms: meta.game_time_ms,
+      ended: meta.ended_at.is_some(),
+      disconnects: meta
+        .player_left_reason_map
+        .values()
+        .filter(|(_, reason)| *reason == PlayerLeaveReason::LeaveDisconnect)
+        .count(),
+    }
+  }
+
+  fn elapsed_since(&self, t: DateTime<Utc>) -> Duration {
+    self
+      .now
+      .signed_duration_since(t)
+      .to_std()
+      .unwrap_or_default()
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+  pub rule: String,
+  pub severity: AlertSeverity,
+  pub game_id: i32,
+  pub message: String,
+  pub fired_at: DateTime<Utc>,
+}
+
+/// Evaluates rules over the in-memory games, each rule fires at most once per game
+pub struct AlertEngine {
+  interval: Duration,
+  rules: Vec<AlertRule>,
+  sinks: Vec<AlertSink>,
+  fired: BTreeSet<(i32, usize)>,
+}
+
+impl AlertEngine {
+  pub fn from_env() -> Result<Option<Self>> {
+    let config = if let Some(config) = AlertConfig::from_env()? {
+      config
+    } else {
+      return Ok(None);
+    };
+    Ok(Some(Self::new(config)))
+  }
+
+  pub fn new(config: AlertConfig) -> Self {
+    Self {
+      interval: Duration::from_secs(std::cmp::max(config.interval_secs, 1)),
+      rules: config.rules,
+      sinks: config.sinks.into_iter().map(AlertSink::new).collect(),
+      fired: BTreeSet::new(),
+    }
+  }
+
+  pub fn interval(&self) -> Duration {
+    self.interval
+  }
+
+  pub fn eval<I>(&mut self, games: I) -> Vec<Alert>
+  where
+    I: IntoIterator<Item = AlertGameState>,
+  {
+    let mut alerts = vec![];
+    let mut game_ids = BTreeSet::new();
+    for game in games {
+      game_ids.insert(game.game_id);
+      for (idx, rule) in self.rules.iter().enumerate() {
+        if self.fired.contains(&(game.game_id, idx)) {
+          continue;
+        }
+        if let Some(message) = rule.condition.eval(&game) {
+          self.fired.insert((game.game_id, idx));
+          alerts.push(Alert {
+            rule: rule.name.clone(),
+            severity: rule.severity,
+            game_id: game.game_id,
+            message,
+            fired_at: game.now,
+          });
+        }
+      }
+    }
+    self.fired.retain(|(game_id, _)| game_ids.contains(game_id));
+    alerts
+  }
+
+  pub fn fire(&self, alerts: Vec<Alert>) {
+    for alert in alerts {
+      tracing::warn!(
+        game_id = alert.game_id,
+        "alert: {}: {}",
+        alert.rule,
+        alert.message
+      );
+      for sink in &self.sinks {
+        let sink = sink.clone();
+        let alert = alert.clone();
+        tokio::spawn(async move {
+          if let Err(err) = sink.send(&alert).await {
+            tracing::error!(game_id = alert.game_id, "alert sink: {}", err);
+          }
+        });
+      }
+    }
+  }
+}
+
+#[test]
+fn test_alert_engine() {
+  let config: AlertConfig = serde_json::from_str(
+    r#"{
+      "rules": [
+        { "name": "stuck", "condition": { "kind": "stuck_loading", "secs": 300 } },
+        { "name": "dc", "severity": "critical", "condition": { "kind": "disconnects", "count": 2 } }
+      ]
+    }"#,
+  )
+  .unwrap();
+  let mut engine = AlertEngine::new(config);
+
+  let now = Utc::now();
+  let game = |game_id, started_secs_ago: i64, disconnects| AlertGameState {
+    game_id,
+    now,
+    started_at: now - chrono::Duration::seconds(started_secs_ago),
+    last_record_at: now,
+    game_time_ms: 0,
+    ended: false,
+    disconnects,
+  };
+
+  assert!(engine.eval(vec![game(1, 10, 0)]).is_empty());
+
+  let alerts = engine.eval(vec![game(1, 600, 2), game(2, 10, 0)]);
+  assert_eq!(alerts.len(), 2);
+  assert_eq!(alerts[0].rule, "stuck");
+  assert_eq!(alerts[1].rule, "dc");
+
+  // fires once per game
+  assert!(engine.eval(vec![game(1, 600, 2)]).is_empty());
+
+  // forgotten after the game is removed
+  engine.eval(vec![]);
+  assert_eq!(engine.eval(vec![game(1, 600, 0)]).len(), 1);
+}
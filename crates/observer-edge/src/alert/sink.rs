@@ -0,0 +1,92 @@
+use super::{Alert, AlertSeverity};
+use crate::error::{Error, Result};
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request};
+use hyper_tls::HttpsConnector;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const PAGER_DUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertSinkConfig {
+  /// Posts the alert as JSON
+  Webhook { url: String },
+  /// PagerDuty Events API v2
+  PagerDuty {
+    routing_key: String,
+    #[serde(default)]
+    url: Option<String>,
+  },
+}
+
+#[derive(Clone)]
+pub struct AlertSink {
+  config: Arc<AlertSinkConfig>,
+  client: Client<HttpsConnector<HttpConnector>>,
+}
+
+impl AlertSink {
+  pub fn new(config: AlertSinkConfig) -> Self {
+    Self {
+      config: Arc::new(config),
+      client: Client::builder().build(HttpsConnector::new()),
+    }
+  }
+
+  pub async fn send(&self, alert: &Alert) -> Result<()> {
+    let (url, payload) = match *self.config {
+      AlertSinkConfig::Webhook { ref url } => (url.as_str(), serde_json::to_vec(alert)?),
+      AlertSinkConfig::PagerDuty {
+        ref routing_key,
+        ref url,
+      } => (
+        url.as_deref().unwrap_or(PAGER_DUTY_EVENTS_URL),
+        serde_json::to_vec(&PagerDutyEvent {
+          routing_key,
+          event_action: "trigger",
+          dedup_key: format!("flo-{}-{}", alert.rule, alert.game_id),
+          payload: PagerDutyPayload {
+            summary: format!("game #{}: {}: {}", alert.game_id, alert.rule, alert.message),
+            source: "flo-observer-edge",
+            severity: match alert.severity {
+              AlertSeverity::Info => "info",
+              AlertSeverity::Warning => "warning",
+              AlertSeverity::Error => "error",
+              AlertSeverity::Critical => "critical",
+            },
+            timestamp: alert.fired_at.to_rfc3339(),
+          },
+        })?,
+      ),
+    };
+    let req = Request::builder()
+      .method(Method::POST)
+      .uri(url)
+      .header("content-type", "application/json")
+      .body(Body::from(payload))
+      .map_err(|err| Error::AlertSink(err.to_string()))?;
+    let res = self.client.request(req).await?;
+    if !res.status().is_success() {
+      return Err(Error::AlertSink(res.status().to_string()));
+    }
+    Ok(())
+  }
+}
+
+#[derive(Serialize)]
+struct PagerDutyEvent<'a> {
+  routing_key: &'a str,
+  event_action: &'static str,
+  dedup_key: String,
+  payload: PagerDutyPayload,
+}
+
+#[derive(Serialize)]
+struct PagerDutyPayload {
+  summary: String,
+  source: &'static str,
+  severity: &'static str,
+  timestamp: String,
+}
@@ -0,0 +1,576 @@
+//! Moves archives older than `FLO_ARCHIVE_COLD_AFTER_DAYS` to a cold tier.
+//!
+//! The cold tier is either a separate bucket (`FLO_ARCHIVE_COLD_BUCKET`) or a storage class
+//! of the archive bucket (`FLO_ARCHIVE_COLD_STORAGE_CLASS`, defaults to `GLACIER`).
+//! Each archive has an index entry recording its tier, stored as
+//! `index/hot/{game_id}.json` or `index/cold/{game_id}.json` in the archive bucket,
+//! so a sweep only lists archives that are still hot.
+
+use crate::env::ENV;
+use crate::error::{Error, Result};
+use crate::game::finished::FinishedGame;
+use crate::game::versions::VersionDay;
+use bytes::Bytes;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures::stream::StreamExt;
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+  CopyObjectRequest, DeleteObjectRequest, GetObjectError, GetObjectRequest, GlacierJobParameters,
+  HeadObjectRequest, ListObjectsV2Request, PutObjectRequest, RestoreObjectRequest, RestoreRequest,
+  S3Client, S3,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const INDEX_HOT_PREFIX: &str = "index/hot/";
+const INDEX_COLD_PREFIX: &str = "index/cold/";
+const DEFAULT_COLD_STORAGE_CLASS: &str = "GLACIER";
+const DEFAULT_RESTORE_DAYS: i64 = 3;
+const DEFAULT_SWEEP_INTERVAL_SECS: u64 = 3600 * 6;
+const BACKFILL_CONCURRENCY: usize = 16;
+const VERSION_STATS_PREFIX: &str = "stats/versions/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveTier {
+  Hot,
+  Cold,
+}
+
+/// Finished game index entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveIndexEntry {
+  pub game_id: i32,
+  pub size: usize,
+  pub md5: String,
+  pub tier: ArchiveTier,
+  pub archived_at: DateTime<Utc>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub transitioned_at: Option<DateTime<Utc>>,
+  /// Missing in entries written before the startup backfill was added
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub game: Option<FinishedGame>,
+}
+
+impl ArchiveIndexEntry {
+  pub fn new(game_id: i32, size: usize, md5: String) -> Self {
+    Self {
+      game_id,
+      size,
+      md5,
+      tier: ArchiveTier::Hot,
+      archived_at: Utc::now(),
+      transitioned_at: None,
+      game: None,
+    }
+  }
+
+  fn index_key(&self) -> String {
+    index_key(self.tier, self.game_id)
+  }
+
+  fn should_transition(&self, now: DateTime<Utc>, cold_after: Duration) -> bool {
+    self.tier == ArchiveTier::Hot && now.signed_duration_since(self.archived_at) >= cold_after
+  }
+}
+
+// `index/hot/{game_id}.json` => `game_id`
+fn parse_index_key(key: &str) -> Option<i32> {
+  key
+    .trim_start_matches(INDEX_HOT_PREFIX)
+    .trim_start_matches(INDEX_COLD_PREFIX)
+    .trim_end_matches(".json")
+    .parse()
+    .ok()
+}
+
+fn parse_version_day_key(key: &str) -> Option<NaiveDate> {
+  key
+    .strip_prefix(VERSION_STATS_PREFIX)?
+    .strip_suffix(".json")?
+    .parse()
+    .ok()
+}
+
+fn index_key(tier: ArchiveTier, game_id: i32) -> String {
+  match tier {
+    ArchiveTier::Hot => format!("{}{}.json", INDEX_HOT_PREFIX, game_id),
+    ArchiveTier::Cold => format!("{}{}.json", INDEX_COLD_PREFIX, game_id),
+  }
+}
+
+#[derive(Debug, Clone)]
+pub enum ColdTier {
+  Bucket(String),
+  StorageClass(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct LifecycleConfig {
+  pub cold_after: Duration,
+  pub cold_tier: ColdTier,
+  pub restore_days: i64,
+  pub sweep_interval: std::time::Duration,
+}
+
+impl LifecycleConfig {
+  pub fn from_env() -> Option<Self> {
+    let days = ENV.archive_cold_after_days?;
+    let cold_tier = if let Some(bucket) = ENV.archive_cold_bucket.clone() {
+      ColdTier::Bucket(bucket)
+    } else {
+      ColdTier::StorageClass(
+        ENV
+          .archive_cold_storage_class
+          .clone()
+          .unwrap_or_else(|| DEFAULT_COLD_STORAGE_CLASS.to_string()),
+      )
+    };
+    Some(Self {
+      cold_after: Duration::days(days),
+      cold_tier,
+      restore_days: ENV.archive_restore_days.unwrap_or(DEFAULT_RESTORE_DAYS),
+      sweep_interval: std::time::Duration::from_secs(
+        ENV
+          .archive_lifecycle_interval_secs
+          .unwrap_or(DEFAULT_SWEEP_INTERVAL_SECS),
+      ),
+    })
+  }
+}
+
+/// Result of fetching an archive for the replay API
+#[derive(Debug)]
+pub enum ArchiveFetch {
+  NotFound,
+  Ready(Vec<Bytes>),
+  /// The archive is in cold storage, a restore was requested
+  Restoring,
+}
+
+#[derive(Debug, PartialEq)]
+enum RestoreStatus {
+  NotRequested,
+  InProgress,
+  Restored,
+}
+
+// `x-amz-restore: ongoing-request="false", expiry-date="..."`
+fn parse_restore_status(value: Option<&str>) -> RestoreStatus {
+  match value {
+    None => RestoreStatus::NotRequested,
+    Some(v) if v.contains("ongoing-request=\"true\"") => RestoreStatus::InProgress,
+    Some(_) => RestoreStatus::Restored,
+  }
+}
+
+#[derive(Clone)]
+pub struct ArchiveStore {
+  bucket: String,
+  client: Arc<S3Client>,
+  lifecycle: Option<Arc<LifecycleConfig>>,
+}
+
+impl ArchiveStore {
+  pub fn new(bucket: String, client: Arc<S3Client>, lifecycle: Option<LifecycleConfig>) -> Self {
+    Self {
+      bucket,
+      client,
+      lifecycle: lifecycle.map(Arc::new),
+    }
+  }
+
+  pub fn lifecycle(&self) -> Option<&LifecycleConfig> {
+    self.lifecycle.as_deref()
+  }
+
+  pub async fn put_index(&self, entry: &ArchiveIndexEntry) -> Result<()> {
+    let body = serde_json::to_vec(entry)?;
+    self
+      .client
+      .put_object(PutObjectRequest {
+        bucket: self.bucket.clone(),
+        key: entry.index_key(),
+        body: Some(body.into()),
+        content_type: Some("application/json".to_string()),
+        ..Default::default()
+      })
+      .await
+      .map_err(storage_error)?;
+    Ok(())
+  }
+
+  pub async fn get_index(&self, game_id: i32) -> Result<Option<ArchiveIndexEntry>> {
+    for tier in [ArchiveTier::Hot, ArchiveTier::Cold] {
+      if let Some(parts) = self
+        .get_object(&self.bucket, &index_key(tier, game_id))
+        .await?
+      {
+        let bytes: Vec<u8> = parts.into_iter().flatten().collect();
+        return Ok(Some(serde_json::from_slice(&bytes)?));
+      }
+    }
+    Ok(None)
+  }
+
+  /// Finished games of hot archives indexed since `since`.
+  /// Archives in the cold tier are older than any useful backfill window.
+  pub async fn recent_finished_games(&self, since: DateTime<Utc>) -> Result<Vec<FinishedGame>> {
+    let mut game_ids = vec![];
+    let mut continuation_token = None;
+    loop {
+      let res = self
+        .client
+        .list_objects_v2(ListObjectsV2Request {
+          bucket: self.bucket.clone(),
+          prefix: Some(INDEX_HOT_PREFIX.to_string()),
+          continuation_token: continuation_token.take(),
+          ..Default::default()
+        })
+        .await
+        .map_err(storage_error)?;
+
+      for object in res.contents.unwrap_or_default() {
+        let modified_at = object
+          .last_modified
+          .as_deref()
+          .and_then(|v| DateTime::parse_from_rfc3339(v).ok());
+        match modified_at {
+          Some(t) if t >= since => {}
+          _ => continue,
+        }
+        if let Some(game_id) = object.key.as_deref().and_then(parse_index_key) {
+          game_ids.push(game_id);
+        }
+      }
+
+      if res.is_truncated == Some(true) && res.next_continuation_token.is_some() {
+        continuation_token = res.next_continuation_token;
+      } else {
+        break;
+      }
+    }
+
+    let entries: Vec<_> = futures::stream::iter(game_ids)
+      .map(|game_id| async move { (game_id, self.get_index(game_id).await) })
+      .buffer_unordered(BACKFILL_CONCURRENCY)
+      .collect()
+      .await;
+
+    let mut games = vec![];
+    for (game_id, entry) in entries {
+      match entry {
+        Ok(entry) => games.extend(
+          entry
+            .and_then(|entry| entry.game)
+            .filter(|game| game.ended_at >= since),
+        ),
+        Err(err) => tracing::warn!(game_id, "backfill: load index: {}", err),
+      }
+    }
+    Ok(games)
+  }
+
+  pub async fn put_version_day(&self, date: NaiveDate, day: &VersionDay) -> Result<()> {
+    let body = serde_json::to_vec(day)?;
+    self
+      .client
+      .put_object(PutObjectRequest {
+        bucket: self.bucket.clone(),
+        key: format!("{}{}.json", VERSION_STATS_PREFIX, date),
+        body: Some(body.into()),
+        content_type: Some("application/json".to_string()),
+        ..Default::default()
+      })
+      .await
+      .map_err(storage_error)?;
+    Ok(())
+  }
+
+  /// Stored game version days since `since`
+  pub async fn load_version_days(&self, since: NaiveDate) -> Result<Vec<(NaiveDate, VersionDay)>> {
+    let res = self
+      .client
+      .list_objects_v2(ListObjectsV2Request {
+        bucket: self.bucket.clone(),
+        prefix: Some(VERSION_STATS_PREFIX.to_string()),
+        start_after: Some(format!("{}{}", VERSION_STATS_PREFIX, since.pred())),
+        ..Default::default()
+      })
+      .await
+      .map_err(storage_error)?;
+
+    let mut days = vec![];
+    for object in res.contents.unwrap_or_default() {
+      let (key, date) = match object.key {
+        Some(key) => match parse_version_day_key(&key) {
+          Some(date) if date >= since => (key, date),
+          _ => continue,
+        },
+        None => continue,
+      };
+      if let Some(parts) = self.get_object(&self.bucket, &key).await? {
+        let bytes: Vec<u8> = parts.into_iter().flatten().collect();
+        days.push((date, serde_json::from_slice(&bytes)?));
+      }
+    }
+    Ok(days)
+  }
+
+  /// Transitions every hot archive older than `cold_after`, returns the number of transitioned archives
+  pub async fn sweep(&self) -> Result<usize> {
+    let config = if let Some(config) = self.lifecycle.as_ref() {
+      config
+    } else {
+      return Ok(0);
+    };
+    let now = Utc::now();
+    let mut count = 0;
+    let mut continuation_token = None;
+    loop {
+      let res = self
+        .client
+        .list_objects_v2(ListObjectsV2Request {
+          bucket: self.bucket.clone(),
+          prefix: Some(INDEX_HOT_PREFIX.to_string()),
+          continuation_token: continuation_token.take(),
+          ..Default::default()
+        })
+        .await
+        .map_err(storage_error)?;
+
+      for object in res.contents.unwrap_or_default() {
+        // index entries are written once when archived
+        let modified_at = object
+          .last_modified
+          .as_deref()
+          .and_then(|v| DateTime::parse_from_rfc3339(v).ok());
+        if let Some(t) = modified_at {
+          if now.signed_duration_since(t) < config.cold_after {
+            continue;
+          }
+        }
+        let key = if let Some(key) = object.key {
+          key
+        } else {
+          continue;
+        };
+        let game_id = match parse_index_key(&key) {
+          Some(id) => id,
+          None => continue,
+        };
+        let entry = match self.get_index(game_id).await? {
+          Some(entry) => entry,
+          None => continue,
+        };
+        if entry.should_transition(now, config.cold_after) {
+          if let Err(err) = self.transition(config, entry).await {
+            tracing::error!(game_id, "archive transition: {}", err);
+          } else {
+            count += 1;
+          }
+        }
+      }
+
+      if res.is_truncated == Some(true) && res.next_continuation_token.is_some() {
+        continuation_token = res.next_continuation_token;
+      } else {
+        break;
+      }
+    }
+    Ok(count)
+  }
+
+  async fn transition(&self, config: &LifecycleConfig, mut entry: ArchiveIndexEntry) -> Result<()> {
+    let key = entry.game_id.to_string();
+    let copy_source = format!("{}/{}", self.bucket, key);
+    match config.cold_tier {
+      ColdTier::Bucket(ref bucket) => {
+        self
+          .client
+          .copy_object(CopyObjectRequest {
+            bucket: bucket.clone(),
+            key: key.clone(),
+            copy_source,
+            ..Default::default()
+          })
+          .await
+          .map_err(storage_error)?;
+        self
+          .client
+          .delete_object(DeleteObjectRequest {
+            bucket: self.bucket.clone(),
+            key,
+            ..Default::default()
+          })
+          .await
+          .map_err(storage_error)?;
+      }
+      ColdTier::StorageClass(ref storage_class) => {
+        self
+          .client
+          .copy_object(CopyObjectRequest {
+            bucket: self.bucket.clone(),
+            key,
+            copy_source,
+            storage_class: Some(storage_class.clone()),
+            ..Default::default()
+          })
+          .await
+          .map_err(storage_error)?;
+      }
+    }
+
+    let hot_key = entry.index_key();
+    entry.tier = ArchiveTier::Cold;
+    entry.transitioned_at = Some(Utc::now());
+    self.put_index(&entry).await?;
+    self
+      .client
+      .delete_object(DeleteObjectRequest {
+        bucket: self.bucket.clone(),
+        key: hot_key,
+        ..Default::default()
+      })
+      .await
+      .map_err(storage_error)?;
+
+    tracing::info!(game_id = entry.game_id, "archive moved to cold tier");
+    Ok(())
+  }
+
+  pub async fn fetch(&self, game_id: i32) -> Result<ArchiveFetch> {
+    let key = game_id.to_string();
+    let tier = self
+      .get_index(game_id)
+      .await?
+      .map(|entry| entry.tier)
+      .unwrap_or(ArchiveTier::Hot);
+
+    let cold_tier = self.lifecycle.as_ref().map(|config| &config.cold_tier);
+    let bucket = match (tier, cold_tier) {
+      (ArchiveTier::Cold, Some(ColdTier::Bucket(bucket))) => bucket.clone(),
+      (ArchiveTier::Cold, _) => {
+        if !self.restore(&key).await? {
+          return Ok(ArchiveFetch::Restoring);
+        }
+        self.bucket.clone()
+      }
+      (ArchiveTier::Hot, _) => self.bucket.clone(),
+    };
+
+    Ok(match self.get_object(&bucket, &key).await? {
+      Some(parts) => ArchiveFetch::Ready(parts),
+      None => ArchiveFetch::NotFound,
+    })
+  }
+
+  /// Returns `true` if the object is readable, otherwise requests a restore
+  async fn restore(&self, key: &str) -> Result<bool> {
+    let head = self
+      .client
+      .head_object(HeadObjectRequest {
+        bucket: self.bucket.clone(),
+        key: key.to_string(),
+        ..Default::default()
+      })
+      .await
+      .map_err(storage_error)?;
+
+    match parse_restore_status(head.restore.as_deref()) {
+      RestoreStatus::Restored => return Ok(true),
+      RestoreStatus::InProgress => return Ok(false),
+      RestoreStatus::NotRequested => {}
+    }
+
+    let days = self
+      .lifecycle
+      .as_ref()
+      .map(|config| config.restore_days)
+      .unwrap_or(DEFAULT_RESTORE_DAYS);
+    self
+      .client
+      .restore_object(RestoreObjectRequest {
+        bucket: self.bucket.clone(),
+        key: key.to_string(),
+        restore_request: Some(RestoreRequest {
+          days: Some(days),
+          glacier_job_parameters: Some(GlacierJobParameters {
+            tier: "Standard".to_string(),
+          }),
+          ..Default::default()
+        }),
+        ..Default::default()
+      })
+      .await
+      .map_err(storage_error)?;
+    tracing::info!("archive restore requested: {}", key);
+    Ok(false)
+  }
+
+  async fn get_object(&self, bucket: &str, key: &str) -> Result<Option<Vec<Bytes>>> {
+    let req = GetObjectRequest {
+      key: key.to_string(),
+      bucket: bucket.to_string(),
+      ..Default::default()
+    };
+    let parts = match self.client.get_object(req).await {
+      Ok(res) => {
+        if let Some(stream) = res.body {
+          stream
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?
+        } else {
+          return Ok(None);
+        }
+      }
+      Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => return Ok(None),
+      Err(err) => return Err(err.into()),
+    };
+    Ok(Some(parts))
+  }
+}
+
+fn storage_error<E: std::fmt::Display>(err: E) -> Error {
+  Error::ArchiveStorage(err.to_string())
+}
+
+#[test]
+fn test_archive_lifecycle() {
+  let mut entry = ArchiveIndexEntry::new(1, 100, "md5".to_string());
+  let now = entry.archived_at;
+  assert_eq!(entry.index_key(), "index/hot/1.json");
+  assert_eq!(parse_index_key(&entry.index_key()), Some(1));
+  assert_eq!(parse_index_key("index/hot/x.json"), None);
+  assert_eq!(
+    parse_version_day_key("stats/versions/2026-10-18.json"),
+    Some(NaiveDate::from_ymd(2026, 10, 18))
+  );
+  assert_eq!(parse_version_day_key("index/hot/1.json"), None);
+  assert!(!entry.should_transition(now + Duration::days(29), Duration::days(30)));
+  assert!(entry.should_transition(now + Duration::days(30), Duration::days(30)));
+
+  entry.tier = ArchiveTier::Cold;
+  assert_eq!(entry.index_key(), "index/cold/1.json");
+  assert!(!entry.should_transition(now + Duration::days(60), Duration::days(30)));
+
+  let json = serde_json::to_string(&entry).unwrap();
+  assert_eq!(
+    serde_json::from_str::<ArchiveIndexEntry>(&json).unwrap(),
+    entry
+  );
+
+  assert_eq!(parse_restore_status(None), RestoreStatus::NotRequested);
+  assert_eq!(
+    parse_restore_status(Some("ongoing-request=\"true\"")),
+    RestoreStatus::InProgress
+  );
+  assert_eq!(
+    parse_restore_status(Some(
+      "ongoing-request=\"false\", expiry-date=\"Fri, 21 Dec 2012 00:00:00 GMT\""
+    )),
+    RestoreStatus::Restored
+  );
+}
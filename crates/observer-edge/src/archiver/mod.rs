@@ -1,21 +1,35 @@
 use crate::env::ENV;
 use crate::error::{Error, Result};
+use crate::game::finished::FinishedGame;
+use crate::game::versions::VersionDay;
 use backoff::backoff::Backoff;
 use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, Utc};
 use rusoto_core::{credential::StaticProvider, request::HttpClient};
 use rusoto_s3::{S3Client, S3};
 use std::io::Write;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 
+mod lifecycle;
+use lifecycle::{ArchiveIndexEntry, ArchiveStore, LifecycleConfig};
+
+pub use lifecycle::ArchiveFetch;
+
 pub struct Archiver {
   s3_bucket: String,
   s3_client: Arc<S3Client>,
+  store: ArchiveStore,
   rx: mpsc::Receiver<Msg>,
 }
 
 impl Archiver {
   pub fn new() -> Result<Option<(Self, ArchiverHandle)>> {
+    // keeps fabricated games out of the archive bucket
+    #[cfg(feature = "synthetic")]
+    if crate::synthetic::SYNTHETIC.is_some() {
+      return Ok(None);
+    }
     let s3_bucket = if let Some(value) = ENV.aws_s3_bucket.clone() {
       value
     } else {
@@ -46,18 +60,21 @@ impl Archiver {
 
     let (tx, rx) = mpsc::channel(100);
 
+    let lifecycle = LifecycleConfig::from_env();
+    if let Some(config) = lifecycle.as_ref() {
+      tracing::debug!("archive lifecycle: {:?}", config);
+    }
+    let store = ArchiveStore::new(s3_bucket.clone(), s3_client.clone(), lifecycle);
+
     Ok(
       (
         Self {
-          s3_bucket: s3_bucket.clone(),
-          s3_client: s3_client.clone(),
-          rx,
-        },
-        ArchiverHandle {
-          tx,
           s3_bucket,
           s3_client,
+          store: store.clone(),
+          rx,
         },
+        ArchiverHandle { tx, store },
       )
         .into(),
     )
@@ -67,15 +84,32 @@ impl Archiver {
     let Self {
       s3_bucket,
       s3_client,
+      store,
       mut rx,
     } = self;
 
+    if let Some(period) = store.lifecycle().map(|config| config.sweep_interval) {
+      let store = store.clone();
+      tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+          interval.tick().await;
+          match store.sweep().await {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("archive lifecycle: {} archives moved to cold tier", n),
+            Err(err) => tracing::error!("archive lifecycle: {}", err),
+          }
+        }
+      });
+    }
+
     loop {
       tokio::select! {
         msg = rx.recv() => {
           match msg {
             Some(Msg::AddArchive(archive)) => {
-              Self::upload(&s3_bucket, s3_client.clone(), archive).await;
+              Self::upload(&s3_bucket, s3_client.clone(), &store, archive).await;
             },
             None => break,
           }
@@ -87,7 +121,13 @@ impl Archiver {
   async fn upload(
     bucket: &str,
     s3_client: Arc<S3Client>,
-    ArchiveInfo { game_id, data, md5 }: ArchiveInfo,
+    store: &ArchiveStore,
+    ArchiveInfo {
+      game_id,
+      data,
+      md5,
+      finished,
+    }: ArchiveInfo,
   ) {
     use futures::stream;
     use rusoto_core::{ByteStream, RusotoError};
@@ -117,6 +157,13 @@ impl Archiver {
           span.in_scope(|| {
             tracing::info!("uploaded: {} bytes", data.len());
           });
+          let mut entry = ArchiveIndexEntry::new(game_id, data.len(), md5.clone());
+          entry.game = finished.as_deref().cloned();
+          if let Err(err) = store.put_index(&entry).await {
+            span.in_scope(|| {
+              tracing::error!("index: {}", err);
+            });
+          }
           break
         },
         Err(RusotoError::HttpDispatch(err)) => {
@@ -146,8 +193,7 @@ impl Archiver {
 #[allow(unused)]
 pub struct ArchiverHandle {
   tx: mpsc::Sender<Msg>,
-  s3_bucket: String,
-  s3_client: Arc<S3Client>,
+  store: ArchiveStore,
 }
 
 impl ArchiverHandle {
@@ -155,37 +201,24 @@ impl ArchiverHandle {
     self.tx.try_send(Msg::AddArchive(archive)).is_ok()
   }
 
+  /// Archives in cold storage are restored on demand,
+  /// `ArchiveFetch::Restoring` is returned until the restore completes
   #[allow(unused)]
-  pub async fn fetch(&self, game_id: i32) -> Result<Option<Vec<Bytes>>> {
-    use futures::stream::StreamExt;
-    use rusoto_core::RusotoError;
-    use rusoto_s3::GetObjectError;
-    use rusoto_s3::GetObjectRequest;
-
-    let key = game_id.to_string();
-
-    let req = GetObjectRequest {
-      key: key.clone(),
-      bucket: self.s3_bucket.clone(),
-      ..Default::default()
-    };
-    let parts = match self.s3_client.get_object(req).await {
-      Ok(res) => {
-        if let Some(stream) = res.body {
-          stream
-            .collect::<Vec<_>>()
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()?
-        } else {
-          return Ok(None);
-        }
-      }
-      Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => return Ok(None),
-      Err(err) => return Err(err.into()),
-    };
+  pub async fn fetch(&self, game_id: i32) -> Result<ArchiveFetch> {
+    self.store.fetch(game_id).await
+  }
+
+  /// Finished games archived since `since`, read from the archive index
+  pub async fn recent_finished_games(&self, since: DateTime<Utc>) -> Result<Vec<FinishedGame>> {
+    self.store.recent_finished_games(since).await
+  }
+
+  pub async fn put_version_day(&self, date: NaiveDate, day: &VersionDay) -> Result<()> {
+    self.store.put_version_day(date, day).await
+  }
 
-    Ok(Some(parts))
+  pub async fn load_version_days(&self, since: NaiveDate) -> Result<Vec<(NaiveDate, VersionDay)>> {
+    self.store.load_version_days(since).await
   }
 }
 
@@ -199,6 +232,8 @@ pub struct ArchiveInfo {
   pub game_id: i32,
   pub data: Bytes,
   pub md5: String,
+  /// Stored in the index entry for the startup backfill
+  pub finished: Option<Arc<FinishedGame>>,
 }
 
 pub struct Md5Writer<W> {
@@ -29,6 +29,10 @@ where
   pub fn is_closed(&self) -> bool {
     self.tx.receiver_count() == 0
   }
+
+  pub fn receiver_count(&self) -> usize {
+    self.tx.receiver_count()
+  }
 }
 
 pub struct BroadcastReceiver<E> {
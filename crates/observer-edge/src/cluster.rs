@@ -0,0 +1,332 @@
+//! Running several edges over the same record stream.
+//!
+//! Each edge owns the games whose ids map to it on a consistent hash ring built from the
+//! reachable edges, records of other games are skipped. Edges check each other's health,
+//! an unreachable edge is taken out of the ring and its games move to the remaining edges,
+//! which pick them up at the next keyframe.
+//!
+//! Configured with `FLO_OBSERVER_EDGE_ID` and `FLO_OBSERVER_EDGE_PEERS` (JSON, every edge
+//! including this one), without them this edge owns every game.
+//!
+//! ```json
+//! [
+//!   { "id": "a", "url": "http://10.0.0.1:3558", "stream_host": "edge-a.w3flo.com" },
+//!   { "id": "b", "url": "http://10.0.0.2:3558", "stream_host": "edge-b.w3flo.com" }
+//! ]
+//! ```
+
+use crate::error::{Error, Result};
+use crate::game::snapshot::GameSnapshot;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, StatusCode};
+use hyper_tls::HttpsConnector;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Points of each edge on the ring, more points spread games more evenly
+const VIRTUAL_NODES: u32 = 64;
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub static FLO_OBSERVER_EDGE_HEALTH_CHECK_SECS: Lazy<u64> = Lazy::new(|| {
+  std::env::var("FLO_OBSERVER_EDGE_HEALTH_CHECK_SECS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(5)
+});
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EdgePeer {
+  pub id: String,
+  /// Base url of the service embedding the edge, serving `/cluster/health` and `/cluster/games/:id`
+  pub url: String,
+  /// Public host of the edge's observer stream server
+  #[serde(default)]
+  pub stream_host: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameOwner {
+  Local,
+  Remote(EdgePeer),
+}
+
+#[derive(Debug)]
+pub struct HashRing {
+  /// Sorted by hash
+  points: Vec<(u64, usize)>,
+  peers: Vec<EdgePeer>,
+}
+
+impl HashRing {
+  pub fn new(peers: Vec<EdgePeer>) -> Self {
+    let mut points: Vec<(u64, usize)> = peers
+      .iter()
+      .enumerate()
+      .flat_map(|(idx, peer)| {
+        (0..VIRTUAL_NODES).map(move |n| (hash(format!("{}#{}", peer.id, n).as_bytes()), idx))
+      })
+      .collect();
+    points.sort_unstable();
+    Self { points, peers }
+  }
+
+  pub fn get(&self, game_id: i32) -> Option<&EdgePeer> {
+    if self.points.is_empty() {
+      return None;
+    }
+    let key = hash(&game_id.to_le_bytes());
+    let idx = match self.points.binary_search_by(|(h, _)| h.cmp(&key)) {
+      Ok(idx) => idx,
+      Err(idx) => idx % self.points.len(),
+    };
+    self.peers.get(self.points[idx].1)
+  }
+}
+
+/// FNV-1a with a splitmix64 finalizer, stable across builds unlike `DefaultHasher`
+fn hash(bytes: &[u8]) -> u64 {
+  let mut h: u64 = 0xcbf29ce484222325;
+  for b in bytes {
+    h ^= *b as u64;
+    h = h.wrapping_mul(0x100000001b3);
+  }
+  h ^= h >> 30;
+  h = h.wrapping_mul(0xbf58476d1ce4e5b9);
+  h ^= h >> 27;
+  h = h.wrapping_mul(0x94d049bb133111eb);
+  h ^ (h >> 31)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerStatus {
+  pub peer: EdgePeer,
+  pub live: bool,
+}
+
+struct State {
+  self_id: String,
+  peers: Vec<EdgePeer>,
+  live: BTreeSet<String>,
+  ring: HashRing,
+}
+
+/// Game ownership shared by the dispatcher and query handles
+#[derive(Clone)]
+pub struct Cluster {
+  state: Option<Arc<RwLock<State>>>,
+  client: Client<HttpsConnector<HttpConnector>>,
+}
+
+impl Cluster {
+  pub fn from_env() -> Result<Self> {
+    let client = Client::builder().build(HttpsConnector::new());
+    let peers = match std::env::var("FLO_OBSERVER_EDGE_PEERS").ok() {
+      Some(json) => serde_json::from_str::<Vec<EdgePeer>>(&json)
+        .map_err(|err| Error::ClusterConfig(err.to_string()))?,
+      None => {
+        return Ok(Self {
+          state: None,
+          client,
+        })
+      }
+    };
+    let self_id = std::env::var("FLO_OBSERVER_EDGE_ID")
+      .map_err(|_| Error::ClusterConfig("FLO_OBSERVER_EDGE_ID is required".to_string()))?;
+    Ok(Self {
+      state: Some(Arc::new(RwLock::new(State::new(self_id, peers)?))),
+      client,
+    })
+  }
+
+  pub fn enabled(&self) -> bool {
+    self.state.is_some()
+  }
+
+  pub fn owner(&self, game_id: i32) -> GameOwner {
+    let state = if let Some(state) = self.state.as_ref() {
+      state.read()
+    } else {
+      return GameOwner::Local;
+    };
+    match state.ring.get(game_id) {
+      Some(peer) if peer.id != state.self_id => GameOwner::Remote(peer.clone()),
+      _ => GameOwner::Local,
+    }
+  }
+
+  pub fn owns(&self, game_id: i32) -> bool {
+    self.owner(game_id) == GameOwner::Local
+  }
+
+  pub fn peer_status(&self) -> Vec<PeerStatus> {
+    let state = if let Some(state) = self.state.as_ref() {
+      state.read()
+    } else {
+      return vec![];
+    };
+    state
+      .peers
+      .iter()
+      .map(|peer| PeerStatus {
+        peer: peer.clone(),
+        live: state.live.contains(&peer.id),
+      })
+      .collect()
+  }
+
+  /// Checks the health of the other edges and rebuilds the ring from the reachable ones,
+  /// returns `true` if game ownership changed
+  pub async fn check_peers(&self) -> bool {
+    let state = if let Some(state) = self.state.as_ref() {
+      state
+    } else {
+      return false;
+    };
+    let (self_id, peers) = {
+      let state = state.read();
+      (state.self_id.clone(), state.peers.clone())
+    };
+
+    let checks = peers
+      .iter()
+      .filter(|peer| peer.id != self_id)
+      .map(|peer| async move {
+        let live =
+          tokio::time::timeout(HEALTH_CHECK_TIMEOUT, self.get(&peer.url, "/cluster/health"))
+            .await
+            .map(|res| res.is_ok())
+            .unwrap_or(false);
+        (peer.id.clone(), live)
+      });
+    let mut live: BTreeSet<String> = futures::future::join_all(checks)
+      .await
+      .into_iter()
+      .filter(|(_, live)| *live)
+      .map(|(id, _)| id)
+      .collect();
+    live.insert(self_id);
+
+    let mut state = state.write();
+    if state.live == live {
+      return false;
+    }
+    tracing::info!("cluster edges changed: {:?} -> {:?}", state.live, live);
+    state.set_live(live);
+    true
+  }
+
+  /// Looks up a game on the edge that owns it
+  pub async fn fetch_game(&self, peer: &EdgePeer, game_id: i32) -> Result<GameSnapshot> {
+    let body = self
+      .get(&peer.url, &format!("/cluster/games/{}", game_id))
+      .await
+      .map_err(|err| match err {
+        Error::ClusterPeer { status, .. } if status == StatusCode::NOT_FOUND.as_u16() => {
+          Error::GameNotFound(game_id)
+        }
+        err => err,
+      })?;
+    Ok(serde_json::from_slice(&body)?)
+  }
+
+  async fn get(&self, base_url: &str, path: &str) -> Result<Vec<u8>> {
+    let req = Request::builder()
+      .method(Method::GET)
+      .uri(format!("{}{}", base_url.trim_end_matches('/'), path))
+      .body(Body::empty())
+      .map_err(|err| Error::ClusterConfig(err.to_string()))?;
+    let res = self.client.request(req).await?;
+    let status = res.status();
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+    if !status.is_success() {
+      return Err(Error::ClusterPeer {
+        url: base_url.to_string(),
+        status: status.as_u16(),
+      });
+    }
+    Ok(body.to_vec())
+  }
+}
+
+impl State {
+  fn new(self_id: String, peers: Vec<EdgePeer>) -> Result<Self> {
+    if !peers.iter().any(|peer| peer.id == self_id) {
+      return Err(Error::ClusterConfig(format!(
+        "FLO_OBSERVER_EDGE_PEERS does not include `{}`",
+        self_id
+      )));
+    }
+    // peers are assumed live until the first health check
+    let live = peers.iter().map(|peer| peer.id.clone()).collect();
+    let mut state = Self {
+      self_id,
+      peers,
+      live: BTreeSet::new(),
+      ring: HashRing::new(vec![]),
+    };
+    state.set_live(live);
+    Ok(state)
+  }
+
+  fn set_live(&mut self, live: BTreeSet<String>) {
+    self.ring = HashRing::new(
+      self
+        .peers
+        .iter()
+        .filter(|peer| live.contains(&peer.id))
+        .cloned()
+        .collect(),
+    );
+    self.live = live;
+  }
+}
+
+#[test]
+fn test_hash_ring() {
+  let peer = |id: &str| EdgePeer {
+    id: id.to_string(),
+    url: format!("http://{}", id),
+    stream_host: None,
+  };
+  let ring = HashRing::new(vec![peer("a"), peer("b"), peer("c")]);
+  let owners: Vec<String> = (0..3000)
+    .map(|id| ring.get(id).unwrap().id.clone())
+    .collect();
+  for id in ["a", "b", "c"] {
+    let count = owners.iter().filter(|v| *v == id).count();
+    assert!(count > 600, "{}: {}", id, count);
+  }
+
+  // only the games of the removed edge move
+  let ring_without_b = HashRing::new(vec![peer("a"), peer("c")]);
+  for (id, owner) in owners.iter().enumerate() {
+    let new_owner = &ring_without_b.get(id as i32).unwrap().id;
+    if owner != "b" {
+      assert_eq!(owner, new_owner);
+    }
+  }
+
+  assert!(HashRing::new(vec![]).get(1).is_none());
+}
+
+#[test]
+fn test_cluster_state() {
+  let peers: Vec<EdgePeer> = ["a", "b"]
+    .iter()
+    .map(|id| EdgePeer {
+      id: id.to_string(),
+      url: format!("http://{}", id),
+      stream_host: None,
+    })
+    .collect();
+  assert!(State::new("c".to_string(), peers.clone()).is_err());
+
+  let mut state = State::new("a".to_string(), peers).unwrap();
+  assert!((0..100).any(|id| state.ring.get(id).unwrap().id == "b"));
+  state.set_live(["a".to_string()].iter().cloned().collect());
+  assert!((0..100).all(|id| state.ring.get(id).unwrap().id == "a"));
+}
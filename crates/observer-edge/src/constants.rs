@@ -1,8 +1,66 @@
+use flo_task::SendPolicy;
 use once_cell::sync::Lazy;
+use std::time::Duration;
 
 pub static FLO_STATS_MAX_IN_MEMORY_GAMES: Lazy<usize> = Lazy::new(|| {
   std::env::var("FLO_STATS_MAX_IN_MEMORY_GAMES")
     .ok()
     .and_then(|v| v.parse().ok())
     .unwrap_or(100)
-});
\ No newline at end of file
+});
+
+/// Number of finished games kept in memory for bulk export
+pub static FLO_STATS_MAX_FINISHED_GAMES: Lazy<usize> = Lazy::new(|| {
+  std::env::var("FLO_STATS_MAX_FINISHED_GAMES")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(10000)
+});
+
+/// Finished games of the last hours loaded from the archive index on startup, 0 to disable
+pub static FLO_STATS_FINISHED_BACKFILL_HOURS: Lazy<i64> = Lazy::new(|| {
+  std::env::var("FLO_STATS_FINISHED_BACKFILL_HOURS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(24)
+});
+
+/// Dispatcher requests fail with `Error::ActorTimeout` if not handled within this time
+pub static FLO_STATS_DISPATCHER_TIMEOUT_MS: Lazy<u64> = Lazy::new(|| {
+  std::env::var("FLO_STATS_DISPATCHER_TIMEOUT_MS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(10000)
+});
+
+/// Retries are only used for requests that don't change the dispatcher state,
+/// others are sent once with the same timeout
+pub static DISPATCHER_QUERY_POLICY: Lazy<SendPolicy> = Lazy::new(|| {
+  SendPolicy::new(Duration::from_millis(*FLO_STATS_DISPATCHER_TIMEOUT_MS))
+    .with_retries(2, Duration::from_millis(100))
+});
+
+/// Days of game version distribution kept by the edge
+pub static FLO_STATS_VERSION_RETENTION_DAYS: Lazy<i64> = Lazy::new(|| {
+  std::env::var("FLO_STATS_VERSION_RETENTION_DAYS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(90)
+});
+
+/// Interval of refreshing the viewer counts of games
+pub static FLO_STATS_VIEWER_COUNT_INTERVAL_SECS: Lazy<u64> = Lazy::new(|| {
+  std::env::var("FLO_STATS_VIEWER_COUNT_INTERVAL_SECS")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .filter(|v| *v > 0)
+    .unwrap_or(5)
+});
+
+/// Stats events of delta encoded game update subscriptions are sent in full every this many events
+pub static FLO_STATS_DELTA_KEYFRAME_INTERVAL: Lazy<u32> = Lazy::new(|| {
+  std::env::var("FLO_STATS_DELTA_KEYFRAME_INTERVAL")
+    .ok()
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(10)
+});
@@ -5,13 +5,20 @@ use flo_grpc::{
 use s2_grpc_utils::S2ProtoUnpack;
 use tonic::{service::Interceptor, metadata::{MetadataValue, Ascii}, codegen::InterceptedService};
 use crate::error::{Result, Error};
-use crate::game::Game;
+use crate::game::{Game, PlayerPrivacy};
 
 type Client = FloControllerClient<InterceptedService<Channel, WithSecretInterceptor>>;
 
 #[derive(Debug, Clone)]
 pub struct Controller {
-  client: Client,
+  inner: Inner,
+}
+
+#[derive(Debug, Clone)]
+enum Inner {
+  Grpc(Client),
+  #[cfg(feature = "synthetic")]
+  Synthetic(&'static crate::synthetic::SyntheticConfig),
 }
 
 impl Controller {
@@ -19,19 +26,40 @@ impl Controller {
     let chan = Channel::from_static(crate::env::ENV.controller_url.as_str());
     let secret = crate::env::ENV.controller_secret.parse().unwrap();
     Self {
-      client: FloControllerClient::with_interceptor(chan.connect_lazy(), WithSecretInterceptor {
+      inner: Inner::Grpc(FloControllerClient::with_interceptor(chan.connect_lazy(), WithSecretInterceptor {
         secret,
-    }),
+    })),
+    }
+  }
+
+  /// Serves the games of the synthetic record source without a controller
+  #[cfg(feature = "synthetic")]
+  pub fn synthetic(config: &'static crate::synthetic::SyntheticConfig) -> Self {
+    Self {
+      inner: Inner::Synthetic(config),
     }
   }
 
-  pub async fn fetch_game(&self, game_id: i32) -> Result<Game> {
+  pub async fn fetch_game(&self, game_id: i32) -> Result<(Game, PlayerPrivacy)> {
     use flo_grpc::controller::GetGameRequest;
-    let res = self.client.clone().get_game(GetGameRequest {
+    let mut client = match self.inner {
+      Inner::Grpc(ref client) => client.clone(),
+      #[cfg(feature = "synthetic")]
+      Inner::Synthetic(config) => {
+        return config
+          .game(game_id)
+          .map(|game| (game, PlayerPrivacy::default()))
+          .ok_or_else(|| Error::InvalidGameId(game_id))
+      }
+    };
+    let res = client.get_game(GetGameRequest {
       game_id
     }).await;
     match res {
-      Ok(res) => Ok(Game::unpack(res.into_inner().game)?),
+      Ok(res) => {
+        let privacy = PlayerPrivacy::from_metadata(res.metadata());
+        Ok((Game::unpack(res.into_inner().game)?, privacy))
+      },
       Err(status) => {
         if status.code() == tonic::Code::InvalidArgument {
           Err(Error::InvalidGameId(game_id))
@@ -61,7 +89,7 @@ impl Interceptor for WithSecretInterceptor {
 async fn test_ctrlr_client() -> anyhow::Result<()> {
   dotenv::dotenv().unwrap();
   let client = Controller::from_env();
-  let game = client.fetch_game(2048816).await?;
+  let (game, _) = client.fetch_game(2048816).await?;
   dbg!(game);
   Ok(())
 }
@@ -1,20 +1,34 @@
+use crate::alert::{AlertEngine, AlertGameState};
 use crate::broadcast::BroadcastReceiver;
-use crate::constants::FLO_STATS_MAX_IN_MEMORY_GAMES;
+use crate::cluster::Cluster;
+use crate::constants::{
+  FLO_STATS_MAX_FINISHED_GAMES, FLO_STATS_MAX_IN_MEMORY_GAMES, FLO_STATS_VERSION_RETENTION_DAYS,
+  FLO_STATS_VIEWER_COUNT_INTERVAL_SECS,
+};
 use crate::error::{Error, Result};
-use crate::game::event::{GameListUpdateEvent, GameUpdateEvent};
-use crate::game::snapshot::{GameSnapshot, GameSnapshotMap, GameSnapshotWithStats};
+use crate::game::event::{GameListUpdateEvent, GameUpdateEventMask};
+use crate::game::finished::{FinishedGame, FinishedGamePage, FinishedGameStore, PlayerGames};
+use crate::game::schema;
+use crate::game::snapshot::{
+  GameSnapshot, GameSnapshotMap, GameSnapshotWithStats, GameUpdateReceiver,
+};
 use crate::game::stream::GameStreamMap;
-use crate::game::{Game, GameHandler, GameMeta};
+use crate::game::timeline::TimelineEvent;
+use crate::game::versions::{VersionDay, VersionDistribution, VersionStats};
+use crate::game::{split_at_keyframe, Game, GameHandler, GameMeta, PlayerPrivacy};
 use crate::server::peer::GameStreamServer;
 use crate::services::Services;
+use crate::source::RecordSource;
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
-use flo_kinesis::data_stream::DataStreamIterator;
-use flo_kinesis::iterator::Chunk;
+use chrono::{NaiveDate, Utc};
+use flo_kinesis::iterator::{Chunk, GameChunk};
 use flo_net::observer::GameInfo;
 use flo_observer::record::GameRecordData;
 use flo_state::{async_trait, Actor, Addr, Context, Handler, Message};
 use lru::LruCache;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio_stream::StreamExt;
 
@@ -24,20 +38,28 @@ pub struct Dispatcher {
   inactive_cache: LruCache<i32, ()>,
   snapshots: GameSnapshotMap,
   streams: GameStreamMap,
+  finished: FinishedGameStore,
+  versions: VersionStats,
+  alerts: Option<AlertEngine>,
+  cluster: Cluster,
 }
 
 impl Dispatcher {
-  pub fn new(services: Services) -> Self {
+  pub fn new(services: Services, alerts: Option<AlertEngine>, cluster: Cluster) -> Self {
     Self {
       services,
       slots: LruCache::new(*FLO_STATS_MAX_IN_MEMORY_GAMES),
       inactive_cache: LruCache::new(*FLO_STATS_MAX_IN_MEMORY_GAMES),
       snapshots: GameSnapshotMap::new(),
       streams: GameStreamMap::new(),
+      finished: FinishedGameStore::new(*FLO_STATS_MAX_FINISHED_GAMES),
+      versions: VersionStats::new(*FLO_STATS_VERSION_RETENTION_DAYS),
+      alerts,
+      cluster,
     }
   }
 
-  async fn run_iter(addr: Addr<Self>, mut iter: DataStreamIterator) {
+  async fn run_iter(addr: Addr<Self>, mut iter: RecordSource) {
     while let Some(v) = iter.next().await {
       if addr.notify(HandleChunk(v)).await.is_err() {
         break;
@@ -47,10 +69,18 @@ impl Dispatcher {
 
   fn handle_chunk(&mut self, ctx: &mut Context<Self>, chunk: Chunk) {
     for (game_id, game_chunk) in chunk.game_records {
+      // handled by another edge
+      if !self.cluster.owns(game_id) {
+        continue;
+      }
+
       self.streams.dispatch_game_records(game_id, &game_chunk);
 
       if self.inactive_cache.get(&game_id).is_some() {
-        continue;
+        if !has_keyframe(&game_chunk) {
+          continue;
+        }
+        self.inactive_cache.pop(&game_id);
       }
 
       let mut should_remove = false;
@@ -67,41 +97,69 @@ impl Dispatcher {
             tracing::error!(game_id, "handle records: {}", err);
           } else {
             if is_last_chunk {
-              Self::upload_archive(self.services.clone(), handler);
+              let finished = match handler.make_finished_game() {
+                Ok(Some(game)) => {
+                  self.versions.record(&game);
+                  Some(self.finished.insert(game))
+                }
+                Ok(None) => None,
+                Err(err) => {
+                  tracing::warn!(game_id, "finished game: {}", err);
+                  None
+                }
+              };
+              Self::upload_archive(self.services.clone(), handler, finished);
             }
           }
         }
         None => {
-          if game_chunk.min_seq_id != 0 {
-            if game_chunk.records.len() < 8 {
-              tracing::debug!(
-                game_id,
-                "unexpected initial records: {:?}: {:?}",
-                [game_chunk.min_seq_id, game_chunk.max_seq_id],
-                game_chunk.records
-              );
-            } else {
-              tracing::debug!(
-                game_id,
-                "unexpected initial records: {:?}",
-                [game_chunk.min_seq_id, game_chunk.max_seq_id]
-              );
-            }
-            if self.slots.len() == self.slots.cap() {
-              if let Some((game_id, mut removed)) = self.slots.pop_lru() {
-                tracing::info!(game_id, "expired");
-                self.snapshots.remove_game(game_id);
-                Self::upload_archive(self.services.clone(), &mut removed);
+          let (mut handler, game_chunk) = if game_chunk.min_seq_id != 0 {
+            match split_at_keyframe(game_chunk) {
+              Ok((seq_id, keyframe, game_chunk)) => {
+                let handler = GameHandler::resume(
+                  self.services.clone(),
+                  game_id,
+                  game_chunk.approximate_arrival_timestamp,
+                  seq_id,
+                  &keyframe,
+                );
+                (handler, game_chunk)
+              }
+              Err(game_chunk) => {
+                if game_chunk.records.len() < 8 {
+                  tracing::debug!(
+                    game_id,
+                    "unexpected initial records: {:?}: {:?}",
+                    [game_chunk.min_seq_id, game_chunk.max_seq_id],
+                    game_chunk.records
+                  );
+                } else {
+                  tracing::debug!(
+                    game_id,
+                    "unexpected initial records: {:?}",
+                    [game_chunk.min_seq_id, game_chunk.max_seq_id]
+                  );
+                }
+                if self.slots.len() == self.slots.cap() {
+                  if let Some((game_id, mut removed)) = self.slots.pop_lru() {
+                    tracing::info!(game_id, "expired");
+                    self.snapshots.remove_game(game_id);
+                    Self::upload_archive(self.services.clone(), &mut removed, None);
+                  }
+                }
+                // picked up at the next keyframe
+                self.inactive_cache.put(game_id, ());
+                continue;
               }
             }
-            self.inactive_cache.put(game_id, ());
-            continue;
-          }
-          let mut handler = GameHandler::new(
-            self.services.clone(),
-            game_id,
-            game_chunk.approximate_arrival_timestamp,
-          );
+          } else {
+            let handler = GameHandler::new(
+              self.services.clone(),
+              game_id,
+              game_chunk.approximate_arrival_timestamp,
+            );
+            (handler, game_chunk)
+          };
 
           if let Err(err) = handler.handle_chunk(game_chunk, &mut self.snapshots) {
             tracing::error!(game_id, "handle initial records: {}", err);
@@ -110,7 +168,7 @@ impl Dispatcher {
               if let Some((game_id, mut removed)) = self.slots.pop_lru() {
                 tracing::info!(game_id, "expired");
                 self.snapshots.remove_game(game_id);
-                Self::upload_archive(self.services.clone(), &mut removed);
+                Self::upload_archive(self.services.clone(), &mut removed, None);
               }
             }
             self.slots.put(game_id, handler);
@@ -120,7 +178,7 @@ impl Dispatcher {
       }
       if should_remove {
         if let Some(mut removed) = self.slots.pop(&game_id) {
-          Self::upload_archive(self.services.clone(), &mut removed);
+          Self::upload_archive(self.services.clone(), &mut removed, None);
         }
         self.snapshots.remove_game(game_id);
       }
@@ -170,7 +228,11 @@ impl Dispatcher {
     }
   }
 
-  fn upload_archive(services: Services, handler: &mut GameHandler) {
+  fn upload_archive(
+    services: Services,
+    handler: &mut GameHandler,
+    finished: Option<Arc<FinishedGame>>,
+  ) {
     let archiver = if let Some(handle) = services.archiver.clone() {
       handle
     } else {
@@ -178,7 +240,8 @@ impl Dispatcher {
     };
     let game_id = handler.id();
     match handler.make_archive() {
-      Ok(Some(archive)) => {
+      Ok(Some(mut archive)) => {
+        archive.finished = finished;
         if !archiver.add_archive(archive) {
           tracing::warn!(game_id, "archive upload cancelled");
         }
@@ -209,10 +272,37 @@ impl Actor for Dispatcher {
         }
       }
     });
+
+    let addr = ctx.addr();
+    ctx.spawn(async move {
+      let mut interval =
+        tokio::time::interval(Duration::from_secs(*FLO_STATS_VIEWER_COUNT_INTERVAL_SECS));
+      interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+      loop {
+        interval.tick().await;
+        if addr.send(UpdateViewerCounts).await.is_err() {
+          break;
+        }
+      }
+    });
+
+    if let Some(period) = self.alerts.as_ref().map(|alerts| alerts.interval()) {
+      let addr = ctx.addr();
+      ctx.spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+          interval.tick().await;
+          if addr.send(EvaluateAlerts).await.is_err() {
+            break;
+          }
+        }
+      });
+    }
   }
 }
 
-pub struct AddIterator(pub DataStreamIterator);
+pub struct AddIterator(pub RecordSource);
 
 impl Message for AddIterator {
   type Result = ();
@@ -225,6 +315,7 @@ impl Handler<AddIterator> for Dispatcher {
   }
 }
 
+#[derive(Clone)]
 pub struct ListGames;
 
 impl Message for ListGames {
@@ -238,6 +329,7 @@ impl Handler<ListGames> for Dispatcher {
   }
 }
 
+#[derive(Clone)]
 pub struct GetGame {
   pub game_id: i32,
 }
@@ -257,6 +349,28 @@ impl Handler<GetGame> for Dispatcher {
   }
 }
 
+/// Batched `GetGame`, unknown game ids are left out
+#[derive(Clone)]
+pub struct GetGames {
+  pub game_ids: Vec<i32>,
+}
+
+impl Message for GetGames {
+  type Result = HashMap<i32, GameSnapshot>;
+}
+
+#[async_trait]
+impl Handler<GetGames> for Dispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GetGames { game_ids }: GetGames,
+  ) -> HashMap<i32, GameSnapshot> {
+    self.snapshots.get_snapshots(&game_ids)
+  }
+}
+
+#[derive(Clone)]
 pub struct GetGameInfo {
   pub game_id: i32,
 }
@@ -281,12 +395,204 @@ impl Handler<GetGameInfo> for Dispatcher {
   }
 }
 
+#[derive(Clone)]
+pub struct GetGameTimeline {
+  pub game_id: i32,
+}
+
+impl Message for GetGameTimeline {
+  type Result = Result<Vec<TimelineEvent>>;
+}
+
+#[async_trait]
+impl Handler<GetGameTimeline> for Dispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GetGameTimeline { game_id }: GetGameTimeline,
+  ) -> Result<Vec<TimelineEvent>> {
+    self
+      .slots
+      .peek(&game_id)
+      .map(|handler| handler.timeline().to_vec())
+      .ok_or_else(|| Error::GameNotFound(game_id))
+  }
+}
+
+#[derive(Clone)]
+pub struct ListFinishedGames {
+  pub after: Option<i32>,
+  pub limit: usize,
+}
+
+impl Message for ListFinishedGames {
+  type Result = FinishedGamePage;
+}
+
+#[async_trait]
+impl Handler<ListFinishedGames> for Dispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    ListFinishedGames { after, limit }: ListFinishedGames,
+  ) -> FinishedGamePage {
+    self.finished.page(after, limit)
+  }
+}
+
+/// Snapshot with stats of an in-memory game, without subscribing to updates
+#[derive(Clone)]
+pub struct GetGameWithStats {
+  pub game_id: i32,
+}
+
+impl Message for GetGameWithStats {
+  type Result = Result<GameSnapshotWithStats>;
+}
+
+#[async_trait]
+impl Handler<GetGameWithStats> for Dispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GetGameWithStats { game_id }: GetGameWithStats,
+  ) -> Result<GameSnapshotWithStats> {
+    self
+      .slots
+      .peek(&game_id)
+      .map(|handler| handler.make_snapshot_with_stats())
+      .ok_or_else(|| Error::GameNotFound(game_id))?
+  }
+}
+
+#[derive(Clone)]
+pub struct GetFinishedGame {
+  pub game_id: i32,
+}
+
+impl Message for GetFinishedGame {
+  type Result = Option<Arc<FinishedGame>>;
+}
+
+#[async_trait]
+impl Handler<GetFinishedGame> for Dispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GetFinishedGame { game_id }: GetFinishedGame,
+  ) -> Option<Arc<FinishedGame>> {
+    self.finished.get(game_id)
+  }
+}
+
+#[derive(Clone)]
+pub struct ListPlayerGames {
+  pub player_id: i32,
+  pub limit: usize,
+}
+
+impl Message for ListPlayerGames {
+  type Result = PlayerGames;
+}
+
+#[async_trait]
+impl Handler<ListPlayerGames> for Dispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    ListPlayerGames { player_id, limit }: ListPlayerGames,
+  ) -> PlayerGames {
+    PlayerGames {
+      live: self.snapshots.list_player_snapshots(player_id),
+      finished: self.finished.player_games(player_id, limit),
+    }
+  }
+}
+
+pub struct BackfillFinishedGames(pub Vec<FinishedGame>);
+
+impl Message for BackfillFinishedGames {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<BackfillFinishedGames> for Dispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    BackfillFinishedGames(games): BackfillFinishedGames,
+  ) {
+    let total = games.len();
+    for game in &games {
+      self.versions.record(game);
+    }
+    let inserted = self.finished.backfill(games);
+    tracing::info!("backfilled {}/{} finished games", inserted, total);
+  }
+}
+
+pub struct GetVersionDistribution {
+  pub days: usize,
+}
+
+impl Message for GetVersionDistribution {
+  type Result = Vec<VersionDistribution>;
+}
+
+#[async_trait]
+impl Handler<GetVersionDistribution> for Dispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    GetVersionDistribution { days }: GetVersionDistribution,
+  ) -> Vec<VersionDistribution> {
+    self.versions.distribution(days)
+  }
+}
+
+/// Game version days loaded from the archive bucket
+pub struct LoadVersionDays(pub Vec<(NaiveDate, VersionDay)>);
+
+impl Message for LoadVersionDays {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<LoadVersionDays> for Dispatcher {
+  async fn handle(&mut self, _: &mut Context<Self>, LoadVersionDays(days): LoadVersionDays) {
+    tracing::info!("loaded {} game version days", days.len());
+    self.versions.load(days);
+  }
+}
+
+/// Game version days changed since the last call, to be written to the archive bucket
+pub struct TakeDirtyVersionDays;
+
+impl Message for TakeDirtyVersionDays {
+  type Result = Vec<(NaiveDate, VersionDay)>;
+}
+
+#[async_trait]
+impl Handler<TakeDirtyVersionDays> for Dispatcher {
+  async fn handle(
+    &mut self,
+    _: &mut Context<Self>,
+    _: TakeDirtyVersionDays,
+  ) -> Vec<(NaiveDate, VersionDay)> {
+    self.versions.take_dirty()
+  }
+}
+
 pub struct SubscribeGameUpdate {
   pub game_id: i32,
+  pub mask: GameUpdateEventMask,
+  pub delta: bool,
+  /// The current version if `None`
+  pub schema_version: Option<u32>,
 }
 
 impl Message for SubscribeGameUpdate {
-  type Result = Result<(GameSnapshotWithStats, BroadcastReceiver<GameUpdateEvent>)>;
+  type Result = Result<(GameSnapshotWithStats, GameUpdateReceiver)>;
 }
 
 #[async_trait]
@@ -295,13 +601,19 @@ impl Handler<SubscribeGameUpdate> for Dispatcher {
     &mut self,
     _: &mut Context<Self>,
     msg: SubscribeGameUpdate,
-  ) -> Result<(GameSnapshotWithStats, BroadcastReceiver<GameUpdateEvent>)> {
+  ) -> Result<(GameSnapshotWithStats, GameUpdateReceiver)> {
+    let schema_version = schema::negotiate(msg.schema_version, msg.delta)?;
     let snapshot = self
       .slots
       .get(&msg.game_id)
       .map(|handler| handler.make_snapshot_with_stats())
       .ok_or_else(|| Error::GameNotFound(msg.game_id))??;
-    Ok((snapshot, self.snapshots.subscribe_game_updates(msg.game_id)))
+    Ok((
+      schema::convert_snapshot_with_stats(snapshot, schema_version),
+      self
+        .snapshots
+        .subscribe_game_updates(msg.game_id, msg.mask, msg.delta, schema_version),
+    ))
   }
 }
 
@@ -343,6 +655,9 @@ impl Handler<CreateGameStreamServer> for Dispatcher {
     }: CreateGameStreamServer,
   ) -> Result<GameStreamServer> {
     match self.slots.get(&game_id) {
+      Some(handler) if handler.resumed() => Err(Error::GameNotReady(
+        "resumed from keyframe, records are incomplete".to_string(),
+      )),
       Some(handler) => {
         let (snapshot, rx) =
           self
@@ -372,7 +687,7 @@ impl Handler<HandleChunk> for Dispatcher {
 
 struct FetchGameResult {
   game_id: i32,
-  result: Result<Game>,
+  result: Result<(Game, PlayerPrivacy)>,
 }
 
 impl Message for FetchGameResult {
@@ -412,6 +727,78 @@ impl Handler<StreamGc> for Dispatcher {
   }
 }
 
+struct UpdateViewerCounts;
+
+impl Message for UpdateViewerCounts {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<UpdateViewerCounts> for Dispatcher {
+  async fn handle(&mut self, _: &mut Context<Self>, _: UpdateViewerCounts) {
+    for game_id in self.snapshots.game_ids() {
+      let stream_viewers = self.streams.viewer_count(game_id);
+      self.snapshots.update_viewer_count(game_id, stream_viewers);
+    }
+  }
+}
+
+/// Game ownership changed, games that moved to other edges are dropped
+pub struct ClusterChanged;
+
+impl Message for ClusterChanged {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<ClusterChanged> for Dispatcher {
+  async fn handle(&mut self, _: &mut Context<Self>, _: ClusterChanged) {
+    let moved: Vec<i32> = self
+      .slots
+      .iter()
+      .map(|(game_id, _)| *game_id)
+      .filter(|game_id| !self.cluster.owns(*game_id))
+      .collect();
+    for game_id in moved {
+      tracing::info!(game_id, "moved to another edge");
+      self.slots.pop(&game_id);
+      self.snapshots.remove_game(game_id);
+    }
+  }
+}
+
+struct EvaluateAlerts;
+
+impl Message for EvaluateAlerts {
+  type Result = ();
+}
+
+#[async_trait]
+impl Handler<EvaluateAlerts> for Dispatcher {
+  async fn handle(&mut self, _: &mut Context<Self>, _: EvaluateAlerts) {
+    let alerts = if let Some(v) = self.alerts.as_mut() {
+      v
+    } else {
+      return;
+    };
+    let now = Utc::now();
+    let fired = alerts.eval(
+      self
+        .slots
+        .iter()
+        .map(|(_, handler)| AlertGameState::new(handler, now)),
+    );
+    alerts.fire(fired);
+  }
+}
+
+fn has_keyframe(game_chunk: &GameChunk) -> bool {
+  game_chunk
+    .records
+    .iter()
+    .any(|r| matches!(r, GameRecordData::Keyframe(_)))
+}
+
 #[tokio::test]
 async fn test_dispatcher() -> anyhow::Result<()> {
   use flo_kinesis::data_stream::DataStream;
@@ -422,10 +809,10 @@ async fn test_dispatcher() -> anyhow::Result<()> {
   flo_log_subscriber::init();
 
   let services = Services::from_env();
-  let d = Dispatcher::new(services).start();
+  let d = Dispatcher::new(services, None, Cluster::from_env()?).start();
   let ds = DataStream::from_env();
   let it = ShardIteratorType::at_timestamp_backward(Duration::from_secs(3600));
-  d.send(AddIterator(ds.into_iter(it).await?)).await?;
+  d.send(AddIterator(ds.into_iter(it).await?.into())).await?;
   futures::future::pending::<()>().await;
   Ok(())
 }
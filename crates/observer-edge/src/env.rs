@@ -13,12 +13,19 @@ pub struct Env {
   pub aws_s3_bucket: Option<String>,
   pub aws_access_key_id: Option<String>,
   pub aws_secret_access_key: Option<String>,
+  pub alert_rules_file: Option<String>,
+  pub alert_rules: Option<String>,
+  pub archive_cold_after_days: Option<i64>,
+  pub archive_cold_bucket: Option<String>,
+  pub archive_cold_storage_class: Option<String>,
+  pub archive_restore_days: Option<i64>,
+  pub archive_lifecycle_interval_secs: Option<u64>,
 }
 
 pub static ENV: Lazy<Env> = Lazy::new(|| {
   Env {
-    controller_url: env::var("CONTROLLER_URL").expect("env CONTROLLER_URL"),
-    controller_secret: env::var("CONTROLLER_SECRET").expect("env CONTROLLER_SECRET"),
+    controller_url: controller_env("CONTROLLER_URL"),
+    controller_secret: controller_env("CONTROLLER_SECRET"),
     record_source: std::env::var("OBSERVER_CONSUMER_SOURCE")
       .ok()
       .and_then(|v| v.parse().ok())
@@ -32,5 +39,27 @@ pub static ENV: Lazy<Env> = Lazy::new(|| {
     aws_s3_bucket: env::var("AWS_S3_BUCKET").ok(),
     aws_access_key_id: env::var("AWS_ACCESS_KEY_ID").ok(),
     aws_secret_access_key: env::var("AWS_SECRET_ACCESS_KEY").ok(),
+    alert_rules_file: env::var("FLO_ALERT_RULES_FILE").ok(),
+    alert_rules: env::var("FLO_ALERT_RULES").ok(),
+    archive_cold_after_days: env::var("FLO_ARCHIVE_COLD_AFTER_DAYS")
+      .ok()
+      .and_then(|v| v.parse().ok()),
+    archive_cold_bucket: env::var("FLO_ARCHIVE_COLD_BUCKET").ok(),
+    archive_cold_storage_class: env::var("FLO_ARCHIVE_COLD_STORAGE_CLASS").ok(),
+    archive_restore_days: env::var("FLO_ARCHIVE_RESTORE_DAYS")
+      .ok()
+      .and_then(|v| v.parse().ok()),
+    archive_lifecycle_interval_secs: env::var("FLO_ARCHIVE_LIFECYCLE_INTERVAL_SECS")
+      .ok()
+      .and_then(|v| v.parse().ok()),
   }
 });
+
+// Not used with synthetic records
+fn controller_env(name: &str) -> String {
+  #[cfg(feature = "synthetic")]
+  if crate::synthetic::SYNTHETIC.is_some() {
+    return env::var(name).unwrap_or_default();
+  }
+  env::var(name).unwrap_or_else(|_| panic!("env {}", name))
+}
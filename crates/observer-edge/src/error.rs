@@ -1,4 +1,6 @@
+use flo_task::SendError;
 use rusoto_core::RusotoError;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -29,14 +31,41 @@ pub enum Error {
   Io(#[from] std::io::Error),
   #[error("actor: {0}")]
   Actor(#[from] flo_state::error::Error),
+  #[error("actor did not respond within {timeout:?} ({attempts} attempts)")]
+  ActorTimeout { timeout: Duration, attempts: usize },
   #[error("proto: {0}")]
   Proto(#[from] s2_grpc_utils::result::Error),
   #[error("net: {0}")]
   Net(#[from] flo_net::error::Error),
   #[error("get archived object: {0}")]
   GetArchivedObject(#[from] RusotoError<rusoto_s3::GetObjectError>),
+  #[error("archive storage: {0}")]
+  ArchiveStorage(String),
   #[error("invalid S3 credentials: {0}")]
   InvalidS3Credentials(&'static str),
+  #[error("alert config: {0}")]
+  AlertConfig(String),
+  #[error("alert sink: {0}")]
+  AlertSink(String),
+  #[error("cluster config: {0}")]
+  ClusterConfig(String),
+  #[error("cluster peer {url}: status {status}")]
+  ClusterPeer { url: String, status: u16 },
+  #[error("http: {0}")]
+  Http(#[from] hyper::Error),
+  #[error("json: {0}")]
+  Json(#[from] serde_json::Error),
+  #[error("unsupported schema version: {0}")]
+  UnsupportedSchemaVersion(u32),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<SendError> for Error {
+  fn from(err: SendError) -> Self {
+    match err {
+      SendError::Timeout { timeout, attempts } => Self::ActorTimeout { timeout, attempts },
+      SendError::Actor(err) => Self::Actor(err),
+    }
+  }
+}
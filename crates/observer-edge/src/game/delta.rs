@@ -0,0 +1,303 @@
+//! Delta encoding of stats events, negotiated per game update subscription.
+//!
+//! Every `keyframe_interval`-th stats event of a kind is sent in full as a keyframe,
+//! events in between only carry the players and fields that changed since the previous
+//! event of the same kind, whose broadcast revision is sent as `base_revision`.
+
+use crate::game::event::{GameUpdateEvent, GameUpdateEventData};
+use crate::game::stats::{Action, Ping};
+use async_graphql::SimpleObject;
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PingStatsDelta {
+  /// Revision of the previous ping stats event this delta applies to
+  pub base_revision: u64,
+  pub time: u32,
+  pub data: Vec<PingDelta>,
+}
+
+/// Fields that didn't change are `null`
+#[derive(Debug, Clone, PartialEq, SimpleObject)]
+pub struct PingDelta {
+  pub player_id: i32,
+  pub min: Option<u16>,
+  pub max: Option<u16>,
+  pub avg: Option<f32>,
+  pub ticks: Option<u16>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct ActionStatsDelta {
+  /// Revision of the previous action stats event this delta applies to
+  pub base_revision: u64,
+  pub time: u32,
+  pub data: Vec<ActionDelta>,
+}
+
+/// Fields that didn't change are `null`
+#[derive(Debug, Clone, PartialEq, SimpleObject)]
+pub struct ActionDelta {
+  pub player_id: i32,
+  pub apm: Option<f32>,
+  pub total: Option<u32>,
+}
+
+pub struct GameUpdateDeltaEncoder {
+  keyframe_interval: u32,
+  ping: DeltaState<Ping>,
+  action: DeltaState<Action>,
+}
+
+impl GameUpdateDeltaEncoder {
+  pub fn new(keyframe_interval: u32) -> Self {
+    Self {
+      keyframe_interval,
+      ping: DeltaState::new(),
+      action: DeltaState::new(),
+    }
+  }
+
+  /// Events other than stats events are passed through
+  pub fn encode(&mut self, event: GameUpdateEvent) -> GameUpdateEvent {
+    let GameUpdateEvent {
+      game_id,
+      game_time_ms,
+      revision,
+      schema_version,
+      data,
+    } = event;
+    let data = match data {
+      GameUpdateEventData::PingStats(item) => {
+        match self.ping.push(revision, &item.data, self.keyframe_interval) {
+          Some((base_revision, data)) => GameUpdateEventData::PingStatsDelta(PingStatsDelta {
+            base_revision,
+            time: item.time,
+            data,
+          }),
+          None => GameUpdateEventData::PingStats(item),
+        }
+      }
+      GameUpdateEventData::ActionStats(item) => {
+        match self
+          .action
+          .push(revision, &item.data, self.keyframe_interval)
+        {
+          Some((base_revision, data)) => GameUpdateEventData::ActionStatsDelta(ActionStatsDelta {
+            base_revision,
+            time: item.time,
+            data,
+          }),
+          None => GameUpdateEventData::ActionStats(item),
+        }
+      }
+      data => data,
+    };
+    GameUpdateEvent {
+      game_id,
+      game_time_ms,
+      revision,
+      schema_version,
+      data,
+    }
+  }
+}
+
+trait DeltaItem: Clone {
+  type Delta;
+
+  fn player_id(&self) -> i32;
+  /// `None` if no field changed
+  fn diff(&self, prev: Option<&Self>) -> Option<Self::Delta>;
+}
+
+impl DeltaItem for Ping {
+  type Delta = PingDelta;
+
+  fn player_id(&self) -> i32 {
+    self.player_id
+  }
+
+  fn diff(&self, prev: Option<&Self>) -> Option<PingDelta> {
+    let delta = PingDelta {
+      player_id: self.player_id,
+      min: changed(prev.map(|v| v.min), self.min),
+      max: changed(prev.map(|v| v.max), self.max),
+      avg: changed(prev.map(|v| v.avg), self.avg),
+      ticks: changed(prev.map(|v| v.ticks), self.ticks),
+    };
+    if delta.min.is_none() && delta.max.is_none() && delta.avg.is_none() && delta.ticks.is_none() {
+      None
+    } else {
+      Some(delta)
+    }
+  }
+}
+
+impl DeltaItem for Action {
+  type Delta = ActionDelta;
+
+  fn player_id(&self) -> i32 {
+    self.player_id
+  }
+
+  fn diff(&self, prev: Option<&Self>) -> Option<ActionDelta> {
+    let delta = ActionDelta {
+      player_id: self.player_id,
+      apm: changed(prev.map(|v| v.apm), self.apm),
+      total: changed(prev.map(|v| v.total), self.total),
+    };
+    if delta.apm.is_none() && delta.total.is_none() {
+      None
+    } else {
+      Some(delta)
+    }
+  }
+}
+
+fn changed<T: PartialEq>(prev: Option<T>, value: T) -> Option<T> {
+  if prev.as_ref() == Some(&value) {
+    None
+  } else {
+    Some(value)
+  }
+}
+
+struct DeltaState<T> {
+  /// Revision of the last event sent to the subscriber, `None` before the first keyframe
+  revision: Option<u64>,
+  since_keyframe: u32,
+  items: Vec<T>,
+}
+
+impl<T: DeltaItem> DeltaState<T> {
+  fn new() -> Self {
+    Self {
+      revision: None,
+      since_keyframe: 0,
+      items: vec![],
+    }
+  }
+
+  /// Returns the base revision and the changes if the items should be sent as a delta
+  fn push(
+    &mut self,
+    revision: u64,
+    items: &[T],
+    keyframe_interval: u32,
+  ) -> Option<(u64, Vec<T::Delta>)> {
+    let base_revision = self.revision.replace(revision);
+    let prev = std::mem::replace(&mut self.items, items.to_vec());
+    match base_revision {
+      Some(base_revision) if self.since_keyframe + 1 < keyframe_interval => {
+        self.since_keyframe += 1;
+        let delta = items
+          .iter()
+          .filter_map(|item| {
+            item.diff(
+              prev
+                .iter()
+                .find(|prev| prev.player_id() == item.player_id()),
+            )
+          })
+          .collect();
+        Some((base_revision, delta))
+      }
+      _ => {
+        self.since_keyframe = 0;
+        None
+      }
+    }
+  }
+}
+
+#[test]
+fn test_delta_encoder() {
+  use crate::game::stats::{ActionStats, PingStats};
+
+  fn ping(player_id: i32, avg: f32) -> Ping {
+    Ping {
+      player_id,
+      min: 10,
+      max: 100,
+      avg,
+      ticks: 5,
+    }
+  }
+
+  fn ping_event(revision: u64, data: Vec<Ping>) -> GameUpdateEvent {
+    let mut event = GameUpdateEvent::ping_stats(1, revision as u32, PingStats { time: 0, data });
+    event.revision = revision;
+    event
+  }
+
+  let mut encoder = GameUpdateDeltaEncoder::new(3);
+
+  // first event is always a keyframe
+  let event = encoder.encode(ping_event(1, vec![ping(1, 50.), ping(2, 60.)]));
+  assert!(matches!(event.data, GameUpdateEventData::PingStats(_)));
+
+  let event = encoder.encode(ping_event(2, vec![ping(1, 50.), ping(2, 70.)]));
+  match event.data {
+    GameUpdateEventData::PingStatsDelta(delta) => {
+      assert_eq!(delta.base_revision, 1);
+      assert_eq!(
+        delta.data,
+        vec![PingDelta {
+          player_id: 2,
+          min: None,
+          max: None,
+          avg: Some(70.),
+          ticks: None,
+        }]
+      );
+    }
+    _ => panic!("expected delta"),
+  }
+
+  // other kinds don't move the ping base revision
+  let mut action = GameUpdateEvent::action_stats(
+    1,
+    ActionStats {
+      time: 3,
+      data: vec![Action {
+        player_id: 1,
+        apm: 100.,
+        total: 25,
+      }],
+    },
+  );
+  action.revision = 3;
+  let event = encoder.encode(action);
+  assert!(matches!(event.data, GameUpdateEventData::ActionStats(_)));
+
+  // new player is sent in full
+  let event = encoder.encode(ping_event(4, vec![ping(1, 50.), ping(2, 70.), ping(3, 1.)]));
+  match event.data {
+    GameUpdateEventData::PingStatsDelta(delta) => {
+      assert_eq!(delta.base_revision, 2);
+      assert_eq!(
+        delta.data,
+        vec![PingDelta {
+          player_id: 3,
+          min: Some(10),
+          max: Some(100),
+          avg: Some(1.),
+          ticks: Some(5),
+        }]
+      );
+    }
+    _ => panic!("expected delta"),
+  }
+
+  // keyframe interval reached
+  let event = encoder.encode(ping_event(5, vec![ping(1, 50.)]));
+  assert!(matches!(event.data, GameUpdateEventData::PingStats(_)));
+  assert_eq!(event.revision, 5);
+
+  // every event is a keyframe if the interval is 1
+  let mut encoder = GameUpdateDeltaEncoder::new(1);
+  for revision in 1..3 {
+    let event = encoder.encode(ping_event(revision, vec![ping(1, 50.)]));
+    assert!(matches!(event.data, GameUpdateEventData::PingStats(_)));
+  }
+}
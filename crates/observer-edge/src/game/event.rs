@@ -1,22 +1,33 @@
 use crate::game::{
+  delta::{ActionStatsDelta, PingStatsDelta},
+  schema::GAME_UPDATE_SCHEMA_VERSION,
   snapshot::GameSnapshot,
   stats::{ActionStats, PingStats},
   PlayerLeaveReason,
 };
-use async_graphql::{SimpleObject, Union};
+use async_graphql::{Enum, SimpleObject, Union};
 use chrono::{DateTime, Utc};
 use std::sync::Arc;
 
 #[derive(Clone, SimpleObject)]
 pub struct GameUpdateEvent {
   pub game_id: i32,
+  /// Approximate in-game time when the event happened
+  pub game_time_ms: u32,
+  /// Broadcast revision of the game, increases by one for every event sent to the subscribers
+  pub revision: u64,
+  /// Version of the payload shape, negotiated when subscribing
+  pub schema_version: u32,
   pub data: GameUpdateEventData,
 }
 
 impl GameUpdateEvent {
-  pub fn ended(game_id: i32, data: GameUpdateEventDataEnded) -> Self {
+  pub fn ended(game_id: i32, game_time_ms: u32, data: GameUpdateEventDataEnded) -> Self {
     GameUpdateEvent {
       game_id,
+      game_time_ms,
+      revision: 0,
+      schema_version: GAME_UPDATE_SCHEMA_VERSION,
       data: GameUpdateEventData::Ended(data),
     }
   }
@@ -24,15 +35,21 @@ impl GameUpdateEvent {
   pub fn removed(snapshot: GameSnapshot) -> Self {
     GameUpdateEvent {
       game_id: snapshot.id,
+      game_time_ms: snapshot.game_time_ms,
+      revision: 0,
+      schema_version: GAME_UPDATE_SCHEMA_VERSION,
       data: GameUpdateEventData::Removed(GameUpdateEventDataRemoved {
         snapshot: Arc::new(snapshot),
       }),
     }
   }
 
-  pub fn ping_stats(game_id: i32, item: PingStats) -> Self {
+  pub fn ping_stats(game_id: i32, game_time_ms: u32, item: PingStats) -> Self {
     GameUpdateEvent {
       game_id,
+      game_time_ms,
+      revision: 0,
+      schema_version: GAME_UPDATE_SCHEMA_VERSION,
       data: GameUpdateEventData::PingStats(item),
     }
   }
@@ -40,13 +57,29 @@ impl GameUpdateEvent {
   pub fn action_stats(game_id: i32, item: ActionStats) -> Self {
     GameUpdateEvent {
       game_id,
+      game_time_ms: item.time,
+      revision: 0,
+      schema_version: GAME_UPDATE_SCHEMA_VERSION,
       data: GameUpdateEventData::ActionStats(item),
     }
   }
 
+  pub fn viewer_count(game_id: i32, game_time_ms: u32, viewers: u32) -> Self {
+    GameUpdateEvent {
+      game_id,
+      game_time_ms,
+      revision: 0,
+      schema_version: GAME_UPDATE_SCHEMA_VERSION,
+      data: GameUpdateEventData::ViewerCount(GameUpdateEventDataViewerCount { viewers }),
+    }
+  }
+
   pub fn player_left(game_id: i32, time: u32, player_id: i32, reason: PlayerLeaveReason) -> Self {
     GameUpdateEvent {
       game_id,
+      game_time_ms: time,
+      revision: 0,
+      schema_version: GAME_UPDATE_SCHEMA_VERSION,
       data: GameUpdateEventData::PlayerLeft(GameUpdateEventDataPlayerLeft { time, player_id, reason }),
     }
   }
@@ -59,6 +92,72 @@ pub enum GameUpdateEventData {
   PingStats(PingStats),
   ActionStats(ActionStats),
   PlayerLeft(GameUpdateEventDataPlayerLeft),
+  ViewerCount(GameUpdateEventDataViewerCount),
+  /// Only sent to delta subscriptions
+  PingStatsDelta(PingStatsDelta),
+  /// Only sent to delta subscriptions
+  ActionStatsDelta(ActionStatsDelta),
+}
+
+impl GameUpdateEventData {
+  pub fn kind(&self) -> GameUpdateEventKind {
+    match self {
+      GameUpdateEventData::Ended(_) => GameUpdateEventKind::Ended,
+      GameUpdateEventData::Removed(_) => GameUpdateEventKind::Removed,
+      GameUpdateEventData::PingStats(_) => GameUpdateEventKind::PingStats,
+      GameUpdateEventData::ActionStats(_) => GameUpdateEventKind::ActionStats,
+      GameUpdateEventData::PlayerLeft(_) => GameUpdateEventKind::PlayerLeft,
+      GameUpdateEventData::ViewerCount(_) => GameUpdateEventKind::ViewerCount,
+      GameUpdateEventData::PingStatsDelta(_) => GameUpdateEventKind::PingStats,
+      GameUpdateEventData::ActionStatsDelta(_) => GameUpdateEventKind::ActionStats,
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Enum)]
+pub enum GameUpdateEventKind {
+  Ended,
+  Removed,
+  PingStats,
+  ActionStats,
+  PlayerLeft,
+  ViewerCount,
+}
+
+/// Set of event kinds a game update subscriber receives
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct GameUpdateEventMask(u8);
+
+impl GameUpdateEventMask {
+  pub const ALL: Self = Self(0b111111);
+  pub const NONE: Self = Self(0);
+
+  pub fn from_kinds<I>(kinds: I) -> Self
+  where
+    I: IntoIterator<Item = GameUpdateEventKind>,
+  {
+    kinds
+      .into_iter()
+      .fold(Self::NONE, |mask, kind| mask.union(Self::bit(kind)))
+  }
+
+  pub fn contains(&self, kind: GameUpdateEventKind) -> bool {
+    self.0 & Self::bit(kind).0 != 0
+  }
+
+  pub fn union(self, other: Self) -> Self {
+    Self(self.0 | other.0)
+  }
+
+  fn bit(kind: GameUpdateEventKind) -> Self {
+    Self(1 << kind as u8)
+  }
+}
+
+impl Default for GameUpdateEventMask {
+  fn default() -> Self {
+    Self::ALL
+  }
 }
 
 #[derive(Clone, SimpleObject)]
@@ -79,11 +178,18 @@ pub struct GameUpdateEventDataPlayerLeft {
   pub reason: PlayerLeaveReason,
 }
 
+/// Live observer streams and game update subscriptions of the game
+#[derive(Clone, SimpleObject)]
+pub struct GameUpdateEventDataViewerCount {
+  pub viewers: u32,
+}
+
 #[derive(Clone, Union)]
 pub enum GameListUpdateEvent {
   Added(GameListUpdateEventAdded),
   Ended(GameListUpdateEventEnded),
   Removed(GameListUpdateEventRemoved),
+  ViewerCount(GameListUpdateEventViewerCount),
 }
 
 #[derive(Clone, SimpleObject)]
@@ -102,6 +208,12 @@ pub struct GameListUpdateEventRemoved {
   pub game_id: i32,
 }
 
+#[derive(Clone, SimpleObject)]
+pub struct GameListUpdateEventViewerCount {
+  pub game_id: i32,
+  pub viewers: u32,
+}
+
 impl GameListUpdateEvent {
   pub fn add(snapshot: GameSnapshot) -> Self {
     Self::Added(GameListUpdateEventAdded {
@@ -116,4 +228,33 @@ impl GameListUpdateEvent {
   pub fn removed(game_id: i32) -> Self {
     Self::Removed(GameListUpdateEventRemoved { game_id })
   }
-}
\ No newline at end of file
+
+  pub fn viewer_count(game_id: i32, viewers: u32) -> Self {
+    Self::ViewerCount(GameListUpdateEventViewerCount { game_id, viewers })
+  }
+}
+#[test]
+fn test_game_update_event_mask() {
+  use GameUpdateEventKind::*;
+
+  let stats = GameUpdateEventMask::from_kinds(vec![PingStats, ActionStats]);
+  assert!(stats.contains(PingStats));
+  assert!(stats.contains(ActionStats));
+  assert!(!stats.contains(Ended));
+  assert!(!stats.contains(PlayerLeft));
+
+  let all = GameUpdateEventMask::from_kinds(vec![
+    Ended,
+    Removed,
+    PingStats,
+    ActionStats,
+    PlayerLeft,
+    ViewerCount,
+  ]);
+  assert_eq!(all, GameUpdateEventMask::ALL);
+  assert_eq!(stats.union(all), GameUpdateEventMask::ALL);
+  assert_eq!(
+    GameUpdateEventMask::from_kinds(vec![]),
+    GameUpdateEventMask::NONE
+  );
+}
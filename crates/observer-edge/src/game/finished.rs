@@ -0,0 +1,312 @@
+use super::snapshot::GameSnapshot;
+use super::stats::ActionStats;
+use super::{Game, GameMeta, PlayerLeaveReason, Race};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Final state of a game, captured when its `GameEnd` record arrives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinishedGame {
+  pub id: i32,
+  pub name: String,
+  pub map_name: String,
+  pub map_path: String,
+  pub node_name: String,
+  pub game_version: Option<String>,
+  pub started_at: DateTime<Utc>,
+  pub ended_at: DateTime<Utc>,
+  pub duration_ms: Option<u64>,
+  pub game_time_ms: u32,
+  /// Player names are replaced with slot numbers
+  #[serde(default)]
+  pub mask_player_names: bool,
+  pub players: Vec<FinishedGamePlayer>,
+  /// APM samples, one entry per collect interval
+  pub apm: Vec<ActionStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinishedGamePlayer {
+  pub player_id: i32,
+  pub name: String,
+  pub slot: usize,
+  pub team: i32,
+  pub race: Race,
+  /// In-game time the player left at, and why
+  pub left: Option<(u32, PlayerLeaveReason)>,
+  /// The player's profile is private, the game is left out of the player's games
+  #[serde(default)]
+  pub hidden: bool,
+}
+
+impl FinishedGame {
+  pub fn new(meta: &GameMeta, game: &Game, apm: Vec<ActionStats>) -> Option<Self> {
+    let ended_at = meta.ended_at?;
+    Some(Self {
+      id: game.id,
+      name: game.name.clone(),
+      map_name: game.map.name.clone(),
+      map_path: game.map.path.clone(),
+      node_name: game.node.name.clone(),
+      game_version: game.game_version.clone(),
+      started_at: meta.started_at,
+      ended_at,
+      duration_ms: meta.duration.map(|v| v.as_millis() as u64),
+      game_time_ms: meta.game_time_ms,
+      mask_player_names: game.mask_player_names,
+      players: game
+        .slots
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, slot)| {
+          let player = slot.player.as_ref()?;
+          Some(FinishedGamePlayer {
+            player_id: player.id,
+            name: game.player_name(&meta.privacy, idx, player),
+            slot: idx,
+            team: slot.settings.team,
+            race: slot.settings.race,
+            left: meta.player_left_reason_map.get(&player.id).cloned(),
+            hidden: meta.privacy.is_hidden(player.id),
+          })
+        })
+        .collect(),
+      apm,
+    })
+  }
+}
+
+/// Page of finished games ordered by game id
+#[derive(Debug, Clone)]
+pub struct FinishedGamePage {
+  pub games: Vec<Arc<FinishedGame>>,
+  /// Pass as `after` to get the next page, `None` if this is the last page
+  pub next_cursor: Option<i32>,
+}
+
+/// Recent games of a player
+#[derive(Debug, Clone)]
+pub struct PlayerGames {
+  pub live: Vec<GameSnapshot>,
+  /// Most recent first
+  pub finished: Vec<Arc<FinishedGame>>,
+}
+
+/// Finished games kept in memory for export, the oldest games are dropped first
+pub struct FinishedGameStore {
+  map: BTreeMap<i32, Arc<FinishedGame>>,
+  cap: usize,
+}
+
+impl FinishedGameStore {
+  pub fn new(cap: usize) -> Self {
+    Self {
+      map: BTreeMap::new(),
+      cap: std::cmp::max(cap, 1),
+    }
+  }
+
+  pub fn insert(&mut self, game: FinishedGame) -> Arc<FinishedGame> {
+    let game = Arc::new(game);
+    self.map.insert(game.id, game.clone());
+    self.truncate();
+    game
+  }
+
+  /// Adds games loaded from the archive index, games already in the store are kept.
+  /// Returns the number of added games.
+  pub fn backfill(&mut self, games: Vec<FinishedGame>) -> usize {
+    let mut count = 0;
+    for game in games {
+      if !self.map.contains_key(&game.id) {
+        self.map.insert(game.id, Arc::new(game));
+        count += 1;
+      }
+    }
+    self.truncate();
+    count
+  }
+
+  fn truncate(&mut self) {
+    while self.map.len() > self.cap {
+      let id = *self.map.keys().next().expect("map is not empty");
+      self.map.remove(&id);
+    }
+  }
+
+  pub fn get(&self, game_id: i32) -> Option<Arc<FinishedGame>> {
+    self.map.get(&game_id).cloned()
+  }
+
+  /// Most recent games of a player first,
+  /// games with masked player names and players with hidden profiles are left out
+  pub fn player_games(&self, player_id: i32, limit: usize) -> Vec<Arc<FinishedGame>> {
+    self
+      .map
+      .values()
+      .rev()
+      .filter(|g| {
+        !g.mask_player_names
+          && g
+            .players
+            .iter()
+            .any(|p| p.player_id == player_id && !p.hidden)
+      })
+      .take(limit)
+      .cloned()
+      .collect()
+  }
+
+  pub fn page(&self, after: Option<i32>, limit: usize) -> FinishedGamePage {
+    use std::ops::Bound;
+    let lower = match after {
+      Some(id) => Bound::Excluded(id),
+      None => Bound::Unbounded,
+    };
+    let mut iter = self.map.range((lower, Bound::Unbounded));
+    let games: Vec<_> = iter.by_ref().take(limit).map(|(_, v)| v.clone()).collect();
+    let next_cursor = if iter.next().is_some() {
+      games.last().map(|v| v.id)
+    } else {
+      None
+    };
+    FinishedGamePage { games, next_cursor }
+  }
+}
+
+#[test]
+fn test_finished_game_store_page() {
+  fn game(id: i32) -> FinishedGame {
+    FinishedGame {
+      id,
+      name: String::new(),
+      map_name: String::new(),
+      map_path: String::new(),
+      node_name: String::new(),
+      game_version: None,
+      started_at: Utc::now(),
+      ended_at: Utc::now(),
+      duration_ms: None,
+      game_time_ms: 0,
+      mask_player_names: false,
+      players: vec![],
+      apm: vec![],
+    }
+  }
+
+  let mut store = FinishedGameStore::new(4);
+  for id in [5, 1, 3, 2, 4] {
+    store.insert(game(id));
+  }
+
+  // game 1 is dropped
+  let page = store.page(None, 3);
+  assert_eq!(
+    page.games.iter().map(|g| g.id).collect::<Vec<_>>(),
+    [2, 3, 4]
+  );
+  assert_eq!(page.next_cursor, Some(4));
+
+  let page = store.page(page.next_cursor, 3);
+  assert_eq!(page.games.iter().map(|g| g.id).collect::<Vec<_>>(), [5]);
+  assert_eq!(page.next_cursor, None);
+}
+
+#[test]
+fn test_finished_game_store_player_games() {
+  fn game(id: i32, player_ids: &[i32], mask_player_names: bool) -> FinishedGame {
+    FinishedGame {
+      id,
+      name: String::new(),
+      map_name: String::new(),
+      map_path: String::new(),
+      node_name: String::new(),
+      game_version: None,
+      started_at: Utc::now(),
+      ended_at: Utc::now(),
+      duration_ms: None,
+      game_time_ms: 0,
+      mask_player_names,
+      players: player_ids
+        .iter()
+        .enumerate()
+        .map(|(slot, player_id)| FinishedGamePlayer {
+          player_id: *player_id,
+          name: String::new(),
+          slot,
+          team: slot as i32,
+          race: Race::Random,
+          left: None,
+          hidden: false,
+        })
+        .collect(),
+      apm: vec![],
+    }
+  }
+
+  let mut store = FinishedGameStore::new(10);
+  store.insert(game(1, &[7, 8], false));
+  store.insert(game(2, &[8, 9], false));
+  store.insert(game(3, &[7, 9], true));
+  store.insert(game(4, &[9, 7], false));
+  store.insert(game(5, &[7, 8], false));
+  let mut hidden = game(6, &[7, 8], false);
+  hidden.players[0].hidden = true;
+  store.insert(hidden);
+
+  let ids = |games: Vec<Arc<FinishedGame>>| games.iter().map(|g| g.id).collect::<Vec<_>>();
+  assert_eq!(ids(store.player_games(7, 10)), [5, 4, 1]);
+  assert_eq!(ids(store.player_games(8, 10)), [6, 5, 2, 1]);
+  assert_eq!(ids(store.player_games(7, 2)), [5, 4]);
+  assert_eq!(ids(store.player_games(1, 10)), Vec::<i32>::new());
+  assert_eq!(store.get(3).map(|g| g.id), Some(3));
+  assert!(store.get(6).is_none());
+}
+
+#[test]
+fn test_finished_game_store_backfill() {
+  fn game(id: i32, name: &str) -> FinishedGame {
+    FinishedGame {
+      id,
+      name: name.to_string(),
+      map_name: String::new(),
+      map_path: String::new(),
+      node_name: String::new(),
+      game_version: None,
+      started_at: Utc::now(),
+      ended_at: Utc::now(),
+      duration_ms: None,
+      game_time_ms: 0,
+      mask_player_names: false,
+      players: vec![],
+      apm: vec![],
+    }
+  }
+
+  let mut store = FinishedGameStore::new(3);
+  store.insert(game(3, "live"));
+  assert_eq!(
+    store.backfill(vec![
+      game(1, "archived"),
+      game(2, "archived"),
+      game(3, "archived")
+    ]),
+    2
+  );
+
+  let page = store.page(None, 10);
+  assert_eq!(
+    page
+      .games
+      .iter()
+      .map(|g| (g.id, g.name.as_str()))
+      .collect::<Vec<_>>(),
+    [(1, "archived"), (2, "archived"), (3, "live")]
+  );
+
+  let json = serde_json::to_string(&*page.games[0]).unwrap();
+  let decoded: FinishedGame = serde_json::from_str(&json).unwrap();
+  assert_eq!(decoded.id, 1);
+}
@@ -1,10 +1,17 @@
+pub mod delta;
 pub mod event;
+pub mod finished;
+pub mod schema;
 pub mod snapshot;
 pub mod stats;
 pub mod stream;
+pub mod timeline;
+pub mod versions;
 
+use self::finished::FinishedGame;
 use self::snapshot::{GameSnapshot, GameSnapshotMap, GameSnapshotWithStats};
 use self::stats::GameStats;
+use self::timeline::{TimelineEvent, TimelineEventKind};
 use crate::archiver::{Md5Writer, ArchiveInfo};
 use crate::error::{Error, Result};
 use crate::services::Services;
@@ -14,12 +21,13 @@ use chrono::{DateTime, TimeZone, Utc};
 use flate2::write::GzEncoder;
 use flo_kinesis::iterator::GameChunk;
 use flo_net::observer::GameInfo;
-use flo_observer::record::{GameRecordData, RTTStats};
+use flo_observer::record::{GameRecordData, Keyframe, RTTStats};
 use flo_w3gs::action::PlayerAction;
 use flo_w3gs::protocol;
 use flo_w3gs::protocol::constants::PacketTypeId;
 use s2_grpc_utils::{S2ProtoEnum, S2ProtoUnpack};
-use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Write;
 use std::time::Duration;
 use tracing::Span;
@@ -34,11 +42,73 @@ pub struct GameHandler {
   records: Vec<GameRecordData>,
   archive: Option<GzEncoder<Md5Writer<Vec<u8>>>>,
   record_encode_buf: BytesMut,
+  resumed: bool,
   span: Span,
 }
 
 impl GameHandler {
   pub fn new(services: Services, game_id: i32, initial_arrival_time: f64) -> Self {
+    Self::create(services, game_id, initial_arrival_time, true)
+  }
+
+  /// Picks up a game whose beginning was missed, e.g. after a restart, from the keyframe
+  /// with record id `seq_id`. Earlier records are unknown, so the game can't be streamed
+  /// to observers or archived
+  pub fn resume(
+    services: Services,
+    game_id: i32,
+    arrival_time: f64,
+    seq_id: u32,
+    keyframe: &Keyframe,
+  ) -> Self {
+    let initial_arrival_time = arrival_time - (keyframe.time as f64) / 1000.;
+    let mut handler = Self::create(services, game_id, initial_arrival_time, false);
+    let time = keyframe.time;
+    handler.resumed = true;
+    handler.next_record_id = seq_id + 1;
+    handler.meta.game_time_ms = time;
+    handler
+      .meta
+      .timeline
+      .push(TimelineEvent::new(time, TimelineEventKind::Resumed));
+
+    let lagging: Vec<i32> = keyframe
+      .players
+      .iter()
+      .filter(|p| p.lagging && !p.left)
+      .map(|p| p.player_id)
+      .collect();
+    if !lagging.is_empty() {
+      handler.meta.timeline.push(TimelineEvent::lag(
+        time,
+        TimelineEventKind::LagStarted,
+        lagging,
+      ));
+    }
+
+    // leave reasons are not part of keyframes
+    if let FetchGameState::Loading { ref mut deferred } = handler.game {
+      for p in keyframe
+        .players
+        .iter()
+        .filter(|p| p.left && p.slot_player_id != 0)
+      {
+        deferred.push(DeferredOp::PushPlayerLeft {
+          time,
+          slot: (p.slot_player_id - 1) as usize,
+          reason: PlayerLeaveReason::LeaveUnknown,
+        });
+      }
+    }
+
+    handler.span.in_scope(|| {
+      tracing::info!(seq_id, "resumed from keyframe: game time = {}ms", time);
+    });
+
+    handler
+  }
+
+  fn create(services: Services, game_id: i32, initial_arrival_time: f64, archive: bool) -> Self {
     let span = tracing::info_span!("game", game_id);
     let meta = GameMeta {
       id: game_id,
@@ -46,13 +116,16 @@ impl GameHandler {
       ended_at: None,
       duration: None,
       player_left_reason_map: BTreeMap::new(),
+      privacy: PlayerPrivacy::default(),
+      game_time_ms: 0,
+      timeline: vec![TimelineEvent::new(0, TimelineEventKind::Started)],
     };
 
     span.in_scope(|| {
       tracing::info!("started at: {}", meta.started_at);
     });
 
-    let archive = if services.archiver.is_some() {
+    let archive = if archive && services.archiver.is_some() {
       let mut archive = GzEncoder::new(Md5Writer::new(vec![]), flate2::Compression::best());
       let header = flo_observer_fs::FileHeader::new(meta.id);
       if let Err(err) = archive.write_all(&header.bytes()) {
@@ -77,6 +150,7 @@ impl GameHandler {
       records: vec![],
       archive,
       record_encode_buf: BytesMut::new(),
+      resumed: false,
       span,
     }
   }
@@ -89,9 +163,19 @@ impl GameHandler {
     self.initial_arrival_time
   }
 
-  pub fn set_fetch_result(&mut self, result: Result<Game>, snapshot_map: &mut GameSnapshotMap) {
+  /// The game was picked up from a keyframe, see `resume`
+  pub fn resumed(&self) -> bool {
+    self.resumed
+  }
+
+  pub fn set_fetch_result(
+    &mut self,
+    result: Result<(Game, PlayerPrivacy)>,
+    snapshot_map: &mut GameSnapshotMap,
+  ) {
     match result {
-      Ok(game) => {
+      Ok((game, privacy)) => {
+        self.meta.privacy = privacy;
         let game_id = game.id;
         let mut stats = GameStats::new(&game);
 
@@ -103,8 +187,8 @@ impl GameHandler {
                   snapshot_map.insert_game_action_stats(game_id, item);
                 }
               }
-              DeferredOp::PushRTTStats(item) => {
-                snapshot_map.insert_game_rtt_stats(game_id, stats.put_rtt(item));
+              DeferredOp::PushRTTStats(game_time_ms, item) => {
+                snapshot_map.insert_game_rtt_stats(game_id, game_time_ms, stats.put_rtt(item));
               }
               DeferredOp::PushPlayerLeft { time, slot, reason } => {
                 insert_game_player_left(&game, &mut self.meta, snapshot_map, time, slot, reason);
@@ -140,6 +224,18 @@ impl GameHandler {
     }
   }
 
+  /// Returns `None` if the game hasn't ended
+  pub fn make_finished_game(&self) -> Result<Option<FinishedGame>> {
+    match self.game {
+      FetchGameState::Loading { .. } => Err(Error::GameNotReady("still loading".to_string())),
+      FetchGameState::Loaded {
+        ref game,
+        ref stats,
+      } => Ok(FinishedGame::new(&self.meta, game, stats.actions().to_vec())),
+      FetchGameState::Failed(ref e) => Err(Error::GameNotReady(e.to_string())),
+    }
+  }
+
   pub fn make_game_info(&self) -> Result<(GameMeta, GameInfo)> {
     use flo_net::observer::{Map, PlayerInfo, Slot, SlotSettings};
     let game = self.game.get()?;
@@ -162,11 +258,7 @@ impl GameHandler {
           .map(|(idx, slot)| Slot {
             player: slot.player.as_ref().map(|v| PlayerInfo {
               id: v.id,
-              name: if game.mask_player_names {
-                format!("Player {}", idx + 1)
-              } else {
-                v.name.clone()
-              },
+              name: game.player_name(&self.meta.privacy, idx, v),
             }),
             settings: {
               let mut msg = SlotSettings {
@@ -206,6 +298,7 @@ impl GameHandler {
       game_id: self.meta.id,
       data: Bytes::from(bytes),
       md5,
+      finished: None,
     }))
   }
 
@@ -245,6 +338,18 @@ impl GameHandler {
     Ok(())
   }
 
+  pub fn meta(&self) -> &GameMeta {
+    &self.meta
+  }
+
+  pub fn last_arrival_timestamp(&self) -> Option<f64> {
+    self.last_arrival_timestamp
+  }
+
+  pub fn timeline(&self) -> &[TimelineEvent] {
+    &self.meta.timeline
+  }
+
   pub fn records(&self) -> &[GameRecordData] {
     &self.records
   }
@@ -255,6 +360,7 @@ impl GameHandler {
     records: Vec<GameRecordData>,
     snapshot_map: &mut GameSnapshotMap,
   ) -> Result<()> {
+    let game_time_ms = self.meta.game_time_ms;
     for record in records {
       if let Some(archive) = self.archive.as_mut() {
         self.record_encode_buf.clear();
@@ -265,6 +371,7 @@ impl GameHandler {
         GameRecordData::W3GS(ref packet) => match packet.type_id() {
           PacketTypeId::IncomingAction | PacketTypeId::IncomingAction2 => {
             let payload: protocol::action::TimeSlot = packet.decode_payload_bytes()?;
+            self.meta.game_time_ms += payload.time_increment_ms as u32;
             self.game.put_actions(
               self.meta.id,
               payload.time_increment_ms,
@@ -292,7 +399,7 @@ impl GameHandler {
 
             if payload.player_id != 0 {
               self.game.push_player_left(
-                self.game.time(),
+                self.meta.game_time_ms,
                 (payload.player_id - 1) as usize,
                 reason,
                 &mut self.meta,
@@ -310,13 +417,26 @@ impl GameHandler {
           }
           _ => {}
         },
-        GameRecordData::StartLag(_) => {}
-        GameRecordData::StopLag(_) => {}
+        GameRecordData::StartLag(ref player_ids) => {
+          self.meta.timeline.push(TimelineEvent::lag(
+            self.meta.game_time_ms,
+            TimelineEventKind::LagStarted,
+            player_ids.clone(),
+          ));
+        }
+        GameRecordData::StopLag(player_id) => {
+          self.meta.timeline.push(TimelineEvent::lag(
+            self.meta.game_time_ms,
+            TimelineEventKind::LagStopped,
+            vec![player_id],
+          ));
+        }
         GameRecordData::GameEnd => {
           let ended_at = Utc.timestamp_millis((approx_arrival_time * 1000.) as i64);
           let duration = ended_at.signed_duration_since(self.meta.started_at);
           self.meta.ended_at.replace(ended_at);
           self.meta.duration = duration.to_std().ok();
+          self.meta.timeline.push(TimelineEvent::new(self.meta.game_time_ms, TimelineEventKind::Ended));
           self.span.in_scope(|| {
             tracing::info!("ended at: {:?}, duration: {}", ended_at, duration);
           });
@@ -333,17 +453,51 @@ impl GameHandler {
         }
         GameRecordData::TickChecksum { .. } => {}
         GameRecordData::RTTStats(stats) => {
-          self.game.put_rtt(self.meta.id, stats, snapshot_map)?;
+          self.game.put_rtt(self.meta.id, self.meta.game_time_ms, stats, snapshot_map)?;
           continue;
         }
+        // only used to pick up games, see `resume`
+        GameRecordData::Keyframe(_) => continue,
       }
 
       self.records.push(record);
     }
+    if self.meta.game_time_ms != game_time_ms {
+      snapshot_map.update_game_time(self.meta.id, self.meta.game_time_ms);
+    }
     Ok(())
   }
 }
 
+/// Splits the first chunk received for a game that started earlier at its last keyframe.
+/// Returns the keyframe record id, the keyframe and the records after it
+pub fn split_at_keyframe(mut chunk: GameChunk) -> Result<(u32, Keyframe, GameChunk), GameChunk> {
+  let idx = match chunk
+    .records
+    .iter()
+    .rposition(|r| matches!(r, GameRecordData::Keyframe(_)))
+  {
+    Some(idx) => idx,
+    None => return Err(chunk),
+  };
+  let records = chunk.records.split_off(idx + 1);
+  let keyframe = match chunk.records.pop() {
+    Some(GameRecordData::Keyframe(keyframe)) => keyframe,
+    _ => unreachable!(),
+  };
+  let seq_id = chunk.min_seq_id + idx as u32;
+  Ok((
+    seq_id,
+    keyframe,
+    GameChunk {
+      approximate_arrival_timestamp: chunk.approximate_arrival_timestamp,
+      min_seq_id: seq_id + 1,
+      max_seq_id: chunk.max_seq_id,
+      records,
+    },
+  ))
+}
+
 // There was bug causes the `GameEnd` records have record id 0
 fn is_delayed_game_end_record(records: &GameChunk) -> bool {
   if records.min_seq_id == 0 && records.records.len() == 1 {
@@ -361,6 +515,10 @@ pub struct GameMeta {
   pub ended_at: Option<DateTime<Utc>>,
   pub duration: Option<Duration>,
   pub player_left_reason_map: BTreeMap<i32, (u32, PlayerLeaveReason)>,
+  pub privacy: PlayerPrivacy,
+  /// Approximate in-game clock, sum of the time increments of all received time slots
+  pub game_time_ms: u32,
+  pub timeline: Vec<TimelineEvent>,
 }
 
 enum FetchGameState {
@@ -382,14 +540,6 @@ impl FetchGameState {
     }
   }
 
-  fn time(&self) -> u32 {
-    match self {
-      FetchGameState::Loading { .. } => 0,
-      FetchGameState::Loaded { ref stats, .. } => stats.time(),
-      FetchGameState::Failed(_) => 0,
-    }
-  }
-
   fn put_actions(
     &mut self,
     id: i32,
@@ -412,14 +562,14 @@ impl FetchGameState {
     }
   }
 
-  fn put_rtt(&mut self, id: i32, item: RTTStats, snapshot_map: &mut GameSnapshotMap) -> Result<()> {
+  fn put_rtt(&mut self, id: i32, game_time_ms: u32, item: RTTStats, snapshot_map: &mut GameSnapshotMap) -> Result<()> {
     match self {
       FetchGameState::Loading { ref mut deferred } => {
-        deferred.push(DeferredOp::PushRTTStats(item));
+        deferred.push(DeferredOp::PushRTTStats(game_time_ms, item));
         Ok(())
       }
       FetchGameState::Loaded { ref mut stats, .. } => {
-        snapshot_map.insert_game_rtt_stats(id, stats.put_rtt(item));
+        snapshot_map.insert_game_rtt_stats(id, game_time_ms, stats.put_rtt(item));
         Ok(())
       }
       FetchGameState::Failed(ref e) => Err(Error::GameNotReady(e.to_string())),
@@ -465,6 +615,9 @@ fn insert_game_player_left(
     meta
       .player_left_reason_map
       .insert(player_id, (time, reason));
+    // deferred until the game info is fetched, keep the timeline ordered
+    let idx = meta.timeline.partition_point(|e| e.game_time_ms <= time);
+    meta.timeline.insert(idx, TimelineEvent::player_left(time, player_id, reason));
     snapshot_map.insert_game_player_left(meta.id, time, player_id, reason);
   } else {
     tracing::error!(game_id, "invalid left player slot: {}", slot);
@@ -473,7 +626,7 @@ fn insert_game_player_left(
 
 enum DeferredOp {
   PushAction(u16, Vec<PlayerAction>),
-  PushRTTStats(RTTStats),
+  PushRTTStats(u32, RTTStats),
   PushPlayerLeft {
     time: u32,
     slot: usize,
@@ -494,6 +647,50 @@ pub struct Game {
   pub mask_player_names: bool,
 }
 
+impl Game {
+  /// Name shown to observers and in the stats
+  pub fn player_name(&self, privacy: &PlayerPrivacy, slot: usize, player: &Player) -> String {
+    if self.mask_player_names || privacy.anonymized.contains(&player.id) {
+      format!("Player {}", slot + 1)
+    } else {
+      player.name.clone()
+    }
+  }
+}
+
+/// Privacy settings of the players of a game, sent by the controller as `GetGame` reply metadata
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerPrivacy {
+  pub anonymized: BTreeSet<i32>,
+  /// Left out of player game lists
+  pub hidden: BTreeSet<i32>,
+}
+
+impl PlayerPrivacy {
+  pub fn from_metadata(metadata: &tonic::metadata::MetadataMap) -> Self {
+    let parse = |key: &str| -> BTreeSet<i32> {
+      metadata
+        .get(key)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+          v.split(',')
+            .filter_map(|id| id.trim().parse().ok())
+            .collect()
+        })
+        .unwrap_or_default()
+    };
+    Self {
+      anonymized: parse(flo_constants::GRPC_METADATA_ANONYMIZED_PLAYERS),
+      hidden: parse(flo_constants::GRPC_METADATA_HIDDEN_PLAYERS),
+    }
+  }
+
+  /// Anonymized players are hidden too, their games would give them away
+  pub fn is_hidden(&self, player_id: i32) -> bool {
+    self.hidden.contains(&player_id) || self.anonymized.contains(&player_id)
+  }
+}
+
 #[derive(Debug, S2ProtoUnpack, SimpleObject)]
 #[s2_grpc(message_type = "flo_grpc::game::Map")]
 pub struct Map {
@@ -537,7 +734,7 @@ pub struct Player {
   pub name: String,
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, S2ProtoEnum, Enum)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, S2ProtoEnum, Enum, Serialize, Deserialize)]
 #[s2_grpc(proto_enum_type(flo_grpc::game::Race, flo_net::proto::flo_common::Race))]
 pub enum Race {
   Human,
@@ -547,7 +744,7 @@ pub enum Race {
   Random,
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Enum)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Enum, Serialize, Deserialize)]
 pub enum PlayerLeaveReason {
   LeaveDisconnect,
   LeaveLost,
@@ -571,3 +768,27 @@ impl From<protocol::leave::LeaveReason> for PlayerLeaveReason {
     }
   }
 }
+
+#[test]
+fn test_player_privacy_from_metadata() {
+  let mut metadata = tonic::metadata::MetadataMap::new();
+  metadata.insert(
+    flo_constants::GRPC_METADATA_ANONYMIZED_PLAYERS,
+    "1,2".parse().unwrap(),
+  );
+  metadata.insert(
+    flo_constants::GRPC_METADATA_HIDDEN_PLAYERS,
+    "3".parse().unwrap(),
+  );
+  let privacy = PlayerPrivacy::from_metadata(&metadata);
+  assert_eq!(
+    privacy.anonymized.iter().cloned().collect::<Vec<_>>(),
+    [1, 2]
+  );
+  assert!(privacy.is_hidden(1));
+  assert!(privacy.is_hidden(3));
+  assert!(!privacy.is_hidden(4));
+
+  let privacy = PlayerPrivacy::from_metadata(&tonic::metadata::MetadataMap::new());
+  assert_eq!(privacy, PlayerPrivacy::default());
+}
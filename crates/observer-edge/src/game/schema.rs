@@ -0,0 +1,160 @@
+//! Schema versions of game update events and snapshots.
+//!
+//! Subscribers pick a version when subscribing and receive events converted to it,
+//! so payload changes don't break consumers that haven't been updated yet.
+//!
+//! - 1: `Ended`, `Removed`, `PingStats`, `ActionStats` and `PlayerLeft` events
+//! - 2: adds `ViewerCount` events, delta encoded stats events, and snapshot viewer counts
+
+use super::event::{GameUpdateEvent, GameUpdateEventData};
+use super::snapshot::{GameSnapshot, GameSnapshotWithStats};
+use crate::error::{Error, Result};
+
+pub const GAME_UPDATE_SCHEMA_VERSION: u32 = 2;
+/// Oldest version still emitted
+pub const MIN_GAME_UPDATE_SCHEMA_VERSION: u32 = 1;
+
+/// Version of payloads written before versions were introduced
+pub(crate) fn unversioned() -> u32 {
+  1
+}
+
+/// Schema version of a subscription, the current version if not requested
+pub fn negotiate(requested: Option<u32>, delta: bool) -> Result<u32> {
+  let version = requested.unwrap_or(GAME_UPDATE_SCHEMA_VERSION);
+  if !(MIN_GAME_UPDATE_SCHEMA_VERSION..=GAME_UPDATE_SCHEMA_VERSION).contains(&version) {
+    return Err(Error::UnsupportedSchemaVersion(version));
+  }
+  if delta && version < 2 {
+    return Err(Error::UnsupportedSchemaVersion(version));
+  }
+  Ok(version)
+}
+
+/// Returns `None` if the event doesn't exist in `version`
+pub fn convert_event(mut event: GameUpdateEvent, version: u32) -> Option<GameUpdateEvent> {
+  if version >= event.schema_version {
+    return Some(event);
+  }
+  if version < 2 {
+    match event.data {
+      GameUpdateEventData::ViewerCount(_)
+      | GameUpdateEventData::PingStatsDelta(_)
+      | GameUpdateEventData::ActionStatsDelta(_) => return None,
+      _ => {}
+    }
+    if let GameUpdateEventData::Removed(ref mut data) = event.data {
+      data.snapshot = std::sync::Arc::new(convert_snapshot((*data.snapshot).clone(), version));
+    }
+  }
+  event.schema_version = version;
+  Some(event)
+}
+
+pub fn convert_snapshot(mut snapshot: GameSnapshot, version: u32) -> GameSnapshot {
+  if version >= snapshot.schema_version {
+    return snapshot;
+  }
+  if version < 2 {
+    snapshot.viewers = 0;
+  }
+  snapshot.schema_version = version;
+  snapshot
+}
+
+pub fn convert_snapshot_with_stats(
+  snapshot: GameSnapshotWithStats,
+  version: u32,
+) -> GameSnapshotWithStats {
+  GameSnapshotWithStats {
+    game: convert_snapshot(snapshot.game, version),
+    ..snapshot
+  }
+}
+
+#[test]
+fn test_negotiate() {
+  assert_eq!(negotiate(None, true).unwrap(), GAME_UPDATE_SCHEMA_VERSION);
+  assert_eq!(negotiate(Some(1), false).unwrap(), 1);
+  assert!(matches!(
+    negotiate(Some(1), true),
+    Err(Error::UnsupportedSchemaVersion(1))
+  ));
+  assert!(negotiate(Some(0), false).is_err());
+  assert!(negotiate(Some(GAME_UPDATE_SCHEMA_VERSION + 1), false).is_err());
+}
+
+#[test]
+fn test_convert_event() {
+  use super::event::GameUpdateEventDataEnded;
+  use super::stats::PingStats;
+  use chrono::Utc;
+
+  let event = GameUpdateEvent::viewer_count(1, 1000, 5);
+  assert_eq!(event.schema_version, GAME_UPDATE_SCHEMA_VERSION);
+  assert!(convert_event(event.clone(), 1).is_none());
+  assert_eq!(convert_event(event, 2).unwrap().schema_version, 2);
+
+  let event = convert_event(
+    GameUpdateEvent::ping_stats(
+      1,
+      1000,
+      PingStats {
+        time: 1000,
+        data: vec![],
+      },
+    ),
+    1,
+  )
+  .unwrap();
+  assert_eq!(event.schema_version, 1);
+  assert!(matches!(event.data, GameUpdateEventData::PingStats(_)));
+
+  let event = convert_event(
+    GameUpdateEvent::ended(
+      1,
+      1000,
+      GameUpdateEventDataEnded {
+        ended_at: Utc::now(),
+        duration_millis: 1000,
+      },
+    ),
+    1,
+  )
+  .unwrap();
+  assert!(matches!(event.data, GameUpdateEventData::Ended(_)));
+}
+
+#[test]
+fn test_convert_snapshot() {
+  let snapshot: GameSnapshot = serde_json::from_value(serde_json::json!({
+    "id": 1,
+    "game_name": "game",
+    "map_name": "map",
+    "map_path": "maps/map.w3x",
+    "map_sha1": [],
+    "map_checksum": 0,
+    "node_id": 1,
+    "node_name": "node",
+    "started_at": "2021-01-01T00:00:00Z",
+    "ended_at": null,
+    "game_time_ms": 0,
+    "players": [],
+    "random_seed": 0,
+    "game_version": null,
+    "mask_player_names": false,
+    "viewers": 3,
+  }))
+  .unwrap();
+  // snapshots of peers without schema versions
+  assert_eq!(snapshot.schema_version, 1);
+
+  let snapshot = GameSnapshot {
+    schema_version: GAME_UPDATE_SCHEMA_VERSION,
+    ..snapshot
+  };
+  let converted = convert_snapshot(snapshot.clone(), 1);
+  assert_eq!(converted.schema_version, 1);
+  assert_eq!(converted.viewers, 0);
+  assert_eq!(convert_snapshot(snapshot, 2).viewers, 3);
+}
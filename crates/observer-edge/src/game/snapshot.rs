@@ -1,16 +1,21 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use chrono::{DateTime, Utc};
 use async_graphql::SimpleObject;
+use serde::{Deserialize, Serialize};
 use super::stats::{PingStats, ActionStats, GameStatsSnapshot};
 use super::event::*;
+use super::delta::GameUpdateDeltaEncoder;
+use super::schema::{self, GAME_UPDATE_SCHEMA_VERSION};
 use super::{GameMeta, PlayerLeaveReason};
 use super::{Race, Game};
 use crate::error::{Result, Error};
+use crate::constants::FLO_STATS_DELTA_KEYFRAME_INTERVAL;
 use crate::broadcast::{BroadcastSender, BroadcastReceiver};
+use tokio_stream::{Stream, StreamExt};
 
 pub struct GameSnapshotMap {
   map: BTreeMap<i32, GameSnapshot>,
-  tx_map_game_update: BTreeMap<i32, BroadcastSender<GameUpdateEvent>>,
+  tx_map_game_update: BTreeMap<i32, GameUpdateSender>,
   tx_list: Option<BroadcastSender<GameListUpdateEvent>>,
 }
 
@@ -27,10 +32,23 @@ impl GameSnapshotMap {
     self.map.get(&game_id).cloned().ok_or_else(|| Error::GameNotFound(game_id))
   }
 
+  /// Unknown game ids are left out
+  pub fn get_snapshots(&self, game_ids: &[i32]) -> HashMap<i32, GameSnapshot> {
+    game_ids.iter().filter_map(|id| self.map.get(id).map(|v| (*id, v.clone()))).collect()
+  }
+
   pub fn list_snapshots(&self) -> Vec<GameSnapshot> {
     self.map.values().cloned().collect()
   }
 
+  /// Games of a player that haven't ended,
+  /// games with masked player names and players with hidden profiles are left out
+  pub fn list_player_snapshots(&self, player_id: i32) -> Vec<GameSnapshot> {
+    self.map.values().filter(|g| {
+      g.ended_at.is_none() && !g.mask_player_names && g.players.iter().any(|p| p.id == player_id && !p.hidden)
+    }).cloned().collect()
+  }
+
   pub fn insert_game(&mut self, snapshot: GameSnapshot) {
     self.send_game_list_update_event(|| GameListUpdateEvent::add(snapshot.clone()));
     self.map.insert(snapshot.id, snapshot);
@@ -40,52 +58,88 @@ impl GameSnapshotMap {
     if let (Some(ended_at), Some(duration)) = (meta.ended_at.clone(), meta.duration.clone()) {
       if let Some(g) = self.map.get_mut(&meta.id) {
         g.ended_at = Some(ended_at);
+        g.game_time_ms = meta.game_time_ms;
       }
       self.send_game_list_update_event(|| GameListUpdateEvent::ended(meta.id, ended_at));
-      self.send_game_update_event(meta.id, || GameUpdateEvent::ended(meta.id, GameUpdateEventDataEnded {
+      self.send_game_update_event(meta.id, GameUpdateEventKind::Ended, || GameUpdateEvent::ended(meta.id, meta.game_time_ms, GameUpdateEventDataEnded {
         ended_at,
         duration_millis: duration.as_millis() as i64,
       }))
     }
   }
 
+  pub fn update_game_time(&mut self, game_id: i32, game_time_ms: u32) {
+    if let Some(g) = self.map.get_mut(&game_id) {
+      g.game_time_ms = game_time_ms;
+    }
+  }
+
   pub fn remove_game(&mut self, game_id: i32) {
     self.send_game_list_update_event(|| GameListUpdateEvent::removed(game_id));
     self.tx_map_game_update.remove(&game_id);
     if let Some(snapshot) = self.map.remove(&game_id) {
-      self.send_game_update_event(game_id, || {
+      self.send_game_update_event(game_id, GameUpdateEventKind::Removed, || {
         GameUpdateEvent::removed(snapshot)
       })
     }
   }
 
-  pub fn insert_game_rtt_stats(&mut self, game_id: i32, item: PingStats) {
-    self.send_game_update_event(game_id, || {
-      GameUpdateEvent::ping_stats(game_id, item)
+  pub fn insert_game_rtt_stats(&mut self, game_id: i32, game_time_ms: u32, item: PingStats) {
+    self.send_game_update_event(game_id, GameUpdateEventKind::PingStats, || {
+      GameUpdateEvent::ping_stats(game_id, game_time_ms, item)
     })
   }
 
   pub fn insert_game_action_stats(&mut self, game_id: i32, item: ActionStats) {
-    self.send_game_update_event(game_id, || {
+    self.send_game_update_event(game_id, GameUpdateEventKind::ActionStats, || {
       GameUpdateEvent::action_stats(game_id, item)
     })
   }
 
+  /// `stream_viewers`: connected stream servers of the game.
+  /// Subscribers are told if the count changed
+  pub fn update_viewer_count(&mut self, game_id: i32, stream_viewers: usize) {
+    let subscriptions = self.tx_map_game_update.get(&game_id).map(|sender| sender.tx.receiver_count()).unwrap_or(0);
+    let viewers = (stream_viewers + subscriptions) as u32;
+    let game_time_ms = match self.map.get_mut(&game_id) {
+      Some(g) if g.viewers != viewers => {
+        g.viewers = viewers;
+        g.game_time_ms
+      },
+      _ => return,
+    };
+    self.send_game_list_update_event(|| GameListUpdateEvent::viewer_count(game_id, viewers));
+    self.send_game_update_event(game_id, GameUpdateEventKind::ViewerCount, || {
+      GameUpdateEvent::viewer_count(game_id, game_time_ms, viewers)
+    })
+  }
+
+  pub fn game_ids(&self) -> Vec<i32> {
+    self.map.keys().cloned().collect()
+  }
+
   pub fn insert_game_player_left(&mut self, game_id: i32, time: u32, player_id: i32, reason: PlayerLeaveReason) {
-    self.send_game_update_event(game_id, || {
+    self.send_game_update_event(game_id, GameUpdateEventKind::PlayerLeft, || {
       GameUpdateEvent::player_left(game_id, time, player_id, reason)
     })
   }
 
-  pub fn subscribe_game_updates(&mut self, game_id: i32) -> BroadcastReceiver<GameUpdateEvent> {
-    match self.tx_map_game_update.get(&game_id).map(|tx| tx.subscribe()) {
-      Some(rx) => rx,
+  /// Events not in `mask` are dropped before they reach the subscriber,
+  /// and not built at all if no subscriber of the game wants them.
+  /// Stats events are delta encoded for the subscriber if `delta` is set
+  pub fn subscribe_game_updates(&mut self, game_id: i32, mask: GameUpdateEventMask, delta: bool, schema_version: u32) -> GameUpdateReceiver {
+    let rx = match self.tx_map_game_update.get_mut(&game_id) {
+      Some(sender) => {
+        sender.mask = sender.mask.union(mask);
+        sender.tx.subscribe()
+      },
       None => {
         let (tx, rx) = BroadcastSender::channel();
-        self.tx_map_game_update.insert(game_id, tx);
+        self.tx_map_game_update.insert(game_id, GameUpdateSender { tx, mask, revision: 0 });
         rx
       },
-    }
+    };
+    GameUpdateReceiver { rx, mask, delta, schema_version }
   }
 
   pub fn subscribe_game_list_updates(&mut self) -> BroadcastReceiver<GameListUpdateEvent> {
@@ -112,12 +166,19 @@ impl GameSnapshotMap {
     }
   }
 
-  fn send_game_update_event<F>(&mut self, game_id: i32, f: F) 
+  fn send_game_update_event<F>(&mut self, game_id: i32, kind: GameUpdateEventKind, f: F) 
   where F: FnOnce() -> GameUpdateEvent
   {
     let mut should_remove_tx = false;
-    if let Some(tx) = self.tx_map_game_update.get(&game_id) {
-      should_remove_tx = !tx.send(f());
+    if let Some(sender) = self.tx_map_game_update.get_mut(&game_id) {
+      if sender.mask.contains(kind) {
+        sender.revision += 1;
+        let mut event = f();
+        event.revision = sender.revision;
+        should_remove_tx = !sender.tx.send(event);
+      } else {
+        should_remove_tx = sender.tx.is_closed();
+      }
     }
     if should_remove_tx {
       self.tx_map_game_update.remove(&game_id);
@@ -126,7 +187,40 @@ impl GameSnapshotMap {
   }
 }
 
-#[derive(Debug, Clone, SimpleObject)]
+struct GameUpdateSender {
+  tx: BroadcastSender<GameUpdateEvent>,
+  /// Union of all subscriber masks since the channel was created
+  mask: GameUpdateEventMask,
+  revision: u64,
+}
+
+pub struct GameUpdateReceiver {
+  rx: BroadcastReceiver<GameUpdateEvent>,
+  mask: GameUpdateEventMask,
+  delta: bool,
+  schema_version: u32,
+}
+
+impl GameUpdateReceiver {
+  pub fn into_stream(self) -> impl Stream<Item = GameUpdateEvent> {
+    let mask = self.mask;
+    let schema_version = self.schema_version;
+    let mut encoder = if self.delta {
+      Some(GameUpdateDeltaEncoder::new(*FLO_STATS_DELTA_KEYFRAME_INTERVAL))
+    } else {
+      None
+    };
+    self.rx.into_stream()
+      .filter(move |event| mask.contains(event.data.kind()))
+      .map(move |event| match encoder.as_mut() {
+        Some(encoder) => encoder.encode(event),
+        None => event,
+      })
+      .filter_map(move |event| schema::convert_event(event, schema_version))
+  }
+}
+
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
 pub struct GameSnapshot {
   pub id: i32,
   pub game_name: String,
@@ -138,10 +232,18 @@ pub struct GameSnapshot {
   pub node_name: String,
   pub started_at: DateTime<Utc>,
   pub ended_at: Option<DateTime<Utc>>,
+  pub game_time_ms: u32,
   pub players: Vec<Player>,
   pub random_seed: i32,
   pub game_version: Option<String>,
   pub mask_player_names: bool,
+  /// Live observer streams and game update subscriptions,
+  /// refreshed every `FLO_STATS_VIEWER_COUNT_INTERVAL_SECS`
+  #[serde(default)]
+  pub viewers: u32,
+  /// Version of the snapshot shape, see `schema`
+  #[serde(default = "schema::unversioned")]
+  pub schema_version: u32,
 }
 
 impl GameSnapshot {
@@ -151,15 +253,12 @@ impl GameSnapshot {
         let left = meta.player_left_reason_map.get(&player.id);
         Some(Player {
           id: player.id,
-          name: if game.mask_player_names {
-            format!("Player {}", i + 1)
-          } else {
-            player.name.clone()
-          },
+          name: game.player_name(&meta.privacy, i, player),
           race: slot.settings.race,
           team: slot.settings.team,
           left_at: left.as_ref().map(|(time, _)| *time),
           leave_reason: left.as_ref().map(|(_, reason)| *reason),
+          hidden: meta.privacy.is_hidden(player.id),
         })
       } else {
         None
@@ -176,15 +275,18 @@ impl GameSnapshot {
       node_name: game.node.name.clone(),
       started_at: meta.started_at.clone(),
       ended_at: meta.ended_at.clone(),
+      game_time_ms: meta.game_time_ms,
       players,
       random_seed: game.random_seed,
       game_version: game.game_version.clone(),
       mask_player_names: game.mask_player_names,
+      viewers: 0,
+      schema_version: GAME_UPDATE_SCHEMA_VERSION,
     }
   }
 }
 
-#[derive(Debug, Clone, SimpleObject)]
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
 pub struct Player {
   pub id: i32,
   pub name: String,
@@ -192,6 +294,9 @@ pub struct Player {
   pub team: i32,
   pub left_at: Option<u32>,
   pub leave_reason: Option<PlayerLeaveReason>,
+  #[graphql(skip)]
+  #[serde(default)]
+  pub hidden: bool,
 }
 
 #[derive(Debug, Clone, SimpleObject)]
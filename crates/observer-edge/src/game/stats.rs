@@ -1,4 +1,5 @@
 use async_graphql::{SimpleObject};
+use serde::{Deserialize, Serialize};
 use flo_observer::record;
 use flo_w3gs::protocol::action::PlayerAction;
 
@@ -57,6 +58,10 @@ impl GameStats {
     }
   }
 
+  pub fn actions(&self) -> &[ActionStats] {
+    &self.action
+  }
+
   pub fn make_snapshot(&self) -> GameStatsSnapshot {
     GameStatsSnapshot {
       ping: self.ping.clone(),
@@ -99,13 +104,13 @@ impl ApmCollect {
 
   fn try_collect(&mut self, now: u32, actions: &[PlayerAction]) -> Option<ActionStats> {
     for action in actions {
-      if let Some(action_id) = action.peek_action_id() {
-        // Only track certain action types
-        // https://github.com/PBug90/w3gjs/blob/14582c54f78f304993c23ac04e38db59d2fe960e/src/Player.ts#L326
-        if [0x17, 0x18, 0x1C, 0x1D, 0x66, 0x67, 0x1E, 0x61].contains(&action_id) {
-          if let Some(Some(ref mut item)) = self.player_slots.get_mut(action.player_id.saturating_sub(1) as usize) {
-            item.total += 1;
-          }
+      // Stop at the first action that can't be decoded, the rest of the block is unreadable
+      let count = action.actions().take_while(|v| v.is_ok()).filter(|v| {
+        matches!(v, Ok(v) if v.is_apm_action())
+      }).count();
+      if count > 0 {
+        if let Some(Some(ref mut item)) = self.player_slots.get_mut(action.player_id.saturating_sub(1) as usize) {
+          item.total += count as u32;
         }
       }
     }
@@ -155,14 +160,14 @@ pub struct Ping {
   pub ticks: u16,
 }
 
-#[derive(Debug, Clone, SimpleObject)]
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
 pub struct ActionStats 
 {
   pub time: u32,
   pub data: Vec<Action>,
 }
 
-#[derive(Debug, Clone, SimpleObject)]
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
 pub struct Action {
   pub player_id: i32,
   pub apm: f32,
@@ -50,6 +50,15 @@ impl GameStreamMap {
     }
   }
 
+  /// Number of connected stream servers of the game
+  pub fn viewer_count(&self, game_id: i32) -> usize {
+    self
+      .map
+      .get(&game_id)
+      .map(|stream| stream.tx.receiver_count())
+      .unwrap_or(0)
+  }
+
   // Needs to be called regularly to clean up streams that have expired, that is streams have no active receiver.
   pub fn remove_all_disconnected(&mut self) {
     let map = std::mem::replace(&mut self.map, BTreeMap::new());
@@ -0,0 +1,51 @@
+use async_graphql::{Enum, SimpleObject};
+use super::PlayerLeaveReason;
+
+/// A significant game event, stamped with the reconstructed in-game time
+#[derive(Debug, Clone, SimpleObject)]
+pub struct TimelineEvent {
+  pub game_time_ms: u32,
+  pub kind: TimelineEventKind,
+  pub player_ids: Vec<i32>,
+  pub leave_reason: Option<PlayerLeaveReason>,
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Enum)]
+pub enum TimelineEventKind {
+  Started,
+  /// Stats were picked up from a keyframe, earlier events are unknown
+  Resumed,
+  PlayerLeft,
+  LagStarted,
+  LagStopped,
+  Ended,
+}
+
+impl TimelineEvent {
+  pub fn new(game_time_ms: u32, kind: TimelineEventKind) -> Self {
+    Self {
+      game_time_ms,
+      kind,
+      player_ids: vec![],
+      leave_reason: None,
+    }
+  }
+
+  pub fn player_left(game_time_ms: u32, player_id: i32, reason: PlayerLeaveReason) -> Self {
+    Self {
+      game_time_ms,
+      kind: TimelineEventKind::PlayerLeft,
+      player_ids: vec![player_id],
+      leave_reason: Some(reason),
+    }
+  }
+
+  pub fn lag(game_time_ms: u32, kind: TimelineEventKind, player_ids: Vec<i32>) -> Self {
+    Self {
+      game_time_ms,
+      kind,
+      player_ids,
+      leave_reason: None,
+    }
+  }
+}
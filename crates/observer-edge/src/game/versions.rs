@@ -0,0 +1,195 @@
+//! Per-day distribution of the game versions of finished games.
+//!
+//! Games are counted on the UTC day they started. Days with new games are written to the
+//! archive bucket as `stats/versions/{date}.json` and loaded back on startup,
+//! days keep the ids of their games so games recorded twice (e.g. by the startup backfill)
+//! are only counted once.
+
+use super::finished::FinishedGame;
+use async_graphql::SimpleObject;
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Games without a reported version are counted under this name
+pub const UNKNOWN_VERSION: &str = "unknown";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VersionDay {
+  pub games: BTreeMap<i32, VersionDayGame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionDayGame {
+  pub version: Option<String>,
+  pub players: u32,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct VersionDistribution {
+  pub date: NaiveDate,
+  pub total_games: u64,
+  /// Sorted by number of games, descending
+  pub versions: Vec<VersionCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, SimpleObject)]
+pub struct VersionCount {
+  pub version: String,
+  pub games: u64,
+  pub players: u64,
+}
+
+#[derive(Debug)]
+pub struct VersionStats {
+  retention_days: i64,
+  days: BTreeMap<NaiveDate, VersionDay>,
+  /// Days changed since the last `take_dirty`
+  dirty: BTreeSet<NaiveDate>,
+}
+
+impl VersionStats {
+  pub fn new(retention_days: i64) -> Self {
+    Self {
+      retention_days,
+      days: BTreeMap::new(),
+      dirty: BTreeSet::new(),
+    }
+  }
+
+  /// Returns `false` if the game was already recorded or started before the retention window
+  pub fn record(&mut self, game: &FinishedGame) -> bool {
+    let date = game.started_at.date().naive_utc();
+    if date < self.first_date() {
+      return false;
+    }
+    let day = self.days.entry(date).or_default();
+    if day.games.contains_key(&game.id) {
+      return false;
+    }
+    day.games.insert(
+      game.id,
+      VersionDayGame {
+        version: game.game_version.clone(),
+        players: game.players.len() as u32,
+      },
+    );
+    self.dirty.insert(date);
+    self.truncate();
+    true
+  }
+
+  /// Merges days loaded from storage, games recorded since startup are kept
+  pub fn load(&mut self, days: Vec<(NaiveDate, VersionDay)>) {
+    for (date, loaded) in days {
+      let day = self.days.entry(date).or_default();
+      let recorded = day.games.len();
+      for (game_id, game) in loaded.games {
+        day.games.entry(game_id).or_insert(game);
+      }
+      // the stored day is missing games recorded before the load completed
+      if recorded > 0 {
+        self.dirty.insert(date);
+      }
+    }
+    self.truncate();
+  }
+
+  pub fn take_dirty(&mut self) -> Vec<(NaiveDate, VersionDay)> {
+    std::mem::take(&mut self.dirty)
+      .into_iter()
+      .filter_map(|date| self.days.get(&date).map(|day| (date, day.clone())))
+      .collect()
+  }
+
+  /// Distribution of the last `days` days, most recent first
+  pub fn distribution(&self, days: usize) -> Vec<VersionDistribution> {
+    self
+      .days
+      .iter()
+      .rev()
+      .take(days)
+      .map(|(date, day)| {
+        let mut counts: BTreeMap<&str, VersionCount> = BTreeMap::new();
+        for game in day.games.values() {
+          let version = game.version.as_deref().unwrap_or(UNKNOWN_VERSION);
+          let count = counts.entry(version).or_insert_with(|| VersionCount {
+            version: version.to_string(),
+            games: 0,
+            players: 0,
+          });
+          count.games += 1;
+          count.players += game.players as u64;
+        }
+        let mut versions: Vec<VersionCount> = counts.into_values().collect();
+        versions.sort_by(|a, b| b.games.cmp(&a.games));
+        VersionDistribution {
+          date: *date,
+          total_games: day.games.len() as u64,
+          versions,
+        }
+      })
+      .collect()
+  }
+
+  fn first_date(&self) -> NaiveDate {
+    (Utc::now() - Duration::days(self.retention_days - 1))
+      .date()
+      .naive_utc()
+  }
+
+  fn truncate(&mut self) {
+    let first_date = self.first_date();
+    self.days = self.days.split_off(&first_date);
+    self.dirty = self.dirty.split_off(&first_date);
+  }
+}
+
+#[test]
+fn test_version_stats() {
+  let now = Utc::now();
+  let game = |id: i32, version: Option<&str>, days_ago: i64| FinishedGame {
+    id,
+    name: format!("game {}", id),
+    map_name: String::new(),
+    map_path: String::new(),
+    node_name: String::new(),
+    game_version: version.map(ToString::to_string),
+    started_at: now - Duration::days(days_ago),
+    ended_at: now - Duration::days(days_ago),
+    duration_ms: None,
+    game_time_ms: 0,
+    mask_player_names: false,
+    players: vec![],
+    apm: vec![],
+  };
+
+  let mut stats = VersionStats::new(30);
+  assert!(stats.record(&game(1, Some("1.26.0.6401"), 0)));
+  assert!(stats.record(&game(2, Some("1.33.0.19378"), 0)));
+  assert!(stats.record(&game(3, Some("1.33.0.19378"), 0)));
+  assert!(stats.record(&game(4, None, 1)));
+  assert!(!stats.record(&game(3, Some("1.33.0.19378"), 0)));
+  assert!(!stats.record(&game(5, Some("1.26.0.6401"), 30)));
+
+  let dirty = stats.take_dirty();
+  assert_eq!(dirty.len(), 2);
+  assert!(stats.take_dirty().is_empty());
+
+  let distribution = stats.distribution(30);
+  assert_eq!(distribution.len(), 2);
+  assert_eq!(distribution[0].date, now.date().naive_utc());
+  assert_eq!(distribution[0].total_games, 3);
+  assert_eq!(distribution[0].versions[0].version, "1.33.0.19378");
+  assert_eq!(distribution[0].versions[0].games, 2);
+  assert_eq!(distribution[1].versions[0].version, UNKNOWN_VERSION);
+  assert_eq!(stats.distribution(1).len(), 1);
+
+  // stored days are merged with the games recorded since startup
+  let mut stats = VersionStats::new(30);
+  stats.record(&game(6, Some("1.26.0.6401"), 0));
+  stats.take_dirty();
+  stats.load(dirty);
+  assert_eq!(stats.distribution(1)[0].total_games, 4);
+  assert_eq!(stats.take_dirty().len(), 1);
+}
@@ -1,31 +1,52 @@
+mod alert;
 mod broadcast;
+pub mod cluster;
 mod constants;
 mod controller;
 mod dispatcher;
 mod env;
 mod error;
 pub mod game;
+pub mod loader;
 mod server;
 mod services;
+mod source;
+#[cfg(feature = "synthetic")]
+pub mod synthetic;
 mod version;
 mod archiver;
 
-use crate::archiver::Archiver;
+use crate::alert::AlertEngine;
+use crate::archiver::{Archiver, ArchiverHandle};
 use crate::broadcast::BroadcastReceiver;
+use crate::cluster::{Cluster, GameOwner, PeerStatus};
+use constants::DISPATCHER_QUERY_POLICY;
 use dispatcher::{
-  AddIterator, Dispatcher, GetGame, ListGames, SubscribeGameListUpdate, SubscribeGameUpdate,
+  AddIterator, BackfillFinishedGames, ClusterChanged, Dispatcher, GetFinishedGame, GetGame,
+  GetGameTimeline, GetGameWithStats, GetGames, GetVersionDistribution, ListFinishedGames,
+  ListGames, ListPlayerGames, LoadVersionDays, SubscribeGameListUpdate, SubscribeGameUpdate,
+  TakeDirtyVersionDays,
 };
+pub use error::Error;
 use error::Result;
 use flo_kinesis::{data_stream::DataStream, iterator::ShardIteratorType};
 use flo_state::{Actor, Addr, Owner};
-use game::event::{GameListUpdateEvent, GameUpdateEvent};
-use game::snapshot::{GameSnapshot, GameSnapshotWithStats};
+use flo_task::SendExt;
+use game::event::{GameListUpdateEvent, GameUpdateEventMask};
+use game::finished::{FinishedGame, FinishedGamePage, PlayerGames};
+use game::snapshot::{GameSnapshot, GameSnapshotWithStats, GameUpdateReceiver};
+use game::timeline::TimelineEvent;
+use game::versions::VersionDistribution;
 use server::StreamServer;
 use services::Services;
+use source::RecordSource;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub struct FloObserverEdge {
   dispatcher: Owner<Dispatcher>,
+  cluster: Cluster,
   stream_server: StreamServer,
   archiver: Option<Archiver>,
 }
@@ -41,20 +62,27 @@ impl FloObserverEdge {
       tracing::debug!("archiver disabled.");
       None
     };
-    let dispatcher = Dispatcher::new(services).start();
-
-    let data_stream = DataStream::from_env();
-    let iter_type = ShardIteratorType::at_timestamp_backward(Duration::from_secs(
-      crate::env::ENV.record_backscan_secs,
-    ));
+    let alerts = AlertEngine::from_env()?;
+    if alerts.is_some() {
+      tracing::debug!("alerting enabled.");
+    }
+    let cluster = Cluster::from_env()?;
+    let archiver_handle = services.archiver.clone();
+    let dispatcher = Dispatcher::new(services, alerts, cluster.clone()).start();
 
-    tracing::debug!("creating iterator...");
+    if cluster.enabled() {
+      tracing::debug!("cluster enabled.");
+      spawn_cluster_health_check(cluster.clone(), dispatcher.addr());
+    }
 
-    let iter = data_stream.into_iter(iter_type).await?;
+    if let Some(handle) = archiver_handle {
+      spawn_version_stats(handle.clone(), dispatcher.addr());
+      spawn_finished_backfill(handle, dispatcher.addr());
+    }
 
-    tracing::debug!("iterator created.");
+    let source = Self::create_record_source().await?;
 
-    dispatcher.send(AddIterator(iter)).await?;
+    dispatcher.send(AddIterator(source)).await?;
 
     tracing::debug!("iterator added.");
 
@@ -67,11 +95,33 @@ impl FloObserverEdge {
 
     Ok(Self {
       dispatcher,
+      cluster,
       stream_server,
       archiver,
     })
   }
 
+  async fn create_record_source() -> Result<RecordSource> {
+    #[cfg(feature = "synthetic")]
+    if let Some(config) = crate::synthetic::SYNTHETIC.as_ref() {
+      tracing::warn!("using synthetic records: {:?}", config);
+      return Ok(crate::synthetic::SyntheticSource::new(config.clone()).into());
+    }
+
+    let data_stream = DataStream::from_env();
+    let iter_type = ShardIteratorType::at_timestamp_backward(Duration::from_secs(
+      crate::env::ENV.record_backscan_secs,
+    ));
+
+    tracing::debug!("creating iterator...");
+
+    let iter = data_stream.into_iter(iter_type).await?;
+
+    tracing::debug!("iterator created.");
+
+    Ok(iter.into())
+  }
+
   pub async fn serve(self) -> Result<()> {
     if let Some(archiver) = self.archiver {
       tokio::pin! {
@@ -91,33 +141,219 @@ impl FloObserverEdge {
   }
 
   pub fn handle(&self) -> FloObserverEdgeHandle {
-    FloObserverEdgeHandle(self.dispatcher.addr())
+    FloObserverEdgeHandle {
+      addr: self.dispatcher.addr(),
+      cluster: self.cluster.clone(),
+    }
   }
 }
 
+// Loads recently finished games from the archive index without blocking startup
+fn spawn_finished_backfill(handle: ArchiverHandle, addr: Addr<Dispatcher>) {
+  let hours = *crate::constants::FLO_STATS_FINISHED_BACKFILL_HOURS;
+  if hours <= 0 {
+    return;
+  }
+  let since = chrono::Utc::now() - chrono::Duration::hours(hours);
+  tokio::spawn(async move {
+    match handle.recent_finished_games(since).await {
+      Ok(games) => {
+        if let Err(err) = addr.send(BackfillFinishedGames(games)).await {
+          tracing::error!("backfill finished games: {}", err);
+        }
+      }
+      Err(err) => tracing::error!("load recent finished games: {}", err),
+    }
+  });
+}
+
+// Loads stored game version days, then writes changed days periodically
+fn spawn_version_stats(handle: ArchiverHandle, addr: Addr<Dispatcher>) {
+  const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+  let retention_days = *crate::constants::FLO_STATS_VERSION_RETENTION_DAYS;
+  let since = (chrono::Utc::now() - chrono::Duration::days(retention_days - 1))
+    .date()
+    .naive_utc();
+  tokio::spawn(async move {
+    match handle.load_version_days(since).await {
+      Ok(days) => {
+        if addr.send(LoadVersionDays(days)).await.is_err() {
+          return;
+        }
+      }
+      Err(err) => tracing::error!("load game version days: {}", err),
+    }
+
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+      interval.tick().await;
+      let days = match addr.send(TakeDirtyVersionDays).await {
+        Ok(days) => days,
+        Err(_) => break,
+      };
+      for (date, day) in days {
+        if let Err(err) = handle.put_version_day(date, &day).await {
+          tracing::error!("store game version day {}: {}", date, err);
+        }
+      }
+    }
+  });
+}
+
+fn spawn_cluster_health_check(cluster: Cluster, addr: Addr<Dispatcher>) {
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(Duration::from_secs(
+      *crate::cluster::FLO_OBSERVER_EDGE_HEALTH_CHECK_SECS,
+    ));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+      interval.tick().await;
+      if cluster.check_peers().await && addr.send(ClusterChanged).await.is_err() {
+        break;
+      }
+    }
+  });
+}
+
 #[derive(Clone)]
-pub struct FloObserverEdgeHandle(Addr<Dispatcher>);
+pub struct FloObserverEdgeHandle {
+  addr: Addr<Dispatcher>,
+  cluster: Cluster,
+}
 
 impl FloObserverEdgeHandle {
   pub async fn list_games(&self) -> Result<Vec<GameSnapshot>> {
-    self.0.send(ListGames).await.map_err(Into::into)
+    self
+      .addr
+      .send_with_policy(*DISPATCHER_QUERY_POLICY, ListGames)
+      .await
+      .map_err(Into::into)
+  }
+
+  /// Edge that owns the game, always `GameOwner::Local` if clustering is disabled
+  pub fn game_owner(&self, game_id: i32) -> GameOwner {
+    self.cluster.owner(game_id)
+  }
+
+  pub fn cluster_status(&self) -> Vec<PeerStatus> {
+    self.cluster.peer_status()
+  }
+
+  /// Looks up the game on the edge that owns it, `get_game` only looks up games of this edge
+  pub async fn get_routed_game(&self, game_id: i32) -> Result<(GameSnapshot, GameOwner)> {
+    match self.cluster.owner(game_id) {
+      GameOwner::Local => Ok((self.get_game(game_id).await?, GameOwner::Local)),
+      GameOwner::Remote(peer) => {
+        let game = self.cluster.fetch_game(&peer, game_id).await?;
+        Ok((game, GameOwner::Remote(peer)))
+      }
+    }
   }
 
   pub async fn get_game(&self, game_id: i32) -> Result<GameSnapshot> {
-    let game = self.0.send(GetGame { game_id }).await??;
+    let game = self
+      .addr
+      .send_with_policy(*DISPATCHER_QUERY_POLICY, GetGame { game_id })
+      .await??;
     Ok(game)
   }
 
+  /// Games that are not found are left out,
+  /// use `loader::GameSnapshotLoader` to batch lookups from GraphQL resolvers
+  pub async fn get_games(&self, game_ids: Vec<i32>) -> Result<HashMap<i32, GameSnapshot>> {
+    self
+      .addr
+      .send_with_policy(*DISPATCHER_QUERY_POLICY, GetGames { game_ids })
+      .await
+      .map_err(Into::into)
+  }
+
+  pub async fn get_game_timeline(&self, game_id: i32) -> Result<Vec<TimelineEvent>> {
+    self
+      .addr
+      .send_with_policy(*DISPATCHER_QUERY_POLICY, GetGameTimeline { game_id })
+      .await?
+  }
+
+  /// Finished games with id greater than `after`, ordered by id
+  pub async fn list_finished_games(
+    &self,
+    after: Option<i32>,
+    limit: usize,
+  ) -> Result<FinishedGamePage> {
+    self
+      .addr
+      .send_with_policy(*DISPATCHER_QUERY_POLICY, ListFinishedGames { after, limit })
+      .await
+      .map_err(Into::into)
+  }
+
+  /// Current stats of an in-memory game, use `subscribe_game_updates` to follow them
+  pub async fn get_game_with_stats(&self, game_id: i32) -> Result<GameSnapshotWithStats> {
+    self
+      .addr
+      .send_with_policy(*DISPATCHER_QUERY_POLICY, GetGameWithStats { game_id })
+      .await?
+  }
+
+  /// Returns `None` if the game hasn't ended or was dropped from the finished game store
+  pub async fn get_finished_game(&self, game_id: i32) -> Result<Option<Arc<FinishedGame>>> {
+    self
+      .addr
+      .send_with_policy(*DISPATCHER_QUERY_POLICY, GetFinishedGame { game_id })
+      .await
+      .map_err(Into::into)
+  }
+
+  /// Live games and up to `limit` finished games of a player
+  pub async fn list_player_games(&self, player_id: i32, limit: usize) -> Result<PlayerGames> {
+    self
+      .addr
+      .send_with_policy(*DISPATCHER_QUERY_POLICY, ListPlayerGames { player_id, limit })
+      .await
+      .map_err(Into::into)
+  }
+
+  /// Game versions of finished games per day, most recent first
+  pub async fn get_version_distribution(&self, days: usize) -> Result<Vec<VersionDistribution>> {
+    self
+      .addr
+      .send_with_policy(*DISPATCHER_QUERY_POLICY, GetVersionDistribution { days })
+      .await
+      .map_err(Into::into)
+  }
+
   pub async fn subscribe_game_list_updates(
     &self,
   ) -> Result<(Vec<GameSnapshot>, BroadcastReceiver<GameListUpdateEvent>)> {
-    self.0.send(SubscribeGameListUpdate).await?
+    self
+      .addr
+      .send_within(DISPATCHER_QUERY_POLICY.timeout, SubscribeGameListUpdate)
+      .await?
   }
 
+  /// Only events in `mask` are sent to the subscriber,
+  /// stats events are delta encoded if `delta` is set.
+  /// The snapshot and events are converted to `schema_version`, the current version if `None`
   pub async fn subscribe_game_updates(
     &self,
     game_id: i32,
-  ) -> Result<(GameSnapshotWithStats, BroadcastReceiver<GameUpdateEvent>)> {
-    self.0.send(SubscribeGameUpdate { game_id }).await?
+    mask: GameUpdateEventMask,
+    delta: bool,
+    schema_version: Option<u32>,
+  ) -> Result<(GameSnapshotWithStats, GameUpdateReceiver)> {
+    self
+      .addr
+      .send_within(
+        DISPATCHER_QUERY_POLICY.timeout,
+        SubscribeGameUpdate {
+          game_id,
+          mask,
+          delta,
+          schema_version,
+        },
+      )
+      .await?
   }
 }
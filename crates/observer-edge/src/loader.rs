@@ -0,0 +1,30 @@
+use crate::error::Error;
+use crate::game::snapshot::GameSnapshot;
+use crate::FloObserverEdgeHandle;
+use async_graphql::dataloader::{DataLoader, Loader};
+use flo_state::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Loads game snapshots for GraphQL resolvers.
+///
+/// Lookups made while resolving a query are collected into batches,
+/// each batch is a single `GetGames` dispatcher message.
+pub struct GameSnapshotLoader(FloObserverEdgeHandle);
+
+impl GameSnapshotLoader {
+  /// Create one per query, loaded snapshots are cached by the returned loader
+  pub fn new(handle: FloObserverEdgeHandle) -> DataLoader<Self> {
+    DataLoader::new(Self(handle), tokio::spawn)
+  }
+}
+
+#[async_trait]
+impl Loader<i32> for GameSnapshotLoader {
+  type Value = GameSnapshot;
+  type Error = Arc<Error>;
+
+  async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, GameSnapshot>, Self::Error> {
+    self.0.get_games(keys.to_vec()).await.map_err(Arc::new)
+  }
+}
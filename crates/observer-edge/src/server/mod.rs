@@ -1,12 +1,14 @@
 pub mod peer;
 mod send_queue;
 
+use crate::constants::DISPATCHER_QUERY_POLICY;
 use crate::dispatcher::{CreateGameStreamServer, GetGameInfo};
 use crate::error::Error;
 use crate::error::Result;
 use crate::Dispatcher;
 use flo_net::{listener::FloListener, observer::ObserverConnectRejectReason, stream::FloStream};
 use flo_state::Addr;
+use flo_task::SendExt;
 use std::time::SystemTime;
 use tokio_stream::StreamExt;
 
@@ -56,10 +58,13 @@ impl Handler {
 
     let server = self
       .dispatcher
-      .send(CreateGameStreamServer {
-        game_id: accepted.game_id,
-        delay_secs: accepted.delay_secs,
-      })
+      .send_within(
+        DISPATCHER_QUERY_POLICY.timeout,
+        CreateGameStreamServer {
+          game_id: accepted.game_id,
+          delay_secs: accepted.delay_secs,
+        },
+      )
       .await??;
 
     server.run(self.transport).await?;
@@ -81,9 +86,12 @@ impl Handler {
     };
     let (meta, game) = match self
       .dispatcher
-      .send(GetGameInfo {
-        game_id: token.game_id,
-      })
+      .send_with_policy(
+        *DISPATCHER_QUERY_POLICY,
+        GetGameInfo {
+          game_id: token.game_id,
+        },
+      )
       .await?
     {
       Ok(game) => game,
@@ -8,6 +8,13 @@ pub struct Services {
 
 impl Services {
   pub fn from_env() -> Self {
+    #[cfg(feature = "synthetic")]
+    if let Some(config) = crate::synthetic::SYNTHETIC.as_ref() {
+      return Self {
+        controller: Controller::synthetic(config),
+        archiver: None,
+      };
+    }
     Self {
       controller: Controller::from_env(),
       archiver: None,
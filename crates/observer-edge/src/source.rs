@@ -0,0 +1,38 @@
+use flo_kinesis::data_stream::DataStreamIterator;
+use flo_kinesis::iterator::Chunk;
+use futures::stream::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Where the dispatcher reads game records from
+pub enum RecordSource {
+  Kinesis(DataStreamIterator),
+  /// Fabricated games for local development, see `crate::synthetic`
+  #[cfg(feature = "synthetic")]
+  Synthetic(futures::stream::BoxStream<'static, Chunk>),
+}
+
+impl From<DataStreamIterator> for RecordSource {
+  fn from(iter: DataStreamIterator) -> Self {
+    Self::Kinesis(iter)
+  }
+}
+
+#[cfg(feature = "synthetic")]
+impl From<crate::synthetic::SyntheticSource> for RecordSource {
+  fn from(source: crate::synthetic::SyntheticSource) -> Self {
+    Self::Synthetic(source.into_stream())
+  }
+}
+
+impl Stream for RecordSource {
+  type Item = Chunk;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    match self.get_mut() {
+      RecordSource::Kinesis(iter) => Pin::new(iter).poll_next(cx),
+      #[cfg(feature = "synthetic")]
+      RecordSource::Synthetic(stream) => stream.as_mut().poll_next(cx),
+    }
+  }
+}
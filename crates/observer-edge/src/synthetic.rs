@@ -0,0 +1,494 @@
+//! Fabricated game record streams for local development and load tests.
+//!
+//! Built with the `synthetic` feature and enabled by `FLO_OBSERVER_SYNTHETIC_GAMES`,
+//! the edge then reads records from `SyntheticSource` instead of the Kinesis stream
+//! and game details from the config instead of the controller.
+//! Records of a game only depend on the config and the game id, runs with the same
+//! config produce the same games.
+
+use crate::game::{Game, Map, Node, Player, Race, Slot, SlotSettings};
+use chrono::Utc;
+use flo_kinesis::iterator::{Chunk, GameChunk};
+use flo_observer::record::{GameRecordData, Keyframe, KeyframePlayer, RTTStats, RTTStatsItem};
+use flo_w3gs::protocol::action::{IncomingAction, PlayerAction, TimeSlot};
+use flo_w3gs::protocol::leave::{LeaveReason, PlayerLeft};
+use flo_w3gs::protocol::packet::Packet;
+use futures::stream::{BoxStream, StreamExt};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// `None` if synthetic records are disabled
+pub static SYNTHETIC: Lazy<Option<SyntheticConfig>> = Lazy::new(SyntheticConfig::from_env);
+
+/// Game time of a chunk, records arrive about once a second from the Kinesis stream too
+const CHUNK_MS: u32 = 1000;
+const TIME_SLOT_MS: u32 = 100;
+const RTT_STATS_INTERVAL_MS: u32 = 15_000;
+const KEYFRAME_INTERVAL_MS: u32 = 30_000;
+/// Players are picked from a shared pool, so player game lists fill up
+const PLAYER_POOL: u64 = 64;
+const GAME_VERSION: &str = "1.36.1.21015";
+
+#[derive(Debug, Clone)]
+pub struct SyntheticConfig {
+  /// Games running at the same time, a finished game is replaced by a new one
+  pub games: usize,
+  /// Stops after this many games, runs forever if `None`
+  pub total_games: Option<usize>,
+  /// Average game duration, each game is up to 25% shorter or longer
+  pub duration: Duration,
+  /// Average actions per minute of a player, each player is up to 30% slower or faster
+  pub apm: u32,
+  /// Players per game, split into 2 teams
+  pub players: usize,
+  pub seed: u64,
+  /// Game time per second, 0 sends chunks as fast as the dispatcher takes them
+  pub speed: f64,
+  pub first_game_id: i32,
+}
+
+impl Default for SyntheticConfig {
+  fn default() -> Self {
+    Self {
+      games: 1,
+      total_games: None,
+      duration: Duration::from_secs(900),
+      apm: 150,
+      players: 2,
+      seed: 0,
+      speed: 1.,
+      first_game_id: 1,
+    }
+  }
+}
+
+impl SyntheticConfig {
+  fn from_env() -> Option<Self> {
+    fn parse_env<T: std::str::FromStr>(name: &str) -> Option<T> {
+      std::env::var(name).ok().and_then(|v| v.parse().ok())
+    }
+
+    let games = parse_env::<usize>("FLO_OBSERVER_SYNTHETIC_GAMES").filter(|v| *v > 0)?;
+    let default = Self::default();
+    Some(Self {
+      games,
+      total_games: parse_env("FLO_OBSERVER_SYNTHETIC_TOTAL_GAMES"),
+      duration: parse_env("FLO_OBSERVER_SYNTHETIC_DURATION_SECS")
+        .map(Duration::from_secs)
+        .unwrap_or(default.duration),
+      apm: parse_env("FLO_OBSERVER_SYNTHETIC_APM").unwrap_or(default.apm),
+      players: parse_env::<usize>("FLO_OBSERVER_SYNTHETIC_PLAYERS")
+        .map(|v| v.max(1).min(24))
+        .unwrap_or(default.players),
+      seed: parse_env("FLO_OBSERVER_SYNTHETIC_SEED").unwrap_or(default.seed),
+      speed: parse_env::<f64>("FLO_OBSERVER_SYNTHETIC_SPEED")
+        .filter(|v| *v >= 0.)
+        .unwrap_or(default.speed),
+      first_game_id: parse_env("FLO_OBSERVER_SYNTHETIC_FIRST_GAME_ID")
+        .unwrap_or(default.first_game_id),
+    })
+  }
+
+  /// Game details the controller would return, `None` if the source never plays `game_id`
+  pub fn game(&self, game_id: i32) -> Option<Game> {
+    let index = game_id.checked_sub(self.first_game_id)?;
+    if index < 0
+      || self
+        .total_games
+        .map(|v| index as usize >= v)
+        .unwrap_or(false)
+    {
+      return None;
+    }
+    Some(SyntheticGame::new(self, index as usize).game())
+  }
+}
+
+pub struct SyntheticSource {
+  config: SyntheticConfig,
+  started_at: f64,
+  time_ms: u64,
+  next_game: usize,
+  running: Vec<SyntheticGame>,
+  sequence_number: u64,
+}
+
+impl SyntheticSource {
+  pub fn new(config: SyntheticConfig) -> Self {
+    Self {
+      config,
+      started_at: Utc::now().timestamp_millis() as f64 / 1000.,
+      time_ms: 0,
+      next_game: 0,
+      running: vec![],
+      sequence_number: 0,
+    }
+  }
+
+  /// Chunks are paced by `SyntheticConfig::speed`
+  pub fn into_stream(self) -> BoxStream<'static, Chunk> {
+    let interval = if self.config.speed > 0. {
+      let mut interval = tokio::time::interval(Duration::from_secs_f64(
+        CHUNK_MS as f64 / 1000. / self.config.speed,
+      ));
+      interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+      Some(interval)
+    } else {
+      None
+    };
+    futures::stream::unfold((self, interval), |(mut source, mut interval)| async move {
+      if let Some(interval) = interval.as_mut() {
+        interval.tick().await;
+      }
+      let chunk = source.next_chunk()?;
+      Some((chunk, (source, interval)))
+    })
+    .boxed()
+  }
+
+  /// Records of the next second of all running games, `None` after the last game ended
+  pub fn next_chunk(&mut self) -> Option<Chunk> {
+    while self.running.len() < self.config.games
+      && self
+        .config
+        .total_games
+        .map(|v| self.next_game < v)
+        .unwrap_or(true)
+    {
+      self
+        .running
+        .push(SyntheticGame::new(&self.config, self.next_game));
+      self.next_game += 1;
+    }
+
+    if self.running.is_empty() {
+      return None;
+    }
+
+    self.time_ms += CHUNK_MS as u64;
+    let approximate_arrival_timestamp = self.started_at + self.time_ms as f64 / 1000.;
+    let mut game_records = BTreeMap::new();
+    for game in &mut self.running {
+      let min_seq_id = game.next_seq_id;
+      let records = game.advance();
+      game.next_seq_id += records.len() as u32;
+      game_records.insert(
+        game.id,
+        GameChunk {
+          approximate_arrival_timestamp,
+          min_seq_id,
+          max_seq_id: game.next_seq_id - 1,
+          records,
+        },
+      );
+    }
+    self.running.retain(|game| !game.ended());
+
+    self.sequence_number += 1;
+    Some(Chunk {
+      max_sequence_number: format!("{:020}", self.sequence_number),
+      millis_behind_latest: Some(0),
+      game_records,
+    })
+  }
+}
+
+struct SyntheticGame {
+  id: i32,
+  rng: Rng,
+  random_seed: i32,
+  players: Vec<SyntheticPlayer>,
+  duration_ms: u32,
+  time_ms: u32,
+  next_seq_id: u32,
+  ended: bool,
+}
+
+struct SyntheticPlayer {
+  id: i32,
+  race: Race,
+  /// Expected actions per time slot
+  actions_per_slot: f64,
+  ping_ms: u16,
+}
+
+impl SyntheticGame {
+  fn new(config: &SyntheticConfig, index: usize) -> Self {
+    let mut rng = Rng(config.seed ^ (index as u64).wrapping_mul(0x2545_F491_4F6C_DD1D));
+    let duration_ms = rng
+      .jitter(config.duration.as_millis() as f64, 0.25)
+      .max(1000.) as u32;
+    let mut players: Vec<SyntheticPlayer> = Vec::with_capacity(config.players);
+    while players.len() < config.players.min(PLAYER_POOL as usize) {
+      let id = (rng.next_u64() % PLAYER_POOL) as i32 + 1;
+      if players.iter().any(|p| p.id == id) {
+        continue;
+      }
+      let race = match rng.next_u64() % 5 {
+        0 => Race::Human,
+        1 => Race::Orc,
+        2 => Race::NightElf,
+        3 => Race::Undead,
+        _ => Race::Random,
+      };
+      let apm = rng.jitter(config.apm as f64, 0.3);
+      players.push(SyntheticPlayer {
+        id,
+        race,
+        actions_per_slot: apm * TIME_SLOT_MS as f64 / 60_000.,
+        ping_ms: 20 + (rng.next_u64() % 130) as u16,
+      });
+    }
+    Self {
+      id: config.first_game_id + index as i32,
+      random_seed: rng.next_u64() as i32,
+      rng,
+      players,
+      duration_ms,
+      time_ms: 0,
+      next_seq_id: 0,
+      ended: false,
+    }
+  }
+
+  fn ended(&self) -> bool {
+    self.ended
+  }
+
+  fn game(&self) -> Game {
+    Game {
+      id: self.id,
+      name: format!("Synthetic #{}", self.id),
+      map: Map {
+        sha1: vec![0; 20],
+        checksum: 0,
+        name: "Echo Isles".to_string(),
+        path: "Maps\\FrozenThrone\\(2)EchoIsles.w3x".to_string(),
+      },
+      node: Node {
+        id: 0,
+        name: "Synthetic".to_string(),
+        country_id: "US".to_string(),
+      },
+      slots: self
+        .players
+        .iter()
+        .enumerate()
+        .map(|(idx, player)| Slot {
+          player: Some(Player {
+            id: player.id,
+            name: format!("Synthetic{}", player.id),
+          }),
+          settings: SlotSettings {
+            team: (idx % 2) as i32,
+            color: idx as i32,
+            computer: 0,
+            handicap: 100,
+            status: 2,
+            race: player.race,
+          },
+        })
+        .collect(),
+      random_seed: self.random_seed,
+      game_version: Some(GAME_VERSION.to_string()),
+      mask_player_names: false,
+    }
+  }
+
+  /// Records of the next chunk, the last chunk ends with the player leaves and `GameEnd`
+  fn advance(&mut self) -> Vec<GameRecordData> {
+    let mut records = vec![];
+    let end = (self.time_ms + CHUNK_MS).min(self.duration_ms);
+    while self.time_ms < end {
+      let time_increment_ms = TIME_SLOT_MS.min(end - self.time_ms);
+      self.time_ms += time_increment_ms;
+      records.push(GameRecordData::W3GS(self.make_time_slot(time_increment_ms)));
+      if self.time_ms % RTT_STATS_INTERVAL_MS == 0 {
+        records.push(GameRecordData::RTTStats(self.make_rtt_stats()));
+      }
+      if self.time_ms % KEYFRAME_INTERVAL_MS == 0 {
+        records.push(GameRecordData::Keyframe(self.make_keyframe()));
+      }
+    }
+
+    if self.time_ms >= self.duration_ms {
+      // team 0 wins
+      for (idx, _) in self.players.iter().enumerate().rev() {
+        let reason = if idx % 2 == 0 {
+          LeaveReason::LeaveWon
+        } else {
+          LeaveReason::LeaveLost
+        };
+        let packet = Packet::simple(PlayerLeft {
+          player_id: (idx + 1) as u8,
+          reason,
+        })
+        .expect("encode PlayerLeft");
+        records.push(GameRecordData::W3GS(packet));
+      }
+      records.push(GameRecordData::GameEnd);
+      self.ended = true;
+    }
+    records
+  }
+
+  fn make_time_slot(&mut self, time_increment_ms: u32) -> Packet {
+    let mut actions = vec![];
+    for (idx, player) in self.players.iter().enumerate() {
+      let rate = player.actions_per_slot * time_increment_ms as f64 / TIME_SLOT_MS as f64;
+      let count = (rate + self.rng.next_f64()) as usize;
+      if count == 0 {
+        continue;
+      }
+      // `SelectGroupHotkey`, counted for APM
+      let mut data = Vec::with_capacity(count * 3);
+      for _ in 0..count {
+        data.extend_from_slice(&[0x18, (self.rng.next_u64() % 10) as u8, 0x03]);
+      }
+      actions.push(PlayerAction {
+        player_id: (idx + 1) as u8,
+        data: data.into(),
+      });
+    }
+    Packet::with_payload(IncomingAction(TimeSlot {
+      time_increment_ms: time_increment_ms as u16,
+      actions,
+    }))
+    .expect("encode IncomingAction")
+  }
+
+  fn make_rtt_stats(&mut self) -> RTTStats {
+    let rng = &mut self.rng;
+    let items = self.players.iter().map(|player| {
+      let spread = (rng.next_u64() % 40) as u16;
+      RTTStatsItem {
+        player_id: player.id,
+        ticks: (RTT_STATS_INTERVAL_MS / TIME_SLOT_MS) as u16,
+        min: player.ping_ms.saturating_sub(spread / 2),
+        max: player.ping_ms + spread,
+        avg: player.ping_ms as f32 + spread as f32 / 4.,
+      }
+    });
+    RTTStats::new(self.time_ms, items)
+  }
+
+  fn make_keyframe(&self) -> Keyframe {
+    Keyframe::new(
+      self.time_ms,
+      self.time_ms / TIME_SLOT_MS,
+      self
+        .players
+        .iter()
+        .enumerate()
+        .map(|(idx, player)| KeyframePlayer {
+          player_id: player.id,
+          slot_player_id: (idx + 1) as u8,
+          left: false,
+          lagging: false,
+        }),
+    )
+  }
+}
+
+/// SplitMix64, good enough for fake games and stable across platforms and dependency updates
+struct Rng(u64);
+
+impl Rng {
+  fn next_u64(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+  }
+
+  /// In `[0, 1)`
+  fn next_f64(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+  }
+
+  /// `value` changed by up to `ratio` in either direction
+  fn jitter(&mut self, value: f64, ratio: f64) -> f64 {
+    value * (1. + ratio * (self.next_f64() * 2. - 1.))
+  }
+}
+
+#[test]
+fn test_synthetic_source() {
+  let config = SyntheticConfig {
+    games: 2,
+    total_games: Some(3),
+    duration: Duration::from_secs(60),
+    apm: 120,
+    players: 4,
+    seed: 42,
+    speed: 0.,
+    first_game_id: 100,
+  };
+
+  let mut source = SyntheticSource::new(config.clone());
+  let mut games: BTreeMap<i32, Vec<GameRecordData>> = BTreeMap::new();
+  let mut chunks = 0;
+  while let Some(chunk) = source.next_chunk() {
+    chunks += 1;
+    assert!(chunk.game_records.len() <= 2);
+    for (game_id, game_chunk) in chunk.game_records {
+      let records = games.entry(game_id).or_default();
+      assert_eq!(game_chunk.min_seq_id, records.len() as u32);
+      assert_eq!(
+        game_chunk.max_seq_id - game_chunk.min_seq_id + 1,
+        game_chunk.records.len() as u32
+      );
+      records.extend(game_chunk.records);
+    }
+  }
+  assert!(chunks >= 90);
+  assert_eq!(
+    games.keys().cloned().collect::<Vec<_>>(),
+    vec![100, 101, 102]
+  );
+
+  for (game_id, records) in &games {
+    assert!(matches!(records.last(), Some(GameRecordData::GameEnd)));
+    assert!(records
+      .iter()
+      .any(|r| matches!(r, GameRecordData::Keyframe(_))));
+    let actions: usize = records
+      .iter()
+      .filter_map(|r| match r {
+        GameRecordData::W3GS(packet)
+          if packet.type_id() == flo_w3gs::protocol::constants::PacketTypeId::IncomingAction =>
+        {
+          let payload: TimeSlot = packet.decode_payload_bytes().unwrap();
+          Some(
+            payload
+              .actions
+              .iter()
+              .flat_map(|a| a.actions())
+              .filter(|a| matches!(a, Ok(a) if a.is_apm_action()))
+              .count(),
+          )
+        }
+        _ => None,
+      })
+      .sum();
+    // 4 players, 45 - 75 seconds, 84 - 156 apm
+    assert!(actions > 4 * 84 * 45 / 60 / 2, "{}: {}", game_id, actions);
+    assert!(actions < 4 * 156 * 75 / 60 * 2, "{}: {}", game_id, actions);
+
+    let game = config.game(*game_id).unwrap();
+    assert_eq!(game.slots.len(), 4);
+  }
+  assert!(config.game(99).is_none());
+  assert!(config.game(103).is_none());
+
+  // same config, same records
+  let mut source = SyntheticSource::new(config);
+  let chunk = source.next_chunk().unwrap();
+  let records = &chunk.game_records[&100].records;
+  assert_eq!(
+    format!("{:?}", records),
+    format!("{:?}", &games[&100][..records.len()])
+  );
+}
@@ -22,6 +22,8 @@ pub enum RecordError {
   DecodeW3GSHeader(flo_util::error::BinDecodeError),
   #[error("decode rtt stats record: {0}")]
   DecodeRTTStatsRecord(flo_util::error::BinDecodeError),
+  #[error("decode keyframe record: {0}")]
+  DecodeKeyframeRecord(flo_util::error::BinDecodeError),
   #[error("decode w3gs: {0}")]
   DecodeW3GS(flo_w3gs::error::Error),
 }
@@ -107,6 +109,7 @@ pub enum GameRecordData {
   GameEnd,
   TickChecksum { tick: u32, checksum: u32 },
   RTTStats(RTTStats),
+  Keyframe(Keyframe),
 }
 
 #[derive(Debug, Clone, BinEncode, BinDecode)]
@@ -137,6 +140,39 @@ pub struct RTTStatsItem {
   pub avg: f32,
 }
 
+/// Summary of the game state published periodically,
+/// consumers that missed the beginning of a game can pick it up from here
+#[derive(Debug, Clone, PartialEq, BinEncode, BinDecode)]
+pub struct Keyframe {
+  /// Game time in milliseconds
+  pub time: u32,
+  pub tick: u32,
+  players_len: u8,
+  #[bin(repeat = "players_len")]
+  pub players: Vec<KeyframePlayer>,
+}
+
+impl Keyframe {
+  pub fn new(time: u32, tick: u32, players: impl Iterator<Item = KeyframePlayer>) -> Self {
+    let players: Vec<_> = players.into_iter().take(u8::MAX as usize).collect();
+    Self {
+      time,
+      tick,
+      players_len: players.len() as _,
+      players,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, BinEncode, BinDecode)]
+pub struct KeyframePlayer {
+  pub player_id: i32,
+  /// W3GS player id, slot index + 1
+  pub slot_player_id: u8,
+  pub left: bool,
+  pub lagging: bool,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
 pub enum DataTypeId {
@@ -146,6 +182,7 @@ pub enum DataTypeId {
   GameEnd = 4,
   TickChecksum = 5,
   RTTStat = 6,
+  Keyframe = 7,
 }
 
 impl GameRecordData {
@@ -157,6 +194,7 @@ impl GameRecordData {
       GameRecordData::GameEnd => DataTypeId::GameEnd,
       GameRecordData::TickChecksum { .. } => DataTypeId::TickChecksum,
       GameRecordData::RTTStats { .. } => DataTypeId::RTTStat,
+      GameRecordData::Keyframe(_) => DataTypeId::Keyframe,
     }
   }
 
@@ -168,6 +206,9 @@ impl GameRecordData {
       GameRecordData::GameEnd => 0,
       GameRecordData::TickChecksum { .. } => 4 + 4,
       GameRecordData::RTTStats(ref data) => 4 + 1 + (data.items.len() * RTTStatsItem::MIN_SIZE),
+      GameRecordData::Keyframe(ref data) => {
+        4 + 4 + 1 + (data.players.len() * KeyframePlayer::MIN_SIZE)
+      }
     }
   }
 
@@ -200,6 +241,9 @@ impl GameRecordData {
       GameRecordData::RTTStats(ref data) => {
         data.encode(&mut buf);
       }
+      GameRecordData::Keyframe(ref data) => {
+        data.encode(&mut buf);
+      }
     }
   }
 
@@ -214,6 +258,7 @@ impl GameRecordData {
       4 => DataTypeId::GameEnd,
       5 => DataTypeId::TickChecksum,
       6 => DataTypeId::RTTStat,
+      7 => DataTypeId::Keyframe,
       other => return Err(RecordError::UnknownDataTypeId(other)),
     };
     Ok(match data_type {
@@ -262,6 +307,9 @@ impl GameRecordData {
       DataTypeId::RTTStat => {
         Self::RTTStats(RTTStats::decode(&mut buf).map_err(RecordError::DecodeRTTStatsRecord)?)
       }
+      DataTypeId::Keyframe => {
+        Self::Keyframe(Keyframe::decode(&mut buf).map_err(RecordError::DecodeKeyframeRecord)?)
+      }
     })
   }
 }
@@ -309,6 +357,13 @@ impl GameRecord {
     }
   }
 
+  pub fn new_keyframe(game_id: i32, keyframe: Keyframe) -> Self {
+    Self {
+      game_id,
+      data: GameRecordData::Keyframe(keyframe),
+    }
+  }
+
   pub fn encode_len(&self) -> usize {
     4 + self.data.encode_len()
   }
@@ -440,4 +495,36 @@ fn test_encode_decode() {
     assert_eq!(max, i as u16);
     assert_eq!(avg, i as f32);
   }
+
+  let keyframe = Keyframe::new(
+    60000,
+    600,
+    vec![
+      KeyframePlayer {
+        player_id: 1,
+        slot_player_id: 1,
+        left: false,
+        lagging: true,
+      },
+      KeyframePlayer {
+        player_id: 2,
+        slot_player_id: 3,
+        left: true,
+        lagging: false,
+      },
+    ]
+    .into_iter(),
+  );
+  let record = GameRecord::new_keyframe(1234, keyframe.clone());
+  let mut buf = BytesMut::new();
+  record.encode(&mut buf);
+  assert_eq!(buf.len(), record.encode_len());
+  let record = encode_then_decode(&record);
+  assert_eq!(record.game_id, 1234);
+  assert_eq!(record.data.type_id(), DataTypeId::Keyframe);
+  let inner = match record.data {
+    GameRecordData::Keyframe(inner) => inner,
+    _ => unreachable!(),
+  };
+  assert_eq!(inner, keyframe);
 }
@@ -58,7 +58,8 @@ impl ClientPlatformInfo {
       .ok_or_else(|| Error::NoInstallationFolder)?;
 
     let executable_path = installation_path.join("_retail_/x86_64/Warcraft III.exe");
-    let version = crate::war3::get_war3_version(&executable_path)?;
+    let version = crate::war3::get_war3_version(&executable_path)
+      .or_else(|_| crate::war3::get_build_info_version(&installation_path))?;
 
     Ok(ClientPlatformInfo {
       user_data_path: config
@@ -84,7 +85,8 @@ impl ClientPlatformInfo {
 
     tracing::debug!("executable_path: {:?}", executable_path);
 
-    let version = crate::war3::get_war3_version(&executable_path)?;
+    let version = crate::war3::get_war3_version(&executable_path)
+      .or_else(|_| crate::war3::get_build_info_version(&installation_path))?;
 
     tracing::debug!("version: {:?}", version);
 
@@ -120,11 +122,17 @@ impl ClientPlatformInfo {
       .ok_or_else(|| Error::NoUserDataPath)?;
     tracing::debug!("user_data_path: {:?}", user_data_path);
 
-    //TODO: maybe put version into config for Linux
+    // the executable can't be inspected here, read the launcher's build info instead
+    let version = crate::war3::get_build_info_version(&installation_path).unwrap_or_else(|err| {
+      tracing::warn!("detect version: {}, assuming 1.32.6", err);
+      String::from("1.32.6")
+    });
+    tracing::debug!("version: {:?}", version);
+
     Ok(ClientPlatformInfo {
       user_data_path,
       installation_path,
-      version: String::from("1.32.6"),
+      version,
       executable_path,
     })
   }
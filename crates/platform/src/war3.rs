@@ -1,3 +1,6 @@
+use crate::error::{Error, Result};
+use std::path::Path;
+
 #[cfg(windows)]
 mod windows {
   use std::os::windows::ffi::OsStrExt;
@@ -87,3 +90,54 @@ mod macos {
 }
 #[cfg(target_os = "macos")]
 pub use self::macos::*;
+
+/// Reads the version of the active build from the `.build.info` file
+/// the Battle.net launcher writes into the installation folder
+pub fn get_build_info_version(installation_path: &Path) -> Result<String> {
+  let content = std::fs::read_to_string(installation_path.join(".build.info")).map_err(|err| {
+    tracing::debug!("read .build.info: {}", err);
+    Error::GetWar3Version
+  })?;
+  parse_build_info_version(&content).ok_or_else(|| Error::GetWar3Version)
+}
+
+// The first line is the header, e.g. `Branch!STRING:0|Active!DEC:1|...|Version!STRING:0|...`,
+// followed by one row per installed build
+fn parse_build_info_version(content: &str) -> Option<String> {
+  let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+  let header: Vec<&str> = lines
+    .next()?
+    .split('|')
+    .map(|column| column.split('!').next().unwrap_or_default().trim())
+    .collect();
+  let version_idx = header.iter().position(|name| *name == "Version")?;
+  let active_idx = header.iter().position(|name| *name == "Active");
+  let rows: Vec<Vec<&str>> = lines
+    .map(|line| line.split('|').map(str::trim).collect())
+    .collect();
+  let row = rows
+    .iter()
+    .find(|row| active_idx.and_then(|idx| row.get(idx)) == Some(&"1"))
+    .or_else(|| rows.first())?;
+  row
+    .get(version_idx)
+    .filter(|v| !v.is_empty())
+    .map(|v| v.to_string())
+}
+
+#[test]
+fn test_parse_build_info_version() {
+  let content = "Branch!STRING:0|Active!DEC:1|Build Key!HEX:16|Version!STRING:0|Product!STRING:0\n\
+    eu|0|aa|1.36.0.20257|w3\n\
+    us|1|bb|1.36.1.21015|w3\n";
+  assert_eq!(
+    parse_build_info_version(content).as_deref(),
+    Some("1.36.1.21015")
+  );
+  assert_eq!(
+    parse_build_info_version("Branch!STRING:0|Version!STRING:0\neu|1.32.10.18820").as_deref(),
+    Some("1.32.10.18820")
+  );
+  assert_eq!(parse_build_info_version("Branch!STRING:0\neu"), None);
+  assert_eq!(parse_build_info_version(""), None);
+}
@@ -1,2 +1,4 @@
+mod send;
 mod spawn_scope;
+pub use send::{SendError, SendExt, SendPolicy};
 pub use spawn_scope::{SpawnScope, SpawnScopeHandle};
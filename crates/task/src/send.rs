@@ -0,0 +1,180 @@
+use flo_state::{async_trait, Actor, Addr, Handler, Message};
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
+
+/// How long to wait for an actor to handle a message, and how often to try again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SendPolicy {
+  pub timeout: Duration,
+  pub retries: usize,
+  pub retry_delay: Duration,
+}
+
+impl SendPolicy {
+  pub const fn new(timeout: Duration) -> Self {
+    Self {
+      timeout,
+      retries: 0,
+      retry_delay: Duration::from_millis(0),
+    }
+  }
+
+  /// Only use with messages that are safe to handle more than once,
+  /// a timed out message might still be handled by the actor
+  pub const fn with_retries(self, retries: usize, retry_delay: Duration) -> Self {
+    Self {
+      retries,
+      retry_delay,
+      ..self
+    }
+  }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SendError {
+  #[error("actor did not respond within {timeout:?} ({attempts} attempts)")]
+  Timeout { timeout: Duration, attempts: usize },
+  #[error("actor: {0}")]
+  Actor(#[from] flo_state::error::Error),
+}
+
+impl SendError {
+  pub fn is_timeout(&self) -> bool {
+    matches!(self, SendError::Timeout { .. })
+  }
+}
+
+#[async_trait]
+pub trait SendExt<S> {
+  /// Like `Addr::send`, fails with `SendError::Timeout` if the actor didn't respond in time
+  async fn send_within<M>(&self, timeout: Duration, message: M) -> Result<M::Result, SendError>
+  where
+    M: Message,
+    S: Handler<M>;
+
+  /// Sends `message` again after a timeout, up to `policy.retries` times
+  async fn send_with_policy<M>(
+    &self,
+    policy: SendPolicy,
+    message: M,
+  ) -> Result<M::Result, SendError>
+  where
+    M: Message + Clone,
+    S: Handler<M>;
+}
+
+#[async_trait]
+impl<S> SendExt<S> for Addr<S>
+where
+  S: Actor,
+{
+  async fn send_within<M>(&self, duration: Duration, message: M) -> Result<M::Result, SendError>
+  where
+    M: Message,
+    S: Handler<M>,
+  {
+    match timeout(duration, self.send(message)).await {
+      Ok(Err(flo_state::error::Error::SendTimeout)) | Err(_) => Err(SendError::Timeout {
+        timeout: duration,
+        attempts: 1,
+      }),
+      Ok(res) => res.map_err(Into::into),
+    }
+  }
+
+  async fn send_with_policy<M>(
+    &self,
+    policy: SendPolicy,
+    message: M,
+  ) -> Result<M::Result, SendError>
+  where
+    M: Message + Clone,
+    S: Handler<M>,
+  {
+    let mut attempts = 0;
+    loop {
+      attempts += 1;
+      match self.send_within(policy.timeout, message.clone()).await {
+        Err(SendError::Timeout { .. }) if attempts <= policy.retries => {
+          sleep(policy.retry_delay).await;
+        }
+        Err(SendError::Timeout { timeout, .. }) => {
+          return Err(SendError::Timeout { timeout, attempts })
+        }
+        res => return res,
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_send_policy() {
+    use flo_state::Context;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Slow {
+      calls: AtomicUsize,
+    }
+
+    impl Actor for Slow {}
+
+    /// Handled after the given delay, only for the first `n` calls
+    #[derive(Clone)]
+    struct Delay(Duration, usize);
+
+    impl Message for Delay {
+      type Result = usize;
+    }
+
+    #[async_trait]
+    impl Handler<Delay> for Slow {
+      async fn handle(&mut self, _: &mut Context<Self>, Delay(delay, n): Delay) -> usize {
+        let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if calls <= n {
+          sleep(delay).await;
+        }
+        calls
+      }
+    }
+
+    let actor = Slow {
+      calls: AtomicUsize::new(0),
+    }
+    .start();
+    let addr = actor.addr();
+
+    let res = addr
+      .send_within(
+        Duration::from_millis(10),
+        Delay(Duration::from_millis(100), 1),
+      )
+      .await;
+    assert!(matches!(res, Err(SendError::Timeout { attempts: 1, .. })));
+
+    // the timed out call still gets handled before this one
+    let res = addr
+      .send_within(Duration::from_millis(500), Delay(Duration::ZERO, 0))
+      .await;
+    assert_eq!(res.unwrap(), 2);
+
+    // calls 3 and 4 time out, the actor handles them one after another
+    let policy =
+      SendPolicy::new(Duration::from_millis(100)).with_retries(2, Duration::from_millis(1));
+    let res = addr
+      .send_with_policy(policy, Delay(Duration::from_millis(120), 4))
+      .await;
+    assert_eq!(res.unwrap(), 5);
+
+    let res = addr
+      .send_with_policy(
+        SendPolicy::new(Duration::from_millis(10)).with_retries(1, Duration::ZERO),
+        Delay(Duration::from_millis(100), usize::MAX),
+      )
+      .await;
+    assert!(matches!(res, Err(SendError::Timeout { attempts: 2, .. })));
+  }
+}
@@ -17,6 +17,23 @@ pub struct PlayerSession {
   pub player: PlayerInfo,
   pub status: PlayerStatus,
   pub game_id: Option<i32>,
+  pub penalty: Option<PlayerPenalty>,
+  pub privacy: Option<PlayerPrivacy>,
+  pub features: Vec<String>,
+}
+
+#[derive(Debug, S2ProtoUnpack, Serialize, Clone)]
+#[s2_grpc(message_type = "flo_net::proto::flo_connect::PlayerPenalty")]
+pub struct PlayerPenalty {
+  pub offenses: i32,
+  pub ranked_join_blocked_until_millis: i64,
+}
+
+#[derive(Debug, S2ProtoUnpack, Serialize, Clone)]
+#[s2_grpc(message_type = "flo_net::proto::flo_connect::PlayerPrivacy")]
+pub struct PlayerPrivacy {
+  pub hide_profile: bool,
+  pub anonymize: bool,
 }
 
 #[derive(Debug, S2ProtoUnpack, Serialize, Clone)]
@@ -55,6 +72,7 @@ pub enum RejectReason {
   Unknown = 0,
   ClientVersionTooOld = 1,
   InvalidToken = 2,
+  ChallengeFailed = 3,
 }
 
 #[derive(Debug, S2ProtoUnpack, Serialize)]
@@ -70,6 +88,26 @@ pub struct GameInfo {
   pub is_live: bool,
   pub random_seed: i32,
   pub created_by: Option<PlayerInfo>,
+  pub metadata: Option<GameMetadata>,
+  pub saved_game: Option<SavedGame>,
+}
+
+#[derive(Debug, S2ProtoUnpack, Serialize, Clone)]
+#[s2_grpc(message_type = "flo_net::proto::flo_connect::GameMetadata")]
+pub struct GameMetadata {
+  pub description: String,
+  pub tags: Vec<String>,
+  pub language: String,
+  pub mode: String,
+  pub e2e_chat: bool,
+}
+
+/// Save loaded by a resume lobby instead of the map
+#[derive(Debug, S2ProtoUnpack, Serialize, Clone)]
+#[s2_grpc(message_type = "flo_net::proto::flo_connect::SavedGame")]
+pub struct SavedGame {
+  pub name: String,
+  pub magic_number: u32,
 }
 
 #[derive(Debug, S2ProtoEnum, PartialEq, Copy, Clone, Serialize)]
@@ -264,7 +302,11 @@ pub struct MapDetail {
   pub path: String,
   pub sha1: String,
   pub crc32: u32,
+  /// Raw name, may contain color codes
   pub name: String,
+  /// Name without color codes, for lobby listings
+  pub plain_name: String,
+  pub name_segments: Vec<MapTextSegment>,
   pub author: String,
   pub description: String,
   pub width: u32,
@@ -276,6 +318,13 @@ pub struct MapDetail {
   pub forces: Vec<MapForceOwned>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct MapTextSegment {
+  pub text: String,
+  /// `#RRGGBB`
+  pub color: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct MapPlayerOwned {
   pub name: String,
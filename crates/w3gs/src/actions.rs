@@ -205,21 +205,78 @@ action_enum! {
   }
 }
 
+/// `ChangeSelection::select_mode` values
+pub const SELECT_MODE_ADD: u8 = 0x01;
+pub const SELECT_MODE_REMOVE: u8 = 0x02;
+
+impl Action {
+  /// Actions issued by the player, counted for APM.
+  /// Selection removals and subgroup updates the game sends on its own are excluded
+  pub fn is_apm_action(&self) -> bool {
+    match *self {
+      Action::UnitBuildingAbility(_)
+      | Action::UnitBuildingAbilityTargeted(_)
+      | Action::UnitBuildingAbilityTargetedId(_)
+      | Action::ItemGivenDropped(_)
+      | Action::UnitBuildingAbility2Targets2Items(_)
+      | Action::AssignGroupHotkey(_)
+      | Action::SelectGroupHotkey(_)
+      | Action::SelectGroundItem(_)
+      | Action::CancelHeroRevival(_)
+      | Action::RemoveUnitFromBuildingQueue(_)
+      | Action::EscPressed
+      | Action::EnterChooseHeroSkillSubmenu
+      | Action::EnterChooseBuildingSubmenu => true,
+      Action::ChangeSelection(ref v) => v.select_mode == SELECT_MODE_ADD,
+      _ => false,
+    }
+  }
+
+  /// Rawcode of the unit trained, structure built, upgrade researched or item bought,
+  /// e.g. `b"hpea"`. `None` for ability orders and other actions
+  pub fn object_code(&self) -> Option<[u8; 4]> {
+    let item_id = match *self {
+      Action::UnitBuildingAbility(ref v) => v.item_id,
+      Action::UnitBuildingAbilityTargeted(ref v) => v.item_id,
+      Action::UnitBuildingAbilityTargetedId(ref v) => v.item_id,
+      _ => return None,
+    };
+    object_code(item_id)
+  }
+}
+
+// Order ids of abilities are small integers (0x000D00xx),
+// objects are referenced by their rawcode stored as a reversed FourCC
+fn object_code(item_id: u32) -> Option<[u8; 4]> {
+  let code = item_id.to_be_bytes();
+  if code.iter().all(u8::is_ascii_alphanumeric) {
+    Some(code)
+  } else {
+    None
+  }
+}
+
 #[derive(Debug, BinDecode)]
 pub struct GameSpeed {
   pub speed: u8,
 }
 
-#[derive(Debug, BinDecode)]
+#[derive(Debug, BinDecode, BinEncode)]
 pub struct SaveGame {
   pub name: CString,
 }
 
-#[derive(Debug, BinDecode)]
+#[derive(Debug, BinDecode, BinEncode)]
 pub struct SaveGameFinished {
   _unknown: u32,
 }
 
+impl Default for SaveGameFinished {
+  fn default() -> Self {
+    Self { _unknown: 1 }
+  }
+}
+
 #[derive(Debug, BinDecode)]
 pub struct UnitBuildingAbility {
   pub ability_flag: u16,
@@ -386,3 +443,65 @@ impl<const SIZE: usize> BinDecode for Unknown<SIZE> {
     Ok(Self { _unknown: data })
   }
 }
+
+#[test]
+fn test_decode_actions() {
+  use crate::protocol::action::PlayerAction;
+
+  // select 4 peasants, the game updates the subgroup
+  let action = PlayerAction {
+    player_id: 2,
+    data: Bytes::from(vec![
+      22, 1, 4, 0, 116, 51, 0, 0, 116, 51, 0, 0, 139, 51, 0, 0, 139, 51, 0, 0, 185, 51, 0, 0, 185,
+      51, 0, 0, 208, 51, 0, 0, 208, 51, 0, 0, 26, 25, 97, 101, 112, 104, 116, 51, 0, 0, 116, 51, 0,
+      0,
+    ]),
+  };
+  let actions: Vec<_> = action.actions().collect::<Result<_, _>>().unwrap();
+  assert_eq!(
+    actions.iter().map(Action::type_id).collect::<Vec<_>>(),
+    vec![
+      ActionTypeId::ChangeSelection,
+      ActionTypeId::PreSubselection,
+      ActionTypeId::SelectSubgroup114b
+    ]
+  );
+  match actions[0] {
+    Action::ChangeSelection(ref v) => assert_eq!(v.selected_objects.len(), 4),
+    ref other => panic!("unexpected action: {:?}", other),
+  }
+  match actions[2] {
+    Action::SelectSubgroup114b(ref v) => assert_eq!(object_code(v.item_id), Some(*b"hpea")),
+    ref other => panic!("unexpected action: {:?}", other),
+  }
+  assert_eq!(
+    actions.iter().filter(|a| a.is_apm_action()).count(),
+    1
+  );
+}
+
+#[test]
+fn test_object_code() {
+  let mut buf = BytesMut::new();
+  // train a footman ("hfoo")
+  buf.put_u8(0x10);
+  buf.put_u16_le(0x40);
+  buf.put_slice(b"oofh");
+  buf.put_u32_le(0xFFFFFFFF);
+  buf.put_u32_le(0xFFFFFFFF);
+  // right click order
+  buf.put_u8(0x10);
+  buf.put_u16_le(0x40);
+  buf.put_u32_le(0x000D0003);
+  buf.put_u32_le(0xFFFFFFFF);
+  buf.put_u32_le(0xFFFFFFFF);
+
+  let mut buf = buf.freeze();
+  let train = Action::decode(&mut buf).unwrap();
+  assert_eq!(train.object_code(), Some(*b"hfoo"));
+  assert!(train.is_apm_action());
+  let order = Action::decode(&mut buf).unwrap();
+  assert_eq!(order.object_code(), None);
+  assert!(order.is_apm_action());
+  assert!(!buf.has_remaining());
+}
@@ -0,0 +1,182 @@
+//! Strips personal information from recorded W3GS packets,
+//! so game streams can be shared without identifying the players.
+//!
+//! Player names become `Player <id>`, socket addresses are nulled and
+//! chat messages are dropped or redacted. Actions are passed through untouched,
+//! maps that sync player names through the game cache (W3MMD `init pid`) still expose them.
+
+use flo_util::binary::{CString, SockAddr};
+
+use crate::chat::{ChatFromHost, ChatMessage, ChatToHost};
+use crate::constants::{PacketTypeId, ProtoBufMessageTypeId};
+use crate::error::Result;
+use crate::join::SlotInfoJoin;
+use crate::packet::{Packet, ProtoBufPayload};
+use crate::player::{PlayerInfo, PlayerProfileMessage};
+
+/// What happens to chat messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChatPolicy {
+  /// Chat packets are removed from the stream
+  Drop,
+  /// Message text is replaced, who talked to whom and when is kept
+  Redact,
+}
+
+const REDACTED_MESSAGE: &str = "*";
+
+#[derive(Debug)]
+pub struct Anonymizer {
+  chat: ChatPolicy,
+}
+
+impl Anonymizer {
+  pub fn new(chat: ChatPolicy) -> Self {
+    Self { chat }
+  }
+
+  /// Pseudonym of a W3GS player id, stable across games
+  pub fn player_name(player_id: u8) -> CString {
+    CString::new(format!("Player {}", player_id)).expect("no nul bytes")
+  }
+
+  /// Returns the anonymized packet, or `None` if the packet should be removed
+  pub fn anonymize(&self, packet: Packet) -> Result<Option<Packet>> {
+    let packet = match packet.type_id() {
+      PacketTypeId::PlayerInfo => {
+        let mut payload: PlayerInfo = packet.decode_simple()?;
+        payload.player_name = Self::player_name(payload.player_id);
+        payload.external_addr = SockAddr::new_null();
+        payload.internal_addr = SockAddr::new_null();
+        Packet::simple(payload)?
+      }
+      PacketTypeId::SlotInfoJoin => {
+        let mut payload: SlotInfoJoin = packet.decode_simple()?;
+        payload.external_addr = SockAddr::new_null();
+        Packet::simple(payload)?
+      }
+      PacketTypeId::ProtoBuf => {
+        let payload: ProtoBufPayload = packet.decode_simple()?;
+        if payload.message_type_id() != ProtoBufMessageTypeId::PlayerProfile {
+          return Ok(Some(packet));
+        }
+        let mut message: PlayerProfileMessage = payload.decode_message()?;
+        message.battle_tag = Self::player_name(message.player_id as u8)
+          .to_string_lossy()
+          .into_owned();
+        message.clan = String::new();
+        Packet::simple(ProtoBufPayload::new(message))?
+      }
+      PacketTypeId::ChatToHost => {
+        let payload: ChatToHost = packet.decode_simple()?;
+        match self.anonymize_chat(payload) {
+          Some(payload) => Packet::simple(payload)?,
+          None => return Ok(None),
+        }
+      }
+      PacketTypeId::ChatFromHost => {
+        let ChatFromHost(payload) = packet.decode_simple()?;
+        match self.anonymize_chat(payload) {
+          Some(payload) => Packet::simple(ChatFromHost(payload))?,
+          None => return Ok(None),
+        }
+      }
+      _ => packet,
+    };
+    Ok(Some(packet))
+  }
+
+  fn anonymize_chat(&self, mut payload: ChatToHost) -> Option<ChatToHost> {
+    let message = match payload.message {
+      ChatMessage::Chat(ref mut message) => message,
+      ChatMessage::Scoped {
+        ref mut message, ..
+      } => message,
+      // lobby slot changes
+      _ => return Some(payload),
+    };
+    match self.chat {
+      ChatPolicy::Drop => None,
+      ChatPolicy::Redact => {
+        *message = CString::new(REDACTED_MESSAGE).expect("no nul bytes");
+        Some(payload)
+      }
+    }
+  }
+}
+
+#[test]
+fn test_anonymize() {
+  use crate::action::{IncomingAction, PlayerAction, TimeSlot};
+  use crate::chat::MessageScope;
+
+  let a = Anonymizer::new(ChatPolicy::Redact);
+
+  let packet = a
+    .anonymize(Packet::simple(PlayerInfo::new(2, "fluxxu#1815")).unwrap())
+    .unwrap()
+    .unwrap();
+  let payload: PlayerInfo = packet.decode_simple().unwrap();
+  assert_eq!(payload.player_name.to_str().unwrap(), "Player 2");
+
+  let packet = a
+    .anonymize(
+      Packet::simple(ProtoBufPayload::new(PlayerProfileMessage::new(
+        2,
+        "fluxxu#1815",
+      )))
+      .unwrap(),
+    )
+    .unwrap()
+    .unwrap();
+  let message: PlayerProfileMessage = packet.decode_protobuf().unwrap();
+  assert_eq!(message.player_id, 2);
+  assert_eq!(message.battle_tag, "Player 2");
+
+  let chat = Packet::simple(ChatFromHost(ChatToHost::in_game(
+    MessageScope::All,
+    2,
+    &[1, 3],
+    "hello",
+  )))
+  .unwrap();
+  let packet = a.anonymize(chat.clone()).unwrap().unwrap();
+  let ChatFromHost(payload) = packet.decode_simple().unwrap();
+  assert_eq!(payload.from_player, 2);
+  assert_eq!(payload.to_players, vec![1, 3]);
+  assert_eq!(
+    payload.message,
+    ChatMessage::Scoped {
+      scope: MessageScope::All,
+      message: CString::new(REDACTED_MESSAGE).unwrap(),
+    }
+  );
+  assert!(Anonymizer::new(ChatPolicy::Drop)
+    .anonymize(chat)
+    .unwrap()
+    .is_none());
+
+  let team_change = Packet::simple(ChatToHost {
+    to_players_len: 1,
+    to_players: vec![1],
+    from_player: 2,
+    message: ChatMessage::TeamChange(1),
+  })
+  .unwrap();
+  let packet = Anonymizer::new(ChatPolicy::Drop)
+    .anonymize(team_change.clone())
+    .unwrap()
+    .unwrap();
+  assert_eq!(packet.payload, team_change.payload);
+
+  let action = Packet::with_payload(IncomingAction(TimeSlot {
+    time_increment_ms: 100,
+    actions: vec![PlayerAction {
+      player_id: 2,
+      data: vec![0x10, 0x00, 0x00].into(),
+    }],
+  }))
+  .unwrap();
+  let packet = a.anonymize(action.clone()).unwrap().unwrap();
+  assert_eq!(packet.payload, action.payload);
+}
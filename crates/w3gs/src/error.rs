@@ -20,6 +20,8 @@ pub enum Error {
   InvalidStringNulByte(#[from] std::ffi::NulError),
   #[error("io: {0}")]
   Io(#[from] std::io::Error),
+  #[error("no free port in {start}..{end}")]
+  PortRangeExhausted { start: u16, end: u16 },
   #[error("unexpected bytes after payload: {0}")]
   ExtraPayloadBytes(usize),
   #[error("packet type id mismatch: expected `{expected:?}`, found `{found:?}`")]
@@ -29,10 +31,76 @@ pub enum Error {
   },
   #[error("invalid checksum")]
   InvalidChecksum,
+  #[error("packet too large for fragmentation: {len} > {max}")]
+  FragmentLimitExceeded { len: usize, max: usize },
+  #[error("invalid fragment: index = {index}, count = {count}")]
+  InvalidFragment { index: u8, count: u8 },
+  #[error(transparent)]
+  PacketDecode(Box<PacketDecodeError>),
   #[error("bin decode: {0}")]
   BinDecode(#[from] flo_util::binary::BinDecodeError),
   #[error("protobuf decode: {0}")]
   ProtoBufDecode(#[from] prost::DecodeError),
+  #[error("invalid mmd message: {0}")]
+  InvalidMmdMessage(String),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Payload bytes kept in `PacketDecodeError`
+const DECODE_ERROR_DUMP_LEN: usize = 64;
+
+/// A packet payload that failed to decode, with enough context to find the bad bytes
+#[derive(Error, Debug)]
+#[error(
+  "decode {type_id:?}: {source}: payload len = {payload_len}, offset = {offset}, bytes [{dump_offset}..]: {}",
+  hex(dump)
+)]
+pub struct PacketDecodeError {
+  pub type_id: PacketTypeId,
+  pub payload_len: usize,
+  /// Payload offset where decoding stopped
+  pub offset: usize,
+  /// Payload offset of the first byte in `dump`
+  pub dump_offset: usize,
+  /// Up to 64 payload bytes, starting shortly before `offset`
+  pub dump: Vec<u8>,
+  pub source: Error,
+}
+
+impl PacketDecodeError {
+  pub fn new(type_id: PacketTypeId, payload: &[u8], offset: usize, source: Error) -> Self {
+    let offset = std::cmp::min(offset, payload.len());
+    let dump_offset = std::cmp::min(
+      offset.saturating_sub(DECODE_ERROR_DUMP_LEN / 4),
+      payload.len().saturating_sub(DECODE_ERROR_DUMP_LEN),
+    );
+    let dump_end = std::cmp::min(dump_offset + DECODE_ERROR_DUMP_LEN, payload.len());
+    Self {
+      type_id,
+      payload_len: payload.len(),
+      offset,
+      dump_offset,
+      dump: payload[dump_offset..dump_end].to_vec(),
+      source,
+    }
+  }
+}
+
+impl Error {
+  /// The underlying error, without packet context
+  pub fn root(&self) -> &Error {
+    match *self {
+      Error::PacketDecode(ref e) => e.source.root(),
+      ref e => e,
+    }
+  }
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes
+    .iter()
+    .map(|b| format!("{:02x}", b))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
@@ -4,3 +4,5 @@ pub mod protocol;
 
 pub use protocol::*;
 pub mod actions;
+pub mod anonymize;
+pub mod mmd;
@@ -0,0 +1,584 @@
+//! W3MMD, stats and results reported by custom maps.
+//!
+//! Maps store each message as the key of an integer in the `MMD.Dat` game cache,
+//! under the mission key `val:<message id>`, and sync it with `SyncStoredInteger`,
+//! which shows up in the action stream as `Action::MMDMessage`.
+//! Message arguments are separated by spaces, spaces and backslashes inside an argument
+//! are escaped with a backslash.
+//!
+//! Player ids (`pid`) in messages are the map's player numbers (0-based slot index),
+//! not W3GS player ids.
+
+use crate::actions::{Action, MMDMessage};
+use crate::error::{Error, Result};
+use crate::protocol::action::PlayerAction;
+use crate::protocol::leave::GameResult;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const MMD_FILE_NAME: &str = "MMD.Dat";
+const MESSAGE_MISSION_KEY_PREFIX: &str = "val:";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VarType {
+  Int,
+  Real,
+  String,
+}
+
+/// Which values are considered better
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Goal {
+  High,
+  Low,
+  None,
+}
+
+/// How the map suggests the variable is displayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Suggestion {
+  None,
+  Track,
+  Leaderboard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+  Set,
+  Add,
+  Subtract,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flag {
+  Winner,
+  Loser,
+  Drawer,
+  Leaver,
+  Practicing,
+}
+
+impl Flag {
+  /// `None` for flags that don't decide the result
+  pub fn result(&self) -> Option<GameResult> {
+    match *self {
+      Flag::Winner => Some(GameResult::Win),
+      Flag::Loser => Some(GameResult::Loss),
+      Flag::Drawer => Some(GameResult::Draw),
+      Flag::Leaver | Flag::Practicing => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Int(i32),
+  Real(f64),
+  String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarDef {
+  pub name: String,
+  pub var_type: VarType,
+  pub goal: Goal,
+  pub suggestion: Suggestion,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventDef {
+  pub name: String,
+  pub arg_names: Vec<String>,
+  /// Display format, `{0}` is replaced by the first argument
+  pub format: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MmdEvent {
+  InitVersion {
+    minimum: u32,
+    current: u32,
+  },
+  InitPlayer {
+    pid: u8,
+    name: String,
+  },
+  DefineVar(VarDef),
+  /// `value` is typed by the definition of the variable, see `MmdState`
+  SetVar {
+    pid: u8,
+    name: String,
+    operation: Operation,
+    value: String,
+  },
+  Flag {
+    pid: u8,
+    flag: Flag,
+  },
+  DefineEvent(EventDef),
+  Event {
+    name: String,
+    args: Vec<String>,
+  },
+  Blank,
+  Custom(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MmdMessage {
+  pub id: u32,
+  pub event: MmdEvent,
+}
+
+impl MmdMessage {
+  /// Returns `None` for game cache syncs that are not W3MMD messages, including checksums
+  pub fn from_action(action: &MMDMessage) -> Result<Option<Self>> {
+    if action.name.as_bytes() != MMD_FILE_NAME.as_bytes() {
+      return Ok(None);
+    }
+    let mission_key = action.checksum.to_string_lossy();
+    let id = match mission_key.strip_prefix(MESSAGE_MISSION_KEY_PREFIX) {
+      Some(id) => id
+        .parse()
+        .map_err(|_| Error::InvalidMmdMessage(format!("message id: {}", mission_key)))?,
+      None => return Ok(None),
+    };
+    Ok(Some(Self {
+      id,
+      event: MmdEvent::parse(&action.second_checksum.to_string_lossy())?,
+    }))
+  }
+}
+
+impl MmdEvent {
+  pub fn parse(message: &str) -> Result<Self> {
+    let args = split_args(message);
+    let mut args = Args {
+      message,
+      iter: args.into_iter(),
+    };
+    let event = match args.next("type")?.as_str() {
+      "init" => match args.next("init type")?.as_str() {
+        "version" => MmdEvent::InitVersion {
+          minimum: args.parse("minimum version")?,
+          current: args.parse("current version")?,
+        },
+        "pid" => MmdEvent::InitPlayer {
+          pid: args.parse("pid")?,
+          name: args.next("name")?,
+        },
+        _ => return Err(args.invalid("init type")),
+      },
+      "DefVarP" => MmdEvent::DefineVar(VarDef {
+        name: args.next("name")?,
+        var_type: match args.next("type")?.as_str() {
+          "int" => VarType::Int,
+          "real" => VarType::Real,
+          "string" => VarType::String,
+          _ => return Err(args.invalid("type")),
+        },
+        goal: match args.next("goal")?.as_str() {
+          "high" => Goal::High,
+          "low" => Goal::Low,
+          "none" => Goal::None,
+          _ => return Err(args.invalid("goal")),
+        },
+        suggestion: match args.next("suggestion")?.as_str() {
+          "none" => Suggestion::None,
+          "track" => Suggestion::Track,
+          "leaderboard" => Suggestion::Leaderboard,
+          _ => return Err(args.invalid("suggestion")),
+        },
+      }),
+      "VarP" => MmdEvent::SetVar {
+        pid: args.parse("pid")?,
+        name: args.next("name")?,
+        operation: match args.next("operation")?.as_str() {
+          "=" => Operation::Set,
+          "+=" => Operation::Add,
+          "-=" => Operation::Subtract,
+          _ => return Err(args.invalid("operation")),
+        },
+        value: args.next("value")?,
+      },
+      "FlagP" => MmdEvent::Flag {
+        pid: args.parse("pid")?,
+        flag: match args.next("flag")?.as_str() {
+          "winner" => Flag::Winner,
+          "loser" => Flag::Loser,
+          "drawer" => Flag::Drawer,
+          "leaver" => Flag::Leaver,
+          "practicing" => Flag::Practicing,
+          _ => return Err(args.invalid("flag")),
+        },
+      },
+      "DefEvent" => {
+        let name = args.next("name")?;
+        let count: usize = args.parse("argument count")?;
+        let mut arg_names = Vec::with_capacity(count);
+        for _ in 0..count {
+          arg_names.push(args.next("argument name")?);
+        }
+        MmdEvent::DefineEvent(EventDef {
+          name,
+          arg_names,
+          format: args.next("format")?,
+        })
+      }
+      "Event" => MmdEvent::Event {
+        name: args.next("name")?,
+        args: args.rest(),
+      },
+      "Blank" => MmdEvent::Blank,
+      "Custom" => MmdEvent::Custom(args.rest()),
+      _ => return Err(Error::InvalidMmdMessage(format!("unknown: {}", message))),
+    };
+    Ok(event)
+  }
+}
+
+struct Args<'a> {
+  message: &'a str,
+  iter: std::vec::IntoIter<String>,
+}
+
+impl<'a> Args<'a> {
+  fn next(&mut self, name: &str) -> Result<String> {
+    self
+      .iter
+      .next()
+      .ok_or_else(|| Error::InvalidMmdMessage(format!("missing {}: {}", name, self.message)))
+  }
+
+  fn parse<T: std::str::FromStr>(&mut self, name: &str) -> Result<T> {
+    self.next(name)?.parse().map_err(|_| self.invalid(name))
+  }
+
+  fn rest(&mut self) -> Vec<String> {
+    self.iter.by_ref().collect()
+  }
+
+  fn invalid(&self, name: &str) -> Error {
+    Error::InvalidMmdMessage(format!("invalid {}: {}", name, self.message))
+  }
+}
+
+fn split_args(message: &str) -> Vec<String> {
+  let mut args = vec![];
+  let mut arg = String::new();
+  let mut chars = message.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => {
+        if let Some(c) = chars.next() {
+          arg.push(c)
+        }
+      }
+      ' ' => {
+        if !arg.is_empty() {
+          args.push(std::mem::take(&mut arg));
+        }
+      }
+      c => arg.push(c),
+    }
+  }
+  if !arg.is_empty() {
+    args.push(arg);
+  }
+  args
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MmdPlayer {
+  pub name: Option<String>,
+  pub flags: Vec<Flag>,
+  pub values: BTreeMap<String, Value>,
+}
+
+/// W3MMD state of a game, built from the messages of all players
+#[derive(Debug, Default)]
+pub struct MmdState {
+  /// Every client syncs the same messages, only the first copy of an id is applied
+  seen_ids: BTreeSet<u32>,
+  pub version: Option<u32>,
+  pub players: BTreeMap<u8, MmdPlayer>,
+  pub vars: BTreeMap<String, VarDef>,
+  pub events: BTreeMap<String, EventDef>,
+}
+
+impl MmdState {
+  /// Applies the W3MMD messages of a player action,
+  /// returns the events of messages not seen before
+  pub fn put_action(&mut self, action: &PlayerAction) -> Result<Vec<MmdEvent>> {
+    let mut events = vec![];
+    for item in action.actions() {
+      if let Action::MMDMessage(ref message) = item? {
+        if let Some(message) = MmdMessage::from_action(message)? {
+          if let Some(event) = self.apply(message)? {
+            events.push(event);
+          }
+        }
+      }
+    }
+    Ok(events)
+  }
+
+  /// Returns `None` if the message was already applied
+  pub fn apply(&mut self, message: MmdMessage) -> Result<Option<MmdEvent>> {
+    if !self.seen_ids.insert(message.id) {
+      return Ok(None);
+    }
+
+    match message.event {
+      MmdEvent::InitVersion { current, .. } => self.version = Some(current),
+      MmdEvent::InitPlayer { pid, ref name } => {
+        self.players.entry(pid).or_default().name = Some(name.clone())
+      }
+      MmdEvent::DefineVar(ref def) => {
+        self.vars.insert(def.name.clone(), def.clone());
+      }
+      MmdEvent::SetVar {
+        pid,
+        ref name,
+        operation,
+        ref value,
+      } => {
+        let def = self
+          .vars
+          .get(name)
+          .ok_or_else(|| Error::InvalidMmdMessage(format!("undefined var: {}", name)))?;
+        let values = &mut self.players.entry(pid).or_default().values;
+        let next = update_value(def.var_type, values.get(name), operation, value)?;
+        values.insert(name.clone(), next);
+      }
+      MmdEvent::Flag { pid, flag } => {
+        let flags = &mut self.players.entry(pid).or_default().flags;
+        if !flags.contains(&flag) {
+          flags.push(flag);
+        }
+      }
+      MmdEvent::DefineEvent(ref def) => {
+        self.events.insert(def.name.clone(), def.clone());
+      }
+      MmdEvent::Event { .. } | MmdEvent::Blank | MmdEvent::Custom(_) => {}
+    }
+
+    Ok(Some(message.event))
+  }
+
+  /// Players flagged as winners by the map
+  pub fn winners(&self) -> Vec<u8> {
+    self.flagged(Flag::Winner)
+  }
+
+  /// Result of the player decided by its flags, the last flag with a result wins
+  pub fn result(&self, pid: u8) -> Option<GameResult> {
+    self
+      .players
+      .get(&pid)?
+      .flags
+      .iter()
+      .rev()
+      .find_map(Flag::result)
+  }
+
+  pub fn flagged(&self, flag: Flag) -> Vec<u8> {
+    self
+      .players
+      .iter()
+      .filter(|(_, player)| player.flags.contains(&flag))
+      .map(|(pid, _)| *pid)
+      .collect()
+  }
+}
+
+fn update_value(
+  var_type: VarType,
+  current: Option<&Value>,
+  operation: Operation,
+  value: &str,
+) -> Result<Value> {
+  let invalid = || Error::InvalidMmdMessage(format!("invalid {:?} value: {}", var_type, value));
+  let next = match var_type {
+    VarType::Int => {
+      let value: i32 = value.parse().map_err(|_| invalid())?;
+      let current = match current {
+        Some(Value::Int(v)) => *v,
+        _ => 0,
+      };
+      Value::Int(match operation {
+        Operation::Set => value,
+        Operation::Add => current.wrapping_add(value),
+        Operation::Subtract => current.wrapping_sub(value),
+      })
+    }
+    VarType::Real => {
+      let value: f64 = value.parse().map_err(|_| invalid())?;
+      let current = match current {
+        Some(Value::Real(v)) => *v,
+        _ => 0.,
+      };
+      Value::Real(match operation {
+        Operation::Set => value,
+        Operation::Add => current + value,
+        Operation::Subtract => current - value,
+      })
+    }
+    VarType::String => {
+      if operation != Operation::Set {
+        return Err(invalid());
+      }
+      let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+      Value::String(value.to_string())
+    }
+  };
+  Ok(next)
+}
+
+#[test]
+fn test_split_args() {
+  assert_eq!(
+    split_args("init pid 0 Some\\ Player"),
+    vec!["init", "pid", "0", "Some Player"]
+  );
+  assert_eq!(
+    split_args("Event  kill a\\\\b "),
+    vec!["Event", "kill", "a\\b"]
+  );
+}
+
+#[test]
+fn test_parse_event() {
+  assert_eq!(
+    MmdEvent::parse("init version 0 1").unwrap(),
+    MmdEvent::InitVersion {
+      minimum: 0,
+      current: 1
+    }
+  );
+  assert_eq!(
+    MmdEvent::parse("DefVarP kills int high leaderboard").unwrap(),
+    MmdEvent::DefineVar(VarDef {
+      name: "kills".to_string(),
+      var_type: VarType::Int,
+      goal: Goal::High,
+      suggestion: Suggestion::Leaderboard,
+    })
+  );
+  assert_eq!(
+    MmdEvent::parse("VarP 3 kills += 2").unwrap(),
+    MmdEvent::SetVar {
+      pid: 3,
+      name: "kills".to_string(),
+      operation: Operation::Add,
+      value: "2".to_string(),
+    }
+  );
+  assert_eq!(
+    MmdEvent::parse("FlagP 1 winner").unwrap(),
+    MmdEvent::Flag {
+      pid: 1,
+      flag: Flag::Winner
+    }
+  );
+  assert_eq!(
+    MmdEvent::parse("DefEvent kill 2 killer victim {0}\\ killed\\ {1}").unwrap(),
+    MmdEvent::DefineEvent(EventDef {
+      name: "kill".to_string(),
+      arg_names: vec!["killer".to_string(), "victim".to_string()],
+      format: "{0} killed {1}".to_string(),
+    })
+  );
+  assert_eq!(
+    MmdEvent::parse("Event kill 0 1").unwrap(),
+    MmdEvent::Event {
+      name: "kill".to_string(),
+      args: vec!["0".to_string(), "1".to_string()],
+    }
+  );
+  assert_eq!(MmdEvent::parse("Blank").unwrap(), MmdEvent::Blank);
+  assert!(MmdEvent::parse("FlagP 1 champion").is_err());
+  assert!(MmdEvent::parse("VarP 1 kills").is_err());
+  assert!(MmdEvent::parse("Unknown 1").is_err());
+}
+
+#[test]
+fn test_message_from_action() {
+  use std::ffi::CString;
+  let action = |name: &str, mission_key: &str, key: &str| MMDMessage {
+    name: CString::new(name).unwrap(),
+    checksum: CString::new(mission_key).unwrap(),
+    second_checksum: CString::new(key).unwrap(),
+    weak_checksum: 0,
+  };
+  assert_eq!(
+    MmdMessage::from_action(&action(MMD_FILE_NAME, "val:2", "FlagP 0 loser")).unwrap(),
+    Some(MmdMessage {
+      id: 2,
+      event: MmdEvent::Flag {
+        pid: 0,
+        flag: Flag::Loser
+      },
+    })
+  );
+  assert_eq!(
+    MmdMessage::from_action(&action(MMD_FILE_NAME, "chk:2", "123")).unwrap(),
+    None
+  );
+  assert_eq!(
+    MmdMessage::from_action(&action("Other.Dat", "val:2", "FlagP 0 loser")).unwrap(),
+    None
+  );
+  assert!(MmdMessage::from_action(&action(MMD_FILE_NAME, "val:x", "Blank")).is_err());
+}
+
+#[test]
+fn test_state() {
+  let mut state = MmdState::default();
+  let messages = [
+    "init version 0 1",
+    "init pid 0 Alice",
+    "init pid 1 Bob",
+    "DefVarP kills int high leaderboard",
+    "DefVarP hero string none track",
+    "VarP 0 kills = 3",
+    "VarP 0 kills += 2",
+    "VarP 1 kills -= 1",
+    "VarP 1 hero = \"Blade\\ Master\"",
+    "FlagP 0 winner",
+    "FlagP 1 loser",
+    "FlagP 1 leaver",
+  ];
+  for (id, message) in messages.iter().enumerate() {
+    let message = MmdMessage {
+      id: id as u32,
+      event: MmdEvent::parse(message).unwrap(),
+    };
+    assert!(state.apply(message.clone()).unwrap().is_some());
+    // synced by another player
+    assert!(state.apply(message).unwrap().is_none());
+  }
+
+  assert_eq!(state.version, Some(1));
+  assert_eq!(state.winners(), vec![0]);
+  assert_eq!(state.flagged(Flag::Leaver), vec![1]);
+  assert_eq!(state.players[&0].name.as_deref(), Some("Alice"));
+  assert_eq!(state.players[&0].values["kills"], Value::Int(5));
+  assert_eq!(state.players[&1].values["kills"], Value::Int(-1));
+  assert_eq!(
+    state.players[&1].values["hero"],
+    Value::String("Blade Master".to_string())
+  );
+  assert_eq!(state.players[&1].flags, vec![Flag::Loser, Flag::Leaver]);
+  assert_eq!(state.result(0), Some(GameResult::Win));
+  assert_eq!(state.result(1), Some(GameResult::Loss));
+  assert_eq!(state.result(2), None);
+
+  let undefined = MmdMessage {
+    id: 100,
+    event: MmdEvent::parse("VarP 0 deaths = 1").unwrap(),
+  };
+  assert!(state.apply(undefined).is_err());
+}
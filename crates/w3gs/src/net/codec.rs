@@ -1,35 +1,46 @@
 use flo_util::binary::*;
+use std::sync::Arc;
+use std::time::Instant;
 use tokio_util::codec::{Decoder, Encoder};
 
+use super::metrics::CodecMetrics;
 use crate::error::Error;
 use crate::protocol::packet::{Header, Packet};
 
 #[derive(Debug)]
 pub struct W3GSCodec {
   decode_state: DecoderState,
+  metrics: Option<Arc<CodecMetrics>>,
 }
 
 impl W3GSCodec {
   pub fn new() -> Self {
     Self {
       decode_state: DecoderState::DecodingHeader,
+      metrics: None,
     }
   }
-}
 
-impl Decoder for W3GSCodec {
-  type Item = Packet;
-  type Error = Error;
+  pub fn metrics(&self) -> Option<&Arc<CodecMetrics>> {
+    self.metrics.as_ref()
+  }
 
-  fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+  pub fn set_metrics(&mut self, metrics: Option<Arc<CodecMetrics>>) {
+    self.metrics = metrics;
+  }
+
+  fn decode_packet(&mut self, src: &mut BytesMut) -> Result<Option<Packet>, Error> {
     match self.decode_state {
       DecoderState::DecodingHeader => {
         if src.remaining() >= Header::MIN_SIZE {
-          let header = Header::decode(src)?;
+          // a bad header stays in `src` for the capture
+          let header = Header::decode(&mut &src[..Header::MIN_SIZE])?;
           let payload_len = header.get_payload_len()?;
+          src.advance(Header::MIN_SIZE);
           if src.remaining() >= payload_len {
             // payload received
             let packet = Packet::decode(header, src)?;
+            self.record_received(&packet, None);
             Ok(Some(packet))
           } else {
             // wait payload
@@ -37,6 +48,7 @@ impl Decoder for W3GSCodec {
             self.decode_state = DecoderState::DecodingPayload {
               header: Some(header),
               payload_len,
+              header_at: self.metrics.as_ref().map(|_| Instant::now()),
             };
             Ok(None)
           }
@@ -48,6 +60,7 @@ impl Decoder for W3GSCodec {
       DecoderState::DecodingPayload {
         ref mut header,
         payload_len,
+        header_at,
       } => {
         if src.remaining() >= payload_len {
           let packet = Packet::decode(
@@ -55,6 +68,7 @@ impl Decoder for W3GSCodec {
             src,
           )?;
           self.decode_state = DecoderState::DecodingHeader;
+          self.record_received(&packet, header_at);
           Ok(Some(packet))
         } else {
           Ok(None)
@@ -62,6 +76,26 @@ impl Decoder for W3GSCodec {
       }
     }
   }
+
+  fn record_received(&self, packet: &Packet, header_at: Option<Instant>) {
+    if let Some(metrics) = self.metrics.as_ref() {
+      let latency = header_at.map(|t| t.elapsed()).unwrap_or_default();
+      metrics.record_received(packet.type_id(), packet.len() as usize, latency);
+    }
+  }
+}
+
+impl Decoder for W3GSCodec {
+  type Item = Packet;
+  type Error = Error;
+
+  fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+    let res = self.decode_packet(src);
+    if let (Err(err), Some(metrics)) = (res.as_ref(), self.metrics.as_ref()) {
+      metrics.capture_bytes(src, err);
+    }
+    res
+  }
 }
 
 #[derive(Debug)]
@@ -70,6 +104,8 @@ enum DecoderState {
   DecodingPayload {
     header: Option<Header>,
     payload_len: usize,
+    /// Set if metrics are enabled
+    header_at: Option<Instant>,
   },
 }
 
@@ -77,7 +113,52 @@ impl Encoder<Packet> for W3GSCodec {
   type Error = Error;
 
   fn encode(&mut self, item: Packet, dst: &mut BytesMut) -> Result<(), Self::Error> {
+    if let Some(metrics) = self.metrics.as_ref() {
+      metrics.record_sent(item.type_id(), item.len() as usize);
+    }
     item.encode(dst);
     Ok(())
   }
 }
+
+#[test]
+fn test_codec_capture() {
+  use super::metrics::CodecMetrics;
+  use crate::protocol::constants::PacketTypeId;
+
+  let metrics = Arc::new(CodecMetrics::new(4));
+  let mut codec = W3GSCodec::new();
+  codec.set_metrics(Some(metrics.clone()));
+  let mut buf = BytesMut::new();
+
+  codec
+    .encode(
+      Packet {
+        header: Header::new(PacketTypeId::PingFromHost, 8),
+        payload: Bytes::from_static(&[1, 2, 3, 4]),
+      },
+      &mut buf,
+    )
+    .unwrap();
+  // split payload
+  let mut rest = buf.split_off(6);
+  assert!(codec.decode(&mut buf).unwrap().is_none());
+  buf.unsplit(rest.split());
+  let packet = codec.decode(&mut buf).unwrap().unwrap();
+  assert_eq!(packet.type_id(), PacketTypeId::PingFromHost);
+
+  // bad signature byte
+  buf.extend_from_slice(&[0xF8, 0x01, 0x08, 0x00, 1, 2]);
+  assert!(codec.decode(&mut buf).is_err());
+  assert_eq!(buf.len(), 6);
+
+  let snapshot = metrics.snapshot();
+  assert_eq!(snapshot.malformed, 1);
+  let ping = &snapshot.packets[0];
+  assert_eq!((ping.received, ping.received_bytes), (1, 8));
+  assert_eq!((ping.sent, ping.sent_bytes), (1, 8));
+  assert_eq!(
+    metrics.malformed_packets()[0].bytes.as_ref(),
+    &[0xF8, 0x01, 0x08, 0x00, 1, 2]
+  );
+}
@@ -0,0 +1,443 @@
+//! Splits W3GS packets into MTU sized fragments and merges them back,
+//! for transports that don't preserve packet boundaries of large writes (UDP).
+//!
+//! Fragment layout: `seq: u16`, `index: u8`, `count: u8`, followed by a slice
+//! of the encoded packet (header included).
+
+use flo_util::binary::*;
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::error::{Error, Result};
+use crate::protocol::packet::{Header, Packet};
+
+/// Ethernet MTU
+pub const DEFAULT_MTU: usize = 1500;
+/// IPv4 (20) + UDP (8) headers
+pub const IP_UDP_HEADER_LEN: usize = 28;
+pub const FRAGMENT_HEADER_LEN: usize = 4;
+/// The smallest MTU every IPv4 host has to accept
+pub const MIN_MTU: usize = 576;
+pub const MAX_FRAGMENTS: usize = u8::MAX as usize;
+
+#[cfg(test)]
+const PACKET_HEADER_LEN: usize = 4;
+const DEFAULT_MAX_PENDING: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MtuBudget {
+  mtu: usize,
+}
+
+impl Default for MtuBudget {
+  fn default() -> Self {
+    Self { mtu: DEFAULT_MTU }
+  }
+}
+
+impl MtuBudget {
+  /// Values below `MIN_MTU` are raised to `MIN_MTU`
+  pub fn new(mtu: usize) -> Self {
+    Self {
+      mtu: std::cmp::max(mtu, MIN_MTU),
+    }
+  }
+
+  pub fn mtu(&self) -> usize {
+    self.mtu
+  }
+
+  /// Packet bytes carried by a single fragment
+  pub fn fragment_data_len(&self) -> usize {
+    self.mtu - IP_UDP_HEADER_LEN - FRAGMENT_HEADER_LEN
+  }
+
+  /// The largest encoded packet that can be sent with this budget
+  pub fn max_packet_len(&self) -> usize {
+    std::cmp::min(self.fragment_data_len() * MAX_FRAGMENTS, u16::MAX as usize)
+  }
+
+  pub fn fragment_count(&self, packet: &Packet) -> usize {
+    let len = packet.get_encode_len();
+    len.div_ceil(self.fragment_data_len())
+  }
+
+  /// Returns `true` if `packet` fits in one fragment
+  pub fn fits_single(&self, packet: &Packet) -> bool {
+    self.fragment_count(packet) <= 1
+  }
+
+  pub fn check(&self, packet: &Packet) -> Result<()> {
+    let len = packet.get_encode_len();
+    if len > self.max_packet_len() {
+      return Err(Error::FragmentLimitExceeded {
+        len,
+        max: self.max_packet_len(),
+      });
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+  pub seq: u16,
+  pub index: u8,
+  pub count: u8,
+  pub data: Bytes,
+}
+
+impl Fragment {
+  pub fn encode_len(&self) -> usize {
+    FRAGMENT_HEADER_LEN + self.data.len()
+  }
+
+  pub fn encode(&self, buf: &mut BytesMut) {
+    buf.reserve(self.encode_len());
+    buf.put_u16_le(self.seq);
+    buf.put_u8(self.index);
+    buf.put_u8(self.count);
+    buf.put_slice(&self.data);
+  }
+
+  pub fn decode(buf: &mut Bytes) -> Result<Self> {
+    if buf.remaining() < FRAGMENT_HEADER_LEN {
+      return Err(
+        BinDecodeError::incomplete()
+          .context("fragment header")
+          .into(),
+      );
+    }
+    let seq = buf.get_u16_le();
+    let index = buf.get_u8();
+    let count = buf.get_u8();
+    if count == 0 || index >= count {
+      return Err(Error::InvalidFragment { index, count });
+    }
+    Ok(Self {
+      seq,
+      index,
+      count,
+      data: buf.split_to(buf.remaining()),
+    })
+  }
+}
+
+/// Splits packets according to a `MtuBudget`, each packet gets a new sequence number
+#[derive(Debug, Default)]
+pub struct Fragmenter {
+  budget: MtuBudget,
+  next_seq: u16,
+}
+
+impl Fragmenter {
+  pub fn new(budget: MtuBudget) -> Self {
+    Self {
+      budget,
+      next_seq: 0,
+    }
+  }
+
+  pub fn budget(&self) -> &MtuBudget {
+    &self.budget
+  }
+
+  pub fn split(&mut self, packet: &Packet) -> Result<Vec<Fragment>> {
+    self.budget.check(packet)?;
+
+    let mut buf = BytesMut::new();
+    packet.encode(&mut buf);
+    let mut bytes = buf.freeze();
+
+    let seq = self.next_seq;
+    self.next_seq = self.next_seq.wrapping_add(1);
+
+    let count = self.budget.fragment_count(packet);
+    let data_len = self.budget.fragment_data_len();
+    let mut fragments = Vec::with_capacity(count);
+    for index in 0..count {
+      let len = std::cmp::min(data_len, bytes.len());
+      fragments.push(Fragment {
+        seq,
+        index: index as u8,
+        count: count as u8,
+        data: bytes.split_to(len),
+      });
+    }
+    Ok(fragments)
+  }
+}
+
+#[derive(Debug)]
+struct PendingPacket {
+  parts: Vec<Option<Bytes>>,
+  received: usize,
+}
+
+/// Merges fragments back into packets, fragments can arrive in any order.
+/// The oldest incomplete packets are dropped once more than `max_pending` packets are pending.
+#[derive(Debug)]
+pub struct Reassembler {
+  pending: BTreeMap<u16, PendingPacket>,
+  /// Pending sequence numbers in arrival order, sequence numbers wrap around
+  order: VecDeque<u16>,
+  max_pending: usize,
+}
+
+impl Default for Reassembler {
+  fn default() -> Self {
+    Self::new(DEFAULT_MAX_PENDING)
+  }
+}
+
+impl Reassembler {
+  pub fn new(max_pending: usize) -> Self {
+    Self {
+      pending: BTreeMap::new(),
+      order: VecDeque::new(),
+      max_pending: std::cmp::max(max_pending, 1),
+    }
+  }
+
+  pub fn pending_len(&self) -> usize {
+    self.pending.len()
+  }
+
+  /// Returns the packet if `fragment` completed it.
+  /// Malformed fragments are rejected, a pending packet is kept if a fragment doesn't match it
+  pub fn push(&mut self, fragment: Fragment) -> Result<Option<Packet>> {
+    if fragment.count == 0 || fragment.index >= fragment.count {
+      return Err(Error::InvalidFragment {
+        index: fragment.index,
+        count: fragment.count,
+      });
+    }
+
+    if fragment.count == 1 {
+      return decode_packet(fragment.data).map(Some);
+    }
+
+    if !self.pending.contains_key(&fragment.seq) {
+      self.order.push_back(fragment.seq);
+    }
+    let entry = self
+      .pending
+      .entry(fragment.seq)
+      .or_insert_with(|| PendingPacket {
+        parts: vec![None; fragment.count as usize],
+        received: 0,
+      });
+
+    if entry.parts.len() != fragment.count as usize {
+      return Err(Error::InvalidFragment {
+        index: fragment.index,
+        count: fragment.count,
+      });
+    }
+
+    let slot = &mut entry.parts[fragment.index as usize];
+    if slot.is_none() {
+      *slot = Some(fragment.data);
+      entry.received += 1;
+    }
+
+    if entry.received < entry.parts.len() {
+      while self.pending.len() > self.max_pending {
+        let seq = self.order.pop_front().expect("order is not empty");
+        self.pending.remove(&seq);
+      }
+      return Ok(None);
+    }
+
+    let entry = self.remove(fragment.seq).expect("entry exists");
+    let mut buf = BytesMut::new();
+    for part in entry.parts.into_iter().flatten() {
+      buf.put_slice(&part);
+    }
+    decode_packet(buf.freeze()).map(Some)
+  }
+
+  fn remove(&mut self, seq: u16) -> Option<PendingPacket> {
+    if let Some(pos) = self.order.iter().position(|v| *v == seq) {
+      self.order.remove(pos);
+    }
+    self.pending.remove(&seq)
+  }
+}
+
+fn decode_packet(bytes: Bytes) -> Result<Packet> {
+  let mut buf = BytesMut::from(bytes.as_ref());
+  let header = Header::decode(&mut buf)?;
+  let packet = Packet::decode(header, &mut buf)?;
+  if buf.has_remaining() {
+    return Err(Error::ExtraPayloadBytes(buf.remaining()));
+  }
+  Ok(packet)
+}
+
+#[test]
+fn test_largest_slot_info_fits_single_fragment() {
+  use crate::protocol::join::SlotInfoJoin;
+  use crate::protocol::slot::SlotInfo;
+
+  // 24 player slots is the largest lobby layout
+  let slot_info = SlotInfo::build()
+    .num_slots(24)
+    .num_players(24)
+    .random_seed(-1)
+    .build();
+  let packet = Packet::simple(SlotInfoJoin {
+    slot_info,
+    player_id: 24,
+    external_addr: SockAddr::new_null(),
+  })
+  .unwrap();
+
+  assert!(packet.get_encode_len() > PACKET_HEADER_LEN + 24 * 9);
+  assert!(MtuBudget::default().fits_single(&packet));
+  assert!(MtuBudget::new(MIN_MTU).fits_single(&packet));
+
+  let mut fragmenter = Fragmenter::default();
+  let fragments = fragmenter.split(&packet).unwrap();
+  assert_eq!(fragments.len(), 1);
+  assert!(fragments[0].encode_len() + IP_UDP_HEADER_LEN <= DEFAULT_MTU);
+
+  let mut reassembler = Reassembler::default();
+  let merged = reassembler
+    .push(fragments.into_iter().next().unwrap())
+    .unwrap()
+    .unwrap();
+  assert_eq!(merged.payload, packet.payload);
+}
+
+#[test]
+fn test_fragment_split_merge() {
+  use crate::protocol::constants::PacketTypeId;
+
+  let payload: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+  let packet = Packet {
+    header: Header::new(
+      PacketTypeId::IncomingAction2,
+      (payload.len() + PACKET_HEADER_LEN) as u16,
+    ),
+    payload: Bytes::from(payload),
+  };
+
+  let budget = MtuBudget::new(1200);
+  assert_eq!(budget.fragment_count(&packet), 3);
+
+  let mut fragmenter = Fragmenter::new(budget);
+  fragmenter.split(&packet).unwrap();
+  let mut fragments = fragmenter.split(&packet).unwrap();
+  assert!(fragments.iter().all(|f| f.seq == 1 && f.count == 3));
+  assert!(fragments
+    .iter()
+    .all(|f| f.encode_len() + IP_UDP_HEADER_LEN <= budget.mtu()));
+
+  // encode / decode, out of order
+  fragments.reverse();
+  let mut reassembler = Reassembler::default();
+  let mut merged = None;
+  for fragment in fragments {
+    let mut buf = BytesMut::new();
+    fragment.encode(&mut buf);
+    let fragment = Fragment::decode(&mut buf.freeze()).unwrap();
+    assert!(merged.is_none());
+    merged = reassembler.push(fragment).unwrap();
+  }
+  let merged = merged.unwrap();
+  assert_eq!(merged.type_id(), PacketTypeId::IncomingAction2);
+  assert_eq!(merged.payload, packet.payload);
+  assert_eq!(reassembler.pending_len(), 0);
+}
+
+#[test]
+fn test_fragment_limit() {
+  use crate::protocol::constants::PacketTypeId;
+
+  let budget = MtuBudget::new(MIN_MTU);
+  let len = budget.max_packet_len() + 1;
+  let packet = Packet {
+    header: Header::new(PacketTypeId::IncomingAction2, u16::MAX),
+    payload: Bytes::from(vec![0; len - PACKET_HEADER_LEN]),
+  };
+  assert!(matches!(
+    Fragmenter::new(budget).split(&packet),
+    Err(Error::FragmentLimitExceeded { .. })
+  ));
+
+  let mut reassembler = Reassembler::new(2);
+  for seq in 0..3 {
+    let fragment = Fragment {
+      seq,
+      index: 0,
+      count: 2,
+      data: Bytes::new(),
+    };
+    assert!(reassembler.push(fragment).unwrap().is_none());
+  }
+  assert_eq!(reassembler.pending_len(), 2);
+}
+
+#[test]
+fn test_reassembler_evict_across_seq_wrap() {
+  use crate::protocol::constants::PacketTypeId;
+
+  let payload: Vec<u8> = (0..2000).map(|i| i as u8).collect();
+  let packet = Packet {
+    header: Header::new(
+      PacketTypeId::IncomingAction2,
+      (payload.len() + PACKET_HEADER_LEN) as u16,
+    ),
+    payload: Bytes::from(payload),
+  };
+
+  let mut fragmenter = Fragmenter {
+    budget: MtuBudget::new(1200),
+    next_seq: u16::MAX - 1,
+  };
+  let packets: Vec<Vec<Fragment>> = (0..3).map(|_| fragmenter.split(&packet).unwrap()).collect();
+  let seqs: Vec<u16> = packets.iter().map(|fragments| fragments[0].seq).collect();
+  assert_eq!(seqs, vec![u16::MAX - 1, u16::MAX, 0]);
+
+  let mut reassembler = Reassembler::new(2);
+  for fragments in &packets {
+    assert!(reassembler.push(fragments[0].clone()).unwrap().is_none());
+  }
+  assert_eq!(reassembler.pending_len(), 2);
+
+  // the oldest packet was dropped, not the one with the lowest sequence number
+  for fragments in &packets[1..] {
+    let merged = reassembler.push(fragments[1].clone()).unwrap().unwrap();
+    assert_eq!(merged.payload, packet.payload);
+  }
+  assert_eq!(reassembler.pending_len(), 0);
+  assert!(reassembler.push(packets[0][1].clone()).unwrap().is_none());
+}
+
+#[test]
+fn test_reassembler_reject_malformed() {
+  let fragment = |index: u8, count: u8| Fragment {
+    seq: 1,
+    index,
+    count,
+    data: Bytes::new(),
+  };
+
+  let mut reassembler = Reassembler::default();
+  for (index, count) in [(0, 0), (2, 2), (5, 1)].iter().cloned() {
+    assert!(matches!(
+      reassembler.push(fragment(index, count)),
+      Err(Error::InvalidFragment { .. })
+    ));
+  }
+  assert_eq!(reassembler.pending_len(), 0);
+
+  assert!(reassembler.push(fragment(0, 3)).unwrap().is_none());
+  // doesn't match the pending packet, the fragment is dropped
+  assert!(matches!(
+    reassembler.push(fragment(1, 2)),
+    Err(Error::InvalidFragment { .. })
+  ));
+  assert_eq!(reassembler.pending_len(), 1);
+  assert!(reassembler.push(fragment(1, 3)).unwrap().is_none());
+}
@@ -0,0 +1,226 @@
+//! Shows a hosted game in the Local Area Network screen of game clients.
+//!
+//! The advertiser shares the LAN port with game clients running on the same machine,
+//! it broadcasts the game when it is created or changes and answers searches.
+
+use flo_util::binary::*;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+use tokio::net::UdpSocket;
+
+use crate::error::*;
+use crate::protocol::lan::{CreateGame, DecreateGame, GameInfo, RefreshGame, SearchGame, LAN_PORT};
+use crate::protocol::packet::{Packet, PacketPayload};
+
+/// Larger than any LAN message
+const RECV_BUF_SIZE: usize = 2048;
+
+#[derive(Debug)]
+pub struct W3GSLanAdvertiser {
+  socket: UdpSocket,
+  broadcast_addr: SocketAddr,
+  info: GameInfo,
+  slots_available: AtomicU32,
+  created_at: Instant,
+}
+
+impl W3GSLanAdvertiser {
+  pub fn bind(info: GameInfo) -> Result<Self> {
+    Self::bind_with_addrs(
+      SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, LAN_PORT).into(),
+      SocketAddrV4::new(Ipv4Addr::BROADCAST, LAN_PORT).into(),
+      info,
+    )
+  }
+
+  pub fn bind_with_addrs(
+    addr: SocketAddr,
+    broadcast_addr: SocketAddr,
+    info: GameInfo,
+  ) -> Result<Self> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_broadcast(true)?;
+    socket.bind(&addr.into())?;
+    socket.set_nonblocking(true)?;
+    let socket = UdpSocket::from_std(socket.into())?;
+    Ok(Self {
+      socket,
+      broadcast_addr,
+      slots_available: AtomicU32::new(info.slots_available),
+      info,
+      created_at: Instant::now(),
+    })
+  }
+
+  pub fn local_addr(&self) -> Result<SocketAddr> {
+    Ok(self.socket.local_addr()?)
+  }
+
+  /// The reply to searches
+  pub fn game_info(&self) -> GameInfo {
+    let mut info = self.info.clone();
+    info.slots_available = self.slots_available.load(Ordering::Relaxed);
+    info.uptime_secs = self.created_at.elapsed().as_secs() as u32;
+    info
+  }
+
+  /// Broadcasts the game to clients in the LAN screen
+  pub async fn create(&self) -> Result<()> {
+    self
+      .broadcast(CreateGame {
+        product: self.info.product,
+        version: self.info.version,
+        game_id: self.info.game_id,
+      })
+      .await?;
+    self.broadcast(self.game_info()).await
+  }
+
+  pub async fn refresh(&self, slots_available: u32) -> Result<()> {
+    self
+      .slots_available
+      .store(slots_available, Ordering::Relaxed);
+    self
+      .broadcast(RefreshGame {
+        game_id: self.info.game_id,
+        players: self.info.slots_total.saturating_sub(slots_available),
+        slots_total: self.info.slots_total,
+      })
+      .await
+  }
+
+  /// Removes the game from the LAN screen, sent when it starts or ends
+  pub async fn decreate(&self) -> Result<()> {
+    self
+      .broadcast(DecreateGame {
+        game_id: self.info.game_id,
+      })
+      .await
+  }
+
+  /// Answers searches of clients running the same game version.
+  /// Datagrams that aren't searches are ignored
+  pub async fn serve(&self) -> Result<()> {
+    let mut buf = vec![0_u8; RECV_BUF_SIZE];
+    loop {
+      let (len, from) = self.socket.recv_from(&mut buf).await?;
+      let search: SearchGame = match decode_datagram(&buf[..len]) {
+        Some(v) => v,
+        None => continue,
+      };
+      if search.product == self.info.product && search.version == self.info.version {
+        self.send_to(self.game_info(), from).await?;
+      }
+    }
+  }
+
+  async fn broadcast<T>(&self, payload: T) -> Result<()>
+  where
+    T: PacketPayload + BinEncode + std::fmt::Debug,
+  {
+    self.send_to(payload, self.broadcast_addr).await
+  }
+
+  async fn send_to<T>(&self, payload: T, addr: SocketAddr) -> Result<()>
+  where
+    T: PacketPayload + BinEncode + std::fmt::Debug,
+  {
+    let mut buf = BytesMut::new();
+    Packet::simple(payload)?.encode(&mut buf);
+    self.socket.send_to(&buf, addr).await?;
+    Ok(())
+  }
+}
+
+/// Returns `None` if the datagram isn't a valid `T`
+pub fn decode_datagram<T>(bytes: &[u8]) -> Option<T>
+where
+  T: PacketPayload + BinDecode,
+{
+  let mut buf = BytesMut::from(bytes);
+  let header = Packet::decode_header(&mut buf).ok()?;
+  if header.type_id != T::PACKET_TYPE_ID {
+    return None;
+  }
+  let packet = Packet::decode(header, &mut buf).ok()?;
+  packet.decode_simple().ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_lan_advertiser() {
+    use crate::protocol::constants::GameSettingFlags;
+    use crate::protocol::game::{GameSettings, GameSettingsMap};
+
+    let client = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    let info = GameInfo::new(
+      26,
+      1,
+      0x1234,
+      "flo",
+      GameSettings::new(
+        GameSettingFlags::SPEED_FAST,
+        GameSettingsMap {
+          path: "Maps\\(2)EchoIsles.w3x".to_string(),
+          width: 116,
+          height: 116,
+          sha1: [0; 20],
+          checksum: 0,
+        },
+      ),
+      2,
+      6113,
+    );
+    let advertiser = W3GSLanAdvertiser::bind_with_addrs(
+      SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0).into(),
+      client.local_addr().unwrap(),
+      info.clone(),
+    )
+    .unwrap();
+    let advertiser_addr = advertiser.local_addr().unwrap();
+    let mut buf = vec![0_u8; RECV_BUF_SIZE];
+
+    advertiser.create().await.unwrap();
+    let len = client.recv(&mut buf).await.unwrap();
+    let create: CreateGame = decode_datagram(&buf[..len]).unwrap();
+    assert_eq!(create.game_id, 1);
+    let len = client.recv(&mut buf).await.unwrap();
+    let broadcast_info: GameInfo = decode_datagram(&buf[..len]).unwrap();
+    assert_eq!(broadcast_info.game_name, info.game_name);
+
+    advertiser.refresh(1).await.unwrap();
+    let len = client.recv(&mut buf).await.unwrap();
+    let refresh: RefreshGame = decode_datagram(&buf[..len]).unwrap();
+    assert_eq!(refresh.players, 1);
+
+    tokio::select! {
+      res = advertiser.serve() => panic!("serve exited: {:?}", res),
+      _ = async {
+        let mut search = BytesMut::new();
+        // other versions are ignored
+        Packet::simple(SearchGame::new(27)).unwrap().encode(&mut search);
+        client.send_to(&search, advertiser_addr).await.unwrap();
+        search.clear();
+        Packet::simple(SearchGame::new(26)).unwrap().encode(&mut search);
+        client.send_to(&search, advertiser_addr).await.unwrap();
+
+        let len = client.recv(&mut buf).await.unwrap();
+        let reply: GameInfo = decode_datagram(&buf[..len]).unwrap();
+        assert_eq!(reply.version, 26);
+        assert_eq!(reply.slots_available, 1);
+        assert_eq!(reply.port, 6113);
+      } => {}
+    }
+
+    advertiser.decreate().await.unwrap();
+    let len = client.recv(&mut buf).await.unwrap();
+    let decreate: DecreateGame = decode_datagram(&buf[..len]).unwrap();
+    assert_eq!(decreate.game_id, 1);
+  }
+}
@@ -0,0 +1,201 @@
+//! Opt-in packet metrics of `W3GSCodec`.
+//!
+//! Counts packets and bytes per packet type in both directions, buckets how long received
+//! packets took from their header to the end of their payload, and keeps the raw bytes of
+//! the last packets that failed to decode.
+
+use flo_util::binary::Bytes;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::protocol::constants::PacketTypeId;
+use crate::protocol::packet::Packet;
+
+/// Upper bounds of the receive latency buckets, the last bucket has no upper bound
+pub const LATENCY_BUCKETS: [Duration; 6] = [
+  Duration::from_millis(1),
+  Duration::from_millis(5),
+  Duration::from_millis(20),
+  Duration::from_millis(100),
+  Duration::from_millis(500),
+  Duration::from_millis(2000),
+];
+/// Bytes kept of a malformed packet
+pub const MAX_CAPTURE_LEN: usize = 512;
+
+/// Can be shared by the codecs of many streams
+#[derive(Debug)]
+pub struct CodecMetrics {
+  packets: Mutex<BTreeMap<u8, PacketTypeMetrics>>,
+  capture: Mutex<Capture>,
+}
+
+#[derive(Debug)]
+struct Capture {
+  capacity: usize,
+  total: u64,
+  packets: VecDeque<MalformedPacket>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketTypeMetrics {
+  pub type_id: PacketTypeId,
+  pub received: u64,
+  pub received_bytes: u64,
+  pub sent: u64,
+  pub sent_bytes: u64,
+  /// Received packets per `LATENCY_BUCKETS` entry, plus one for longer latencies
+  pub latency_buckets: [u64; LATENCY_BUCKETS.len() + 1],
+}
+
+impl PacketTypeMetrics {
+  fn new(type_id: PacketTypeId) -> Self {
+    Self {
+      type_id,
+      received: 0,
+      received_bytes: 0,
+      sent: 0,
+      sent_bytes: 0,
+      latency_buckets: [0; LATENCY_BUCKETS.len() + 1],
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct MalformedPacket {
+  pub time: SystemTime,
+  /// Header and payload as received, truncated to `MAX_CAPTURE_LEN`
+  pub bytes: Bytes,
+  pub error: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CodecMetricsSnapshot {
+  /// Ordered by packet type id
+  pub packets: Vec<PacketTypeMetrics>,
+  /// Including packets dropped from the capture
+  pub malformed: u64,
+}
+
+impl CodecMetrics {
+  /// Keeps the last `capture_capacity` malformed packets
+  pub fn new(capture_capacity: usize) -> Self {
+    Self {
+      packets: Mutex::new(BTreeMap::new()),
+      capture: Mutex::new(Capture {
+        capacity: capture_capacity,
+        total: 0,
+        packets: VecDeque::with_capacity(capture_capacity),
+      }),
+    }
+  }
+
+  pub(crate) fn record_received(&self, type_id: PacketTypeId, len: usize, latency: Duration) {
+    let bucket = LATENCY_BUCKETS
+      .iter()
+      .position(|bound| latency <= *bound)
+      .unwrap_or(LATENCY_BUCKETS.len());
+    let mut packets = self.packets.lock().unwrap();
+    let entry = packets
+      .entry(type_id.into())
+      .or_insert_with(|| PacketTypeMetrics::new(type_id));
+    entry.received += 1;
+    entry.received_bytes += len as u64;
+    entry.latency_buckets[bucket] += 1;
+  }
+
+  pub(crate) fn record_sent(&self, type_id: PacketTypeId, len: usize) {
+    let mut packets = self.packets.lock().unwrap();
+    let entry = packets
+      .entry(type_id.into())
+      .or_insert_with(|| PacketTypeMetrics::new(type_id));
+    entry.sent += 1;
+    entry.sent_bytes += len as u64;
+  }
+
+  /// Records bytes that failed to decode
+  pub fn capture_bytes(&self, bytes: &[u8], error: &dyn std::error::Error) {
+    let mut capture = self.capture.lock().unwrap();
+    capture.total += 1;
+    if capture.capacity == 0 {
+      return;
+    }
+    if capture.packets.len() == capture.capacity {
+      capture.packets.pop_front();
+    }
+    capture.packets.push_back(MalformedPacket {
+      time: SystemTime::now(),
+      bytes: Bytes::copy_from_slice(&bytes[..bytes.len().min(MAX_CAPTURE_LEN)]),
+      error: error.to_string(),
+    });
+  }
+
+  /// Records a packet whose payload failed to decode
+  pub fn capture_packet(&self, packet: &Packet, error: &dyn std::error::Error) {
+    let mut bytes = flo_util::binary::BytesMut::new();
+    packet.encode(&mut bytes);
+    self.capture_bytes(&bytes, error)
+  }
+
+  pub fn snapshot(&self) -> CodecMetricsSnapshot {
+    let packets = self.packets.lock().unwrap().values().cloned().collect();
+    CodecMetricsSnapshot {
+      packets,
+      malformed: self.capture.lock().unwrap().total,
+    }
+  }
+
+  /// Oldest first
+  pub fn malformed_packets(&self) -> Vec<MalformedPacket> {
+    self
+      .capture
+      .lock()
+      .unwrap()
+      .packets
+      .iter()
+      .cloned()
+      .collect()
+  }
+
+  /// Clears the capture
+  pub fn take_malformed_packets(&self) -> Vec<MalformedPacket> {
+    self.capture.lock().unwrap().packets.drain(..).collect()
+  }
+}
+
+#[test]
+fn test_codec_metrics() {
+  use crate::error::Error;
+
+  let metrics = CodecMetrics::new(2);
+  metrics.record_received(PacketTypeId::PingFromHost, 8, Duration::from_millis(0));
+  metrics.record_received(PacketTypeId::PingFromHost, 8, Duration::from_millis(30));
+  metrics.record_received(PacketTypeId::ChatToHost, 20, Duration::from_secs(10));
+  metrics.record_sent(PacketTypeId::PingFromHost, 8);
+
+  let snapshot = metrics.snapshot();
+  assert_eq!(snapshot.packets.len(), 2);
+  let ping = &snapshot.packets[0];
+  assert_eq!(ping.type_id, PacketTypeId::PingFromHost);
+  assert_eq!((ping.received, ping.received_bytes), (2, 16));
+  assert_eq!((ping.sent, ping.sent_bytes), (1, 8));
+  assert_eq!(ping.latency_buckets, [1, 0, 0, 1, 0, 0, 0]);
+  assert_eq!(
+    snapshot.packets[1].latency_buckets[LATENCY_BUCKETS.len()],
+    1
+  );
+
+  for len in 1..=3 {
+    metrics.capture_bytes(&vec![0; len], &Error::InvalidPacketLength(len as u16));
+  }
+  metrics.capture_bytes(&[0; MAX_CAPTURE_LEN + 1], &Error::StreamClosed);
+  assert_eq!(metrics.snapshot().malformed, 4);
+  let captured = metrics.take_malformed_packets();
+  assert_eq!(
+    captured.iter().map(|v| v.bytes.len()).collect::<Vec<_>>(),
+    vec![3, MAX_CAPTURE_LEN]
+  );
+  assert_eq!(captured[0].error, "invalid packet length: 3");
+  assert!(metrics.malformed_packets().is_empty());
+}
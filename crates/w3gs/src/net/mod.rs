@@ -1,8 +1,11 @@
 use futures::sink::SinkExt;
 use futures::stream::TryStreamExt;
 use futures::{ready, StreamExt};
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::ops::Range;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 use tokio_stream::Stream;
@@ -12,30 +15,93 @@ use crate::error::*;
 use crate::protocol::packet::{Packet, PacketPayload, PacketPayloadDecode};
 
 mod codec;
+pub mod fragment;
+mod lan;
+pub mod metrics;
+mod socket;
 use self::codec::W3GSCodec;
+pub use self::lan::{decode_datagram, W3GSLanAdvertiser};
+use self::metrics::{CodecMetrics, MalformedPacket};
+pub use self::socket::{KeepaliveConfig, SocketConfig};
 
 #[derive(Debug)]
 pub struct W3GSListener {
   listener: TcpListener,
   local_addr: SocketAddr,
+  config: SocketConfig,
 }
 
 impl W3GSListener {
   pub async fn bind() -> Result<Self, Error> {
-    let listener = TcpListener::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)).await?;
+    Self::bind_with_config(SocketConfig::global().clone()).await
+  }
+
+  pub async fn bind_with_config(config: SocketConfig) -> Result<Self, Error> {
+    Self::bind_addr_with_config(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0).into(), config).await
+  }
+
+  /// Binds `addr`, an IPv6 unspecified address also accepts IPv4 clients
+  pub async fn bind_addr(addr: SocketAddr) -> Result<Self, Error> {
+    Self::bind_addr_with_config(addr, SocketConfig::global().clone()).await
+  }
+
+  pub async fn bind_addr_with_config(
+    addr: SocketAddr,
+    config: SocketConfig,
+  ) -> Result<Self, Error> {
+    let listener = config.bind(addr)?;
+    Self::from_listener(listener, config)
+  }
+
+  /// Binds the first free port of `ports` on all IPv4 interfaces
+  pub async fn bind_port_range(ports: Range<u16>) -> Result<Self, Error> {
+    Self::bind_port_range_with_config(
+      Ipv4Addr::UNSPECIFIED.into(),
+      ports,
+      SocketConfig::global().clone(),
+    )
+    .await
+  }
+
+  pub async fn bind_port_range_with_config(
+    ip: IpAddr,
+    ports: Range<u16>,
+    config: SocketConfig,
+  ) -> Result<Self, Error> {
+    for port in ports.clone() {
+      match config.bind(SocketAddr::new(ip, port)) {
+        Ok(listener) => return Self::from_listener(listener, config),
+        // Windows reports ports reserved by the system as access denied
+        Err(err)
+          if err.kind() == io::ErrorKind::AddrInUse
+            || err.kind() == io::ErrorKind::PermissionDenied =>
+        {
+          continue
+        }
+        Err(err) => return Err(err.into()),
+      }
+    }
+    Err(Error::PortRangeExhausted {
+      start: ports.start,
+      end: ports.end,
+    })
+  }
+
+  fn from_listener(listener: TcpListener, config: SocketConfig) -> Result<Self, Error> {
     let local_addr = listener.local_addr()?;
     Ok(W3GSListener {
       listener,
       local_addr,
+      config,
     })
   }
 
   pub fn incoming(&mut self) -> Incoming {
-    Incoming::new(&mut self.listener)
+    Incoming::new(&mut self.listener, &self.config)
   }
 
   pub async fn accept(&mut self) -> Result<Option<W3GSStream>> {
-    match Incoming::new(&mut self.listener).next().await {
+    match Incoming::new(&mut self.listener, &self.config).next().await {
       None => Ok(None),
       Some(res) => Ok(Some(res?)),
     }
@@ -74,6 +140,31 @@ impl W3GSStream {
     self.peer_addr
   }
 
+  /// Starts recording packet metrics and malformed packets into `metrics`,
+  /// which can be shared with other streams
+  pub fn enable_metrics(&mut self, metrics: Arc<CodecMetrics>) {
+    self.transport.codec_mut().set_metrics(Some(metrics))
+  }
+
+  pub fn metrics(&self) -> Option<&Arc<CodecMetrics>> {
+    self.transport.codec().metrics()
+  }
+
+  /// Last packets that failed to decode, empty if metrics are disabled
+  pub fn malformed_packets(&self) -> Vec<MalformedPacket> {
+    self
+      .metrics()
+      .map(|metrics| metrics.malformed_packets())
+      .unwrap_or_default()
+  }
+
+  /// Captures a received packet whose payload failed to decode outside of the stream
+  pub fn capture_malformed(&self, packet: &Packet, error: &Error) {
+    if let Some(metrics) = self.metrics() {
+      metrics.capture_packet(packet, error)
+    }
+  }
+
   #[inline]
   pub async fn send(&mut self, packet: Packet) -> Result<()> {
     self.transport.send(packet).await?;
@@ -110,7 +201,9 @@ impl W3GSStream {
       });
     }
 
-    Ok(pkt.decode_payload()?)
+    pkt
+      .decode_payload()
+      .inspect_err(|err| self.capture_malformed(&pkt, err))
   }
 
   #[inline]
@@ -123,21 +216,22 @@ impl W3GSStream {
 
 pub struct Incoming<'a> {
   inner: &'a mut TcpListener,
+  config: &'a SocketConfig,
 }
 
-impl Incoming<'_> {
-  pub(crate) fn new(listener: &mut TcpListener) -> Incoming<'_> {
-    Incoming { inner: listener }
+impl<'a> Incoming<'a> {
+  pub(crate) fn new(listener: &'a mut TcpListener, config: &'a SocketConfig) -> Incoming<'a> {
+    Incoming {
+      inner: listener,
+      config,
+    }
   }
 
   #[inline]
   pub fn poll_accept(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<W3GSStream>> {
     let (socket, addr) = ready!(self.inner.poll_accept(cx))?;
 
-    socket.set_nodelay(true).ok();
-
-    //TODO: for now not supported by tokio https://github.com/tokio-rs/tokio/pull/3146
-    //socket.set_keepalive(None).ok();
+    self.config.apply(&socket).ok();
 
     let stream = W3GSStream {
       local_addr: socket.local_addr()?,
@@ -158,3 +252,71 @@ impl Stream for Incoming<'_> {
     Poll::Ready(Some(Ok(stream)))
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_stream_metrics() {
+    use crate::protocol::constants::PacketTypeId;
+    use crate::protocol::ping::PingFromHost;
+
+    let mut listener = W3GSListener::bind_addr((Ipv4Addr::LOCALHOST, 0).into())
+      .await
+      .unwrap();
+    let mut client = W3GSStream::connect(listener.local_addr()).await.unwrap();
+    let mut server = listener.accept().await.unwrap().unwrap();
+    let metrics = Arc::new(CodecMetrics::new(8));
+    server.enable_metrics(metrics.clone());
+
+    client
+      .send(Packet::simple(PingFromHost::with_payload(1)).unwrap())
+      .await
+      .unwrap();
+    // not a `PingFromHost` payload
+    client
+      .send(Packet {
+        header: crate::protocol::packet::Header::new(PacketTypeId::PingFromHost, 5),
+        payload: flo_util::binary::Bytes::from_static(&[1]),
+      })
+      .await
+      .unwrap();
+
+    for _ in 0..2 {
+      let packet = server.recv().await.unwrap().unwrap();
+      if let Err(err) = packet.decode_simple::<PingFromHost>() {
+        server.capture_malformed(&packet, &err);
+      }
+    }
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.packets[0].received, 2);
+    assert_eq!(snapshot.malformed, 1);
+    assert_eq!(
+      server.malformed_packets()[0].bytes.as_ref(),
+      &[0xF7, 0x01, 0x05, 0x00, 0x01]
+    );
+  }
+
+  #[tokio::test]
+  async fn test_listener_bind_port_range() {
+    let ip = IpAddr::from(Ipv4Addr::LOCALHOST);
+    let first = W3GSListener::bind_addr((ip, 0).into()).await.unwrap();
+    let port = first.port();
+
+    match W3GSListener::bind_port_range_with_config(ip, port..port + 1, SocketConfig::default())
+      .await
+    {
+      Err(Error::PortRangeExhausted { start, end }) => assert_eq!((start, end), (port, port + 1)),
+      other => panic!("unexpected result: {:?}", other),
+    }
+
+    let next =
+      W3GSListener::bind_port_range_with_config(ip, port..port + 16, SocketConfig::default())
+        .await
+        .unwrap();
+    assert!(next.port() > port && next.port() < port + 16);
+    assert_eq!(next.local_addr().ip(), ip);
+  }
+}
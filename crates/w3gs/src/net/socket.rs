@@ -0,0 +1,198 @@
+//! TCP socket options of listeners and accepted connections.
+//!
+//! Loaded from env by `SocketConfig::from_env`, unset values keep the OS defaults:
+//!
+//! - `FLO_SOCKET_NODELAY`: `0` or `false` to enable Nagle's algorithm, enabled by default
+//! - `FLO_SOCKET_SEND_BUFFER_SIZE`, `FLO_SOCKET_RECV_BUFFER_SIZE`: `SO_SNDBUF`/`SO_RCVBUF` in bytes
+//! - `FLO_SOCKET_KEEPALIVE_SECS`: idle time before keepalive probes are sent
+//! - `FLO_SOCKET_KEEPALIVE_INTERVAL_SECS`: time between keepalive probes
+//! - `FLO_SOCKET_KEEPALIVE_RETRIES`: unanswered probes before the connection is dropped
+//! - `FLO_SOCKET_BACKLOG`: accept backlog, 1024 by default
+
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+
+const DEFAULT_BACKLOG: u32 = 1024;
+
+lazy_static::lazy_static! {
+  static ref SOCKET_CONFIG: SocketConfig = SocketConfig::from_env();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SocketConfig {
+  pub nodelay: bool,
+  pub send_buffer_size: Option<usize>,
+  pub recv_buffer_size: Option<usize>,
+  pub keepalive: Option<KeepaliveConfig>,
+  pub backlog: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeepaliveConfig {
+  pub time: Duration,
+  /// Not supported on some platforms, ignored there
+  pub interval: Option<Duration>,
+  /// Not supported on Windows, ignored there
+  pub retries: Option<u32>,
+}
+
+impl Default for SocketConfig {
+  fn default() -> Self {
+    Self {
+      nodelay: true,
+      send_buffer_size: None,
+      recv_buffer_size: None,
+      keepalive: None,
+      backlog: DEFAULT_BACKLOG,
+    }
+  }
+}
+
+impl SocketConfig {
+  /// Loaded once, used by listeners bound without a config
+  pub fn global() -> &'static SocketConfig {
+    &SOCKET_CONFIG
+  }
+
+  pub fn from_env() -> Self {
+    Self::from_vars(|name| std::env::var(name).ok())
+  }
+
+  fn from_vars<F>(get: F) -> Self
+  where
+    F: Fn(&str) -> Option<String>,
+  {
+    let parse = |name: &str| get(name).and_then(|v| v.trim().parse::<u64>().ok());
+    let default = Self::default();
+    Self {
+      nodelay: get("FLO_SOCKET_NODELAY")
+        .map(|v| !matches!(v.trim(), "0" | "false"))
+        .unwrap_or(default.nodelay),
+      send_buffer_size: parse("FLO_SOCKET_SEND_BUFFER_SIZE").map(|v| v as usize),
+      recv_buffer_size: parse("FLO_SOCKET_RECV_BUFFER_SIZE").map(|v| v as usize),
+      keepalive: parse("FLO_SOCKET_KEEPALIVE_SECS").map(|secs| KeepaliveConfig {
+        time: Duration::from_secs(secs),
+        interval: parse("FLO_SOCKET_KEEPALIVE_INTERVAL_SECS").map(Duration::from_secs),
+        retries: parse("FLO_SOCKET_KEEPALIVE_RETRIES").map(|v| v as u32),
+      }),
+      backlog: parse("FLO_SOCKET_BACKLOG")
+        .map(|v| v as u32)
+        .unwrap_or(default.backlog),
+    }
+  }
+
+  /// Buffer sizes set on the listener are inherited by accepted connections,
+  /// receive buffers above 64KB only get a large enough window scale this way
+  pub fn bind(&self, addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    if addr.is_ipv6() {
+      // dual stack, the default differs between platforms
+      socket.set_only_v6(false)?;
+    }
+    self.set_buffer_sizes(&socket)?;
+    socket.bind(&addr.into())?;
+    socket.listen(self.backlog as i32)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+  }
+
+  /// Applies the options to an accepted or connected stream
+  pub fn apply(&self, stream: &TcpStream) -> std::io::Result<()> {
+    stream.set_nodelay(self.nodelay)?;
+    let socket = SockRef::from(stream);
+    self.set_buffer_sizes(&socket)?;
+    if let Some(ref keepalive) = self.keepalive {
+      socket.set_tcp_keepalive(&keepalive.to_tcp_keepalive())?;
+    }
+    Ok(())
+  }
+
+  fn set_buffer_sizes(&self, socket: &Socket) -> std::io::Result<()> {
+    if let Some(size) = self.send_buffer_size {
+      socket.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = self.recv_buffer_size {
+      socket.set_recv_buffer_size(size)?;
+    }
+    Ok(())
+  }
+}
+
+impl KeepaliveConfig {
+  fn to_tcp_keepalive(&self) -> TcpKeepalive {
+    let keepalive = TcpKeepalive::new().with_time(self.time);
+    #[cfg(any(target_os = "linux", target_vendor = "apple", windows))]
+    let keepalive = match self.interval {
+      Some(interval) => keepalive.with_interval(interval),
+      None => keepalive,
+    };
+    #[cfg(any(target_os = "linux", target_vendor = "apple"))]
+    let keepalive = match self.retries {
+      Some(retries) => keepalive.with_retries(retries),
+      None => keepalive,
+    };
+    keepalive
+  }
+}
+
+#[test]
+fn test_socket_config_from_vars() {
+  let config = SocketConfig::from_vars(|_| None);
+  assert_eq!(config, SocketConfig::default());
+
+  let config = SocketConfig::from_vars(|name| {
+    match name {
+      "FLO_SOCKET_NODELAY" => Some("false"),
+      "FLO_SOCKET_RECV_BUFFER_SIZE" => Some("262144"),
+      "FLO_SOCKET_KEEPALIVE_SECS" => Some("30"),
+      "FLO_SOCKET_KEEPALIVE_RETRIES" => Some("4"),
+      "FLO_SOCKET_BACKLOG" => Some("invalid"),
+      _ => None,
+    }
+    .map(ToString::to_string)
+  });
+  assert_eq!(
+    config,
+    SocketConfig {
+      nodelay: false,
+      send_buffer_size: None,
+      recv_buffer_size: Some(262144),
+      keepalive: Some(KeepaliveConfig {
+        time: Duration::from_secs(30),
+        interval: None,
+        retries: Some(4),
+      }),
+      backlog: DEFAULT_BACKLOG,
+    }
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_socket_config_bind() {
+    let config = SocketConfig {
+      recv_buffer_size: Some(128 * 1024),
+      keepalive: Some(KeepaliveConfig {
+        time: Duration::from_secs(30),
+        interval: Some(Duration::from_secs(5)),
+        retries: Some(3),
+      }),
+      ..Default::default()
+    };
+    let listener = config.bind(([127, 0, 0, 1], 0).into()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stream, accepted) = tokio::join!(TcpStream::connect(addr), listener.accept());
+    let (accepted, _) = accepted.unwrap();
+    config.apply(&accepted).unwrap();
+    config.apply(&stream.unwrap()).unwrap();
+    assert!(accepted.nodelay().unwrap());
+    assert!(SockRef::from(&accepted).keepalive().unwrap());
+  }
+}
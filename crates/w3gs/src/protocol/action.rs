@@ -39,14 +39,7 @@ impl PacketPayloadDecode for OutgoingAction {
 
     let checksum = u32::decode(buf)?;
 
-    if !buf.has_remaining() {
-      return Err(
-        BinDecodeError::incomplete()
-          .context("OutgoingAction data")
-          .into(),
-      );
-    }
-
+    // an empty action block is valid, its checksum is 0
     let data = buf.split_to(buf.remaining());
 
     let mut crc32 = crc32fast::Hasher::new();
@@ -174,16 +167,15 @@ impl TimeSlot {
       let mut actions = vec![];
       for action in self.actions {
         let action_len = action.byte_len();
-        if data_len + action_len > Self::MAX_ACTION_DATA_LEN {
+        if data_len + action_len > Self::MAX_ACTION_DATA_LEN && !actions.is_empty() {
           data_len = 0;
           payloads.push(TimeSlot {
             time_increment_ms: 0,
-            actions: std::mem::replace(&mut actions, vec![action]),
+            actions: std::mem::replace(&mut actions, vec![]),
           });
-        } else {
-          data_len = data_len + action_len;
-          actions.push(action)
         }
+        data_len = data_len + action_len;
+        actions.push(action)
       }
 
       if !actions.is_empty() {
@@ -347,6 +339,8 @@ fn crc16(data: &[u8]) -> u16 {
   (crc32.finalize() & 0x0000FFFF) as u16
 }
 
+/// Decodes the actions of a `PlayerAction` block.
+/// Actions have no length prefix, nothing after the first error can be decoded
 pub struct ActionIter {
   data: Bytes,
 }
@@ -356,13 +350,114 @@ impl Iterator for ActionIter {
 
   fn next(&mut self) -> Option<Self::Item> {
     if self.data.has_remaining() {
-      Some(Action::decode(&mut self.data).map_err(Into::into))
+      let res = Action::decode(&mut self.data).map_err(Into::into);
+      if res.is_err() {
+        self.data.clear();
+      }
+      Some(res)
     } else {
       None
     }
   }
 }
 
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  OutgoingAction,
+  bytes(TimeSlot::MAX_ACTION_DATA_LEN).prop_map(|data| OutgoingAction::new(&data))
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PlayerAction,
+  (any::<u8>(), bytes(256)).prop_map(|(player_id, data)| PlayerAction { player_id, data })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  TimeSlot,
+  (
+    any::<u16>(),
+    proptest::collection::vec(any::<PlayerAction>(), 0..=24)
+  )
+    .prop_map(|(time_increment_ms, actions)| TimeSlot {
+      time_increment_ms,
+      actions,
+    })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  IncomingAction,
+  any::<TimeSlot>().prop_map(IncomingAction)
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  IncomingAction2,
+  any::<TimeSlot>().prop_map(IncomingAction2)
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  OutgoingKeepAlive,
+  (any::<u8>(), any::<u32>())
+    .prop_map(|(unknown, checksum)| OutgoingKeepAlive { unknown, checksum })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_action_roundtrip(
+    outgoing: OutgoingAction,
+    incoming: IncomingAction,
+    incoming2: IncomingAction2,
+    keep_alive: OutgoingKeepAlive,
+  ) {
+    crate::packet::test_payload_roundtrip(&outgoing);
+    crate::packet::test_payload_roundtrip(&incoming);
+    crate::packet::test_payload_roundtrip(&incoming2);
+    crate::packet::test_simple_payload_roundtrip(&keep_alive);
+  }
+
+  #[test]
+  fn test_time_slot_split_chunks(slot: TimeSlot) {
+    let time_increment_ms = slot.time_increment_ms;
+    let actions = slot.actions.clone();
+    let chunks: Vec<_> = slot.split_chunks().collect();
+    for chunk in &chunks {
+      let data_len: usize = chunk.actions.iter().map(PlayerAction::byte_len).sum();
+      proptest::prop_assert!(data_len <= TimeSlot::MAX_ACTION_DATA_LEN);
+    }
+    for chunk in chunks.iter().rev().skip(1) {
+      proptest::prop_assert_eq!(chunk.time_increment_ms, 0);
+    }
+    proptest::prop_assert_eq!(chunks.last().unwrap().time_increment_ms, time_increment_ms);
+    proptest::prop_assert_eq!(
+      chunks.into_iter().flat_map(|chunk| chunk.actions).collect::<Vec<_>>(),
+      actions
+    );
+  }
+}
+
+#[test]
+fn test_action_iter_stops_on_error() {
+  let action = PlayerAction {
+    player_id: 1,
+    // EscPressed, an unknown action id and an EscPressed that can't be told apart from garbage
+    data: Bytes::from_static(&[0x61, 0xFF, 0x61]),
+  };
+  let mut iter = action.actions();
+  assert!(matches!(iter.next(), Some(Ok(Action::EscPressed))));
+  assert!(matches!(iter.next(), Some(Err(_))));
+  assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_outgoing_action_empty() {
+  crate::packet::test_payload_roundtrip(&OutgoingAction::new(&[]));
+}
+
 #[test]
 fn test_outgoing_action() {
   let mut buf = Bytes::from(flo_util::sample_bytes!("packet", "outgoing_action.bin")).split_off(4);
@@ -0,0 +1,74 @@
+//! Proptest strategies shared by the `Arbitrary` implementations of the packet payloads.
+//! Generated values are the ones a peer can put on the wire, so they survive an
+//! encode -> decode -> encode round trip unchanged.
+
+use flo_util::binary::*;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::strategy::LazyJust;
+use std::fmt::Debug;
+
+/// Implements `proptest::arbitrary::Arbitrary` for `$ty` with a boxed `$strategy`,
+/// the strategies of this module and the proptest prelude are in scope of `$strategy`
+macro_rules! impl_arbitrary {
+  ($ty:ty, $strategy:expr) => {
+    impl proptest::arbitrary::Arbitrary for $ty {
+      type Parameters = ();
+      type Strategy = proptest::strategy::BoxedStrategy<Self>;
+
+      fn arbitrary_with(_: ()) -> Self::Strategy {
+        #[allow(unused_imports)]
+        use crate::protocol::arbitrary::*;
+        #[allow(unused_imports)]
+        use proptest::prelude::*;
+        ($strategy).boxed()
+      }
+    }
+  };
+}
+
+pub(crate) use impl_arbitrary;
+
+/// Strings without interior NULs, including the empty string
+pub(crate) fn c_string(max_len: usize) -> impl Strategy<Value = CString> {
+  vec(1..=u8::MAX, 0..=max_len).prop_map(|bytes| CString::new(bytes).expect("no interior NUL"))
+}
+
+/// Null, IPv4 and unknown family addresses
+pub(crate) fn sock_addr() -> impl Strategy<Value = SockAddr> {
+  prop_oneof![
+    LazyJust::new(SockAddr::new_null),
+    (any::<[u8; 4]>(), any::<u16>()).prop_map(|(ip, port)| SockAddr::new_ipv4(ip, port)),
+    (
+      any::<u16>().prop_filter("not IPv4", |v| *v != 2),
+      any::<[u8; 14]>()
+    )
+      .prop_map(|(family, unknown)| {
+        let mut bytes = family.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&unknown);
+        SockAddr::decode(&mut bytes.as_slice()).expect("unknown family address")
+      }),
+  ]
+}
+
+/// `u8` represented enums, known and unknown values
+pub(crate) fn enum_u8<T>() -> impl Strategy<Value = T>
+where
+  T: BinDecode + Debug,
+{
+  any::<u8>().prop_map(|v| T::decode(&mut &[v][..]).expect("u8 enum decodes any value"))
+}
+
+/// `u32` represented enums, known values are small so they are generated more often
+pub(crate) fn enum_u32<T>() -> impl Strategy<Value = T>
+where
+  T: BinDecode + Debug,
+{
+  prop_oneof![0..=0xFF_u32, any::<u32>()]
+    .prop_map(|v| T::decode(&mut &v.to_le_bytes()[..]).expect("u32 enum decodes any value"))
+}
+
+/// Payload bytes, empty included
+pub(crate) fn bytes(max_len: usize) -> impl Strategy<Value = Bytes> {
+  vec(any::<u8>(), 0..=max_len).prop_map(Bytes::from)
+}
@@ -146,6 +146,7 @@ pub enum MessageScope {
   All,
   Allies,
   Observers,
+  /// Private message to a player id, player ids start at 1
   Player(u8),
 }
 
@@ -159,7 +160,7 @@ impl BinDecode for MessageScope {
       0x00 => Ok(Self::All),
       0x01 => Ok(Self::Allies),
       0x02 => Ok(Self::Observers),
-      n if n <= (u8::MAX - 0x03) as u32 => Ok(Self::Player((n - 0x03) as u8)),
+      n if n <= 0x02 + u8::MAX as u32 => Ok(Self::Player((n - 0x02) as u8)),
       n => Err(BinDecodeError::failure(format!(
         "invalid chat message scope value: {}",
         n
@@ -224,3 +225,81 @@ impl ChatFromOthers {
 impl PacketPayload for ChatFromOthers {
   const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::ChatFromOthers;
 }
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  MessageScope,
+  prop_oneof![
+    Just(MessageScope::All),
+    Just(MessageScope::Allies),
+    Just(MessageScope::Observers),
+    (1..=u8::MAX).prop_map(MessageScope::Player),
+  ]
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  ChatMessage,
+  prop_oneof![
+    c_string(255).prop_map(ChatMessage::Chat),
+    any::<u8>().prop_map(ChatMessage::TeamChange),
+    any::<u8>().prop_map(ChatMessage::ColorChange),
+    any::<u8>().prop_map(ChatMessage::RaceChange),
+    any::<u8>().prop_map(ChatMessage::HandicapChange),
+    (any::<MessageScope>(), c_string(255))
+      .prop_map(|(scope, message)| ChatMessage::Scoped { scope, message }),
+  ]
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  ChatToHost,
+  (
+    proptest::collection::vec(any::<u8>(), 0..=24),
+    any::<u8>(),
+    any::<ChatMessage>()
+  )
+    .prop_map(|(to_players, from_player, message)| ChatToHost {
+      to_players_len: to_players.len() as u8,
+      to_players,
+      from_player,
+      message,
+    })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  ChatFromHost,
+  any::<ChatToHost>().prop_map(ChatFromHost)
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  ChatFromOthers,
+  any::<ChatToHost>().prop_map(ChatFromOthers)
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_chat_roundtrip(to_host: ChatToHost, from_host: ChatFromHost, from_others: ChatFromOthers) {
+    proptest::prop_assert_eq!(
+      to_host.message.encode_len(),
+      to_host.message.encode_to_bytes().len()
+    );
+    crate::packet::test_simple_payload_roundtrip(&to_host);
+    crate::packet::test_simple_payload_roundtrip(&from_host);
+    crate::packet::test_simple_payload_roundtrip(&from_others);
+  }
+}
+
+#[test]
+fn test_message_scope() {
+  let mut buf = BytesMut::new();
+  MessageScope::Player(1).encode(&mut buf);
+  assert_eq!(buf.as_ref(), &[0x03, 0, 0, 0]);
+  assert_eq!(
+    MessageScope::decode(&mut buf.freeze()).unwrap(),
+    MessageScope::Player(1)
+  );
+}
@@ -17,3 +17,22 @@ pub struct Desync {
 impl PacketPayload for Desync {
   const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::Desync;
 }
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  Desync,
+  (any::<u32>(), any::<u32>()).prop_map(|(unknown_1, unknown_3)| Desync {
+    unknown_1,
+    unknown_2: 4,
+    unknown_3,
+    unknown_4: 0,
+  })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_desync_roundtrip(desync: Desync) {
+    crate::packet::test_simple_payload_roundtrip(&desync);
+  }
+}
@@ -80,8 +80,8 @@ impl BinDecode for GameSettings {
       + size_of::<u16>() /* Map width */
       + size_of::<u16>() /* Map height */
       + size_of::<u32>() /* Map xoro */
-      + "x.w3m".len() + 1 /* Map path */
-      + "h".len() + 1 /* Host name */
+      + 1 /* Map path, can be empty */
+      + 1 /* Host name, can be empty */
       + 1 /* 0x0 */
       + 20 /* Map Sha1 hash */;
 
@@ -185,3 +185,86 @@ fn test_game_loaded_self() {
 fn test_player_loaded() {
   crate::packet::test_simple_payload_type("player_loaded.bin", &PlayerLoaded { player_id: 2 })
 }
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  GameSettings,
+  (
+    (
+      any::<u32>(),
+      any::<u8>(),
+      any::<u16>(),
+      any::<u16>(),
+      any::<u32>()
+    ),
+    c_string(260),
+    c_string(32),
+    any::<[u8; 20]>()
+  )
+    .prop_map(
+      |((flags, unk_1, map_width, map_height, map_checksum), map_path, host_name, map_sha1)| {
+        GameSettings {
+          game_setting_flags: GameSettingFlags::from_bits_truncate(flags),
+          unk_1,
+          map_width,
+          map_height,
+          map_checksum,
+          map_path,
+          host_name,
+          map_sha1,
+        }
+      }
+    )
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  CountDownStart,
+  proptest::strategy::LazyJust::new(|| CountDownStart)
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  CountDownEnd,
+  proptest::strategy::LazyJust::new(|| CountDownEnd)
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  GameLoadedSelf,
+  proptest::strategy::LazyJust::new(|| GameLoadedSelf)
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PlayerLoaded,
+  any::<u8>().prop_map(|player_id| PlayerLoaded { player_id })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_game_roundtrip(
+    start: CountDownStart,
+    end: CountDownEnd,
+    loaded_self: GameLoadedSelf,
+    loaded: PlayerLoaded,
+  ) {
+    crate::packet::test_simple_payload_roundtrip(&start);
+    crate::packet::test_simple_payload_roundtrip(&end);
+    crate::packet::test_simple_payload_roundtrip(&loaded_self);
+    crate::packet::test_simple_payload_roundtrip(&loaded);
+  }
+
+  #[test]
+  fn test_game_settings_roundtrip(settings: GameSettings) {
+    let mut buf = BytesMut::new();
+    settings.encode(&mut buf);
+    let bytes = buf.freeze();
+    let decoded = GameSettings::decode(&mut bytes.clone()).unwrap();
+    proptest::prop_assert_eq!(&decoded, &settings);
+    let mut buf = BytesMut::new();
+    decoded.encode(&mut buf);
+    proptest::prop_assert_eq!(buf.freeze(), bytes);
+  }
+}
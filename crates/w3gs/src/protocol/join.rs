@@ -134,3 +134,62 @@ fn test_slot_info_join() {
     },
   )
 }
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  ReqJoin,
+  (
+    (any::<u32>(), any::<u32>(), any::<u16>(), any::<u32>()),
+    c_string(32),
+    proptest::collection::vec(any::<u8>(), 0..=u8::MAX as usize),
+    sock_addr()
+  )
+    .prop_map(
+      |(
+        (host_counter, entry_key, listen_port, join_counter),
+        player_name,
+        unknown_2,
+        internal_addr,
+      )| {
+        ReqJoin {
+          host_counter,
+          entry_key,
+          _unknown_1: 0,
+          listen_port,
+          join_counter,
+          player_name,
+          _num_unknown_2: unknown_2.len() as u8,
+          _unknown_2: unknown_2,
+          internal_addr,
+        }
+      }
+    )
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  SlotInfoJoin,
+  (any::<SlotInfo>(), any::<u8>(), sock_addr()).prop_map(
+    |(slot_info, player_id, external_addr)| SlotInfoJoin {
+      slot_info,
+      player_id,
+      external_addr,
+    }
+  )
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  RejectJoin,
+  enum_u32().prop_map(|reason| RejectJoin { reason })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_join_roundtrip(req: ReqJoin, slot_info_join: SlotInfoJoin, reject: RejectJoin) {
+    crate::packet::test_simple_payload_roundtrip(&req);
+    crate::packet::test_simple_payload_roundtrip(&slot_info_join);
+    crate::packet::test_simple_payload_roundtrip(&reject);
+  }
+}
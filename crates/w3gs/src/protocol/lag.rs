@@ -39,3 +39,30 @@ pub struct StopLag(pub LagPlayer);
 impl PacketPayload for StopLag {
   const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::StopLag;
 }
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  LagPlayer,
+  (any::<u8>(), any::<u32>()).prop_map(|(player_id, lag_duration_ms)| LagPlayer {
+    player_id,
+    lag_duration_ms
+  })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  StartLag,
+  proptest::collection::vec(any::<LagPlayer>(), 0..=24).prop_map(StartLag::new)
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(StopLag, any::<LagPlayer>().prop_map(StopLag));
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_lag_roundtrip(start: StartLag, stop: StopLag) {
+    crate::packet::test_simple_payload_roundtrip(&start);
+    crate::packet::test_simple_payload_roundtrip(&stop);
+  }
+}
@@ -0,0 +1,227 @@
+//! UDP messages of the game's Local Area Network screen.
+//!
+//! Clients broadcast `SearchGame` and hosts reply with `GameInfo`.
+//! Hosts broadcast `CreateGame`, `RefreshGame` and `DecreateGame` when a game is created,
+//! its player count changes, or it starts or ends.
+
+use flo_util::binary::*;
+use flo_util::{BinDecode, BinEncode};
+
+use crate::protocol::constants::PacketTypeId;
+use crate::protocol::game::GameSettings;
+use crate::protocol::packet::PacketPayload;
+
+/// UDP port of the LAN screen
+pub const LAN_PORT: u16 = 6112;
+/// "W3XP", The Frozen Throne
+pub const PRODUCT_TFT: [u8; 4] = *b"PX3W";
+
+#[derive(Debug, BinDecode, BinEncode, PartialEq, Clone)]
+pub struct SearchGame {
+  pub product: [u8; 4],
+  pub version: u32,
+  _unknown_1: u32,
+}
+
+impl SearchGame {
+  pub fn new(version: u32) -> Self {
+    Self {
+      product: PRODUCT_TFT,
+      version,
+      _unknown_1: 0,
+    }
+  }
+}
+
+impl PacketPayload for SearchGame {
+  const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::SearchGame;
+}
+
+#[derive(Debug, BinDecode, BinEncode, PartialEq, Clone)]
+pub struct GameInfo {
+  pub product: [u8; 4],
+  pub version: u32,
+  /// Host counter, identifies the game in `RefreshGame` and `DecreateGame`
+  pub game_id: u32,
+  /// Sent back by clients in `ReqJoin`
+  pub entry_key: u32,
+  pub game_name: CString,
+  #[bin(eq = 0)]
+  _password: u8,
+  pub game_settings: GameSettings,
+  pub slots_total: u32,
+  /// Game type flags, `0x01` for custom games
+  pub game_type: u32,
+  #[bin(eq = 1)]
+  _unknown_1: u32,
+  pub slots_available: u32,
+  pub uptime_secs: u32,
+  /// TCP port of the game
+  pub port: u16,
+}
+
+impl GameInfo {
+  pub fn new(
+    version: u32,
+    game_id: u32,
+    entry_key: u32,
+    game_name: &str,
+    game_settings: GameSettings,
+    slots_total: u32,
+    port: u16,
+  ) -> Self {
+    Self {
+      product: PRODUCT_TFT,
+      version,
+      game_id,
+      entry_key,
+      game_name: game_name.into_c_string_lossy(),
+      _password: 0,
+      game_settings,
+      slots_total,
+      game_type: 0x01,
+      _unknown_1: 1,
+      slots_available: slots_total,
+      uptime_secs: 0,
+      port,
+    }
+  }
+}
+
+impl PacketPayload for GameInfo {
+  const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::GameInfo;
+}
+
+#[derive(Debug, BinDecode, BinEncode, PartialEq, Clone)]
+pub struct CreateGame {
+  pub product: [u8; 4],
+  pub version: u32,
+  pub game_id: u32,
+}
+
+impl PacketPayload for CreateGame {
+  const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::CreateGame;
+}
+
+#[derive(Debug, BinDecode, BinEncode, PartialEq, Clone)]
+pub struct RefreshGame {
+  pub game_id: u32,
+  pub players: u32,
+  pub slots_total: u32,
+}
+
+impl PacketPayload for RefreshGame {
+  const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::RefreshGame;
+}
+
+#[derive(Debug, BinDecode, BinEncode, PartialEq, Clone)]
+pub struct DecreateGame {
+  pub game_id: u32,
+}
+
+impl PacketPayload for DecreateGame {
+  const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::DecreateGame;
+}
+
+#[test]
+fn test_search_game() {
+  let payload = SearchGame::new(26).encode_to_bytes();
+  assert_eq!(&payload[..], b"PX3W\x1a\x00\x00\x00\x00\x00\x00\x00");
+}
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  SearchGame,
+  (any::<[u8; 4]>(), any::<u32>(), any::<u32>()).prop_map(|(product, version, _unknown_1)| {
+    SearchGame {
+      product,
+      version,
+      _unknown_1,
+    }
+  })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  GameInfo,
+  (
+    (any::<u32>(), any::<u32>(), any::<u32>(), c_string(31)),
+    any::<GameSettings>(),
+    (
+      any::<u32>(),
+      any::<u32>(),
+      any::<u32>(),
+      any::<u32>(),
+      any::<u16>()
+    )
+  )
+    .prop_map(
+      |(
+        (version, game_id, entry_key, game_name),
+        game_settings,
+        (slots_total, game_type, slots_available, uptime_secs, port),
+      )| GameInfo {
+        product: PRODUCT_TFT,
+        version,
+        game_id,
+        entry_key,
+        game_name,
+        _password: 0,
+        game_settings,
+        slots_total,
+        game_type,
+        _unknown_1: 1,
+        slots_available,
+        uptime_secs,
+        port,
+      }
+    )
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  CreateGame,
+  (any::<[u8; 4]>(), any::<u32>(), any::<u32>()).prop_map(|(product, version, game_id)| {
+    CreateGame {
+      product,
+      version,
+      game_id,
+    }
+  })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  RefreshGame,
+  (any::<u32>(), any::<u32>(), any::<u32>()).prop_map(|(game_id, players, slots_total)| {
+    RefreshGame {
+      game_id,
+      players,
+      slots_total,
+    }
+  })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  DecreateGame,
+  any::<u32>().prop_map(|game_id| DecreateGame { game_id })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_lan_roundtrip(
+    search: SearchGame,
+    info: GameInfo,
+    create: CreateGame,
+    refresh: RefreshGame,
+    decreate: DecreateGame,
+  ) {
+    crate::packet::test_simple_payload_roundtrip(&search);
+    crate::packet::test_simple_payload_roundtrip(&info);
+    crate::packet::test_simple_payload_roundtrip(&create);
+    crate::packet::test_simple_payload_roundtrip(&refresh);
+    crate::packet::test_simple_payload_roundtrip(&decreate);
+  }
+}
@@ -5,6 +5,61 @@ use crate::protocol::packet::PacketPayload;
 
 pub use crate::protocol::constants::LeaveReason;
 
+/// Result of a player's game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+  Win,
+  Loss,
+  Draw,
+}
+
+/// What a leave reason says about how the player left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LeaveOutcome {
+  /// The connection was lost, set by the host
+  Disconnected,
+  /// Quit the running game, also sent by players defeated by map triggers
+  Surrendered,
+  /// All buildings were destroyed
+  Defeated,
+  Won,
+  Draw,
+  Observer,
+  /// Left before the game started
+  Lobby,
+  /// Invalid save game or unknown value
+  Unknown(LeaveReason),
+}
+
+impl LeaveOutcome {
+  /// `None` if the leave says nothing about the result, e.g. disconnects
+  pub fn result(&self) -> Option<GameResult> {
+    match *self {
+      LeaveOutcome::Surrendered | LeaveOutcome::Defeated => Some(GameResult::Loss),
+      LeaveOutcome::Won => Some(GameResult::Win),
+      LeaveOutcome::Draw => Some(GameResult::Draw),
+      _ => None,
+    }
+  }
+}
+
+impl LeaveReason {
+  pub fn outcome(&self) -> LeaveOutcome {
+    match *self {
+      LeaveReason::LeaveDisconnect => LeaveOutcome::Disconnected,
+      LeaveReason::LeaveLost => LeaveOutcome::Surrendered,
+      LeaveReason::LeaveLostBuildings => LeaveOutcome::Defeated,
+      LeaveReason::LeaveWon => LeaveOutcome::Won,
+      LeaveReason::LeaveDraw => LeaveOutcome::Draw,
+      LeaveReason::LeaveObserver => LeaveOutcome::Observer,
+      LeaveReason::LeaveLobby => LeaveOutcome::Lobby,
+      LeaveReason::LeaveInvalidSaveGame | LeaveReason::UnknownValue(_) => {
+        LeaveOutcome::Unknown(*self)
+      }
+    }
+  }
+}
+
 #[derive(Debug, BinDecode, BinEncode, PartialEq)]
 pub struct LeaveReq(LeaveReason);
 
@@ -16,6 +71,10 @@ impl LeaveReq {
   pub fn reason(&self) -> LeaveReason {
     self.0
   }
+
+  pub fn outcome(&self) -> LeaveOutcome {
+    self.0.outcome()
+  }
 }
 
 impl PacketPayload for LeaveReq {
@@ -35,6 +94,12 @@ pub struct PlayerLeft {
   pub reason: LeaveReason,
 }
 
+impl PlayerLeft {
+  pub fn outcome(&self) -> LeaveOutcome {
+    self.reason.outcome()
+  }
+}
+
 impl PacketPayload for PlayerLeft {
   const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::PlayerLeft;
 }
@@ -47,3 +112,86 @@ pub struct PlayerKicked {
 impl PacketPayload for PlayerKicked {
   const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::PlayerKicked;
 }
+
+/// Sent by the host when the game is over
+#[derive(Debug, BinDecode, BinEncode, PartialEq)]
+pub struct GameOver {
+  pub player_id: u8,
+}
+
+impl PacketPayload for GameOver {
+  const PACKET_TYPE_ID: PacketTypeId = PacketTypeId::GameOver;
+}
+
+#[test]
+fn test_leave_outcome() {
+  let left = PlayerLeft {
+    player_id: 1,
+    reason: LeaveReason::LeaveDisconnect,
+  };
+  assert_eq!(left.outcome(), LeaveOutcome::Disconnected);
+  assert_eq!(left.outcome().result(), None);
+
+  let req = LeaveReq::new(LeaveReason::LeaveLost);
+  assert_eq!(req.outcome(), LeaveOutcome::Surrendered);
+  assert_eq!(req.outcome().result(), Some(GameResult::Loss));
+  assert_eq!(
+    LeaveReason::LeaveLostBuildings.outcome().result(),
+    Some(GameResult::Loss)
+  );
+  assert_eq!(
+    LeaveReason::LeaveWon.outcome().result(),
+    Some(GameResult::Win)
+  );
+
+  let reason: LeaveReason = flo_util::binary::BinDecode::decode(&mut &[0x20, 0, 0, 0][..]).unwrap();
+  assert_eq!(
+    reason.outcome(),
+    LeaveOutcome::Unknown(LeaveReason::UnknownValue(0x20))
+  );
+}
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(LeaveReq, enum_u32().prop_map(LeaveReq));
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  LeaveAck,
+  proptest::strategy::LazyJust::new(|| LeaveAck)
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PlayerLeft,
+  (any::<u8>(), enum_u32()).prop_map(|(player_id, reason)| PlayerLeft { player_id, reason })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PlayerKicked,
+  enum_u32().prop_map(|reason| PlayerKicked { reason })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  GameOver,
+  any::<u8>().prop_map(|player_id| GameOver { player_id })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_leave_roundtrip(
+    req: LeaveReq,
+    ack: LeaveAck,
+    left: PlayerLeft,
+    kicked: PlayerKicked,
+    game_over: GameOver,
+  ) {
+    crate::packet::test_simple_payload_roundtrip(&req);
+    crate::packet::test_simple_payload_roundtrip(&ack);
+    crate::packet::test_simple_payload_roundtrip(&left);
+    crate::packet::test_simple_payload_roundtrip(&kicked);
+    crate::packet::test_simple_payload_roundtrip(&game_over);
+  }
+}
@@ -83,3 +83,44 @@ fn test_map_size() {
     },
   )
 }
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  MapCheck,
+  (
+    c_string(260),
+    any::<u32>(),
+    any::<u32>(),
+    any::<u32>(),
+    any::<[u8; 20]>()
+  )
+    .prop_map(
+      |(file_path, file_size, file_crc, map_xoro, sha1)| MapCheck {
+        _unknown_1: 0x01,
+        file_path,
+        file_size,
+        file_crc,
+        map_xoro,
+        sha1,
+      }
+    )
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  MapSize,
+  (any::<u8>(), any::<u32>()).prop_map(|(size_flag, map_size)| MapSize {
+    _unknown_1: 0x01,
+    size_flag,
+    map_size,
+  })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_map_roundtrip(check: MapCheck, size: MapSize) {
+    crate::packet::test_simple_payload_roundtrip(&check);
+    crate::packet::test_simple_payload_roundtrip(&size);
+  }
+}
@@ -1,15 +1,19 @@
 pub mod action;
+#[cfg(test)]
+pub(crate) mod arbitrary;
 pub mod chat;
 pub mod constants;
 pub mod desync;
 pub mod game;
 pub mod join;
 pub mod lag;
+pub mod lan;
 pub mod leave;
 pub mod map;
 pub mod packet;
 pub mod ping;
 pub mod player;
+pub mod save;
 pub mod slot;
 
 mod protobuf {
@@ -4,7 +4,7 @@ use flo_util::binary::*;
 use flo_util::binary::{BinDecode, BinEncode};
 use flo_util::{BinDecode, BinEncode};
 
-use crate::error::{Error, Result};
+use crate::error::{Error, PacketDecodeError, Result};
 use crate::protocol::constants::{PacketTypeId, ProtoBufMessageTypeId};
 
 pub trait PacketPayload: Sized {
@@ -134,9 +134,10 @@ impl Packet {
     T: PacketPayloadDecode,
   {
     let mut buf = self.payload.clone();
-    let payload = T::decode(&mut buf)?;
+    let payload = T::decode(&mut buf).map_err(|err| self.decode_error(buf.remaining(), err))?;
     if buf.has_remaining() {
-      return Err(Error::ExtraPayloadBytes(buf.remaining()));
+      let remaining = buf.remaining();
+      return Err(self.decode_error(remaining, Error::ExtraPayloadBytes(remaining)));
     }
     Ok(payload)
   }
@@ -157,7 +158,19 @@ impl Packet {
     T: PacketProtoBufMessage,
   {
     let payload: ProtoBufPayload = self.decode_simple()?;
-    payload.decode_message()
+    payload
+      .decode_message()
+      .map_err(|err| self.decode_error(payload.data.len(), err))
+  }
+
+  // `remaining` is the number of payload bytes left when decoding stopped
+  fn decode_error(&self, remaining: usize, source: Error) -> Error {
+    Error::PacketDecode(Box::new(PacketDecodeError::new(
+      self.header.type_id,
+      &self.payload,
+      self.payload.len().saturating_sub(remaining),
+      source,
+    )))
   }
 
   #[inline]
@@ -310,7 +323,7 @@ where
   let payload: T = packet
     .decode_payload()
     .map_err(|e| {
-      if let Error::ExtraPayloadBytes(len) = e {
+      if let Error::ExtraPayloadBytes(len) = *e.root() {
         let extra = &packet.payload[(packet.payload.len() - len)..];
         println!("{:?}", extra);
         flo_util::dump_hex(extra);
@@ -340,7 +353,7 @@ where
   let payload: T = packet
     .decode_simple()
     .map_err(|e| {
-      if let Error::ExtraPayloadBytes(len) = e {
+      if let Error::ExtraPayloadBytes(len) = *e.root() {
         let extra = &packet.payload[(packet.payload.len() - len)..];
         println!("{:?}", extra);
         flo_util::dump_hex(extra);
@@ -382,6 +395,91 @@ pub(crate) fn test_protobuf_payload_type<
   assert_eq!(new.encode_to_bytes(), packet.payload);
 }
 
+/// Encodes `payload` into a packet, decodes it back and encodes the result again,
+/// both the payload and the bytes must come back unchanged
+#[cfg(test)]
+pub(crate) fn test_payload_roundtrip<T>(payload: &T)
+where
+  T: PacketPayload
+    + PacketPayloadEncode
+    + PacketPayloadDecode
+    + std::cmp::PartialEq
+    + std::fmt::Debug,
+{
+  let bytes = encode_roundtrip_packet(Packet::with_payload(payload).unwrap());
+  let decoded: T = decode_roundtrip_packet(&bytes).decode_payload().unwrap();
+  assert_eq!(&decoded, payload);
+  assert_eq!(
+    encode_roundtrip_packet(Packet::with_payload(&decoded).unwrap()),
+    bytes
+  );
+}
+
+#[cfg(test)]
+pub(crate) fn test_simple_payload_roundtrip<T>(payload: &T)
+where
+  T: PacketPayload + BinEncode + BinDecode + std::cmp::PartialEq + std::fmt::Debug,
+{
+  let bytes = encode_roundtrip_packet(Packet::simple(payload).unwrap());
+  let decoded: T = decode_roundtrip_packet(&bytes).decode_simple().unwrap();
+  assert_eq!(&decoded, payload);
+  assert_eq!(
+    encode_roundtrip_packet(Packet::simple(&decoded).unwrap()),
+    bytes
+  );
+}
+
+#[cfg(test)]
+pub(crate) fn test_protobuf_roundtrip<T>(message: &T)
+where
+  T: PacketProtoBufMessage + Clone + std::cmp::PartialEq + std::fmt::Debug,
+{
+  let bytes =
+    encode_roundtrip_packet(Packet::simple(ProtoBufPayload::new(message.clone())).unwrap());
+  let decoded: T = decode_roundtrip_packet(&bytes).decode_protobuf().unwrap();
+  assert_eq!(&decoded, message);
+  assert_eq!(
+    encode_roundtrip_packet(Packet::simple(ProtoBufPayload::new(decoded)).unwrap()),
+    bytes
+  );
+}
+
+#[cfg(test)]
+fn encode_roundtrip_packet(packet: Packet) -> Bytes {
+  let mut buf = BytesMut::new();
+  packet.encode(&mut buf);
+  buf.freeze()
+}
+
+#[cfg(test)]
+fn decode_roundtrip_packet(bytes: &Bytes) -> Packet {
+  let mut buf = BytesMut::from(bytes.as_ref());
+  let header = Packet::decode_header(&mut buf).unwrap();
+  let packet = Packet::decode(header, &mut buf).unwrap();
+  assert!(!buf.has_remaining());
+  packet
+}
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  ProtoBufPayload,
+  (enum_u8(), proptest::collection::vec(any::<u8>(), 0..=256)).prop_map(|(type_id, data)| {
+    ProtoBufPayload {
+      type_id,
+      len: data.len() as u32,
+      data,
+    }
+  })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_protobuf_payload_roundtrip(payload: ProtoBufPayload) {
+    test_simple_payload_roundtrip(&payload);
+  }
+}
+
 #[test]
 fn test_packet() {
   use flo_util::binary::*;
@@ -395,3 +493,41 @@ fn test_packet() {
   dbg!(p1);
   dbg!(p2);
 }
+
+#[test]
+fn test_decode_error_context() {
+  use crate::protocol::lag::{LagPlayer, StopLag};
+
+  let packet = Packet::simple(StopLag(LagPlayer {
+    player_id: 1,
+    lag_duration_ms: 1000,
+  }))
+  .unwrap();
+
+  let mut truncated = packet.clone();
+  truncated.payload = truncated.payload.slice(0..3);
+  let err = truncated.decode_simple::<StopLag>().unwrap_err();
+  match err {
+    Error::PacketDecode(ref e) => {
+      assert_eq!(e.type_id, PacketTypeId::StopLag);
+      assert_eq!(e.payload_len, 3);
+      assert_eq!(e.dump, vec![1, 0xe8, 0x03]);
+      assert!(matches!(e.source, Error::BinDecode(ref e) if e.is_incomplete()));
+    }
+    ref e => panic!("unexpected error: {}", e),
+  }
+
+  let mut extra = packet.clone();
+  extra.payload = [&packet.payload[..], &[0xAB, 0xCD]].concat().into();
+  let err = extra.decode_simple::<StopLag>().unwrap_err();
+  match err {
+    Error::PacketDecode(ref e) => {
+      assert_eq!(e.offset, 5);
+      assert_eq!(e.dump_offset, 0);
+      assert!(matches!(e.source, Error::ExtraPayloadBytes(2)));
+      assert!(err.to_string().contains("ab cd"));
+    }
+    ref e => panic!("unexpected error: {}", e),
+  }
+  assert!(matches!(err.root(), Error::ExtraPayloadBytes(2)));
+}
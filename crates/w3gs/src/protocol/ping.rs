@@ -70,3 +70,24 @@ fn test_pong_to_host() {
     &PongToHost(Ping { payload: 95750587 }),
   )
 }
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PingFromHost,
+  any::<u32>().prop_map(PingFromHost::with_payload)
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PongToHost,
+  any::<u32>().prop_map(|payload| PongToHost(Ping { payload }))
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_ping_roundtrip(ping: PingFromHost, pong: PongToHost) {
+    crate::packet::test_simple_payload_roundtrip(&ping);
+    crate::packet::test_simple_payload_roundtrip(&pong);
+  }
+}
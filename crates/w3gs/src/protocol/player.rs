@@ -167,3 +167,99 @@ fn test_player_unknown_02() {
   let p: ProtoBufPayload = p.decode_simple().unwrap();
   dbg!(&p);
 }
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PlayerInfo,
+  (
+    any::<u32>(),
+    any::<u8>(),
+    c_string(32),
+    proptest::collection::vec(any::<u8>(), 0..=u8::MAX as usize),
+    sock_addr(),
+    sock_addr()
+  )
+    .prop_map(
+      |(join_counter, player_id, player_name, unknown_1, external_addr, internal_addr)| {
+        PlayerInfo {
+          join_counter,
+          player_id,
+          player_name,
+          _num_unknown_1: unknown_1.len() as u8,
+          _unknown_1: unknown_1,
+          external_addr,
+          internal_addr,
+        }
+      }
+    )
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(PlayerLoaded, any::<u8>().prop_map(PlayerLoaded::new));
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PlayerProfileMessage,
+  (
+    any::<u32>(),
+    (".{0,32}", ".{0,16}", ".{0,8}"),
+    any::<i32>(),
+    ".{0,16}"
+  )
+    .prop_map(
+      |(player_id, (battle_tag, clan, portrait), realm, unknown_1)| PlayerProfileMessage {
+        player_id,
+        battle_tag,
+        clan,
+        portrait,
+        realm,
+        unknown_1,
+      }
+    )
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PlayerSkinsMessage,
+  (
+    any::<u32>(),
+    proptest::collection::vec(
+      (any::<u64>(), any::<u64>(), ".{0,16}").prop_map(|(unit, skin, collection)| PlayerSkin {
+        unit,
+        skin,
+        collection,
+      }),
+      0..=32
+    )
+  )
+    .prop_map(|(player_id, skins)| PlayerSkinsMessage { player_id, skins })
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  PlayerUnknown5Message,
+  (any::<u32>(), any::<u32>()).prop_map(|(player_id, unknown_1)| PlayerUnknown5Message {
+    player_id,
+    unknown_1,
+  })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_player_roundtrip(info: PlayerInfo, loaded: PlayerLoaded) {
+    crate::packet::test_simple_payload_roundtrip(&info);
+    crate::packet::test_simple_payload_roundtrip(&loaded);
+  }
+
+  #[test]
+  fn test_player_protobuf_roundtrip(
+    profile: PlayerProfileMessage,
+    skins: PlayerSkinsMessage,
+    unknown_5: PlayerUnknown5Message,
+  ) {
+    crate::packet::test_protobuf_roundtrip(&profile);
+    crate::packet::test_protobuf_roundtrip(&skins);
+    crate::packet::test_protobuf_roundtrip(&unknown_5);
+  }
+}
@@ -0,0 +1,278 @@
+//! Multiplayer saves.
+//!
+//! Saving is driven by game actions: the saving player sends `SaveGame` (0x06) with the
+//! file name, then every client sends `SaveGameFinished` (0x07) after the file is written.
+//!
+//! Loading is negotiated through the advertised game info: a lobby created from a save is
+//! advertised with [`LoadGame`], Warcraft III then joins it as a saved game and expects every
+//! player in the slot they had when the game was saved.
+
+use flo_util::binary::*;
+use std::collections::BTreeSet;
+
+use crate::actions::{Action, ActionTypeId, SaveGame, SaveGameFinished};
+use crate::constants::GameFlags;
+use crate::protocol::action::PlayerAction;
+use crate::protocol::game::GameSettings;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveAction {
+  Started { name: String },
+  Finished,
+}
+
+impl SaveAction {
+  /// Returns the first save action in the action block of a player, if any.
+  /// Actions are not length prefixed, scanning stops at the first action that can't be decoded.
+  pub fn parse(mut data: &[u8]) -> Option<Self> {
+    while data.has_remaining() {
+      match Action::decode(&mut data).ok()? {
+        Action::SaveGame(SaveGame { name }) => {
+          return Some(SaveAction::Started {
+            name: name.to_string_lossy().to_string(),
+          })
+        }
+        Action::SaveGameFinished(_) => return Some(SaveAction::Finished),
+        _ => {}
+      }
+    }
+    None
+  }
+
+  /// Returns the save actions of a time slot with the player id that sent them.
+  /// Each player action is length prefixed, a block with an unknown action is skipped
+  /// without affecting the blocks after it.
+  pub fn parse_player_actions(actions: &[PlayerAction]) -> Vec<(u8, Self)> {
+    actions
+      .iter()
+      .filter_map(|action| Self::parse(&action.data).map(|save| (action.player_id, save)))
+      .collect()
+  }
+
+  pub fn encode_to_bytes(&self) -> Bytes {
+    let mut buf = BytesMut::new();
+    match *self {
+      SaveAction::Started { ref name } => {
+        ActionTypeId::SaveGame.encode(&mut buf);
+        SaveGame {
+          name: name.as_str().into_c_string_lossy(),
+        }
+        .encode(&mut buf);
+      }
+      SaveAction::Finished => {
+        ActionTypeId::SaveGameFinished.encode(&mut buf);
+        SaveGameFinished::default().encode(&mut buf);
+      }
+    }
+    buf.freeze()
+  }
+}
+
+/// Tracks which players haven't finished writing a save
+#[derive(Debug)]
+pub struct SaveGameProgress {
+  name: String,
+  initiator: u8,
+  pending: BTreeSet<u8>,
+}
+
+impl SaveGameProgress {
+  pub fn new<I>(initiator: u8, name: String, player_ids: I) -> Self
+  where
+    I: IntoIterator<Item = u8>,
+  {
+    Self {
+      name,
+      initiator,
+      pending: player_ids.into_iter().collect(),
+    }
+  }
+
+  pub fn name(&self) -> &str {
+    self.name.as_ref()
+  }
+
+  pub fn initiator(&self) -> u8 {
+    self.initiator
+  }
+
+  pub fn pending(&self) -> impl Iterator<Item = u8> + '_ {
+    self.pending.iter().cloned()
+  }
+
+  /// Returns `true` if all players finished saving
+  pub fn finish(&mut self, player_id: u8) -> bool {
+    self.pending.remove(&player_id);
+    self.pending.is_empty()
+  }
+
+  /// Players who left don't need to finish, returns `true` if all remaining players finished
+  pub fn remove_player(&mut self, player_id: u8) -> bool {
+    self.finish(player_id)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedSlot {
+  pub slot_index: u8,
+  pub player_id: u8,
+  pub name: String,
+}
+
+/// Slot layout of a saved game, a resumed game has to use the same slots
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SavedSlotMap {
+  slots: Vec<SavedSlot>,
+}
+
+impl SavedSlotMap {
+  pub fn new(mut slots: Vec<SavedSlot>) -> Self {
+    slots.sort_by_key(|s| s.slot_index);
+    Self { slots }
+  }
+
+  pub fn slots(&self) -> &[SavedSlot] {
+    self.slots.as_ref()
+  }
+}
+
+const MULTIPLAYER_SAVE_DIR: &str = "Save\\Multiplayer\\";
+
+/// A saved game to load instead of a map.
+///
+/// Warcraft III only lists the game if it finds a save at `path` with the same magic number,
+/// the magic number is stored in the save file and can't be derived from the save actions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadGame {
+  pub path: String,
+  pub magic_number: u32,
+}
+
+impl LoadGame {
+  /// `name` is the file name sent with `SaveGame`, relative to the multiplayer save folder
+  pub fn new(name: &str, magic_number: u32) -> Self {
+    let path = if name.contains('\\') {
+      name.to_string()
+    } else {
+      format!("{}{}", MULTIPLAYER_SAVE_DIR, name)
+    };
+    Self { path, magic_number }
+  }
+
+  /// Replaces the map of advertised game settings with the save,
+  /// a saved game has no map size and uses the magic number as map checksum
+  pub fn game_settings(&self, settings: &GameSettings) -> GameSettings {
+    GameSettings {
+      map_width: 0,
+      map_height: 0,
+      map_checksum: self.magic_number,
+      map_path: self.path.as_str().into_c_string_lossy(),
+      ..settings.clone()
+    }
+  }
+
+  /// Replaces the game type of advertised game flags
+  pub fn game_flags(&self, flags: GameFlags) -> GameFlags {
+    (flags - GameFlags::TYPE_MASK) | GameFlags::SAVED_GAME
+  }
+}
+
+#[test]
+fn test_save_action() {
+  let started = SaveAction::Started {
+    name: "flo_save.w3z".to_string(),
+  };
+  let bytes = started.encode_to_bytes();
+  assert_eq!(SaveAction::parse(&bytes), Some(started));
+
+  let bytes = SaveAction::Finished.encode_to_bytes();
+  assert_eq!(SaveAction::parse(&bytes), Some(SaveAction::Finished));
+
+  assert_eq!(SaveAction::parse(&[0x01]), None);
+}
+
+#[test]
+fn test_save_action_skip_unknown() {
+  let started = SaveAction::Started {
+    name: "flo_save.w3z".to_string(),
+  };
+  let actions = vec![
+    PlayerAction {
+      player_id: 1,
+      // unknown action type id, followed by bytes that would decode as a save action
+      data: Bytes::from_static(&[0xFF, 0x07, 0x01, 0x00, 0x00, 0x00]),
+    },
+    PlayerAction {
+      player_id: 2,
+      data: started.encode_to_bytes(),
+    },
+    PlayerAction {
+      player_id: 3,
+      data: SaveAction::Finished.encode_to_bytes(),
+    },
+  ];
+  assert_eq!(
+    SaveAction::parse_player_actions(&actions),
+    vec![(2, started), (3, SaveAction::Finished)]
+  );
+}
+
+#[test]
+fn test_save_game_progress() {
+  let mut progress = SaveGameProgress::new(1, "a.w3z".to_string(), vec![1, 2, 3]);
+  assert!(!progress.finish(1));
+  assert!(!progress.remove_player(3));
+  assert!(progress.finish(2));
+
+  let map = SavedSlotMap::new(vec![
+    SavedSlot {
+      slot_index: 1,
+      player_id: 2,
+      name: "b".to_string(),
+    },
+    SavedSlot {
+      slot_index: 0,
+      player_id: 1,
+      name: "a".to_string(),
+    },
+  ]);
+  assert_eq!(map.slots()[0].name, "a");
+  assert_eq!(map.slots()[1].slot_index, 1);
+}
+
+#[test]
+fn test_load_game() {
+  use crate::protocol::constants::GameSettingFlags;
+  use crate::protocol::game::GameSettingsMap;
+
+  let load = LoadGame::new("flo_save.w3z", 0x12345678);
+  assert_eq!(load.path, "Save\\Multiplayer\\flo_save.w3z");
+  assert_eq!(
+    LoadGame::new("Save\\Multiplayer\\a.w3z", 1).path,
+    "Save\\Multiplayer\\a.w3z"
+  );
+
+  let settings = GameSettings::new(
+    GameSettingFlags::default(),
+    GameSettingsMap {
+      path: "Maps\\(2)ConcealedHill.w3x".to_string(),
+      width: 96,
+      height: 96,
+      sha1: [1; 20],
+      checksum: 0xAABBCCDD,
+    },
+  );
+  let loaded = load.game_settings(&settings);
+  assert_eq!(loaded.map_width, 0);
+  assert_eq!(loaded.map_height, 0);
+  assert_eq!(loaded.map_checksum, 0x12345678);
+  assert_eq!(
+    loaded.map_path.to_str().unwrap(),
+    "Save\\Multiplayer\\flo_save.w3z"
+  );
+  assert_eq!(loaded.map_sha1, [1; 20]);
+  assert_eq!(loaded.host_name, settings.host_name);
+
+  let flags = load.game_flags(GameFlags::CUSTOM_GAME | GameFlags::OBS_FULL);
+  assert_eq!(flags, GameFlags::SAVED_GAME | GameFlags::OBS_FULL);
+}
@@ -74,6 +74,46 @@ impl SlotInfo {
     Some(slot)
   }
 
+  /// Encodes a map mode string into the handicaps of occupied slots, one character per slot
+  /// (HCL). Returns `false` and leaves the slots unchanged if the string contains characters
+  /// not in `HCL_CHARS` or is longer than the number of occupied slots.
+  pub fn encode_hcl(&mut self, mode: &str) -> bool {
+    let char_indices: Option<Vec<usize>> = mode.chars().map(|c| HCL_CHARS.find(c)).collect();
+    let char_indices = match char_indices {
+      Some(v) => v,
+      None => return false,
+    };
+    let occupied = self
+      .slots
+      .iter()
+      .filter(|s| s.slot_status == SlotStatus::Occupied)
+      .count();
+    if char_indices.len() > occupied {
+      return false;
+    }
+
+    // handicap values maps treat as unencoded are skipped
+    let mut table = [0_u8; HCL_CHARS.len() * 6];
+    let mut value: u8 = 0;
+    for v in table.iter_mut() {
+      if matches!(value, 0 | 50 | 60 | 70 | 80 | 90 | 100) {
+        value += 1;
+      }
+      *v = value;
+      value += 1;
+    }
+
+    let slots = self
+      .slots
+      .iter_mut()
+      .filter(|s| s.slot_status == SlotStatus::Occupied);
+    for (slot, char_index) in slots.zip(char_indices) {
+      let handicap_index = (slot.handicap.max(50).min(100) - 50) / 10;
+      slot.handicap = table[handicap_index as usize + char_index * 6];
+    }
+    true
+  }
+
   // TODO: handle teams, forces
   // pub fn join(&mut self) -> Option<&mut SlotData> {
   //   let (i, slot) = self
@@ -92,6 +132,9 @@ impl SlotInfo {
   // }
 }
 
+/// Characters supported by `SlotInfo::encode_hcl`
+pub const HCL_CHARS: &str = "abcdefghijklmnopqrstuvwxyz0123456789 -=,.";
+
 #[derive(Debug)]
 pub struct SlotInfoBuilder {
   inner: SlotInfo,
@@ -272,3 +315,84 @@ fn test_slot_info_3() {
     },
   );
 }
+
+#[test]
+fn test_encode_hcl() {
+  let mut info = SlotInfo::build().num_slots(24).build();
+  for i in [0, 1, 3] {
+    info.slot_mut(i).unwrap().slot_status = SlotStatus::Occupied;
+  }
+  info.slot_mut(1).unwrap().handicap = 50;
+
+  assert!(!info.encode_hcl("apem"));
+  assert!(!info.encode_hcl("AP"));
+  let handicaps =
+    |info: &SlotInfo| -> Vec<u8> { info.slots()[0..4].iter().map(|s| s.handicap).collect() };
+  assert_eq!(handicaps(&info), vec![100, 50, 100, 100]);
+
+  assert!(info.encode_hcl("ap"));
+  assert_eq!(handicaps(&info), vec![6, 96, 100, 100]);
+
+  let mut info = SlotInfo::build().num_slots(24).build();
+  info.slot_mut(0).unwrap().slot_status = SlotStatus::Occupied;
+  assert!(info.encode_hcl("."));
+  assert_eq!(info.slots()[0].handicap, 252);
+}
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  SlotData,
+  (
+    (any::<u8>(), any::<u8>(), enum_u8(), any::<bool>()),
+    (
+      any::<u8>(),
+      any::<u8>(),
+      any::<u8>(),
+      enum_u8(),
+      any::<u8>()
+    )
+  )
+    .prop_map(
+      |(
+        (player_id, download_status, slot_status, computer),
+        (team, color, race, computer_type, handicap),
+      )| SlotData {
+        player_id,
+        download_status,
+        slot_status,
+        computer,
+        team,
+        color,
+        race: RacePref::from_bits_truncate(race),
+        computer_type,
+        handicap,
+      }
+    )
+);
+
+#[cfg(test)]
+crate::protocol::arbitrary::impl_arbitrary!(
+  SlotInfo,
+  (
+    proptest::collection::vec(any::<SlotData>(), 0..=24),
+    any::<u32>(),
+    enum_u8(),
+    any::<u8>()
+  )
+    .prop_map(|(slots, random_seed, slot_layout, num_players)| SlotInfo {
+      _length_of_slot_data: (7 + SlotData::MIN_SIZE * slots.len()) as u16,
+      _num_slots: slots.len() as u8,
+      slots,
+      random_seed,
+      slot_layout,
+      num_players,
+    })
+);
+
+#[cfg(test)]
+proptest::proptest! {
+  #[test]
+  fn test_slot_info_roundtrip(slot_info: SlotInfo) {
+    crate::packet::test_simple_payload_roundtrip(&slot_info);
+  }
+}
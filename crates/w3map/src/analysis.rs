@@ -0,0 +1,272 @@
+// Placement metrics used in map pool reviews
+
+use crate::pathing::{DistanceField, PathingGrid};
+use crate::units::PlacedUnit;
+
+/// Start locations and gold mines can sit on cells blocked by their own footprint
+const WALKABLE_SEARCH_RADIUS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapAnalysis {
+  pub start_locations: Vec<StartLocationMetrics>,
+  /// Every pair of start locations
+  pub spawn_distances: Vec<SpawnDistance>,
+  pub num_gold_mines: usize,
+  /// Sorted by `placement_deviation`, the most likely symmetry first
+  pub symmetry: Vec<SymmetryEstimate>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartLocationMetrics {
+  pub player_id: u32,
+  pub x: f32,
+  pub y: f32,
+  pub nearest_gold_mine: Option<GoldMineDistance>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoldMineDistance {
+  pub x: f32,
+  pub y: f32,
+  pub gold: i32,
+  /// Straight line distance in world units
+  pub distance: f32,
+  /// Walking distance in world units, `None` without pathing data or if unreachable
+  pub walk_distance: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnDistance {
+  pub player_ids: [u32; 2],
+  pub distance: f32,
+  pub walk_distance: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymmetryKind {
+  /// Point symmetry around the map center
+  Rotation180,
+  Rotation90,
+  /// Mirrored across the vertical center line
+  MirrorHorizontal,
+  /// Mirrored across the horizontal center line
+  MirrorVertical,
+  /// Mirrored across the bottom left to top right diagonal
+  MirrorDiagonal,
+  /// Mirrored across the top left to bottom right diagonal
+  MirrorAntiDiagonal,
+}
+
+impl SymmetryKind {
+  const ALL: [SymmetryKind; 6] = [
+    SymmetryKind::Rotation180,
+    SymmetryKind::Rotation90,
+    SymmetryKind::MirrorHorizontal,
+    SymmetryKind::MirrorVertical,
+    SymmetryKind::MirrorDiagonal,
+    SymmetryKind::MirrorAntiDiagonal,
+  ];
+
+  /// Maps `(x, y)` to its image, `center` is the map center
+  pub fn apply(&self, center: (f32, f32), x: f32, y: f32) -> (f32, f32) {
+    let (dx, dy) = (x - center.0, y - center.1);
+    let (tx, ty) = match *self {
+      SymmetryKind::Rotation180 => (-dx, -dy),
+      SymmetryKind::Rotation90 => (-dy, dx),
+      SymmetryKind::MirrorHorizontal => (-dx, dy),
+      SymmetryKind::MirrorVertical => (dx, -dy),
+      SymmetryKind::MirrorDiagonal => (dy, dx),
+      SymmetryKind::MirrorAntiDiagonal => (-dy, -dx),
+    };
+    (center.0 + tx, center.1 + ty)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymmetryEstimate {
+  pub kind: SymmetryKind,
+  /// Mean distance in world units between each start location and gold mine
+  /// and the closest placement of the same kind to its image, 0 if perfectly symmetric
+  pub placement_deviation: f32,
+  /// Ratio of pathing cells matching their image, `None` without pathing data
+  pub pathing_match: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct StartLocation {
+  pub player_id: u32,
+  pub x: f32,
+  pub y: f32,
+}
+
+pub(crate) fn analyze(
+  start_locations: &[StartLocation],
+  gold_mines: &[&PlacedUnit],
+  pathing: Option<&PathingGrid>,
+  center: (f32, f32),
+) -> MapAnalysis {
+  let fields: Vec<Option<DistanceField>> = start_locations
+    .iter()
+    .map(|loc| {
+      let grid = pathing?;
+      let cell = grid.nearest_walkable(grid.cell_at(loc.x, loc.y)?, WALKABLE_SEARCH_RADIUS)?;
+      Some(grid.distance_field(cell))
+    })
+    .collect();
+  let walk_distance = |idx: usize, x: f32, y: f32| -> Option<f32> {
+    let grid = pathing?;
+    let field = fields[idx].as_ref()?;
+    let cell = grid.nearest_walkable(grid.cell_at(x, y)?, WALKABLE_SEARCH_RADIUS)?;
+    field.get(cell)
+  };
+
+  let metrics = start_locations
+    .iter()
+    .enumerate()
+    .map(|(idx, loc)| StartLocationMetrics {
+      player_id: loc.player_id,
+      x: loc.x,
+      y: loc.y,
+      nearest_gold_mine: gold_mines
+        .iter()
+        .map(|mine| (mine, distance((loc.x, loc.y), (mine.x, mine.y))))
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(mine, distance)| GoldMineDistance {
+          x: mine.x,
+          y: mine.y,
+          gold: mine.gold,
+          distance,
+          walk_distance: walk_distance(idx, mine.x, mine.y),
+        }),
+    })
+    .collect();
+
+  let mut spawn_distances = vec![];
+  for (i, a) in start_locations.iter().enumerate() {
+    for b in &start_locations[(i + 1)..] {
+      spawn_distances.push(SpawnDistance {
+        player_ids: [a.player_id, b.player_id],
+        distance: distance((a.x, a.y), (b.x, b.y)),
+        walk_distance: walk_distance(i, b.x, b.y),
+      });
+    }
+  }
+
+  let starts: Vec<_> = start_locations.iter().map(|v| (v.x, v.y)).collect();
+  let mines: Vec<_> = gold_mines.iter().map(|v| (v.x, v.y)).collect();
+  let mut symmetry: Vec<_> = SymmetryKind::ALL
+    .iter()
+    .map(|kind| {
+      let f = |x, y| kind.apply(center, x, y);
+      SymmetryEstimate {
+        kind: *kind,
+        placement_deviation: mean_deviation(&[&starts, &mines], f),
+        pathing_match: pathing.map(|grid| grid.match_ratio(f)),
+      }
+    })
+    .collect();
+  symmetry.sort_by(|a, b| {
+    a.placement_deviation
+      .partial_cmp(&b.placement_deviation)
+      .unwrap_or(std::cmp::Ordering::Equal)
+      .then_with(|| {
+        b.pathing_match
+          .partial_cmp(&a.pathing_match)
+          .unwrap_or(std::cmp::Ordering::Equal)
+      })
+  });
+
+  MapAnalysis {
+    start_locations: metrics,
+    spawn_distances,
+    num_gold_mines: gold_mines.len(),
+    symmetry,
+  }
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+  ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+fn mean_deviation<F>(groups: &[&[(f32, f32)]], transform: F) -> f32
+where
+  F: Fn(f32, f32) -> (f32, f32),
+{
+  let mut sum = 0.;
+  let mut count = 0;
+  for group in groups {
+    for &(x, y) in group.iter() {
+      let image = transform(x, y);
+      let d = group
+        .iter()
+        .map(|p| distance(image, *p))
+        .fold(f32::INFINITY, f32::min);
+      sum += d;
+      count += 1;
+    }
+  }
+  if count == 0 {
+    0.
+  } else {
+    sum / count as f32
+  }
+}
+
+#[test]
+fn test_analyze() {
+  use crate::units::GOLD_MINE_TYPE_ID;
+
+  // 8x8 cells, a wall in the middle with a gap at the bottom, mirrored left to right
+  let grid = crate::pathing::grid_from_rows(&[
+    "...##...", //
+    "...##...", //
+    "...##...", //
+    "...##...", //
+    "...##...", //
+    "...##...", //
+    "........", //
+    "........", //
+  ]);
+  let center = (128., 128.);
+  let starts = [
+    StartLocation {
+      player_id: 0,
+      x: 16.,
+      y: 240.,
+    },
+    StartLocation {
+      player_id: 1,
+      x: 240.,
+      y: 240.,
+    },
+  ];
+  let mine = |x, y| PlacedUnit {
+    type_id: GOLD_MINE_TYPE_ID,
+    x,
+    y,
+    player: 15,
+    gold: 12500,
+    ..Default::default()
+  };
+  let mines = [mine(80., 240.), mine(176., 240.)];
+  let mine_refs: Vec<_> = mines.iter().collect();
+
+  let analysis = analyze(&starts, &mine_refs, Some(&grid), center);
+
+  let nearest = analysis.start_locations[0]
+    .nearest_gold_mine
+    .as_ref()
+    .unwrap();
+  assert_eq!((nearest.x, nearest.distance), (80., 64.));
+  assert_eq!(nearest.walk_distance, Some(64.));
+
+  assert_eq!(analysis.spawn_distances.len(), 1);
+  let spawn = &analysis.spawn_distances[0];
+  assert_eq!(spawn.distance, 224.);
+  // around the wall
+  assert!(spawn.walk_distance.unwrap() > 400.);
+
+  assert_eq!(analysis.symmetry[0].kind, SymmetryKind::MirrorHorizontal);
+  assert_eq!(analysis.symmetry[0].placement_deviation, 0.);
+  assert_eq!(analysis.symmetry[0].pathing_match, Some(1.));
+}
@@ -0,0 +1,353 @@
+//! Writes patched copies of map archives.
+//!
+//! `MapBuilder` reads every file of a map, lets files be replaced, injected or removed,
+//! and writes a new MPQ archive. The map header in front of the archive is kept, so the
+//! written map shows up in the game with the original name and player count.
+//!
+//! Files are written zlib compressed in 4 KiB sectors without encryption, and
+//! `(listfile)` is generated from the written file names. `(attributes)` and `(signature)`
+//! are dropped because they no longer match the patched content.
+
+use crate::error::Result;
+use crate::files::{
+  encrypt, hash_string, MapArchive, MapFileFlags, HASH_ENTRY_FREE, MPQ_HEADER_ALIGN,
+  MPQ_HEADER_MAGIC,
+};
+use crate::{GAMEPLAY_FILES, MAP_LOCALES};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::path::Path;
+
+const HEADER_SIZE: u32 = 32;
+/// Sector size is `512 << SECTOR_SIZE_SHIFT`, Warcraft III maps use 4 KiB sectors
+const SECTOR_SIZE_SHIFT: u16 = 3;
+const SECTOR_SIZE: usize = 512 << SECTOR_SIZE_SHIFT;
+const COMPRESSION_ZLIB: u8 = 0x02;
+
+/// Archive metadata files, regenerated or dropped on write
+const INTERNAL_FILES: &[&str] = &["(listfile)", "(attributes)", "(signature)"];
+
+/// Files read even if the listfile doesn't name them
+const KNOWN_FILES: &[&str] = &[
+  "war3mapMap.blp",
+  "war3mapMap.tga",
+  "war3mapPreview.tga",
+  "war3mapPath.tga",
+  "war3map.mmp",
+  "war3map.shd",
+];
+
+/// Files of a map archive that are written into a new archive
+#[derive(Debug, Clone, Default)]
+pub struct MapBuilder {
+  /// Bytes in front of the MPQ header, the `HM3W` header of Warcraft III maps
+  prefix: Vec<u8>,
+  files: Vec<(String, Vec<u8>)>,
+}
+
+impl MapBuilder {
+  /// An empty archive without a map header
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+    Self::from_bytes(&std::fs::read(path)?)
+  }
+
+  /// Reads the listed files, the gameplay files and other files every map can have.
+  /// Files that are neither listed nor known are lost,
+  /// protected maps without a listfile can lose imported files.
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+    let mut archive = MapArchive::open_memory(bytes)?;
+    let mut paths = archive.list_files()?;
+    let known = GAMEPLAY_FILES
+      .iter()
+      .chain(KNOWN_FILES)
+      .map(|path| path.to_string())
+      .chain(
+        MAP_LOCALES
+          .iter()
+          .map(|locale| format!("_Locales\\{}.w3mod\\war3map.wts", locale)),
+      );
+    for path in known {
+      if !paths.iter().any(|p| same_path(p, &path)) {
+        paths.push(path);
+      }
+    }
+
+    let mut builder = Self {
+      prefix: bytes[..find_header(bytes).unwrap_or(0)].to_vec(),
+      files: vec![],
+    };
+    for path in paths {
+      if INTERNAL_FILES.iter().any(|name| same_path(name, &path)) {
+        continue;
+      }
+      // listfiles can name files that were removed from the archive
+      if let Some(file) = archive.read_file(&path)? {
+        builder
+          .files
+          .push((normalize_separators(&path), file.bytes));
+      }
+    }
+    Ok(builder)
+  }
+
+  pub fn file_names(&self) -> impl Iterator<Item = &str> {
+    self.files.iter().map(|(path, _)| path.as_str())
+  }
+
+  /// Paths are case insensitive and treat `/` and `\` the same
+  pub fn get(&self, path: &str) -> Option<&[u8]> {
+    self
+      .files
+      .iter()
+      .find(|(p, _)| same_path(p, path))
+      .map(|(_, bytes)| bytes.as_slice())
+  }
+
+  /// Replaces the file at `path` or adds it, returns the replaced content
+  pub fn insert(&mut self, path: &str, bytes: Vec<u8>) -> Option<Vec<u8>> {
+    match self.files.iter_mut().find(|(p, _)| same_path(p, path)) {
+      Some((_, existing)) => Some(std::mem::replace(existing, bytes)),
+      None => {
+        self.files.push((normalize_separators(path), bytes));
+        None
+      }
+    }
+  }
+
+  pub fn remove(&mut self, path: &str) -> Option<Vec<u8>> {
+    let idx = self.files.iter().position(|(p, _)| same_path(p, path))?;
+    Some(self.files.remove(idx).1)
+  }
+
+  /// Map header followed by the archive
+  pub fn to_bytes(&self) -> Result<Vec<u8>> {
+    let mut out = self.prefix.clone();
+    let align = MPQ_HEADER_ALIGN as usize;
+    out.resize((out.len() + align - 1) / align * align, 0);
+    out.extend(self.encode_archive()?);
+    Ok(out)
+  }
+
+  pub fn write<W: Write>(&self, w: &mut W) -> Result<()> {
+    w.write_all(&self.to_bytes()?)?;
+    Ok(())
+  }
+
+  pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    std::fs::write(path, self.to_bytes()?)?;
+    Ok(())
+  }
+
+  fn encode_archive(&self) -> Result<Vec<u8>> {
+    let listfile = self
+      .files
+      .iter()
+      .map(|(path, _)| path.as_str())
+      .collect::<Vec<_>>()
+      .join("\r\n")
+      .into_bytes();
+    let files: Vec<(&str, &[u8])> = self
+      .files
+      .iter()
+      .map(|(path, bytes)| (path.as_str(), bytes.as_slice()))
+      .chain(std::iter::once(("(listfile)", listfile.as_slice())))
+      .collect();
+
+    let mut data = vec![0; HEADER_SIZE as usize];
+    let mut blocks = Vec::with_capacity(files.len());
+    for (_, bytes) in &files {
+      let pos = data.len() as u32;
+      let (compressed_size, flags) = if bytes.is_empty() {
+        (0, MapFileFlags::EXISTS)
+      } else {
+        let encoded = compress_sectors(bytes)?;
+        data.extend_from_slice(&encoded);
+        (
+          encoded.len() as u32,
+          MapFileFlags::EXISTS | MapFileFlags::COMPRESS,
+        )
+      };
+      blocks.push([pos, compressed_size, bytes.len() as u32, flags.bits()]);
+    }
+
+    let hash_table_size = ((files.len() * 2) as u32).next_power_of_two().max(16);
+    let mut hash = vec![[HASH_ENTRY_FREE; 4]; hash_table_size as usize];
+    for (block_index, (path, _)) in files.iter().enumerate() {
+      let start = hash_string(path, 0) % hash_table_size;
+      let slot = (0..hash_table_size)
+        .map(|i| ((start + i) % hash_table_size) as usize)
+        .find(|slot| hash[*slot][3] == HASH_ENTRY_FREE)
+        .expect("hash table has free entries");
+      hash[slot] = [
+        hash_string(path, 1),
+        hash_string(path, 2),
+        // neutral locale, default platform
+        0,
+        block_index as u32,
+      ];
+    }
+
+    let hash_table_pos = data.len() as u32;
+    write_table(&mut data, &hash, "(hash table)");
+    let block_table_pos = data.len() as u32;
+    write_table(&mut data, &blocks, "(block table)");
+
+    let header = [
+      MPQ_HEADER_MAGIC,
+      HEADER_SIZE,
+      data.len() as u32,
+      // format version 0 in the low word
+      (SECTOR_SIZE_SHIFT as u32) << 16,
+      hash_table_pos,
+      block_table_pos,
+      hash_table_size,
+      blocks.len() as u32,
+    ];
+    for (chunk, word) in data.chunks_exact_mut(4).zip(header.iter()) {
+      chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    Ok(data)
+  }
+}
+
+/// Sector offset table followed by the sectors,
+/// sectors that don't get smaller are stored uncompressed
+fn compress_sectors(bytes: &[u8]) -> Result<Vec<u8>> {
+  let sectors: Vec<&[u8]> = bytes.chunks(SECTOR_SIZE).collect();
+  let table_len = (sectors.len() + 1) * 4;
+  let mut offsets = Vec::with_capacity(sectors.len() + 1);
+  let mut encoded = vec![];
+  for sector in sectors {
+    offsets.push((table_len + encoded.len()) as u32);
+    let mut w = ZlibEncoder::new(vec![COMPRESSION_ZLIB], Compression::best());
+    w.write_all(sector)?;
+    let compressed = w.finish()?;
+    if compressed.len() < sector.len() {
+      encoded.extend(compressed);
+    } else {
+      encoded.extend_from_slice(sector);
+    }
+  }
+  offsets.push((table_len + encoded.len()) as u32);
+
+  let mut out = Vec::with_capacity(table_len + encoded.len());
+  for offset in offsets {
+    out.extend_from_slice(&offset.to_le_bytes());
+  }
+  out.extend(encoded);
+  Ok(out)
+}
+
+fn write_table(out: &mut Vec<u8>, entries: &[[u32; 4]], key: &str) {
+  let mut words: Vec<u32> = entries.iter().flatten().cloned().collect();
+  encrypt(&mut words, hash_string(key, 3));
+  for word in words {
+    out.extend_from_slice(&word.to_le_bytes());
+  }
+}
+
+fn find_header(bytes: &[u8]) -> Option<usize> {
+  (0..bytes.len())
+    .step_by(MPQ_HEADER_ALIGN as usize)
+    .find(|offset| bytes[*offset..].starts_with(&MPQ_HEADER_MAGIC.to_le_bytes()))
+}
+
+fn normalize_separators(path: &str) -> String {
+  path.replace('/', "\\")
+}
+
+fn same_path(a: &str, b: &str) -> bool {
+  a.len() == b.len()
+    && a
+      .bytes()
+      .zip(b.bytes())
+      .all(|(a, b)| normalize_separator(a).eq_ignore_ascii_case(&normalize_separator(b)))
+}
+
+fn normalize_separator(b: u8) -> u8 {
+  if b == b'/' {
+    b'\\'
+  } else {
+    b
+  }
+}
+
+#[test]
+fn test_map_builder() {
+  let mut builder = MapBuilder::new();
+  builder.insert(
+    "war3map.j",
+    b"function main takes nothing returns nothing\nendfunction".to_vec(),
+  );
+  builder.insert("Scripts/empty.txt", vec![]);
+  let large: Vec<u8> = (0..(SECTOR_SIZE * 3 + 7))
+    .map(|i| (i % 251) as u8)
+    .collect();
+  builder.insert("war3map.w3e", large.clone());
+  assert_eq!(
+    builder
+      .insert("WAR3MAP.J", b"-- patched".to_vec())
+      .unwrap()
+      .len(),
+    55
+  );
+  assert_eq!(builder.get("scripts\\EMPTY.txt"), Some(&[][..]));
+
+  let bytes = builder.to_bytes().unwrap();
+  let mut archive = MapArchive::open_memory(&bytes).unwrap();
+  assert_eq!(
+    archive.list_files().unwrap(),
+    vec!["Scripts\\empty.txt", "war3map.j", "war3map.w3e"]
+  );
+  assert_eq!(
+    archive.read_file("war3map.j").unwrap().unwrap().bytes,
+    b"-- patched"
+  );
+  assert!(archive
+    .read_file("scripts\\empty.txt")
+    .unwrap()
+    .unwrap()
+    .bytes
+    .is_empty());
+  let file = archive.read_file("war3map.w3e").unwrap().unwrap();
+  assert_eq!(file.bytes, large);
+  assert!(file.compression.unwrap().is_compressed());
+  assert!(archive.read_file("(attributes)").unwrap().is_none());
+}
+
+#[test]
+fn test_map_builder_patch() {
+  use crate::W3Map;
+
+  let path = flo_util::sample_path!("map", "(2)ConcealedHill.w3x");
+  let original = std::fs::read(&path).unwrap();
+  let mut builder = MapBuilder::from_bytes(&original).unwrap();
+  let script = builder.get("war3map.j").unwrap().to_vec();
+  let mut patched = b"// flo\r\n".to_vec();
+  patched.extend(&script);
+  builder.insert("war3map.j", patched.clone());
+  builder.insert("flo\\stamp.txt", b"ladder".to_vec());
+
+  let bytes = builder.to_bytes().unwrap();
+  assert_eq!(&bytes[..4], &original[..4]);
+  let map = W3Map::open_memory(&bytes).unwrap();
+  let source = W3Map::open(&path).unwrap();
+  assert_eq!(map.name(), source.name());
+  assert_eq!(map.get_players().len(), source.get_players().len());
+
+  let mut archive = MapArchive::open_memory(&bytes).unwrap();
+  assert_eq!(
+    archive.read_file("war3map.j").unwrap().unwrap().bytes,
+    patched
+  );
+  assert_eq!(
+    archive.read_file("flo\\stamp.txt").unwrap().unwrap().bytes,
+    b"ladder"
+  );
+  assert!(archive.verify().unwrap().is_empty());
+}
@@ -1,6 +1,7 @@
 use crate::error::{Error, Result};
 use crate::Archive;
 use std::io::{BufReader, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 const CHUNK_SIZE: usize = 200 * 1024;
 
@@ -14,6 +15,20 @@ pub struct MapChecksum {
 
 impl MapChecksum {
   pub(crate) fn compute(archive: &mut Archive) -> Result<Self> {
+    Self::compute_cancellable(archive, &AtomicBool::new(false))
+  }
+
+  /// Returns `Error::Cancelled` as soon as `cancelled` is set,
+  /// checked between chunks and between the hashed files
+  pub(crate) fn compute_cancellable(archive: &mut Archive, cancelled: &AtomicBool) -> Result<Self> {
+    let check_cancelled = || {
+      if cancelled.load(Ordering::Relaxed) {
+        Err(Error::Cancelled)
+      } else {
+        Ok(())
+      }
+    };
+
     let mut sha1 = sha1::Sha1::new();
     let mut crc32 = crc32fast::Hasher::new();
     let file_size;
@@ -26,6 +41,7 @@ impl MapChecksum {
         file_size = file.metadata()?.len() as usize;
         let mut r = BufReader::new(file);
         loop {
+          check_cancelled()?;
           let len = r.read(&mut buf)?;
           if len == 0 {
             break;
@@ -58,6 +74,7 @@ impl MapChecksum {
       ];
 
       for (i, paths) in files.into_iter().enumerate() {
+        check_cancelled()?;
         let mut found = false;
         for path in *paths {
           if let Some(bytes) = archive.read_file_all_opt(path)? {
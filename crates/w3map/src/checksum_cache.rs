@@ -0,0 +1,267 @@
+// On-disk cache of map checksums, so large maps are only hashed again after they change
+
+use crate::checksum::MapChecksum;
+use crate::error::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+const INDEX_HEADER: &str = "flo-w3map-checksums 1";
+const MAX_ENTRIES: usize = 4096;
+
+/// Identifies the content of a map file without reading it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ChecksumCacheKey {
+  path: PathBuf,
+  size: u64,
+  modified: Duration,
+}
+
+impl ChecksumCacheKey {
+  /// Returns `None` if the file system doesn't report modification times
+  pub(crate) fn new(path: &Path) -> Result<Option<Self>> {
+    let path = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&path)?;
+    let modified = match metadata
+      .modified()
+      .ok()
+      .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+    {
+      Some(v) => v,
+      None => return Ok(None),
+    };
+    Ok(Some(Self {
+      path,
+      size: metadata.len(),
+      modified,
+    }))
+  }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+  size: u64,
+  modified: Duration,
+  checksum: MapChecksum,
+}
+
+/// Checksums of map files keyed by absolute path, persisted to an index file.
+/// An entry is only used while the size and the modification time of the file match.
+#[derive(Debug)]
+pub struct ChecksumCache {
+  index_path: PathBuf,
+  entries: Mutex<HashMap<PathBuf, Entry>>,
+}
+
+impl ChecksumCache {
+  /// Loads the index at `index_path`,
+  /// a missing or unreadable index starts an empty cache
+  pub fn open<P: AsRef<Path>>(index_path: P) -> Self {
+    let index_path = index_path.as_ref().to_owned();
+    let entries = fs::read_to_string(&index_path)
+      .map(|content| parse_index(&content))
+      .unwrap_or_default();
+    Self {
+      index_path,
+      entries: Mutex::new(entries),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.lock().unwrap().len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Returns the cached checksum if the file didn't change since it was computed
+  pub fn get<P: AsRef<Path>>(&self, path: P) -> Result<Option<MapChecksum>> {
+    Ok(ChecksumCacheKey::new(path.as_ref())?.and_then(|key| self.lookup(&key)))
+  }
+
+  pub(crate) fn lookup(&self, key: &ChecksumCacheKey) -> Option<MapChecksum> {
+    let entries = self.entries.lock().unwrap();
+    entries
+      .get(&key.path)
+      .filter(|entry| entry.size == key.size && entry.modified == key.modified)
+      .map(|entry| entry.checksum.clone())
+  }
+
+  /// Records the checksum of the file identified by `key` and rewrites the index.
+  /// The oldest files are dropped once the index holds `MAX_ENTRIES`.
+  pub(crate) fn store(&self, key: ChecksumCacheKey, checksum: MapChecksum) -> Result<()> {
+    let mut entries = self.entries.lock().unwrap();
+    entries.insert(
+      key.path,
+      Entry {
+        size: key.size,
+        modified: key.modified,
+        checksum,
+      },
+    );
+    while entries.len() > MAX_ENTRIES {
+      let oldest = entries
+        .iter()
+        .min_by_key(|(_, entry)| entry.modified)
+        .map(|(path, _)| path.clone())
+        .expect("entries is not empty");
+      entries.remove(&oldest);
+    }
+    self.persist(&entries)
+  }
+
+  // written to a temporary file first, readers never see a partial index
+  fn persist(&self, entries: &HashMap<PathBuf, Entry>) -> Result<()> {
+    if let Some(dir) = self.index_path.parent() {
+      fs::create_dir_all(dir)?;
+    }
+    let mut tmp_path = self.index_path.clone().into_os_string();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    {
+      let mut w = std::io::BufWriter::new(fs::File::create(&tmp_path)?);
+      writeln!(w, "{}", INDEX_HEADER)?;
+      for (path, entry) in entries {
+        if let Some(line) = format_entry(path, entry) {
+          writeln!(w, "{}", line)?;
+        }
+      }
+      w.flush()?;
+    }
+    fs::rename(&tmp_path, &self.index_path)?;
+    Ok(())
+  }
+}
+
+// size, modification time, sha1, crc32, xoro and the path, separated by tabs
+fn format_entry(path: &Path, entry: &Entry) -> Option<String> {
+  let path = path.to_str()?;
+  if path.contains('\n') {
+    return None;
+  }
+  Some(format!(
+    "{}\t{}.{:09}\t{}\t{:08x}\t{:08x}\t{}",
+    entry.size,
+    entry.modified.as_secs(),
+    entry.modified.subsec_nanos(),
+    entry.checksum.get_sha1_hex_string(),
+    entry.checksum.crc32,
+    entry.checksum.xoro,
+    path
+  ))
+}
+
+fn parse_index(content: &str) -> HashMap<PathBuf, Entry> {
+  let mut lines = content.lines();
+  if lines.next() != Some(INDEX_HEADER) {
+    return HashMap::new();
+  }
+  lines.filter_map(parse_entry).collect()
+}
+
+fn parse_entry(line: &str) -> Option<(PathBuf, Entry)> {
+  let mut parts = line.splitn(6, '\t');
+  let size: u64 = parts.next()?.parse().ok()?;
+  let modified = {
+    let mut parts = parts.next()?.splitn(2, '.');
+    let secs: u64 = parts.next()?.parse().ok()?;
+    let nanos: u32 = parts.next()?.parse().ok()?;
+    if nanos >= 1_000_000_000 {
+      return None;
+    }
+    Duration::new(secs, nanos)
+  };
+  let sha1 = parse_sha1(parts.next()?)?;
+  let crc32 = u32::from_str_radix(parts.next()?, 16).ok()?;
+  let xoro = u32::from_str_radix(parts.next()?, 16).ok()?;
+  let path = parts.next()?;
+  if path.is_empty() {
+    return None;
+  }
+  Some((
+    PathBuf::from(path),
+    Entry {
+      size,
+      modified,
+      checksum: MapChecksum {
+        xoro,
+        crc32,
+        sha1,
+        file_size: size as usize,
+      },
+    },
+  ))
+}
+
+fn parse_sha1(hex: &str) -> Option<[u8; 20]> {
+  if hex.len() != 40 || !hex.is_ascii() {
+    return None;
+  }
+  let mut bytes = [0; 20];
+  for (i, byte) in bytes.iter_mut().enumerate() {
+    *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+  }
+  Some(bytes)
+}
+
+#[test]
+fn test_index_roundtrip() {
+  let checksum = MapChecksum {
+    xoro: 0x1234abcd,
+    crc32: 0xdeadbeef,
+    sha1: [
+      0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 250, 251, 255,
+    ],
+    file_size: 42,
+  };
+  let entry = Entry {
+    size: 42,
+    modified: Duration::new(1_600_000_000, 123),
+    checksum: checksum.clone(),
+  };
+  let line = format_entry(Path::new("/maps/a\tb.w3x"), &entry).unwrap();
+  let (path, parsed) = parse_entry(&line).unwrap();
+  assert_eq!(path, Path::new("/maps/a\tb.w3x"));
+  assert_eq!(parsed.size, 42);
+  assert_eq!(parsed.modified, entry.modified);
+  assert_eq!(parsed.checksum, checksum);
+
+  assert!(parse_index(&line).is_empty());
+  assert_eq!(
+    parse_index(&format!("{}\n{}\nbroken", INDEX_HEADER, line)).len(),
+    1
+  );
+}
+
+#[test]
+fn test_checksum_cache() {
+  use crate::W3Map;
+
+  let dir = std::env::temp_dir().join(format!("flo-w3map-checksum-cache-{}", std::process::id()));
+  let index_path = dir.join("checksums");
+  let map_path = flo_util::sample_path!("map", "(2)ConcealedHill.w3x");
+
+  let cache = ChecksumCache::open(&index_path);
+  assert!(cache.is_empty());
+  assert_eq!(cache.get(&map_path).unwrap(), None);
+  let (_, checksum) = W3Map::open_with_checksum_cached(&map_path, &cache).unwrap();
+  assert_eq!(checksum, W3Map::open_with_checksum(&map_path).unwrap().1);
+  assert_eq!(cache.get(&map_path).unwrap(), Some(checksum.clone()));
+
+  // loaded from the index
+  let cache = ChecksumCache::open(&index_path);
+  assert_eq!(cache.len(), 1);
+  let (_, cached) = W3Map::open_with_checksum_cached(&map_path, &cache).unwrap();
+  assert_eq!(cached, checksum);
+
+  // a changed file misses
+  let mut key = ChecksumCacheKey::new(&map_path).unwrap().unwrap();
+  key.modified += Duration::from_secs(1);
+  assert_eq!(cache.lookup(&key), None);
+
+  fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,532 @@
+use crate::error::Result;
+use crate::objects::{CustomObjects, ObjectDefinition, ObjectField, ObjectKind, ObjectValue};
+use crate::{Archive, MapArchive, W3Map};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Files that affect gameplay or lobby behavior
+pub const GAMEPLAY_FILES: &[&str] = &[
+  "war3map.j",
+  "scripts\\war3map.j",
+  "war3map.lua",
+  "war3map.w3i",
+  "war3map.wts",
+  "war3map.w3e",
+  "war3map.wpm",
+  "war3map.doo",
+  "war3mapUnits.doo",
+  "war3map.w3r",
+  "war3map.w3c",
+  "war3map.w3s",
+  "war3map.w3u",
+  "war3map.w3t",
+  "war3map.w3b",
+  "war3map.w3d",
+  "war3map.w3a",
+  "war3map.w3h",
+  "war3map.w3q",
+  "war3mapMisc.txt",
+  "war3mapSkin.txt",
+  "war3mapExtra.txt",
+  "war3map.imp",
+];
+
+pub const SCRIPT_FILES: &[&str] = &["war3map.j", "scripts\\war3map.j", "war3map.lua"];
+
+/// Object data fields that only change names, tooltips, icons, models or sounds
+const COSMETIC_FIELDS: &[&[u8; 4]] = &[
+  b"unam", b"utip", b"utub", b"ides", b"anam", b"atp1", b"aub1", b"uico", b"iico", b"aart",
+  b"arar", b"umdl", b"ifil", b"usnd", b"aefs",
+];
+
+/// SHA-1 digests of the gameplay files present in a map archive
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapFileDigests(BTreeMap<&'static str, [u8; 20]>);
+
+impl MapFileDigests {
+  pub(crate) fn compute(archive: &mut Archive) -> Result<Self> {
+    let mut map = BTreeMap::new();
+    for path in GAMEPLAY_FILES {
+      if let Some(bytes) = archive.read_file_all_opt(path)? {
+        let mut sha1 = sha1::Sha1::new();
+        sha1.update(&bytes);
+        map.insert(*path, sha1.digest().bytes());
+      }
+    }
+    Ok(Self(map))
+  }
+
+  pub fn get(&self, path: &str) -> Option<&[u8; 20]> {
+    self.0.get(path)
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = (&'static str, &[u8; 20])> {
+    self.0.iter().map(|(k, v)| (*k, v))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+  Added,
+  Removed,
+  Modified,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+  pub path: &'static str,
+  pub kind: ChangeKind,
+}
+
+/// A changed `war3map.w3i` value, formatted for display
+#[derive(Debug, Clone, PartialEq)]
+pub struct InfoChange {
+  pub field: &'static str,
+  pub old: String,
+  pub new: String,
+}
+
+/// A changed object data field, `None` if the map doesn't modify the field
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectFieldChange {
+  pub field_id: [u8; 4],
+  pub level: u32,
+  pub data_pointer: u32,
+  pub old: Option<ObjectValue>,
+  pub new: Option<ObjectValue>,
+}
+
+/// A unit, item or ability that was added, removed or has gameplay fields changed.
+/// `fields` only contains gameplay fields, see `COSMETIC_FIELDS`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectChange {
+  pub object_kind: ObjectKind,
+  pub id: [u8; 4],
+  pub base_id: [u8; 4],
+  pub kind: ChangeKind,
+  pub fields: Vec<ObjectFieldChange>,
+}
+
+/// Hash-level script change, works for protected/obfuscated maps
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptChange {
+  pub old_path: Option<&'static str>,
+  pub new_path: Option<&'static str>,
+  pub old_sha1: Option<[u8; 20]>,
+  pub new_sha1: Option<[u8; 20]>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MapDiff {
+  pub files: Vec<FileChange>,
+  pub info: Vec<InfoChange>,
+  pub objects: Vec<ObjectChange>,
+  pub script: Option<ScriptChange>,
+}
+
+impl MapDiff {
+  pub fn is_empty(&self) -> bool {
+    self.files.is_empty()
+      && self.info.is_empty()
+      && self.objects.is_empty()
+      && self.script.is_none()
+  }
+}
+
+impl W3Map {
+  /// Opens the map files at `old` and `new` and compares them, see `W3Map::diff`
+  pub fn diff_paths<P: AsRef<Path>, Q: AsRef<Path>>(old: P, new: Q) -> Result<MapDiff> {
+    let map = W3Map::open(old.as_ref())?;
+    let other = W3Map::open(new.as_ref())?;
+    map.diff(
+      &mut MapArchive::open(old)?,
+      &other,
+      &mut MapArchive::open(new)?,
+    )
+  }
+
+  /// Compares this map (old) with `other` (new) and reports gameplay-relevant changes.
+  ///
+  /// `archive` and `other_archive` are the archives the maps were opened from.
+  /// A `W3Map` doesn't keep its archive open and doesn't hash files while opening,
+  /// which would slow down every map open for a check only map pool audits need,
+  /// so the gameplay files are read and hashed from the archives here.
+  pub fn diff(
+    &self,
+    archive: &mut MapArchive,
+    other: &W3Map,
+    other_archive: &mut MapArchive,
+  ) -> Result<MapDiff> {
+    let old_digests = archive.file_digests()?;
+    let new_digests = other_archive.file_digests()?;
+    let files = diff_files(&old_digests, &new_digests);
+    let script = diff_script(&old_digests, &new_digests);
+    let objects = diff_objects(&self.custom_objects, &other.custom_objects);
+
+    let mut info = vec![];
+    let mut push = |field: &'static str, old: String, new: String| {
+      if old != new {
+        info.push(InfoChange { field, old, new })
+      }
+    };
+    push(
+      "dimension",
+      format!("{:?}", self.dimension()),
+      format!("{:?}", other.dimension()),
+    );
+    push(
+      "flags",
+      format!("{:?}", self.flags()),
+      format!("{:?}", other.flags()),
+    );
+    push(
+      "num_players",
+      self.num_players().to_string(),
+      other.num_players().to_string(),
+    );
+    push("players", format_players(self), format_players(other));
+    push(
+      "num_forces",
+      self.num_forces().to_string(),
+      other.num_forces().to_string(),
+    );
+    push("forces", format_forces(self), format_forces(other));
+
+    Ok(MapDiff {
+      files,
+      info,
+      objects,
+      script,
+    })
+  }
+}
+
+fn diff_files(old: &MapFileDigests, new: &MapFileDigests) -> Vec<FileChange> {
+  let mut changes = vec![];
+  for path in GAMEPLAY_FILES {
+    let kind = match (old.get(path), new.get(path)) {
+      (Some(_), None) => ChangeKind::Removed,
+      (None, Some(_)) => ChangeKind::Added,
+      (Some(a), Some(b)) if a != b => ChangeKind::Modified,
+      _ => continue,
+    };
+    changes.push(FileChange { path, kind })
+  }
+  changes
+}
+
+fn diff_script(old: &MapFileDigests, new: &MapFileDigests) -> Option<ScriptChange> {
+  let find = |digests: &MapFileDigests| {
+    SCRIPT_FILES
+      .iter()
+      .find_map(|path| digests.get(path).map(|sha1| (*path, *sha1)))
+  };
+  let old = find(old);
+  let new = find(new);
+  if old.map(|v| v.1) == new.map(|v| v.1) {
+    return None;
+  }
+  Some(ScriptChange {
+    old_path: old.map(|v| v.0),
+    new_path: new.map(|v| v.0),
+    old_sha1: old.map(|v| v.1),
+    new_sha1: new.map(|v| v.1),
+  })
+}
+
+fn diff_objects(old: &CustomObjects, new: &CustomObjects) -> Vec<ObjectChange> {
+  fn definitions(
+    objects: &CustomObjects,
+    kind: ObjectKind,
+  ) -> BTreeMap<[u8; 4], &ObjectDefinition> {
+    objects
+      .get(kind)
+      .into_iter()
+      .flat_map(|table| table.iter())
+      .map(|def| (def.id(), def))
+      .collect()
+  }
+
+  let mut changes = vec![];
+  for object_kind in [ObjectKind::Unit, ObjectKind::Item, ObjectKind::Ability]
+    .iter()
+    .cloned()
+  {
+    let old = definitions(old, object_kind);
+    let new = definitions(new, object_kind);
+    let ids: BTreeSet<[u8; 4]> = old.keys().chain(new.keys()).cloned().collect();
+    for id in ids {
+      let (old, new) = (old.get(&id), new.get(&id));
+      let fields = diff_fields(
+        old.map(|def| def.fields.as_slice()).unwrap_or_default(),
+        new.map(|def| def.fields.as_slice()).unwrap_or_default(),
+      );
+      let (kind, base_id) = match (old, new) {
+        (Some(old), None) => (ChangeKind::Removed, old.base_id),
+        (None, Some(new)) => (ChangeKind::Added, new.base_id),
+        (Some(_), Some(new)) if !fields.is_empty() => (ChangeKind::Modified, new.base_id),
+        _ => continue,
+      };
+      changes.push(ObjectChange {
+        object_kind,
+        id,
+        base_id,
+        kind,
+        fields,
+      })
+    }
+  }
+  changes
+}
+
+fn diff_fields(old: &[ObjectField], new: &[ObjectField]) -> Vec<ObjectFieldChange> {
+  // keyed by `(field_id, level, data_pointer)`
+  fn values(fields: &[ObjectField]) -> BTreeMap<([u8; 4], u32, u32), &ObjectValue> {
+    fields
+      .iter()
+      .filter(|field| !COSMETIC_FIELDS.contains(&&field.field_id))
+      .map(|field| {
+        (
+          (field.field_id, field.level, field.data_pointer),
+          &field.value,
+        )
+      })
+      .collect()
+  }
+  let old = values(old);
+  let new = values(new);
+  let keys: BTreeSet<_> = old.keys().chain(new.keys()).cloned().collect();
+  keys
+    .into_iter()
+    .filter_map(|key| {
+      let (old, new) = (old.get(&key).cloned(), new.get(&key).cloned());
+      if old == new {
+        return None;
+      }
+      let (field_id, level, data_pointer) = key;
+      Some(ObjectFieldChange {
+        field_id,
+        level,
+        data_pointer,
+        old: old.cloned(),
+        new: new.cloned(),
+      })
+    })
+    .collect()
+}
+
+fn format_players(map: &W3Map) -> String {
+  map
+    .get_players()
+    .iter()
+    .map(|p| format!("(type={}, race={}, flags={})", p.r#type, p.race, p.flags))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+fn format_forces(map: &W3Map) -> String {
+  map
+    .get_forces()
+    .iter()
+    .map(|f| format!("(flags={}, player_set={})", f.flags, f.player_set))
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+#[test]
+fn test_diff_files() {
+  let a = MapFileDigests(
+    vec![("war3map.j", [0; 20]), ("war3map.w3u", [0; 20])]
+      .into_iter()
+      .collect(),
+  );
+  let b = MapFileDigests(
+    vec![("war3map.j", [1; 20]), ("war3map.w3a", [0; 20])]
+      .into_iter()
+      .collect(),
+  );
+  assert_eq!(
+    diff_files(&a, &b),
+    vec![
+      FileChange {
+        path: "war3map.j",
+        kind: ChangeKind::Modified
+      },
+      FileChange {
+        path: "war3map.w3u",
+        kind: ChangeKind::Removed
+      },
+      FileChange {
+        path: "war3map.w3a",
+        kind: ChangeKind::Added
+      },
+    ]
+  );
+  let script = diff_script(&a, &b).unwrap();
+  assert_eq!(script.old_sha1, Some([0; 20]));
+  assert_eq!(script.new_sha1, Some([1; 20]));
+  assert_eq!(diff_script(&a, &a), None);
+
+  let diff = MapDiff {
+    script: Some(script),
+    ..Default::default()
+  };
+  assert!(!diff.is_empty());
+  assert!(MapDiff::default().is_empty());
+}
+
+#[test]
+fn test_diff_map() {
+  use crate::builder::MapBuilder;
+  use flo_util::binary::*;
+
+  // `(base_id, custom_id, fields)`
+  type UnitDef<'a> = (
+    &'a [u8; 4],
+    Option<&'a [u8; 4]>,
+    &'a [(&'a [u8; 4], ObjectValue)],
+  );
+
+  // war3map.w3u, version 2
+  fn units(defs: &[UnitDef]) -> Vec<u8> {
+    let mut buf = BytesMut::new();
+    buf.put_u32_le(2);
+    for custom in &[false, true] {
+      let defs: Vec<_> = defs
+        .iter()
+        .filter(|(_, custom_id, _)| custom_id.is_some() == *custom)
+        .collect();
+      buf.put_u32_le(defs.len() as u32);
+      for (base_id, custom_id, fields) in defs {
+        buf.put_slice(*base_id);
+        buf.put_slice(custom_id.map(|v| &v[..]).unwrap_or(&[0; 4]));
+        buf.put_u32_le(fields.len() as u32);
+        for (field_id, value) in fields.iter() {
+          buf.put_slice(*field_id);
+          match value {
+            ObjectValue::Int(v) => {
+              buf.put_u32_le(0);
+              buf.put_i32_le(*v);
+            }
+            ObjectValue::String(v) => {
+              buf.put_u32_le(3);
+              buf.put_slice(v.as_bytes());
+              buf.put_u8(0);
+            }
+            _ => unreachable!(),
+          }
+          buf.put_slice(&[0; 4]);
+        }
+      }
+    }
+    buf.to_vec()
+  }
+
+  let path = flo_util::sample_path!("map", "(2)ConcealedHill.w3x");
+  let mut builder = MapBuilder::open(&path).unwrap();
+  builder.insert(
+    "war3map.w3u",
+    units(&[
+      (b"hfoo", None, &[(b"uhpm", ObjectValue::Int(420))]),
+      (
+        b"hfoo",
+        Some(b"h000"),
+        &[(b"unam", ObjectValue::String("Knight".to_string()))],
+      ),
+    ]),
+  );
+  let old_bytes = builder.to_bytes().unwrap();
+
+  let mut script = b"// flo\r\n".to_vec();
+  script.extend(builder.get("war3map.j").unwrap());
+  builder.insert("war3map.j", script);
+  builder.insert(
+    "war3map.w3u",
+    units(&[
+      (
+        b"hfoo",
+        None,
+        &[
+          (b"uhpm", ObjectValue::Int(500)),
+          (b"unam", ObjectValue::String("Footman".to_string())),
+        ],
+      ),
+      (b"hfoo", Some(b"h001"), &[(b"uhpm", ObjectValue::Int(300))]),
+    ]),
+  );
+  let new_bytes = builder.to_bytes().unwrap();
+
+  let old = W3Map::open_memory(&old_bytes).unwrap();
+  let new = W3Map::open_memory(&new_bytes).unwrap();
+  let mut old_archive = MapArchive::open_memory(&old_bytes).unwrap();
+  let mut new_archive = MapArchive::open_memory(&new_bytes).unwrap();
+
+  let path = flo_util::sample_path!("map", "test_tft.w3x");
+  assert!(W3Map::diff_paths(&path, &path).unwrap().is_empty());
+
+  assert!(old
+    .diff(
+      &mut old_archive,
+      &old,
+      &mut MapArchive::open_memory(&old_bytes).unwrap()
+    )
+    .unwrap()
+    .is_empty());
+
+  let diff = old.diff(&mut old_archive, &new, &mut new_archive).unwrap();
+  assert_eq!(
+    diff.files,
+    vec![
+      FileChange {
+        path: "war3map.j",
+        kind: ChangeKind::Modified
+      },
+      FileChange {
+        path: "war3map.w3u",
+        kind: ChangeKind::Modified
+      },
+    ]
+  );
+  assert!(diff.info.is_empty());
+  let script = diff.script.unwrap();
+  assert_eq!(
+    (script.old_path, script.new_path),
+    (Some("war3map.j"), Some("war3map.j"))
+  );
+  assert_ne!(script.old_sha1, script.new_sha1);
+
+  let uhpm = |old: Option<i32>, new: Option<i32>| ObjectFieldChange {
+    field_id: *b"uhpm",
+    level: 0,
+    data_pointer: 0,
+    old: old.map(ObjectValue::Int),
+    new: new.map(ObjectValue::Int),
+  };
+  assert_eq!(
+    diff.objects,
+    vec![
+      // the name of h000 is not a gameplay field
+      ObjectChange {
+        object_kind: ObjectKind::Unit,
+        id: *b"h000",
+        base_id: *b"hfoo",
+        kind: ChangeKind::Removed,
+        fields: vec![],
+      },
+      ObjectChange {
+        object_kind: ObjectKind::Unit,
+        id: *b"h001",
+        base_id: *b"hfoo",
+        kind: ChangeKind::Added,
+        fields: vec![uhpm(None, Some(300))],
+      },
+      ObjectChange {
+        object_kind: ObjectKind::Unit,
+        id: *b"hfoo",
+        base_id: *b"hfoo",
+        kind: ChangeKind::Modified,
+        fields: vec![uhpm(Some(420), Some(500))],
+      },
+    ]
+  );
+}
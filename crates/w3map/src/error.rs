@@ -1,3 +1,4 @@
+use crate::files::CorruptFile;
 use flo_util::binary::BinDecodeError;
 use thiserror::Error;
 
@@ -24,8 +25,59 @@ pub enum Error {
   ReadMinimapIcons(BinDecodeError),
   #[error("read map trigger strings: {0}")]
   ReadTriggerStrings(BinDecodeError),
+  #[error("read map object data: {0}")]
+  ReadObjects(BinDecodeError),
+  #[error("encode image: {0}")]
+  EncodeImage(#[from] image::ImageError),
+  #[error("corrupted map files: {}", format_corrupted(.0))]
+  CorruptedFiles(Vec<CorruptFile>),
+  #[error("cancelled")]
+  Cancelled,
+  #[error("task join: {0}")]
+  TaskJoin(#[from] tokio::task::JoinError),
   #[error("io: {0}")]
   Io(#[from] std::io::Error),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Parts of a map `W3Map::open_partial` loads independently,
+/// the map info is always required
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapSection {
+  TriggerStrings,
+  Image,
+  MinimapIcons,
+  Objects,
+  Skins,
+}
+
+impl std::fmt::Display for MapSection {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let name = match *self {
+      MapSection::TriggerStrings => "trigger strings",
+      MapSection::Image => "image",
+      MapSection::MinimapIcons => "minimap icons",
+      MapSection::Objects => "object data",
+      MapSection::Skins => "skins",
+    };
+    f.write_str(name)
+  }
+}
+
+/// A section that failed to load, the map has the section's empty value
+#[derive(Error, Debug)]
+#[error("{section}: {source}")]
+pub struct SectionError {
+  pub section: MapSection,
+  #[source]
+  pub source: Error,
+}
+
+fn format_corrupted(files: &[CorruptFile]) -> String {
+  files
+    .iter()
+    .map(ToString::to_string)
+    .collect::<Vec<_>>()
+    .join(", ")
+}
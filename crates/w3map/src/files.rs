@@ -0,0 +1,666 @@
+use crate::error::Result;
+use crate::{Archive, MapFileDigests, W3Map, GAMEPLAY_FILES, SCRIPT_FILES};
+use bitflags::bitflags;
+use std::fmt;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+bitflags! {
+  /// MPQ block table flags
+  pub struct MapFileFlags: u32 {
+    const IMPLODE = 0x0000_0100;
+    const COMPRESS = 0x0000_0200;
+    const ENCRYPTED = 0x0001_0000;
+    const FIX_KEY = 0x0002_0000;
+    const SINGLE_UNIT = 0x0100_0000;
+    const DELETE_MARKER = 0x0200_0000;
+    const SECTOR_CRC = 0x0400_0000;
+    const EXISTS = 0x8000_0000;
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapFileCompression {
+  pub compressed_size: u32,
+  pub file_size: u32,
+  pub flags: MapFileFlags,
+}
+
+impl MapFileCompression {
+  pub fn is_compressed(&self) -> bool {
+    self
+      .flags
+      .intersects(MapFileFlags::IMPLODE | MapFileFlags::COMPRESS)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileCorruption {
+  /// The sector offset table points outside of the file
+  SectorTable,
+  /// Index of the first sector with a mismatching checksum
+  SectorChecksum(usize),
+  /// The archive backend failed to read or decompress the file
+  Read(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorruptFile {
+  pub path: String,
+  pub corruption: FileCorruption,
+}
+
+impl fmt::Display for CorruptFile {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.corruption {
+      FileCorruption::SectorTable => write!(f, "{}: invalid sector table", self.path),
+      FileCorruption::SectorChecksum(sector) => {
+        write!(f, "{}: sector {} checksum mismatch", self.path, sector)
+      }
+      FileCorruption::Read(ref err) => write!(f, "{}: {}", self.path, err),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct MapFile {
+  pub path: String,
+  pub bytes: Vec<u8>,
+  /// `None` if the archive tables can't be read, protected maps often tamper with them
+  pub compression: Option<MapFileCompression>,
+}
+
+/// Raw access to the files of a map archive
+pub struct MapArchive<'a> {
+  archive: Archive<'a>,
+  tables: Option<Option<MpqTables>>,
+}
+
+impl MapArchive<'static> {
+  pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+    Ok(Self::new(W3Map::open_archive_file(path)?))
+  }
+}
+
+impl<'a> MapArchive<'a> {
+  pub fn open_memory(bytes: &'a [u8]) -> Result<Self> {
+    Ok(Self::new(W3Map::open_archive_memory(bytes)?))
+  }
+
+  pub(crate) fn new(archive: Archive<'a>) -> Self {
+    Self {
+      archive,
+      tables: None,
+    }
+  }
+
+  pub(crate) fn into_archive(self) -> Archive<'a> {
+    self.archive
+  }
+
+  /// Returns `None` if the file doesn't exist
+  pub fn read_file(&mut self, path: &str) -> Result<Option<MapFile>> {
+    let bytes = match self.archive.read_file_all_opt(path)? {
+      Some(bytes) => bytes,
+      None => return Ok(None),
+    };
+    Ok(Some(MapFile {
+      path: path.to_string(),
+      bytes,
+      compression: self.compression(path)?,
+    }))
+  }
+
+  pub fn has_file(&mut self, path: &str) -> Result<bool> {
+    self.archive.has_file(path)
+  }
+
+  /// Reads the map script, `war3map.j` or `war3map.lua` for Lua maps.
+  /// Returns `None` if the map has no script, protected maps often remove it
+  pub fn read_script(&mut self) -> Result<Option<MapFile>> {
+    for path in SCRIPT_FILES {
+      if let Some(file) = self.read_file(path)? {
+        return Ok(Some(file));
+      }
+    }
+    Ok(None)
+  }
+
+  /// SHA-1 digests of the gameplay files, see `GAMEPLAY_FILES`
+  pub fn file_digests(&mut self) -> Result<MapFileDigests> {
+    MapFileDigests::compute(&mut self.archive)
+  }
+
+  /// File names in the archive listfile, maps without a listfile have no listed files
+  pub fn list_files(&mut self) -> Result<Vec<String>> {
+    let bytes = match self.archive.read_file_all_opt("(listfile)")? {
+      Some(bytes) => bytes,
+      None => return Ok(vec![]),
+    };
+    let mut names: Vec<String> = String::from_utf8_lossy(&bytes)
+      .split(|c| c == '\r' || c == '\n' || c == ';')
+      .map(str::trim)
+      .filter(|name| !name.is_empty())
+      .map(ToString::to_string)
+      .collect();
+    names.sort_by_key(|name| normalize(name));
+    names.dedup_by_key(|name| normalize(name));
+    Ok(names)
+  }
+
+  /// Reads the listed files matching `pattern`.
+  ///
+  /// Matching is case insensitive and treats `/` and `\` the same.
+  /// `*` matches any sequence including path separators and `?` matches one character,
+  /// so `ReplaceableTextures/*` matches every file under that directory.
+  pub fn read_files_glob(&mut self, pattern: &str) -> Result<Vec<MapFile>> {
+    let pattern = normalize(pattern);
+    let mut files = vec![];
+    for name in self.list_files()? {
+      if !glob_match(pattern.as_bytes(), normalize(&name).as_bytes()) {
+        continue;
+      }
+      // listfiles can name files that were removed from the archive
+      if let Some(file) = self.read_file(&name)? {
+        files.push(file);
+      }
+    }
+    Ok(files)
+  }
+
+  /// Reads the listed files and the gameplay files, and checks the sector checksums of
+  /// the files that have them. Returns the files that are corrupted.
+  ///
+  /// Files without sector checksums or with compressed checksum tables are only checked
+  /// by reading them.
+  pub fn verify(&mut self) -> Result<Vec<CorruptFile>> {
+    let mut paths = self.list_files()?;
+    for path in GAMEPLAY_FILES {
+      if !paths.iter().any(|p| p.eq_ignore_ascii_case(path)) {
+        paths.push(path.to_string());
+      }
+    }
+
+    self.load_tables()?;
+    let mut corrupted = vec![];
+    for path in paths {
+      let corruption = match self.archive.read_file_all_opt(&path) {
+        Ok(None) => continue,
+        Ok(Some(_)) => self.verify_sectors(&path)?,
+        Err(err) => Some(FileCorruption::Read(err.to_string())),
+      };
+      if let Some(corruption) = corruption {
+        corrupted.push(CorruptFile { path, corruption });
+      }
+    }
+    Ok(corrupted)
+  }
+
+  fn verify_sectors(&mut self, path: &str) -> Result<Option<FileCorruption>> {
+    let tables = match self.tables.as_ref().and_then(|tables| tables.as_ref()) {
+      Some(tables) => tables,
+      None => return Ok(None),
+    };
+    let block = match tables.find_block(path) {
+      Some(block) => block,
+      None => return Ok(None),
+    };
+    let res = match self.archive {
+      Archive::File(ref archive) => tables.verify_sectors(
+        &mut std::io::BufReader::new(std::fs::File::open(&archive.path)?),
+        path,
+        block,
+      ),
+      Archive::Memory(ref archive) => {
+        tables.verify_sectors(&mut Cursor::new(archive.bytes), path, block)
+      }
+    };
+    Ok(match res {
+      Ok(corruption) => corruption,
+      // the block points past the end of the archive
+      Err(_) => Some(FileCorruption::SectorTable),
+    })
+  }
+
+  fn load_tables(&mut self) -> Result<()> {
+    if self.tables.is_none() {
+      let tables = match self.archive {
+        Archive::File(ref archive) => MpqTables::read(&mut std::io::BufReader::new(
+          std::fs::File::open(&archive.path)?,
+        )),
+        Archive::Memory(ref archive) => MpqTables::read(&mut Cursor::new(archive.bytes)),
+      };
+      self.tables = Some(tables.ok().flatten());
+    }
+    Ok(())
+  }
+
+  fn compression(&mut self, path: &str) -> Result<Option<MapFileCompression>> {
+    self.load_tables()?;
+    Ok(
+      self
+        .tables
+        .as_ref()
+        .and_then(|tables| tables.as_ref())
+        .and_then(|tables| tables.find(path)),
+    )
+  }
+}
+
+pub(crate) const MPQ_HEADER_ALIGN: u64 = 0x200;
+pub(crate) const MPQ_HEADER_MAGIC: u32 = 0x1A51_504D;
+// Corrupted sizes shouldn't make us allocate gigabytes
+const MPQ_MAX_TABLE_ENTRIES: u32 = 1 << 20;
+const MPQ_MAX_SECTOR_SIZE_SHIFT: u32 = 15;
+pub(crate) const HASH_ENTRY_FREE: u32 = 0xFFFF_FFFF;
+const HASH_ENTRY_DELETED: u32 = 0xFFFF_FFFE;
+
+struct MpqTables {
+  /// Position of the archive header, block positions are relative to it
+  offset: u64,
+  sector_size: u32,
+  hash: Vec<[u32; 4]>,
+  block: Vec<[u32; 4]>,
+}
+
+impl MpqTables {
+  /// Returns `None` if the archive header or tables are malformed
+  fn read<R: Read + Seek>(r: &mut R) -> std::io::Result<Option<Self>> {
+    let len = r.seek(SeekFrom::End(0))?;
+    let mut offset = 0;
+    let header = loop {
+      if offset + 32 > len {
+        return Ok(None);
+      }
+      r.seek(SeekFrom::Start(offset))?;
+      let header = read_u32s(r, 8)?;
+      if header[0] == MPQ_HEADER_MAGIC {
+        break header;
+      }
+      offset += MPQ_HEADER_ALIGN;
+    };
+
+    let hash_table_pos = offset + header[4] as u64;
+    let block_table_pos = offset + header[5] as u64;
+    let (hash_table_size, block_table_size) = (header[6], header[7]);
+    // the high word of the format version
+    let sector_size_shift = header[3] >> 16;
+    if hash_table_size == 0
+      || sector_size_shift > MPQ_MAX_SECTOR_SIZE_SHIFT
+      || hash_table_size > MPQ_MAX_TABLE_ENTRIES
+      || block_table_size > MPQ_MAX_TABLE_ENTRIES
+      || hash_table_pos + hash_table_size as u64 * 16 > len
+      || block_table_pos + block_table_size as u64 * 16 > len
+    {
+      return Ok(None);
+    }
+
+    Ok(Some(Self {
+      offset,
+      sector_size: 512 << sector_size_shift,
+      hash: read_table(r, hash_table_pos, hash_table_size, "(hash table)")?,
+      block: read_table(r, block_table_pos, block_table_size, "(block table)")?,
+    }))
+  }
+
+  fn find(&self, path: &str) -> Option<MapFileCompression> {
+    let [_pos, compressed_size, file_size, flags] = self.find_block(path)?;
+    Some(MapFileCompression {
+      compressed_size,
+      file_size,
+      flags: MapFileFlags::from_bits_truncate(flags),
+    })
+  }
+
+  fn find_block(&self, path: &str) -> Option<[u32; 4]> {
+    let len = self.hash.len();
+    let start = hash_string(path, 0) as usize % len;
+    let (name1, name2) = (hash_string(path, 1), hash_string(path, 2));
+    for i in 0..len {
+      let [entry_name1, entry_name2, _locale_platform, block_index] = self.hash[(start + i) % len];
+      if block_index == HASH_ENTRY_FREE {
+        break;
+      }
+      if block_index != HASH_ENTRY_DELETED && entry_name1 == name1 && entry_name2 == name2 {
+        return self.block.get(block_index as usize).cloned();
+      }
+    }
+    None
+  }
+
+  /// Checks the sector checksums of a compressed multi-sector file.
+  /// Returns `None` if they match or the file has no usable checksum table
+  fn verify_sectors<R: Read + Seek>(
+    &self,
+    r: &mut R,
+    path: &str,
+    [pos, compressed_size, file_size, flags]: [u32; 4],
+  ) -> std::io::Result<Option<FileCorruption>> {
+    let flags = MapFileFlags::from_bits_truncate(flags);
+    if !flags.contains(MapFileFlags::SECTOR_CRC)
+      || flags.contains(MapFileFlags::SINGLE_UNIT)
+      || !flags.intersects(MapFileFlags::IMPLODE | MapFileFlags::COMPRESS)
+    {
+      return Ok(None);
+    }
+
+    let key = flags
+      .contains(MapFileFlags::ENCRYPTED)
+      .then(|| file_key(path, pos, file_size, flags));
+    let pos = self.offset + pos as u64;
+    let sectors =
+      ((file_size as u64 + self.sector_size as u64 - 1) / self.sector_size as u64) as usize;
+    if (sectors + 2) * 4 > compressed_size as usize {
+      return Ok(Some(FileCorruption::SectorTable));
+    }
+
+    // sector offsets, followed by the offset of the checksum table and its end
+    r.seek(SeekFrom::Start(pos))?;
+    let mut offsets = read_u32s(r, sectors + 2)?;
+    if let Some(key) = key {
+      decrypt(&mut offsets, key.wrapping_sub(1));
+    }
+    if offsets.windows(2).any(|w| w[0] > w[1]) || offsets[sectors + 1] > compressed_size {
+      return Ok(Some(FileCorruption::SectorTable));
+    }
+
+    // compressed checksum tables are skipped
+    if (offsets[sectors + 1] - offsets[sectors]) as usize != sectors * 4 {
+      return Ok(None);
+    }
+    r.seek(SeekFrom::Start(pos + offsets[sectors] as u64))?;
+    let checksums = read_u32s(r, sectors)?;
+
+    for (idx, checksum) in checksums.into_iter().enumerate() {
+      if checksum == 0 || checksum == u32::MAX {
+        continue;
+      }
+      r.seek(SeekFrom::Start(pos + offsets[idx] as u64))?;
+      let mut bytes = vec![0; (offsets[idx + 1] - offsets[idx]) as usize];
+      r.read_exact(&mut bytes)?;
+      if let Some(key) = key {
+        decrypt_bytes(&mut bytes, key.wrapping_add(idx as u32));
+      }
+      if adler32(&bytes) != checksum {
+        return Ok(Some(FileCorruption::SectorChecksum(idx)));
+      }
+    }
+    Ok(None)
+  }
+}
+
+// encryption key of a file, derived from the file name without the directory
+fn file_key(path: &str, pos: u32, file_size: u32, flags: MapFileFlags) -> u32 {
+  let name = path
+    .rsplit(|c| c == '\\' || c == '/')
+    .next()
+    .unwrap_or(path);
+  let key = hash_string(name, 3);
+  if flags.contains(MapFileFlags::FIX_KEY) {
+    key.wrapping_add(pos) ^ file_size
+  } else {
+    key
+  }
+}
+
+// MPQ sector checksums are Adler-32 starting at 0 instead of 1
+fn adler32(bytes: &[u8]) -> u32 {
+  const MOD: u32 = 65521;
+  let (mut a, mut b) = (0u32, 0u32);
+  for chunk in bytes.chunks(4096) {
+    for byte in chunk {
+      a += *byte as u32;
+      b += a;
+    }
+    a %= MOD;
+    b %= MOD;
+  }
+  (b << 16) | a
+}
+
+fn read_u32s<R: Read>(r: &mut R, n: usize) -> std::io::Result<Vec<u32>> {
+  let mut buf = vec![0; n * 4];
+  r.read_exact(&mut buf)?;
+  Ok(
+    buf
+      .chunks_exact(4)
+      .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+      .collect(),
+  )
+}
+
+fn read_table<R: Read + Seek>(
+  r: &mut R,
+  pos: u64,
+  entries: u32,
+  key: &str,
+) -> std::io::Result<Vec<[u32; 4]>> {
+  r.seek(SeekFrom::Start(pos))?;
+  let mut words = read_u32s(r, entries as usize * 4)?;
+  decrypt(&mut words, hash_string(key, 3));
+  Ok(
+    words
+      .chunks_exact(4)
+      .map(|v| [v[0], v[1], v[2], v[3]])
+      .collect(),
+  )
+}
+
+lazy_static::lazy_static! {
+  static ref CRYPT_TABLE: [u32; 0x500] = {
+    let mut table = [0; 0x500];
+    let mut seed: u32 = 0x0010_0001;
+    for i in 0..0x100 {
+      for j in 0..5 {
+        seed = (seed * 125 + 3) % 0x2A_AAAB;
+        let hi = (seed & 0xFFFF) << 0x10;
+        seed = (seed * 125 + 3) % 0x2A_AAAB;
+        let lo = seed & 0xFFFF;
+        table[i + j * 0x100] = hi | lo;
+      }
+    }
+    table
+  };
+}
+
+pub(crate) fn hash_string(value: &str, hash_type: usize) -> u32 {
+  let mut seed1: u32 = 0x7FED_7FED;
+  let mut seed2: u32 = 0xEEEE_EEEE;
+  for b in value.bytes() {
+    let b = match b.to_ascii_uppercase() {
+      b'/' => b'\\',
+      b => b,
+    } as u32;
+    seed1 = CRYPT_TABLE[hash_type * 0x100 + b as usize] ^ seed1.wrapping_add(seed2);
+    seed2 = b
+      .wrapping_add(seed1)
+      .wrapping_add(seed2)
+      .wrapping_add(seed2 << 5)
+      .wrapping_add(3);
+  }
+  seed1
+}
+
+fn decrypt(words: &mut [u32], mut key: u32) {
+  let mut seed: u32 = 0xEEEE_EEEE;
+  for word in words {
+    seed = seed.wrapping_add(CRYPT_TABLE[0x400 + (key & 0xFF) as usize]);
+    let value = *word ^ key.wrapping_add(seed);
+    key = ((!key << 0x15).wrapping_add(0x1111_1111)) | (key >> 0x0B);
+    seed = value
+      .wrapping_add(seed)
+      .wrapping_add(seed << 5)
+      .wrapping_add(3);
+    *word = value;
+  }
+}
+
+pub(crate) fn encrypt(words: &mut [u32], mut key: u32) {
+  let mut seed: u32 = 0xEEEE_EEEE;
+  for word in words {
+    seed = seed.wrapping_add(CRYPT_TABLE[0x400 + (key & 0xFF) as usize]);
+    let value = *word;
+    *word = value ^ key.wrapping_add(seed);
+    key = ((!key << 0x15).wrapping_add(0x1111_1111)) | (key >> 0x0B);
+    seed = value
+      .wrapping_add(seed)
+      .wrapping_add(seed << 5)
+      .wrapping_add(3);
+  }
+}
+
+// decrypts the whole words of `bytes`, trailing bytes are not encrypted
+fn decrypt_bytes(bytes: &mut [u8], key: u32) {
+  let mut words: Vec<u32> = bytes
+    .chunks_exact(4)
+    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    .collect();
+  decrypt(&mut words, key);
+  for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+    chunk.copy_from_slice(&word.to_le_bytes());
+  }
+}
+
+fn normalize(path: &str) -> String {
+  path.to_ascii_lowercase().replace('/', "\\")
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+  let (mut p, mut n) = (0, 0);
+  // position after the last `*` and the name position it is matched up to
+  let mut backtrack = None;
+  while n < name.len() {
+    match pattern.get(p) {
+      Some(b'*') => {
+        p += 1;
+        backtrack = Some((p, n));
+      }
+      Some(&c) if c == b'?' || c == name[n] => {
+        p += 1;
+        n += 1;
+      }
+      _ => match backtrack {
+        Some((bp, bn)) => {
+          p = bp;
+          n = bn + 1;
+          backtrack = Some((bp, bn + 1));
+        }
+        None => return false,
+      },
+    }
+  }
+  pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[test]
+fn test_hash_string() {
+  assert_eq!(hash_string("(hash table)", 3), 0xC3AF3770);
+  assert_eq!(hash_string("(block table)", 3), 0xEC83B3A3);
+  assert_eq!(hash_string("war3map.j", 1), hash_string("WAR3MAP.J", 1));
+}
+
+#[test]
+fn test_glob_match() {
+  let m = |pattern: &str, name: &str| {
+    glob_match(normalize(pattern).as_bytes(), normalize(name).as_bytes())
+  };
+  assert!(m(
+    "ReplaceableTextures/*",
+    "ReplaceableTextures\\CommandButtons\\BTNFootman.blp"
+  ));
+  assert!(m("war3map.*", "war3map.w3i"));
+  assert!(m("*.blp", "war3mapMap.blp"));
+  assert!(m("war3map.w3?", "WAR3MAP.W3E"));
+  assert!(m("*", ""));
+  assert!(!m("war3map.w3?", "war3map.j"));
+  assert!(!m("*.blp", "war3map.mmp"));
+  assert!(!m("ReplaceableTextures/*", "Textures\\Foo.blp"));
+}
+
+#[test]
+fn test_read_files_glob() {
+  let mut archive =
+    MapArchive::open(flo_util::sample_path!("map", "(2)ConcealedHill.w3x")).unwrap();
+
+  let file = archive.read_file("war3map.w3i").unwrap().unwrap();
+  let compression = file.compression.unwrap();
+  assert_eq!(compression.file_size as usize, file.bytes.len());
+  assert!(compression.flags.contains(MapFileFlags::EXISTS));
+  assert!(archive.read_file("not_found.txt").unwrap().is_none());
+
+  let files = archive.read_files_glob("war3map.w3?").unwrap();
+  assert!(files
+    .iter()
+    .any(|f| f.path.eq_ignore_ascii_case("war3map.w3i")));
+  assert!(files
+    .iter()
+    .all(|f| normalize(&f.path).starts_with("war3map.w3")));
+}
+
+#[test]
+fn test_open_archive() {
+  let path = flo_util::sample_path!("map", "(2)ConcealedHill.w3x");
+  let mut archive = W3Map::open_archive(&path).unwrap();
+  assert!(archive.has_file("war3map.w3i").unwrap());
+  assert!(archive.has_file("WAR3MAP.W3I").unwrap());
+  assert!(!archive.has_file("not_found.txt").unwrap());
+  let script = archive.read_script().unwrap().unwrap();
+
+  let bytes = std::fs::read(&path).unwrap();
+  let mut archive = MapArchive::open_memory(&bytes).unwrap();
+  assert!(archive.has_file("war3map.w3i").unwrap());
+  assert!(!archive.has_file("not_found.txt").unwrap());
+  assert_eq!(archive.read_script().unwrap().unwrap().bytes, script.bytes);
+}
+
+#[test]
+fn test_verify_sectors() {
+  assert_eq!(adler32(b"Wikipedia"), 0x11DD_0397);
+
+  // 2 sectors, the offset table and the checksum table are 4 words each
+  let sectors: [&[u8]; 2] = [&[1; 100], &[2; 50]];
+  let mut bytes = vec![];
+  let data_start = 4 * 4;
+  let crc_start = data_start + 150;
+  for offset in &[data_start, data_start + 100, crc_start, crc_start + 8] {
+    bytes.extend_from_slice(&(*offset as u32).to_le_bytes());
+  }
+  for sector in &sectors {
+    bytes.extend_from_slice(sector);
+  }
+  for sector in &sectors {
+    bytes.extend_from_slice(&adler32(sector).to_le_bytes());
+  }
+
+  let tables = MpqTables {
+    offset: 0,
+    sector_size: 512,
+    hash: vec![],
+    block: vec![],
+  };
+  let flags = (MapFileFlags::COMPRESS | MapFileFlags::SECTOR_CRC | MapFileFlags::EXISTS).bits();
+  let block = [0, bytes.len() as u32, 600, flags];
+  let verify = |bytes: &[u8]| {
+    tables
+      .verify_sectors(&mut Cursor::new(bytes), "war3map.j", block)
+      .unwrap()
+  };
+  assert_eq!(verify(&bytes), None);
+
+  let mut corrupted = bytes.clone();
+  corrupted[data_start + 120] = 0;
+  assert_eq!(verify(&corrupted), Some(FileCorruption::SectorChecksum(1)));
+
+  let mut corrupted = bytes.clone();
+  corrupted[4..8].copy_from_slice(&1000u32.to_le_bytes());
+  assert_eq!(verify(&corrupted), Some(FileCorruption::SectorTable));
+
+  assert_eq!(
+    CorruptFile {
+      path: "war3map.j".to_string(),
+      corruption: FileCorruption::SectorChecksum(1),
+    }
+    .to_string(),
+    "war3map.j: sector 1 checksum mismatch"
+  );
+}
@@ -0,0 +1,69 @@
+// The war3map.imp file : Imported files list
+
+use flo_util::binary::*;
+
+/// Path prefix of imported files that don't use a custom path
+const DEFAULT_IMPORT_PATH: &str = "war3mapImported\\";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedFile {
+  /// Path inside the map archive
+  pub path: String,
+  pub custom_path: bool,
+}
+
+pub(crate) fn decode_imports<T: Buf>(buf: &mut T) -> Result<Vec<ImportedFile>, BinDecodeError> {
+  buf.check_size(4 + 4)?;
+  let _version = buf.get_u32_le();
+  let count = buf.get_u32_le() as usize;
+  let mut files = Vec::with_capacity(std::cmp::min(count, 1024));
+  for _ in 0..count {
+    buf.check_size(1)?;
+    // 5 or 8: default path, 10 or 13: custom path
+    let custom_path = match buf.get_u8() {
+      10 | 13 => true,
+      _ => false,
+    };
+    let (bytes, _) = buf.get_delimited_bytes(b'\0')?;
+    let name = String::from_utf8_lossy(&bytes);
+    files.push(ImportedFile {
+      path: if custom_path {
+        name.into_owned()
+      } else {
+        format!("{}{}", DEFAULT_IMPORT_PATH, name)
+      },
+      custom_path,
+    });
+  }
+  Ok(files)
+}
+
+#[test]
+fn test_decode_imports() {
+  let mut buf = BytesMut::new();
+  buf.put_u32_le(1);
+  buf.put_u32_le(2);
+  buf.put_u8(13);
+  buf.put_slice(b"UI\\Glues\\Loading.blp\0");
+  buf.put_u8(5);
+  buf.put_slice(b"Hero.mdx\0");
+
+  let files = decode_imports(&mut buf.clone().freeze()).unwrap();
+  assert_eq!(
+    files,
+    vec![
+      ImportedFile {
+        path: "UI\\Glues\\Loading.blp".to_string(),
+        custom_path: true,
+      },
+      ImportedFile {
+        path: "war3mapImported\\Hero.mdx".to_string(),
+        custom_path: false,
+      }
+    ]
+  );
+
+  let mut bytes = buf.freeze();
+  bytes.truncate(bytes.len() - 3);
+  assert!(decode_imports(&mut bytes).is_err());
+}
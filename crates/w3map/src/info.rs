@@ -1,4 +1,5 @@
 use crate::trigger_string::TriggerStringRef;
+use bitflags::bitflags;
 use flo_util::binary::*;
 use flo_util::dword_string::DwordString;
 use flo_util::{BinDecode, BinEncode};
@@ -33,6 +34,7 @@ pub struct MapInfo {
   pub height: u32,
   pub flags: u32,
   pub tile_set: u8,
+  /// Preset loading screen index, `u32::MAX` for none or a custom model in `ls_path`
   pub ls_background: u32,
   #[bin(condition = "version >= MapFormatVersion::TFT")]
   pub ls_path: Option<TriggerStringRef>,
@@ -47,12 +49,15 @@ pub struct MapInfo {
   pub ps_sub_title: TriggerStringRef,
   #[bin(condition = "version >= MapFormatVersion::TFT")]
   pub env: Option<GameEnv>,
+  /// 0 for JASS, 1 for Lua
   #[bin(condition = "version >= MapFormatVersion::TFT131")]
   pub code_format: Option<u32>,
+  /// `SupportedModes` bits
   #[bin(condition = "version >= MapFormatVersion::Reforged")]
-  pub _unknown_reforged_1: Option<u32>,
+  pub supported_modes: Option<u32>,
+  /// 0 for Reign of Chaos game data, 1 for The Frozen Throne
   #[bin(condition = "version >= MapFormatVersion::Reforged")]
-  pub _unknown_reforged_2: Option<u32>,
+  pub game_data_version: Option<u32>,
   pub num_players: u32,
   #[bin(condition = "version < MapFormatVersion::Reforged")]
   #[bin(repeat = "num_players")]
@@ -116,8 +121,8 @@ pub struct ReforgedPlayer {
   pub start_pos_y: f32,
   pub ally_prio_low: u32,
   pub ally_prio_high: u32,
-  pub _unknown_1: u32,
-  pub _unknown_2: u32,
+  pub enemy_prio_low: u32,
+  pub enemy_prio_high: u32,
 }
 
 #[derive(Debug, Clone, BinDecode)]
@@ -127,6 +132,31 @@ pub struct Force {
   pub name: TriggerStringRef,
 }
 
+bitflags! {
+  /// Graphics modes a Reforged map can be played in
+  pub struct SupportedModes: u32 {
+    const SD = 0x1;
+    const HD = 0x2;
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameDataVersion {
+  ReignOfChaos,
+  FrozenThrone,
+  Unknown(u32),
+}
+
+impl From<u32> for GameDataVersion {
+  fn from(value: u32) -> Self {
+    match value {
+      0 => GameDataVersion::ReignOfChaos,
+      1 => GameDataVersion::FrozenThrone,
+      other => GameDataVersion::Unknown(other),
+    }
+  }
+}
+
 #[test]
 fn test_parse_w3i_reforged() {
   let mut map = crate::open_archive(flo_util::sample_path!("map", "(2)ConcealedHill.w3x")).unwrap();
@@ -136,6 +166,10 @@ fn test_parse_w3i_reforged() {
   assert_eq!(info.version, MapFormatVersion::Reforged);
   assert_eq!(info.num_players, 2);
   assert_eq!(info.num_forces, 1);
+  assert!(info.game_version.is_some());
+  assert!(info.supported_modes.is_some());
+  assert!(info.game_data_version.is_some());
+  assert_eq!(info.players_reforged.as_ref().map(|p| p.len()), Some(2));
   dbg!("{:#?}", info);
 }
 
@@ -193,4 +227,91 @@ fn test_fixed_player() {
   // assert_eq!(info.num_players, 0);
   // assert_eq!(info.num_forces, 1);
   dbg!("{:#?}", info);
-}
\ No newline at end of file
+}
+
+/// A minimal version 31 war3map.w3i with an imported loading screen model
+#[cfg(test)]
+pub(crate) fn w3i_31_fixture() -> Bytes {
+  fn put_str(buf: &mut BytesMut, value: &str) {
+    buf.put_slice(value.as_bytes());
+    buf.put_u8(0);
+  }
+
+  let mut buf = BytesMut::new();
+  buf.put_u32_le(31);
+  buf.put_u32_le(5);
+  buf.put_u32_le(6112);
+  for v in &[1, 32, 10, 18820] {
+    buf.put_u32_le(*v);
+  }
+  put_str(&mut buf, "TRIGSTR_001");
+  put_str(&mut buf, "author");
+  put_str(&mut buf, "");
+  put_str(&mut buf, "");
+  buf.put_slice(&[0; 4 * 12]);
+  buf.put_u32_le(100);
+  buf.put_u32_le(80);
+  buf.put_u32_le(0x4);
+  buf.put_u8(b'L');
+  buf.put_u32_le(u32::MAX);
+  put_str(&mut buf, "war3mapImported\\Loading.mdx");
+  put_str(&mut buf, "text");
+  put_str(&mut buf, "title");
+  put_str(&mut buf, "");
+  buf.put_u32_le(0);
+  for _ in 0..4 {
+    put_str(&mut buf, "");
+  }
+  buf.put_slice(&[0; 4 * 5]);
+  buf.put_slice(b"RAhr");
+  put_str(&mut buf, "");
+  buf.put_u8(0);
+  buf.put_u32_le(0xFF00_00FF);
+  buf.put_u32_le(1);
+  buf.put_u32_le(3);
+  buf.put_u32_le(1);
+  buf.put_u32_le(1);
+  for v in &[0, 1, 1, 0] {
+    buf.put_u32_le(*v);
+  }
+  put_str(&mut buf, "TRIGSTR_002");
+  buf.put_slice(&[0; 4 * 2]);
+  for v in &[0, 0, 1, 2] {
+    buf.put_u32_le(*v);
+  }
+  buf.put_u32_le(1);
+  buf.put_u32_le(0);
+  buf.put_u32_le(1);
+  put_str(&mut buf, "force");
+  buf.freeze()
+}
+
+#[test]
+fn test_decode_w3i_31() {
+  let info = MapInfo::decode(&mut w3i_31_fixture()).unwrap();
+  assert_eq!(info.version, MapFormatVersion::Reforged);
+  assert_eq!(info.game_version.as_ref().map(|v| v.commit), Some(18820));
+  assert_eq!(info.ls_background, u32::MAX);
+  assert_eq!(
+    info.ls_path,
+    Some(TriggerStringRef::Inline(
+      "war3mapImported\\Loading.mdx".to_string()
+    ))
+  );
+  assert_eq!(info.code_format, Some(1));
+  assert_eq!(
+    info.supported_modes.map(SupportedModes::from_bits_truncate),
+    Some(SupportedModes::SD | SupportedModes::HD)
+  );
+  assert_eq!(
+    info.game_data_version.map(GameDataVersion::from),
+    Some(GameDataVersion::FrozenThrone)
+  );
+  let players = info.players_reforged.unwrap();
+  assert_eq!(players[0].name, TriggerStringRef::Ref(2));
+  assert_eq!(
+    (players[0].enemy_prio_low, players[0].enemy_prio_high),
+    (1, 2)
+  );
+  assert_eq!(info.forces.len(), 1);
+}
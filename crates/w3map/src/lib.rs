@@ -1,29 +1,84 @@
 use std::borrow::Cow;
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use stormlib::OpenArchiveFlags;
 
 use flo_util::binary::BinDecode;
 
 pub mod error;
 
+mod analysis;
+pub mod builder;
 mod checksum;
+mod checksum_cache;
 mod constants;
+mod diff;
+mod files;
+mod imports;
 mod info;
 mod minimap;
+mod objects;
+mod pathing;
+mod preview;
+mod script;
+mod terrain;
+mod text;
 mod trigger_string;
+mod units;
+pub mod validation;
 
+pub use self::analysis::{
+  GoldMineDistance, MapAnalysis, SpawnDistance, StartLocationMetrics, SymmetryEstimate,
+  SymmetryKind,
+};
 pub use self::checksum::MapChecksum;
+pub use self::checksum_cache::ChecksumCache;
 pub use self::constants::*;
+pub use self::diff::*;
+pub use self::files::{
+  CorruptFile, FileCorruption, MapArchive, MapFile, MapFileCompression, MapFileFlags,
+};
+pub use self::imports::ImportedFile;
 pub use self::info::*;
 pub use self::minimap::*;
+pub use self::objects::{
+  CustomObjects, ObjectDefinition, ObjectField, ObjectKind, ObjectTable, ObjectValue,
+};
+pub use self::pathing::{DistanceField, PathingGrid, PATHING_CELL_SIZE};
+pub use self::preview::{PreviewFormat, PreviewOptions};
+pub use self::script::{normalize_script, MapScript, ScriptLanguage};
+pub use self::terrain::{
+  Terrain, TerrainHeader, TerrainStats, TilePoint, TilePointFlags, TILE_SIZE,
+};
+pub use self::text::{FormattedText, TextColor, TextSegment};
 pub use self::trigger_string::*;
+pub use self::units::{
+  CreepCamp, DropItem, DropItemSet, InventoryItem, MapUnits, PlacedUnit, RandomChoice, RandomSpec,
+  UnitAbility, CREEP_CAMP_RADIUS, GOLD_MINE_TYPE_ID, START_LOCATION_TYPE_ID,
+};
 
 pub use flo_blp::BLPImage;
 #[cfg(feature = "w3storage")]
 use flo_w3storage::W3Storage;
 
-use self::error::{Error, Result};
+use self::error::{Error, MapSection, Result, SectionError};
+
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+  /// Reads every file and checks the sector checksums before loading the map,
+  /// opening fails with `Error::CorruptedFiles` listing the broken files
+  pub verify: bool,
+}
+
+/// A map loaded by `W3Map::open_partial`
+#[derive(Debug)]
+pub struct PartialMap {
+  pub map: W3Map,
+  /// Sections that failed to load, empty in `map`
+  pub errors: Vec<SectionError>,
+}
 
 #[derive(Debug)]
 pub struct W3Map {
@@ -33,6 +88,14 @@ pub struct W3Map {
   image: Option<BLPImage>,
   minimap_icons: MinimapIcons,
   trigger_strings: TriggerStringMap,
+  localized_trigger_strings: LocalizedTriggerStrings,
+  units: Option<MapUnits>,
+  custom_objects: CustomObjects,
+  skins: CustomObjects,
+  imported_files: Vec<ImportedFile>,
+  script: Option<MapScript>,
+  terrain: Option<Terrain>,
+  pathing: Option<PathingGrid>,
 }
 
 impl W3Map {
@@ -47,10 +110,53 @@ impl W3Map {
     Ok((map, checksum))
   }
 
+  /// `open_with_checksum` that reuses the checksum computed for the same file,
+  /// the file is identified by its absolute path, size and modification time
+  pub fn open_with_checksum_cached<P: AsRef<Path>>(
+    path: P,
+    cache: &ChecksumCache,
+  ) -> Result<(Self, MapChecksum)> {
+    let path = path.as_ref();
+    let key = checksum_cache::ChecksumCacheKey::new(path)?;
+    if let Some(checksum) = key.as_ref().and_then(|key| cache.lookup(key)) {
+      return Ok((Self::open(path)?, checksum));
+    }
+    let (map, checksum) = Self::open_with_checksum(path)?;
+    if let Some(key) = key {
+      // the cache only saves work, failing to persist it doesn't fail the open
+      cache.store(key, checksum.clone()).ok();
+    }
+    Ok((map, checksum))
+  }
+
   pub fn open_memory(bytes: &[u8]) -> Result<Self> {
     Self::load_info(Self::open_archive_memory(bytes)?)
   }
 
+  pub fn open_with_options<P: AsRef<Path>>(path: P, options: &OpenOptions) -> Result<Self> {
+    Self::load_info_with_options(Self::open_archive_file(path)?, options)
+  }
+
+  pub fn open_memory_with_options(bytes: &[u8], options: &OpenOptions) -> Result<Self> {
+    Self::load_info_with_options(Self::open_archive_memory(bytes)?, options)
+  }
+
+  /// Loads the sections that can be read, a corrupted image or object table doesn't fail
+  /// the load. Fails if the archive or the map info can't be read
+  pub fn open_partial<P: AsRef<Path>>(path: P) -> Result<PartialMap> {
+    Self::load_partial(Self::open_archive_file(path)?)
+  }
+
+  pub fn open_memory_partial(bytes: &[u8]) -> Result<PartialMap> {
+    Self::load_partial(Self::open_archive_memory(bytes)?)
+  }
+
+  /// Opens the map archive for reading files `W3Map` doesn't decode,
+  /// e.g. the map script or imported assets
+  pub fn open_archive<P: AsRef<Path>>(path: P) -> Result<MapArchive<'static>> {
+    MapArchive::open(path)
+  }
+
   #[cfg(feature = "w3storage")]
   pub fn open_storage(storage: &W3Storage, path: &str) -> Result<Self> {
     use flo_w3storage::Data;
@@ -67,6 +173,39 @@ impl W3Map {
   pub fn open_storage_with_checksum(
     storage: &W3Storage,
     path: &str,
+  ) -> Result<(Self, MapChecksum)> {
+    Self::open_storage_with_checksum_cancellable(storage, path, &AtomicBool::new(false))
+  }
+
+  /// `open_storage_with_checksum` with the checksums of override files cached,
+  /// see `open_with_checksum_cached`
+  #[cfg(feature = "w3storage")]
+  pub fn open_storage_with_checksum_cached(
+    storage: &W3Storage,
+    path: &str,
+    cache: &ChecksumCache,
+  ) -> Result<(Self, MapChecksum)> {
+    use flo_w3storage::Data;
+    let file = storage
+      .resolve_file(path)?
+      .ok_or_else(|| Error::StorageFileNotFound(path.to_string()))?;
+    match *file.data() {
+      Data::Path(ref path) => Self::open_with_checksum_cached(path, cache),
+      // files in the game storage have no modification time
+      Data::Bytes(ref bytes) => {
+        let mut archive = Self::open_archive_memory(bytes)?;
+        let checksum = MapChecksum::compute(&mut archive)?;
+        let map = Self::load_info(archive)?;
+        Ok((map, checksum))
+      }
+    }
+  }
+
+  #[cfg(feature = "w3storage")]
+  fn open_storage_with_checksum_cancellable(
+    storage: &W3Storage,
+    path: &str,
+    cancelled: &AtomicBool,
   ) -> Result<(Self, MapChecksum)> {
     use flo_w3storage::Data;
     let file = storage
@@ -76,25 +215,97 @@ impl W3Map {
       Data::Path(ref path) => Self::open_archive_file(path),
       Data::Bytes(ref bytes) => Self::open_archive_memory(bytes),
     }?;
-    let checksum = MapChecksum::compute(&mut archive)?;
+    let checksum = MapChecksum::compute_cancellable(&mut archive, cancelled)?;
     let map = Self::load_info(archive)?;
     Ok((map, checksum))
   }
 
   #[cfg(feature = "w3storage")]
   pub fn calc_checksum(storage: &W3Storage, path: &str) -> Result<MapChecksum> {
-    use flo_w3storage::Data;
     let file = storage
       .resolve_file(path)?
       .ok_or_else(|| Error::StorageFileNotFound(path.to_string()))?;
+    Self::calc_file_checksum(&file)
+  }
+
+  #[cfg(feature = "w3storage")]
+  pub fn calc_file_checksum(file: &flo_w3storage::File) -> Result<MapChecksum> {
+    Self::calc_file_checksum_cancellable(file, &AtomicBool::new(false))
+  }
+
+  #[cfg(feature = "w3storage")]
+  fn calc_file_checksum_cancellable(
+    file: &flo_w3storage::File,
+    cancelled: &AtomicBool,
+  ) -> Result<MapChecksum> {
+    use flo_w3storage::Data;
     let mut archive = match *file.data() {
       Data::Path(ref path) => Self::open_archive_file(path),
       Data::Bytes(ref bytes) => Self::open_archive_memory(bytes),
     }?;
-    let checksum = MapChecksum::compute(&mut archive)?;
+    let checksum = MapChecksum::compute_cancellable(&mut archive, cancelled)?;
     Ok(checksum)
   }
 
+  /// Non-blocking `open`, the archive is read on the blocking thread pool
+  pub async fn open_async<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let path = path.as_ref().to_owned();
+    spawn_blocking(move |_| Self::open(path)).await
+  }
+
+  /// Non-blocking `open_partial`
+  pub async fn open_partial_async<P: AsRef<Path>>(path: P) -> Result<PartialMap> {
+    let path = path.as_ref().to_owned();
+    spawn_blocking(move |_| Self::open_partial(path)).await
+  }
+
+  /// Non-blocking `open_with_checksum`,
+  /// dropping the future stops the checksum computation
+  pub async fn open_with_checksum_async<P: AsRef<Path>>(path: P) -> Result<(Self, MapChecksum)> {
+    let path = path.as_ref().to_owned();
+    spawn_blocking(move |cancelled| {
+      let mut archive = Self::open_archive_file(path)?;
+      let checksum = MapChecksum::compute_cancellable(&mut archive, cancelled)?;
+      let map = Self::load_info(archive)?;
+      Ok((map, checksum))
+    })
+    .await
+  }
+
+  /// Non-blocking `open_storage`
+  #[cfg(feature = "w3storage")]
+  pub async fn open_storage_async(storage: Arc<W3Storage>, path: &str) -> Result<Self> {
+    let path = path.to_string();
+    spawn_blocking(move |_| Self::open_storage(&storage, &path)).await
+  }
+
+  /// Non-blocking `open_storage_with_checksum`,
+  /// dropping the future stops the checksum computation
+  #[cfg(feature = "w3storage")]
+  pub async fn open_storage_with_checksum_async(
+    storage: Arc<W3Storage>,
+    path: &str,
+  ) -> Result<(Self, MapChecksum)> {
+    let path = path.to_string();
+    spawn_blocking(move |cancelled| {
+      Self::open_storage_with_checksum_cancellable(&storage, &path, cancelled)
+    })
+    .await
+  }
+
+  /// Non-blocking `calc_checksum`, dropping the future stops the computation
+  #[cfg(feature = "w3storage")]
+  pub async fn calc_checksum_async(storage: Arc<W3Storage>, path: &str) -> Result<MapChecksum> {
+    let path = path.to_string();
+    spawn_blocking(move |cancelled| {
+      let file = storage
+        .resolve_file(&path)?
+        .ok_or_else(|| Error::StorageFileNotFound(path.clone()))?;
+      Self::calc_file_checksum_cancellable(&file, cancelled)
+    })
+    .await
+  }
+
   pub fn render_preview_jpeg(&self) -> Vec<u8> {
     let mut bg = if let Some(ref image) = self.image {
       image.buffer().clone()
@@ -138,6 +349,24 @@ impl W3Map {
       .unwrap_or(Cow::Borrowed(""))
   }
 
+  /// Name in `locale` if the map ships strings for it, otherwise in the default language
+  pub fn name_localized(&self, locale: &str) -> Cow<str> {
+    self
+      .localized_trigger_strings
+      .resolve(locale, &self.trigger_strings, &self.info.name)
+      .unwrap_or(Cow::Borrowed(""))
+  }
+
+  /// Locales with strings under `_Locales`, empty for classic maps
+  pub fn locales(&self) -> impl Iterator<Item = &str> {
+    self.localized_trigger_strings.locales()
+  }
+
+  /// Name with embedded trigger strings resolved, parsed into colored segments
+  pub fn name_text(&self) -> FormattedText {
+    FormattedText::parse(&self.trigger_strings.interpolate(&self.name()))
+  }
+
   pub fn description(&self) -> Cow<str> {
     self
       .trigger_strings
@@ -216,6 +445,152 @@ impl W3Map {
   pub fn flags(&self) -> MapFlags {
     MapFlags::from_bits_truncate(self.info.flags)
   }
+
+  /// Game version the map was saved with, `None` for maps saved before 1.31
+  pub fn game_version(&self) -> Option<&GameVersion> {
+    self.info.game_version.as_ref()
+  }
+
+  pub fn script_language(&self) -> ScriptLanguage {
+    match self.info.code_format {
+      Some(1) => ScriptLanguage::Lua,
+      _ => ScriptLanguage::Jass,
+    }
+  }
+
+  /// `None` for maps saved before Reforged
+  pub fn supported_modes(&self) -> Option<SupportedModes> {
+    self
+      .info
+      .supported_modes
+      .map(SupportedModes::from_bits_truncate)
+  }
+
+  /// `None` for maps saved before Reforged
+  pub fn game_data_version(&self) -> Option<GameDataVersion> {
+    self.info.game_data_version.map(GameDataVersion::from)
+  }
+
+  pub fn loading_screen(&self) -> LoadingScreen {
+    let get = |value: &TriggerStringRef| self.trigger_strings.get(value).unwrap_or_default();
+    LoadingScreen {
+      preset: Some(self.info.ls_background).filter(|v| *v != u32::MAX),
+      custom_model: self
+        .info
+        .ls_path
+        .as_ref()
+        .and_then(|path| self.trigger_strings.get(path))
+        .filter(|path| !path.is_empty()),
+      text: get(&self.info.ls_text),
+      title: get(&self.info.ls_title),
+      sub_title: get(&self.info.ls_sub_title),
+    }
+  }
+
+  /// Preplaced units and items, `None` if the map doesn't contain war3mapUnits.doo
+  pub fn units(&self) -> Option<&MapUnits> {
+    self.units.as_ref()
+  }
+
+  /// Modified and custom units, items and abilities
+  pub fn custom_objects(&self) -> &CustomObjects {
+    &self.custom_objects
+  }
+
+  /// Skin overrides of units, items and abilities, empty for maps saved before Reforged
+  pub fn skins(&self) -> &CustomObjects {
+    &self.skins
+  }
+
+  pub fn creep_camps(&self) -> Vec<CreepCamp> {
+    // maps saved by 1.31+ support 24 players
+    let neutral_hostile = if self.info.version >= MapFormatVersion::TFT131 {
+      24
+    } else {
+      12
+    };
+    self
+      .units
+      .as_ref()
+      .map(|units| units.creep_camps(neutral_hostile))
+      .unwrap_or_default()
+  }
+
+  /// Files listed in war3map.imp
+  pub fn imported_files(&self) -> &[ImportedFile] {
+    &self.imported_files
+  }
+
+  /// The map script and its fingerprint, `None` if the map has no script,
+  /// protected maps often remove it
+  pub fn script(&self) -> Option<&MapScript> {
+    self.script.as_ref()
+  }
+
+  /// Tilesets, heights and water of the map, `None` if the map doesn't contain war3map.w3e
+  pub fn terrain(&self) -> Option<&Terrain> {
+    self.terrain.as_ref()
+  }
+
+  /// Walkability of the map, `None` if the map doesn't contain war3map.w3e and war3map.wpm
+  pub fn pathing(&self) -> Option<&PathingGrid> {
+    self.pathing.as_ref()
+  }
+
+  pub(crate) fn start_locations(&self) -> Vec<analysis::StartLocation> {
+    self
+      .info
+      .players_classic
+      .as_ref()
+      .map(|players| {
+        players
+          .iter()
+          .map(|p| analysis::StartLocation {
+            player_id: p.id,
+            x: p.start_pos_x,
+            y: p.start_pos_y,
+          })
+          .collect()
+      })
+      .or_else(|| {
+        self.info.players_reforged.as_ref().map(|players| {
+          players
+            .iter()
+            .map(|p| analysis::StartLocation {
+              player_id: p.id,
+              x: p.start_pos_x,
+              y: p.start_pos_y,
+            })
+            .collect()
+        })
+      })
+      .unwrap_or_default()
+  }
+
+  /// Spawn-to-spawn and nearest gold mine distances per start location, and symmetry estimates.
+  /// Walking distances and pathing symmetry are only available if the map contains
+  /// its terrain and pathing files.
+  pub fn analysis(&self) -> MapAnalysis {
+    let start_locations = self.start_locations();
+    let gold_mines: Vec<_> = self
+      .units
+      .as_ref()
+      .map(|units| units.gold_mines().collect())
+      .unwrap_or_default();
+    let center = match self.terrain {
+      Some(ref terrain) => terrain.header().center(),
+      None => {
+        let b = &self.info.camera_bounds.bounds;
+        ((b[0] + b[2] + b[4] + b[6]) / 4., (b[1] + b[3] + b[5] + b[7]) / 4.)
+      }
+    };
+    analysis::analyze(
+      &start_locations,
+      &gold_mines,
+      self.pathing.as_ref(),
+      center,
+    )
+  }
 }
 
 pub(crate) fn open_archive<P: AsRef<Path>>(path: P) -> Result<stormlib::Archive> {
@@ -243,20 +618,53 @@ impl W3Map {
     }))
   }
 
-  fn load_info(mut archive: Archive) -> Result<Self> {
-    let trigger_strings = match archive.read_file_all_opt("war3map.wts") {
-      Ok(Some(bytes)) => {
-        TriggerStringMap::decode(&mut bytes.as_slice()).map_err(Error::ReadTriggerStrings)?
+  fn load_info_with_options(archive: Archive, options: &OpenOptions) -> Result<Self> {
+    let archive = if options.verify {
+      let mut archive = MapArchive::new(archive);
+      let corrupted = archive.verify()?;
+      if !corrupted.is_empty() {
+        return Err(Error::CorruptedFiles(corrupted));
       }
-      Ok(None) => TriggerStringMap::empty(),
-      Err(err) => {
-        if Archive::is_err_file_not_found(&err) {
-          TriggerStringMap::empty()
-        } else {
-          return Err(err);
+      archive.into_archive()
+    } else {
+      archive
+    };
+    Self::load_info(archive)
+  }
+
+  fn load_info(archive: Archive) -> Result<Self> {
+    Self::load_sections(archive, None)
+  }
+
+  fn load_partial(archive: Archive) -> Result<PartialMap> {
+    let mut errors = vec![];
+    let map = Self::load_sections(archive, Some(&mut errors))?;
+    Ok(PartialMap { map, errors })
+  }
+
+  /// Without `errors` the first section error fails the load,
+  /// otherwise failed sections are recorded and left empty
+  fn load_sections(
+    mut archive: Archive,
+    mut errors: Option<&mut Vec<SectionError>>,
+  ) -> Result<Self> {
+    let trigger_strings = {
+      let res = match archive.read_file_all_opt("war3map.wts") {
+        Ok(Some(bytes)) => {
+          TriggerStringMap::decode(&mut bytes.as_slice()).map_err(Error::ReadTriggerStrings)
         }
-      }
+        Ok(None) => Ok(TriggerStringMap::empty()),
+        Err(err) if Archive::is_err_file_not_found(&err) => Ok(TriggerStringMap::empty()),
+        Err(err) => Err(err),
+      };
+      check_section(
+        &mut errors,
+        MapSection::TriggerStrings,
+        res,
+        TriggerStringMap::empty,
+      )?
     };
+    let localized_trigger_strings = LocalizedTriggerStrings::read(&mut archive);
 
     let info: MapInfo = {
       let bytes = archive
@@ -265,6 +673,93 @@ impl W3Map {
       BinDecode::decode(&mut bytes.as_slice()).map_err(Error::ReadInfo)?
     };
 
+    // placement data is optional, protected maps often strip these files
+    let units = {
+      let has_skin_id = info
+        .game_version
+        .as_ref()
+        .map(|v| v.major * 100 + v.minor >= 132)
+        .unwrap_or(false);
+      archive
+        .read_file_all_opt("war3mapUnits.doo")
+        .ok()
+        .flatten()
+        .and_then(|bytes| MapUnits::decode(&mut bytes.as_slice(), has_skin_id).ok())
+    };
+    let mut read_objects = |skins: bool| -> Result<CustomObjects> {
+      let mut read = |kind: ObjectKind| -> Result<Option<ObjectTable>> {
+        let file_name = if skins {
+          kind.skin_file_name()
+        } else {
+          kind.file_name()
+        };
+        archive
+          .read_file_all_opt(file_name)?
+          .map(|bytes| ObjectTable::decode(&mut bytes.as_slice(), kind).map_err(Error::ReadObjects))
+          .transpose()
+      };
+      Ok(CustomObjects {
+        units: read(ObjectKind::Unit)?,
+        items: read(ObjectKind::Item)?,
+        abilities: read(ObjectKind::Ability)?,
+      })
+    };
+    let custom_objects = check_section(
+      &mut errors,
+      MapSection::Objects,
+      read_objects(false),
+      Default::default,
+    )?;
+    let skins = check_section(
+      &mut errors,
+      MapSection::Skins,
+      read_objects(true),
+      Default::default,
+    )?;
+    let imported_files = archive
+      .read_file_all_opt("war3map.imp")
+      .ok()
+      .flatten()
+      .and_then(|bytes| imports::decode_imports(&mut bytes.as_slice()).ok())
+      .unwrap_or_default();
+    let script = MapScript::read(&mut archive).ok().flatten();
+    let terrain: Option<Terrain> = archive
+      .read_file_all_opt("war3map.w3e")
+      .ok()
+      .flatten()
+      .and_then(|bytes| BinDecode::decode(&mut bytes.as_slice()).ok());
+    let pathing = match terrain {
+      Some(ref terrain) => archive
+        .read_file_all_opt("war3map.wpm")
+        .ok()
+        .flatten()
+        .and_then(|bytes| PathingGrid::decode(&mut bytes.as_slice(), terrain.header()).ok()),
+      None => None,
+    };
+    let image = check_section(
+      &mut errors,
+      MapSection::Image,
+      archive
+        .read_file_all_opt("war3mapMap.blp")
+        .and_then(|bytes| {
+          bytes
+            .map(|bytes| BLPImage::decode(&mut bytes.as_slice()).map_err(Error::ReadImage))
+            .transpose()
+        }),
+      || None,
+    )?;
+    let minimap_icons: MinimapIcons = check_section(
+      &mut errors,
+      MapSection::MinimapIcons,
+      archive
+        .read_file_all_opt("war3map.mmp")
+        .and_then(|bytes| match bytes {
+          Some(bytes) => BinDecode::decode(&mut bytes.as_slice()).map_err(Error::ReadMinimapIcons),
+          None => Ok(Default::default()),
+        }),
+      Default::default,
+    )?;
+
     Ok(W3Map {
       suggested_players: trigger_strings
         .get(&info.suggested_players)
@@ -272,21 +767,17 @@ impl W3Map {
         .unwrap_or_else(|| "".to_string()),
       file_size: archive.get_size()?,
       info,
-      image: {
-        archive
-          .read_file_all_opt("war3mapMap.blp")?
-          .map(|bytes| BLPImage::decode(&mut bytes.as_slice()).map_err(Error::ReadImage))
-          .transpose()?
-      },
-      minimap_icons: {
-        let bytes = archive.read_file_all_opt("war3map.mmp")?;
-        if let Some(bytes) = bytes {
-          BinDecode::decode(&mut bytes.as_slice()).map_err(Error::ReadMinimapIcons)?
-        } else {
-          Default::default()
-        }
-      },
+      image,
+      minimap_icons,
       trigger_strings,
+      localized_trigger_strings,
+      units,
+      custom_objects,
+      skins,
+      imported_files,
+      script,
+      terrain,
+      pathing,
     })
   }
 }
@@ -325,6 +816,21 @@ impl<'a> Archive<'a> {
     Ok(bytes)
   }
 
+  fn has_file(&mut self, path: &str) -> Result<bool> {
+    let res: Result<()> = match *self {
+      Archive::File(ref mut archive) => archive.inner.open_file(path).map(drop).map_err(Into::into),
+      // ceres_mpq can't look up a file without reading it
+      Archive::Memory(ref mut archive) => {
+        archive.inner.read_file(path).map(drop).map_err(Into::into)
+      }
+    };
+    match res {
+      Ok(()) => Ok(true),
+      Err(e) if Self::is_err_file_not_found(&e) => Ok(false),
+      Err(e) => Err(e),
+    }
+  }
+
   fn read_file_all_opt(&mut self, path: &str) -> Result<Option<Vec<u8>>> {
     self.read_file_all(path).map(Some).or_else(|e| {
       if Self::is_err_file_not_found(&e) {
@@ -352,6 +858,17 @@ pub struct MapPlayer<'a> {
   pub flags: u32,
 }
 
+#[derive(Debug)]
+pub struct LoadingScreen<'a> {
+  /// Index of the preset loading screen, `None` for the default or a custom model
+  pub preset: Option<u32>,
+  /// Model path of an imported loading screen
+  pub custom_model: Option<Cow<'a, str>>,
+  pub text: Cow<'a, str>,
+  pub title: Cow<'a, str>,
+  pub sub_title: Cow<'a, str>,
+}
+
 #[derive(Debug)]
 pub struct MapForce<'a> {
   pub name: Cow<'a, str>,
@@ -372,8 +889,96 @@ fn test_open_map() {
     let map = W3Map::open(flo_util::sample_path!("map", name)).unwrap();
     // let _data = map.render_preview_png();
     // std::fs::write(format!("{}.png", name), data).unwrap()
-    dbg!(map);
+    let analysis = map.analysis();
+    let n = analysis.start_locations.len();
+    assert_eq!(n, map.num_players(), "{}", name);
+    assert_eq!(analysis.spawn_distances.len(), n * (n - 1) / 2, "{}", name);
+    assert_eq!(analysis.symmetry.len(), 6, "{}", name);
+    for spawn in &analysis.spawn_distances {
+      assert!(spawn.distance > 0., "{}", name);
+    }
+  }
+
+  let map = W3Map::open(flo_util::sample_path!("map", "(2)ConcealedHill.w3x")).unwrap();
+  let analysis = map.analysis();
+  assert_eq!(analysis.start_locations.len(), 2);
+  assert!(analysis.num_gold_mines >= 2);
+  for loc in &analysis.start_locations {
+    let mine = loc.nearest_gold_mine.as_ref().unwrap();
+    assert!(mine.gold > 0);
+    // main base mine
+    assert!(mine.distance > 0. && mine.distance < 1024.);
+    assert!(mine.walk_distance.is_some());
+  }
+  let spawn = &analysis.spawn_distances[0];
+  assert_eq!(spawn.player_ids, [0, 1]);
+  // snapping to walkable cells can shorten the path by a few cells
+  let walk_distance = spawn.walk_distance.unwrap();
+  assert!(walk_distance > spawn.distance - 2. * 4. * PATHING_CELL_SIZE);
+  // a 1v1 ladder map is symmetric
+  assert!(analysis.symmetry[0].placement_deviation < 2. * PATHING_CELL_SIZE);
+  let camps = map.creep_camps();
+  assert!(!camps.is_empty());
+  assert!(camps.iter().all(|camp| !camp.units.is_empty()));
+}
+
+// The section's empty value is used if the load records errors instead of failing
+fn check_section<T, F>(
+  errors: &mut Option<&mut Vec<SectionError>>,
+  section: MapSection,
+  res: Result<T>,
+  empty: F,
+) -> Result<T>
+where
+  F: FnOnce() -> T,
+{
+  match (res, errors) {
+    (Ok(v), _) => Ok(v),
+    (Err(source), Some(errors)) => {
+      errors.push(SectionError { section, source });
+      Ok(empty())
+    }
+    (Err(err), None) => Err(err),
+  }
+}
+
+/// Runs `f` on the blocking thread pool.
+/// The flag passed to `f` is set if the returned future is dropped before `f` finishes,
+/// the blocking work can't be aborted, long running steps check the flag instead
+async fn spawn_blocking<F, R>(f: F) -> Result<R>
+where
+  F: FnOnce(&AtomicBool) -> Result<R> + Send + 'static,
+  R: Send + 'static,
+{
+  struct CancelOnDrop(Arc<AtomicBool>);
+
+  impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+      self.0.store(true, Ordering::Relaxed);
+    }
   }
+
+  let cancelled = Arc::new(AtomicBool::new(false));
+  let _guard = CancelOnDrop(cancelled.clone());
+  tokio::task::spawn_blocking(move || f(&cancelled)).await?
+}
+
+#[test]
+fn test_open_async() {
+  let rt = tokio::runtime::Builder::new_current_thread()
+    .build()
+    .unwrap();
+  let path = flo_util::sample_path!("map", "test_tft.w3x");
+  let (map, checksum) = rt.block_on(W3Map::open_with_checksum_async(&path)).unwrap();
+  let (expected_map, expected_checksum) = W3Map::open_with_checksum(&path).unwrap();
+  assert_eq!(map.name(), expected_map.name());
+  assert_eq!(checksum, expected_checksum);
+
+  let mut archive = W3Map::open_archive_file(&path).unwrap();
+  assert!(matches!(
+    MapChecksum::compute_cancellable(&mut archive, &AtomicBool::new(true)),
+    Err(Error::Cancelled)
+  ));
 }
 
 #[cfg(feature = "w3storage")]
@@ -428,3 +1033,57 @@ fn test_open_map_special() {
   .unwrap();
   dbg!(map.flags());
 }
+
+#[test]
+fn test_reforged_metadata() {
+  let map = W3Map::open(flo_util::sample_path!("map", "(2)ConcealedHill.w3x")).unwrap();
+  assert!(map.game_version().is_some());
+  assert!(map.supported_modes().is_some());
+  assert!(map.game_data_version().is_some());
+  assert_eq!(map.script_language(), ScriptLanguage::Jass);
+  let loading_screen = map.loading_screen();
+  assert!(loading_screen.preset.is_none() || loading_screen.custom_model.is_none());
+
+  let mut builder =
+    builder::MapBuilder::open(flo_util::sample_path!("map", "(2)ConcealedHill.w3x")).unwrap();
+  builder.insert("war3map.w3i", info::w3i_31_fixture().to_vec());
+  let bytes = builder.to_bytes().unwrap();
+  let map = W3Map::open_memory(&bytes).unwrap();
+  let loading_screen = map.loading_screen();
+  assert_eq!(loading_screen.preset, None);
+  assert_eq!(
+    loading_screen.custom_model.as_deref(),
+    Some("war3mapImported\\Loading.mdx")
+  );
+  assert_eq!(loading_screen.text, "text");
+  assert_eq!(loading_screen.title, "title");
+  assert_eq!(loading_screen.sub_title, "");
+
+  let map = W3Map::open(flo_util::sample_path!("map", "test_roc.w3m")).unwrap();
+  assert!(map.game_version().is_none());
+  assert!(map.supported_modes().is_none());
+  assert!(map.game_data_version().is_none());
+  assert!(map.skins().is_empty());
+  assert_eq!(map.loading_screen().custom_model, None);
+}
+
+#[test]
+fn test_open_partial() {
+  let mut builder =
+    builder::MapBuilder::open(flo_util::sample_path!("map", "test_tft.w3x")).unwrap();
+  let expected = W3Map::open(flo_util::sample_path!("map", "test_tft.w3x")).unwrap();
+  builder.insert("war3map.mmp", vec![1, 2, 3]);
+  let bytes = builder.to_bytes().unwrap();
+
+  assert!(matches!(
+    W3Map::open_memory(&bytes),
+    Err(Error::ReadMinimapIcons(_))
+  ));
+
+  let PartialMap { map, errors } = W3Map::open_memory_partial(&bytes).unwrap();
+  assert_eq!(errors.len(), 1);
+  assert_eq!(errors[0].section, MapSection::MinimapIcons);
+  assert_eq!(map.name(), expected.name());
+  assert_eq!(map.get_players().len(), expected.get_players().len());
+  assert_eq!(map.minimap_icons.iter().count(), 0);
+}
@@ -0,0 +1,356 @@
+// The war3map.w3u, war3map.w3t and war3map.w3a files : Object data,
+// modified standard units, items and abilities, and custom ones based on them
+
+use flo_util::binary::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectKind {
+  Unit,
+  Item,
+  Ability,
+}
+
+impl ObjectKind {
+  pub fn file_name(&self) -> &'static str {
+    match *self {
+      ObjectKind::Unit => "war3map.w3u",
+      ObjectKind::Item => "war3map.w3t",
+      ObjectKind::Ability => "war3map.w3a",
+    }
+  }
+
+  /// Reforged skin data, same format as `file_name`
+  pub fn skin_file_name(&self) -> &'static str {
+    match *self {
+      ObjectKind::Unit => "war3mapSkin.w3u",
+      ObjectKind::Item => "war3mapSkin.w3t",
+      ObjectKind::Ability => "war3mapSkin.w3a",
+    }
+  }
+
+  // abilities, doodads and upgrades have per level values
+  fn has_levels(&self) -> bool {
+    match *self {
+      ObjectKind::Ability => true,
+      ObjectKind::Unit | ObjectKind::Item => false,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectValue {
+  Int(i32),
+  Real(f32),
+  /// Real in the range [0, 1]
+  Unreal(f32),
+  /// Can be a trigger string reference like `TRIGSTR_012`
+  String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectField {
+  /// e.g. `unam` for the name of a unit
+  pub field_id: [u8; 4],
+  /// Ability level the value applies to, 0 for fields without levels
+  pub level: u32,
+  /// Column of ability data fields like `DataA`, 0 for other fields
+  pub data_pointer: u32,
+  pub value: ObjectValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectDefinition {
+  /// The standard object modified or the custom object is based on
+  pub base_id: [u8; 4],
+  /// `None` if this modifies the standard object
+  pub custom_id: Option<[u8; 4]>,
+  pub fields: Vec<ObjectField>,
+}
+
+impl ObjectDefinition {
+  /// The id the game uses for this object
+  pub fn id(&self) -> [u8; 4] {
+    self.custom_id.unwrap_or(self.base_id)
+  }
+
+  pub fn id_str(&self) -> String {
+    String::from_utf8_lossy(&self.id()).into_owned()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectTable {
+  pub kind: ObjectKind,
+  pub version: u32,
+  /// Modified standard objects
+  pub original: Vec<ObjectDefinition>,
+  pub custom: Vec<ObjectDefinition>,
+}
+
+impl ObjectTable {
+  pub fn decode<T: Buf>(buf: &mut T, kind: ObjectKind) -> Result<Self, BinDecodeError> {
+    buf.check_size(4)?;
+    let version = buf.get_u32_le();
+    let original =
+      decode_definitions(buf, kind, version).map_err(|err| err.context("original table"))?;
+    let custom =
+      decode_definitions(buf, kind, version).map_err(|err| err.context("custom table"))?;
+    Ok(Self {
+      kind,
+      version,
+      original,
+      custom,
+    })
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &ObjectDefinition> {
+    self.original.iter().chain(self.custom.iter())
+  }
+
+  pub fn get(&self, id: [u8; 4]) -> Option<&ObjectDefinition> {
+    self.iter().find(|def| def.id() == id)
+  }
+
+  /// The modified standard object and the custom objects based on `base_id`
+  pub fn derived_from(&self, base_id: [u8; 4]) -> impl Iterator<Item = &ObjectDefinition> {
+    self.iter().filter(move |def| def.base_id == base_id)
+  }
+}
+
+/// Object data of a map, tables are `None` if the map doesn't contain the file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CustomObjects {
+  pub units: Option<ObjectTable>,
+  pub items: Option<ObjectTable>,
+  pub abilities: Option<ObjectTable>,
+}
+
+impl CustomObjects {
+  pub fn get(&self, kind: ObjectKind) -> Option<&ObjectTable> {
+    match kind {
+      ObjectKind::Unit => self.units.as_ref(),
+      ObjectKind::Item => self.items.as_ref(),
+      ObjectKind::Ability => self.abilities.as_ref(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self
+      .units
+      .iter()
+      .chain(self.items.iter())
+      .chain(self.abilities.iter())
+      .all(|table| table.original.is_empty() && table.custom.is_empty())
+  }
+
+  /// Objects modifying or based on any of `base_ids`
+  pub fn find_derived(&self, kind: ObjectKind, base_ids: &[[u8; 4]]) -> Vec<&ObjectDefinition> {
+    self
+      .get(kind)
+      .into_iter()
+      .flat_map(|table| table.iter())
+      .filter(|def| base_ids.contains(&def.base_id))
+      .collect()
+  }
+}
+
+fn decode_definitions<T: Buf>(
+  buf: &mut T,
+  kind: ObjectKind,
+  version: u32,
+) -> Result<Vec<ObjectDefinition>, BinDecodeError> {
+  buf.check_size(4)?;
+  let count = buf.get_u32_le() as usize;
+  let mut defs = Vec::with_capacity(std::cmp::min(count, 1024));
+  for idx in 0..count {
+    let def = decode_definition(buf, kind, version)
+      .map_err(|err| err.context(format!("object #{}", idx)))?;
+    defs.push(def);
+  }
+  Ok(defs)
+}
+
+fn decode_definition<T: Buf>(
+  buf: &mut T,
+  kind: ObjectKind,
+  version: u32,
+) -> Result<ObjectDefinition, BinDecodeError> {
+  buf.check_size(4 + 4 + 4)?;
+  let mut base_id = [0; 4];
+  buf.copy_to_slice(&mut base_id);
+  let mut custom_id = [0; 4];
+  buf.copy_to_slice(&mut custom_id);
+
+  // 1.33+ groups the fields into sets, the fields of all sets are merged
+  let sets = if version >= 3 { buf.get_u32_le() } else { 1 };
+  let mut fields = vec![];
+  for _ in 0..sets {
+    if version >= 3 {
+      buf.check_size(4)?;
+      let _flags = buf.get_u32_le();
+    }
+    buf.check_size(4)?;
+    let count = buf.get_u32_le() as usize;
+    for _ in 0..count {
+      fields.push(decode_field(buf, kind)?);
+    }
+  }
+
+  Ok(ObjectDefinition {
+    base_id,
+    custom_id: if custom_id == [0; 4] {
+      None
+    } else {
+      Some(custom_id)
+    },
+    fields,
+  })
+}
+
+fn decode_field<T: Buf>(buf: &mut T, kind: ObjectKind) -> Result<ObjectField, BinDecodeError> {
+  buf.check_size(4 + 4)?;
+  let mut field_id = [0; 4];
+  buf.copy_to_slice(&mut field_id);
+  let value_type = buf.get_u32_le();
+  let (level, data_pointer) = if kind.has_levels() {
+    buf.check_size(4 + 4)?;
+    (buf.get_u32_le(), buf.get_u32_le())
+  } else {
+    (0, 0)
+  };
+  let value = match value_type {
+    0 | 1 | 2 => {
+      buf.check_size(4)?;
+      match value_type {
+        0 => ObjectValue::Int(buf.get_i32_le()),
+        1 => ObjectValue::Real(buf.get_f32_le()),
+        _ => ObjectValue::Unreal(buf.get_f32_le()),
+      }
+    }
+    3 => {
+      let (bytes, _) = buf.get_delimited_bytes(b'\0')?;
+      ObjectValue::String(String::from_utf8_lossy(&bytes).into_owned())
+    }
+    other => {
+      return Err(BinDecodeError::failure(format!(
+        "field {}: unknown value type: {}",
+        String::from_utf8_lossy(&field_id),
+        other
+      )))
+    }
+  };
+  // end of field marker, the object id or zeros
+  buf.check_size(4)?;
+  buf.advance(4);
+  Ok(ObjectField {
+    field_id,
+    level,
+    data_pointer,
+    value,
+  })
+}
+
+#[test]
+fn test_decode_objects() {
+  fn put_field(
+    buf: &mut BytesMut,
+    field_id: &[u8],
+    level: Option<(u32, u32)>,
+    value: &ObjectValue,
+  ) {
+    buf.put_slice(field_id);
+    buf.put_u32_le(match value {
+      ObjectValue::Int(_) => 0,
+      ObjectValue::Real(_) => 1,
+      ObjectValue::Unreal(_) => 2,
+      ObjectValue::String(_) => 3,
+    });
+    if let Some((level, data_pointer)) = level {
+      buf.put_u32_le(level);
+      buf.put_u32_le(data_pointer);
+    }
+    match value {
+      ObjectValue::Int(v) => buf.put_i32_le(*v),
+      ObjectValue::Real(v) | ObjectValue::Unreal(v) => buf.put_f32_le(*v),
+      ObjectValue::String(v) => {
+        buf.put_slice(v.as_bytes());
+        buf.put_u8(0);
+      }
+    }
+    buf.put_slice(&[0; 4]);
+  }
+
+  // units
+  let mut buf = BytesMut::new();
+  buf.put_u32_le(2);
+  buf.put_u32_le(1);
+  buf.put_slice(b"hfoo");
+  buf.put_slice(&[0; 4]);
+  buf.put_u32_le(1);
+  put_field(
+    &mut buf,
+    b"unam",
+    None,
+    &ObjectValue::String("TRIGSTR_001".to_string()),
+  );
+  buf.put_u32_le(1);
+  buf.put_slice(b"hfoo");
+  buf.put_slice(b"h000");
+  buf.put_u32_le(2);
+  put_field(&mut buf, b"uhpm", None, &ObjectValue::Int(1000));
+  put_field(&mut buf, b"usca", None, &ObjectValue::Real(1.5));
+
+  let units = ObjectTable::decode(&mut buf.freeze(), ObjectKind::Unit).unwrap();
+  assert_eq!(units.original.len(), 1);
+  assert_eq!(units.original[0].custom_id, None);
+  assert_eq!(units.original[0].id_str(), "hfoo");
+  let custom = units.get(*b"h000").unwrap();
+  assert_eq!(custom.base_id, *b"hfoo");
+  assert_eq!(
+    custom.fields[0],
+    ObjectField {
+      field_id: *b"uhpm",
+      level: 0,
+      data_pointer: 0,
+      value: ObjectValue::Int(1000),
+    }
+  );
+  assert_eq!(units.derived_from(*b"hfoo").count(), 2);
+
+  // abilities, 1.33 format with field sets
+  let mut buf = BytesMut::new();
+  buf.put_u32_le(3);
+  buf.put_u32_le(0);
+  buf.put_u32_le(1);
+  buf.put_slice(b"AHbz");
+  buf.put_slice(b"A000");
+  buf.put_u32_le(1);
+  buf.put_u32_le(0);
+  buf.put_u32_le(1);
+  put_field(&mut buf, b"Hbz1", Some((2, 1)), &ObjectValue::Unreal(0.5));
+
+  let abilities = ObjectTable::decode(&mut buf.clone().freeze(), ObjectKind::Ability).unwrap();
+  let objects = CustomObjects {
+    abilities: Some(abilities),
+    ..Default::default()
+  };
+  assert!(!objects.is_empty());
+  let derived = objects.find_derived(ObjectKind::Ability, &[*b"AHbz", *b"AHtb"]);
+  assert_eq!(derived.len(), 1);
+  assert_eq!(derived[0].custom_id, Some(*b"A000"));
+  assert_eq!(
+    (
+      derived[0].fields[0].level,
+      derived[0].fields[0].data_pointer
+    ),
+    (2, 1)
+  );
+  assert!(objects
+    .find_derived(ObjectKind::Unit, &[*b"AHbz"])
+    .is_empty());
+
+  // truncated
+  let mut bytes = buf.freeze();
+  bytes.truncate(bytes.len() - 1);
+  assert!(ObjectTable::decode(&mut bytes, ObjectKind::Ability).is_err());
+}
@@ -0,0 +1,273 @@
+// The war3map.wpm file : Pathing map, positioned with the war3map.w3e header
+
+use crate::terrain::TerrainHeader;
+use flo_util::binary::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// World units per pathing cell
+pub const PATHING_CELL_SIZE: f32 = 32.;
+
+const WPM_MAGIC: &[u8] = b"MP3W";
+
+const FLAG_UNWALKABLE: u8 = 0x02;
+
+const COST_STRAIGHT: u32 = 100;
+const COST_DIAGONAL: u32 = 141;
+
+/// Walkability of the map, rows start from the bottom
+pub struct PathingGrid {
+  width: usize,
+  height: usize,
+  offset_x: f32,
+  offset_y: f32,
+  cells: Vec<u8>,
+}
+
+impl PathingGrid {
+  pub fn decode<T: Buf>(buf: &mut T, terrain: &TerrainHeader) -> Result<Self, BinDecodeError> {
+    buf.check_size(16)?;
+    buf.get_tag(WPM_MAGIC)?;
+    let _version = buf.get_u32_le();
+    let width = buf.get_u32_le() as usize;
+    let height = buf.get_u32_le() as usize;
+    buf.check_size(width * height)?;
+    let mut cells = vec![0; width * height];
+    buf.copy_to_slice(&mut cells);
+    Ok(Self {
+      width,
+      height,
+      offset_x: terrain.offset_x,
+      offset_y: terrain.offset_y,
+      cells,
+    })
+  }
+
+  pub fn dimension(&self) -> (usize, usize) {
+    (self.width, self.height)
+  }
+
+  pub fn is_walkable(&self, cell: (usize, usize)) -> bool {
+    if cell.0 >= self.width {
+      return false;
+    }
+    self
+      .cells
+      .get(cell.1 * self.width + cell.0)
+      .map(|flags| flags & FLAG_UNWALKABLE == 0)
+      .unwrap_or(false)
+  }
+
+  /// Number of walkable cells, one cell is `PATHING_CELL_SIZE` world units wide
+  pub fn walkable_cells(&self) -> usize {
+    self
+      .cells
+      .iter()
+      .filter(|flags| *flags & FLAG_UNWALKABLE == 0)
+      .count()
+  }
+
+  pub fn cell_at(&self, x: f32, y: f32) -> Option<(usize, usize)> {
+    let cx = ((x - self.offset_x) / PATHING_CELL_SIZE).floor();
+    let cy = ((y - self.offset_y) / PATHING_CELL_SIZE).floor();
+    if cx < 0. || cy < 0. || cx >= self.width as f32 || cy >= self.height as f32 {
+      return None;
+    }
+    Some((cx as usize, cy as usize))
+  }
+
+  /// The closest walkable cell within `radius` cells,
+  /// buildings and start locations can sit on unwalkable cells.
+  pub fn nearest_walkable(&self, cell: (usize, usize), radius: usize) -> Option<(usize, usize)> {
+    if self.is_walkable(cell) {
+      return Some(cell);
+    }
+    if self.width == 0 || self.height == 0 {
+      return None;
+    }
+    let mut best: Option<((usize, usize), usize)> = None;
+    for y in cell.1.saturating_sub(radius)..=std::cmp::min(cell.1 + radius, self.height - 1) {
+      for x in cell.0.saturating_sub(radius)..=std::cmp::min(cell.0 + radius, self.width - 1) {
+        if !self.is_walkable((x, y)) {
+          continue;
+        }
+        let d = (x as isize - cell.0 as isize).pow(2) as usize
+          + (y as isize - cell.1 as isize).pow(2) as usize;
+        if best.map(|(_, bd)| d < bd).unwrap_or(true) {
+          best = Some(((x, y), d));
+        }
+      }
+    }
+    best.map(|(cell, _)| cell)
+  }
+
+  /// Walking distances in world units from `from` to every cell, `None` if unreachable.
+  /// Diagonal moves can't cut unwalkable corners.
+  pub fn distance_field(&self, from: (usize, usize)) -> DistanceField {
+    let mut costs = vec![u32::MAX; self.cells.len()];
+    let mut heap = BinaryHeap::new();
+    if self.is_walkable(from) {
+      let idx = from.1 * self.width + from.0;
+      costs[idx] = 0;
+      heap.push(Reverse((0, idx)));
+    }
+
+    while let Some(Reverse((cost, idx))) = heap.pop() {
+      if cost > costs[idx] {
+        continue;
+      }
+      let (x, y) = ((idx % self.width) as isize, (idx / self.width) as isize);
+      for (dx, dy) in &[
+        (-1, 0),
+        (1, 0),
+        (0, -1),
+        (0, 1),
+        (-1, -1),
+        (1, -1),
+        (-1, 1),
+        (1, 1),
+      ] {
+        let (nx, ny) = (x + dx, y + dy);
+        if !self.in_bounds(nx, ny) || !self.is_walkable((nx as usize, ny as usize)) {
+          continue;
+        }
+        let step = if *dx != 0 && *dy != 0 {
+          if !self.is_walkable((nx as usize, y as usize))
+            || !self.is_walkable((x as usize, ny as usize))
+          {
+            continue;
+          }
+          COST_DIAGONAL
+        } else {
+          COST_STRAIGHT
+        };
+        let nidx = ny as usize * self.width + nx as usize;
+        let next = cost + step;
+        if next < costs[nidx] {
+          costs[nidx] = next;
+          heap.push(Reverse((next, nidx)));
+        }
+      }
+    }
+
+    DistanceField {
+      width: self.width,
+      costs,
+    }
+  }
+
+  /// Ratio of cells with the same walkability as their image under `transform`
+  /// (in world coordinates), cells mapped outside of the grid are not counted.
+  pub fn match_ratio<F>(&self, transform: F) -> f32
+  where
+    F: Fn(f32, f32) -> (f32, f32),
+  {
+    let mut total = 0_usize;
+    let mut matched = 0_usize;
+    for y in 0..self.height {
+      for x in 0..self.width {
+        let (wx, wy) = transform(
+          (x as f32 + 0.5) * PATHING_CELL_SIZE + self.offset_x,
+          (y as f32 + 0.5) * PATHING_CELL_SIZE + self.offset_y,
+        );
+        let cell = match self.cell_at(wx, wy) {
+          Some(cell) => cell,
+          None => continue,
+        };
+        total += 1;
+        if self.is_walkable((x, y)) == self.is_walkable(cell) {
+          matched += 1;
+        }
+      }
+    }
+    if total == 0 {
+      0.
+    } else {
+      matched as f32 / total as f32
+    }
+  }
+
+  fn in_bounds(&self, x: isize, y: isize) -> bool {
+    x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+  }
+}
+
+impl std::fmt::Debug for PathingGrid {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("PathingGrid")
+      .field("width", &self.width)
+      .field("height", &self.height)
+      .field("offset_x", &self.offset_x)
+      .field("offset_y", &self.offset_y)
+      .finish()
+  }
+}
+
+pub struct DistanceField {
+  width: usize,
+  costs: Vec<u32>,
+}
+
+impl DistanceField {
+  pub fn get(&self, cell: (usize, usize)) -> Option<f32> {
+    match self.costs.get(cell.1 * self.width + cell.0) {
+      Some(&cost) if cost != u32::MAX => {
+        Some(cost as f32 / COST_STRAIGHT as f32 * PATHING_CELL_SIZE)
+      }
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+pub(crate) fn grid_from_rows(rows: &[&str]) -> PathingGrid {
+  // rows are given top to bottom, `#` is unwalkable
+  let height = rows.len();
+  let width = rows.first().map(|row| row.len()).unwrap_or(0);
+  let mut cells = vec![0; width * height];
+  for (i, row) in rows.iter().enumerate() {
+    let y = height - 1 - i;
+    for (x, c) in row.bytes().enumerate() {
+      if c == b'#' {
+        cells[y * width + x] = FLAG_UNWALKABLE;
+      }
+    }
+  }
+  PathingGrid {
+    width,
+    height,
+    offset_x: 0.,
+    offset_y: 0.,
+    cells,
+  }
+}
+
+#[test]
+fn test_distance_field() {
+  let grid = grid_from_rows(&[
+    ".....", //
+    ".###.", //
+    ".#...", //
+    ".#.#.", //
+    "...#.", //
+  ]);
+  let field = grid.distance_field((0, 0));
+  assert_eq!(field.get((0, 0)), Some(0.));
+  assert_eq!(field.get((2, 0)), Some(64.));
+  // around the wall, no corner cutting
+  assert_eq!(field.get((2, 2)), Some(128.));
+  assert_eq!(field.get((1, 1)), None);
+  assert!(field.get((4, 4)).unwrap() > 4. * 32.);
+  assert_eq!(grid.cell_at(33., 70.), Some((1, 2)));
+  assert_eq!(grid.cell_at(-1., 0.), None);
+  assert_eq!(grid.nearest_walkable((1, 1), 1), Some((1, 0)));
+  assert_eq!(grid.walkable_cells(), 18);
+}
+
+#[test]
+fn test_empty_grid() {
+  let grid = grid_from_rows(&[]);
+  assert_eq!(grid.dimension(), (0, 0));
+  assert_eq!(grid.cell_at(0., 0.), None);
+  assert_eq!(grid.nearest_walkable((0, 0), 4), None);
+}
@@ -0,0 +1,234 @@
+// Minimap preview rendering at arbitrary sizes
+
+use crate::error::Result;
+use crate::terrain::TILE_SIZE;
+use crate::W3Map;
+use image::imageops::{resize, FilterType};
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PreviewFormat {
+  Png,
+  Jpeg,
+}
+
+impl PreviewFormat {
+  fn image_format(self) -> ImageFormat {
+    match self {
+      PreviewFormat::Png => ImageFormat::Png,
+      PreviewFormat::Jpeg => ImageFormat::Jpeg,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreviewOptions {
+  /// Draws a marker in the player color at each start location,
+  /// on top of the crosses the map's minimap icons may already have
+  pub start_locations: bool,
+}
+
+// in-game player colors, by player id
+const PLAYER_COLORS: [[u8; 3]; 24] = [
+  [0xff, 0x03, 0x03],
+  [0x00, 0x42, 0xff],
+  [0x1c, 0xe6, 0xb9],
+  [0x54, 0x00, 0x81],
+  [0xff, 0xfc, 0x00],
+  [0xfe, 0x8a, 0x0e],
+  [0x20, 0xc0, 0x00],
+  [0xe5, 0x5b, 0xb0],
+  [0x95, 0x96, 0x97],
+  [0x7e, 0xbf, 0xf1],
+  [0x10, 0x62, 0x46],
+  [0x4a, 0x2a, 0x04],
+  [0x9b, 0x00, 0x00],
+  [0x00, 0x00, 0xc3],
+  [0x00, 0xea, 0xff],
+  [0xbe, 0x00, 0xfe],
+  [0xeb, 0xcd, 0x87],
+  [0xf8, 0xa4, 0x8b],
+  [0xbf, 0xff, 0x80],
+  [0xdc, 0xb9, 0xeb],
+  [0x28, 0x28, 0x28],
+  [0xeb, 0xf0, 0xff],
+  [0x00, 0x78, 0x1e],
+  [0xa4, 0x6f, 0x33],
+];
+
+impl W3Map {
+  /// Renders the preview resized to `width` x `height`,
+  /// returns an empty buffer if the map has no preview image
+  pub fn render_preview_scaled(&self, width: u32, height: u32, format: PreviewFormat) -> Vec<u8> {
+    self.render_preview_scaled_with_options(width, height, format, PreviewOptions::default())
+  }
+
+  pub fn render_preview_scaled_with_options(
+    &self,
+    width: u32,
+    height: u32,
+    format: PreviewFormat,
+    options: PreviewOptions,
+  ) -> Vec<u8> {
+    let mut bytes = vec![];
+    self
+      .write_preview_scaled(&mut bytes, width, height, format, options)
+      .ok();
+    bytes
+  }
+
+  /// Encodes the resized preview into `w`, nothing is written if the map has no preview image
+  pub fn write_preview_scaled<W: Write>(
+    &self,
+    w: &mut W,
+    width: u32,
+    height: u32,
+    format: PreviewFormat,
+    options: PreviewOptions,
+  ) -> Result<()> {
+    let image = match self.render_preview_image(width, height, options) {
+      Some(image) => image,
+      None => return Ok(()),
+    };
+    let image = match format {
+      // no alpha channel in JPEG
+      PreviewFormat::Jpeg => DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8()),
+      PreviewFormat::Png => DynamicImage::ImageRgba8(image),
+    };
+    image.write_to(w, format.image_format())?;
+    Ok(())
+  }
+
+  fn render_preview_image(
+    &self,
+    width: u32,
+    height: u32,
+    options: PreviewOptions,
+  ) -> Option<RgbaImage> {
+    let mut bg = self.image.as_ref()?.buffer().clone();
+    for icon in self.minimap_icons.iter() {
+      icon.draw_into(&mut bg);
+    }
+    let (source_width, source_height) = bg.dimensions();
+    if source_width == 0 || source_height == 0 || width == 0 || height == 0 {
+      return None;
+    }
+
+    let mut image = if (width, height) == (source_width, source_height) {
+      bg
+    } else {
+      resize(&bg, width, height, FilterType::Lanczos3)
+    };
+
+    if options.start_locations {
+      let playable = self.playable_bounds();
+      let radius = std::cmp::max(3, std::cmp::min(width, height) / 48) as i64;
+      for location in self.start_locations() {
+        let (x, y) = project(
+          playable,
+          (source_width, source_height),
+          (location.x, location.y),
+        );
+        let x = x * width as f32 / source_width as f32;
+        let y = y * height as f32 / source_height as f32;
+        let [r, g, b] = PLAYER_COLORS[location.player_id as usize % PLAYER_COLORS.len()];
+        draw_marker(
+          &mut image,
+          (x as i64, y as i64),
+          radius,
+          Rgba([r, g, b, 0xff]),
+        );
+      }
+    }
+
+    Some(image)
+  }
+
+  // left, bottom, width, height of the playable area in world coordinates
+  fn playable_bounds(&self) -> [f32; 4] {
+    let width = self.info.width as f32 * TILE_SIZE;
+    let height = self.info.height as f32 * TILE_SIZE;
+    match self.terrain {
+      Some(ref terrain) => {
+        let header = terrain.header();
+        let complements = &self.info.camera_bounds.complements;
+        [
+          header.offset_x + complements[0] as f32 * TILE_SIZE,
+          header.offset_y + complements[2] as f32 * TILE_SIZE,
+          width,
+          height,
+        ]
+      }
+      None => {
+        let b = &self.info.camera_bounds.bounds;
+        let center_x = (b[0] + b[2] + b[4] + b[6]) / 4.;
+        let center_y = (b[1] + b[3] + b[5] + b[7]) / 4.;
+        [center_x - width / 2., center_y - height / 2., width, height]
+      }
+    }
+  }
+}
+
+/// Maps world coordinates to pixels of a minimap of `size`.
+/// The minimap shows the playable area scaled to fit and centered, the y axis points down.
+fn project(playable: [f32; 4], size: (u32, u32), pos: (f32, f32)) -> (f32, f32) {
+  let [left, bottom, width, height] = playable;
+  if width <= 0. || height <= 0. {
+    return (size.0 as f32 / 2., size.1 as f32 / 2.);
+  }
+  let scale = f32::min(size.0 as f32 / width, size.1 as f32 / height);
+  let offset_x = (size.0 as f32 - width * scale) / 2.;
+  let offset_y = (size.1 as f32 - height * scale) / 2.;
+  (
+    offset_x + (pos.0 - left) * scale,
+    offset_y + (height - (pos.1 - bottom)) * scale,
+  )
+}
+
+// filled circle with a black outline
+fn draw_marker(image: &mut RgbaImage, center: (i64, i64), radius: i64, color: Rgba<u8>) {
+  let outline = radius + 1;
+  for dy in -outline..=outline {
+    for dx in -outline..=outline {
+      let (x, y) = (center.0 + dx, center.1 + dy);
+      if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+        continue;
+      }
+      let d2 = dx * dx + dy * dy;
+      if d2 <= radius * radius {
+        image.put_pixel(x as u32, y as u32, color);
+      } else if d2 <= outline * outline {
+        image.put_pixel(x as u32, y as u32, Rgba([0, 0, 0, 0xff]));
+      }
+    }
+  }
+}
+
+#[test]
+fn test_project() {
+  // 64x32 cells, fits the width of a square minimap
+  let playable = [-4096., -2048., 8192., 4096.];
+  assert_eq!(project(playable, (256, 256), (-4096., 2048.)), (0., 64.));
+  assert_eq!(project(playable, (256, 256), (4096., -2048.)), (256., 192.));
+  assert_eq!(project(playable, (256, 256), (0., 0.)), (128., 128.));
+  assert_eq!(project([0., 0., 0., 0.], (256, 128), (1., 1.)), (128., 64.));
+}
+
+#[test]
+fn test_render_preview_scaled() {
+  let map = W3Map::open(flo_util::sample_path!("map", "(2)ConcealedHill.w3x")).unwrap();
+  let png = map.render_preview_scaled_with_options(
+    100,
+    80,
+    PreviewFormat::Png,
+    PreviewOptions {
+      start_locations: true,
+    },
+  );
+  let image = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+  assert_eq!(image.to_rgba8().dimensions(), (100, 80));
+  let jpeg = map.render_preview_scaled(64, 64, PreviewFormat::Jpeg);
+  let image = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap();
+  assert_eq!(image.to_rgb8().dimensions(), (64, 64));
+}
@@ -0,0 +1,218 @@
+// The map script: war3map.j (JASS) or war3map.lua
+
+use crate::error::Result;
+use crate::{Archive, SCRIPT_FILES};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptLanguage {
+  Jass,
+  Lua,
+}
+
+impl ScriptLanguage {
+  pub fn from_path(path: &str) -> Self {
+    if path.to_ascii_lowercase().ends_with(".lua") {
+      ScriptLanguage::Lua
+    } else {
+      ScriptLanguage::Jass
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapScript {
+  pub path: &'static str,
+  pub language: ScriptLanguage,
+  pub source: String,
+  /// SHA-1 of the script with comments and insignificant whitespace removed,
+  /// reformatting or recommenting the script doesn't change it
+  pub fingerprint: [u8; 20],
+}
+
+impl MapScript {
+  pub fn new(path: &'static str, bytes: &[u8]) -> Self {
+    let language = ScriptLanguage::from_path(path);
+    let source = String::from_utf8_lossy(bytes);
+    let source = source.trim_start_matches('\u{feff}').to_string();
+    let mut sha1 = sha1::Sha1::new();
+    sha1.update(normalize_script(&source, language).as_bytes());
+    Self {
+      path,
+      language,
+      source,
+      fingerprint: sha1.digest().bytes(),
+    }
+  }
+
+  pub(crate) fn read(archive: &mut Archive) -> Result<Option<Self>> {
+    for path in SCRIPT_FILES {
+      if let Some(bytes) = archive.read_file_all_opt(path)? {
+        return Ok(Some(Self::new(*path, &bytes)));
+      }
+    }
+    Ok(None)
+  }
+
+  pub fn get_fingerprint_hex_string(&self) -> String {
+    self
+      .fingerprint
+      .iter()
+      .map(|b| format!("{:02x}", b))
+      .collect()
+  }
+}
+
+/// Removes comments and collapses whitespace outside of string literals.
+/// A space is kept only between two identifier characters, e.g. `local integer i`
+pub fn normalize_script(source: &str, language: ScriptLanguage) -> String {
+  let chars: Vec<char> = source.chars().collect();
+  let mut out = String::with_capacity(source.len());
+  let mut pending_space = false;
+  let mut i = 0;
+  while i < chars.len() {
+    if let Some(end) = comment_end(&chars, i, language) {
+      pending_space = true;
+      i = end;
+      continue;
+    }
+
+    let c = chars[i];
+    if c.is_whitespace() {
+      pending_space = true;
+      i += 1;
+      continue;
+    }
+
+    if pending_space {
+      if out.chars().next_back().map(is_word).unwrap_or(false) && is_word(c) {
+        out.push(' ');
+      }
+      pending_space = false;
+    }
+
+    let end = literal_end(&chars, i, language).unwrap_or(i + 1);
+    out.extend(&chars[i..end]);
+    i = end;
+  }
+  out
+}
+
+fn is_word(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+fn starts_with(chars: &[char], i: usize, s: &str) -> bool {
+  s.chars()
+    .enumerate()
+    .all(|(n, c)| chars.get(i + n) == Some(&c))
+}
+
+fn line_end(chars: &[char], i: usize) -> usize {
+  chars[i..]
+    .iter()
+    .position(|c| *c == '\n')
+    .map(|pos| i + pos)
+    .unwrap_or(chars.len())
+}
+
+// end of a comment starting at `i`
+fn comment_end(chars: &[char], i: usize, language: ScriptLanguage) -> Option<usize> {
+  match language {
+    ScriptLanguage::Jass if starts_with(chars, i, "//") => Some(line_end(chars, i)),
+    ScriptLanguage::Lua if starts_with(chars, i, "--") => {
+      Some(match long_bracket_level(chars, i + 2) {
+        Some(level) => long_bracket_end(chars, i + 2, level),
+        None => line_end(chars, i),
+      })
+    }
+    _ => None,
+  }
+}
+
+// end of a string literal or rawcode starting at `i`
+fn literal_end(chars: &[char], i: usize, language: ScriptLanguage) -> Option<usize> {
+  match chars[i] {
+    quote @ '"' | quote @ '\'' => {
+      let mut j = i + 1;
+      while j < chars.len() {
+        match chars[j] {
+          '\\' => j += 2,
+          c if c == quote => return Some(j + 1),
+          _ => j += 1,
+        }
+      }
+      Some(chars.len())
+    }
+    '[' if language == ScriptLanguage::Lua => {
+      long_bracket_level(chars, i).map(|level| long_bracket_end(chars, i, level))
+    }
+    _ => None,
+  }
+}
+
+// `[[`, `[=[`, `[==[`...
+fn long_bracket_level(chars: &[char], i: usize) -> Option<usize> {
+  if chars.get(i) != Some(&'[') {
+    return None;
+  }
+  let level = chars[(i + 1)..].iter().take_while(|c| **c == '=').count();
+  if chars.get(i + 1 + level) == Some(&'[') {
+    Some(level)
+  } else {
+    None
+  }
+}
+
+fn long_bracket_end(chars: &[char], i: usize, level: usize) -> usize {
+  let close = format!("]{}]", "=".repeat(level));
+  let mut j = i + level + 2;
+  while j < chars.len() {
+    if starts_with(chars, j, &close) {
+      return j + close.len();
+    }
+    j += 1;
+  }
+  chars.len()
+}
+
+#[test]
+fn test_normalize_script() {
+  let jass = r#"
+// generated
+function main takes nothing returns nothing
+    local integer i = 'hfoo' // footman
+    call BJDebugMsg( "a  //  b" )
+endfunction
+"#;
+  assert_eq!(
+    normalize_script(jass, ScriptLanguage::Jass),
+    r#"function main takes nothing returns nothing local integer i='hfoo'call BJDebugMsg("a  //  b")endfunction"#
+  );
+
+  let lua = r#"
+--[==[ header
+]] still a comment ]==]
+function main() -- entry
+  local s = [[ -- not
+a comment ]]
+  print( 'it\'s' , s )
+end
+"#;
+  assert_eq!(
+    normalize_script(lua, ScriptLanguage::Lua),
+    r#"function main()local s=[[ -- not
+a comment ]]print('it\'s',s)end"#
+  );
+
+  let a = MapScript::new("war3map.j", jass.as_bytes());
+  let reformatted = jass.replace("    ", "\t").replace("// footman", "");
+  let b = MapScript::new("war3map.j", reformatted.as_bytes());
+  assert_eq!(a.fingerprint, b.fingerprint);
+  let modified = jass.replace("'hfoo'", "'hkni'");
+  let c = MapScript::new("war3map.j", modified.as_bytes());
+  assert_ne!(a.fingerprint, c.fingerprint);
+  assert_eq!(
+    MapScript::new("war3map.lua", lua.as_bytes()).language,
+    ScriptLanguage::Lua
+  );
+}
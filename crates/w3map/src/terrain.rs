@@ -0,0 +1,345 @@
+// The war3map.w3e file : Environment, tilesets and the tilepoints (tile corners) of the map
+
+use bitflags::bitflags;
+use flo_util::binary::*;
+
+/// World units between two tilepoints
+pub const TILE_SIZE: f32 = 128.;
+
+const MAGIC: &[u8] = b"W3E!";
+
+/// Raw ground height and water level of world height 0
+const ZERO_LEVEL: f32 = 8192.;
+/// Raw height of a cliff level
+const LAYER_HEIGHT: f32 = 512.;
+/// World height of the water surface relative to its raw level
+const WATER_OFFSET: f32 = -89.6;
+
+const WATER_LEVEL_MASK: u16 = 0x3FFF;
+const WATER_MAP_EDGE: u16 = 0xC000;
+
+bitflags! {
+  pub struct TilePointFlags: u8 {
+    const RAMP = 0x01;
+    const BLIGHT = 0x02;
+    const WATER = 0x04;
+    /// Camera and pathing boundary
+    const BOUNDARY = 0x08;
+    /// Outside of the map bounds
+    const MAP_EDGE = 0x10;
+  }
+}
+
+/// The part of the war3map.w3e header that positions the map in world coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainHeader {
+  /// Number of tile corners
+  pub width: u32,
+  pub height: u32,
+  /// World coordinates of the bottom left corner
+  pub offset_x: f32,
+  pub offset_y: f32,
+}
+
+impl TerrainHeader {
+  pub fn center(&self) -> (f32, f32) {
+    (
+      self.offset_x + (self.width.saturating_sub(1) as f32) * TILE_SIZE / 2.,
+      self.offset_y + (self.height.saturating_sub(1) as f32) * TILE_SIZE / 2.,
+    )
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TilePoint {
+  /// Raw values, use `height()` and `water_height()` for world heights
+  pub ground_height: u16,
+  pub water_level: u16,
+  pub flags: TilePointFlags,
+  /// Index into the ground tilesets
+  pub ground_texture: u8,
+  pub variation: u8,
+  /// Index into the cliff tilesets
+  pub cliff_texture: u8,
+  /// Cliff level
+  pub layer: u8,
+}
+
+impl TilePoint {
+  /// World height of the ground, including the cliff level
+  pub fn height(&self) -> f32 {
+    (self.ground_height as f32 - ZERO_LEVEL + (self.layer as f32 - 2.) * LAYER_HEIGHT) / 4.
+  }
+
+  pub fn water_height(&self) -> f32 {
+    (self.water_level as f32 - ZERO_LEVEL) / 4. + WATER_OFFSET
+  }
+
+  /// Depth of the water above the ground, `None` if the tilepoint has no water
+  pub fn water_depth(&self) -> Option<f32> {
+    if self.flags.contains(TilePointFlags::WATER) {
+      Some((self.water_height() - self.height()).max(0.))
+    } else {
+      None
+    }
+  }
+
+  pub fn is_boundary(&self) -> bool {
+    self
+      .flags
+      .intersects(TilePointFlags::BOUNDARY | TilePointFlags::MAP_EDGE)
+  }
+}
+
+#[derive(Clone)]
+pub struct Terrain {
+  pub version: u32,
+  /// Main tileset, e.g. `L` for Lordaeron Summer
+  pub tileset: u8,
+  pub custom_tilesets: bool,
+  pub ground_tilesets: Vec<[u8; 4]>,
+  pub cliff_tilesets: Vec<[u8; 4]>,
+  header: TerrainHeader,
+  /// Rows start from the bottom
+  points: Vec<TilePoint>,
+}
+
+impl Terrain {
+  pub fn header(&self) -> &TerrainHeader {
+    &self.header
+  }
+
+  /// Number of tilepoints
+  pub fn dimension(&self) -> (usize, usize) {
+    (self.header.width as usize, self.header.height as usize)
+  }
+
+  /// Rows start from the bottom
+  pub fn points(&self) -> &[TilePoint] {
+    &self.points
+  }
+
+  pub fn point(&self, x: usize, y: usize) -> Option<&TilePoint> {
+    if x >= self.header.width as usize {
+      return None;
+    }
+    self.points.get(y * self.header.width as usize + x)
+  }
+
+  /// World height of the ground of every tilepoint, rows start from the bottom
+  pub fn heightmap(&self) -> Vec<f32> {
+    self.points.iter().map(TilePoint::height).collect()
+  }
+
+  /// Counts of tilepoints inside the boundary
+  pub fn stats(&self) -> TerrainStats {
+    let mut stats = TerrainStats::default();
+    for point in &self.points {
+      if point.is_boundary() {
+        stats.boundary += 1;
+        continue;
+      }
+      stats.playable += 1;
+      if point.flags.contains(TilePointFlags::WATER) {
+        stats.water += 1;
+      }
+      if point.flags.contains(TilePointFlags::RAMP) {
+        stats.ramps += 1;
+      }
+      if point.flags.contains(TilePointFlags::BLIGHT) {
+        stats.blight += 1;
+      }
+      let layer = point.layer as usize;
+      if stats.layers.len() <= layer {
+        stats.layers.resize(layer + 1, 0);
+      }
+      stats.layers[layer] += 1;
+      let height = point.height();
+      stats.min_height = Some(stats.min_height.map_or(height, |v| v.min(height)));
+      stats.max_height = Some(stats.max_height.map_or(height, |v| v.max(height)));
+    }
+    stats
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TerrainStats {
+  pub playable: usize,
+  pub boundary: usize,
+  pub water: usize,
+  pub ramps: usize,
+  pub blight: usize,
+  /// Number of playable tilepoints per cliff level
+  pub layers: Vec<usize>,
+  /// World heights of the playable ground, `None` if there are no playable tilepoints
+  pub min_height: Option<f32>,
+  pub max_height: Option<f32>,
+}
+
+impl BinDecode for Terrain {
+  const MIN_SIZE: usize = 4 + 4 + 1 + 4 + 4;
+  const FIXED_SIZE: bool = false;
+  fn decode<T: Buf>(buf: &mut T) -> Result<Self, BinDecodeError> {
+    buf.check_size(Self::MIN_SIZE)?;
+    buf.get_tag(MAGIC)?;
+    let version = buf.get_u32_le();
+    if version != 11 && version != 12 {
+      return Err(BinDecodeError::failure(format!(
+        "unsupported w3e version: {}",
+        version
+      )));
+    }
+    let tileset = buf.get_u8();
+    let custom_tilesets = buf.get_u32_le() != 0;
+    let ground_tilesets = decode_ids(buf).map_err(|err| err.context("ground tilesets"))?;
+    let cliff_tilesets = decode_ids(buf).map_err(|err| err.context("cliff tilesets"))?;
+    buf.check_size(16)?;
+    let header = TerrainHeader {
+      width: buf.get_u32_le(),
+      height: buf.get_u32_le(),
+      offset_x: buf.get_f32_le(),
+      offset_y: buf.get_f32_le(),
+    };
+
+    // 1.33+ widened the ground texture index
+    let point_size = if version >= 12 { 8 } else { 7 };
+    let count = (header.width as usize).saturating_mul(header.height as usize);
+    buf.check_size(count.saturating_mul(point_size))?;
+    let mut points = Vec::with_capacity(count);
+    for _ in 0..count {
+      let ground_height = buf.get_u16_le();
+      let water = buf.get_u16_le();
+      let (ground_texture, mut flags) = if version >= 12 {
+        let v = buf.get_u16_le();
+        (
+          (v & 0x3F) as u8,
+          TilePointFlags::from_bits_truncate(((v >> 6) & 0x0F) as u8),
+        )
+      } else {
+        let v = buf.get_u8();
+        (v & 0x0F, TilePointFlags::from_bits_truncate(v >> 4))
+      };
+      if water & WATER_MAP_EDGE != 0 {
+        flags |= TilePointFlags::MAP_EDGE;
+      }
+      let variation = buf.get_u8();
+      let cliff = buf.get_u8();
+      points.push(TilePoint {
+        ground_height,
+        water_level: water & WATER_LEVEL_MASK,
+        flags,
+        ground_texture,
+        variation,
+        cliff_texture: cliff >> 4,
+        layer: cliff & 0x0F,
+      })
+    }
+
+    Ok(Self {
+      version,
+      tileset,
+      custom_tilesets,
+      ground_tilesets,
+      cliff_tilesets,
+      header,
+      points,
+    })
+  }
+}
+
+fn decode_ids<T: Buf>(buf: &mut T) -> Result<Vec<[u8; 4]>, BinDecodeError> {
+  buf.check_size(4)?;
+  let n = buf.get_u32_le() as usize;
+  buf.check_size(n * 4)?;
+  Ok(
+    (0..n)
+      .map(|_| {
+        let mut id = [0; 4];
+        buf.copy_to_slice(&mut id);
+        id
+      })
+      .collect(),
+  )
+}
+
+impl std::fmt::Debug for Terrain {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Terrain")
+      .field("version", &self.version)
+      .field("tileset", &(self.tileset as char))
+      .field("custom_tilesets", &self.custom_tilesets)
+      .field("ground_tilesets", &self.ground_tilesets.len())
+      .field("cliff_tilesets", &self.cliff_tilesets.len())
+      .field("header", &self.header)
+      .finish()
+  }
+}
+
+#[test]
+fn test_decode_terrain() {
+  let mut buf = BytesMut::new();
+  buf.put_slice(MAGIC);
+  buf.put_u32_le(11);
+  buf.put_u8(b'L');
+  buf.put_u32_le(0);
+  buf.put_u32_le(2);
+  buf.put_slice(b"Ldrt");
+  buf.put_slice(b"Lgrs");
+  buf.put_u32_le(1);
+  buf.put_slice(b"CLdi");
+  buf.put_u32_le(2);
+  buf.put_u32_le(2);
+  buf.put_f32_le(-128.);
+  buf.put_f32_le(-256.);
+  // boundary
+  buf.put_slice(&[0x00, 0x20, 0x00, 0x60, 0x80, 0x00, 0x02]);
+  // level 2 ground
+  buf.put_slice(&[0x00, 0x20, 0x00, 0x20, 0x01, 0x03, 0x02]);
+  // raised ramp on cliff level 3
+  buf.put_slice(&[0x80, 0x20, 0x00, 0x20, 0x10, 0x00, 0x13]);
+  // water below level 1
+  buf.put_slice(&[0x00, 0x1E, 0x00, 0x20, 0x40, 0x00, 0x01]);
+
+  let terrain = Terrain::decode(&mut buf.clone().freeze()).unwrap();
+  assert_eq!(terrain.tileset, b'L');
+  assert_eq!(terrain.ground_tilesets, vec![*b"Ldrt", *b"Lgrs"]);
+  assert_eq!(terrain.cliff_tilesets, vec![*b"CLdi"]);
+  assert_eq!(terrain.dimension(), (2, 2));
+  assert_eq!(terrain.header().center(), (-64., -192.));
+
+  let point = terrain.point(1, 0).unwrap();
+  assert_eq!(point.ground_texture, 1);
+  assert_eq!(point.variation, 3);
+  assert_eq!(point.height(), 0.);
+  assert_eq!(point.water_depth(), None);
+
+  let point = terrain.point(0, 1).unwrap();
+  assert_eq!(point.flags, TilePointFlags::RAMP);
+  assert_eq!(point.cliff_texture, 1);
+  assert_eq!(point.layer, 3);
+  assert_eq!(point.height(), 160.);
+
+  let point = terrain.point(1, 1).unwrap();
+  assert_eq!(point.height(), -256.);
+  assert_eq!(point.water_depth(), Some(256. - 89.6));
+  assert!(terrain.point(2, 0).is_none());
+
+  assert_eq!(
+    terrain.stats(),
+    TerrainStats {
+      playable: 3,
+      boundary: 1,
+      water: 1,
+      ramps: 1,
+      blight: 0,
+      layers: vec![0, 1, 1, 1],
+      min_height: Some(-256.),
+      max_height: Some(160.),
+    }
+  );
+
+  // truncated
+  let mut bytes = buf.freeze();
+  bytes.truncate(bytes.len() - 1);
+  assert!(Terrain::decode(&mut bytes).is_err());
+}
@@ -0,0 +1,158 @@
+// Formatted text of map strings: `|cAARRGGBB` starts a color, `|r` resets it, `|n` is a line break
+
+/// ARGB color of a `|cAARRGGBB` code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextColor {
+  pub a: u8,
+  pub r: u8,
+  pub g: u8,
+  pub b: u8,
+}
+
+impl TextColor {
+  fn parse(hex: &str) -> Option<Self> {
+    if hex.len() != 8 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+      return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let [a, r, g, b] = value.to_be_bytes();
+    Some(TextColor { a, r, g, b })
+  }
+
+  /// `#RRGGBB`
+  pub fn to_hex_rgb(&self) -> String {
+    format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSegment {
+  pub text: String,
+  pub color: Option<TextColor>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormattedText {
+  raw: String,
+  segments: Vec<TextSegment>,
+}
+
+impl FormattedText {
+  pub fn parse(raw: &str) -> Self {
+    let mut segments: Vec<TextSegment> = vec![];
+    let mut color = None;
+    let mut text = String::new();
+    let mut rest = raw;
+
+    while let Some(pos) = rest.find('|') {
+      text.push_str(&rest[..pos]);
+      rest = &rest[pos..];
+
+      let code = rest[1..].chars().next().map(|c| c.to_ascii_lowercase());
+      match code {
+        Some('c') => {
+          if let Some(next) = rest.get(2..10).and_then(TextColor::parse) {
+            push_segment(&mut segments, &mut text, color);
+            color = Some(next);
+            rest = &rest[10..];
+            continue;
+          }
+        }
+        Some('r') => {
+          push_segment(&mut segments, &mut text, color);
+          color = None;
+          rest = &rest[2..];
+          continue;
+        }
+        Some('n') => {
+          text.push('\n');
+          rest = &rest[2..];
+          continue;
+        }
+        Some('|') => {
+          text.push('|');
+          rest = &rest[2..];
+          continue;
+        }
+        _ => {}
+      }
+
+      // not a code
+      text.push('|');
+      rest = &rest[1..];
+    }
+    text.push_str(rest);
+    push_segment(&mut segments, &mut text, color);
+
+    FormattedText {
+      raw: raw.to_string(),
+      segments,
+    }
+  }
+
+  pub fn raw(&self) -> &str {
+    self.raw.as_ref()
+  }
+
+  pub fn segments(&self) -> &[TextSegment] {
+    self.segments.as_ref()
+  }
+
+  /// Text without the formatting codes, line breaks are replaced by spaces
+  pub fn plain_text(&self) -> String {
+    let text: String = self.segments.iter().map(|s| s.text.as_str()).collect();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+  }
+}
+
+// adjacent segments with the same color are merged
+fn push_segment(segments: &mut Vec<TextSegment>, text: &mut String, color: Option<TextColor>) {
+  if text.is_empty() {
+    return;
+  }
+  let text = std::mem::take(text);
+  match segments.last_mut() {
+    Some(last) if last.color == color => last.text.push_str(&text),
+    _ => segments.push(TextSegment { text, color }),
+  }
+}
+
+#[test]
+fn test_formatted_text() {
+  let text = FormattedText::parse("|cffff0000Red|r Plain |CFF00FF00Green|n2||3");
+  assert_eq!(text.raw(), "|cffff0000Red|r Plain |CFF00FF00Green|n2||3");
+  assert_eq!(
+    text.segments(),
+    &[
+      TextSegment {
+        text: "Red".to_string(),
+        color: Some(TextColor {
+          a: 0xff,
+          r: 0xff,
+          g: 0,
+          b: 0
+        }),
+      },
+      TextSegment {
+        text: " Plain ".to_string(),
+        color: None,
+      },
+      TextSegment {
+        text: "Green\n2|3".to_string(),
+        color: Some(TextColor {
+          a: 0xff,
+          r: 0,
+          g: 0xff,
+          b: 0
+        }),
+      },
+    ]
+  );
+  assert_eq!(text.segments()[0].color.unwrap().to_hex_rgb(), "#ff0000");
+  assert_eq!(text.plain_text(), "Red Plain Green 2|3");
+
+  // invalid codes are kept as text
+  let text = FormattedText::parse("a|cffzz|b|");
+  assert_eq!(text.plain_text(), "a|cffzz|b|");
+  assert_eq!(FormattedText::parse("").segments(), &[]);
+}
@@ -1,11 +1,17 @@
 // The war3map.wts file : The Trigger String Data File
 
+use crate::Archive;
 use flo_util::binary::*;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 
 const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
 
+/// Locales Reforged maps can ship string files for, under `_Locales\<locale>.w3mod\`
+pub const MAP_LOCALES: &[&str] = &[
+  "deDE", "enUS", "esES", "esMX", "frFR", "itIT", "koKR", "plPL", "ptBR", "ruRU", "zhCN", "zhTW",
+];
+
 #[derive(Debug)]
 pub struct TriggerStringMap(BTreeMap<i32, String>);
 
@@ -26,6 +32,34 @@ impl TriggerStringMap {
     }
   }
 
+  /// Replaces `TRIGSTR_<id>` tokens embedded in `value`, unknown ids are kept as is
+  pub fn interpolate<'a>(&self, value: &'a str) -> Cow<'a, str> {
+    const TOKEN: &str = "TRIGSTR_";
+    if !value.contains(TOKEN) {
+      return Cow::Borrowed(value);
+    }
+
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find(TOKEN) {
+      out.push_str(&rest[..pos]);
+      rest = &rest[(pos + TOKEN.len())..];
+      let len = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+      match rest[..len].parse().ok().and_then(|id| self.0.get(&id)) {
+        Some(resolved) => out.push_str(resolved),
+        None => {
+          out.push_str(TOKEN);
+          out.push_str(&rest[..len]);
+        }
+      }
+      rest = &rest[len..];
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+  }
+
   pub fn iter(&self) -> impl Iterator<Item = (TriggerStringRef, &str)> {
     self
       .0
@@ -73,6 +107,71 @@ impl BinDecode for TriggerStringMap {
   }
 }
 
+/// Per-locale trigger strings of a Reforged map, lookups fall back to the default `war3map.wts`
+#[derive(Debug, Default)]
+pub struct LocalizedTriggerStrings(BTreeMap<&'static str, TriggerStringMap>);
+
+impl LocalizedTriggerStrings {
+  /// Missing or unreadable locale files are skipped
+  pub(crate) fn read(archive: &mut Archive) -> Self {
+    let mut map = BTreeMap::new();
+    for locale in MAP_LOCALES {
+      let path = format!("_Locales\\{}.w3mod\\war3map.wts", locale);
+      let strings = archive
+        .read_file_all_opt(&path)
+        .ok()
+        .flatten()
+        .and_then(|bytes| TriggerStringMap::decode(&mut bytes.as_slice()).ok());
+      if let Some(strings) = strings {
+        map.insert(*locale, strings);
+      }
+    }
+    LocalizedTriggerStrings(map)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  pub fn locales(&self) -> impl Iterator<Item = &str> {
+    self.0.keys().cloned()
+  }
+
+  /// Returns the strings of the locale the map ships closest to `locale`.
+  /// Accepts `enUS`, `en-US` or `en_us`, a language alone (`de`) matches the first locale of it.
+  pub fn get(&self, locale: &str) -> Option<&TriggerStringMap> {
+    let normalized: String = locale.chars().filter(|c| *c != '-' && *c != '_').collect();
+    if let Some((_, strings)) = self
+      .0
+      .iter()
+      .find(|(k, _)| k.eq_ignore_ascii_case(&normalized))
+    {
+      return Some(strings);
+    }
+    if normalized.len() == 2 {
+      return self
+        .0
+        .iter()
+        .find(|(k, _)| k[..2].eq_ignore_ascii_case(&normalized))
+        .map(|(_, strings)| strings);
+    }
+    None
+  }
+
+  /// Resolves `id` in `locale`, then in `default`
+  pub fn resolve<'a>(
+    &'a self,
+    locale: &str,
+    default: &'a TriggerStringMap,
+    id: &TriggerStringRef,
+  ) -> Option<Cow<'a, str>> {
+    self
+      .get(locale)
+      .and_then(|strings| strings.get(id))
+      .or_else(|| default.get(id))
+  }
+}
+
 #[derive(Debug)]
 struct TriggerString {
   pub id: i32,
@@ -211,6 +310,48 @@ fn test_parse_trigger_string_file() {
   )
 }
 
+#[test]
+fn test_trigger_string_interpolate() {
+  let mut map = BTreeMap::new();
+  map.insert(4, "Small Wars".to_string());
+  map.insert(7, "Rorslae".to_string());
+  let map = TriggerStringMap(map);
+  assert_eq!(map.interpolate("Small Wars"), "Small Wars");
+  assert_eq!(
+    map.interpolate("|cffff0000TRIGSTR_004|r by TRIGSTR_007"),
+    "|cffff0000Small Wars|r by Rorslae"
+  );
+  assert_eq!(map.interpolate("TRIGSTR_9 TRIGSTR_"), "TRIGSTR_9 TRIGSTR_");
+}
+
+#[test]
+fn test_localized_trigger_strings() {
+  let strings = |pairs: &[(i32, &str)]| {
+    TriggerStringMap(pairs.iter().map(|(k, v)| (*k, v.to_string())).collect())
+  };
+  let default = strings(&[(1, "Small Wars"), (2, "Rorslae")]);
+  let mut map = BTreeMap::new();
+  map.insert("deDE", strings(&[(1, "Kleine Kriege")]));
+  map.insert("zhCN", strings(&[(1, "小战争")]));
+  let localized = LocalizedTriggerStrings(map);
+
+  assert_eq!(
+    localized.locales().collect::<Vec<_>>(),
+    vec!["deDE", "zhCN"]
+  );
+  let resolve = |locale, id| localized.resolve(locale, &default, &TriggerStringRef::Ref(id));
+  assert_eq!(resolve("deDE", 1).unwrap(), "Kleine Kriege");
+  assert_eq!(resolve("de-DE", 1).unwrap(), "Kleine Kriege");
+  assert_eq!(resolve("zh_cn", 1).unwrap(), "小战争");
+  assert_eq!(resolve("de", 1).unwrap(), "Kleine Kriege");
+  // missing in the locale
+  assert_eq!(resolve("deDE", 2).unwrap(), "Rorslae");
+  // locale not shipped
+  assert_eq!(resolve("frFR", 1).unwrap(), "Small Wars");
+  assert_eq!(resolve("", 1).unwrap(), "Small Wars");
+  assert_eq!(resolve("deDE", 3), None);
+}
+
 #[test]
 fn test_parse_trigger_string_item() {
   let mut buf =
@@ -0,0 +1,478 @@
+// The war3mapUnits.doo file : Units and items placed in the editor
+
+use flo_util::binary::*;
+
+pub const GOLD_MINE_TYPE_ID: [u8; 4] = *b"ngol";
+pub const START_LOCATION_TYPE_ID: [u8; 4] = *b"sloc";
+
+/// Units of the same owner within this distance (world units) belong to the same creep camp
+pub const CREEP_CAMP_RADIUS: f32 = 400.;
+
+const MAGIC: &[u8] = b"W3do";
+
+/// A preplaced unit or item, both share the same record format.
+/// Telling them apart requires the object data of the game.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlacedUnit {
+  pub type_id: [u8; 4],
+  pub variation: u32,
+  pub x: f32,
+  pub y: f32,
+  pub z: f32,
+  /// Facing in radians
+  pub angle: f32,
+  pub scale: [f32; 3],
+  /// Maps saved by 1.32+ only
+  pub skin_id: Option<[u8; 4]>,
+  pub flags: u8,
+  pub player: u32,
+  /// -1 for the default value
+  pub hp: i32,
+  pub mp: i32,
+  /// Index of a drop table defined in war3map.w3i
+  pub item_table: Option<u32>,
+  /// When the unit dies, one item is dropped from each set
+  pub drop_sets: Vec<DropItemSet>,
+  /// Gold amount of gold mines
+  pub gold: i32,
+  pub target_acquisition: f32,
+  pub hero_level: u32,
+  /// Strength, agility, intelligence
+  pub hero_attributes: Option<[u32; 3]>,
+  pub inventory: Vec<InventoryItem>,
+  pub abilities: Vec<UnitAbility>,
+  /// `None` if this is not a random unit or item
+  pub random: Option<RandomSpec>,
+  pub color: i32,
+  pub waygate_destination: i32,
+  pub creation_number: u32,
+}
+
+impl PlacedUnit {
+  pub fn is_gold_mine(&self) -> bool {
+    self.type_id == GOLD_MINE_TYPE_ID
+  }
+
+  pub fn type_id_str(&self) -> String {
+    String::from_utf8_lossy(&self.type_id).into_owned()
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DropItemSet {
+  pub items: Vec<DropItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropItem {
+  /// All zero for "no item", can be a random item id like `YiI1`
+  pub item_id: [u8; 4],
+  /// Percentage
+  pub chance: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventoryItem {
+  pub slot: u32,
+  pub item_id: [u8; 4],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitAbility {
+  pub ability_id: [u8; 4],
+  pub autocast: bool,
+  pub level: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RandomSpec {
+  /// Any unit or item of the level, -1 for any level
+  Level { level: i32, item_class: u8 },
+  /// An entry of a random group defined in war3map.w3i
+  Group { group: u32, position: u32 },
+  /// One of the listed units or items
+  Custom(Vec<RandomChoice>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RandomChoice {
+  pub type_id: [u8; 4],
+  /// Percentage
+  pub chance: u32,
+}
+
+/// Neutral hostile units placed close to each other
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreepCamp {
+  /// Centroid of the units
+  pub x: f32,
+  pub y: f32,
+  pub units: Vec<PlacedUnit>,
+}
+
+#[derive(Debug, Default)]
+pub struct MapUnits {
+  units: Vec<PlacedUnit>,
+}
+
+impl MapUnits {
+  /// `has_skin_id`: maps saved by 1.32+ store a skin id after the scale
+  pub fn decode<T: Buf>(buf: &mut T, has_skin_id: bool) -> Result<Self, BinDecodeError> {
+    buf.check_size(16)?;
+    buf.get_tag(MAGIC)?;
+    let _version = buf.get_u32_le();
+    let subversion = buf.get_u32_le();
+    let count = buf.get_u32_le() as usize;
+
+    let mut units = Vec::with_capacity(std::cmp::min(count, 4096));
+    for idx in 0..count {
+      let unit = decode_unit(buf, subversion, has_skin_id)
+        .map_err(|err| err.context(format!("unit #{}", idx)))?;
+      units.push(unit);
+    }
+    Ok(Self { units })
+  }
+
+  pub fn len(&self) -> usize {
+    self.units.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.units.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &PlacedUnit> {
+    self.units.iter()
+  }
+
+  pub fn gold_mines(&self) -> impl Iterator<Item = &PlacedUnit> {
+    self.units.iter().filter(|unit| unit.is_gold_mine())
+  }
+
+  /// Groups units owned by `neutral_hostile` into camps,
+  /// a unit joins a camp if it is within `CREEP_CAMP_RADIUS` of any unit of the camp.
+  pub fn creep_camps(&self, neutral_hostile: u32) -> Vec<CreepCamp> {
+    let creeps: Vec<&PlacedUnit> = self
+      .units
+      .iter()
+      .filter(|unit| unit.player == neutral_hostile)
+      .collect();
+
+    let mut camp_of: Vec<Option<usize>> = vec![None; creeps.len()];
+    let mut num_camps = 0;
+    for start in 0..creeps.len() {
+      if camp_of[start].is_some() {
+        continue;
+      }
+      camp_of[start] = Some(num_camps);
+      let mut stack = vec![start];
+      while let Some(i) = stack.pop() {
+        for j in 0..creeps.len() {
+          if camp_of[j].is_none() && is_near(creeps[i], creeps[j]) {
+            camp_of[j] = Some(num_camps);
+            stack.push(j);
+          }
+        }
+      }
+      num_camps += 1;
+    }
+
+    let mut camps: Vec<Vec<PlacedUnit>> = vec![vec![]; num_camps];
+    for (unit, camp) in creeps.into_iter().zip(camp_of) {
+      if let Some(camp) = camp {
+        camps[camp].push(unit.clone());
+      }
+    }
+    camps
+      .into_iter()
+      .map(|units| {
+        let n = units.len() as f32;
+        CreepCamp {
+          x: units.iter().map(|unit| unit.x).sum::<f32>() / n,
+          y: units.iter().map(|unit| unit.y).sum::<f32>() / n,
+          units,
+        }
+      })
+      .collect()
+  }
+}
+
+fn is_near(a: &PlacedUnit, b: &PlacedUnit) -> bool {
+  (a.x - b.x).powi(2) + (a.y - b.y).powi(2) <= CREEP_CAMP_RADIUS * CREEP_CAMP_RADIUS
+}
+
+fn decode_unit<T: Buf>(
+  buf: &mut T,
+  subversion: u32,
+  has_skin_id: bool,
+) -> Result<PlacedUnit, BinDecodeError> {
+  // type id, variation, position, angle, scale
+  buf.check_size(4 + 4 + 12 + 4 + 12)?;
+  let type_id = get_id(buf);
+  let variation = buf.get_u32_le();
+  let x = buf.get_f32_le();
+  let y = buf.get_f32_le();
+  let z = buf.get_f32_le();
+  let angle = buf.get_f32_le();
+  let scale = [buf.get_f32_le(), buf.get_f32_le(), buf.get_f32_le()];
+  let skin_id = if has_skin_id {
+    buf.check_size(4)?;
+    Some(get_id(buf))
+  } else {
+    None
+  };
+  // flags, player, unknown, hp, mp
+  buf.check_size(1 + 4 + 2 + 4 + 4)?;
+  let flags = buf.get_u8();
+  let player = buf.get_u32_le();
+  buf.advance(2);
+  let hp = buf.get_i32_le();
+  let mp = buf.get_i32_le();
+  let item_table = if subversion >= 11 {
+    buf.check_size(4)?;
+    match buf.get_i32_le() {
+      -1 => None,
+      v => Some(v as u32),
+    }
+  } else {
+    None
+  };
+
+  let num_sets = get_u32(buf)? as usize;
+  let mut drop_sets = Vec::with_capacity(std::cmp::min(num_sets, 16));
+  for _ in 0..num_sets {
+    let num_items = get_u32(buf)? as usize;
+    buf.check_size(num_items * 8)?;
+    let items = (0..num_items)
+      .map(|_| DropItem {
+        item_id: get_id(buf),
+        chance: buf.get_u32_le(),
+      })
+      .collect();
+    drop_sets.push(DropItemSet { items });
+  }
+
+  // gold, target acquisition, hero level
+  buf.check_size(4 + 4 + 4)?;
+  let gold = buf.get_i32_le();
+  let target_acquisition = buf.get_f32_le();
+  let hero_level = buf.get_u32_le();
+  let hero_attributes = if subversion >= 11 {
+    buf.check_size(12)?;
+    Some([buf.get_u32_le(), buf.get_u32_le(), buf.get_u32_le()])
+  } else {
+    None
+  };
+
+  let num_items = get_u32(buf)? as usize;
+  buf.check_size(num_items * 8)?;
+  let inventory = (0..num_items)
+    .map(|_| InventoryItem {
+      slot: buf.get_u32_le(),
+      item_id: get_id(buf),
+    })
+    .collect();
+
+  let num_abilities = get_u32(buf)? as usize;
+  buf.check_size(num_abilities * 12)?;
+  let abilities = (0..num_abilities)
+    .map(|_| UnitAbility {
+      ability_id: get_id(buf),
+      autocast: buf.get_u32_le() != 0,
+      level: buf.get_u32_le(),
+    })
+    .collect();
+
+  let random = match get_u32(buf)? {
+    0 => {
+      // 24-bit level and item class
+      buf.check_size(4)?;
+      let mut level = [0; 4];
+      buf.copy_to_slice(&mut level[..3]);
+      let item_class = buf.get_u8();
+      if level[..3] == [0xFF; 3] {
+        None
+      } else {
+        Some(RandomSpec::Level {
+          level: i32::from_le_bytes(level),
+          item_class,
+        })
+      }
+    }
+    1 => {
+      buf.check_size(8)?;
+      Some(RandomSpec::Group {
+        group: buf.get_u32_le(),
+        position: buf.get_u32_le(),
+      })
+    }
+    2 => {
+      let n = get_u32(buf)? as usize;
+      buf.check_size(n * 8)?;
+      Some(RandomSpec::Custom(
+        (0..n)
+          .map(|_| RandomChoice {
+            type_id: get_id(buf),
+            chance: buf.get_u32_le(),
+          })
+          .collect(),
+      ))
+    }
+    other => {
+      return Err(BinDecodeError::failure(format!(
+        "unknown random type: {}",
+        other
+      )))
+    }
+  };
+
+  // custom color, waygate destination, creation number
+  buf.check_size(12)?;
+  let color = buf.get_i32_le();
+  let waygate_destination = buf.get_i32_le();
+  let creation_number = buf.get_u32_le();
+
+  Ok(PlacedUnit {
+    type_id,
+    variation,
+    x,
+    y,
+    z,
+    angle,
+    scale,
+    skin_id,
+    flags,
+    player,
+    hp,
+    mp,
+    item_table,
+    drop_sets,
+    gold,
+    target_acquisition,
+    hero_level,
+    hero_attributes,
+    inventory,
+    abilities,
+    random,
+    color,
+    waygate_destination,
+    creation_number,
+  })
+}
+
+fn get_id<T: Buf>(buf: &mut T) -> [u8; 4] {
+  let mut id = [0; 4];
+  buf.copy_to_slice(&mut id);
+  id
+}
+
+fn get_u32<T: Buf>(buf: &mut T) -> Result<u32, BinDecodeError> {
+  buf.check_size(4)?;
+  Ok(buf.get_u32_le())
+}
+
+#[test]
+fn test_decode_units() {
+  fn put_unit(buf: &mut BytesMut, type_id: &[u8; 4], x: f32, y: f32, gold: i32) {
+    buf.put_slice(type_id);
+    buf.put_u32_le(0);
+    buf.put_f32_le(x);
+    buf.put_f32_le(y);
+    buf.put_f32_le(0.);
+    buf.put_f32_le(0.);
+    buf.put_slice(&[0; 12]);
+    buf.put_slice(b"ngol");
+    buf.put_u8(2);
+    buf.put_u32_le(15);
+    buf.put_slice(&[0; 2]);
+    buf.put_i32_le(-1);
+    buf.put_i32_le(-1);
+    buf.put_i32_le(-1);
+    // one dropped item set with one item
+    buf.put_u32_le(1);
+    buf.put_u32_le(1);
+    buf.put_slice(b"ratc");
+    buf.put_u32_le(100);
+    buf.put_i32_le(gold);
+    buf.put_f32_le(-1.);
+    buf.put_u32_le(1);
+    buf.put_slice(&[0; 12]);
+    buf.put_u32_le(0);
+    buf.put_u32_le(0);
+    // random from custom list
+    buf.put_u32_le(2);
+    buf.put_u32_le(1);
+    buf.put_slice(b"hfoo");
+    buf.put_u32_le(100);
+    buf.put_i32_le(-1);
+    buf.put_i32_le(-1);
+    buf.put_u32_le(0);
+  }
+
+  let mut buf = BytesMut::new();
+  buf.put_slice(MAGIC);
+  buf.put_u32_le(8);
+  buf.put_u32_le(11);
+  buf.put_u32_le(2);
+  put_unit(&mut buf, &GOLD_MINE_TYPE_ID, 128., -256., 12500);
+  put_unit(&mut buf, &START_LOCATION_TYPE_ID, 0., 64., 0);
+
+  let units = MapUnits::decode(&mut buf.clone().freeze(), true).unwrap();
+  assert_eq!(units.len(), 2);
+  let mines: Vec<_> = units.gold_mines().collect();
+  assert_eq!(mines.len(), 1);
+  assert_eq!(
+    (mines[0].x, mines[0].y, mines[0].gold),
+    (128., -256., 12500)
+  );
+  assert_eq!(mines[0].skin_id, Some(*b"ngol"));
+  assert_eq!(mines[0].item_table, None);
+  assert_eq!(
+    mines[0].drop_sets,
+    vec![DropItemSet {
+      items: vec![DropItem {
+        item_id: *b"ratc",
+        chance: 100
+      }]
+    }]
+  );
+  assert_eq!(
+    mines[0].random,
+    Some(RandomSpec::Custom(vec![RandomChoice {
+      type_id: *b"hfoo",
+      chance: 100
+    }]))
+  );
+
+  // truncated
+  let mut bytes = buf.freeze();
+  bytes.truncate(bytes.len() - 1);
+  assert!(MapUnits::decode(&mut bytes, true).is_err());
+}
+
+#[test]
+fn test_creep_camps() {
+  let unit = |player, x, y| PlacedUnit {
+    type_id: *b"nfsh",
+    x,
+    y,
+    player,
+    ..Default::default()
+  };
+  let units = MapUnits {
+    units: vec![
+      unit(24, 0., 0.),
+      unit(24, 1000., 1000.),
+      unit(24, 300., 0.),
+      // chained through the previous one
+      unit(24, 600., 100.),
+      unit(0, 100., 100.),
+    ],
+  };
+  let camps = units.creep_camps(24);
+  assert_eq!(camps.len(), 2);
+  assert_eq!(camps[0].units.len(), 3);
+  assert_eq!((camps[0].x, camps[0].y), (300., 100. / 3.));
+  assert_eq!(camps[1].units.len(), 1);
+  assert_eq!((camps[1].x, camps[1].y), (1000., 1000.));
+}
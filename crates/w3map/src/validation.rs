@@ -0,0 +1,341 @@
+//! Checks a map against a configurable rule set before it's used to host a game.
+
+use crate::trigger_string::TriggerStringRef;
+use crate::{MapFlags, W3Map};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+  Warning,
+  Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticKind {
+  FileTooLarge {
+    size: usize,
+    max: usize,
+  },
+  NotMelee,
+  TooManyPlayers {
+    players: usize,
+    max: usize,
+  },
+  ForbiddenImport {
+    path: String,
+    pattern: String,
+  },
+  /// `field` references a trigger string the map doesn't contain
+  MissingTriggerString {
+    field: String,
+    id: i32,
+  },
+  DimensionOutOfBounds {
+    width: u32,
+    height: u32,
+    min: (u32, u32),
+    max: (u32, u32),
+  },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  pub kind: DiagnosticKind,
+}
+
+impl fmt::Display for Diagnostic {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.kind {
+      DiagnosticKind::FileTooLarge { size, max } => write!(
+        f,
+        "map file is {} KB, the limit is {} KB",
+        size / 1024,
+        max / 1024
+      ),
+      DiagnosticKind::NotMelee => write!(f, "map is not a melee map"),
+      DiagnosticKind::TooManyPlayers { players, max } => {
+        write!(f, "map has {} player slots, the limit is {}", players, max)
+      }
+      DiagnosticKind::ForbiddenImport {
+        ref path,
+        ref pattern,
+      } => write!(
+        f,
+        "imported file `{}` is not allowed (matches `{}`)",
+        path, pattern
+      ),
+      DiagnosticKind::MissingTriggerString { ref field, id } => {
+        write!(f, "{} references missing trigger string {}", field, id)
+      }
+      DiagnosticKind::DimensionOutOfBounds {
+        width,
+        height,
+        min,
+        max,
+      } => write!(
+        f,
+        "map size {}x{} is out of the allowed range {}x{} - {}x{}",
+        width, height, min.0, min.1, max.0, max.1
+      ),
+    }
+  }
+}
+
+pub trait MapRule: Send + Sync {
+  fn check(&self, map: &W3Map, diagnostics: &mut Vec<Diagnostic>);
+}
+
+/// Map file size limit in bytes
+pub struct MaxFileSize(pub usize);
+
+impl MapRule for MaxFileSize {
+  fn check(&self, map: &W3Map, diagnostics: &mut Vec<Diagnostic>) {
+    if map.file_size() > self.0 {
+      diagnostics.push(Diagnostic {
+        severity: Severity::Error,
+        kind: DiagnosticKind::FileTooLarge {
+          size: map.file_size(),
+          max: self.0,
+        },
+      })
+    }
+  }
+}
+
+pub struct RequireMelee;
+
+impl MapRule for RequireMelee {
+  fn check(&self, map: &W3Map, diagnostics: &mut Vec<Diagnostic>) {
+    if !map.flags().contains(MapFlags::MELEE) {
+      diagnostics.push(Diagnostic {
+        severity: Severity::Error,
+        kind: DiagnosticKind::NotMelee,
+      })
+    }
+  }
+}
+
+pub struct MaxPlayers(pub usize);
+
+impl MapRule for MaxPlayers {
+  fn check(&self, map: &W3Map, diagnostics: &mut Vec<Diagnostic>) {
+    if map.num_players() > self.0 {
+      diagnostics.push(Diagnostic {
+        severity: Severity::Error,
+        kind: DiagnosticKind::TooManyPlayers {
+          players: map.num_players(),
+          max: self.0,
+        },
+      })
+    }
+  }
+}
+
+/// Case insensitive patterns of archive paths, `*` matches any characters,
+/// e.g. `*.mdx` or `war3mapImported\*`
+pub struct ForbiddenImports(pub Vec<String>);
+
+impl MapRule for ForbiddenImports {
+  fn check(&self, map: &W3Map, diagnostics: &mut Vec<Diagnostic>) {
+    for file in map.imported_files() {
+      if let Some(pattern) = self.0.iter().find(|p| matches_pattern(p, &file.path)) {
+        diagnostics.push(Diagnostic {
+          severity: Severity::Error,
+          kind: DiagnosticKind::ForbiddenImport {
+            path: file.path.clone(),
+            pattern: pattern.clone(),
+          },
+        })
+      }
+    }
+  }
+}
+
+/// Map info strings referencing missing trigger strings,
+/// the game shows them as empty or raw `TRIGSTR_` tokens
+pub struct TriggerStrings;
+
+impl MapRule for TriggerStrings {
+  fn check(&self, map: &W3Map, diagnostics: &mut Vec<Diagnostic>) {
+    let info = &map.info;
+    let mut refs: Vec<(String, &TriggerStringRef)> = vec![
+      ("name".to_string(), &info.name),
+      ("author".to_string(), &info.author),
+      ("description".to_string(), &info.description),
+      ("suggested players".to_string(), &info.suggested_players),
+    ];
+    let player_names = info
+      .players_classic
+      .iter()
+      .flatten()
+      .map(|p| (p.id, &p.name))
+      .chain(
+        info
+          .players_reforged
+          .iter()
+          .flatten()
+          .map(|p| (p.id, &p.name)),
+      );
+    for (id, name) in player_names {
+      refs.push((format!("player {} name", id), name));
+    }
+    for (idx, force) in info.forces.iter().enumerate() {
+      refs.push((format!("force {} name", idx + 1), &force.name));
+    }
+
+    for (field, value) in refs {
+      if let TriggerStringRef::Ref(id) = *value {
+        if map.trigger_strings.get(value).is_none() {
+          diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            kind: DiagnosticKind::MissingTriggerString { field, id },
+          })
+        }
+      }
+    }
+  }
+}
+
+/// Playable size bounds, in cells
+pub struct DimensionBounds {
+  pub min: (u32, u32),
+  pub max: (u32, u32),
+}
+
+impl MapRule for DimensionBounds {
+  fn check(&self, map: &W3Map, diagnostics: &mut Vec<Diagnostic>) {
+    let (width, height) = map.dimension();
+    if width < self.min.0 || height < self.min.1 || width > self.max.0 || height > self.max.1 {
+      diagnostics.push(Diagnostic {
+        severity: Severity::Error,
+        kind: DiagnosticKind::DimensionOutOfBounds {
+          width,
+          height,
+          min: self.min,
+          max: self.max,
+        },
+      })
+    }
+  }
+}
+
+/// Runs a set of rules against maps
+///
+/// ```ignore
+/// let validator = MapValidator::new()
+///   .rule(MaxFileSize(8 * 1024 * 1024))
+///   .rule(RequireMelee)
+///   .rule(MaxPlayers(12));
+/// let report = validator.validate(&map);
+/// ```
+#[derive(Default)]
+pub struct MapValidator {
+  rules: Vec<Box<dyn MapRule>>,
+}
+
+impl MapValidator {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn rule<R: MapRule + 'static>(mut self, rule: R) -> Self {
+    self.rules.push(Box::new(rule));
+    self
+  }
+
+  pub fn validate(&self, map: &W3Map) -> ValidationReport {
+    let mut diagnostics = vec![];
+    for rule in &self.rules {
+      rule.check(map, &mut diagnostics);
+    }
+    ValidationReport { diagnostics }
+  }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+  /// The map passed if there are only warnings
+  pub fn is_ok(&self) -> bool {
+    self.errors().next().is_none()
+  }
+
+  pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+    self
+      .diagnostics
+      .iter()
+      .filter(|d| d.severity == Severity::Error)
+  }
+
+  pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+    self
+      .diagnostics
+      .iter()
+      .filter(|d| d.severity == Severity::Warning)
+  }
+}
+
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+  fn normalize(s: &str) -> String {
+    s.to_ascii_lowercase().replace('/', "\\")
+  }
+  let pattern = normalize(pattern);
+  let path = normalize(path);
+
+  let mut parts = pattern.split('*');
+  let first = parts.next().unwrap_or_default();
+  if !path.starts_with(first) {
+    return false;
+  }
+  let mut rest = &path[first.len()..];
+  let parts: Vec<&str> = parts.collect();
+  let last = match parts.split_last() {
+    Some((last, middle)) => {
+      for part in middle {
+        match rest.find(part) {
+          Some(pos) => rest = &rest[(pos + part.len())..],
+          None => return false,
+        }
+      }
+      last
+    }
+    // no `*`
+    None => return rest.is_empty(),
+  };
+  rest.ends_with(last)
+}
+
+#[test]
+fn test_matches_pattern() {
+  assert!(matches_pattern("*.mdx", "war3mapImported\\Hero.MDX"));
+  assert!(!matches_pattern("*.mdx", "war3mapImported\\Hero.mdl"));
+  assert!(matches_pattern(
+    "war3mapImported/*",
+    "war3mapImported\\Hero.mdx"
+  ));
+  assert!(matches_pattern("UI\\*\\*.blp", "UI\\Glues\\Loading.blp"));
+  assert!(!matches_pattern("UI\\*\\*.blp", "UI\\Loading.blp"));
+  assert!(matches_pattern("war3map.j", "WAR3MAP.J"));
+  assert!(!matches_pattern("war3map.j", "war3map.j.bak"));
+  assert!(matches_pattern("*", "anything"));
+  assert!(!matches_pattern("a*a", "a"));
+
+  let report = ValidationReport {
+    diagnostics: vec![Diagnostic {
+      severity: Severity::Warning,
+      kind: DiagnosticKind::MissingTriggerString {
+        field: "name".to_string(),
+        id: 1,
+      },
+    }],
+  };
+  assert!(report.is_ok());
+  assert_eq!(
+    report.diagnostics[0].to_string(),
+    "name references missing trigger string 1"
+  );
+}
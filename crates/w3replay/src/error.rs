@@ -18,6 +18,10 @@ pub enum Error {
   NoGameInfoRecord,
   #[error("no slot info record")]
   NoSlotInfoRecord,
+  #[error("unexpected packet: {0:?}")]
+  UnexpectedPacket(flo_w3gs::constants::PacketTypeId),
+  #[error("W3GS: {0}")]
+  W3GS(#[from] flo_w3gs::error::Error),
   #[error("decompress: {0}")]
   Decompress(#[from] flate2::DecompressError),
   #[error("bin decode: {0}")]
@@ -18,6 +18,8 @@ pub use header::Header;
 pub use records::*;
 pub mod replay;
 pub use replay::*;
+pub mod writer;
+pub use writer::*;
 
 #[derive(Debug)]
 pub struct W3Replay<R> {
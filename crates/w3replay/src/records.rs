@@ -222,6 +222,27 @@ pub struct GameInfo {
   pub language_id: u32,
 }
 
+impl GameInfo {
+  pub fn new(
+    host_player_info: PlayerInfo,
+    game_name: CString,
+    game_settings: GameSettings,
+    player_count: u32,
+    game_flags: GameFlags,
+  ) -> Self {
+    Self {
+      num_of_host_records: 1,
+      host_player_info,
+      game_name,
+      _unk_1: 0,
+      game_settings,
+      player_count,
+      game_flags,
+      language_id: 0,
+    }
+  }
+}
+
 #[derive(Debug, BinEncode, BinDecode, PartialEq, Clone)]
 pub struct PlayerInfo {
   pub id: u8,
@@ -231,6 +252,18 @@ pub struct PlayerInfo {
   pub additional_data: Vec<u8>,
 }
 
+impl PlayerInfo {
+  /// Player info of a custom game, which carries a single zero byte of additional data
+  pub fn new(id: u8, name: CString) -> Self {
+    Self {
+      id,
+      name,
+      _size_of_additional_data: 1,
+      additional_data: vec![0],
+    }
+  }
+}
+
 #[derive(Debug, BinEncode, BinDecode, PartialEq)]
 pub struct PlayerInfoRecord {
   pub player_info: PlayerInfo,
@@ -251,10 +284,16 @@ pub struct GameStart {
   pub unknown: u32,
 }
 
-#[derive(Debug, BinEncode, BinDecode, PartialEq)]
+impl Default for GameStart {
+  fn default() -> Self {
+    Self { unknown: 1 }
+  }
+}
+
+#[derive(Debug, Default, BinEncode, BinDecode, PartialEq)]
 pub struct CountDownStart(GameStart);
 
-#[derive(Debug, BinEncode, BinDecode, PartialEq)]
+#[derive(Debug, Default, BinEncode, BinDecode, PartialEq)]
 pub struct CountDownEnd(GameStart);
 
 #[derive(Debug, PartialEq)]
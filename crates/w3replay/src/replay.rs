@@ -3,17 +3,25 @@ use flo_util::binary::{BinDecode, BinEncode};
 
 use crate::{
   block::{Blocks, BlocksEncoder},
-  error::Result,
+  error::{Error, Result},
   header::GameVersion,
-  Header, Record, RecordIter,
+  GameInfo, Header, PlayerAction, PlayerInfo, Record, RecordIter, SlotInfo,
 };
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
 
 pub struct ReplayDecoder<R> {
   header: Header,
   blocks: Blocks<R>,
 }
 
+impl ReplayDecoder<BufReader<File>> {
+  pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+    Self::new(BufReader::new(File::open(path)?))
+  }
+}
+
 impl<R: Read> ReplayDecoder<R> {
   pub fn new(mut r: R) -> Result<Self> {
     let mut header = [0_u8; Header::MIN_SIZE];
@@ -38,6 +46,150 @@ impl<R: Read> ReplayDecoder<R> {
   pub fn into_records(self) -> RecordIter<R> {
     RecordIter::new(self.blocks)
   }
+
+  /// Reads the records before the game start,
+  /// returns the game metadata and the in-game records
+  pub fn inspect(self) -> Result<(ReplayMetadata, TimedRecords<R>)> {
+    let Header {
+      game_version,
+      duration_ms,
+      ..
+    } = self.header;
+    let mut game = None;
+    let mut players = vec![];
+    let mut slots = None;
+    let mut iter = RecordIter::new(self.blocks);
+    while let Some(record) = iter.next() {
+      match record? {
+        Record::GameInfo(info) => {
+          players.insert(0, info.host_player_info.clone());
+          game = Some(info);
+        }
+        Record::PlayerInfo(info) => players.push(info.player_info),
+        Record::SlotInfo(info) => slots = Some(info),
+        Record::GameStart(_) => break,
+        _ => {}
+      }
+    }
+    Ok((
+      ReplayMetadata {
+        game_version,
+        duration_ms,
+        game: game.ok_or_else(|| Error::NoGameInfoRecord)?,
+        players,
+        slots: slots.ok_or_else(|| Error::NoSlotInfoRecord)?,
+      },
+      TimedRecords {
+        iter,
+        time_ms: 0,
+        done: false,
+      },
+    ))
+  }
+}
+
+#[derive(Debug)]
+pub struct ReplayMetadata {
+  pub game_version: GameVersion,
+  pub duration_ms: u32,
+  /// Game name, settings and the host player
+  pub game: GameInfo,
+  /// All players that joined the game, the host first
+  pub players: Vec<PlayerInfo>,
+  pub slots: SlotInfo,
+}
+
+/// A record and the game time it was received at
+#[derive(Debug, PartialEq)]
+pub struct TimedRecord {
+  pub time_ms: u32,
+  pub record: Record,
+}
+
+/// In-game records, the game time advances with every time slot record
+#[derive(Debug)]
+pub struct TimedRecords<R> {
+  iter: RecordIter<R>,
+  time_ms: u32,
+  done: bool,
+}
+
+impl<R> TimedRecords<R> {
+  /// Player actions of the time slot records
+  pub fn into_actions(self) -> TimedActions<R> {
+    TimedActions {
+      records: self,
+      time_ms: 0,
+      pending: vec![].into_iter(),
+    }
+  }
+}
+
+impl<R: Read> Iterator for TimedRecords<R> {
+  type Item = Result<TimedRecord>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None;
+    }
+    let record = match self.iter.next()? {
+      Ok(record) => record,
+      Err(err) => {
+        self.done = true;
+        return Some(Err(err.into()));
+      }
+    };
+    match record {
+      Record::TimeSlot(ref slot) => self.time_ms += slot.time_increment_ms as u32,
+      Record::TimeSlotFragment(ref slot) => self.time_ms += slot.0.time_increment_ms as u32,
+      _ => {}
+    }
+    Some(Ok(TimedRecord {
+      time_ms: self.time_ms,
+      record,
+    }))
+  }
+}
+
+/// A player action and the game time of its time slot
+#[derive(Debug, PartialEq)]
+pub struct TimedAction {
+  pub time_ms: u32,
+  pub action: PlayerAction,
+}
+
+#[derive(Debug)]
+pub struct TimedActions<R> {
+  records: TimedRecords<R>,
+  time_ms: u32,
+  pending: std::vec::IntoIter<PlayerAction>,
+}
+
+impl<R: Read> Iterator for TimedActions<R> {
+  type Item = Result<TimedAction>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      if let Some(action) = self.pending.next() {
+        return Some(Ok(TimedAction {
+          time_ms: self.time_ms,
+          action,
+        }));
+      }
+
+      let TimedRecord { time_ms, record } = match self.records.next()? {
+        Ok(record) => record,
+        Err(err) => return Some(Err(err)),
+      };
+      let actions = match record {
+        Record::TimeSlot(slot) => slot.actions,
+        Record::TimeSlotFragment(slot) => slot.0.actions,
+        _ => continue,
+      };
+      self.time_ms = time_ms;
+      self.pending = actions.into_iter();
+    }
+  }
 }
 
 pub struct ReplayEncoder<W> {
@@ -59,17 +211,22 @@ impl<W: Write + Seek> ReplayEncoder<W> {
     I: IntoIterator<Item = &'a Record>,
   {
     for r in iter {
-      match *r {
-        Record::TimeSlotFragment(ref slot) => {
-          self.header.duration_ms += slot.0.time_increment_ms as u32;
-        }
-        Record::TimeSlot(ref slot) => {
-          self.header.duration_ms += slot.time_increment_ms as u32;
-        }
-        _ => {}
+      self.encode_record(r)?;
+    }
+    Ok(())
+  }
+
+  pub fn encode_record(&mut self, r: &Record) -> Result<()> {
+    match *r {
+      Record::TimeSlotFragment(ref slot) => {
+        self.header.duration_ms += slot.0.time_increment_ms as u32;
+      }
+      Record::TimeSlot(ref slot) => {
+        self.header.duration_ms += slot.time_increment_ms as u32;
       }
-      self.w.encode(r)?;
+      _ => {}
     }
+    self.w.encode(r)?;
     Ok(())
   }
 
@@ -177,3 +334,76 @@ fn test_encode() {
   //   assert_eq!(a, b);
   // }
 }
+
+#[test]
+fn test_inspect_timed() {
+  use crate::records::*;
+  use flo_util::binary::CString;
+  use flo_w3gs::constants::GameSettingFlags;
+  use flo_w3gs::game::GameSettingsMap;
+  use std::io::Cursor;
+
+  let action = |player_id: u8| PlayerAction {
+    player_id,
+    data: vec![0x10, 0x00, 0x00].into(),
+  };
+  let records = vec![
+    Record::GameInfo(GameInfo::new(
+      PlayerInfo::new(1, CString::new("host").unwrap()),
+      CString::new("FLO").unwrap(),
+      GameSettings::new(
+        GameSettingFlags::SPEED_FAST,
+        GameSettingsMap {
+          path: "Maps\\test.w3x".to_string(),
+          width: 116,
+          height: 116,
+          sha1: [0; 20],
+          checksum: 0,
+        },
+      ),
+      24,
+      GameFlags::CUSTOM_GAME,
+    )),
+    Record::PlayerInfo(PlayerInfoRecord {
+      player_info: PlayerInfo::new(2, CString::new("guest").unwrap()),
+      unknown: 0,
+    }),
+    Record::SlotInfo(SlotInfo::default()),
+    Record::CountDownStart(Default::default()),
+    Record::CountDownEnd(Default::default()),
+    Record::GameStart(Default::default()),
+    Record::TimeSlot(TimeSlot {
+      time_increment_ms: 100,
+      actions: vec![action(1), action(2)],
+    }),
+    Record::TimeSlot(TimeSlot {
+      time_increment_ms: 100,
+      actions: vec![],
+    }),
+    Record::TimeSlotFragment(TimeSlotFragment(TimeSlot {
+      time_increment_ms: 50,
+      actions: vec![action(2)],
+    })),
+  ];
+
+  let mut buf = Cursor::new(vec![]);
+  let mut e = ReplayEncoder::new(GameVersion::default(), 0, &mut buf).unwrap();
+  e.encode_records(&records).unwrap();
+  e.finish().unwrap();
+
+  let d = ReplayDecoder::new(Cursor::new(buf.into_inner())).unwrap();
+  let (meta, iter) = d.inspect().unwrap();
+  assert_eq!(meta.duration_ms, 250);
+  assert_eq!(
+    meta.players.iter().map(|p| p.id).collect::<Vec<_>>(),
+    vec![1, 2]
+  );
+  assert_eq!(meta.game.game_name.to_str().unwrap(), "FLO");
+
+  let actions = iter
+    .into_actions()
+    .map(|a| a.map(|a| (a.time_ms, a.action.player_id)))
+    .collect::<Result<Vec<_>>>()
+    .unwrap();
+  assert_eq!(actions, vec![(100, 1), (100, 2), (250, 2)]);
+}
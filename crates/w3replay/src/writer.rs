@@ -0,0 +1,279 @@
+//! Builds a replay from the W3GS packets a game client receives from the host
+//!
+//! Records are written in the order Warcraft III expects them:
+//! the game info record, the other players, the final slot info, the start records,
+//! then time slots, chat messages and leave records as the game goes on.
+
+use flo_util::binary::CString;
+use flo_w3gs::action::{IncomingAction, IncomingAction2};
+use flo_w3gs::chat::ChatFromHost;
+use flo_w3gs::constants::{GameFlags, LeaveReason, PacketTypeId};
+use flo_w3gs::desync::Desync;
+use flo_w3gs::game::GameSettings;
+use flo_w3gs::join::SlotInfoJoin;
+use flo_w3gs::leave::PlayerLeft as PlayerLeftPacket;
+use flo_w3gs::packet::{Packet, ProtoBufPayload};
+use flo_w3gs::player::PlayerInfo as PlayerInfoPacket;
+use flo_w3gs::slot::SlotInfo;
+use std::io::{Seek, Write};
+
+use crate::error::{Error, Result};
+use crate::header::GameVersion;
+use crate::records::*;
+use crate::replay::ReplayEncoder;
+
+/// Header flags of LAN and Battle.net games
+const FLAGS_MULTIPLAYER: u16 = 0x8000;
+
+/// Game level information known before the first packet
+#[derive(Debug)]
+pub struct ReplayGameInfo {
+  pub game_version: GameVersion,
+  pub game_name: CString,
+  pub host_player_id: u8,
+  pub host_player_name: CString,
+  pub game_settings: GameSettings,
+  pub num_slots: u32,
+  pub game_flags: GameFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+  Lobby,
+  CountDown,
+  Started,
+}
+
+pub struct ReplayWriter<W> {
+  encoder: ReplayEncoder<W>,
+  host_player_id: u8,
+  slot_info: Option<SlotInfo>,
+  state: State,
+  num_left: u32,
+}
+
+impl<W: Write + Seek> ReplayWriter<W> {
+  pub fn new(info: ReplayGameInfo, w: W) -> Result<Self> {
+    let mut encoder = ReplayEncoder::new(info.game_version, FLAGS_MULTIPLAYER, w)?;
+    encoder.encode_record(&Record::GameInfo(GameInfo::new(
+      PlayerInfo::new(info.host_player_id, info.host_player_name),
+      info.game_name,
+      info.game_settings,
+      info.num_slots,
+      info.game_flags,
+    )))?;
+    Ok(Self {
+      encoder,
+      host_player_id: info.host_player_id,
+      slot_info: None,
+      state: State::Lobby,
+      num_left: 0,
+    })
+  }
+
+  /// Writes the records of a packet sent by the host,
+  /// packets that have no replay representation are ignored
+  pub fn write_packet(&mut self, packet: &Packet) -> Result<()> {
+    match (self.state, packet.type_id()) {
+      (State::Lobby, PacketTypeId::PlayerInfo) => {
+        let payload: PlayerInfoPacket = packet.decode_simple()?;
+        if payload.player_id != self.host_player_id {
+          self.encode(Record::PlayerInfo(PlayerInfoRecord {
+            player_info: PlayerInfo::new(payload.player_id, payload.player_name),
+            unknown: 0,
+          }))?;
+        }
+      }
+      (State::Lobby, PacketTypeId::ProtoBuf) => {
+        let payload: ProtoBufPayload = packet.decode_simple()?;
+        self.encode(Record::ProtoBuf(payload))?;
+      }
+      (State::Lobby, PacketTypeId::SlotInfoJoin) => {
+        let payload: SlotInfoJoin = packet.decode_simple()?;
+        self.slot_info = Some(payload.slot_info);
+      }
+      (State::Lobby, PacketTypeId::SlotInfo) => {
+        self.slot_info = Some(packet.decode_simple()?);
+      }
+      (State::Lobby, PacketTypeId::CountDownStart) => {
+        let slot_info = self
+          .slot_info
+          .take()
+          .ok_or_else(|| Error::NoSlotInfoRecord)?;
+        self.encode(Record::SlotInfo(slot_info))?;
+        self.encode(Record::CountDownStart(Default::default()))?;
+        self.state = State::CountDown;
+      }
+      (State::CountDown, PacketTypeId::CountDownEnd) => {
+        self.encode(Record::CountDownEnd(Default::default()))?;
+        self.encode(Record::GameStart(Default::default()))?;
+        self.state = State::Started;
+      }
+      (State::Started, PacketTypeId::IncomingAction) => {
+        let IncomingAction(slot) = packet.decode_payload()?;
+        self.encode(Record::TimeSlot(TimeSlot {
+          time_increment_ms: slot.time_increment_ms,
+          actions: slot.actions,
+        }))?;
+      }
+      (State::Started, PacketTypeId::IncomingAction2) => {
+        let IncomingAction2(slot) = packet.decode_payload()?;
+        self.encode(Record::TimeSlotFragment(TimeSlotFragment(TimeSlot {
+          time_increment_ms: slot.time_increment_ms,
+          actions: slot.actions,
+        })))?;
+      }
+      (State::Started, PacketTypeId::ChatFromHost) => {
+        let ChatFromHost(chat) = packet.decode_simple()?;
+        if chat.is_in_game_chat() {
+          self.encode(Record::ChatMessage(PlayerChatMessage {
+            player_id: chat.from_player,
+            message: chat.message,
+          }))?;
+        }
+      }
+      (State::Started, PacketTypeId::Desync) => {
+        let payload: Desync = packet.decode_simple()?;
+        self.encode(Record::Desync(payload))?;
+      }
+      (State::CountDown, PacketTypeId::PlayerLeft) | (State::Started, PacketTypeId::PlayerLeft) => {
+        let payload: PlayerLeftPacket = packet.decode_simple()?;
+        self.num_left += 1;
+        self.encode(Record::PlayerLeft(PlayerLeft {
+          // the connection was closed by the remote game
+          reason: LeaveReason::LeaveDisconnect,
+          player_id: payload.player_id,
+          result: payload.reason.into(),
+          unknown: self.num_left,
+        }))?;
+      }
+      (State::Lobby, PacketTypeId::CountDownEnd)
+      | (State::CountDown, PacketTypeId::CountDownStart)
+      | (State::Started, PacketTypeId::CountDownStart)
+      | (State::Started, PacketTypeId::CountDownEnd) => {
+        return Err(Error::UnexpectedPacket(packet.type_id()))
+      }
+      _ => {}
+    }
+    Ok(())
+  }
+
+  /// Writes the header and flushes the output,
+  /// the replay is only playable after this call
+  pub fn finish(self) -> Result<()> {
+    self.encoder.finish()
+  }
+
+  fn encode(&mut self, record: Record) -> Result<()> {
+    self.encoder.encode_record(&record)
+  }
+}
+
+#[test]
+fn test_writer() {
+  use crate::constants::RecordTypeId;
+  use flo_w3gs::action::PlayerAction;
+  use flo_w3gs::chat::{ChatToHost, MessageScope};
+  use flo_w3gs::constants::GameSettingFlags;
+  use flo_w3gs::game::{CountDownEnd, CountDownStart, GameSettingsMap};
+  use std::io::Cursor;
+
+  let info = ReplayGameInfo {
+    game_version: GameVersion {
+      version: 10032,
+      build_number: 6110,
+      ..Default::default()
+    },
+    game_name: CString::new("FLO").unwrap(),
+    host_player_id: 1,
+    host_player_name: CString::new("host").unwrap(),
+    game_settings: GameSettings::new(
+      GameSettingFlags::SPEED_FAST,
+      GameSettingsMap {
+        path: "Maps\\test.w3x".to_string(),
+        width: 116,
+        height: 116,
+        sha1: [0; 20],
+        checksum: 0,
+      },
+    ),
+    num_slots: 24,
+    game_flags: GameFlags::CUSTOM_GAME,
+  };
+
+  let action = PlayerAction {
+    player_id: 2,
+    data: vec![0x10, 0x00, 0x00].into(),
+  };
+
+  let packets = vec![
+    Packet::simple(PlayerInfoPacket::new(1, "host")).unwrap(),
+    Packet::simple(PlayerInfoPacket::new(2, "guest")).unwrap(),
+    Packet::simple(SlotInfo::default()).unwrap(),
+    Packet::simple(CountDownStart).unwrap(),
+    Packet::simple(CountDownEnd).unwrap(),
+    Packet::with_payload(IncomingAction(flo_w3gs::action::TimeSlot {
+      time_increment_ms: 100,
+      actions: vec![action.clone()],
+    }))
+    .unwrap(),
+    Packet::simple(ChatFromHost(ChatToHost::in_game(
+      MessageScope::All,
+      2,
+      &[1],
+      "gg",
+    )))
+    .unwrap(),
+    Packet::with_payload(IncomingAction(flo_w3gs::action::TimeSlot {
+      time_increment_ms: 50,
+      actions: vec![],
+    }))
+    .unwrap(),
+    Packet::simple(PlayerLeftPacket {
+      player_id: 2,
+      reason: LeaveReason::LeaveLost,
+    })
+    .unwrap(),
+  ];
+
+  let mut buf = Cursor::new(vec![]);
+  let mut w = ReplayWriter::new(info, &mut buf).unwrap();
+  for packet in &packets {
+    w.write_packet(packet).unwrap();
+  }
+  w.finish().unwrap();
+
+  let decoder = crate::replay::ReplayDecoder::new(Cursor::new(buf.into_inner())).unwrap();
+  assert_eq!(decoder.header().duration_ms, 150);
+  let records = decoder
+    .into_records()
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap();
+  let type_ids: Vec<_> = records.iter().map(Record::type_id).collect();
+  assert_eq!(
+    type_ids,
+    vec![
+      RecordTypeId::GameInfo,
+      RecordTypeId::PlayerInfo,
+      RecordTypeId::SlotInfo,
+      RecordTypeId::CountDownStart,
+      RecordTypeId::CountDownEnd,
+      RecordTypeId::GameStart,
+      RecordTypeId::TimeSlot,
+      RecordTypeId::ChatMessage,
+      RecordTypeId::TimeSlot,
+      RecordTypeId::PlayerLeft,
+    ]
+  );
+  match records[6] {
+    Record::TimeSlot(ref slot) => assert_eq!(slot.actions, vec![action]),
+    _ => unreachable!(),
+  }
+  match records[9] {
+    Record::PlayerLeft(ref left) => {
+      assert_eq!(left.player_id, 2);
+      assert_eq!(left.result, 0x07);
+    }
+    _ => unreachable!(),
+  }
+}
@@ -0,0 +1,90 @@
+//! Game files of Reforged installations, read from the CASC storage in `Data`.
+//! Stock maps (`maps\frozenthrone\...`) and game data have no loose copies on disk.
+
+use crate::error::*;
+use bytes::Bytes;
+use casclib::{CascError, Storage};
+use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+
+/// Files of the game live in the root mod of the storage
+const ROOT_MOD: &str = "war3.w3mod:";
+
+#[derive(Debug)]
+pub struct CascStorage {
+  path: PathBuf,
+  handle: Mutex<Option<Storage>>,
+}
+
+impl CascStorage {
+  /// The storage is opened on first access
+  pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+    Self {
+      path: path.into(),
+      handle: Mutex::new(None),
+    }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Returns `None` if the storage has no file at `path`
+  pub fn read(&self, path: &str) -> Result<Option<Bytes>> {
+    let bytes = self.with_storage(|s| {
+      s.entry(&storage_path(path))
+        .open()
+        .and_then(|e| e.read_all())
+        .map(|bytes| Some(Bytes::from(bytes)))
+        .or_else(|e| match e {
+          CascError::FileNotFound => Ok(None),
+          e => Err(e),
+        })
+    })??;
+    Ok(bytes)
+  }
+
+  pub fn list(&self, mask: &str) -> Result<Vec<String>> {
+    let names = self.with_storage(|s| -> Result<_, CascError> {
+      use std::iter::FromIterator;
+      Result::<_, CascError>::from_iter(
+        s.files_with_mask(storage_path(mask))
+          .into_iter()
+          .map(|f| f.map(|f| f.get_name().to_string())),
+      )
+    })??;
+    Ok(names)
+  }
+
+  fn with_storage<F, R>(&self, f: F) -> Result<R>
+  where
+    F: FnOnce(&Storage) -> R,
+  {
+    let mut lock = self.handle.lock();
+    if let Some(storage) = lock.as_ref() {
+      Ok(f(storage))
+    } else {
+      let storage = casclib::open(&self.path)?;
+      let r = f(&storage);
+      *lock = Some(storage);
+      Ok(r)
+    }
+  }
+}
+
+// CASC names use backslashes, callers on other platforms may pass slashes
+fn storage_path(path: &str) -> String {
+  format!("{}{}", ROOT_MOD, path.replace('/', "\\"))
+}
+
+#[test]
+fn test_storage_path() {
+  assert_eq!(
+    storage_path("maps\\frozenthrone\\(2)echoisles.w3x"),
+    "war3.w3mod:maps\\frozenthrone\\(2)echoisles.w3x"
+  );
+  assert_eq!(
+    storage_path("maps/frozenthrone/(2)echoisles.w3x"),
+    "war3.w3mod:maps\\frozenthrone\\(2)echoisles.w3x"
+  );
+}
@@ -10,8 +10,21 @@ pub enum Error {
   Io(#[from] std::io::Error),
   #[error("glob pattern error: {0}")]
   GlobPattern(#[from] glob::PatternError),
+  #[cfg(feature = "casc")]
   #[error("casc: {0}")]
   Casc(#[from] casclib::CascError),
+  #[error("invalid url: {0}")]
+  InvalidUrl(String),
+  #[error("http: {0}")]
+  Http(#[from] hyper::Error),
+  #[error("http status: {0}")]
+  HttpStatus(u16),
+  #[error("too many redirects")]
+  TooManyRedirects,
+  #[error("remote file too large")]
+  RemoteFileTooLarge,
+  #[error("checksum mismatch: expected {expected}, got {actual}")]
+  ChecksumMismatch { expected: String, actual: String },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -1,31 +1,40 @@
+#[cfg(feature = "casc")]
+pub mod casc;
 pub mod path_tree;
+pub mod remote;
 
 use bytes::Bytes;
-use casclib::Storage;
 use glob::Pattern;
-use parking_lot::Mutex;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use walkdir::WalkDir;
 
+#[cfg(feature = "casc")]
+use casc::CascStorage;
 use flo_platform::ClientPlatformInfo;
+use remote::RemoteCache;
 
 pub mod error;
 
 use error::*;
 
+/// Files are resolved from the override directories first,
+/// then from the CASC storage of the game if the `casc` feature is enabled.
 #[derive(Debug)]
 pub struct W3Storage {
-  storage_path: PathBuf,
   overrides: Vec<OverridePath>,
-  handle: Mutex<Option<Storage>>,
+  #[cfg(feature = "casc")]
+  casc: CascStorage,
+  remote: RemoteCache,
 }
 
 impl W3Storage {
   pub fn new(platform: &ClientPlatformInfo) -> Result<Self> {
     let mut inst = Self {
-      storage_path: platform.installation_path.join("Data"),
       overrides: vec![],
-      handle: Mutex::new(None),
+      #[cfg(feature = "casc")]
+      casc: CascStorage::new(platform.installation_path.join("Data")),
+      remote: RemoteCache::new(platform.user_data_path.join("flo").join("remote")),
     };
 
     inst.add_override("maps", platform.user_data_path.clone())?;
@@ -47,16 +56,34 @@ impl W3Storage {
     Ok(())
   }
 
+  /// Directory of maps downloaded by `resolve_remote`
+  pub fn set_remote_cache_path(&mut self, path: PathBuf) {
+    self.remote = RemoteCache::new(path);
+  }
+
+  pub fn remote_cache_path(&self) -> &Path {
+    self.remote.path()
+  }
+
+  /// Downloads `url` into the remote cache unless a file with the content digest
+  /// `expected_sha1` is cached already.
+  /// The digest is the SHA-1 of the file, not the map checksum the game uses.
+  pub async fn resolve_remote(&self, url: &str, expected_sha1: &[u8; 20]) -> Result<File> {
+    let path = self.remote.resolve(url, expected_sha1).await?;
+    let metadata = tokio::fs::metadata(&path).await?;
+    Ok(File {
+      source: FileSource::Remote,
+      size: metadata.len(),
+      modified: metadata.modified().ok(),
+      data: Data::Path(path),
+    })
+  }
+
   pub fn list_storage_files(&self, mask: &str) -> Result<Vec<String>> {
-    let cast_mask = Self::get_storage_path(mask);
-    let mut paths: Vec<_> = self.with_storage(|s| -> Result<_, casclib::CascError> {
-      use std::iter::FromIterator;
-      Result::<_, casclib::CascError>::from_iter(
-        s.files_with_mask(cast_mask)
-          .into_iter()
-          .map(|f| f.map(|f| f.get_name().to_string())),
-      )
-    })??;
+    #[cfg(feature = "casc")]
+    let mut paths = self.casc.list(mask)?;
+    #[cfg(not(feature = "casc"))]
+    let mut paths = vec![];
 
     for override_path in &self.overrides {
       let fs_mask = Pattern::new(mask)?;
@@ -94,6 +121,7 @@ impl W3Storage {
             return Ok(Some(File {
               source: FileSource::Override,
               size: m.len(),
+              modified: m.modified().ok(),
               data: Data::Path(resolved_path),
             }))
           }
@@ -104,27 +132,22 @@ impl W3Storage {
         }
       }
     }
-    let bytes = self.with_storage(|s| -> Result<_, casclib::CascError> {
-      s.entry(&Self::get_storage_path(path))
-        .open()
-        .and_then(|e| e.read_all())
-        .map(|bytes| {
-          Some(File {
-            source: FileSource::Storage,
-            size: bytes.len() as u64,
-            data: Data::Bytes(Bytes::from(bytes)),
-          })
-        })
-        .or_else(|e| match e {
-          casclib::CascError::FileNotFound => Ok(None),
-          e => Err(e),
-        })
-    })??;
-    Ok(bytes)
+    self.resolve_storage_file(path)
   }
 
-  fn get_storage_path(path: &str) -> String {
-    format!("war3.w3mod:{}", path)
+  #[cfg(feature = "casc")]
+  fn resolve_storage_file(&self, path: &str) -> Result<Option<File>> {
+    Ok(self.casc.read(path)?.map(|bytes| File {
+      source: FileSource::Storage,
+      size: bytes.len() as u64,
+      modified: None,
+      data: Data::Bytes(bytes),
+    }))
+  }
+
+  #[cfg(not(feature = "casc"))]
+  fn resolve_storage_file(&self, _path: &str) -> Result<Option<File>> {
+    Ok(None)
   }
 
   fn find_overrides(&self, path: &str) -> Vec<PathBuf> {
@@ -140,27 +163,13 @@ impl W3Storage {
       })
       .collect()
   }
-
-  fn with_storage<F, R>(&self, f: F) -> Result<R>
-  where
-    F: FnOnce(&Storage) -> R,
-  {
-    let mut lock = self.handle.lock();
-    if let Some(storage) = lock.as_ref() {
-      Ok(f(storage))
-    } else {
-      let storage = casclib::open(&self.storage_path)?;
-      let r = f(&storage);
-      *lock = Some(storage);
-      Ok(r)
-    }
-  }
 }
 
 #[derive(Debug)]
 pub struct File {
   source: FileSource,
   size: u64,
+  modified: Option<SystemTime>,
   data: Data,
 }
 
@@ -173,6 +182,11 @@ impl File {
     self.size
   }
 
+  /// Modification time of override files, `None` for files in the game storage
+  pub fn modified(&self) -> Option<SystemTime> {
+    self.modified
+  }
+
   pub fn data(&self) -> &Data {
     &self.data
   }
@@ -185,7 +199,10 @@ impl File {
 #[derive(Debug, Copy, Clone)]
 pub enum FileSource {
   Override,
+  /// The CASC storage of the game
   Storage,
+  /// Downloaded by `W3Storage::resolve_remote`
+  Remote,
 }
 
 #[derive(Debug)]
@@ -232,6 +249,7 @@ fn test_storage() {
       .unwrap(),
     std::fs::read(crate_dir.join("src/lib.rs")).unwrap()
   );
+  #[cfg(feature = "casc")]
   assert!(s.resolve_file("scripts/common.j").unwrap().is_some());
 }
 
@@ -0,0 +1,263 @@
+//! Maps downloaded over HTTP, cached on disk by content digest.
+
+use crate::error::*;
+use hyper::body::HttpBody;
+use hyper::header::LOCATION;
+use hyper::{Body, Client, Request, Uri};
+use hyper_tls::HttpsConnector;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::AsyncWriteExt;
+
+const MAX_REDIRECTS: usize = 5;
+/// Larger responses are aborted, Reforged maps stay well below this
+pub const MAX_REMOTE_FILE_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Downloaded files named by the SHA-1 digest of their content.
+/// A cached file is used if its content still matches the digest.
+#[derive(Debug)]
+pub struct RemoteCache {
+  path: PathBuf,
+}
+
+impl RemoteCache {
+  pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+    Self { path: path.into() }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Returns the path of the cached file, downloads `url` on a cache miss.
+  /// The download is only moved into the cache if its digest is `expected_sha1`.
+  pub async fn resolve(&self, url: &str, expected_sha1: &[u8; 20]) -> Result<PathBuf> {
+    let path = self
+      .path
+      .join(format!("{}.{}", hex(expected_sha1), extension(url)));
+
+    match tokio::fs::read(&path).await {
+      Ok(bytes) if &sha1_digest(&bytes) == expected_sha1 => return Ok(path),
+      // corrupted or modified, downloaded again
+      Ok(_) => tokio::fs::remove_file(&path).await?,
+      Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => {}
+      Err(err) => return Err(err.into()),
+    }
+
+    tokio::fs::create_dir_all(&self.path).await?;
+    let tmp_path = self.path.join(format!(
+      "{}.{}.{}.part",
+      hex(expected_sha1),
+      std::process::id(),
+      next_download_id()
+    ));
+    let res = download(url, &tmp_path, expected_sha1).await;
+    if res.is_err() {
+      tokio::fs::remove_file(&tmp_path).await.ok();
+    }
+    res?;
+    tokio::fs::rename(&tmp_path, &path).await?;
+    Ok(path)
+  }
+}
+
+async fn download(url: &str, path: &Path, expected_sha1: &[u8; 20]) -> Result<()> {
+  let client = Client::builder().build::<_, Body>(HttpsConnector::new());
+  let mut uri = parse_uri(url)?;
+  let mut redirects = 0;
+  let mut res = loop {
+    let res = client
+      .request(
+        Request::get(uri.clone())
+          .body(Body::empty())
+          .map_err(|err| Error::InvalidUrl(err.to_string()))?,
+      )
+      .await?;
+    if !res.status().is_redirection() {
+      break res;
+    }
+    let location = res
+      .headers()
+      .get(LOCATION)
+      .and_then(|v| v.to_str().ok())
+      .ok_or_else(|| Error::HttpStatus(res.status().as_u16()))?;
+    redirects += 1;
+    if redirects > MAX_REDIRECTS {
+      return Err(Error::TooManyRedirects);
+    }
+    uri = resolve_location(&uri, location)?;
+  };
+  if !res.status().is_success() {
+    return Err(Error::HttpStatus(res.status().as_u16()));
+  }
+
+  let mut file = tokio::fs::File::create(path).await?;
+  let mut hasher = sha1::Sha1::new();
+  let mut size = 0;
+  while let Some(chunk) = res.body_mut().data().await {
+    let chunk = chunk?;
+    size += chunk.len() as u64;
+    if size > MAX_REMOTE_FILE_SIZE {
+      return Err(Error::RemoteFileTooLarge);
+    }
+    hasher.update(&chunk);
+    file.write_all(&chunk).await?;
+  }
+  file.flush().await?;
+
+  let actual = hasher.digest().bytes();
+  if &actual != expected_sha1 {
+    return Err(Error::ChecksumMismatch {
+      expected: hex(expected_sha1),
+      actual: hex(&actual),
+    });
+  }
+  Ok(())
+}
+
+// `Location` can be relative to the requested URL
+fn resolve_location(base: &Uri, location: &str) -> Result<Uri> {
+  let uri = parse_uri(location)?;
+  if uri.scheme().is_some() {
+    return Ok(uri);
+  }
+  let mut parts = uri.into_parts();
+  parts.scheme = base.scheme().cloned();
+  parts.authority = base.authority().cloned();
+  Uri::from_parts(parts).map_err(|err| Error::InvalidUrl(err.to_string()))
+}
+
+fn parse_uri(value: &str) -> Result<Uri> {
+  value
+    .parse()
+    .map_err(|err: hyper::http::uri::InvalidUri| Error::InvalidUrl(err.to_string()))
+}
+
+fn next_download_id() -> usize {
+  static NEXT: AtomicUsize = AtomicUsize::new(0);
+  NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+// file extension of the URL path, the cache doesn't need it but keeps maps recognizable
+fn extension(url: &str) -> &'static str {
+  let path = url.split(&['?', '#'][..]).next().unwrap_or(url);
+  if path.to_ascii_lowercase().ends_with(".w3m") {
+    "w3m"
+  } else {
+    "w3x"
+  }
+}
+
+fn sha1_digest(bytes: &[u8]) -> [u8; 20] {
+  sha1::Sha1::from(bytes).digest().bytes()
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_resolve_location() {
+  let base: Uri = "https://maps.example.com/maps/a.w3x?v=1".parse().unwrap();
+  assert_eq!(
+    resolve_location(&base, "/cdn/a.w3x").unwrap().to_string(),
+    "https://maps.example.com/cdn/a.w3x"
+  );
+  assert_eq!(
+    resolve_location(&base, "http://cdn.example.com/a.w3x")
+      .unwrap()
+      .to_string(),
+    "http://cdn.example.com/a.w3x"
+  );
+  assert_eq!(extension("https://maps.example.com/a.W3M?v=1"), "w3m");
+  assert_eq!(extension("https://maps.example.com/download/1"), "w3x");
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_remote_cache() {
+    let dir = std::env::temp_dir().join(format!("flo-w3storage-remote-{}", std::process::id()));
+    let cache = RemoteCache::new(&dir);
+    let content = b"HM3W map";
+    let sha1 = sha1_digest(content);
+    // nothing listens on port 9 (discard)
+    let url = "http://127.0.0.1:9/maps/a.w3x";
+
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    let path = dir.join(format!("{}.w3x", hex(&sha1)));
+    tokio::fs::write(&path, content).await.unwrap();
+    assert_eq!(cache.resolve(url, &sha1).await.unwrap(), path);
+
+    // a modified file is removed and downloaded again
+    tokio::fs::write(&path, b"modified").await.unwrap();
+    assert!(matches!(
+      cache.resolve(url, &sha1).await,
+      Err(Error::Http(_))
+    ));
+    assert!(!path.exists());
+
+    tokio::fs::remove_dir_all(&dir).await.ok();
+  }
+
+  #[tokio::test]
+  async fn test_remote_cache_download() {
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Response, Server};
+
+    async fn serve(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+      Ok(match req.uri().path() {
+        "/redirect" => Response::builder()
+          .status(302)
+          .header(LOCATION, "/maps/a.w3x")
+          .body(Body::empty())
+          .unwrap(),
+        "/maps/a.w3x" => Response::new(Body::from(&b"HM3W map"[..])),
+        _ => Response::builder().status(404).body(Body::empty()).unwrap(),
+      })
+    }
+
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service_fn(|_| async {
+      Ok::<_, hyper::Error>(service_fn(serve))
+    }));
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    let dir = std::env::temp_dir().join(format!(
+      "flo-w3storage-remote-download-{}",
+      std::process::id()
+    ));
+    let cache = RemoteCache::new(&dir);
+    let sha1 = sha1_digest(b"HM3W map");
+
+    let path = cache
+      .resolve(&format!("http://{}/redirect", addr), &sha1)
+      .await
+      .unwrap();
+    assert_eq!(tokio::fs::read(&path).await.unwrap(), b"HM3W map");
+
+    assert!(matches!(
+      cache
+        .resolve(&format!("http://{}/maps/a.w3x", addr), &[0; 20])
+        .await,
+      Err(Error::ChecksumMismatch { .. })
+    ));
+    assert!(matches!(
+      cache
+        .resolve(&format!("http://{}/missing.w3x", addr), &[1; 20])
+        .await,
+      Err(Error::HttpStatus(404))
+    ));
+    // failed downloads leave nothing behind
+    let mut entries = tokio::fs::read_dir(&dir).await.unwrap();
+    let mut names = vec![];
+    while let Some(entry) = entries.next_entry().await.unwrap() {
+      names.push(entry.file_name());
+    }
+    assert_eq!(names, vec![path.file_name().unwrap().to_owned()]);
+
+    tokio::fs::remove_dir_all(&dir).await.ok();
+  }
+}
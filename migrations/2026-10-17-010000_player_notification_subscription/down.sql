@@ -0,0 +1 @@
+drop table player_notification_subscription;
\ No newline at end of file
@@ -0,0 +1,10 @@
+create table player_notification_subscription (
+    id serial not null primary key,
+    player_id integer not null references player(id),
+    channel integer not null,
+    target text not null,
+    created_at timestamp with time zone default now() not null,
+    unique(player_id, channel, target)
+);
+
+create index player_notification_subscription_player_id on player_notification_subscription(player_id);
@@ -0,0 +1,2 @@
+alter table game
+    drop column start_requested_at;
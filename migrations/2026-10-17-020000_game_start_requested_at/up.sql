@@ -0,0 +1,2 @@
+alter table game
+    add column start_requested_at timestamptz;
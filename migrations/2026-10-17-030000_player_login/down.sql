@@ -0,0 +1 @@
+drop table player_login;
\ No newline at end of file
@@ -0,0 +1,10 @@
+create table player_login (
+    id serial not null primary key,
+    player_id integer not null references player(id),
+    ip_addr text not null,
+    client_hint text,
+    created_at timestamp with time zone default now() not null
+);
+
+create index player_login_player_id on player_login(player_id);
+create index player_login_created_at on player_login(created_at);
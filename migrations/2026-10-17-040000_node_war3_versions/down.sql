@@ -0,0 +1,2 @@
+alter table node
+    drop column war3_versions;
@@ -0,0 +1,2 @@
+alter table node
+    add column war3_versions text[];
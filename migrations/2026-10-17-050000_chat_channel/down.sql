@@ -0,0 +1,3 @@
+drop table chat_message;
+drop table chat_channel_member;
+drop table chat_channel;
\ No newline at end of file
@@ -0,0 +1,31 @@
+create table chat_channel (
+    id serial not null primary key,
+    kind integer not null,
+    key text not null,
+    name text not null,
+    restricted boolean not null default false,
+    created_at timestamp with time zone default now() not null,
+    unique(kind, key)
+);
+
+create table chat_channel_member (
+    id serial not null primary key,
+    channel_id integer not null references chat_channel(id) on delete cascade,
+    player_id integer not null references player(id),
+    created_at timestamp with time zone default now() not null,
+    unique(channel_id, player_id)
+);
+
+create index chat_channel_member_player_id on chat_channel_member(player_id);
+
+create table chat_message (
+    id bigserial not null primary key,
+    channel_id integer not null references chat_channel(id) on delete cascade,
+    player_id integer not null references player(id),
+    content text not null,
+    created_at timestamp with time zone default now() not null
+);
+
+create index chat_message_channel_id_id on chat_message(channel_id, id);
+
+insert into chat_channel (kind, key, name) values (0, '', 'Global');
@@ -0,0 +1 @@
+drop table player_offense;
\ No newline at end of file
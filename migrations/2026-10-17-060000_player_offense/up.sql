@@ -0,0 +1,10 @@
+create table player_offense (
+    id serial not null primary key,
+    player_id integer not null references player(id),
+    game_id integer references game(id) on delete set null,
+    kind integer not null,
+    created_at timestamp with time zone default now() not null,
+    unique(player_id, game_id)
+);
+
+create index player_offense_player_id_created_at on player_offense(player_id, created_at);
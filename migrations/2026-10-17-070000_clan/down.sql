@@ -0,0 +1,4 @@
+drop table clan_match;
+drop table clan_invite;
+drop table clan_member;
+drop table clan;
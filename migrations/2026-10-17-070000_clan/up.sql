@@ -0,0 +1,44 @@
+create table clan (
+    id serial not null primary key,
+    tag text not null,
+    name text not null,
+    created_by integer not null references player(id),
+    created_at timestamp with time zone default now() not null
+);
+
+create unique index clan_tag_lower on clan(lower(tag));
+
+create table clan_member (
+    id serial not null primary key,
+    clan_id integer not null references clan(id) on delete cascade,
+    player_id integer not null references player(id),
+    role integer not null,
+    created_at timestamp with time zone default now() not null,
+    unique(player_id)
+);
+
+create index clan_member_clan_id on clan_member(clan_id);
+
+create table clan_invite (
+    id serial not null primary key,
+    clan_id integer not null references clan(id) on delete cascade,
+    player_id integer not null references player(id),
+    invited_by integer not null references player(id),
+    created_at timestamp with time zone default now() not null,
+    unique(clan_id, player_id)
+);
+
+create index clan_invite_player_id on clan_invite(player_id);
+
+create table clan_match (
+    id serial not null primary key,
+    game_id integer not null references game(id) on delete cascade,
+    clan_id integer not null references clan(id) on delete cascade,
+    opponent_clan_id integer not null references clan(id) on delete cascade,
+    winner_clan_id integer references clan(id) on delete set null,
+    created_at timestamp with time zone default now() not null,
+    unique(game_id)
+);
+
+create index clan_match_clan_id on clan_match(clan_id);
+create index clan_match_opponent_clan_id on clan_match(opponent_clan_id);
@@ -0,0 +1,2 @@
+drop table game_result;
+drop table api_client_result_policy;
@@ -0,0 +1,19 @@
+create table api_client_result_policy (
+    api_client_id integer not null primary key references api_client(id) on delete cascade,
+    source integer not null default 0,
+    on_conflict integer not null default 2,
+    require_all_players boolean not null default false,
+    updated_at timestamp with time zone default now() not null
+);
+
+create table game_result (
+    id serial not null primary key,
+    game_id integer not null references game(id) on delete cascade,
+    status integer not null,
+    source integer not null,
+    conflict boolean not null,
+    map_error text,
+    players jsonb not null,
+    created_at timestamp with time zone default now() not null,
+    unique(game_id)
+);
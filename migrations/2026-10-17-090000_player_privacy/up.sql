@@ -0,0 +1,6 @@
+create table player_privacy (
+    player_id integer not null primary key references player(id) on delete cascade,
+    hide_profile boolean not null default false,
+    anonymize boolean not null default false,
+    updated_at timestamp with time zone default now() not null
+);
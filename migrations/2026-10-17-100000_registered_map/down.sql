@@ -0,0 +1 @@
+drop table registered_map;
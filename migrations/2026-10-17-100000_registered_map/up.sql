@@ -0,0 +1,8 @@
+create table registered_map (
+    id serial primary key,
+    sha1 bytea not null unique,
+    name text not null,
+    map jsonb not null,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
@@ -0,0 +1 @@
+drop table feature_flag;
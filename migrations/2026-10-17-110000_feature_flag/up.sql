@@ -0,0 +1,7 @@
+create table feature_flag (
+    name text not null primary key,
+    enabled boolean not null default true,
+    rollout_percent integer not null default 0 check (rollout_percent between 0 and 100),
+    player_ids integer[] not null default '{}',
+    updated_at timestamp with time zone default now() not null
+);
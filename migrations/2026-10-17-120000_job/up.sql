@@ -0,0 +1,15 @@
+create table job (
+    id serial primary key,
+    kind text not null,
+    payload jsonb not null,
+    attempts integer not null default 0,
+    max_attempts integer not null default 8,
+    run_at timestamp with time zone default now() not null,
+    locked_until timestamp with time zone default now() not null,
+    last_error text,
+    failed_at timestamp with time zone,
+    created_at timestamp with time zone default now() not null,
+    updated_at timestamp with time zone default now() not null
+);
+
+create index job_run_at on job (run_at) where failed_at is null;
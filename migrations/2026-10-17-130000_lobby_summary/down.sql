@@ -0,0 +1 @@
+drop table lobby_summary;
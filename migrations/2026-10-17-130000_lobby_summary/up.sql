@@ -0,0 +1,10 @@
+create table lobby_summary (
+    id serial not null primary key,
+    game_id integer not null references game(id) on delete cascade,
+    reason integer not null,
+    message text not null,
+    players jsonb not null,
+    created_at timestamp with time zone default now() not null
+);
+
+create index lobby_summary_game_id on lobby_summary (game_id);
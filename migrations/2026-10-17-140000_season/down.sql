@@ -0,0 +1,4 @@
+alter table game_result drop column season_id;
+alter table game drop column season_id;
+drop table season_standing;
+drop table season;
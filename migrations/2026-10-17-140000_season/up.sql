@@ -0,0 +1,30 @@
+create table season (
+    id serial not null primary key,
+    api_client_id integer not null references api_client(id) on delete cascade,
+    name text not null,
+    reset_policy integer not null,
+    starts_at timestamp with time zone not null,
+    ends_at timestamp with time zone,
+    closed_at timestamp with time zone,
+    created_at timestamp with time zone default now() not null
+);
+
+create unique index season_api_client_id_open on season (api_client_id) where closed_at is null;
+
+create table season_standing (
+    id serial not null primary key,
+    season_id integer not null references season(id) on delete cascade,
+    player_id integer not null references player(id),
+    rating double precision not null,
+    wins integer not null default 0,
+    losses integer not null default 0,
+    draws integer not null default 0,
+    rank integer,
+    updated_at timestamp with time zone default now() not null,
+    unique(season_id, player_id)
+);
+
+create index season_standing_season_id_rating on season_standing (season_id, rating desc);
+
+alter table game add column season_id integer references season(id) on delete set null;
+alter table game_result add column season_id integer references season(id) on delete set null;